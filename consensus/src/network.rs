@@ -194,6 +194,14 @@ pub trait QuorumStoreSender: Send + Clone {
     async fn send_proof_of_store_msg_to_self(&mut self, proof_of_stores: Vec<ProofOfStore>);
 }
 
+/// Whether to send each vote twice, the crude misbehavior of the faulty validators of forge tests.
+/// The second vote is identical, so it is no equivocation, and the other validators must not count
+/// it again.
+fn resend_votes() -> bool {
+    fail_point!("consensus::send::vote::resend", |_| true);
+    false
+}
+
 /// Implements the actual networking support for all consensus messaging.
 #[derive(Clone)]
 pub struct NetworkSender {
@@ -406,6 +414,9 @@ impl NetworkSender {
     pub async fn broadcast_vote(&self, vote_msg: VoteMsg) {
         fail_point!("consensus::send::vote", |_| ());
         let msg = ConsensusMsg::VoteMsg(Box::new(vote_msg));
+        if resend_votes() {
+            self.broadcast(msg.clone()).await;
+        }
         self.broadcast(msg).await
     }
 
@@ -436,6 +447,9 @@ impl NetworkSender {
     pub async fn send_vote(&self, vote_msg: VoteMsg, recipients: Vec<Author>) {
         fail_point!("consensus::send::vote", |_| ());
         let msg = ConsensusMsg::VoteMsg(Box::new(vote_msg));
+        if resend_votes() {
+            self.send(msg.clone(), recipients.clone()).await;
+        }
         self.send(msg, recipients).await
     }
 
@@ -7,7 +7,9 @@ use aptos_types::{
     account_address::{AccountAddress, AccountAddressWithChecks},
     chain_id::ChainId,
     network_address::{DnsName, NetworkAddress, Protocol},
-    on_chain_config::{OnChainConsensusConfig, OnChainExecutionConfig, OnChainJWKConsensusConfig},
+    on_chain_config::{
+        Features, OnChainConsensusConfig, OnChainExecutionConfig, OnChainJWKConsensusConfig,
+    },
     transaction::authenticator::AuthenticationKey,
 };
 use aptos_vm_genesis::{AccountBalance, EmployeePool, Validator, ValidatorWithCommissionRate};
@@ -76,6 +78,9 @@ pub struct Layout {
 
     /// An optional JWK consensus config to use, instead of `default_for_genesis()`.
     pub jwk_consensus_config_override: Option<OnChainJWKConsensusConfig>,
+
+    /// An optional set of feature flags to use, instead of the default ones.
+    pub initial_features_override: Option<Features>,
 }
 
 impl Layout {
@@ -116,6 +121,7 @@ impl Default for Layout {
             on_chain_consensus_config: OnChainConsensusConfig::default(),
             on_chain_execution_config: OnChainExecutionConfig::default_for_genesis(),
             jwk_consensus_config_override: None,
+            initial_features_override: None,
         }
     }
 }
@@ -2,7 +2,8 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::{
-    get_version_path_with_base, Client, DEFAULT_VERSION_PATH_BASE, X_APTOS_SDK_HEADER_VALUE,
+    get_version_path_with_base, Client, RetryPolicy, DEFAULT_VERSION_PATH_BASE,
+    X_APTOS_SDK_HEADER_VALUE,
 };
 use anyhow::Result;
 use aptos_api_types::X_APTOS_CLIENT;
@@ -41,6 +42,7 @@ pub struct ClientBuilder {
     base_url: Url,
     timeout: Duration,
     headers: HeaderMap,
+    retry_policy: Option<RetryPolicy>,
 }
 
 impl ClientBuilder {
@@ -57,6 +59,7 @@ impl ClientBuilder {
             version_path_base: DEFAULT_VERSION_PATH_BASE.to_string(),
             timeout: Duration::from_secs(10), // Default to 10 seconds
             headers,
+            retry_policy: None,
         };
 
         if let Ok(key) = env::var("X_API_KEY") {
@@ -75,6 +78,12 @@ impl ClientBuilder {
         self
     }
 
+    /// Bound how long connecting may take, on top of the timeout of the whole request
+    pub fn connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.reqwest_builder = self.reqwest_builder.connect_timeout(connect_timeout);
+        self
+    }
+
     pub fn header(mut self, header_key: &str, header_val: &str) -> Result<Self> {
         self.headers.insert(
             HeaderName::from_str(header_key)?,
@@ -91,11 +100,22 @@ impl ClientBuilder {
         Ok(self)
     }
 
+    pub fn add_root_certificate(mut self, cert: reqwest::Certificate) -> Self {
+        self.reqwest_builder = self.reqwest_builder.add_root_certificate(cert);
+        self
+    }
+
     pub fn version_path_base(mut self, version_path_base: String) -> Self {
         self.version_path_base = version_path_base;
         self
     }
 
+    /// Retry safe requests that fail transiently as the policy allows. Off by default.
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(retry_policy);
+        self
+    }
+
     pub fn build(self) -> Client {
         let version_path_base = get_version_path_with_base(self.base_url.clone());
 
@@ -109,6 +129,7 @@ impl ClientBuilder {
                 .unwrap(),
             base_url: self.base_url,
             version_path_base,
+            retry_policy: self.retry_policy,
         }
     }
 }
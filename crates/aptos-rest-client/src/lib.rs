@@ -10,6 +10,8 @@ pub mod faucet;
 pub use faucet::FaucetClient;
 pub mod response;
 pub use response::Response;
+pub mod retry;
+pub use retry::RetryPolicy;
 pub mod client_builder;
 pub mod state;
 pub mod types;
@@ -18,6 +20,7 @@ pub use crate::client_builder::{AptosBaseUrl, ClientBuilder};
 use crate::{
     aptos::{AptosVersion, Balance},
     error::RestError,
+    retry::SendWithRetries,
 };
 use anyhow::{anyhow, Result};
 pub use aptos_api_types::{
@@ -69,6 +72,7 @@ pub struct Client {
     inner: ReqwestClient,
     base_url: Url,
     version_path_base: String,
+    retry_policy: Option<RetryPolicy>,
 }
 
 impl Client {
@@ -301,7 +305,7 @@ impl Client {
             .post(url)
             .header(CONTENT_TYPE, JSON)
             .body(request)
-            .send()
+            .send_with(self.retry_policy.as_ref())
             .await?;
 
         self.json(response).await
@@ -324,7 +328,7 @@ impl Client {
             .header(CONTENT_TYPE, BCS_VIEW_FUNCTION)
             .header(ACCEPT, BCS)
             .body(txn_payload)
-            .send()
+            .send_with(self.retry_policy.as_ref())
             .await?;
 
         let response = self.check_and_parse_bcs_response(response).await?;
@@ -348,7 +352,7 @@ impl Client {
             .header(CONTENT_TYPE, BCS_VIEW_FUNCTION)
             .header(ACCEPT, JSON)
             .body(txn_payload)
-            .send()
+            .send_with(self.retry_policy.as_ref())
             .await?;
 
         self.json(response).await
@@ -366,7 +370,7 @@ impl Client {
             .post(url)
             .header(CONTENT_TYPE, BCS_SIGNED_TRANSACTION)
             .body(txn_payload)
-            .send()
+            .send_with(self.retry_policy.as_ref())
             .await?;
 
         self.json(response).await
@@ -390,7 +394,7 @@ impl Client {
             .post(url)
             .header(CONTENT_TYPE, BCS_SIGNED_TRANSACTION)
             .body(txn_payload)
-            .send()
+            .send_with(self.retry_policy.as_ref())
             .await?;
 
         self.json(response).await
@@ -409,7 +413,7 @@ impl Client {
             .header(CONTENT_TYPE, BCS_SIGNED_TRANSACTION)
             .header(ACCEPT, BCS)
             .body(txn_payload)
-            .send()
+            .send_with(self.retry_policy.as_ref())
             .await?;
 
         let response = self.check_and_parse_bcs_response(response).await?;
@@ -434,7 +438,7 @@ impl Client {
             .header(CONTENT_TYPE, BCS_SIGNED_TRANSACTION)
             .header(ACCEPT, BCS)
             .body(txn_payload)
-            .send()
+            .send_with(self.retry_policy.as_ref())
             .await?;
 
         let response = self.check_and_parse_bcs_response(response).await?;
@@ -453,7 +457,7 @@ impl Client {
             .post(url)
             .header(CONTENT_TYPE, BCS_SIGNED_TRANSACTION)
             .body(txn_payload)
-            .send()
+            .send_with(self.retry_policy.as_ref())
             .await?;
 
         self.json::<PendingTransaction>(response).await
@@ -468,7 +472,7 @@ impl Client {
             .post(url)
             .header(CONTENT_TYPE, BCS_SIGNED_TRANSACTION)
             .body(txn_payload)
-            .send()
+            .send_with(self.retry_policy.as_ref())
             .await?;
 
         self.check_response(response).await?;
@@ -485,7 +489,7 @@ impl Client {
             .header(CONTENT_TYPE, BCS_SIGNED_TRANSACTION)
             .header(ACCEPT, BCS)
             .body(txn_payload)
-            .send()
+            .send_with(self.retry_policy.as_ref())
             .await?;
 
         let response = self.check_and_parse_bcs_response(response).await?;
@@ -504,7 +508,7 @@ impl Client {
             .post(url)
             .header(CONTENT_TYPE, BCS_SIGNED_TRANSACTION)
             .body(txn_payload)
-            .send()
+            .send_with(self.retry_policy.as_ref())
             .await?;
         self.json(response).await
     }
@@ -522,7 +526,7 @@ impl Client {
             .header(CONTENT_TYPE, BCS_SIGNED_TRANSACTION)
             .header(ACCEPT, BCS)
             .body(txn_payload)
-            .send()
+            .send_with(self.retry_policy.as_ref())
             .await?;
 
         let response = self.check_and_parse_bcs_response(response).await?;
@@ -862,7 +866,7 @@ impl Client {
             request = request.query(&[("limit", limit)])
         }
 
-        let response = request.send().await?;
+        let response = request.send_with(self.retry_policy.as_ref()).await?;
 
         self.json(response).await
     }
@@ -899,7 +903,12 @@ impl Client {
         hash: HashValue,
     ) -> AptosResult<reqwest::Response> {
         let url = self.build_path(&format!("transactions/by_hash/{}", hash.to_hex_literal()))?;
-        let response = self.inner.get(url).header(ACCEPT, BCS).send().await?;
+        let response = self
+            .inner
+            .get(url)
+            .header(ACCEPT, BCS)
+            .send_with(self.retry_policy.as_ref())
+            .await?;
         Ok(response)
     }
 
@@ -908,7 +917,11 @@ impl Client {
         hash: HashValue,
     ) -> AptosResult<reqwest::Response> {
         let url = self.build_path(&format!("transactions/by_hash/{}", hash.to_hex_literal()))?;
-        Ok(self.inner.get(url).send().await?)
+        Ok(self
+            .inner
+            .get(url)
+            .send_with(self.retry_policy.as_ref())
+            .await?)
     }
 
     pub async fn get_transaction_by_version(
@@ -933,7 +946,11 @@ impl Client {
         version: u64,
     ) -> AptosResult<reqwest::Response> {
         let url = self.build_path(&format!("transactions/by_version/{}", version))?;
-        Ok(self.inner.get(url).send().await?)
+        Ok(self
+            .inner
+            .get(url)
+            .send_with(self.retry_policy.as_ref())
+            .await?)
     }
 
     pub async fn get_account_transactions(
@@ -953,7 +970,7 @@ impl Client {
             request = request.query(&[("limit", limit)])
         }
 
-        let response = request.send().await?;
+        let response = request.send_with(self.retry_policy.as_ref()).await?;
 
         self.json(response).await
     }
@@ -1054,7 +1071,7 @@ impl Client {
         let response = self
             .inner
             .get(url)
-            .send()
+            .send_with(self.retry_policy.as_ref())
             .await
             .map_err(anyhow::Error::from)?;
         self.json(response).await
@@ -1136,7 +1153,11 @@ impl Client {
             version
         ))?;
 
-        let response = self.inner.get(url).send().await?;
+        let response = self
+            .inner
+            .get(url)
+            .send_with(self.retry_policy.as_ref())
+            .await?;
         self.json(response).await
     }
 
@@ -1228,7 +1249,7 @@ impl Client {
             request = request.query(&[("limit", limit)])
         }
 
-        let response = request.send().await?;
+        let response = request.send_with(self.retry_policy.as_ref()).await?;
         self.json(response).await
     }
 
@@ -1316,7 +1337,12 @@ impl Client {
             "key": json!(key),
         });
 
-        let response = self.inner.post(url).json(&data).send().await?;
+        let response = self
+            .inner
+            .post(url)
+            .json(&data)
+            .send_with(self.retry_policy.as_ref())
+            .await?;
         self.json(response).await
     }
 
@@ -1338,7 +1364,12 @@ impl Client {
             "key": json!(key),
         });
 
-        let response = self.inner.post(url).json(&data).send().await?;
+        let response = self
+            .inner
+            .post(url)
+            .json(&data)
+            .send_with(self.retry_policy.as_ref())
+            .await?;
         self.json(response).await
     }
 
@@ -1419,7 +1450,11 @@ impl Client {
 
     pub async fn get_account(&self, address: AccountAddress) -> AptosResult<Response<Account>> {
         let url = self.build_path(&format!("accounts/{}", address.to_hex()))?;
-        let response = self.inner.get(url).send().await?;
+        let response = self
+            .inner
+            .get(url)
+            .send_with(self.retry_policy.as_ref())
+            .await?;
         self.json(response).await
     }
 
@@ -1434,7 +1469,11 @@ impl Client {
 
     pub async fn estimate_gas_price(&self) -> AptosResult<Response<GasEstimation>> {
         let url = self.build_path("estimate_gas_price")?;
-        let response = self.inner.get(url).send().await?;
+        let response = self
+            .inner
+            .get(url)
+            .send_with(self.retry_policy.as_ref())
+            .await?;
         self.json(response).await
     }
 
@@ -1445,7 +1484,11 @@ impl Client {
             .append_pair("name", &name)
             .append_pair("actions", &actions)
             .finish();
-        let response = self.inner.get(url.clone()).send().await?;
+        let response = self
+            .inner
+            .get(url.clone())
+            .send_with(self.retry_policy.as_ref())
+            .await?;
 
         if !response.status().is_success() {
             Err(parse_error(response).await)
@@ -1485,7 +1528,7 @@ impl Client {
             .inner
             .get(url)
             .query(&[("duration_secs", seconds)])
-            .send()
+            .send_with(self.retry_policy.as_ref())
             .await?;
 
         if !response.status().is_success() {
@@ -1496,11 +1539,22 @@ impl Client {
     }
 
     async fn get<T: DeserializeOwned>(&self, url: Url) -> AptosResult<Response<T>> {
-        self.json(self.inner.get(url).send().await?).await
+        self.json(
+            self.inner
+                .get(url)
+                .send_with(self.retry_policy.as_ref())
+                .await?,
+        )
+        .await
     }
 
     async fn get_bcs(&self, url: Url) -> AptosResult<Response<bytes::Bytes>> {
-        let response = self.inner.get(url).header(ACCEPT, BCS).send().await?;
+        let response = self
+            .inner
+            .get(url)
+            .header(ACCEPT, BCS)
+            .send_with(self.retry_policy.as_ref())
+            .await?;
         self.check_and_parse_bcs_response(response).await
     }
 
@@ -1514,7 +1568,7 @@ impl Client {
             .post(url)
             .header(ACCEPT, BCS)
             .json(&data)
-            .send()
+            .send_with(self.retry_policy.as_ref())
             .await?;
         self.check_and_parse_bcs_response(response).await
     }
@@ -1534,7 +1588,7 @@ impl Client {
             request = request.query(&[("limit", limit)])
         }
 
-        let response = request.send().await?;
+        let response = request.send_with(self.retry_policy.as_ref()).await?;
         self.check_and_parse_bcs_response(response).await
     }
 
@@ -1639,7 +1693,11 @@ impl Client {
                 ledger_version,
                 &cursor,
             )?;
-            let raw_response = self.inner.get(url).send().await?;
+            let raw_response = self
+                .inner
+                .get(url)
+                .send_with(self.retry_policy.as_ref())
+                .await?;
             let response: Response<Vec<T>> = self.json(raw_response).await?;
             cursor.clone_from(&response.state().cursor);
             if cursor.is_none() {
@@ -1727,6 +1785,7 @@ impl From<(ReqwestClient, Url)> for Client {
             inner,
             base_url,
             version_path_base: DEFAULT_VERSION_PATH_BASE.to_string(),
+            retry_policy: None,
         }
     }
 }
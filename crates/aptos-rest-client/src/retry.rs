@@ -0,0 +1,213 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use aptos_logger::info;
+use reqwest::{Method, RequestBuilder, Response as ReqwestResponse, StatusCode};
+use std::{error::Error as StdError, future::Future, io, time::Duration};
+
+/// When and how often a [`crate::Client`] retries a request that failed transiently, e.g. because
+/// a proxy in front of the node was briefly unable to reach it.
+///
+/// Only GET and HEAD requests are ever retried. Everything else, most importantly transaction
+/// submissions, is sent exactly once no matter the policy: a submission that timed out may well
+/// have reached the node, so whether to resubmit is up to the caller.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    // retries after the first attempt, so a request is sent at most max_retries + 1 times
+    pub max_retries: u32,
+    // doubled after every retry, up to max_backoff
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    pub retryable_status_codes: Vec<StatusCode>,
+    // e.g. connection refused or reset, as when a port-forward restarts
+    pub retry_on_connection_errors: bool,
+    pub retry_on_timeouts: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(5),
+            retryable_status_codes: vec![
+                StatusCode::BAD_GATEWAY,
+                StatusCode::SERVICE_UNAVAILABLE,
+                StatusCode::GATEWAY_TIMEOUT,
+            ],
+            retry_on_connection_errors: true,
+            retry_on_timeouts: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Whether requests with the given method may be retried at all. Only safe requests are.
+    pub fn is_retryable_method(method: &Method) -> bool {
+        matches!(*method, Method::GET | Method::HEAD)
+    }
+
+    /// How long to wait before the given retry, counting from 0
+    pub fn backoff(&self, retry: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(self.max_backoff)
+    }
+
+    fn is_retryable(&self, result: &reqwest::Result<ReqwestResponse>) -> bool {
+        match result {
+            Ok(response) => self.retryable_status_codes.contains(&response.status()),
+            Err(error) if error.is_timeout() => self.retry_on_timeouts,
+            Err(error) => self.retry_on_connection_errors && is_connection_error(error),
+        }
+    }
+}
+
+fn is_connection_error(error: &reqwest::Error) -> bool {
+    if error.is_connect() {
+        return true;
+    }
+    // a connection dropped mid-request only shows as an io error somewhere in the sources
+    let mut source = error.source();
+    while let Some(error) = source {
+        if let Some(io_error) = error.downcast_ref::<io::Error>() {
+            return matches!(
+                io_error.kind(),
+                io::ErrorKind::ConnectionReset
+                    | io::ErrorKind::ConnectionAborted
+                    | io::ErrorKind::BrokenPipe
+                    | io::ErrorKind::UnexpectedEof
+            );
+        }
+        source = error.source();
+    }
+    false
+}
+
+pub(crate) trait SendWithRetries {
+    /// Send the request, retrying it as the policy allows. Without a policy this is `send()`.
+    fn send_with<'a>(
+        self,
+        policy: Option<&'a RetryPolicy>,
+    ) -> impl Future<Output = reqwest::Result<ReqwestResponse>> + Send + 'a;
+}
+
+impl SendWithRetries for RequestBuilder {
+    fn send_with<'a>(
+        self,
+        policy: Option<&'a RetryPolicy>,
+    ) -> impl Future<Output = reqwest::Result<ReqwestResponse>> + Send + 'a {
+        async move {
+            let (client, request) = self.build_split();
+            let mut request = request?;
+            let policy = match policy {
+                Some(policy) if RetryPolicy::is_retryable_method(request.method()) => policy,
+                _ => return client.execute(request).await,
+            };
+            let mut retry = 0;
+            loop {
+                // requests with streaming bodies can't be cloned, and so are never retried
+                let next_request = if retry < policy.max_retries {
+                    request.try_clone()
+                } else {
+                    None
+                };
+                let url = request.url().clone();
+                let result = client.execute(request).await;
+                match next_request {
+                    Some(next_request) if policy.is_retryable(&result) => {
+                        let backoff = policy.backoff(retry);
+                        info!(
+                            "Retrying GET {} in {:?}, attempt {} failed: {:?}",
+                            url,
+                            backoff,
+                            retry + 1,
+                            result.as_ref().map(|response| response.status())
+                        );
+                        tokio::time::sleep(backoff).await;
+                        request = next_request;
+                        retry += 1;
+                    },
+                    _ => return result,
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::Client as ReqwestClient;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    /// Serves 503 to every request, and counts them
+    async fn unavailable_server() -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/v1", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut buf = [0; 4096];
+                let _ = stream.read(&mut buf).await;
+                counter.fetch_add(1, Ordering::SeqCst);
+                let _ = stream
+                    .write_all(
+                        b"HTTP/1.1 503 Service Unavailable\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+                    )
+                    .await;
+            }
+        });
+        (url, requests)
+    }
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy {
+            max_retries: 2,
+            initial_backoff: Duration::from_millis(1),
+            ..RetryPolicy::default()
+        }
+    }
+
+    #[test]
+    fn test_backoff() {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.backoff(0), Duration::from_millis(500));
+        assert_eq!(policy.backoff(2), Duration::from_secs(2));
+        assert_eq!(policy.backoff(10), Duration::from_secs(5));
+        assert_eq!(policy.backoff(u32::MAX), Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn test_only_safe_requests_are_retried() {
+        let (url, requests) = unavailable_server().await;
+        let client = ReqwestClient::new();
+        let policy = policy();
+
+        let response = client.get(&url).send_with(Some(&policy)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(requests.swap(0, Ordering::SeqCst), 3);
+
+        // e.g. a transaction submission
+        let response = client
+            .post(&url)
+            .body("signed transaction")
+            .send_with(Some(&policy))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(requests.swap(0, Ordering::SeqCst), 1);
+
+        client.get(&url).send_with(None).await.unwrap();
+        assert_eq!(requests.swap(0, Ordering::SeqCst), 1);
+    }
+}
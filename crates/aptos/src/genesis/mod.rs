@@ -300,7 +300,7 @@ pub fn fetch_genesis_info(git_options: GitOptions) -> CliTypedResult<GenesisInfo
             consensus_config: layout.on_chain_consensus_config,
             execution_config: layout.on_chain_execution_config,
             gas_schedule: default_gas_schedule(),
-            initial_features_override: None,
+            initial_features_override: layout.initial_features_override.clone(),
             randomness_config_override: None,
             jwk_consensus_config_override: layout.jwk_consensus_config_override.clone(),
         },
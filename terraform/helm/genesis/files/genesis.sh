@@ -192,7 +192,10 @@ create_secrets() {
       files_to_include+=("--from-file=genesis.blob=${WORKSPACE}/genesis.blob")
     fi
 
-    kubectl create secret generic "${username}-genesis-e${ERA}" "${files_to_include[@]}"
+    # labeled with the era, by which forge deletes the secrets of the previous eras
+    kubectl create secret generic "${username}-genesis-e${ERA}" "${files_to_include[@]}" --dry-run=client -o yaml |
+      kubectl label --local -f - "forge-era=${ERA:0:63}" -o yaml |
+      kubectl create -f -
   done
 }
 
@@ -20,7 +20,7 @@ use aptos_forge::{
     ForgeConfig, Options, *,
 };
 use aptos_logger::{info, Level};
-use aptos_rest_client::Client as RestClient;
+use aptos_rest_client::{Client as RestClient, RetryPolicy};
 use aptos_sdk::{
     move_types::account_address::AccountAddress,
     transaction_builder::aptos_stdlib,
@@ -29,34 +29,46 @@ use aptos_sdk::{
     },
 };
 use aptos_testcases::{
+    clock_skew_test::ClockSkewTest,
     compatibility_test::SimpleValidatorUpgrade,
+    consensus_latency_test::ConsensusLatencyTest,
     consensus_reliability_tests::ChangingWorkingQuorumTest,
+    dns_failure_test::DnsFailureTest,
+    faulty_validator_test::FaultyValidatorTest,
     forge_setup_test::ForgeSetupTest,
     framework_upgrade::FrameworkUpgrade,
     fullnode_reboot_stress_test::FullNodeRebootStressTest,
     generate_traffic,
+    haproxy_consistency_test::HaproxyConsistencyTest,
     load_vs_perf_benchmark::{
         ContinuousTraffic, LoadVsPerfBenchmark, TransactionWorkload, Workloads,
     },
-    modifiers::{CpuChaosTest, ExecutionDelayConfig, ExecutionDelayTest},
+    minority_partition_test::MinorityPartitionTest,
+    modifiers::{CpuChaosTest, ExecutionDelayConfig, ExecutionDelayTest, ValidatorCpuStressTest},
     multi_region_network_test::{
         MultiRegionNetworkEmulationConfig, MultiRegionNetworkEmulationTest,
     },
     network_bandwidth_test::NetworkBandwidthTest,
-    network_loss_test::NetworkLossTest,
+    network_loss_test::{NetworkLossTest, ValidatorLoss},
     network_partition_test::NetworkPartitionTest,
+    oom_recovery_test::OomRecoveryTest,
     performance_test::PerformanceBenchmark,
     public_fullnode_performance::PFNPerformance,
     quorum_store_onchain_enable_test::QuorumStoreOnChainEnableTest,
     reconfiguration_test::ReconfigurationTest,
+    simulated_geo_test::SimulatedGeo,
+    stake_weighted_proposals_test::StakeWeightedProposalsTest,
     state_sync_performance::{
-        StateSyncFullnodeFastSyncPerformance, StateSyncFullnodePerformance,
+        StateSyncFullnodeFanOut, StateSyncFullnodeFastSyncPerformance,
+        StateSyncFullnodePerformance, StateSyncValidatorConstrainedBandwidth,
         StateSyncValidatorPerformance,
     },
+    storage_resilience_test::StorageResilienceTest,
     three_region_simulation_test::ThreeRegionSameCloudSimulationTest,
     twin_validator_test::TwinValidatorTest,
     two_traffics_test::TwoTrafficsTest,
     validator_join_leave_test::ValidatorJoinLeaveTest,
+    validator_join_test::ValidatorJoinTest,
     validator_reboot_stress_test::ValidatorRebootStressTest,
     CompositeNetworkTest,
 };
@@ -110,12 +122,133 @@ struct Args {
     suite: String,
     #[clap(long, num_args = 0..)]
     changelog: Option<Vec<String>>,
+    #[clap(flatten)]
+    k8s_backend: K8sBackendArgs,
+    #[clap(flatten)]
+    rest_client: RestClientArgs,
 
     // subcommand groups
     #[clap(subcommand)]
     cli_cmd: CliCommand,
 }
 
+/// Which cluster the k8s backend and the operator commands act on
+#[derive(Parser, Debug)]
+struct K8sBackendArgs {
+    #[clap(
+        long,
+        global = true,
+        env = "FORGE_KUBECTL_PATH",
+        default_value = KUBECTL_BIN,
+        help = "The kubectl binary to use"
+    )]
+    kubectl_path: PathBuf,
+    #[clap(
+        long,
+        global = true,
+        env = "FORGE_KUBECONFIG",
+        help = "The kubeconfig to use. Defaults to KUBECONFIG or ~/.kube/config"
+    )]
+    kubeconfig: Option<PathBuf>,
+    #[clap(
+        long,
+        global = true,
+        env = "FORGE_KUBE_CONTEXT",
+        help = "The kubeconfig context to use. Defaults to the current context"
+    )]
+    kube_context: Option<String>,
+    #[clap(
+        long,
+        global = true,
+        env = "FORGE_NAMESPACE_PREFIX",
+        default_value = DEFAULT_NAMESPACE_PREFIX,
+        help = "The prefix forge namespaces have. Namespaces without it are never touched"
+    )]
+    namespace_prefix: String,
+    #[clap(
+        long,
+        global = true,
+        env = "FORGE_REST_MAX_RETRIES",
+        default_value_t = RetryPolicy::default().max_retries,
+        help = "How often the nodes' REST clients retry safe requests that fail transiently, e.g. \
+                with a 502 from HAProxy or a reset port-forward. 0 disables retries"
+    )]
+    rest_max_retries: u32,
+    #[clap(
+        long,
+        global = true,
+        env = "FORGE_KEEP_ORPHANED_PORT_FORWARDS",
+        help = "Don't kill the kubectl port-forwards to forge namespaces that crashed runs left \
+                behind, e.g. when running port-forwards of your own"
+    )]
+    keep_orphaned_port_forwards: bool,
+    #[clap(
+        long,
+        global = true,
+        env = "FORGE_WATCH_K8S_EVENTS",
+        help = "Log the Warning events of the forge namespace during the run, e.g. pod evictions \
+                and volume failures, and list them in the test report"
+    )]
+    watch_k8s_events: bool,
+    #[clap(
+        long,
+        global = true,
+        env = "FORGE_CHECK_IMAGE_TAGS",
+        help = "Check that the image tags of the nodes exist in the registry of the helm values \
+                before deploying them"
+    )]
+    check_image_tags: bool,
+}
+
+/// How the nodes' REST clients time out, for both backends. Unset keeps the client defaults.
+#[derive(Parser, Debug)]
+struct RestClientArgs {
+    #[clap(
+        long,
+        global = true,
+        env = "FORGE_REST_TIMEOUT_SECS",
+        help = "The timeout of REST requests to the nodes"
+    )]
+    rest_timeout_secs: Option<u64>,
+    #[clap(
+        long,
+        global = true,
+        env = "FORGE_REST_CONNECT_TIMEOUT_SECS",
+        help = "The timeout of connecting to the nodes' REST API"
+    )]
+    rest_connect_timeout_secs: Option<u64>,
+}
+
+impl RestClientArgs {
+    fn options(&self, retry_policy: Option<RetryPolicy>) -> RestClientOptions {
+        RestClientOptions {
+            timeout: self.rest_timeout_secs.map(Duration::from_secs),
+            connect_timeout: self.rest_connect_timeout_secs.map(Duration::from_secs),
+            retry_policy,
+        }
+    }
+}
+
+impl K8sBackendArgs {
+    fn backend_config(&self, rest_client: &RestClientArgs) -> K8sBackendConfig {
+        K8sBackendConfig {
+            kubectl_path: self.kubectl_path.clone(),
+            kubeconfig: self.kubeconfig.clone(),
+            context: self.kube_context.clone(),
+            namespace_prefix: self.namespace_prefix.clone(),
+            rest_client_options: rest_client.options((self.rest_max_retries > 0).then(|| {
+                RetryPolicy {
+                    max_retries: self.rest_max_retries,
+                    ..RetryPolicy::default()
+                }
+            })),
+            cleanup_orphaned_port_forwards: !self.keep_orphaned_port_forwards,
+            watch_events: self.watch_k8s_events,
+            check_image_tags: self.check_image_tags,
+        }
+    }
+}
+
 #[derive(Subcommand, Debug)]
 enum CliCommand {
     /// Subcommands to run forge tests
@@ -124,6 +257,8 @@ enum CliCommand {
     /// Subcommands to set up or manage running forge networks
     #[clap(subcommand)]
     Operator(OperatorCommand),
+    /// Delete the forge namespaces left behind, e.g. by aborted runs
+    Cleanup(StaleCleanup),
 }
 
 #[derive(Subcommand, Debug)]
@@ -166,6 +301,12 @@ struct K8sSwarm {
         default_value = "devnet"
     )]
     upgrade_image_tag: String,
+    #[clap(
+        long,
+        conflicts_with = "image_tag",
+        help = "Start the validators on the latest release tag of the registry before the upgrade image tag, e.g. for compat tests of the previous release with the current build"
+    )]
+    from_previous_release: bool,
     #[clap(
         long,
         help = "Path to flattened directory containing compiled Move modules"
@@ -181,6 +322,17 @@ struct K8sSwarm {
         help = "If set, reuse the forge testnet active in the specified namespace"
     )]
     reuse: bool,
+    #[clap(
+        long,
+        conflicts_with = "namespace",
+        help = "Reuse the forge testnet active in this namespace instead of deploying one. Combine with --keep for it to survive the run"
+    )]
+    reuse_namespace: Option<String>,
+    #[clap(
+        long,
+        help = "If set, reuses the testnet even if its node count or versions differ from what the test asks for"
+    )]
+    force: bool,
     #[clap(
         long,
         help = "If set, keeps the forge testnet active in the specified namespace"
@@ -188,6 +340,13 @@ struct K8sSwarm {
     keep: bool,
     #[clap(long, help = "If set, enables HAProxy for each of the validators")]
     enable_haproxy: bool,
+    #[clap(long, help = "If set, connects to the node REST APIs over HTTPS")]
+    rest_api_tls: bool,
+    #[clap(
+        long,
+        help = "Path to a PEM encoded root CA to trust for the node REST APIs. Implies --rest-api-tls"
+    )]
+    rest_api_root_ca: Option<String>,
 }
 
 #[derive(Parser, Debug)]
@@ -211,6 +370,18 @@ struct CleanUp {
     namespace: Option<String>,
 }
 
+#[derive(Parser, Debug)]
+struct StaleCleanup {
+    #[clap(
+        long,
+        help = "Delete forge namespaces older than this, e.g. 4h, 30m or 2d",
+        value_parser = parse_age
+    )]
+    older_than: Duration,
+    #[clap(long, help = "If set, only prints what would be deleted")]
+    dry_run: bool,
+}
+
 #[derive(Parser, Debug)]
 struct Resize {
     #[clap(long, help = "The kubernetes namespace to resize")]
@@ -264,13 +435,13 @@ static SYSTEM_12_CORES_10GB_THRESHOLD: Lazy<SystemMetricsThreshold> = Lazy::new(
 });
 
 /// Make an easy to remember random namespace for your testnet
-fn random_namespace<R: Rng>(dictionary: Vec<String>, rng: &mut R) -> Result<String> {
+fn random_namespace<R: Rng>(prefix: &str, dictionary: Vec<String>, rng: &mut R) -> Result<String> {
     // Pick four random words
     let random_words = dictionary
         .choose_multiple(rng, 4)
         .cloned()
         .collect::<Vec<String>>();
-    Ok(format!("forge-{}", random_words.join("-")))
+    Ok(format!("{}-{}", prefix, random_words.join("-")))
 }
 
 fn main() -> Result<()> {
@@ -280,6 +451,8 @@ fn main() -> Result<()> {
 
     let args = Args::parse();
     let duration = Duration::from_secs(args.duration_secs as u64);
+    // everything k8s related acts on the selected cluster only, never the active context
+    let backend_config = Arc::new(args.k8s_backend.backend_config(&args.rest_client));
     let suite_name: &str = args.suite.as_ref();
 
     let runtime = Runtime::new()?;
@@ -287,7 +460,8 @@ fn main() -> Result<()> {
         // cmd input for test
         CliCommand::Test(ref test_cmd) => {
             // Identify the test suite to run
-            let mut test_suite = get_test_suite(suite_name, duration, test_cmd)?;
+            let mut test_suite =
+                get_test_suite(suite_name, duration, test_cmd)?.with_suite_name(suite_name);
 
             // Identify the number of validators and fullnodes to run
             // (if overriding what test has specified)
@@ -324,7 +498,8 @@ fn main() -> Result<()> {
                     run_forge(
                         duration,
                         test_suite,
-                        LocalFactory::from_workspace(swarm_dir)?,
+                        LocalFactory::from_workspace(swarm_dir)?
+                            .with_rest_client_options(args.rest_client.options(None)),
                         &args.options,
                         args.changelog.clone(),
                     )
@@ -333,7 +508,9 @@ fn main() -> Result<()> {
                     if let Some(move_modules_dir) = &k8s.move_modules_dir {
                         test_suite = test_suite.with_genesis_modules_path(move_modules_dir.clone());
                     }
-                    let namespace = if k8s.namespace.is_none() {
+                    let namespace = if let Some(namespace) = &k8s.reuse_namespace {
+                        namespace.clone()
+                    } else if k8s.namespace.is_none() {
                         let mut rng: ThreadRng = rand::thread_rng();
                         // Lets pick some four letter words ;)
                         let words = random_word::all_len(4)
@@ -346,37 +523,68 @@ fn main() -> Result<()> {
                             .iter()
                             .map(|s| s.to_string())
                             .collect::<Vec<String>>();
-                        random_namespace(words, &mut rng)?
+                        random_namespace(&backend_config.namespace_prefix, words, &mut rng)?
                     } else {
                         k8s.namespace.clone().unwrap()
                     };
                     let forge_runner_mode =
                         ForgeRunnerMode::try_from_env().unwrap_or(ForgeRunnerMode::K8s);
-                    run_forge(
-                        duration,
-                        test_suite,
-                        K8sFactory::new(
-                            namespace,
-                            k8s.image_tag.clone(),
-                            k8s.upgrade_image_tag.clone(),
-                            // We want to port forward if we're running locally because local means we're not in cluster
-                            k8s.port_forward || forge_runner_mode == ForgeRunnerMode::Local,
-                            k8s.reuse,
-                            k8s.keep,
-                            k8s.enable_haproxy,
-                        )
-                        .unwrap(),
-                        &args.options,
-                        args.changelog,
-                    )?;
+                    let image_tag = if k8s.from_previous_release {
+                        let release = runtime.block_on(previous_release(
+                            DEFAULT_VALIDATOR_IMAGE_REPO,
+                            &k8s.upgrade_image_tag,
+                        ))?;
+                        info!("Starting from the previous release {}", release);
+                        release.to_string()
+                    } else {
+                        k8s.image_tag.clone()
+                    };
+                    let mut factory = K8sFactory::new(
+                        backend_config.clone(),
+                        namespace,
+                        image_tag,
+                        k8s.upgrade_image_tag.clone(),
+                        // We want to port forward if we're running locally because local means we're not in cluster
+                        k8s.port_forward || forge_runner_mode == ForgeRunnerMode::Local,
+                        k8s.reuse || k8s.reuse_namespace.is_some(),
+                        k8s.keep,
+                        k8s.enable_haproxy,
+                    )
+                    .unwrap()
+                    .with_force_reuse(k8s.force);
+                    if let Some(root_ca) = &k8s.rest_api_root_ca {
+                        factory =
+                            factory.with_rest_api_tls(RestApiTls::with_root_ca_file(root_ca)?);
+                    } else if k8s.rest_api_tls {
+                        factory = factory.with_rest_api_tls(RestApiTls::default());
+                    }
+                    run_forge(duration, test_suite, factory, &args.options, args.changelog)?;
                     Ok(())
                 },
             }
         },
+        CliCommand::Cleanup(cleanup) => {
+            let namespaces = runtime.block_on(cleanup_stale_namespaces(
+                &backend_config,
+                cleanup.older_than,
+                cleanup.dry_run,
+            ))?;
+            let verb = if cleanup.dry_run {
+                "Would delete"
+            } else {
+                "Deleted"
+            };
+            println!("{} {} stale namespaces", verb, namespaces.len());
+            for namespace in namespaces {
+                println!("  {}", namespace);
+            }
+            Ok(())
+        },
         // cmd input for cluster operations
         CliCommand::Operator(op_cmd) => match op_cmd {
             OperatorCommand::SetNodeImageTag(set_stateful_set_image_tag_config) => {
                 runtime.block_on(set_stateful_set_image_tag(
+                    &backend_config,
                     set_stateful_set_image_tag_config.stateful_set_name,
                     set_stateful_set_image_tag_config.container_name,
                     set_stateful_set_image_tag_config.image_tag,
@@ -386,14 +594,15 @@ fn main() -> Result<()> {
             },
             OperatorCommand::CleanUp(cleanup) => {
                 if let Some(namespace) = cleanup.namespace {
-                    runtime.block_on(uninstall_testnet_resources(namespace))?;
+                    runtime.block_on(uninstall_testnet_resources(&backend_config, namespace))?;
                 } else {
-                    runtime.block_on(cleanup_cluster_with_management())?;
+                    runtime.block_on(cleanup_cluster_with_management(&backend_config))?;
                 }
                 Ok(())
             },
             OperatorCommand::Resize(resize) => {
                 runtime.block_on(install_testnet_resources(
+                    &backend_config,
                     resize.namespace,
                     resize.num_validators,
                     resize.num_fullnodes,
@@ -404,6 +613,8 @@ fn main() -> Result<()> {
                     resize.enable_haproxy,
                     None,
                     None,
+                    None,
+                    None,
                 ))?;
                 Ok(())
             },
@@ -503,12 +714,12 @@ fn get_test_suite(
 ) -> Result<ForgeConfig> {
     // Check the test name against the multi-test suites
     match test_name {
-        "local_test_suite" => return Ok(local_test_suite()),
+        "local_test_suite" => return Ok(local_test_suite(duration, test_cmd)),
         "pre_release" => return Ok(pre_release_suite()),
         "run_forever" => return Ok(run_forever()),
         // TODO(rustielin): verify each test suite
-        "k8s_suite" => return Ok(k8s_test_suite()),
-        "chaos" => return Ok(chaos_test_suite(duration)),
+        "k8s_suite" => return Ok(k8s_test_suite(duration, test_cmd)),
+        "chaos" => return Ok(chaos_test_suite(duration, test_cmd)),
         _ => {}, // No multi-test suite matches!
     };
 
@@ -533,8 +744,27 @@ fn get_test_suite(
     let ungrouped_test_suite = match test_name {
         "epoch_changer_performance" => epoch_changer_performance(),
         "validators_join_and_leave" => validators_join_and_leave(),
+        "validator_join" => validator_join(),
+        "stake_weighted_proposals" => stake_weighted_proposals(),
+        "compat_mixed_start" => compat_mixed_start(),
+        "compat_new_fullnode" => compat_new_fullnode(),
         "config" => ForgeConfig::default().add_network_test(ReconfigurationTest),
         "network_partition" => network_partition(),
+        "minority_partition" => minority_partition(PartitionDirection::Both),
+        "minority_partition_outgoing" => minority_partition(PartitionDirection::FromAToB),
+        "minority_partition_incoming" => minority_partition(PartitionDirection::FromBToA),
+        "consensus_latency" => consensus_latency(),
+        "clock_skew" => clock_skew(),
+        "storage_resilience" => storage_resilience(),
+        "validator_cpu_stress" => validator_cpu_stress(),
+        "oom_recovery" => oom_recovery(),
+        "dns_failure" => dns_failure(),
+        "haproxy_consistency" => haproxy_consistency(),
+        "faulty_validator_resent_votes" => faulty_validator(FaultyBehavior::ResendVotes),
+        "faulty_validator_withheld_votes" => faulty_validator(FaultyBehavior::WithholdVotes),
+        "consensus_resiliency_loss_2pct" => consensus_resiliency_with_loss(2),
+        "consensus_resiliency_loss_5pct" => consensus_resiliency_with_loss(5),
+        "consensus_resiliency_loss_10pct" => consensus_resiliency_with_loss(10),
         "network_bandwidth" => network_bandwidth(),
         "setup_test" => setup_test(),
         "single_vfn_perf" => single_vfn_perf(),
@@ -578,25 +808,43 @@ fn run_forever() -> ForgeConfig {
         .add_aptos_test(RunForever)
 }
 
-fn local_test_suite() -> ForgeConfig {
+/// The tests the generic suites select by their tags, see [ForgeConfig::retain_tagged]
+fn tagged_tests(duration: Duration, test_cmd: &TestCommand) -> ForgeConfig {
     ForgeConfig::default()
+        .add_network_test(
+            wrap_with_realistic_env(realistic_env_max_load(duration, test_cmd))
+                .with_tags(&["perf", "land-blocking"]),
+        )
         .add_aptos_test(FundAccount)
         .add_aptos_test(TransferCoins)
         .add_admin_test(GetMetadata)
         .add_network_test(RestartValidator)
         .add_network_test(EmitTransaction)
+        .add_network_test(FrameworkUpgrade)
+        .add_network_test(PerformanceBenchmark)
+        .add_network_test(NetworkBandwidthTest)
+        .add_network_test(ThreeRegionSameCloudSimulationTest)
+        .add_network_test(NetworkLossTest)
+}
+
+fn local_test_suite(duration: Duration, test_cmd: &TestCommand) -> ForgeConfig {
+    tagged_tests(duration, test_cmd)
+        .retain_tagged(&TagQuery::any(&["smoke", "restart"]))
         .with_genesis_module_bundle(aptos_cached_packages::head_release_bundle().clone())
 }
 
-fn k8s_test_suite() -> ForgeConfig {
-    ForgeConfig::default()
+fn k8s_test_suite(duration: Duration, test_cmd: &TestCommand) -> ForgeConfig {
+    tagged_tests(duration, test_cmd)
+        .retain_tagged(&TagQuery::any(&["smoke", "compat", "benchmark"]))
         .with_initial_validator_count(NonZeroUsize::new(30).unwrap())
-        .add_aptos_test(FundAccount)
-        .add_aptos_test(TransferCoins)
-        .add_admin_test(GetMetadata)
-        .add_network_test(EmitTransaction)
-        .add_network_test(FrameworkUpgrade)
-        .add_network_test(PerformanceBenchmark)
+}
+
+/// The land-blocking perf tests, on the swarm of the realistic env max load test
+fn land_blocking(duration: Duration, test_cmd: &TestCommand) -> ForgeConfig {
+    realistic_env_max_load_config(duration, test_cmd, 7, 5).add_tagged_tests(
+        tagged_tests(duration, test_cmd),
+        &TagQuery::default().or_all(&["perf", "land-blocking"]),
+    )
 }
 
 /// Attempts to match the test name to a land-blocking test
@@ -606,8 +854,9 @@ fn get_land_blocking_test(
     test_cmd: &TestCommand,
 ) -> Option<ForgeConfig> {
     let test = match test_name {
-        "land_blocking" | "realistic_env_max_load" => {
-            realistic_env_max_load_test(duration, test_cmd, 7, 5)
+        "land_blocking" | "realistic_env_max_load" => land_blocking(duration, test_cmd),
+        "land_blocking_simulated_geo" => {
+            realistic_env_max_load_test(duration, test_cmd, 7, 5, wrap_with_simulated_geo)
         },
         "compat" => compat(),
         "framework_upgrade" => framework_upgrade(),
@@ -686,7 +935,9 @@ fn get_realistic_env_test(
     test_cmd: &TestCommand,
 ) -> Option<ForgeConfig> {
     let test = match test_name {
-        "realistic_env_max_load_large" => realistic_env_max_load_test(duration, test_cmd, 20, 10),
+        "realistic_env_max_load_large" => {
+            realistic_env_max_load_test(duration, test_cmd, 20, 10, wrap_with_realistic_env)
+        },
         "realistic_env_load_sweep" => realistic_env_load_sweep_test(),
         "realistic_env_workload_sweep" => realistic_env_workload_sweep_test(),
         "realistic_env_graceful_workload_sweep" => realistic_env_graceful_workload_sweep(),
@@ -705,7 +956,11 @@ fn get_state_sync_test(test_name: &str) -> Option<ForgeConfig> {
             state_sync_perf_fullnodes_execute_transactions()
         },
         "state_sync_perf_fullnodes_fast_sync" => state_sync_perf_fullnodes_fast_sync(),
+        "state_sync_fullnode_fan_out" => state_sync_fullnode_fan_out(),
         "state_sync_perf_validators" => state_sync_perf_validators(),
+        "state_sync_perf_validators_constrained_bandwidth" => {
+            state_sync_perf_validators_constrained_bandwidth()
+        },
         _ => return None, // The test name does not match a state sync test
     };
     Some(test)
@@ -732,6 +987,15 @@ fn wrap_with_realistic_env<T: NetworkTest + 'static>(test: T) -> CompositeNetwor
     )
 }
 
+/// Like the realistic env, with the latencies of validators spread over mainnet's regions
+fn wrap_with_simulated_geo<T: NetworkTest + 'static>(test: T) -> CompositeNetworkTest {
+    CompositeNetworkTest::new_with_two_wrappers(
+        SimulatedGeo::default(),
+        CpuChaosTest::default(),
+        test,
+    )
+}
+
 fn mempool_config_practically_non_expiring(mempool_config: &mut MempoolConfig) {
     mempool_config.capacity = 3_000_000;
     mempool_config.capacity_bytes = (3_u64 * 1024 * 1024 * 1024) as usize;
@@ -1807,6 +2071,130 @@ fn network_partition() -> ForgeConfig {
         }))
 }
 
+/// With the one-way partitions, the minority only ever sees part of the rounds, and has to
+/// recover through timeouts and sync info
+fn minority_partition(direction: PartitionDirection) -> ForgeConfig {
+    ForgeConfig::default()
+        .with_initial_validator_count(NonZeroUsize::new(7).unwrap())
+        .add_network_test(MinorityPartitionTest { direction })
+        .with_success_criteria(
+            SuccessCriteria::new(2500)
+                .add_no_restarts()
+                .add_wait_for_catchup_s(240),
+        )
+}
+
+fn consensus_latency() -> ForgeConfig {
+    ForgeConfig::default()
+        .with_initial_validator_count(NonZeroUsize::new(6).unwrap())
+        .add_network_test(ConsensusLatencyTest)
+        .with_success_criteria(
+            SuccessCriteria::new(1000)
+                .add_no_restarts()
+                .add_wait_for_catchup_s(240),
+        )
+}
+
+fn clock_skew() -> ForgeConfig {
+    ForgeConfig::default()
+        .with_initial_validator_count(NonZeroUsize::new(5).unwrap())
+        .add_network_test(ClockSkewTest)
+        .with_success_criteria(
+            SuccessCriteria::new(1000)
+                .add_no_restarts()
+                .add_wait_for_catchup_s(240),
+        )
+}
+
+fn storage_resilience() -> ForgeConfig {
+    ForgeConfig::default()
+        .with_initial_validator_count(NonZeroUsize::new(5).unwrap())
+        .add_network_test(StorageResilienceTest)
+        // the filled validator may crash
+        .with_success_criteria(SuccessCriteria::new(1000).add_wait_for_catchup_s(240))
+}
+
+fn validator_cpu_stress() -> ForgeConfig {
+    ForgeConfig::default()
+        .with_initial_validator_count(NonZeroUsize::new(6).unwrap())
+        .add_network_test(ValidatorCpuStressTest {
+            workers: 4,
+            load_percent: 90,
+        })
+        .with_success_criteria(
+            SuccessCriteria::new(1000)
+                .add_no_restarts()
+                .add_wait_for_catchup_s(240)
+                .add_latency_threshold(5.0, LatencyType::P99),
+        )
+}
+
+fn oom_recovery() -> ForgeConfig {
+    ForgeConfig::default()
+        .with_initial_validator_count(NonZeroUsize::new(5).unwrap())
+        .add_network_test(OomRecoveryTest { size_mb: 32 * 1024 })
+        // the stressed validator restarts once OOMKilled
+        .with_success_criteria(SuccessCriteria::new(1000).add_wait_for_catchup_s(240))
+}
+
+fn dns_failure() -> ForgeConfig {
+    ForgeConfig::default()
+        .with_initial_validator_count(NonZeroUsize::new(5).unwrap())
+        .add_network_test(DnsFailureTest)
+        .with_success_criteria(
+            SuccessCriteria::new(1000)
+                .add_no_restarts()
+                .add_wait_for_catchup_s(240),
+        )
+}
+
+/// Run with --enable-haproxy, which it checks the validators' REST APIs through
+fn haproxy_consistency() -> ForgeConfig {
+    ForgeConfig::default()
+        .with_initial_validator_count(NonZeroUsize::new(4).unwrap())
+        .add_network_test(HaproxyConsistencyTest)
+        .with_success_criteria(SuccessCriteria::new(1000).add_no_restarts())
+}
+
+/// 1 of 4 validators misbehaves, and the other three must keep committing and agree
+fn faulty_validator(behavior: FaultyBehavior) -> ForgeConfig {
+    ForgeConfig::default()
+        .with_initial_validator_count(NonZeroUsize::new(4).unwrap())
+        .add_network_test(FaultyValidatorTest {
+            behavior,
+            num_faulty: 1,
+        })
+        // the faulty validator misbehaves through failpoints set over its API
+        .with_validator_override_node_config_fn(Arc::new(|config, _| {
+            config.api.failpoints_enabled = true;
+        }))
+        .with_success_criteria(
+            SuccessCriteria::new(1000)
+                .add_no_restarts()
+                .add_wait_for_catchup_s(240),
+        )
+}
+
+/// The consensus latency test and a performance benchmark, with the given share of the packets
+/// of every validator dropped on top of their delays
+fn consensus_resiliency_with_loss(loss_percentage: u64) -> ForgeConfig {
+    ForgeConfig::default()
+        .with_initial_validator_count(NonZeroUsize::new(6).unwrap())
+        .add_network_test(CompositeNetworkTest::new(
+            ValidatorLoss::new(loss_percentage),
+            ConsensusLatencyTest,
+        ))
+        .add_network_test(CompositeNetworkTest::new(
+            ValidatorLoss::new(loss_percentage),
+            PerformanceBenchmark,
+        ))
+        .with_success_criteria(
+            SuccessCriteria::new(500)
+                .add_no_restarts()
+                .add_wait_for_catchup_s(240),
+        )
+}
+
 fn compat() -> ForgeConfig {
     ForgeConfig::default()
         .with_initial_validator_count(NonZeroUsize::new(4).unwrap())
@@ -1818,6 +2206,26 @@ fn compat() -> ForgeConfig {
         }))
 }
 
+/// The compat test on a swarm that starts with half of its validators on the new version, which
+/// saves upgrading them
+fn compat_mixed_start() -> ForgeConfig {
+    compat().with_mixed_versions(
+        MixedVersions::new()
+            .with_validators(0..2, 1)
+            .with_fullnodes(1),
+    )
+}
+
+/// Fullnodes on the new version following validators that stay on the old one
+fn compat_new_fullnode() -> ForgeConfig {
+    ForgeConfig::default()
+        .with_initial_validator_count(NonZeroUsize::new(4).unwrap())
+        .with_initial_fullnode_count(4)
+        .add_network_test(PerformanceBenchmark)
+        .with_success_criteria(SuccessCriteria::new(5000).add_wait_for_catchup_s(240))
+        .with_mixed_versions(MixedVersions::new().with_fullnodes(1))
+}
+
 fn framework_upgrade() -> ForgeConfig {
     ForgeConfig::default()
         .with_initial_validator_count(NonZeroUsize::new(4).unwrap())
@@ -1835,9 +2243,10 @@ fn epoch_changer_performance() -> ForgeConfig {
         .with_initial_validator_count(NonZeroUsize::new(5).unwrap())
         .with_initial_fullnode_count(2)
         .add_network_test(PerformanceBenchmark)
-        .with_genesis_helm_config_fn(Arc::new(|helm_values| {
-            helm_values["chain"]["epoch_duration_secs"] = 60.into();
-        }))
+        .with_genesis_overrides(GenesisOverrides {
+            epoch_duration_secs: Some(60),
+            ..GenesisOverrides::default()
+        })
 }
 
 /// A default config for running various state sync performance tests
@@ -1895,6 +2304,17 @@ fn state_sync_perf_fullnodes_fast_sync() -> ForgeConfig {
         }))
 }
 
+/// The config for running a state sync test of a wiped fullnode, while the
+/// other fullnodes of its validator serve the traffic.
+fn state_sync_fullnode_fan_out() -> ForgeConfig {
+    ForgeConfig::default()
+        .with_initial_validator_count(NonZeroUsize::new(4).unwrap())
+        .with_initial_fullnode_count(1)
+        .with_fullnodes_per_validator(3)
+        .add_network_test(StateSyncFullnodeFanOut)
+        .with_success_criteria(SuccessCriteria::new(1000))
+}
+
 /// The config for running a state sync performance test when applying
 /// transaction outputs in failed validators.
 fn state_sync_perf_validators() -> ForgeConfig {
@@ -1910,6 +2330,55 @@ fn state_sync_perf_validators() -> ForgeConfig {
         .with_success_criteria(SuccessCriteria::new(5000))
 }
 
+/// The config for running a state sync performance test of failed validators
+/// behind constrained links.
+fn state_sync_perf_validators_constrained_bandwidth() -> ForgeConfig {
+    ForgeConfig::default()
+        .with_initial_validator_count(NonZeroUsize::new(7).unwrap())
+        .with_genesis_helm_config_fn(Arc::new(|helm_values| {
+            helm_values["chain"]["epoch_duration_secs"] = 600.into();
+        }))
+        .with_validator_override_node_config_fn(Arc::new(|config, _| {
+            state_sync_config_apply_transaction_outputs(&mut config.state_sync);
+        }))
+        .add_network_test(StateSyncValidatorConstrainedBandwidth)
+        .with_success_criteria(SuccessCriteria::new(5000))
+}
+
+/// The config for running a test of a validator joining the running validators.
+fn validator_join() -> ForgeConfig {
+    ForgeConfig::default()
+        .with_initial_validator_count(NonZeroUsize::new(4).unwrap())
+        .with_genesis_helm_config_fn(Arc::new(|helm_values| {
+            helm_values["chain"]["allow_new_validators"] = true.into();
+            // a fifth validator with the same stake adds 25% voting power
+            helm_values["chain"]["voting_power_increase_limit"] = 50.into();
+        }))
+        .add_network_test(ValidatorJoinTest)
+        .with_success_criteria(
+            SuccessCriteria::new(1000)
+                .add_no_restarts()
+                .add_wait_for_catchup_s(240),
+        )
+}
+
+/// The config for giving one validator ten times the stake of the others.
+fn stake_weighted_proposals() -> ForgeConfig {
+    ForgeConfig::default()
+        .with_initial_validator_count(NonZeroUsize::new(4).unwrap())
+        .with_genesis_overrides(GenesisOverrides {
+            // fewer epochs for the stake to join in
+            voting_power_increase_limit: Some(50),
+            ..GenesisOverrides::default()
+        })
+        .add_network_test(StakeWeightedProposalsTest)
+        .with_success_criteria(
+            SuccessCriteria::new(1000)
+                .add_no_restarts()
+                .add_wait_for_catchup_s(240),
+        )
+}
+
 /// The config for running a validator join and leave test.
 fn validators_join_and_leave() -> ForgeConfig {
     ForgeConfig::default()
@@ -1936,13 +2405,52 @@ fn realistic_env_max_load_test(
     test_cmd: &TestCommand,
     num_validators: usize,
     num_fullnodes: usize,
+    wrap: fn(TwoTrafficsTest) -> CompositeNetworkTest,
 ) -> ForgeConfig {
-    // Check if HAProxy is enabled
-    let ha_proxy = if let TestCommand::K8sSwarm(k8s) = test_cmd {
+    realistic_env_max_load_config(duration, test_cmd, num_validators, num_fullnodes)
+        .add_network_test(wrap(realistic_env_max_load(duration, test_cmd)))
+}
+
+/// Whether HAProxy is enabled
+fn ha_proxy_enabled(test_cmd: &TestCommand) -> bool {
+    if let TestCommand::K8sSwarm(k8s) = test_cmd {
         k8s.enable_haproxy
     } else {
         false
-    };
+    }
+}
+
+/// The traffics of the realistic env max load test, to be wrapped in the realistic env
+fn realistic_env_max_load(duration: Duration, test_cmd: &TestCommand) -> TwoTrafficsTest {
+    let ha_proxy = ha_proxy_enabled(test_cmd);
+    let long_running = duration.as_secs() >= 2400;
+    let mempool_backlog = if ha_proxy { 30000 } else { 40000 };
+    TwoTrafficsTest {
+        inner_traffic: EmitJobRequest::default()
+            .mode(EmitJobMode::MaxLoad { mempool_backlog })
+            .init_gas_price_multiplier(20),
+        inner_success_criteria: SuccessCriteria::new(
+            if ha_proxy {
+                4600
+            } else if long_running {
+                // This is for forge stable
+                7000
+            } else {
+                // During land time we want to be less strict, otherwise we flaky fail
+                6500
+            },
+        ),
+    }
+}
+
+/// The swarm and success criteria of the realistic env max load test, without its test
+fn realistic_env_max_load_config(
+    duration: Duration,
+    test_cmd: &TestCommand,
+    num_validators: usize,
+    num_fullnodes: usize,
+) -> ForgeConfig {
+    let ha_proxy = ha_proxy_enabled(test_cmd);
 
     // Determine if this is a long running test
     let duration_secs = duration.as_secs();
@@ -1984,27 +2492,9 @@ fn realistic_env_max_load_test(
         )
     }
 
-    // Create the test
-    let mempool_backlog = if ha_proxy { 30000 } else { 40000 };
     ForgeConfig::default()
         .with_initial_validator_count(NonZeroUsize::new(num_validators).unwrap())
         .with_initial_fullnode_count(num_fullnodes)
-        .add_network_test(wrap_with_realistic_env(TwoTrafficsTest {
-            inner_traffic: EmitJobRequest::default()
-                .mode(EmitJobMode::MaxLoad { mempool_backlog })
-                .init_gas_price_multiplier(20),
-            inner_success_criteria: SuccessCriteria::new(
-                if ha_proxy {
-                    4600
-                } else if long_running {
-                    // This is for forge stable
-                    7000
-                } else {
-                    // During land time we want to be less strict, otherwise we flaky fail
-                    6500
-                },
-            ),
-        }))
         .with_genesis_helm_config_fn(Arc::new(move |helm_values| {
             // Have single epoch change in land blocking, and a few on long-running
             helm_values["chain"]["epoch_duration_secs"] =
@@ -2114,10 +2604,12 @@ fn realistic_network_tuned_for_throughput_test() -> ForgeConfig {
             .with_validator_resource_override(NodeResourceOverride {
                 cpu_cores: Some(58),
                 memory_gib: Some(200),
+                ..NodeResourceOverride::default()
             })
             .with_fullnode_resource_override(NodeResourceOverride {
                 cpu_cores: Some(58),
                 memory_gib: Some(200),
+                ..NodeResourceOverride::default()
             })
             .with_success_criteria(
                 SuccessCriteria::new(25000)
@@ -2173,12 +2665,10 @@ fn pre_release_suite() -> ForgeConfig {
         .add_network_test(NetworkBandwidthTest)
 }
 
-fn chaos_test_suite(duration: Duration) -> ForgeConfig {
-    ForgeConfig::default()
+fn chaos_test_suite(duration: Duration, test_cmd: &TestCommand) -> ForgeConfig {
+    tagged_tests(duration, test_cmd)
+        .retain_tagged(&TagQuery::any(&["chaos"]))
         .with_initial_validator_count(NonZeroUsize::new(30).unwrap())
-        .add_network_test(NetworkBandwidthTest)
-        .add_network_test(ThreeRegionSameCloudSimulationTest)
-        .add_network_test(NetworkLossTest)
         .with_success_criteria(
             SuccessCriteria::new(
                 if duration > Duration::from_secs(1200) {
@@ -2355,6 +2845,11 @@ fn quorum_store_reconfig_enable_test() -> ForgeConfig {
         .with_initial_validator_count(NonZeroUsize::new(20).unwrap())
         .with_initial_fullnode_count(20)
         .add_network_test(QuorumStoreOnChainEnableTest {})
+        // for the proposal changing the consensus config to pass well within the test
+        .with_genesis_overrides(GenesisOverrides {
+            voting_duration_secs: Some(120),
+            ..GenesisOverrides::default()
+        })
         .with_success_criteria(
             SuccessCriteria::new(5000)
                 .add_no_restarts()
@@ -2571,6 +3066,10 @@ impl Test for GetMetadata {
     fn name(&self) -> &'static str {
         "get_metadata"
     }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["smoke"]
+    }
 }
 
 impl AdminTest for GetMetadata {
@@ -2605,6 +3104,10 @@ impl Test for FundAccount {
     fn name(&self) -> &'static str {
         "fund_account"
     }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["smoke"]
+    }
 }
 
 #[async_trait::async_trait]
@@ -2629,6 +3132,10 @@ impl Test for TransferCoins {
     fn name(&self) -> &'static str {
         "transfer_coins"
     }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["smoke"]
+    }
 }
 
 #[async_trait::async_trait]
@@ -2660,6 +3167,10 @@ impl Test for RestartValidator {
     fn name(&self) -> &'static str {
         "restart_validator"
     }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["restart"]
+    }
 }
 
 #[async_trait]
@@ -2686,6 +3197,10 @@ impl Test for EmitTransaction {
     fn name(&self) -> &'static str {
         "emit_transaction"
     }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["smoke"]
+    }
 }
 
 #[async_trait]
@@ -2755,7 +3270,7 @@ impl NetworkTest for GatherMetrics {
 }
 
 async fn gather_metrics_one(ctx: &NetworkContext<'_>) {
-    let handle = ctx.runtime.handle();
+    let handle = ctx.handle();
     let outdir = Path::new("/tmp");
     let mut gets = FuturesUnordered::new();
     let now = chrono::prelude::Utc::now()
@@ -2821,10 +3336,28 @@ mod test {
             .iter()
             .map(|s| s.to_string())
             .collect::<Vec<String>>();
-        let namespace = random_namespace(words, &mut rng).unwrap();
+        let namespace = random_namespace(DEFAULT_NAMESPACE_PREFIX, words, &mut rng).unwrap();
         assert_eq!(namespace, "forge-durian-eggplant-fig-apple");
     }
 
+    #[test]
+    fn test_land_blocking() {
+        let test_cmd = TestCommand::LocalSwarm(LocalSwarm { swarmdir: None });
+        let duration = Duration::from_secs(480);
+        let tests = land_blocking(duration, &test_cmd);
+        let tests = tests.all_tests();
+        assert_eq!(tests.len(), 1);
+        assert_eq!(tests[0].tags(), ["perf", "land-blocking"]);
+
+        // the other suites of the tagged tests leave it out
+        let k8s_tests = k8s_test_suite(duration, &test_cmd);
+        assert!(!k8s_tests.all_tests().is_empty());
+        assert!(k8s_tests
+            .all_tests()
+            .iter()
+            .all(|test| !test.tags().contains(&"perf") || test.tags().contains(&"benchmark")));
+    }
+
     #[test]
     fn verify_tool() {
         use clap::CommandFactory;
@@ -4,7 +4,7 @@
 use crate::{changing_working_quorum_test_helper, wrap_with_realistic_env, TestCommand};
 use aptos_forge::{
     success_criteria::{LatencyType, StateProgressThreshold, SuccessCriteria},
-    EmitJobMode, EmitJobRequest, ForgeConfig,
+    EmitJobMode, EmitJobRequest, ForgeConfig, GenesisOverrides,
 };
 use aptos_sdk::types::on_chain_config::{
     BlockGasLimitType, ConsensusAlgorithmConfig, DagConsensusConfigV1, OnChainConsensusConfig,
@@ -193,6 +193,11 @@ fn dag_reconfig_enable_test() -> ForgeConfig {
         .with_initial_validator_count(NonZeroUsize::new(20).unwrap())
         .with_initial_fullnode_count(20)
         .add_network_test(DagOnChainEnableTest {})
+        // for the proposals changing the consensus config to pass well within the test
+        .with_genesis_overrides(GenesisOverrides {
+            voting_duration_secs: Some(120),
+            ..GenesisOverrides::default()
+        })
         .with_validator_override_node_config_fn(Arc::new(|config, _| {
             config.consensus.max_sending_block_txns = 4000;
             config.consensus.max_sending_block_bytes = 6 * 1024 * 1024;
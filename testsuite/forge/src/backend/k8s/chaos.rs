@@ -2,13 +2,21 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::{
-    dump_string_to_file, K8sSwarm, Result, Swarm, SwarmChaos, SwarmCpuStress, SwarmNetEm,
-    SwarmNetworkBandwidth, SwarmNetworkDelay, SwarmNetworkLoss, SwarmNetworkPartition, KUBECTL_BIN,
+    dump_string_to_file, K8sNode, K8sSwarm, Node, PartitionDirection, Result, SkewDirection, Swarm,
+    SwarmChaos, SwarmCpuStress, SwarmDnsFailure, SwarmGroupPartition, SwarmNetEm,
+    SwarmNetworkBandwidth, SwarmNetworkDelay, SwarmNetworkLoss, SwarmNetworkPartition,
+    SwarmPeerBandwidth, SwarmPeerCpuStress, SwarmPeerLoss, SwarmPeerMemoryStress, SwarmTimeSkew,
+    APTOS_NODE_HELM_RELEASE_NAME,
 };
 use anyhow::bail;
 use aptos_logger::info;
 use aptos_sdk::{move_types::account_address::AccountAddress, types::PeerId};
-use std::process::{Command, Stdio};
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    process::Stdio,
+    time::Duration,
+};
 use tempfile::TempDir;
 
 macro_rules! DELAY_NETWORK_CHAOS_TEMPLATE {
@@ -21,18 +29,53 @@ macro_rules! PARTITION_NETWORK_CHAOS_TEMPLATE {
         "chaos/network_partition.yaml"
     };
 }
+macro_rules! GROUP_PARTITION_NETWORK_CHAOS_TEMPLATE {
+    () => {
+        "chaos/group_partition.yaml"
+    };
+}
 macro_rules! BANDWIDTH_NETWORK_CHAOS_TEMPLATE {
     () => {
         "chaos/network_bandwidth.yaml"
     };
 }
 
+macro_rules! PEER_BANDWIDTH_NETWORK_CHAOS_TEMPLATE {
+    () => {
+        "chaos/peer_bandwidth.yaml"
+    };
+}
+
 macro_rules! NETWORK_LOSS_CHAOS_TEMPLATE {
     () => {
         "chaos/network_loss.yaml"
     };
 }
 
+macro_rules! PEER_LOSS_CHAOS_TEMPLATE {
+    () => {
+        "chaos/peer_loss.yaml"
+    };
+}
+
+macro_rules! MEMORY_STRESS_CHAOS_TEMPLATE {
+    () => {
+        "chaos/memory_stress.yaml"
+    };
+}
+
+macro_rules! TIME_SKEW_CHAOS_TEMPLATE {
+    () => {
+        "chaos/time_skew.yaml"
+    };
+}
+
+macro_rules! DNS_FAILURE_CHAOS_TEMPLATE {
+    () => {
+        "chaos/dns_failure.yaml"
+    };
+}
+
 macro_rules! NETEM_CHAOS_TEMPLATE {
     () => {
         "chaos/netem.yaml"
@@ -47,6 +90,9 @@ macro_rules! CPU_STRESS_CHAOS_TEMPLATE {
 
 // The node name for an address that could not be found in the swarm
 const INVALID_NODE_STRING: &str = "invalid-node";
+// The discard port, which nothing in the pods listens on
+const PROBE_PORT: u16 = 9;
+const PROBE_COUNT: u64 = 5;
 
 impl K8sSwarm {
     /// Injects the SwarmChaos into the specified namespace
@@ -72,7 +118,9 @@ impl K8sSwarm {
                         &group.name,
                     ];
                     info!("{:?}", delete_networkchaos);
-                    let delete_networkchaos_output = Command::new(KUBECTL_BIN)
+                    let delete_networkchaos_output = self
+                        .backend_config()
+                        .kubectl()
                         .stdout(Stdio::inherit())
                         .args(delete_networkchaos)
                         .output()
@@ -93,6 +141,18 @@ impl K8sSwarm {
         }
     }
 
+    /// Replaces an injected delay with the given one of the same groups, e.g. to change the
+    /// latency mid-test. The NetworkChaos are updated in place, so the delay never lapses.
+    pub fn update_swarm_chaos(&self, injected: &SwarmChaos, chaos: &SwarmChaos) -> Result<()> {
+        if !updates_in_place(injected, chaos) {
+            bail!("{:?} can't be updated to {:?} in place", injected, chaos);
+        }
+        let template = self.create_chaos_template(chaos)?;
+        info!("Updating chaos: {}", template);
+        // applying the NetworkChaos of the same names updates them
+        self.inject_chaos_template(template)
+    }
+
     fn create_network_delay_template(
         &self,
         swarm_network_delay: &SwarmNetworkDelay,
@@ -112,6 +172,7 @@ impl K8sSwarm {
                 latency_ms = group_network_delay.latency_ms,
                 jitter_ms = group_network_delay.jitter_ms,
                 correlation_percentage = group_network_delay.correlation_percentage,
+                direction = group_network_delay.direction,
                 instance_labels = &source_instance_labels,
                 target_instance_labels = &target_instance_labels,
             ));
@@ -126,10 +187,118 @@ impl K8sSwarm {
         Ok(format!(
             include_str!(PARTITION_NETWORK_CHAOS_TEMPLATE!()),
             namespace = self.kube_namespace,
-            partition_percentage = swarm_network_partition.partition_percentage
+            partition_percentage = swarm_network_partition.partition_percentage,
+            direction = partition_direction(swarm_network_partition.direction),
+        ))
+    }
+
+    fn create_group_partition_template(
+        &self,
+        swarm_group_partition: &SwarmGroupPartition,
+    ) -> Result<String> {
+        let group_a = &swarm_group_partition.group_a;
+        let group_b = &swarm_group_partition.group_b;
+        if group_a.is_empty() || group_b.is_empty() {
+            bail!(
+                "Both groups of a partition need nodes: {}",
+                swarm_group_partition
+            );
+        }
+        if let Some(peer_id) = group_a.iter().find(|peer_id| group_b.contains(peer_id)) {
+            bail!("Node {} is on both sides of the partition", peer_id);
+        }
+        Ok(format_group_partition(
+            &self.kube_namespace,
+            &chaos_name("forge-group-partition", swarm_group_partition),
+            &self.pod_instance_labels(group_a)?,
+            &self.pod_instance_labels(group_b)?,
+            swarm_group_partition.direction,
         ))
     }
 
+    /// Checks that an injected partition drops the packets its direction says, and only those, by
+    /// probing between the pods of a node of each group. The probes are UDP datagrams to a closed
+    /// port, which the receiving kernel counts, so unlike a connection they tell the directions
+    /// apart.
+    pub(crate) async fn verify_group_partition(
+        &self,
+        swarm_group_partition: &SwarmGroupPartition,
+    ) -> Result<()> {
+        let node_a = self.chaos_target(&swarm_group_partition.group_a[0])?;
+        let node_b = self.chaos_target(&swarm_group_partition.group_b[0])?;
+        let reachable = (
+            probe_reaches(node_a, node_b).await?,
+            probe_reaches(node_b, node_a).await?,
+        );
+        let expected = expected_reachability(swarm_group_partition.direction);
+        if reachable != expected {
+            bail!(
+                "Partition {} is not in effect: {} {} reach {}, and {} {} reach {}",
+                swarm_group_partition,
+                node_a.name(),
+                if reachable.0 { "can" } else { "can't" },
+                node_b.name(),
+                node_b.name(),
+                if reachable.1 { "can" } else { "can't" },
+                node_a.name()
+            );
+        }
+        info!(
+            "Verified partition {} between {} and {}",
+            swarm_group_partition.direction,
+            node_a.name(),
+            node_b.name()
+        );
+        Ok(())
+    }
+
+    /// The instance labels of the pods the given nodes' traffic goes through: their own, and
+    /// those of the HAProxies in front of them, which relay the validator network. Unlike for the
+    /// other chaos, a node that is missing from the swarm is an error.
+    fn pod_instance_labels(&self, peers: &[PeerId]) -> Result<Vec<String>> {
+        let mut labels = vec![];
+        for peer_id in peers {
+            let node = self.chaos_target(peer_id)?;
+            labels.push(node.name().to_string());
+            // only the helm nodes have an HAProxy, one per index
+            let haproxy = format!("haproxy-{}", node.index);
+            if node.haproxy_enabled
+                && node
+                    .stateful_set_name()
+                    .starts_with(APTOS_NODE_HELM_RELEASE_NAME)
+                && !labels.contains(&haproxy)
+            {
+                labels.push(haproxy);
+            }
+        }
+        Ok(labels)
+    }
+
+    /// The instance labels of the given nodes' own pods
+    fn node_instance_labels(&self, peers: &[PeerId]) -> Result<Vec<String>> {
+        peers
+            .iter()
+            .map(|peer_id| Ok(self.chaos_target(peer_id)?.name().to_string()))
+            .collect()
+    }
+
+    /// The node to inject chaos into, which must have a pod of its own to be targeted
+    fn chaos_target(&self, peer_id: &PeerId) -> Result<&K8sNode> {
+        let node = match self.k8s_node(peer_id) {
+            Some(node) => node,
+            None => bail!("Node {} to inject chaos into is not in the swarm", peer_id),
+        };
+        // the replicas of a StatefulSet share its pod labels
+        if node.shares_stateful_set {
+            bail!(
+                "Chaos can't target {} apart from the other replicas of {}",
+                node.name(),
+                node.stateful_set_name()
+            );
+        }
+        Ok(node)
+    }
+
     fn create_network_bandwidth_template(
         &self,
         swarm_network_bandwidth: &SwarmNetworkBandwidth,
@@ -149,6 +318,53 @@ impl K8sSwarm {
         Ok(network_chaos_specs.join("\n---\n"))
     }
 
+    /// One NetworkChaos throttles the traffic from the peers to the rest of the swarm, and another
+    /// the traffic back, as tc only shapes the traffic a pod sends
+    fn create_peer_bandwidth_template(
+        &self,
+        swarm_peer_bandwidth: &SwarmPeerBandwidth,
+    ) -> Result<String> {
+        let peers = &swarm_peer_bandwidth.peers;
+        if peers.is_empty() {
+            bail!("No nodes to throttle: {}", swarm_peer_bandwidth);
+        }
+        let others: Vec<PeerId> = self
+            .validators()
+            .map(|v| v.peer_id())
+            .chain(self.full_nodes().map(|f| f.peer_id()))
+            .filter(|peer_id| !peers.contains(peer_id))
+            .collect();
+        if others.is_empty() {
+            bail!(
+                "No other nodes to throttle the links to: {}",
+                swarm_peer_bandwidth
+            );
+        }
+        let peer_labels = self.pod_instance_labels(peers)?.join(",");
+        let other_labels = self.pod_instance_labels(&others)?.join(",");
+
+        let name = chaos_name("forge-peer-bandwidth", swarm_peer_bandwidth);
+        let network_chaos_specs = [
+            (format!("{}-egress", name), &peer_labels, &other_labels),
+            (format!("{}-ingress", name), &other_labels, &peer_labels),
+        ]
+        .iter()
+        .map(|(name, instance_labels, target_instance_labels)| {
+            format!(
+                include_str!(PEER_BANDWIDTH_NETWORK_CHAOS_TEMPLATE!()),
+                name = name,
+                namespace = self.kube_namespace,
+                rate_kbps = swarm_peer_bandwidth.rate_kbps,
+                limit_bytes = swarm_peer_bandwidth.limit_bytes,
+                buffer_bytes = swarm_peer_bandwidth.buffer_bytes,
+                instance_labels = instance_labels,
+                target_instance_labels = target_instance_labels,
+            )
+        })
+        .collect::<Vec<_>>();
+        Ok(network_chaos_specs.join("\n---\n"))
+    }
+
     fn create_network_loss_template(
         &self,
         swarm_network_loss: &SwarmNetworkLoss,
@@ -158,6 +374,27 @@ impl K8sSwarm {
             namespace = self.kube_namespace,
             loss_percentage = swarm_network_loss.loss_percentage,
             correlation_percentage = swarm_network_loss.correlation_percentage,
+            direction = partition_direction(swarm_network_loss.direction),
+        ))
+    }
+
+    fn create_peer_loss_template(&self, swarm_peer_loss: &SwarmPeerLoss) -> Result<String> {
+        if swarm_peer_loss.peers.is_empty() {
+            bail!("No nodes to drop packets of: {}", swarm_peer_loss);
+        }
+        if swarm_peer_loss.loss_percentage > 100 || swarm_peer_loss.correlation_percentage > 100 {
+            bail!(
+                "Percentages of a loss must be at most 100: {}",
+                swarm_peer_loss
+            );
+        }
+        Ok(format!(
+            include_str!(PEER_LOSS_CHAOS_TEMPLATE!()),
+            name = chaos_name("forge-peer-loss", swarm_peer_loss),
+            namespace = self.kube_namespace,
+            loss_percentage = swarm_peer_loss.loss_percentage,
+            correlation_percentage = swarm_peer_loss.correlation_percentage,
+            instance_labels = self.pod_instance_labels(&swarm_peer_loss.peers)?.join(","),
         ))
     }
 
@@ -211,14 +448,96 @@ impl K8sSwarm {
         Ok(cpu_stress_specs.join("\n---\n"))
     }
 
+    fn create_peer_cpu_stress_template(
+        &self,
+        swarm_peer_cpu_stress: &SwarmPeerCpuStress,
+    ) -> Result<String> {
+        if swarm_peer_cpu_stress.peers.is_empty() {
+            bail!("No nodes to stress: {}", swarm_peer_cpu_stress);
+        }
+        if swarm_peer_cpu_stress.load_percent > 100 {
+            bail!(
+                "Load of a CPU stress must be at most 100%: {}",
+                swarm_peer_cpu_stress
+            );
+        }
+        Ok(format!(
+            include_str!(CPU_STRESS_CHAOS_TEMPLATE!()),
+            name = chaos_name("forge-peer-cpu-stress", swarm_peer_cpu_stress),
+            namespace = self.kube_namespace,
+            num_workers = swarm_peer_cpu_stress.workers,
+            load_per_worker = swarm_peer_cpu_stress.load_percent,
+            instance_labels = self
+                .node_instance_labels(&swarm_peer_cpu_stress.peers)?
+                .join(","),
+        ))
+    }
+
+    fn create_peer_memory_stress_template(
+        &self,
+        swarm_peer_memory_stress: &SwarmPeerMemoryStress,
+    ) -> Result<String> {
+        if swarm_peer_memory_stress.peers.is_empty() {
+            bail!("No nodes to stress: {}", swarm_peer_memory_stress);
+        }
+        Ok(format!(
+            include_str!(MEMORY_STRESS_CHAOS_TEMPLATE!()),
+            name = chaos_name("forge-peer-memory-stress", swarm_peer_memory_stress),
+            namespace = self.kube_namespace,
+            size_mb = swarm_peer_memory_stress.size_mb,
+            instance_labels = self
+                .node_instance_labels(&swarm_peer_memory_stress.peers)?
+                .join(","),
+        ))
+    }
+
+    /// Only the nodes' own pods are skewed, not the HAProxies in front of them
+    fn create_time_skew_template(&self, swarm_time_skew: &SwarmTimeSkew) -> Result<String> {
+        if swarm_time_skew.peers.is_empty() {
+            bail!("No nodes to skew the clock of: {}", swarm_time_skew);
+        }
+        let instance_labels = self.node_instance_labels(&swarm_time_skew.peers)?;
+        Ok(format!(
+            include_str!(TIME_SKEW_CHAOS_TEMPLATE!()),
+            name = chaos_name("forge-time-skew", swarm_time_skew),
+            namespace = self.kube_namespace,
+            time_offset = format_time_offset(swarm_time_skew.offset, swarm_time_skew.direction),
+            instance_labels = instance_labels.join(","),
+        ))
+    }
+
+    /// Only the nodes' own pods fail their lookups, not the HAProxies in front of them
+    fn create_dns_failure_template(&self, swarm_dns_failure: &SwarmDnsFailure) -> Result<String> {
+        if swarm_dns_failure.peers.is_empty() {
+            bail!("No nodes to fail the DNS lookups of: {}", swarm_dns_failure);
+        }
+        Ok(format!(
+            include_str!(DNS_FAILURE_CHAOS_TEMPLATE!()),
+            name = chaos_name("forge-dns-failure", swarm_dns_failure),
+            namespace = self.kube_namespace,
+            action = swarm_dns_failure.action,
+            instance_labels = self
+                .node_instance_labels(&swarm_dns_failure.peers)?
+                .join(","),
+            patterns = format_dns_patterns(&swarm_dns_failure.patterns)?,
+        ))
+    }
+
     fn create_chaos_template(&self, chaos: &SwarmChaos) -> Result<String> {
         match chaos {
             SwarmChaos::Delay(c) => self.create_network_delay_template(c),
             SwarmChaos::Partition(c) => self.create_network_partition_template(c),
+            SwarmChaos::GroupPartition(c) => self.create_group_partition_template(c),
             SwarmChaos::Bandwidth(c) => self.create_network_bandwidth_template(c),
+            SwarmChaos::PeerBandwidth(c) => self.create_peer_bandwidth_template(c),
             SwarmChaos::Loss(c) => self.create_network_loss_template(c),
+            SwarmChaos::PeerLoss(c) => self.create_peer_loss_template(c),
             SwarmChaos::NetEm(c) => self.create_netem_template(c),
             SwarmChaos::CpuStress(c) => self.create_cpu_stress_template(c),
+            SwarmChaos::PeerCpuStress(c) => self.create_peer_cpu_stress_template(c),
+            SwarmChaos::PeerMemoryStress(c) => self.create_peer_memory_stress_template(c),
+            SwarmChaos::TimeSkew(c) => self.create_time_skew_template(c),
+            SwarmChaos::DnsFailure(c) => self.create_dns_failure_template(c),
         }
     }
 
@@ -230,7 +549,8 @@ impl K8sSwarm {
             chaos_template,
             &tmp_dir,
         )?;
-        Command::new(KUBECTL_BIN)
+        self.backend_config()
+            .kubectl()
             .args([
                 "-n",
                 &self.kube_namespace,
@@ -252,7 +572,8 @@ impl K8sSwarm {
             chaos_template,
             &tmp_dir,
         )?;
-        Command::new(KUBECTL_BIN)
+        self.backend_config()
+            .kubectl()
             .args([
                 "-n",
                 &self.kube_namespace,
@@ -307,3 +628,255 @@ impl K8sSwarm {
             .join(",")
     }
 }
+
+/// Whether the injected chaos can be updated to the given one in place, which only delays of the
+/// same groups can: each group is a NetworkChaos of its own name.
+pub(crate) fn updates_in_place(injected: &SwarmChaos, chaos: &SwarmChaos) -> bool {
+    match (injected, chaos) {
+        (SwarmChaos::Delay(injected), SwarmChaos::Delay(delay)) => {
+            let names = |delay: &SwarmNetworkDelay| {
+                delay
+                    .group_network_delays
+                    .iter()
+                    .map(|group| group.name.clone())
+                    .collect::<Vec<_>>()
+            };
+            names(injected) == names(delay)
+        },
+        _ => false,
+    }
+}
+
+/// Whether the given chaos can't be injected along with the injected one. Two losses on the same
+/// node would both be merged into its root qdisc, and only one of them would take effect. Likewise,
+/// a clock can only be skewed by one offset at a time.
+pub(crate) fn conflicts(injected: &SwarmChaos, chaos: &SwarmChaos) -> bool {
+    let overlap = |a: &[PeerId], b: &[PeerId]| a.iter().any(|peer_id| b.contains(peer_id));
+    match (injected, chaos) {
+        (SwarmChaos::PeerLoss(injected), SwarmChaos::PeerLoss(loss)) => {
+            overlap(&injected.peers, &loss.peers)
+        },
+        (SwarmChaos::TimeSkew(injected), SwarmChaos::TimeSkew(skew)) => {
+            overlap(&injected.peers, &skew.peers)
+        },
+        _ => false,
+    }
+}
+
+/// The offset of a TimeChaos, as a Go duration
+fn format_time_offset(offset: Duration, direction: SkewDirection) -> String {
+    let sign = match direction {
+        SkewDirection::Ahead => "",
+        SkewDirection::Behind => "-",
+    };
+    format!("{}{}ms", sign, offset.as_millis())
+}
+
+/// The patterns of a DNSChaos, quoted as a `*` would start a YAML alias. Chaos Mesh only takes
+/// domains with wildcards.
+fn format_dns_patterns(patterns: &[String]) -> Result<String> {
+    let mut quoted = vec![];
+    for pattern in patterns {
+        let valid = !pattern.is_empty()
+            && pattern
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | '*' | '?'));
+        if !valid {
+            bail!("Invalid DNS pattern: {:?}", pattern);
+        }
+        quoted.push(format!("\"{}\"", pattern));
+    }
+    Ok(quoted.join(", "))
+}
+
+/// The name of a NetworkChaos for the given chaos, the same for the same chaos so that it can be
+/// removed again
+fn chaos_name(prefix: &str, chaos: impl Hash) -> String {
+    let mut hasher = DefaultHasher::new();
+    chaos.hash(&mut hasher);
+    format!("{}-{:x}", prefix, hasher.finish())
+}
+
+fn format_group_partition(
+    namespace: &str,
+    name: &str,
+    group_a_instance_labels: &[String],
+    group_b_instance_labels: &[String],
+    direction: PartitionDirection,
+) -> String {
+    format!(
+        include_str!(GROUP_PARTITION_NETWORK_CHAOS_TEMPLATE!()),
+        namespace = namespace,
+        name = name,
+        group_a_instance_labels = group_a_instance_labels.join(","),
+        group_b_instance_labels = group_b_instance_labels.join(","),
+        direction = partition_direction(direction),
+    )
+}
+
+/// The Chaos Mesh direction of a partition or loss whose selector is group A and target group B
+fn partition_direction(direction: PartitionDirection) -> &'static str {
+    match direction {
+        PartitionDirection::Both => "both",
+        PartitionDirection::FromAToB => "to",
+        PartitionDirection::FromBToA => "from",
+    }
+}
+
+/// Whether group A reaches group B, and group B group A, across a partition
+fn expected_reachability(direction: PartitionDirection) -> (bool, bool) {
+    match direction {
+        PartitionDirection::Both => (false, false),
+        PartitionDirection::FromAToB => (false, true),
+        PartitionDirection::FromBToA => (true, false),
+    }
+}
+
+/// Whether UDP probes sent from inside the pod of one node arrive in the pod of the other
+async fn probe_reaches(from: &K8sNode, to: &K8sNode) -> Result<bool> {
+    let ip = to.pod_ip().await?;
+    let before = to.udp_no_ports().await?;
+    from.send_udp_probes(&ip, PROBE_PORT, PROBE_COUNT).await?;
+    // the datagrams are counted as they arrive
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    let after = to.udp_no_ports().await?;
+    // other datagrams to closed ports may be counted too, but hardly as many
+    Ok(after.saturating_sub(before) >= PROBE_COUNT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{GroupNetworkDelay, NetworkDelayDirection};
+
+    fn delay(name: &str, latency_ms: u64) -> SwarmChaos {
+        SwarmChaos::Delay(SwarmNetworkDelay {
+            group_network_delays: vec![GroupNetworkDelay {
+                name: name.to_string(),
+                source_nodes: vec![PeerId::random()],
+                target_nodes: vec![PeerId::random()],
+                latency_ms,
+                jitter_ms: 10,
+                correlation_percentage: 50,
+                direction: NetworkDelayDirection::To,
+            }],
+        })
+    }
+
+    #[test]
+    fn test_updates_in_place() {
+        assert!(updates_in_place(
+            &delay("west-to-east", 150),
+            &delay("west-to-east", 300)
+        ));
+        assert!(!updates_in_place(
+            &delay("west-to-east", 150),
+            &delay("east-to-west", 150)
+        ));
+        assert!(!updates_in_place(
+            &delay("west-to-east", 150),
+            &SwarmChaos::Loss(SwarmNetworkLoss {
+                loss_percentage: 10,
+                correlation_percentage: 0,
+                direction: PartitionDirection::Both,
+            })
+        ));
+    }
+
+    #[test]
+    fn test_conflicts() {
+        let peers: Vec<_> = (0..3).map(|_| PeerId::random()).collect();
+        let loss = |peers: &[PeerId]| {
+            SwarmChaos::PeerLoss(SwarmPeerLoss {
+                peers: peers.to_vec(),
+                loss_percentage: 5,
+                correlation_percentage: 25,
+            })
+        };
+        assert!(conflicts(&loss(&peers[..2]), &loss(&peers[1..])));
+        assert!(!conflicts(&loss(&peers[..1]), &loss(&peers[1..])));
+        // a loss composes with a delay of the same nodes
+        assert!(!conflicts(&delay("west-to-east", 150), &loss(&peers)));
+    }
+
+    #[test]
+    fn test_format_time_offset() {
+        assert_eq!(
+            format_time_offset(Duration::from_secs(30), SkewDirection::Ahead),
+            "30000ms"
+        );
+        assert_eq!(
+            format_time_offset(Duration::from_millis(1500), SkewDirection::Behind),
+            "-1500ms"
+        );
+    }
+
+    #[test]
+    fn test_format_dns_patterns() {
+        let patterns = vec!["*.aptoslabs.com".to_string(), "telemetr?".to_string()];
+        assert_eq!(
+            format_dns_patterns(&patterns).unwrap(),
+            r#""*.aptoslabs.com", "telemetr?""#
+        );
+        assert_eq!(format_dns_patterns(&[]).unwrap(), "");
+        assert!(format_dns_patterns(&["evil\", injected".to_string()]).is_err());
+        assert!(format_dns_patterns(&["".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_format_group_partition() {
+        let partition = SwarmGroupPartition {
+            group_a: vec![PeerId::random()],
+            group_b: vec![PeerId::random(), PeerId::random()],
+            direction: PartitionDirection::Both,
+        };
+        assert_eq!(
+            chaos_name("forge-group-partition", &partition),
+            chaos_name("forge-group-partition", &partition.clone())
+        );
+
+        let template = format_group_partition(
+            "forge-test",
+            "forge-group-partition-1",
+            &["validator-0".to_string(), "haproxy-0".to_string()],
+            &["validator-1".to_string(), "validator-2".to_string()],
+            PartitionDirection::Both,
+        );
+        let value: serde_yaml::Value = serde_yaml::from_str(&template).unwrap();
+        assert_eq!(value["spec"]["action"], "partition");
+        assert_eq!(value["spec"]["direction"], "both");
+        let source = &value["spec"]["selector"]["expressionSelectors"][0]["values"];
+        assert_eq!(source[0], "validator-0");
+        assert_eq!(source[1], "haproxy-0");
+        let target = &value["spec"]["target"]["selector"]["expressionSelectors"][0]["values"];
+        assert_eq!(target[1], "validator-2");
+    }
+
+    #[test]
+    fn test_one_way_partition() {
+        // group A is the selector, so dropping what it sends is "to"
+        let template = format_group_partition(
+            "forge-test",
+            "forge-group-partition-1",
+            &["validator-0".to_string()],
+            &["validator-1".to_string()],
+            PartitionDirection::FromAToB,
+        );
+        let value: serde_yaml::Value = serde_yaml::from_str(&template).unwrap();
+        assert_eq!(value["spec"]["direction"], "to");
+        assert_eq!(partition_direction(PartitionDirection::FromBToA), "from");
+
+        assert_eq!(
+            expected_reachability(PartitionDirection::FromAToB),
+            (false, true)
+        );
+        assert_eq!(
+            expected_reachability(PartitionDirection::FromBToA),
+            (true, false)
+        );
+        assert_eq!(
+            expected_reachability(PartitionDirection::Both),
+            (false, false)
+        );
+    }
+}
@@ -7,6 +7,8 @@ use serde::{Deserialize, Serialize};
 pub enum Chaos {
     Network(NetworkChaos),
     Stress(StressChaos),
+    Time(TimeChaos),
+    Dns(DNSChaos),
 }
 
 #[derive(CustomResource, Deserialize, Default, Serialize, Clone, Debug)]
@@ -33,6 +35,30 @@ pub struct NetworkChaosSpec {}
 )]
 pub struct StressChaosSpec {}
 
+#[derive(CustomResource, Default, Serialize, Deserialize, Clone, Debug)]
+#[kube(
+    group = "chaos-mesh.org",
+    version = "v1alpha1",
+    kind = "TimeChaos",
+    status = "ChaosStatus",
+    plural = "timechaos",
+    namespaced,
+    schema = "disabled"
+)]
+pub struct TimeChaosSpec {}
+
+#[derive(CustomResource, Default, Serialize, Deserialize, Clone, Debug)]
+#[kube(
+    group = "chaos-mesh.org",
+    version = "v1alpha1",
+    kind = "DNSChaos",
+    status = "ChaosStatus",
+    plural = "dnschaos",
+    namespaced,
+    schema = "disabled"
+)]
+pub struct DNSChaosSpec {}
+
 #[derive(Deserialize, Serialize, Clone, Debug)]
 pub struct ChaosStatus {
     #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -4,35 +4,37 @@
 
 use crate::{
     get_fullnodes, get_validators, k8s_wait_genesis_strategy, k8s_wait_nodes_strategy,
-    nodes_healthcheck, wait_stateful_set, ForgeRunnerMode, GenesisConfigFn, K8sApi, K8sNode,
-    NodeConfigFn, ReadWrite, Result, APTOS_NODE_HELM_CHART_PATH, APTOS_NODE_HELM_RELEASE_NAME,
-    DEFAULT_ROOT_KEY, DEFAULT_TEST_SUITE_NAME, DEFAULT_USERNAME, FORGE_KEY_SEED,
+    nodes_healthcheck, set_stateful_set_image_tag, wait_stateful_set, ForgeRunnerMode,
+    GenesisConfigFn, InfraFailure, K8sApi, K8sBackendConfig, K8sError, K8sNode, Node, NodeConfigFn,
+    NodeVersions, ReadWrite, RestApiTls, Result, APTOS_NODE_HELM_CHART_PATH,
+    APTOS_NODE_HELM_RELEASE_NAME, DEFAULT_ROOT_KEY, DEFAULT_RUN_ID, DEFAULT_TEST_SUITE_NAME,
+    DEFAULT_USERNAME, FORGE_ERA_LABEL, FORGE_KEY_SEED, FORGE_NAMESPACE_CREATED_AT_LABEL,
+    FORGE_NAMESPACE_MARKER_LABEL, FORGE_NAMESPACE_RUN_ID_LABEL, FORGE_NAMESPACE_TTL_LABEL,
     FULLNODE_HAPROXY_SERVICE_SUFFIX, FULLNODE_SERVICE_SUFFIX, GENESIS_HELM_CHART_PATH,
-    GENESIS_HELM_RELEASE_NAME, HELM_BIN, KUBECTL_BIN, MANAGEMENT_CONFIGMAP_PREFIX,
-    NAMESPACE_CLEANUP_THRESHOLD_SECS, POD_CLEANUP_THRESHOLD_SECS, VALIDATOR_HAPROXY_SERVICE_SUFFIX,
-    VALIDATOR_SERVICE_SUFFIX,
+    GENESIS_HELM_RELEASE_NAME, MANAGEMENT_CONFIGMAP_PREFIX, NAMESPACE_CLEANUP_THRESHOLD_SECS,
+    POD_CLEANUP_THRESHOLD_SECS, VALIDATOR_HAPROXY_SERVICE_SUFFIX, VALIDATOR_SERVICE_SUFFIX,
 };
 use again::RetryPolicy;
-use anyhow::{anyhow, bail, format_err};
-use aptos_logger::info;
+use anyhow::{anyhow, bail, format_err, Context};
+use aptos_logger::{info, warn};
 use aptos_sdk::types::PeerId;
+use futures::future::try_join_all;
 use k8s_openapi::api::{
     apps::v1::{Deployment, StatefulSet},
     batch::v1::Job,
-    core::v1::{ConfigMap, Namespace, PersistentVolume, PersistentVolumeClaim, Pod},
+    core::v1::{ConfigMap, Namespace, PersistentVolume, PersistentVolumeClaim, Pod, Secret},
 };
 use kube::{
     api::{Api, DeleteParams, ListParams, ObjectMeta, Patch, PatchParams, PostParams},
     client::Client as K8sClient,
-    config::{KubeConfigOptions, Kubeconfig},
-    Config, Error as KubeError, ResourceExt,
+    Error as KubeError, ResourceExt,
 };
+use once_cell::sync::Lazy;
 use rand::Rng;
 use serde::de::DeserializeOwned;
 use serde_json::Value;
 use std::{
-    collections::{BTreeMap, HashMap},
-    convert::TryFrom,
+    collections::{BTreeMap, HashMap, HashSet},
     env,
     fmt::Debug,
     fs,
@@ -40,37 +42,94 @@ use std::{
     io::Write,
     net::TcpListener,
     path::Path,
-    process::{Command, Stdio},
+    process::Stdio,
     str,
-    sync::Arc,
+    sync::{Arc, Mutex},
     time::{SystemTime, UNIX_EPOCH},
 };
 use tempfile::TempDir;
 use thiserror::Error;
 use tokio::time::Duration;
 
-/// Gets a free port
-pub fn get_free_port() -> u32 {
-    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
-    listener.local_addr().unwrap().port() as u32
+// how many times to ask the OS for a port that this process has not reserved already
+const MAX_PORT_RESERVATION_ATTEMPTS: usize = 100;
+
+// the ports handed out by get_free_port whose ReservedPort is still alive
+static RESERVED_PORTS: Lazy<Mutex<HashSet<u32>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+/// A free local port, reserved until dropped. The port stays bound until it is released, so that
+/// the OS can't hand it out again. After that, no other reservation in this process gets it.
+#[derive(Debug)]
+pub struct ReservedPort {
+    port: u32,
+    listener: Option<TcpListener>,
+}
+
+impl ReservedPort {
+    pub fn port(&self) -> u32 {
+        self.port
+    }
+
+    /// Hand over the listener the port is bound with, e.g. to serve a port-forward on. Dropping it
+    /// unbinds the port, which stays reserved in this process.
+    pub fn release(&mut self) -> Option<TcpListener> {
+        self.listener.take()
+    }
+}
+
+impl Drop for ReservedPort {
+    fn drop(&mut self) {
+        RESERVED_PORTS.lock().unwrap().remove(&self.port);
+    }
+}
+
+/// Gets a free port, and reserves it until the returned guard is dropped
+pub fn get_free_port() -> Result<ReservedPort> {
+    for _ in 0..MAX_PORT_RESERVATION_ATTEMPTS {
+        let listener =
+            TcpListener::bind("127.0.0.1:0").context("Failed to bind a free local port")?;
+        let port = listener.local_addr()?.port() as u32;
+        // a released port is free as far as the OS knows, until its consumer binds it
+        if RESERVED_PORTS.lock().unwrap().insert(port) {
+            return Ok(ReservedPort {
+                port,
+                listener: Some(listener),
+            });
+        }
+    }
+    bail!(
+        "Could not reserve a free local port after {} attempts",
+        MAX_PORT_RESERVATION_ATTEMPTS
+    )
 }
 
 /// Waits for the testnet's genesis job to complete, while tailing the job's logs
-async fn wait_genesis_job(kube_client: &K8sClient, era: &str, kube_namespace: &str) -> Result<()> {
+async fn wait_genesis_job(
+    config: &K8sBackendConfig,
+    kube_client: &K8sClient,
+    era: &str,
+    kube_namespace: &str,
+) -> Result<()> {
     aptos_retrier::retry_async(k8s_wait_genesis_strategy(), || {
         let jobs: Api<Job> = Api::namespaced(kube_client.clone(), kube_namespace);
         Box::pin(async move {
             let job_name = format!("{}-aptos-genesis-e{}", GENESIS_HELM_RELEASE_NAME, era);
 
-            let genesis_job = jobs.get_status(&job_name).await.unwrap();
+            let genesis_job = jobs
+                .get_status(&job_name)
+                .await
+                .map_err(|e| K8sError::from_kube(format!("Job {}", job_name), e))?;
 
-            let status = genesis_job.status.unwrap();
+            let status = genesis_job
+                .status
+                .with_context(|| format!("Job {} has no status", job_name))?;
             info!("Genesis status: {:?}", status);
             match status.active {
                 Some(_) => {
                     // try tailing the logs of the genesis job
                     // by the time this is done, we can re-evalulate its status
-                    Command::new(KUBECTL_BIN)
+                    config
+                        .kubectl_async()
                         .args([
                             "-n",
                             kube_namespace,
@@ -79,7 +138,8 @@ async fn wait_genesis_job(kube_client: &K8sClient, era: &str, kube_namespace: &s
                             format!("job/{}", &job_name).as_str(),
                         ])
                         .status()
-                        .expect("Failed to tail genesis logs");
+                        .await
+                        .context("Failed to tail genesis logs")?;
                 },
                 None => info!("Genesis completed running"),
             }
@@ -123,11 +183,11 @@ async fn wait_node_haproxy(
                             }
                         }
                         info!("Deployment {} has no status", deployment_name);
-                        bail!("Deployment not ready");
+                        bail!(InfraFailure("Deployment not ready".to_string()));
                     },
                     Err(e) => {
                         info!("Failed to get deployment: {}", e);
-                        bail!("Failed to get deployment: {}", e);
+                        bail!(InfraFailure(format!("Failed to get deployment: {}", e)));
                     },
                 }
             }
@@ -148,14 +208,22 @@ async fn wait_nodes_stateful_set(
     for node in nodes.values() {
         // retry every 10 seconds for 20 minutes
         let retry_policy = RetryPolicy::fixed(Duration::from_secs(10)).with_max_retries(120);
-        wait_stateful_set(
+        if let Err(e) = wait_stateful_set(
             kube_client,
             kube_namespace,
             node.stateful_set_name(),
             1,
             retry_policy,
         )
-        .await?
+        .await
+        {
+            // e.g. FailedScheduling says which of the scheduling constraints no k8s node meets
+            return Err(e.context(format!(
+                "{} did not come up. {}",
+                node.name(),
+                node.describe_warning_events().await
+            )));
+        }
     }
     Ok(())
 }
@@ -190,7 +258,11 @@ where
 }
 
 /// Delete existing k8s resources in the namespace. This is essentially helm uninstall but lighter weight
-pub(crate) async fn delete_k8s_resources(client: K8sClient, kube_namespace: &str) -> Result<()> {
+pub(crate) async fn delete_k8s_resources(
+    config: &K8sBackendConfig,
+    client: K8sClient,
+    kube_namespace: &str,
+) -> Result<()> {
     // selector for the helm chart
     let aptos_node_helm_selector = "app.kubernetes.io/part-of=aptos-node";
     let testnet_addons_helm_selector = "app.kubernetes.io/part-of=testnet-addons";
@@ -225,34 +297,73 @@ pub(crate) async fn delete_k8s_resources(client: K8sClient, kube_namespace: &str
         // delete_k8s_collection(services.clone(), "Services", selector).await?;
     }
 
-    delete_all_chaos(kube_namespace)?;
+    delete_all_chaos(config, kube_namespace)?;
 
     Ok(())
 }
 
-pub(crate) fn delete_all_chaos(kube_namespace: &str) -> Result<()> {
+/// The selector of the resources forge labeled with another era than the given one. Those without
+/// the era label don't match, so that the sweep never deletes resources forge did not deploy.
+pub(crate) fn stale_era_selector(era: &str) -> String {
+    format!(
+        "{},{}!={}",
+        FORGE_ERA_LABEL,
+        FORGE_ERA_LABEL,
+        make_k8s_label(era.to_string())
+    )
+}
+
+/// Delete the volumes, ConfigMaps and Secrets of the previous eras of the namespace, so that the
+/// nodes of the new era never pick up their state and fail on a genesis mismatch
+pub(crate) async fn delete_stale_era_resources(
+    client: K8sClient,
+    kube_namespace: &str,
+    era: &str,
+) -> Result<()> {
+    let selector = stale_era_selector(era);
+    info!(
+        "Deleting the k8s resources of other eras than {} with selector: {}",
+        era, selector
+    );
+    let pvcs: Api<PersistentVolumeClaim> = Api::namespaced(client.clone(), kube_namespace);
+    let config_maps: Api<ConfigMap> = Api::namespaced(client.clone(), kube_namespace);
+    let secrets: Api<Secret> = Api::namespaced(client, kube_namespace);
+    delete_k8s_collection(pvcs, "PersistentVolumeClaims", &selector).await?;
+    delete_k8s_collection(config_maps, "ConfigMaps", &selector).await?;
+    delete_k8s_collection(secrets, "Secrets", &selector).await?;
+    Ok(())
+}
+
+/// Label the resource with the era, see [FORGE_ERA_LABEL]
+pub(crate) fn add_era_label(labels: &mut Option<BTreeMap<String, String>>, era: &str) {
+    labels
+        .get_or_insert_with(BTreeMap::new)
+        .insert(FORGE_ERA_LABEL.to_string(), make_k8s_label(era.to_string()));
+}
+
+pub(crate) fn delete_all_chaos(config: &K8sBackendConfig, kube_namespace: &str) -> Result<()> {
     // clear everything manually, in case there are some dangling
-    let delete_networkchaos = ["-n", kube_namespace, "delete", "networkchaos", "--all"];
-    info!("{:?}", delete_networkchaos);
-    let delete_networkchaos_output = Command::new(KUBECTL_BIN)
-        .stdout(Stdio::inherit())
-        .args(delete_networkchaos)
-        .output()
-        .expect("failed to delete all NetworkChaos");
-    if !delete_networkchaos_output.status.success() {
-        bail!(
-            "{}",
-            String::from_utf8(delete_networkchaos_output.stderr).unwrap()
-        );
+    for kind in ["networkchaos", "stresschaos", "timechaos", "dnschaos"] {
+        let delete_chaos = ["-n", kube_namespace, "delete", kind, "--all"];
+        info!("{:?}", delete_chaos);
+        let delete_chaos_output = config
+            .kubectl()
+            .stdout(Stdio::inherit())
+            .args(delete_chaos)
+            .output()
+            .with_context(|| format!("Failed to delete all {}", kind))?;
+        if !delete_chaos_output.status.success() {
+            bail!("{}", String::from_utf8(delete_chaos_output.stderr).unwrap());
+        }
     }
     Ok(())
 }
 
 /// Deletes all Forge resources from the given namespace. If the namespace is "default", delete the management configmap
-/// as well as all compute resources. If the namespace is a Forge namespace (has the "forge-*" prefix), then simply delete
+/// as well as all compute resources. If the namespace is a Forge namespace (has the configured prefix, "forge-*" by default), then simply delete
 /// the entire namespace
-async fn delete_k8s_cluster(kube_namespace: String) -> Result<()> {
-    let client: K8sClient = create_k8s_client().await?;
+async fn delete_k8s_cluster(config: &K8sBackendConfig, kube_namespace: String) -> Result<()> {
+    let client: K8sClient = config.create_client().await?;
 
     // if operating on the default namespace,
     match kube_namespace.as_str() {
@@ -282,9 +393,9 @@ async fn delete_k8s_cluster(kube_namespace: String) -> Result<()> {
                 },
                 Err(e) => bail!(e),
             };
-            delete_k8s_resources(client, "default").await?;
+            delete_k8s_resources(config, client, "default").await?;
         },
-        s if s.starts_with("forge") => {
+        s if config.is_forge_namespace(s) => {
             let namespaces: Api<Namespace> = Api::all(client);
             namespaces
                 .delete(&kube_namespace, &DeleteParams::default())
@@ -294,8 +405,9 @@ async fn delete_k8s_cluster(kube_namespace: String) -> Result<()> {
         },
         _ => {
             bail!(
-                "Invalid kubernetes namespace provided: {}. Use forge-*",
-                kube_namespace
+                "Invalid kubernetes namespace provided: {}. Use {}-*",
+                kube_namespace,
+                config.namespace_prefix
             );
         },
     }
@@ -304,6 +416,7 @@ async fn delete_k8s_cluster(kube_namespace: String) -> Result<()> {
 }
 
 fn upgrade_helm_release(
+    config: &K8sBackendConfig,
     release_name: String,
     helm_chart: String,
     options: &[String],
@@ -344,7 +457,8 @@ fn upgrade_helm_release(
     let upgrade_override_args = ["--set".to_string(), psp_values.to_string()];
     let upgrade_args = [&upgrade_base_args, options, &upgrade_override_args].concat();
     info!("{:?}", upgrade_args);
-    let upgrade_output = Command::new(HELM_BIN)
+    let upgrade_output = config
+        .helm()
         .stdout(Stdio::inherit())
         .args(&upgrade_args)
         .output()
@@ -355,10 +469,10 @@ fn upgrade_helm_release(
             )
         });
     if !upgrade_output.status.success() {
-        bail!(format!(
+        bail!(InfraFailure(format!(
             "Upgrade not completed: {}",
             String::from_utf8(upgrade_output.stderr).unwrap()
-        ));
+        )));
     }
 
     Ok(())
@@ -374,8 +488,13 @@ fn upgrade_validator(
     todo!()
 }
 
-fn upgrade_aptos_node_helm(options: &[String], kube_namespace: String) -> Result<()> {
+fn upgrade_aptos_node_helm(
+    config: &K8sBackendConfig,
+    options: &[String],
+    kube_namespace: String,
+) -> Result<()> {
     upgrade_helm_release(
+        config,
         APTOS_NODE_HELM_RELEASE_NAME.to_string(),
         APTOS_NODE_HELM_CHART_PATH.to_string(),
         options,
@@ -385,8 +504,13 @@ fn upgrade_aptos_node_helm(options: &[String], kube_namespace: String) -> Result
 
 // runs helm upgrade on the installed aptos-genesis release named "genesis"
 // if a new "era" is specified, a new genesis will be created, and old resources will be destroyed
-fn upgrade_genesis_helm(options: &[String], kube_namespace: String) -> Result<()> {
+fn upgrade_genesis_helm(
+    config: &K8sBackendConfig,
+    options: &[String],
+    kube_namespace: String,
+) -> Result<()> {
     upgrade_helm_release(
+        config,
         GENESIS_HELM_RELEASE_NAME.to_string(),
         GENESIS_HELM_CHART_PATH.to_string(),
         options,
@@ -394,9 +518,12 @@ fn upgrade_genesis_helm(options: &[String], kube_namespace: String) -> Result<()
     )
 }
 
-pub async fn uninstall_testnet_resources(kube_namespace: String) -> Result<()> {
+pub async fn uninstall_testnet_resources(
+    config: &K8sBackendConfig,
+    kube_namespace: String,
+) -> Result<()> {
     // delete kubernetes resources
-    delete_k8s_cluster(kube_namespace.clone()).await?;
+    delete_k8s_cluster(config, kube_namespace.clone()).await?;
     info!(
         "aptos-node resources for Forge removed in namespace: {}",
         kube_namespace
@@ -425,7 +552,8 @@ pub async fn reset_persistent_volumes(kube_client: &K8sClient) -> Result<()> {
     let pv_api: Api<PersistentVolume> = Api::all(kube_client.clone());
     let pvs = pv_api
         .list(&ListParams::default())
-        .await?
+        .await
+        .map_err(|e| K8sError::from_kube("PersistentVolumes", e))?
         .items
         .into_iter()
         .filter(|pv| {
@@ -450,7 +578,8 @@ pub async fn reset_persistent_volumes(kube_client: &K8sClient) -> Result<()> {
         });
         pv_api
             .patch(&name, &PatchParams::default(), &Patch::Merge(&patch))
-            .await?;
+            .await
+            .map_err(|e| K8sError::from_kube(format!("PersistentVolume {}", name), e))?;
     }
 
     Ok(())
@@ -466,7 +595,8 @@ pub async fn check_persistent_volumes(
     let list_params = ListParams::default();
     let pvs = pv_api
         .list(&list_params)
-        .await?
+        .await
+        .map_err(|e| K8sError::from_kube("PersistentVolumes", e))?
         .items
         .into_iter()
         .filter(|pv| {
@@ -503,6 +633,7 @@ pub async fn check_persistent_volumes(
 /// Installs a testnet in a k8s namespace by first running genesis, and the installing the aptos-nodes via helm
 /// Returns the current era, as well as a mapping of validators and fullnodes
 pub async fn install_testnet_resources(
+    config: &Arc<K8sBackendConfig>,
     kube_namespace: String,
     num_validators: usize,
     num_fullnodes: usize,
@@ -511,18 +642,23 @@ pub async fn install_testnet_resources(
     genesis_modules_path: Option<String>,
     use_port_forward: bool,
     enable_haproxy: bool,
+    rest_api_tls: Option<RestApiTls>,
     genesis_helm_config_fn: Option<GenesisConfigFn>,
     node_helm_config_fn: Option<NodeConfigFn>,
+    node_versions: Option<&NodeVersions>,
 ) -> Result<(String, HashMap<PeerId, K8sNode>, HashMap<PeerId, K8sNode>)> {
-    let kube_client = create_k8s_client().await?;
+    let kube_client = config.create_client().await?;
 
     // get deployment-specific helm values and cache it
     let tmp_dir = TempDir::new().expect("Could not create temp dir");
-    let aptos_node_values_file = dump_helm_values_to_file(APTOS_NODE_HELM_RELEASE_NAME, &tmp_dir)?;
-    let genesis_values_file = dump_helm_values_to_file(GENESIS_HELM_RELEASE_NAME, &tmp_dir)?;
+    let aptos_node_values_file =
+        dump_helm_values_to_file(config, APTOS_NODE_HELM_RELEASE_NAME, &tmp_dir)?;
+    let genesis_values_file =
+        dump_helm_values_to_file(config, GENESIS_HELM_RELEASE_NAME, &tmp_dir)?;
 
     // generate a random era to wipe the network state
     let new_era = generate_new_era();
+    delete_stale_era_resources(kube_client.clone(), &kube_namespace, &new_era).await?;
 
     // get forge override helm values and cache it
     let aptos_node_forge_helm_values_yaml = construct_node_helm_values(
@@ -533,7 +669,7 @@ pub async fn install_testnet_resources(
         new_era.clone(),
         num_validators,
         num_fullnodes,
-        node_image_tag,
+        node_image_tag.clone(),
         enable_haproxy,
     )?;
 
@@ -558,13 +694,33 @@ pub async fn install_testnet_resources(
     )?;
 
     // combine all helm values
-    let aptos_node_upgrade_options = vec![
+    let mut aptos_node_upgrade_options = vec![
         // use the old values
         "-f".to_string(),
         aptos_node_values_file,
         "-f".to_string(),
         aptos_node_forge_values_file,
     ];
+    // the chart only has a single validator image tag, so the validators on other versions are
+    // patched after the install. Helm must keep their images on later upgrades.
+    if let Some(node_versions) = node_versions {
+        aptos_node_upgrade_options.extend([
+            "--set".to_string(),
+            "manageImages=false".to_string(),
+            "--set".to_string(),
+            format!("fullnode.image.tag={}", node_versions.fullnodes),
+        ]);
+        if let Some(repo) = &node_versions.validator_image_repo {
+            aptos_node_upgrade_options.extend([
+                "--set".to_string(),
+                format!("validator.image.repo={}", repo),
+            ]);
+        }
+        if let Some(repo) = &node_versions.fullnode_image_repo {
+            aptos_node_upgrade_options
+                .extend(["--set".to_string(), format!("fullnode.image.repo={}", repo)]);
+        }
+    }
 
     let mut genesis_upgrade_options = vec![
         // use the old values
@@ -583,28 +739,74 @@ pub async fn install_testnet_resources(
     }
 
     // upgrade genesis
-    upgrade_genesis_helm(genesis_upgrade_options.as_slice(), kube_namespace.clone())?;
+    upgrade_genesis_helm(
+        config,
+        genesis_upgrade_options.as_slice(),
+        kube_namespace.clone(),
+    )?;
 
     // wait for genesis to run again, and get the updated validators
-    wait_genesis_job(&kube_client, &new_era, &kube_namespace).await?;
+    wait_genesis_job(config, &kube_client, &new_era, &kube_namespace).await?;
 
     // TODO(rustielin): get the helm releases to be consistent
     upgrade_aptos_node_helm(
+        config,
         aptos_node_upgrade_options.as_slice(),
         kube_namespace.clone(),
     )?;
 
+    if let Some(node_versions) = node_versions {
+        set_validator_image_tags(
+            config,
+            &kube_namespace,
+            num_validators,
+            &node_image_tag,
+            node_versions,
+        )
+        .await?;
+    }
+
     let (validators, fullnodes) = collect_running_nodes(
+        config,
         &kube_client,
         kube_namespace,
         use_port_forward,
         enable_haproxy,
+        rest_api_tls,
     )
     .await?;
 
     Ok((new_era.clone(), validators, fullnodes))
 }
 
+/// Set the image tag of each validator that starts on another version than the chart's. Their pods
+/// may come up on the chart's version first, but are replaced before the nodes are collected.
+async fn set_validator_image_tags(
+    config: &K8sBackendConfig,
+    kube_namespace: &str,
+    num_validators: usize,
+    chart_image_tag: &str,
+    node_versions: &NodeVersions,
+) -> Result<()> {
+    for index in 0..num_validators {
+        let image_tag = match node_versions.validator(index) {
+            Some(version) if version.to_string() != chart_image_tag => version.to_string(),
+            _ => continue,
+        };
+        let stateful_set_name = format!("{}-{}-validator", APTOS_NODE_HELM_RELEASE_NAME, index);
+        info!("Starting {} on version {}", stateful_set_name, image_tag);
+        set_stateful_set_image_tag(
+            config,
+            stateful_set_name,
+            "validator".to_string(),
+            image_tag,
+            kube_namespace.to_string(),
+        )
+        .await?;
+    }
+    Ok(())
+}
+
 pub fn construct_node_helm_values(
     node_helm_config_fn: Option<NodeConfigFn>,
     base_helm_values: String,
@@ -619,10 +821,11 @@ pub fn construct_node_helm_values(
     value["numValidators"] = num_validators.into();
     value["numFullnodeGroups"] = num_fullnodes.into();
     value["imageTag"] = image_tag.clone().into();
-    value["chain"]["era"] = era.into();
+    value["chain"]["era"] = era.clone().into();
     value["haproxy"]["enabled"] = enable_haproxy.into();
     value["labels"]["forge-namespace"] = make_k8s_label(kube_namespace).into();
     value["labels"]["forge-image-tag"] = make_k8s_label(image_tag).into();
+    value["labels"][FORGE_ERA_LABEL] = make_k8s_label(era).into();
 
     // if present, tag the node with the test suite name and username
     let suite_name = env::var("FORGE_TEST_SUITE").unwrap_or(DEFAULT_TEST_SUITE_NAME.to_string());
@@ -636,6 +839,30 @@ pub fn construct_node_helm_values(
     serde_yaml::to_string(&value).map_err(|e| anyhow::anyhow!("{:?}", e))
 }
 
+/// The image repos of the validators and fullnodes as the helm values of the nodes render them,
+/// from the values of the release and the config fn, e.g. a CI registry. None where the values
+/// keep the chart's default.
+pub fn node_image_repos(
+    config: &K8sBackendConfig,
+    node_helm_config_fn: Option<NodeConfigFn>,
+) -> Result<(Option<String>, Option<String>)> {
+    let base_helm_values = get_helm_status(config, APTOS_NODE_HELM_RELEASE_NAME)?;
+    let mut value = serde_yaml::to_value(&base_helm_values["config"])?;
+    if let Some(config_fn) = node_helm_config_fn {
+        (config_fn)(&mut value);
+    }
+    Ok(image_repos_of(&value))
+}
+
+fn image_repos_of(helm_values: &serde_yaml::Value) -> (Option<String>, Option<String>) {
+    let repo = |node: &str| {
+        helm_values[node]["image"]["repo"]
+            .as_str()
+            .map(|repo| repo.to_string())
+    };
+    (repo("validator"), repo("fullnode"))
+}
+
 pub fn construct_genesis_helm_values(
     genesis_helm_config_fn: Option<GenesisConfigFn>,
     kube_namespace: String,
@@ -656,7 +883,7 @@ pub fn construct_genesis_helm_values(
     };
     let mut value: serde_yaml::Value = serde_yaml::Value::default();
     value["imageTag"] = genesis_image_tag.clone().into();
-    value["chain"]["era"] = era.into();
+    value["chain"]["era"] = era.clone().into();
     value["chain"]["root_key"] = DEFAULT_ROOT_KEY.into();
     value["genesis"]["numValidators"] = num_validators.into();
     value["genesis"]["validator"]["internal_host_suffix"] = validator_internal_host_suffix.into();
@@ -664,6 +891,7 @@ pub fn construct_genesis_helm_values(
     value["genesis"]["fullnode"]["internal_host_suffix"] = fullnode_internal_host_suffix.into();
     value["labels"]["forge-namespace"] = make_k8s_label(kube_namespace).into();
     value["labels"]["forge-image-tag"] = make_k8s_label(genesis_image_tag).into();
+    value["labels"][FORGE_ERA_LABEL] = make_k8s_label(era).into();
 
     // if present, tag the node with the test suite name and username
     let suite_name = env::var("FORGE_TEST_SUITE").unwrap_or(DEFAULT_TEST_SUITE_NAME.to_string());
@@ -680,17 +908,21 @@ pub fn construct_genesis_helm_values(
 
 /// Collect the running nodes in the network into K8sNodes
 pub async fn collect_running_nodes(
+    config: &Arc<K8sBackendConfig>,
     kube_client: &K8sClient,
     kube_namespace: String,
     use_port_forward: bool,
     enable_haproxy: bool,
+    rest_api_tls: Option<RestApiTls>,
 ) -> Result<(HashMap<PeerId, K8sNode>, HashMap<PeerId, K8sNode>)> {
     // get all validators
     let validators = get_validators(
+        config,
         kube_client.clone(),
         &kube_namespace,
         use_port_forward,
         enable_haproxy,
+        rest_api_tls.clone(),
     )
     .await
     .unwrap();
@@ -704,10 +936,12 @@ pub async fn collect_running_nodes(
 
     // get all fullnodes
     let fullnodes = get_fullnodes(
+        config,
         kube_client.clone(),
         &kube_namespace,
         use_port_forward,
         enable_haproxy,
+        rest_api_tls,
     )
     .await
     .unwrap();
@@ -719,62 +953,34 @@ pub async fn collect_running_nodes(
         .chain(fullnodes.values())
         .collect::<Vec<&K8sNode>>();
 
-    // start port-forward for each of the nodes
+    // start port-forward for each of the nodes, concurrently so large swarms come up quickly
     if use_port_forward {
-        for node in nodes.iter() {
-            node.port_forward_rest_api()?;
-            // assume this will always succeed???
-        }
+        try_join_all(nodes.iter().map(|node| async move {
+            node.port_forward_rest_api().await?;
+            node.port_forward_inspection_service().await
+        }))
+        .await?;
     }
 
     nodes_healthcheck(nodes).await?;
-    Ok((validators, fullnodes))
-}
 
-/// Returns a [Config] object reading the KUBECONFIG environment variable or infering from the
-/// environment. Differently from [`Config::infer()`], this will look at the
-/// `KUBECONFIG` env var first, and only then infer from the environment.
-async fn make_kube_client_config() -> Result<Config> {
-    match Config::from_kubeconfig(&KubeConfigOptions::default()).await {
-        Ok(config) => Ok(config),
-        Err(kubeconfig_err) => {
-            Config::infer()
-                .await
-                .map_err(|infer_err|
-                    anyhow::anyhow!("Unable to construct Config. Failed to infer config {:?}. Failed to read KUBECONFIG {:?}", infer_err, kubeconfig_err)
-                )
-        }
-    }
+    let validators = key_by_identity(validators).await?;
+    let fullnodes = key_by_identity(fullnodes).await?;
+    Ok((validators, fullnodes))
 }
 
-pub async fn create_k8s_client() -> Result<K8sClient> {
-    let mut config = make_kube_client_config().await?;
-
-    let cluster_name = Kubeconfig::read()
-        .map(|k| k.current_context.unwrap_or_default())
-        .unwrap_or_else(|_| config.cluster_url.to_string());
-
-    config.accept_invalid_certs = true;
-
-    let client = K8sClient::try_from(config)?;
-
-    // Test the connection, fail if request fails
-    client.apiserver_version().await.map_err(|_| {
-        if !cluster_name.contains("forge") {
-            format_err!(
-                "Failed to connect to kubernetes cluster {}, \
-                please make sure you have the right kubeconfig",
-                cluster_name
-            )
-        } else {
-            format_err!("Failed to connect to kubernetes cluster {}", cluster_name)
-        }
-    })?;
-    Ok(client)
+/// Set the peer_id of each node to the identity it runs with, which the StatefulSets do not tell
+async fn key_by_identity(nodes: HashMap<PeerId, K8sNode>) -> Result<HashMap<PeerId, K8sNode>> {
+    try_join_all(nodes.into_values().map(|mut node| async move {
+        node.peer_id = node.fetch_identity().await?.peer_id;
+        Ok::<_, anyhow::Error>((node.peer_id, node))
+    }))
+    .await
+    .map(|nodes| nodes.into_iter().collect())
 }
 
 /// Gets the result of helm status command as JSON
-fn get_helm_status(helm_release_name: &str) -> Result<Value> {
+fn get_helm_status(config: &K8sBackendConfig, helm_release_name: &str) -> Result<Value> {
     let status_args = [
         "status",
         helm_release_name,
@@ -784,7 +990,8 @@ fn get_helm_status(helm_release_name: &str) -> Result<Value> {
         "json",
     ];
     info!("{:?}", status_args);
-    let raw_helm_values = Command::new(HELM_BIN)
+    let raw_helm_values = config
+        .helm()
         .args(status_args)
         .output()
         .unwrap_or_else(|_| panic!("Failed to helm status {}", helm_release_name));
@@ -814,9 +1021,13 @@ pub fn dump_string_to_file(
     Ok(file_path_str)
 }
 
-fn dump_helm_values_to_file(helm_release_name: &str, tmp_dir: &TempDir) -> Result<String> {
+fn dump_helm_values_to_file(
+    config: &K8sBackendConfig,
+    helm_release_name: &str,
+    tmp_dir: &TempDir,
+) -> Result<String> {
     // get aptos-node values
-    let v: Value = get_helm_status(helm_release_name).unwrap();
+    let v: Value = get_helm_status(config, helm_release_name).unwrap();
     let config = &v["config"];
     let content = config.to_string();
     let file_name = format!("{}_status.json", helm_release_name);
@@ -831,14 +1042,44 @@ enum ApiError {
     FinalError(String),
 }
 
+/// The labels of a namespace forge creates, for [cleanup_stale_namespaces] to find it. Kept
+/// namespaces have no TTL.
+fn forge_namespace_labels(created_at_secs: u64, ttl: Option<Duration>) -> BTreeMap<String, String> {
+    let run_id = env::var("FORGE_RUN_ID").unwrap_or(DEFAULT_RUN_ID.to_string());
+    let mut labels = BTreeMap::from([
+        (FORGE_NAMESPACE_MARKER_LABEL.to_string(), "true".to_string()),
+        (
+            FORGE_NAMESPACE_CREATED_AT_LABEL.to_string(),
+            created_at_secs.to_string(),
+        ),
+        (
+            FORGE_NAMESPACE_RUN_ID_LABEL.to_string(),
+            make_k8s_label(run_id),
+        ),
+    ]);
+    if let Some(ttl) = ttl {
+        labels.insert(
+            FORGE_NAMESPACE_TTL_LABEL.to_string(),
+            ttl.as_secs().to_string(),
+        );
+    }
+    labels
+}
+
 async fn create_namespace(
     namespace_api: Arc<dyn ReadWrite<Namespace>>,
     kube_namespace: String,
+    ttl: Option<Duration>,
 ) -> Result<(), ApiError> {
     let kube_namespace_name = kube_namespace.clone();
+    let created_at_secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_secs();
     let namespace = Namespace {
         metadata: ObjectMeta {
             name: Some(kube_namespace_name.clone()),
+            labels: Some(forge_namespace_labels(created_at_secs, ttl)),
             ..ObjectMeta::default()
         },
         spec: None,
@@ -870,11 +1111,12 @@ async fn create_namespace(
 }
 
 pub async fn create_management_configmap(
+    config: &K8sBackendConfig,
     kube_namespace: String,
     keep: bool,
     cleanup_duration: Duration,
 ) -> Result<()> {
-    let kube_client = create_k8s_client().await?;
+    let kube_client = config.create_client().await?;
     let namespaces_api = Arc::new(K8sApi::<Namespace>::from_client(kube_client.clone(), None));
     let other_kube_namespace = kube_namespace.clone();
 
@@ -884,7 +1126,13 @@ pub async fn create_management_configmap(
     RetryPolicy::exponential(Duration::from_millis(1000))
         .with_max_delay(Duration::from_millis(10 * 60 * 1000))
         .retry_if(
-            move || create_namespace(namespaces_api.clone(), other_kube_namespace.clone()),
+            move || {
+                create_namespace(
+                    namespaces_api.clone(),
+                    other_kube_namespace.clone(),
+                    (!keep).then_some(cleanup_duration),
+                )
+            },
             |e: &ApiError| matches!(e, ApiError::RetryableError(_)),
         )
         .await?;
@@ -939,8 +1187,8 @@ pub async fn create_management_configmap(
     Ok(())
 }
 
-pub async fn cleanup_cluster_with_management() -> Result<()> {
-    let kube_client = create_k8s_client().await?;
+pub async fn cleanup_cluster_with_management(config: &K8sBackendConfig) -> Result<()> {
+    let kube_client = config.create_client().await?;
     let start = SystemTime::now();
     let time_since_the_epoch = start
         .duration_since(UNIX_EPOCH)
@@ -1007,7 +1255,7 @@ pub async fn cleanup_cluster_with_management() -> Result<()> {
         .collect::<Vec<ConfigMap>>();
     for configmap in configmaps {
         let namespace = configmap.namespace().unwrap();
-        uninstall_testnet_resources(namespace).await?;
+        uninstall_testnet_resources(config, namespace).await?;
     }
 
     Ok(())
@@ -1058,6 +1306,143 @@ fn check_namespace_for_cleanup(
     false
 }
 
+/// Why a namespace is deleted by [cleanup_stale_namespaces], none if it isn't. Only namespaces
+/// with the forge marker label are ever stale, once they are older than `older_than` and their
+/// TTL, if any, has passed. Kept namespaces have no TTL and are never stale.
+fn stale_namespace_age(
+    namespace: &Namespace,
+    now_secs: u64,
+    older_than: Duration,
+) -> Option<Duration> {
+    let labels = namespace.metadata.labels.as_ref()?;
+    if labels.get(FORGE_NAMESPACE_MARKER_LABEL).map(String::as_str) != Some("true") {
+        return None;
+    }
+    let ttl_secs: u64 = labels.get(FORGE_NAMESPACE_TTL_LABEL)?.parse().ok()?;
+    let created_at_secs = match labels.get(FORGE_NAMESPACE_CREATED_AT_LABEL) {
+        Some(created_at) => created_at.parse().ok()?,
+        None => namespace
+            .metadata
+            .creation_timestamp
+            .as_ref()?
+            .0
+            .timestamp() as u64,
+    };
+    let age = Duration::from_secs(now_secs.saturating_sub(created_at_secs));
+    (age >= older_than && age.as_secs() >= ttl_secs).then_some(age)
+}
+
+/// Deletes the forge namespaces older than `older_than`, see [stale_namespace_age], with their
+/// chaos and the Released PersistentVolumes they claimed, except the tagged ones of DB snapshots
+/// that are kept for reuse, see [reset_persistent_volumes]. With `dry_run`, only logs what would
+/// be deleted. Returns the stale namespaces.
+pub async fn cleanup_stale_namespaces(
+    config: &K8sBackendConfig,
+    older_than: Duration,
+    dry_run: bool,
+) -> Result<Vec<String>> {
+    let kube_client = config.create_client().await?;
+    let now_secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_secs();
+    let namespaces_api: Api<Namespace> = Api::all(kube_client.clone());
+    let lp = ListParams::default().labels(&format!("{}=true", FORGE_NAMESPACE_MARKER_LABEL));
+    let namespaces = namespaces_api
+        .list(&lp)
+        .await
+        .map_err(|e| K8sError::from_kube("Namespaces", e))?
+        .items;
+    let mut stale = vec![];
+    for namespace in &namespaces {
+        let Some(age) = stale_namespace_age(namespace, now_secs, older_than) else {
+            continue;
+        };
+        let name = namespace.name();
+        if dry_run {
+            info!("Would delete namespace {}, {}s old", name, age.as_secs());
+        } else {
+            info!("Deleting namespace {}, {}s old", name, age.as_secs());
+            // kubectl blocks, and a namespace whose chaos is left behind is still deleted
+            let (chaos_config, chaos_namespace) = (config.clone(), name.clone());
+            let deleted = tokio::task::spawn_blocking(move || {
+                delete_all_chaos(&chaos_config, &chaos_namespace)
+            })
+            .await
+            .map_err(anyhow::Error::from)
+            .and_then(|deleted| deleted);
+            if let Err(e) = deleted {
+                warn!("Failed to delete the chaos of namespace {}: {:#}", name, e);
+            }
+            namespaces_api
+                .delete(&name, &DeleteParams::default())
+                .await
+                .map_err(|e| K8sError::from_kube(format!("Namespace {}", name), e))?;
+        }
+        stale.push(name);
+    }
+
+    let pv_api: Api<PersistentVolume> = Api::all(kube_client);
+    let pvs = pv_api
+        .list(&ListParams::default())
+        .await
+        .map_err(|e| K8sError::from_kube("PersistentVolumes", e))?
+        .items;
+    for pv in pvs {
+        let released = pv
+            .status
+            .as_ref()
+            .and_then(|status| status.phase.as_deref())
+            == Some("Released");
+        let tagged = pv.labels().contains_key("tag");
+        let claim_namespace = pv
+            .spec
+            .as_ref()
+            .and_then(|spec| spec.claim_ref.as_ref())
+            .and_then(|claim| claim.namespace.clone());
+        let Some(claim_namespace) = claim_namespace else {
+            continue;
+        };
+        if !released || tagged || !stale.contains(&claim_namespace) {
+            continue;
+        }
+        let name = pv.name();
+        if dry_run {
+            info!(
+                "Would delete PersistentVolume {} of namespace {}",
+                name, claim_namespace
+            );
+        } else {
+            info!(
+                "Deleting PersistentVolume {} of namespace {}",
+                name, claim_namespace
+            );
+            pv_api
+                .delete(&name, &DeleteParams::default())
+                .await
+                .map_err(|e| K8sError::from_kube(format!("PersistentVolume {}", name), e))?;
+        }
+    }
+    Ok(stale)
+}
+
+/// Parses an age like "4h", "30m", "90s" or "2d"
+pub fn parse_age(age: &str) -> Result<Duration> {
+    let (amount, unit_secs) = match age.char_indices().last() {
+        Some((i, 's')) => (&age[..i], 1),
+        Some((i, 'm')) => (&age[..i], 60),
+        Some((i, 'h')) => (&age[..i], 3600),
+        Some((i, 'd')) => (&age[..i], 86400),
+        _ => bail!("Invalid age {}, expected e.g. 4h, 30m, 90s or 2d", age),
+    };
+    let secs = amount
+        .parse::<u64>()
+        .ok()
+        .and_then(|amount| amount.checked_mul(unit_secs))
+        .ok_or_else(|| format_err!("Invalid age {}, expected e.g. 4h, 30m, 90s or 2d", age))?;
+    Ok(Duration::from_secs(secs))
+}
+
 /// Ensures that the label is at most 64 characters to meet k8s
 /// label length requirements.
 pub fn make_k8s_label(value: String) -> String {
@@ -1069,16 +1454,79 @@ mod tests {
     use super::*;
     use crate::FailedNamespacesApi;
 
+    #[test]
+    fn test_get_free_port_is_unique() {
+        let reserve = |count: usize| {
+            let threads: Vec<_> = (0..count)
+                .map(|_| std::thread::spawn(get_free_port))
+                .collect();
+            threads
+                .into_iter()
+                .map(|thread| thread.join().unwrap().unwrap())
+                .collect::<Vec<_>>()
+        };
+        let mut reserved = reserve(64);
+        // released ports are not bound anymore, but must not be handed out again either
+        for port in reserved.iter_mut() {
+            port.release();
+            assert!(TcpListener::bind(("127.0.0.1", port.port() as u16)).is_ok());
+        }
+        reserved.extend(reserve(64));
+
+        let ports: HashSet<_> = reserved.iter().map(ReservedPort::port).collect();
+        assert_eq!(ports.len(), reserved.len());
+    }
+
     #[tokio::test]
     async fn test_create_namespace_final_error() {
         let namespace_creator = Arc::new(FailedNamespacesApi::from_status_code(401));
-        let result = create_namespace(namespace_creator, "banana".to_string()).await;
+        let result = create_namespace(
+            namespace_creator,
+            "banana".to_string(),
+            Some(Duration::from_secs(3600)),
+        )
+        .await;
         match result {
             Err(ApiError::FinalError(_)) => {},
             _ => panic!("Expected final error"),
         }
     }
 
+    /// Whether the labels match the selector, as the API server evaluates the `key`, `key=value`
+    /// and `key!=value` requirements of a selector
+    fn selector_matches(selector: &str, labels: &BTreeMap<String, String>) -> bool {
+        selector.split(',').all(|requirement| {
+            if let Some((key, value)) = requirement.split_once("!=") {
+                labels.get(key).map_or(true, |label| label != value)
+            } else if let Some((key, value)) = requirement.split_once('=') {
+                labels.get(key).map_or(false, |label| label == value)
+            } else {
+                labels.contains_key(requirement)
+            }
+        })
+    }
+
+    #[test]
+    fn test_stale_era_selector() {
+        let selector = stale_era_selector("forge42");
+        assert_eq!(selector, "forge-era,forge-era!=forge42");
+
+        // the resources of the current era, and those without the forge labels, are kept
+        let era_label = |era: &str| {
+            let mut labels = None;
+            add_era_label(&mut labels, era);
+            labels.unwrap()
+        };
+        assert!(selector_matches(&selector, &era_label("forge7")));
+        assert!(!selector_matches(&selector, &era_label("forge42")));
+        assert!(!selector_matches(&selector, &BTreeMap::new()));
+        assert_eq!(era_label("forge7")[FORGE_ERA_LABEL], "forge7");
+
+        // the genesis secrets are labeled by the genesis job
+        let genesis_script = include_str!("../../../../../terraform/helm/genesis/files/genesis.sh");
+        assert!(genesis_script.contains(&format!("\"{}=${{ERA:0:63}}\"", FORGE_ERA_LABEL)));
+    }
+
     #[tokio::test]
     async fn test_construct_node_helm_values() {
         let node_helm_values = construct_node_helm_values(
@@ -1104,12 +1552,32 @@ haproxy:
 labels:
   forge-namespace: forge-123
   forge-image-tag: image
+  forge-era: era
   forge-test-suite: unknown-testsuite
   forge-username: unknown-username
 ";
         assert_eq!(node_helm_values, expected_helm_values);
     }
 
+    #[test]
+    fn test_image_repos_of() {
+        let helm_values: serde_yaml::Value = serde_yaml::from_str(
+            "validator:\n  image:\n    repo: us-docker.pkg.dev/aptos-registry/docker/validator\n",
+        )
+        .unwrap();
+        assert_eq!(
+            image_repos_of(&helm_values),
+            (
+                Some("us-docker.pkg.dev/aptos-registry/docker/validator".to_string()),
+                None
+            )
+        );
+        assert_eq!(
+            image_repos_of(&serde_yaml::from_str("{}").unwrap()),
+            (None, None)
+        );
+    }
+
     #[tokio::test]
     async fn test_construct_genesis_helm_values() {
         let genesis_helm_values = construct_genesis_helm_values(
@@ -1139,6 +1607,7 @@ genesis:
 labels:
   forge-namespace: forge-123
   forge-image-tag: genesis_image
+  forge-era: era
   forge-test-suite: unknown-testsuite
   forge-username: unknown-username
 ";
@@ -1149,7 +1618,12 @@ labels:
     #[tokio::test]
     async fn test_create_namespace_retryable_error() {
         let namespace_creator = Arc::new(FailedNamespacesApi::from_status_code(403));
-        let result = create_namespace(namespace_creator, "banana".to_string()).await;
+        let result = create_namespace(
+            namespace_creator,
+            "banana".to_string(),
+            Some(Duration::from_secs(3600)),
+        )
+        .await;
         match result {
             Err(ApiError::RetryableError(_)) => {},
             _ => panic!("Expected retryable error"),
@@ -1221,4 +1695,53 @@ labels:
             time_since_the_epoch
         ));
     }
+
+    #[test]
+    fn test_stale_namespace_age() {
+        let namespace = |labels: BTreeMap<String, String>| Namespace {
+            metadata: ObjectMeta {
+                name: Some("forge-banana".to_string()),
+                labels: Some(labels),
+                ..ObjectMeta::default()
+            },
+            spec: None,
+            status: None,
+        };
+        let hour = Duration::from_secs(3600);
+        let labelled = namespace(forge_namespace_labels(1000, Some(hour)));
+        assert_eq!(
+            stale_namespace_age(&labelled, 1000 + 5 * 3600, 4 * hour),
+            Some(5 * hour)
+        );
+        assert_eq!(
+            stale_namespace_age(&labelled, 1000 + 3 * 3600, 4 * hour),
+            None
+        );
+        // still within its TTL
+        let long_lived = namespace(forge_namespace_labels(1000, Some(10 * hour)));
+        assert_eq!(
+            stale_namespace_age(&long_lived, 1000 + 5 * 3600, 4 * hour),
+            None
+        );
+        // kept
+        let kept = namespace(forge_namespace_labels(1000, None));
+        assert_eq!(stale_namespace_age(&kept, 1000 + 5 * 3600, 4 * hour), None);
+        // without the marker label, however old
+        let mut unmarked = forge_namespace_labels(1000, Some(hour));
+        unmarked.remove(FORGE_NAMESPACE_MARKER_LABEL);
+        assert_eq!(
+            stale_namespace_age(&namespace(unmarked), 1000 + 5 * 3600, 4 * hour),
+            None
+        );
+    }
+
+    #[test]
+    fn test_parse_age() {
+        assert_eq!(parse_age("4h").unwrap(), Duration::from_secs(4 * 3600));
+        assert_eq!(parse_age("30m").unwrap(), Duration::from_secs(1800));
+        assert_eq!(parse_age("2d").unwrap(), Duration::from_secs(2 * 86400));
+        parse_age("4").unwrap_err();
+        parse_age("h").unwrap_err();
+        parse_age(&format!("{}d", u64::MAX / 86400 + 1)).unwrap_err();
+    }
 }
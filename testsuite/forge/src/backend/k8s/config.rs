@@ -0,0 +1,228 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{RestClientOptions, Result, HELM_BIN, KUBECTL_BIN};
+use anyhow::{format_err, Context};
+use aptos_rest_client::RetryPolicy;
+use kube::{
+    config::{KubeConfigOptions, Kubeconfig},
+    Client as K8sClient, Config,
+};
+use std::{convert::TryFrom, path::PathBuf, process::Command};
+
+// the prefix forge namespaces have unless configured otherwise, e.g. "forge-dark-moon-pile-test"
+pub const DEFAULT_NAMESPACE_PREFIX: &str = "forge";
+
+/// Which cluster the k8s backend talks to, and with which kubectl. Every kubectl and helm
+/// invocation and every kube client uses these, so that forge never acts on whatever context
+/// happens to be active.
+#[derive(Clone, Debug)]
+pub struct K8sBackendConfig {
+    pub kubectl_path: PathBuf,
+    // if unset, KUBECONFIG or ~/.kube/config is used
+    pub kubeconfig: Option<PathBuf>,
+    // if unset, the current context of the kubeconfig is used
+    pub context: Option<String>,
+    // namespaces without this prefix are refused, so that forge never wipes someone else's
+    pub namespace_prefix: String,
+    // how the nodes' REST clients are built. Retries are on, since port-forwards and HAProxy
+    // make requests flaky.
+    pub rest_client_options: RestClientOptions,
+    // kill the kubectl port-forwards crashed runs left behind, see
+    // [crate::cleanup_orphaned_port_forwards]. Off for users who run their own port-forwards.
+    pub cleanup_orphaned_port_forwards: bool,
+    // log the Warning events of the namespace while the swarm runs, and add them to the test
+    // report, see [crate::EventWatcher]
+    pub watch_events: bool,
+    // check that the images of the nodes exist in the registry before deploying them, see
+    // [crate::ensure_image_exists]. Off by default, since it needs the credentials of the
+    // registry the helm values pull from.
+    pub check_image_tags: bool,
+}
+
+impl Default for K8sBackendConfig {
+    fn default() -> Self {
+        Self {
+            kubectl_path: PathBuf::from(KUBECTL_BIN),
+            kubeconfig: None,
+            context: None,
+            namespace_prefix: DEFAULT_NAMESPACE_PREFIX.to_string(),
+            rest_client_options: RestClientOptions {
+                retry_policy: Some(RetryPolicy::default()),
+                ..RestClientOptions::default()
+            },
+            cleanup_orphaned_port_forwards: true,
+            watch_events: false,
+            check_image_tags: false,
+        }
+    }
+}
+
+impl K8sBackendConfig {
+    pub fn is_forge_namespace(&self, namespace: &str) -> bool {
+        namespace.starts_with(&self.namespace_prefix)
+    }
+
+    /// The args that select the kubeconfig and context, for kubectl
+    pub fn kubectl_args(&self) -> Vec<String> {
+        self.cluster_args("--context")
+    }
+
+    /// The args that select the kubeconfig and context, for helm
+    pub fn helm_args(&self) -> Vec<String> {
+        self.cluster_args("--kube-context")
+    }
+
+    fn cluster_args(&self, context_flag: &str) -> Vec<String> {
+        let mut args = vec![];
+        if let Some(kubeconfig) = &self.kubeconfig {
+            args.push("--kubeconfig".to_string());
+            args.push(kubeconfig.display().to_string());
+        }
+        if let Some(context) = &self.context {
+            args.push(context_flag.to_string());
+            args.push(context.clone());
+        }
+        args
+    }
+
+    /// A kubectl command pointed at the configured cluster
+    pub fn kubectl(&self) -> Command {
+        let mut command = Command::new(&self.kubectl_path);
+        command.args(self.kubectl_args());
+        command
+    }
+
+    /// A kubectl command pointed at the configured cluster, for running on the tokio runtime
+    pub fn kubectl_async(&self) -> tokio::process::Command {
+        let mut command = tokio::process::Command::new(&self.kubectl_path);
+        command.args(self.kubectl_args());
+        command
+    }
+
+    /// A helm command pointed at the configured cluster
+    pub fn helm(&self) -> Command {
+        let mut command = Command::new(HELM_BIN);
+        command.args(self.helm_args());
+        command
+    }
+
+    /// The kubectl command line to print for humans, e.g. in failure reports
+    pub fn kubectl_command_line(&self) -> String {
+        std::iter::once(self.kubectl_path.display().to_string())
+            .chain(self.kubectl_args())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Returns a [Config] reading the configured kubeconfig, or the KUBECONFIG environment
+    /// variable. Differently from [`Config::infer()`], this will look at the kubeconfig first, and
+    /// only then infer from the environment. An explicitly configured cluster is never inferred.
+    async fn kube_client_config(&self) -> Result<Config> {
+        let options = KubeConfigOptions {
+            context: self.context.clone(),
+            ..KubeConfigOptions::default()
+        };
+        let kubeconfig_result = match &self.kubeconfig {
+            Some(path) => {
+                let kubeconfig = Kubeconfig::read_from(path)
+                    .with_context(|| format!("Failed to read kubeconfig {}", path.display()))?;
+                Config::from_custom_kubeconfig(kubeconfig, &options).await
+            },
+            None => Config::from_kubeconfig(&options).await,
+        };
+        match kubeconfig_result {
+            Ok(config) => Ok(config),
+            Err(kubeconfig_err) if self.kubeconfig.is_some() || self.context.is_some() => Err(
+                format_err!("Failed to load the configured cluster {:?}: {}", self, kubeconfig_err),
+            ),
+            Err(kubeconfig_err) => {
+                Config::infer()
+                    .await
+                    .map_err(|infer_err|
+                        anyhow::anyhow!("Unable to construct Config. Failed to infer config {:?}. Failed to read KUBECONFIG {:?}", infer_err, kubeconfig_err)
+                    )
+            },
+        }
+    }
+
+    fn cluster_name(&self, config: &Config) -> String {
+        if let Some(context) = &self.context {
+            return context.clone();
+        }
+        let kubeconfig = match &self.kubeconfig {
+            Some(path) => Kubeconfig::read_from(path),
+            None => Kubeconfig::read(),
+        };
+        kubeconfig
+            .map(|k| k.current_context.unwrap_or_default())
+            .unwrap_or_else(|_| config.cluster_url.to_string())
+    }
+
+    /// A kube client for the configured cluster, after checking that the cluster is reachable
+    pub async fn create_client(&self) -> Result<K8sClient> {
+        let mut config = self.kube_client_config().await?;
+        let cluster_name = self.cluster_name(&config);
+
+        config.accept_invalid_certs = true;
+
+        let client = K8sClient::try_from(config)?;
+
+        // Test the connection, fail if request fails
+        client.apiserver_version().await.map_err(|_| {
+            if !cluster_name.contains("forge") {
+                format_err!(
+                    "Failed to connect to kubernetes cluster {}, \
+                    please make sure you have the right kubeconfig",
+                    cluster_name
+                )
+            } else {
+                format_err!("Failed to connect to kubernetes cluster {}", cluster_name)
+            }
+        })?;
+        Ok(client)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cluster_args() {
+        let config = K8sBackendConfig::default();
+        assert!(config.kubectl_args().is_empty());
+        assert_eq!(config.kubectl_command_line(), "kubectl");
+        assert!(config.is_forge_namespace("forge-dark-moon"));
+        assert!(!config.is_forge_namespace("default"));
+
+        let config = K8sBackendConfig {
+            kubectl_path: PathBuf::from("/opt/bin/kubectl"),
+            kubeconfig: Some(PathBuf::from("/etc/kube/staging.yaml")),
+            context: Some("staging-us-west".to_string()),
+            namespace_prefix: "ci-forge".to_string(),
+            rest_client_options: RestClientOptions::default(),
+            cleanup_orphaned_port_forwards: true,
+            watch_events: false,
+            check_image_tags: true,
+        };
+        assert_eq!(config.kubectl_args(), vec![
+            "--kubeconfig",
+            "/etc/kube/staging.yaml",
+            "--context",
+            "staging-us-west"
+        ]);
+        assert_eq!(config.helm_args(), vec![
+            "--kubeconfig",
+            "/etc/kube/staging.yaml",
+            "--kube-context",
+            "staging-us-west"
+        ]);
+        assert_eq!(
+            config.kubectl_command_line(),
+            "/opt/bin/kubectl --kubeconfig /etc/kube/staging.yaml --context staging-us-west"
+        );
+        assert!(config.is_forge_namespace("ci-forge-dark-moon"));
+        assert!(!config.is_forge_namespace("forge-dark-moon"));
+    }
+}
@@ -1,6 +1,8 @@
 // Copyright © Aptos Foundation
 // SPDX-License-Identifier: Apache-2.0
 
+use std::time::Duration;
+
 /// A collection of constants and default values for configuring various Forge components.
 
 // These are test keys for forge ephemeral networks. Do not use these elsewhere!
@@ -29,14 +31,34 @@ pub const NAMESPACE_CLEANUP_THRESHOLD_SECS: u64 = 1800;
 pub const NAMESPACE_CLEANUP_DURATION_BUFFER_SECS: u64 = 1200;
 pub const POD_CLEANUP_THRESHOLD_SECS: u64 = 86400;
 pub const MANAGEMENT_CONFIGMAP_PREFIX: &str = "forge-management";
+// labels of the namespaces forge creates. The stale namespace sweeper only ever deletes
+// namespaces with the marker label.
+pub const FORGE_NAMESPACE_MARKER_LABEL: &str = "forge-managed";
+// in seconds since the unix epoch
+pub const FORGE_NAMESPACE_CREATED_AT_LABEL: &str = "forge-created-at";
+pub const FORGE_NAMESPACE_RUN_ID_LABEL: &str = "forge-run-id";
+// in seconds
+pub const FORGE_NAMESPACE_TTL_LABEL: &str = "forge-ttl-secs";
+// the genesis era of the resources forge deploys in a namespace. The resources of other eras are
+// swept before a new genesis, and those without the label are never touched.
+pub const FORGE_ERA_LABEL: &str = "forge-era";
 
 // this is the port on the validator service itself, as opposed to 80 on the validator haproxy service
 pub const NODE_METRIC_PORT: u32 = 9101;
 pub const REST_API_SERVICE_PORT: u32 = 8080;
 pub const REST_API_HAPROXY_SERVICE_PORT: u32 = 80;
+// the name of the REST API port on the Services of the aptos-node helm chart, used to look up the
+// actual ports, which may differ from the defaults above
+pub const REST_API_PORT_NAME: &str = "api";
+// ports the node listens on inside its pod, not all of which are exposed on the Service
+pub const BACKUP_SERVICE_PORT: u32 = 6186;
+pub const ADMIN_SERVICE_PORT: u32 = 9102;
 // when we interact with the node over port-forward
 pub const LOCALHOST: &str = "127.0.0.1";
 
+// a node whose latest ledger timestamp is older than this is considered unhealthy
+pub const DEFAULT_LEDGER_STALENESS_THRESHOLD: Duration = Duration::from_secs(30);
+
 // kubernetes service names
 pub const VALIDATOR_SERVICE_SUFFIX: &str = "validator";
 pub const FULLNODE_SERVICE_SUFFIX: &str = "fullnode";
@@ -52,3 +74,4 @@ pub const VALIDATOR_0_DATA_PERSISTENT_VOLUME_CLAIM_PREFIX: &str = "aptos-node-0-
 // metadata about the cluster
 pub const DEFAULT_TEST_SUITE_NAME: &str = "unknown-testsuite";
 pub const DEFAULT_USERNAME: &str = "unknown-username";
+pub const DEFAULT_RUN_ID: &str = "unknown-run";
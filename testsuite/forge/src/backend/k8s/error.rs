@@ -0,0 +1,243 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use kube::Error as KubeError;
+use std::process::Output;
+use thiserror::Error;
+
+// the reasons a pod or its containers are stuck for that are the cluster's doing
+const INFRA_REASONS: &[&str] = &[
+    "ImagePullBackOff",
+    "ErrImagePull",
+    "CreateContainerConfigError",
+    "Unschedulable",
+];
+
+/// Errors from operations against the k8s cluster, so that callers can tell missing resources and
+/// missing permissions apart from transient failures
+#[derive(Error, Debug)]
+pub enum K8sError {
+    #[error("{resource} not found: {message}")]
+    NotFound { resource: String, message: String },
+    #[error("Forbidden from accessing {resource}: {message}")]
+    Forbidden { resource: String, message: String },
+    #[error("Timed out waiting for {operation}: {message}")]
+    Timeout { operation: String, message: String },
+    #[error("Port-forward on local port {port} failed: {stderr}")]
+    PortForwardFailed { port: u32, stderr: String },
+    #[error("Command {args:?} failed with {status}: {stderr}")]
+    CommandFailed {
+        args: Vec<String>,
+        status: String,
+        stderr: String,
+    },
+    #[error(
+        "Container {container} of pod {pod} is failing with {reason} after {restart_count} restarts: {message}"
+    )]
+    ContainerFailing {
+        pod: String,
+        container: String,
+        // e.g. CrashLoopBackOff, OOMKilled or ImagePullBackOff
+        reason: String,
+        message: String,
+        restart_count: i32,
+    },
+    #[error("Kubernetes API request for {resource} failed: {source}")]
+    Api {
+        resource: String,
+        #[source]
+        source: KubeError,
+    },
+}
+
+impl K8sError {
+    /// Classify an error from the kube client, returned while operating on `resource`
+    pub fn from_kube(resource: impl Into<String>, error: KubeError) -> Self {
+        let resource = resource.into();
+        match &error {
+            KubeError::Api(response) if response.code == 404 => K8sError::NotFound {
+                resource,
+                message: response.message.clone(),
+            },
+            KubeError::Api(response) if response.code == 403 => K8sError::Forbidden {
+                resource,
+                message: response.message.clone(),
+            },
+            _ => K8sError::Api {
+                resource,
+                source: error,
+            },
+        }
+    }
+
+    /// Classify a failed kubectl invocation by the error kubectl reports, e.g.
+    /// `Error from server (NotFound): pods "aptos-node-0-validator-0" not found`
+    pub fn from_kubectl(args: &[&str], output: &Output) -> Self {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        let resource = args.join(" ");
+        if stderr.contains("(NotFound)") {
+            K8sError::NotFound {
+                resource,
+                message: stderr,
+            }
+        } else if stderr.contains("(Forbidden)") {
+            K8sError::Forbidden {
+                resource,
+                message: stderr,
+            }
+        } else {
+            K8sError::CommandFailed {
+                args: args.iter().map(|arg| arg.to_string()).collect(),
+                status: output.status.to_string(),
+                stderr,
+            }
+        }
+    }
+
+    /// Whether the error is the cluster's rather than the test's, see [crate::FailureCategory].
+    /// Only failed requests to the kube API, missing permissions, and pods that can't be scheduled
+    /// or whose image can't be pulled are the cluster's. Everything else is the test's, e.g.
+    /// crashing containers and timeouts waiting on the nodes or the chain.
+    pub fn is_infra(&self) -> bool {
+        match self {
+            K8sError::Api { .. } | K8sError::Forbidden { .. } => true,
+            K8sError::ContainerFailing { reason, .. } => INFRA_REASONS.contains(&reason.as_str()),
+            // e.g. a pod that never got Ready because no node could fit it
+            K8sError::Timeout { message, .. } => {
+                INFRA_REASONS.iter().any(|reason| message.contains(reason))
+            },
+            _ => false,
+        }
+    }
+
+    /// Whether a K8sError::Forbidden caused the given error. Retrying such errors is pointless.
+    pub fn is_forbidden(error: &anyhow::Error) -> bool {
+        error.chain().any(|e| {
+            matches!(
+                e.downcast_ref::<K8sError>(),
+                Some(K8sError::Forbidden { .. })
+            )
+        })
+    }
+
+    /// Whether a K8sError::ContainerFailing caused the given error. Such containers need a fix, e.g.
+    /// more memory or an image that exists, so they are not worth waiting on or retrying.
+    pub fn is_container_failing(error: &anyhow::Error) -> bool {
+        error.chain().any(|e| {
+            matches!(
+                e.downcast_ref::<K8sError>(),
+                Some(K8sError::ContainerFailing { .. })
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+    use kube::error::ErrorResponse;
+    use std::{os::unix::process::ExitStatusExt, process::ExitStatus};
+
+    fn kube_api_error(code: u16) -> KubeError {
+        KubeError::Api(ErrorResponse {
+            status: "Failure".to_string(),
+            message: "denied".to_string(),
+            reason: "".to_string(),
+            code,
+        })
+    }
+
+    fn kubectl_output(stderr: &str) -> Output {
+        Output {
+            status: ExitStatus::from_raw(1 << 8),
+            stdout: vec![],
+            stderr: stderr.as_bytes().to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_from_kube() {
+        assert!(matches!(
+            K8sError::from_kube("pod", kube_api_error(404)),
+            K8sError::NotFound { .. }
+        ));
+        assert!(matches!(
+            K8sError::from_kube("pod", kube_api_error(403)),
+            K8sError::Forbidden { .. }
+        ));
+        assert!(matches!(
+            K8sError::from_kube("pod", kube_api_error(500)),
+            K8sError::Api { .. }
+        ));
+    }
+
+    #[test]
+    fn test_from_kubectl() {
+        let args = ["get", "pod/aptos-node-0-validator-0"];
+        assert!(matches!(
+            K8sError::from_kubectl(
+                &args,
+                &kubectl_output(
+                    "Error from server (NotFound): pods \"aptos-node-0-validator-0\" not found"
+                )
+            ),
+            K8sError::NotFound { .. }
+        ));
+        assert!(matches!(
+            K8sError::from_kubectl(
+                &args,
+                &kubectl_output("Error from server (Forbidden): pods is forbidden")
+            ),
+            K8sError::Forbidden { .. }
+        ));
+        assert!(matches!(
+            K8sError::from_kubectl(&args, &kubectl_output("error: unknown flag")),
+            K8sError::CommandFailed { .. }
+        ));
+    }
+
+    #[test]
+    fn test_is_infra() {
+        assert!(K8sError::from_kube("pod", kube_api_error(500)).is_infra());
+        assert!(K8sError::from_kube("pod", kube_api_error(403)).is_infra());
+        assert!(!K8sError::from_kube("pod", kube_api_error(404)).is_infra());
+        let timeout = |operation: &str, message: &str| K8sError::Timeout {
+            operation: operation.to_string(),
+            message: message.to_string(),
+        };
+        // waiting on the chain or on the nodes is the test's
+        assert!(!timeout("the validator set to have 5 validators", "it has 4").is_infra());
+        assert!(!timeout(
+            "pod aptos-node-0-validator-0 to be Ready",
+            "validator: running but not ready, 0 restarts"
+        )
+        .is_infra());
+        assert!(timeout(
+            "pod aptos-node-0-validator-0 to be Ready",
+            "pod is Pending without container statuses (Unschedulable: 0/3 nodes are available)"
+        )
+        .is_infra());
+        assert!(timeout(
+            "pod aptos-node-0-validator-0 to be Ready",
+            "validator: waiting (ErrImagePull: not found), 0 restarts"
+        )
+        .is_infra());
+        assert!(!K8sError::from_kubectl(&["exec"], &kubectl_output("error: exit 1")).is_infra());
+    }
+
+    #[test]
+    fn test_is_forbidden() {
+        let error: anyhow::Error = K8sError::from_kube("pod", kube_api_error(403)).into();
+        assert!(K8sError::is_forbidden(&error));
+        // still found when wrapped in more context
+        assert!(K8sError::is_forbidden(
+            &error.context("Failed to launch swarm")
+        ));
+
+        let error = Err::<(), _>(K8sError::from_kube("pod", kube_api_error(404)))
+            .context("Failed to launch swarm")
+            .unwrap_err();
+        assert!(!K8sError::is_forbidden(&error));
+    }
+}
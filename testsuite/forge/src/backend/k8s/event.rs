@@ -0,0 +1,414 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{K8sError, Result};
+use aptos_logger::{info, warn};
+use futures::StreamExt;
+use k8s_openapi::{
+    api::core::v1::Event,
+    chrono::{DateTime, Utc},
+};
+use kube::{
+    api::{Api, ListParams, WatchEvent},
+    client::Client as K8sClient,
+    Error as KubeError,
+};
+use std::{
+    fmt::{Display, Formatter},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::task::JoinHandle;
+
+// how many of the latest Warning events to include in error messages
+pub(crate) const MAX_REPORTED_WARNINGS: usize = 5;
+// the oldest of the watched events are dropped beyond these, e.g. for a node in a crash loop
+const MAX_WATCHED_EVENTS: usize = 1000;
+// the API server ends watches after this, and the watch is resumed
+const WATCH_TIMEOUT_SECS: u32 = 290;
+const WATCH_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// A k8s event about one of a node's objects, e.g. FailedScheduling or FailedAttachVolume. These
+/// are what `kubectl describe` shows at the bottom.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct K8sEvent {
+    // e.g. "Pod/aptos-node-0-validator-0"
+    pub object: String,
+    pub type_: String,
+    pub reason: String,
+    pub message: String,
+    // how many times the event occurred, k8s deduplicates repeated events
+    pub count: i32,
+    pub last_timestamp: Option<DateTime<Utc>>,
+}
+
+impl K8sEvent {
+    pub fn is_warning(&self) -> bool {
+        self.type_ == "Warning"
+    }
+}
+
+impl From<Event> for K8sEvent {
+    fn from(event: Event) -> Self {
+        let object = format!(
+            "{}/{}",
+            event.involved_object.kind.unwrap_or_default(),
+            event.involved_object.name.unwrap_or_default()
+        );
+        // newer components only set the eventTime
+        let last_timestamp = event
+            .last_timestamp
+            .map(|time| time.0)
+            .or_else(|| event.event_time.map(|time| time.0))
+            .or_else(|| event.first_timestamp.map(|time| time.0));
+        Self {
+            object,
+            type_: event.type_.unwrap_or_default(),
+            reason: event.reason.unwrap_or_default(),
+            message: event.message.unwrap_or_default(),
+            count: event.count.unwrap_or(1),
+            last_timestamp,
+        }
+    }
+}
+
+impl Display for K8sEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.last_timestamp {
+            Some(time) => write!(f, "{} ", time.to_rfc3339())?,
+            None => write!(f, "<unknown time> ")?,
+        }
+        write!(f, "{} {} {}", self.type_, self.reason, self.object)?;
+        if self.count > 1 {
+            write!(f, " (x{})", self.count)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// Fetch the events of the named objects in the namespace, oldest first
+pub(crate) async fn fetch_events(
+    kube_client: &K8sClient,
+    kube_namespace: &str,
+    object_names: &[String],
+) -> Result<Vec<K8sEvent>> {
+    let event_api: Api<Event> = Api::namespaced(kube_client.clone(), kube_namespace);
+    let mut events = vec![];
+    // field selectors can't OR, so list the events of each object separately
+    for name in object_names {
+        let list_params = ListParams::default().fields(&format!("involvedObject.name={}", name));
+        let object_events = event_api
+            .list(&list_params)
+            .await
+            .map_err(|e| K8sError::from_kube(format!("events of {}", name), e))?
+            .items;
+        events.extend(object_events.into_iter().map(K8sEvent::from));
+    }
+    sort_events(&mut events);
+    Ok(events)
+}
+
+fn sort_events(events: &mut [K8sEvent]) {
+    // events without a timestamp go first
+    events.sort_by(|a, b| a.last_timestamp.cmp(&b.last_timestamp));
+}
+
+/// The latest `max` Warning events, oldest first
+pub(crate) fn last_warnings(events: &[K8sEvent], max: usize) -> Vec<&K8sEvent> {
+    let warnings: Vec<_> = events.iter().filter(|event| event.is_warning()).collect();
+    warnings[warnings.len().saturating_sub(max)..].to_vec()
+}
+
+/// One event per line, for error messages and logs
+pub(crate) fn describe_events<'a>(events: impl IntoIterator<Item = &'a K8sEvent>) -> String {
+    let lines: Vec<_> = events
+        .into_iter()
+        .map(|event| format!("  {}", event))
+        .collect();
+    if lines.is_empty() {
+        "  <no events>".to_string()
+    } else {
+        lines.join("\n")
+    }
+}
+
+/// A Warning event of the namespace during the test, with the node it is about, if any
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InfrastructureEvent {
+    pub node: Option<String>,
+    pub event: K8sEvent,
+}
+
+impl Display for InfrastructureEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.node {
+            Some(node) => write!(f, "[{}] {}", node, self.event),
+            None => write!(f, "{}", self.event),
+        }
+    }
+}
+
+/// Watches the events of the namespace in the background, logging and recording the Warning
+/// ones, e.g. pod evictions, volume failures and scheduling problems. It never fails the test,
+/// failures to watch are only logged and retried.
+pub struct EventWatcher {
+    // the StatefulSet and node names the events are attributed with
+    nodes: Arc<Mutex<Vec<(String, String)>>>,
+    events: Arc<Mutex<Vec<InfrastructureEvent>>>,
+    task: JoinHandle<()>,
+}
+
+impl EventWatcher {
+    /// `nodes` are the StatefulSet and node names the events are attributed with
+    pub fn start(
+        kube_client: K8sClient,
+        kube_namespace: &str,
+        nodes: Vec<(String, String)>,
+    ) -> Self {
+        let nodes = Arc::new(Mutex::new(nodes));
+        let events = Arc::new(Mutex::new(vec![]));
+        let task = tokio::spawn(watch_events(
+            Api::namespaced(kube_client, kube_namespace),
+            nodes.clone(),
+            events.clone(),
+        ));
+        Self {
+            nodes,
+            events,
+            task,
+        }
+    }
+
+    /// Attribute the events of the StatefulSet to the node from now on, e.g. of one added to
+    /// the swarm during the test
+    pub fn add_node(&self, stateful_set_name: &str, node_name: &str) {
+        self.nodes
+            .lock()
+            .unwrap()
+            .push((stateful_set_name.to_string(), node_name.to_string()));
+    }
+
+    /// The Warning events so far, oldest first
+    pub fn events(&self) -> Vec<InfrastructureEvent> {
+        self.events.lock().unwrap().clone()
+    }
+}
+
+impl Drop for EventWatcher {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn watch_events(
+    event_api: Api<Event>,
+    nodes: Arc<Mutex<Vec<(String, String)>>>,
+    events: Arc<Mutex<Vec<InfrastructureEvent>>>,
+) {
+    // where to resume the watch from, none to start from the current events
+    let mut resource_version = None;
+    loop {
+        let version = match resource_version.take() {
+            Some(version) => version,
+            // only the events from now on
+            None => match event_api.list(&ListParams::default().limit(1)).await {
+                Ok(list) => list.metadata.resource_version.unwrap_or_default(),
+                Err(e) => {
+                    info!("Failed to list the events to watch: {}", e);
+                    tokio::time::sleep(WATCH_RETRY_DELAY).await;
+                    continue;
+                },
+            },
+        };
+        let list_params = ListParams::default().timeout(WATCH_TIMEOUT_SECS);
+        let stream = match event_api.watch(&list_params, &version).await {
+            Ok(stream) => stream,
+            Err(e) => {
+                info!("Failed to watch the events: {}", e);
+                if !is_gone(&e) {
+                    resource_version = Some(version.clone());
+                }
+                tokio::time::sleep(WATCH_RETRY_DELAY).await;
+                continue;
+            },
+        };
+        futures::pin_mut!(stream);
+        // resumed from the last event seen, unless the watch expired
+        resource_version = Some(version.clone());
+        while let Some(watch_event) = stream.next().await {
+            match watch_event {
+                Ok(WatchEvent::Added(event)) | Ok(WatchEvent::Modified(event)) => {
+                    if let Some(version) = event.metadata.resource_version.clone() {
+                        resource_version = Some(version);
+                    }
+                    let event = attribute_event(K8sEvent::from(event), &nodes.lock().unwrap());
+                    if event.event.is_warning()
+                        && record_event(&mut events.lock().unwrap(), event.clone())
+                    {
+                        warn!("Infrastructure event: {}", event);
+                    }
+                },
+                Ok(WatchEvent::Deleted(_)) => {},
+                Ok(WatchEvent::Bookmark(bookmark)) => {
+                    resource_version = Some(bookmark.metadata.resource_version);
+                },
+                // the resource version is too old, start over from the current events
+                Ok(WatchEvent::Error(e)) if e.code == 410 => {
+                    resource_version = None;
+                    break;
+                },
+                Ok(WatchEvent::Error(e)) => {
+                    info!("Error watching the events: {}", e);
+                    tokio::time::sleep(WATCH_RETRY_DELAY).await;
+                    break;
+                },
+                Err(e) => {
+                    info!("Error watching the events: {}", e);
+                    if is_gone(&e) {
+                        resource_version = None;
+                    }
+                    tokio::time::sleep(WATCH_RETRY_DELAY).await;
+                    break;
+                },
+            }
+        }
+    }
+}
+
+fn is_gone(e: &KubeError) -> bool {
+    matches!(e, KubeError::Api(response) if response.code == 410)
+}
+
+/// The node of the longest StatefulSet name the event's object is named after, e.g. of a pod or
+/// its volume claims
+fn attribute_event(event: K8sEvent, nodes: &[(String, String)]) -> InfrastructureEvent {
+    let object_name = event.object.split_once('/').map_or("", |(_, name)| name);
+    let node = nodes
+        .iter()
+        .filter(|(stateful_set, _)| object_name.contains(stateful_set.as_str()))
+        .max_by_key(|(stateful_set, _)| stateful_set.len())
+        .map(|(_, node)| node.clone());
+    InfrastructureEvent { node, event }
+}
+
+/// Records the event, in place of the earlier occurrences of it, which k8s keeps counting in
+/// the same event. Returns whether it was not recorded yet.
+fn record_event(events: &mut Vec<InfrastructureEvent>, event: InfrastructureEvent) -> bool {
+    let same = |recorded: &InfrastructureEvent| {
+        recorded.event.object == event.event.object
+            && recorded.event.reason == event.event.reason
+            && recorded.event.message == event.event.message
+    };
+    if let Some(index) = events.iter().position(same) {
+        if events[index] == event {
+            return false;
+        }
+        events.remove(index);
+    }
+    events.push(event);
+    if events.len() > MAX_WATCHED_EVENTS {
+        events.remove(0);
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use k8s_openapi::{
+        api::core::v1::ObjectReference, apimachinery::pkg::apis::meta::v1::Time, chrono::TimeZone,
+    };
+
+    fn event(type_: &str, reason: &str, secs: i64) -> K8sEvent {
+        K8sEvent {
+            object: "Pod/aptos-node-0-validator-0".to_string(),
+            type_: type_.to_string(),
+            reason: reason.to_string(),
+            message: format!("{} happened", reason),
+            count: 1,
+            last_timestamp: Some(Utc.timestamp_opt(secs, 0).unwrap()),
+        }
+    }
+
+    #[test]
+    fn test_from_event() {
+        let event = K8sEvent::from(Event {
+            involved_object: ObjectReference {
+                kind: Some("PersistentVolumeClaim".to_string()),
+                name: Some("aptos-node-0-validator-e1".to_string()),
+                ..ObjectReference::default()
+            },
+            type_: Some("Warning".to_string()),
+            reason: Some("ProvisioningFailed".to_string()),
+            message: Some("storageclass not found".to_string()),
+            count: Some(3),
+            first_timestamp: Some(Time(Utc.timestamp_opt(0, 0).unwrap())),
+            ..Event::default()
+        });
+        assert!(event.is_warning());
+        assert_eq!(
+            event.to_string(),
+            "1970-01-01T00:00:00+00:00 Warning ProvisioningFailed \
+             PersistentVolumeClaim/aptos-node-0-validator-e1 (x3): storageclass not found"
+        );
+    }
+
+    #[test]
+    fn test_last_warnings() {
+        let mut events = vec![
+            event("Warning", "BackOff", 40),
+            event("Normal", "Scheduled", 10),
+            event("Warning", "FailedScheduling", 5),
+            event("Warning", "Failed", 30),
+            event("Normal", "Pulling", 20),
+        ];
+        sort_events(&mut events);
+        let reasons: Vec<_> = last_warnings(&events, 2)
+            .into_iter()
+            .map(|event| event.reason.as_str())
+            .collect();
+        assert_eq!(reasons, vec!["Failed", "BackOff"]);
+        assert_eq!(last_warnings(&events, 10).len(), 3);
+
+        assert_eq!(describe_events(&[]), "  <no events>");
+        assert_eq!(
+            describe_events(last_warnings(&events, 1)),
+            "  1970-01-01T00:00:40+00:00 Warning BackOff Pod/aptos-node-0-validator-0: BackOff happened"
+        );
+    }
+
+    #[test]
+    fn test_record_event() {
+        let nodes = vec![
+            (
+                "aptos-node-0-validator".to_string(),
+                "validator-0".to_string(),
+            ),
+            (
+                "aptos-node-0-fullnode-e1".to_string(),
+                "fullnode-0".to_string(),
+            ),
+        ];
+        let mut evicted = event("Warning", "Evicted", 10);
+        evicted.object = "Pod/aptos-node-0-fullnode-e1-0".to_string();
+        let evicted = attribute_event(evicted, &nodes);
+        assert_eq!(evicted.node.as_deref(), Some("fullnode-0"));
+        let scheduling = attribute_event(event("Warning", "FailedScheduling", 20), &nodes);
+        assert_eq!(scheduling.node.as_deref(), Some("validator-0"));
+        assert_eq!(
+            attribute_event(event("Warning", "BackOff", 5), &[]).to_string(),
+            "1970-01-01T00:00:05+00:00 Warning BackOff Pod/aptos-node-0-validator-0: BackOff happened"
+        );
+
+        let mut events = vec![];
+        assert!(record_event(&mut events, evicted.clone()));
+        assert!(record_event(&mut events, scheduling.clone()));
+        // a watch resumed from an older version sees the same events again
+        assert!(!record_event(&mut events, evicted.clone()));
+        let mut repeated = scheduling;
+        repeated.event.count = 4;
+        assert!(record_event(&mut events, repeated));
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[1].event.count, 4);
+    }
+}
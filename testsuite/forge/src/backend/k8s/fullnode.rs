@@ -2,21 +2,24 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::{
-    get_stateful_set_image, make_k8s_label, K8sNode, ReadWrite, Result, Version,
-    DEFAULT_TEST_SUITE_NAME, DEFAULT_USERNAME, REST_API_SERVICE_PORT,
-    VALIDATOR_0_DATA_PERSISTENT_VOLUME_CLAIM_PREFIX, VALIDATOR_0_GENESIS_SECRET_PREFIX,
-    VALIDATOR_0_STATEFUL_SET_NAME,
+    add_era_label, backend::k8s::node::stateful_set_resources, delete_if_exists,
+    get_stateful_set_image, make_k8s_label, K8sBackendConfig, K8sNode, ReadWrite, Result,
+    ValidatorResourceNames, Version, DEFAULT_LEDGER_STALENESS_THRESHOLD, DEFAULT_TEST_SUITE_NAME,
+    DEFAULT_USERNAME, NODE_METRIC_PORT, REST_API_HAPROXY_SERVICE_PORT, REST_API_SERVICE_PORT,
 };
-use anyhow::Context;
+use anyhow::{format_err, Context};
 use aptos_config::{
     config::{
-        ApiConfig, BaseConfig, DiscoveryMethod, ExecutionConfig, NetworkConfig, NodeConfig,
-        OverrideNodeConfig, RoleType, WaypointConfig,
+        ApiConfig, BaseConfig, BootstrappingMode, DiscoveryMethod, ExecutionConfig, Identity,
+        NetworkConfig, NodeConfig, OverrideNodeConfig, Peer, PeerRole, RoleType, WaypointConfig,
     },
     network_id::NetworkId,
 };
 use aptos_logger::info;
-use aptos_sdk::types::PeerId;
+use aptos_sdk::{
+    crypto::x25519,
+    types::{account_address::from_identity_public_key, network_address::NetworkAddress, PeerId},
+};
 use aptos_short_hex_str::AsShortHexStr;
 use k8s_openapi::{
     api::{
@@ -29,13 +32,18 @@ use k8s_openapi::{
     },
     apimachinery::pkg::apis::meta::v1::LabelSelector,
 };
-use kube::api::{ObjectMeta, PostParams};
+use kube::{
+    api::{Api, ObjectMeta, PostParams},
+    client::Client as K8sClient,
+};
 use std::{
     collections::BTreeMap,
     env,
     net::{Ipv4Addr, SocketAddr, SocketAddrV4},
     path::PathBuf,
-    sync::{atomic::AtomicU32, Arc},
+    str::FromStr,
+    sync::{atomic::AtomicU32, Arc, Mutex},
+    time::Duration,
 };
 use tempfile::TempDir;
 
@@ -59,6 +67,118 @@ const APTOS_CONFIG_VOLUME_PATH: &str = "/opt/aptos/etc";
 const APTOS_DATA_VOLUME_NAME: &str = "aptos-data";
 const APTOS_DATA_VOLUME_PATH: &str = "/opt/aptos/data";
 
+// the validators' identity on their VFN network, which is the same for all of them
+// see terraform/helm/aptos-node/files/configs/validator-base.yaml
+const VALIDATOR_VFN_PEER_ID: &str =
+    "00000000000000000000000000000000d58bc7bb154b38039bc9096ce04e1237";
+const VALIDATOR_VFN_PUBLIC_KEY: &str =
+    "f0274c2774519281a8332d0bb9d8101bd58bc7bb154b38039bc9096ce04e1237";
+const VFN_NETWORK_PORT: u16 = 6181;
+
+// how long a fullnode added to a running swarm gets to catch up, unless configured otherwise
+const DEFAULT_FULLNODE_HEALTH_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// The names of the k8s resources forge creates for a fullnode of the given name
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FullNodeResourceNames {
+    pub stateful_set: String,
+    // the same as the StatefulSet's
+    pub service: String,
+    pub config_map: String,
+    // created by the StatefulSet from its volume claim template, and left behind when it's deleted
+    pub data_volume: String,
+}
+
+impl FullNodeResourceNames {
+    pub fn new(fullnode_name: String) -> Self {
+        Self {
+            service: fullnode_name.clone(),
+            config_map: format!("{}-config", fullnode_name),
+            data_volume: format!("{}-{}-0", APTOS_DATA_VOLUME_NAME, fullnode_name),
+            stateful_set: fullnode_name,
+        }
+    }
+
+    /// The names of a fullnode attached to the validator of the given index
+    pub fn attached_to_validator(validator_index: usize, peer_id: PeerId) -> Self {
+        Self::new(format!(
+            "fullnode-{}-{}",
+            validator_index,
+            peer_id.short_str()
+        ))
+    }
+}
+
+/// How to run a fullnode attached to a validator of a running swarm
+#[derive(Clone, Debug)]
+pub struct FullNodeConfig {
+    // the image is that of the validators, with the version as tag
+    pub version: Version,
+    // e.g. DownloadLatestStates to bootstrap from the latest states rather than from genesis
+    pub bootstrapping_mode: BootstrappingMode,
+    // the rest of the config. The VFN network to the validator and the bootstrapping mode are
+    // set on top of it.
+    pub node_config: NodeConfig,
+    pub health_timeout: Duration,
+}
+
+impl FullNodeConfig {
+    /// A fullnode that syncs from genesis, with no networks but the one to its validator
+    pub fn new(version: Version) -> Self {
+        Self {
+            version,
+            bootstrapping_mode: BootstrappingMode::ExecuteOrApplyFromGenesis,
+            node_config: NodeConfig {
+                full_node_networks: vec![],
+                ..get_default_pfn_node_config()
+            },
+            health_timeout: DEFAULT_FULLNODE_HEALTH_TIMEOUT,
+        }
+    }
+
+    pub fn with_bootstrapping_mode(mut self, bootstrapping_mode: BootstrappingMode) -> Self {
+        self.bootstrapping_mode = bootstrapping_mode;
+        self
+    }
+
+    pub fn with_node_config(mut self, node_config: NodeConfig) -> Self {
+        self.node_config = node_config;
+        self
+    }
+
+    /// The NodeConfig of the fullnode, connecting to the validator with the given identity
+    fn node_config_for(
+        &self,
+        validator: &ValidatorResourceNames,
+        namespace: &str,
+        identity_key: x25519::PrivateKey,
+        peer_id: PeerId,
+    ) -> Result<NodeConfig> {
+        let validator_address = NetworkAddress::from_str(&format!(
+            "/dns/{}.{}.svc/tcp/{}/noise-ik/{}/handshake/0",
+            validator.service, namespace, VFN_NETWORK_PORT, VALIDATOR_VFN_PUBLIC_KEY
+        ))?;
+        let mut vfn_network = NetworkConfig::network_with_id(NetworkId::Vfn);
+        vfn_network.listen_address =
+            NetworkAddress::from_str(&format!("/ip4/0.0.0.0/tcp/{}", VFN_NETWORK_PORT))?;
+        vfn_network.identity = Identity::from_config(identity_key, peer_id);
+        vfn_network.seeds = [(
+            PeerId::from_hex_literal(&format!("0x{}", VALIDATOR_VFN_PEER_ID))?,
+            Peer::from_addrs(PeerRole::Validator, vec![validator_address]),
+        )]
+        .into_iter()
+        .collect();
+
+        let mut node_config = self.node_config.clone();
+        node_config
+            .full_node_networks
+            .retain(|network| network.network_id != NetworkId::Vfn);
+        node_config.full_node_networks.insert(0, vfn_network);
+        node_config.state_sync.state_sync_driver.bootstrapping_mode = self.bootstrapping_mode;
+        Ok(node_config)
+    }
+}
+
 /// Derive the fullnode image from the validator image. They will share the same image repo (validator), but not necessarily the version (image tag)
 fn get_fullnode_image_from_validator_image(
     validator_stateful_set: &StatefulSet,
@@ -296,6 +416,21 @@ fn create_fullnode_stateful_set(
     Ok(fullnode_stateful_set)
 }
 
+/// Label the StatefulSet, its pods and the volumes it claims with the era, so that they are swept
+/// with the other resources of the era, see [crate::FORGE_ERA_LABEL]
+fn add_stateful_set_era_labels(stateful_set: &mut StatefulSet, era: &str) {
+    add_era_label(&mut stateful_set.metadata.labels, era);
+    if let Some(spec) = stateful_set.spec.as_mut() {
+        add_era_label(&mut spec.selector.match_labels, era);
+        if let Some(metadata) = spec.template.metadata.as_mut() {
+            add_era_label(&mut metadata.labels, era);
+        }
+        for claim in spec.volume_claim_templates.iter_mut().flatten() {
+            add_era_label(&mut claim.metadata.labels, era);
+        }
+    }
+}
+
 /// Create a default PFN NodeConfig that uses the genesis, waypoint, and data paths expected in k8s
 pub fn get_default_pfn_node_config() -> NodeConfig {
     let mut waypoint_path = PathBuf::from(GENESIS_CONFIG_VOLUME_PATH);
@@ -333,6 +468,35 @@ pub fn get_default_pfn_node_config() -> NodeConfig {
     }
 }
 
+/// The NodeConfig of a public fullnode: the given one, with only its public network, which
+/// discovers the public networks of the validators' fullnodes from their on-chain addresses,
+/// unless it is seeded with them. The network gets the identity of the given key, whose PeerId is
+/// returned with the config.
+pub fn public_fullnode_node_config(
+    mut node_config: NodeConfig,
+    identity_key: x25519::PrivateKey,
+) -> (PeerId, NodeConfig) {
+    let peer_id = from_identity_public_key(identity_key.public_key());
+    node_config
+        .full_node_networks
+        .retain(|network| network.network_id == NetworkId::Public);
+    if node_config.full_node_networks.is_empty() {
+        node_config
+            .full_node_networks
+            .push(NetworkConfig::network_with_id(NetworkId::Public));
+    }
+    let public_network = &mut node_config.full_node_networks[0];
+    if public_network.seeds.is_empty()
+        && public_network.seed_addrs.is_empty()
+        && public_network.discovery_methods.is_empty()
+    {
+        public_network.discovery_method = DiscoveryMethod::Onchain;
+    }
+    public_network.identity = Identity::from_config(identity_key, peer_id);
+    node_config.base.role = RoleType::FullNode;
+    (peer_id, node_config)
+}
+
 /// Create a PFN stateful set workload
 /// This function assumes that the swarm has already been set up (e.g. there are already validators running) as it borrows
 /// some artifacts such as genesis from the 0th validator
@@ -346,6 +510,7 @@ pub async fn install_public_fullnode<'a>(
     node_config: &'a OverrideNodeConfig,
     era: String,
     namespace: String,
+    backend_config: Arc<K8sBackendConfig>,
     use_port_forward: bool,
     index: usize,
 ) -> Result<(PeerId, K8sNode)> {
@@ -354,30 +519,107 @@ pub async fn install_public_fullnode<'a>(
         .get_peer_id()
         .unwrap_or_else(PeerId::random);
     let fullnode_name = format!("public-fullnode-{}-{}", index, node_peer_id.short_str());
+    let node = install_fullnode(
+        stateful_set_api,
+        configmap_api,
+        persistent_volume_claim_api,
+        service_api,
+        &FullNodeResourceNames::new(fullnode_name),
+        node_peer_id,
+        &ValidatorResourceNames::new(0, &era),
+        version,
+        node_config,
+        namespace,
+        backend_config,
+        use_port_forward,
+        index,
+    )
+    .await?;
+    Ok((node_peer_id, node))
+}
+
+/// Create a fullnode that syncs from the validator of the given index, over the validator's VFN
+/// network. The fullnode gets the genesis of the validator, as well as its image repo and
+/// scheduling. Its PeerId is that of the given identity key. Resources created before a failure
+/// are not deleted, see [delete_fullnode_resources].
+pub async fn install_validator_attached_fullnode(
+    stateful_set_api: Arc<dyn ReadWrite<StatefulSet>>,
+    configmap_api: Arc<dyn ReadWrite<ConfigMap>>,
+    persistent_volume_claim_api: Arc<dyn ReadWrite<PersistentVolumeClaim>>,
+    service_api: Arc<dyn ReadWrite<Service>>,
+    validator_index: usize,
+    era: &str,
+    config: &FullNodeConfig,
+    identity_key: x25519::PrivateKey,
+    namespace: String,
+    backend_config: Arc<K8sBackendConfig>,
+    use_port_forward: bool,
+    index: usize,
+) -> Result<K8sNode> {
+    let node_peer_id = from_identity_public_key(identity_key.public_key());
+    let validator = ValidatorResourceNames::new(validator_index, era);
+    let node_config = config.node_config_for(&validator, &namespace, identity_key, node_peer_id)?;
+    install_fullnode(
+        stateful_set_api,
+        configmap_api,
+        persistent_volume_claim_api,
+        service_api,
+        &FullNodeResourceNames::attached_to_validator(validator_index, node_peer_id),
+        node_peer_id,
+        &validator,
+        &config.version,
+        &OverrideNodeConfig::new_with_default_base(node_config),
+        namespace,
+        backend_config,
+        use_port_forward,
+        index,
+    )
+    .await
+}
+
+/// Create a fullnode StatefulSet, its Service and the ConfigMap of its NodeConfig, from the
+/// StatefulSet, genesis and data volume of the template validator
+async fn install_fullnode(
+    stateful_set_api: Arc<dyn ReadWrite<StatefulSet>>,
+    configmap_api: Arc<dyn ReadWrite<ConfigMap>>,
+    persistent_volume_claim_api: Arc<dyn ReadWrite<PersistentVolumeClaim>>,
+    service_api: Arc<dyn ReadWrite<Service>>,
+    names: &FullNodeResourceNames,
+    node_peer_id: PeerId,
+    template: &ValidatorResourceNames,
+    version: &Version,
+    node_config: &OverrideNodeConfig,
+    namespace: String,
+    backend_config: Arc<K8sBackendConfig>,
+    use_port_forward: bool,
+    index: usize,
+) -> Result<K8sNode> {
+    let fullnode_name = names.stateful_set.clone();
 
     // create the NodeConfig configmap
-    let fullnode_node_config_config_map_name = format!("{}-config", fullnode_name.clone());
-    let fullnode_node_config_config_map =
+    let fullnode_node_config_config_map_name = names.config_map.clone();
+    let mut fullnode_node_config_config_map =
         create_node_config_configmap(fullnode_node_config_config_map_name.clone(), node_config)
             .await?;
+    add_era_label(
+        &mut fullnode_node_config_config_map.metadata.labels,
+        &template.era,
+    );
     configmap_api
         .create(&PostParams::default(), &fullnode_node_config_config_map)
         .await?;
 
-    // assume that the validator workload (val0) has already been created (not necessarily running yet)
+    // assume that the validator workload has already been created (not necessarily running yet)
     // get its spec so we can inherit some of its properties
-    let validator_stateful_set = stateful_set_api.get(VALIDATOR_0_STATEFUL_SET_NAME).await?;
+    let validator_stateful_set = stateful_set_api.get(&template.stateful_set).await?;
 
     // get the fullnode image
     let fullnode_image_full =
         get_fullnode_image_from_validator_image(&validator_stateful_set, version)?;
 
-    // borrow genesis secret from the first validator. it follows this naming convention
-    let fullnode_genesis_secret_name = format!("{}-e{}", VALIDATOR_0_GENESIS_SECRET_PREFIX, era);
-    let validator_data_persistent_volume_claim_name = format!(
-        "{}-e{}",
-        VALIDATOR_0_DATA_PERSISTENT_VOLUME_CLAIM_PREFIX, era
-    );
+    // borrow genesis secret from the validator
+    let fullnode_genesis_secret_name = template.genesis_secret.clone();
+    let validator_data_persistent_volume_claim_name = template.data_volume.clone();
 
     // create the data volume
     let validator_data_volume = persistent_volume_claim_api
@@ -391,7 +633,7 @@ pub async fn install_public_fullnode<'a>(
             )
         })?;
 
-    let fullnode_stateful_set = create_fullnode_stateful_set(
+    let mut fullnode_stateful_set = create_fullnode_stateful_set(
         fullnode_name.clone(),
         fullnode_image_full,
         fullnode_genesis_secret_name,
@@ -399,6 +641,7 @@ pub async fn install_public_fullnode<'a>(
         validator_stateful_set,
         validator_data_volume,
     )?;
+    add_stateful_set_era_labels(&mut fullnode_stateful_set, &template.era);
 
     // check that all the labels are the same
     let fullnode_metadata_labels = fullnode_stateful_set
@@ -437,7 +680,8 @@ pub async fn install_public_fullnode<'a>(
         }
     }
 
-    let fullnode_service = create_fullnode_service(fullnode_name.clone())?;
+    let mut fullnode_service = create_fullnode_service(fullnode_name.clone())?;
+    add_era_label(&mut fullnode_service.metadata.labels, &template.era);
 
     // write the spec to file
     let tmp_dir = TempDir::new().expect("Could not create temp dir");
@@ -490,24 +734,88 @@ pub async fn install_public_fullnode<'a>(
         full_service_name
     };
 
+    let resources = stateful_set_resources(&fullnode_stateful_set);
     let ret_node = K8sNode {
         name: fullnode_name.clone(),
         stateful_set_name: fullnode_stateful_set
             .metadata
             .name
             .context("Fullnode StatefulSet does not have metadata.name")?,
+        replica_index: 0,
+        shares_stateful_set: false,
         peer_id: node_peer_id,
         index,
         service_name: full_service_name,
         version: version.clone(),
+        resources,
         namespace,
+        backend_config,
         haproxy_enabled: false,
 
         port_forward_enabled: use_port_forward,
+        // the fullnodes forge creates are reached through their Service, which never has TLS
+        rest_api_tls: None,
+        ledger_staleness_threshold: Some(DEFAULT_LEDGER_STALENESS_THRESHOLD),
+        clock_skew: Duration::ZERO,
+        memory_stress: None,
+        restarts_by_test: AtomicU32::new(0),
         rest_api_port: AtomicU32::new(REST_API_SERVICE_PORT), // in the case of port-forward, this port will be changed at runtime
+        rest_api_service_port: REST_API_SERVICE_PORT,
+        rest_api_haproxy_service_port: REST_API_HAPROXY_SERVICE_PORT,
+        inspection_service_port: AtomicU32::new(NODE_METRIC_PORT),
+        port_forwards: Mutex::new(Vec::new()),
+        metrics_port_forward: Mutex::new(None),
+        config: Mutex::new(None),
     };
 
-    Ok((node_peer_id, ret_node))
+    Ok(ret_node)
+}
+
+/// Delete the k8s resources forge created for a fullnode, skipping those that don't exist
+pub(crate) async fn delete_fullnode_resources(
+    kube_client: K8sClient,
+    namespace: &str,
+    names: &FullNodeResourceNames,
+) -> Result<()> {
+    let results = vec![
+        delete_if_exists(
+            Api::<StatefulSet>::namespaced(kube_client.clone(), namespace),
+            "StatefulSet",
+            &names.stateful_set,
+        )
+        .await,
+        delete_if_exists(
+            Api::<Service>::namespaced(kube_client.clone(), namespace),
+            "Service",
+            &names.service,
+        )
+        .await,
+        delete_if_exists(
+            Api::<ConfigMap>::namespaced(kube_client.clone(), namespace),
+            "ConfigMap",
+            &names.config_map,
+        )
+        .await,
+        delete_if_exists(
+            Api::<PersistentVolumeClaim>::namespaced(kube_client, namespace),
+            "PersistentVolumeClaim",
+            &names.data_volume,
+        )
+        .await,
+    ];
+    let errors: Vec<_> = results
+        .into_iter()
+        .filter_map(|result| result.err())
+        .map(|e| e.to_string())
+        .collect();
+    if !errors.is_empty() {
+        return Err(format_err!(
+            "Failed to delete the resources of fullnode {}: {}",
+            names.stateful_set,
+            errors.join("; ")
+        ));
+    }
+    Ok(())
 }
 
 #[cfg(test)]
@@ -720,6 +1028,33 @@ mod tests {
         );
     }
 
+    #[test]
+    /// Test that the StatefulSet, its pods and its volumes are labeled with the era alike
+    fn test_add_stateful_set_era_labels() {
+        let mut fullnode_stateful_set = create_fullnode_stateful_set(
+            "fullnode-0".to_string(),
+            "fruit.com/banana:latest".to_string(),
+            "aptos-node-0-genesis-e42069".to_string(),
+            "fullnode-0-config".to_string(),
+            get_dummy_validator_stateful_set(),
+            get_dummy_validator_persistent_volume_claim(),
+        )
+        .unwrap();
+        add_stateful_set_era_labels(&mut fullnode_stateful_set, "42069");
+
+        let labels = fullnode_stateful_set.metadata.labels.unwrap();
+        assert_eq!(labels[crate::FORGE_ERA_LABEL], "42069");
+        let spec = fullnode_stateful_set.spec.unwrap();
+        // the selector must stay the labels of the pods
+        assert_eq!(spec.selector.match_labels, Some(labels.clone()));
+        assert_eq!(spec.template.metadata.unwrap().labels, Some(labels));
+        let claim = &spec.volume_claim_templates.unwrap()[0];
+        assert_eq!(
+            claim.metadata.labels.as_ref().unwrap()[crate::FORGE_ERA_LABEL],
+            "42069"
+        );
+    }
+
     #[tokio::test]
     /// Full PFN installation test, checking that the resulting resources created are as expected
     async fn test_install_public_fullnode() {
@@ -756,6 +1091,7 @@ mod tests {
             &override_config,
             era,
             namespace,
+            Arc::new(K8sBackendConfig::default()),
             false,
             7,
         )
@@ -775,4 +1111,113 @@ mod tests {
         assert!(created_node.name.len() < 64); // This is a k8s limit
         assert_eq!(created_node.index, 7);
     }
+
+    #[test]
+    /// Test that a fullnode attached to a validator has a single VFN network, seeded with the validator
+    fn test_validator_attached_fullnode_node_config() {
+        let identity_key = PrivateKey::generate_for_testing();
+        let peer_id = from_identity_public_key(identity_key.public_key());
+        let config = FullNodeConfig::new(Version::new(0, "banana".to_string()))
+            .with_bootstrapping_mode(BootstrappingMode::DownloadLatestStates);
+
+        let node_config = config
+            .node_config_for(
+                &ValidatorResourceNames::new(3, "42069"),
+                "forge42069",
+                identity_key,
+                peer_id,
+            )
+            .unwrap();
+        assert_eq!(
+            node_config.state_sync.state_sync_driver.bootstrapping_mode,
+            BootstrappingMode::DownloadLatestStates
+        );
+        assert_eq!(node_config.full_node_networks.len(), 1);
+        let vfn_network = &node_config.full_node_networks[0];
+        assert_eq!(vfn_network.network_id, NetworkId::Vfn);
+        assert_eq!(vfn_network.peer_id(), peer_id);
+        let (validator_peer_id, validator) = vfn_network.seeds.iter().next().unwrap();
+        assert_eq!(
+            validator_peer_id.to_hex(),
+            VALIDATOR_VFN_PEER_ID.to_string()
+        );
+        assert_eq!(validator.role, PeerRole::Validator);
+        assert_eq!(
+            validator.addresses[0].to_string(),
+            format!(
+                "/dns/aptos-node-3-validator.forge42069.svc/tcp/6181/noise-ik/0x{}/handshake/0",
+                VALIDATOR_VFN_PUBLIC_KEY
+            )
+        );
+    }
+
+    #[test]
+    /// Test that a public fullnode keeps only its public network, with its own identity
+    fn test_public_fullnode_node_config() {
+        let identity_key = PrivateKey::generate_for_testing();
+        let expected_peer_id = from_identity_public_key(identity_key.public_key());
+        let mut node_config = get_default_pfn_node_config();
+        node_config
+            .full_node_networks
+            .push(NetworkConfig::network_with_id(NetworkId::Vfn));
+
+        let (peer_id, node_config) = public_fullnode_node_config(node_config, identity_key);
+        assert_eq!(peer_id, expected_peer_id);
+        assert_eq!(node_config.get_peer_id(), Some(peer_id));
+        assert_eq!(node_config.full_node_networks.len(), 1);
+        let public_network = &node_config.full_node_networks[0];
+        assert_eq!(public_network.network_id, NetworkId::Public);
+        assert_eq!(public_network.discovery_method, DiscoveryMethod::Onchain);
+        assert_eq!(public_network.peer_id(), peer_id);
+
+        // without any network, the public network is added
+        let (_, node_config) = public_fullnode_node_config(
+            NodeConfig {
+                full_node_networks: vec![],
+                ..get_default_pfn_node_config()
+            },
+            PrivateKey::generate_for_testing(),
+        );
+        assert_eq!(node_config.full_node_networks.len(), 1);
+        assert_eq!(
+            node_config.full_node_networks[0].network_id,
+            NetworkId::Public
+        );
+    }
+
+    #[tokio::test]
+    /// Full installation test of a fullnode attached to a validator
+    async fn test_install_validator_attached_fullnode() {
+        let identity_key = PrivateKey::generate_for_testing();
+        let peer_id = from_identity_public_key(identity_key.public_key());
+        let node = install_validator_attached_fullnode(
+            Arc::new(MockStatefulSetApi::from_stateful_set(
+                get_dummy_validator_stateful_set(),
+            )),
+            Arc::new(MockConfigMapApi::from_config_map(ConfigMap::default())),
+            Arc::new(MockPersistentVolumeClaimApi::from_persistent_volume_claim(
+                get_dummy_validator_persistent_volume_claim(),
+            )),
+            Arc::new(MockServiceApi::from_service(Service::default())),
+            0,
+            "42069",
+            &FullNodeConfig::new(Version::new(0, "banana".to_string())),
+            identity_key,
+            "forge42069".to_string(),
+            Arc::new(K8sBackendConfig::default()),
+            false,
+            2,
+        )
+        .await
+        .unwrap();
+
+        let names = FullNodeResourceNames::attached_to_validator(0, peer_id);
+        assert_eq!(node.peer_id, peer_id);
+        assert_eq!(node.stateful_set_name, names.stateful_set);
+        assert_eq!(
+            names.data_volume,
+            format!("aptos-data-fullnode-0-{}-0", peer_id.short_str())
+        );
+        assert_eq!(node.index, 2);
+    }
 }
@@ -2,35 +2,67 @@
 // Parts of the project are originally copyright © Meta Platforms, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::{Factory, GenesisConfig, GenesisConfigFn, NodeConfigFn, Result, Swarm, Version};
+use crate::{
+    Factory, GenesisConfig, GenesisConfigFn, InfraContext, NodeConfigFn, NodeVersions, Result,
+    Swarm, SwarmExt, Version,
+};
 use anyhow::bail;
-use aptos_logger::info;
+use aptos_logger::{info, warn};
 use rand::rngs::StdRng;
-use std::{convert::TryInto, num::NonZeroUsize, time::Duration};
+use std::{
+    convert::TryInto,
+    num::NonZeroUsize,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
 
 pub mod chaos;
 pub mod chaos_schema;
 mod cluster_helper;
+mod config;
 pub mod constants;
+mod error;
+mod event;
 mod fullnode;
 pub mod kube_api;
 pub mod node;
+mod port_forward;
 pub mod prometheus;
+mod registry;
+mod resource_usage;
 mod stateful_set;
 mod swarm;
+mod upgrade;
+mod validator;
 
 use aptos_sdk::crypto::ed25519::ED25519_PRIVATE_KEY_LENGTH;
 pub use cluster_helper::*;
+pub use config::*;
 pub use constants::*;
+pub use error::*;
+pub use event::{EventWatcher, InfrastructureEvent, K8sEvent};
 pub use fullnode::*;
 #[cfg(test)]
 pub use kube_api::mocks::*;
 pub use kube_api::*;
-pub use node::K8sNode;
+pub use node::{
+    ClearStorageMode, DiskSpace, K8sNode, LocalPortForward, NodeIdentity, PodRestartCount,
+    RestApiTls,
+};
+pub use port_forward::*;
+pub use registry::*;
+pub use resource_usage::*;
 pub use stateful_set::*;
 pub use swarm::*;
+pub use upgrade::*;
+pub use validator::*;
 
 pub struct K8sFactory {
+    // the cluster the swarms are launched in, and how forge talks to it
+    backend_config: Arc<K8sBackendConfig>,
     root_key: [u8; ED25519_PRIVATE_KEY_LENGTH],
     image_tag: String,
     upgrade_image_tag: String,
@@ -39,10 +71,19 @@ pub struct K8sFactory {
     reuse: bool,
     keep: bool,
     enable_haproxy: bool,
+    rest_api_tls: Option<RestApiTls>,
+    // reuse the nodes even if they are not the swarm the test asks for
+    force_reuse: bool,
+    // how many times the swarms moved to a fresh namespace, see [Factory::use_fresh_namespace]
+    fresh_namespaces: AtomicUsize,
 }
 
+// for the nodes of a reused namespace to show progress
+const REUSE_LIVENESS_TIMEOUT: Duration = Duration::from_secs(120);
+
 impl K8sFactory {
     pub fn new(
+        backend_config: Arc<K8sBackendConfig>,
         kube_namespace: String,
         image_tag: String,
         upgrade_image_tag: String,
@@ -58,18 +99,21 @@ impl K8sFactory {
             "default" => {
                 info!("Using the default kubernetes namespace");
             },
-            s if s.starts_with("forge") => {
+            s if backend_config.is_forge_namespace(s) => {
                 info!("Using forge namespace: {}", s);
             },
             _ => {
                 bail!(
-                    "Invalid kubernetes namespace provided: {}. Use forge-*",
-                    kube_namespace
+                    "Invalid kubernetes namespace provided: {}. Use {}-*",
+                    kube_namespace,
+                    backend_config.namespace_prefix
                 );
             },
         }
+        info!("Using k8s backend config: {:?}", backend_config);
 
         Ok(Self {
+            backend_config,
             root_key,
             image_tag,
             upgrade_image_tag,
@@ -78,8 +122,33 @@ impl K8sFactory {
             reuse,
             keep,
             enable_haproxy,
+            rest_api_tls: None,
+            force_reuse: false,
+            fresh_namespaces: AtomicUsize::new(0),
         })
     }
+
+    /// With `reuse`, reuse the nodes of the namespace even if their count or versions differ
+    /// from those the test asks for
+    pub fn with_force_reuse(mut self, force_reuse: bool) -> Self {
+        self.force_reuse = force_reuse;
+        self
+    }
+
+    /// Reach the node REST APIs over HTTPS, for clusters that front them with TLS
+    pub fn with_rest_api_tls(mut self, rest_api_tls: RestApiTls) -> Self {
+        self.rest_api_tls = Some(rest_api_tls);
+        self
+    }
+
+    /// The namespace the next swarm is launched in, the given one until the swarms move to a
+    /// fresh one
+    fn kube_namespace(&self) -> String {
+        match self.fresh_namespaces.load(Ordering::Relaxed) {
+            0 => self.kube_namespace.clone(),
+            n => format!("{}-retry-{}", self.kube_namespace, n),
+        }
+    }
 }
 
 #[async_trait::async_trait]
@@ -92,6 +161,15 @@ impl Factory for K8sFactory {
         Box::new(version.into_iter())
     }
 
+    // a reused namespace has no fresh counterpart
+    fn use_fresh_namespace(&self) -> bool {
+        if self.reuse {
+            return false;
+        }
+        self.fresh_namespaces.fetch_add(1, Ordering::Relaxed);
+        true
+    }
+
     async fn launch_swarm(
         &self,
         _rng: &mut StdRng,
@@ -104,7 +182,21 @@ impl Factory for K8sFactory {
         genesis_config_fn: Option<GenesisConfigFn>,
         node_config_fn: Option<NodeConfigFn>,
         existing_db_tag: Option<String>,
+        node_versions: Option<&NodeVersions>,
     ) -> Result<Box<dyn Swarm>> {
+        let kube_namespace = self.kube_namespace();
+        // port-forwards of crashed runs would hold on to the local ports of new ones
+        cleanup_orphaned_port_forwards(&self.backend_config);
+        if let Some(node_versions) = node_versions {
+            let known_versions: Vec<_> = self.versions().collect();
+            if let Some(version) = node_versions
+                .versions()
+                .into_iter()
+                .find(|version| !known_versions.contains(version))
+            {
+                bail!("k8s forge backend does not know version {}", version);
+            }
+        }
         let genesis_modules_path = match genesis_config {
             Some(config) => match config {
                 GenesisConfig::Bundle(_) => {
@@ -115,13 +207,22 @@ impl Factory for K8sFactory {
             None => None,
         };
 
-        let kube_client = create_k8s_client().await?;
+        let kube_client = self
+            .backend_config
+            .create_client()
+            .await
+            .infra_context("Failed to create the kube client")?;
         let (new_era, validators, fullnodes) = if self.reuse {
+            // the namespace may have been deployed with other options than those of this run
+            let deployment = discover_deployment(kube_client.clone(), &kube_namespace).await?;
+            info!("Reusing namespace {}: {:?}", kube_namespace, deployment);
             let (validators, fullnodes) = match collect_running_nodes(
+                &self.backend_config,
                 &kube_client,
-                self.kube_namespace.clone(),
+                kube_namespace.clone(),
                 self.use_port_forward,
-                self.enable_haproxy,
+                deployment.haproxy_enabled,
+                self.rest_api_tls.clone(),
             )
             .await
             {
@@ -130,14 +231,55 @@ impl Factory for K8sFactory {
                     bail!(e);
                 },
             };
-            let new_era = None; // TODO: get the actual era
-            (new_era, validators, fullnodes)
+            let mismatches = reuse_mismatches(
+                &validators,
+                &fullnodes,
+                num_validators.get(),
+                num_fullnodes,
+                |index| {
+                    node_versions
+                        .and_then(|versions| versions.validator(index))
+                        .unwrap_or(init_version)
+                        .clone()
+                },
+                node_versions.map_or(init_version, |versions| &versions.fullnodes),
+            );
+            if !mismatches.is_empty() {
+                if !self.force_reuse {
+                    bail!(
+                        "Namespace {} does not run the requested swarm, force to reuse it anyway: {}",
+                        kube_namespace,
+                        mismatches.join(", ")
+                    );
+                }
+                warn!(
+                    "Reusing namespace {} anyway: {}",
+                    kube_namespace,
+                    mismatches.join(", ")
+                );
+            }
+            (deployment.era, validators, fullnodes)
         } else {
+            // pods whose image is missing would only fail to pull it once deployed
+            if self.backend_config.check_image_tags {
+                let helm_image_repos =
+                    node_image_repos(&self.backend_config, node_config_fn.clone())
+                        .infra_context("Failed to render the image repos of the nodes")?;
+                check_node_images(init_version, node_versions, helm_image_repos).await?;
+            }
             // clear the cluster of resources
-            delete_k8s_resources(kube_client.clone(), &self.kube_namespace).await?;
+            delete_k8s_resources(&self.backend_config, kube_client.clone(), &kube_namespace)
+                .await
+                .infra_context(format!("Failed to clear namespace {}", kube_namespace))?;
             // create the forge-management configmap before installing anything
-            create_management_configmap(self.kube_namespace.clone(), self.keep, cleanup_duration)
-                .await?;
+            create_management_configmap(
+                &self.backend_config,
+                kube_namespace.clone(),
+                self.keep,
+                cleanup_duration,
+            )
+            .await
+            .infra_context("Failed to create the management configmap")?;
             if let Some(existing_db_tag) = existing_db_tag {
                 // TODO(prod-eng): For now we are managing PVs out of forge, and bind them manually
                 // with the volume. Going forward we should consider automate this process.
@@ -156,7 +298,8 @@ impl Factory for K8sFactory {
             }
             // try installing testnet resources, but clean up if it fails
             match install_testnet_resources(
-                self.kube_namespace.clone(),
+                &self.backend_config,
+                kube_namespace.clone(),
                 num_validators.get(),
                 num_fullnodes,
                 format!("{}", init_version),
@@ -164,32 +307,63 @@ impl Factory for K8sFactory {
                 genesis_modules_path,
                 self.use_port_forward,
                 self.enable_haproxy,
+                self.rest_api_tls.clone(),
                 genesis_config_fn,
                 node_config_fn,
+                node_versions,
             )
             .await
             {
                 Ok(res) => (Some(res.0), res.1, res.2),
                 Err(e) => {
-                    uninstall_testnet_resources(self.kube_namespace.clone()).await?;
+                    uninstall_testnet_resources(&self.backend_config, kube_namespace.clone())
+                        .await
+                        .infra_context(format!(
+                            "Failed to uninstall namespace {}",
+                            kube_namespace
+                        ))?;
                     bail!(e);
                 },
             }
         };
 
         let swarm = K8sSwarm::new(
+            self.backend_config.clone(),
             &self.root_key,
             &self.image_tag,
             &self.upgrade_image_tag,
-            &self.kube_namespace,
+            &kube_namespace,
             validators,
             fullnodes,
             self.keep,
             new_era,
             self.use_port_forward,
         )
-        .await
-        .unwrap();
+        .await?;
+        // the images of the nodes on other versions or from other repos may not be of this chain
+        if node_versions.is_some() {
+            swarm.check_chain_ids().await?;
+        }
+        if self.reuse {
+            let not_on_chain = swarm.validators_not_on_chain().await?;
+            if !not_on_chain.is_empty() {
+                if !self.force_reuse {
+                    bail!(
+                        "Validators of namespace {} are not in the validator set: {}",
+                        kube_namespace,
+                        not_on_chain.join(", ")
+                    );
+                }
+                warn!(
+                    "Reusing namespace {} with validators not in the validator set: {}",
+                    kube_namespace,
+                    not_on_chain.join(", ")
+                );
+            }
+            swarm
+                .liveness_check(Instant::now() + REUSE_LIVENESS_TIMEOUT)
+                .await?;
+        }
         Ok(Box::new(swarm))
     }
 }
@@ -3,43 +3,266 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::{
-    backend::k8s::stateful_set, get_free_port, scale_stateful_set_replicas, FullNode,
-    HealthCheckError, Node, NodeExt, Result, Validator, Version, KUBECTL_BIN, LOCALHOST,
-    NODE_METRIC_PORT, REST_API_HAPROXY_SERVICE_PORT, REST_API_SERVICE_PORT,
+    backend::k8s::{
+        event::{describe_events, fetch_events, last_warnings, MAX_REPORTED_WARNINGS},
+        port_forward::{PortForward, PortForwardError},
+        resource_usage::{
+            fetch_container_resource_usage, fetch_container_resource_usage_by_labels,
+        },
+        stateful_set,
+    },
+    fetch_connected_peers, fetch_counter, genesis_secret_name, get_free_port,
+    scale_stateful_set_replicas, validator_identity_from_secret, DbSnapshotOptions, FullNode,
+    HealthCheckError, K8sBackendConfig, K8sError, K8sEvent, MetricsPortForward, Node,
+    NodeArtifacts, NodeExt, NodeResources, NodeRestarts, PodResourceUsage, ReservedPort,
+    RestClientOptions, Result, ServiceEndpoint, Validator, Version, ADMIN_SERVICE_PORT,
+    BACKUP_SERVICE_PORT, DEFAULT_LEDGER_STALENESS_THRESHOLD, HAPROXY_SERVICE_SUFFIX, LOCALHOST,
+    NODE_METRIC_PORT,
 };
-use anyhow::{anyhow, format_err};
-use aptos_config::config::NodeConfig;
-use aptos_db::common::{LEDGER_DB_NAME, STATE_MERKLE_DB_NAME};
-use aptos_logger::info;
-use aptos_rest_client::Client as RestClient;
-use aptos_sdk::types::PeerId;
-use aptos_state_sync_driver::metadata_storage::STATE_SYNC_DB_NAME;
-use reqwest::Url;
-use serde_json::Value;
+use again::RetryPolicy;
+use anyhow::{bail, format_err, Context};
+use aptos_config::{
+    config::{Identity, IdentityBlob, NodeConfig, PersistableConfig, SecureBackend},
+    network_id::NetworkId,
+};
+use aptos_logger::{info, warn};
+use aptos_rest_client::{AptosBaseUrl, Client as RestClient};
+use aptos_sdk::{
+    crypto::x25519,
+    types::{
+        account_address::from_identity_public_key, network_address::NetworkAddress,
+        on_chain_config::ValidatorSet, PeerId,
+    },
+};
+use k8s_openapi::{
+    api::{
+        apps::v1::StatefulSet,
+        core::v1::{
+            ConfigMap, Container, ContainerState, PersistentVolumeClaim, PersistentVolumeClaimSpec,
+            PersistentVolumeClaimVolumeSource, Pod, PodSpec, Secret, Service, Volume, VolumeMount,
+        },
+    },
+    apimachinery::pkg::{
+        api::resource::Quantity,
+        apis::meta::v1::{ObjectMeta, Status},
+        util::intstr::IntOrString,
+    },
+    chrono::{DateTime, Utc},
+};
+use kube::{
+    api::{
+        Api, AttachParams, AttachedProcess, DeleteParams, ListParams, LogParams, Patch,
+        PatchParams, PostParams,
+    },
+    Client as K8sClient, ResourceExt,
+};
+use reqwest::{Certificate, Url};
 use std::{
-    fmt::{Debug, Formatter},
-    process::{Command, Stdio},
+    collections::BTreeMap,
+    fmt::{Debug, Display, Formatter},
+    fs,
+    future::Future,
+    path::{Path, PathBuf},
     str::FromStr,
-    sync::atomic::{AtomicU32, Ordering},
-    thread,
-    time::{Duration, Instant},
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
+use thiserror::Error;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+
+// how long to wait for a node to become healthy after starting it, unless specified otherwise
+const DEFAULT_NODE_START_TIMEOUT: Duration = Duration::from_secs(60);
+// how long to wait for the node's pod to terminate after stopping it
+const DEFAULT_NODE_STOP_TIMEOUT: Duration = Duration::from_secs(120);
+const POD_READY_POLL_INTERVAL: Duration = Duration::from_secs(2);
+const POD_TERMINATION_POLL_INTERVAL: Duration = Duration::from_secs(2);
+// how long to wait for the node's PVCs to be deleted when clearing its storage
+const DEFAULT_PVC_DELETION_TIMEOUT: Duration = Duration::from_secs(300);
+const PVC_DELETION_POLL_INTERVAL: Duration = Duration::from_secs(5);
+const CLEAR_STORAGE_POD_POLL_INTERVAL: Duration = Duration::from_secs(2);
+const RESET_SAFETY_RULES_STORAGE_TIMEOUT: Duration = Duration::from_secs(300);
+// the default timeout for commands run inside the node's container
+const DEFAULT_EXEC_TIMEOUT: Duration = Duration::from_secs(60);
+// how many local ports to try before giving up on a port-forward
+const PORT_FORWARD_MAX_ATTEMPTS: usize = 3;
+// the name of the volume the node ConfigMap is mounted as, given by the aptos-node helm chart
+const APTOS_CONFIG_VOLUME_NAME: &str = "aptos-config";
+const VALIDATOR_CONFIG_MAP_KEY: &str = "validator.yaml";
+const FULLNODE_CONFIG_MAP_KEY: &str = "fullnode.yaml";
+// the file fill_disk takes up the space with, at the root of the node's data volume
+const DISK_FILL_FILE_NAME: &str = "forge-disk-fill";
+// writing out gigabytes with dd, where fallocate is not supported, can take a while
+const DISK_FILL_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// How the storage of a node is cleared
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ClearStorageMode {
+    /// Delete the node's PVCs, so that fresh volumes are provisioned for them. This can take
+    /// minutes, depending on the storage class.
+    #[default]
+    DeleteVolumes,
+    /// Remove the contents of the node's volumes from a short-lived pod mounting them, and keep
+    /// the volumes. Falls back to DeleteVolumes if that fails.
+    InPlace,
+}
+
+/// The output of a command run inside a node's container
+#[derive(Clone, Debug)]
+pub struct ExecOutput {
+    pub stdout: String,
+    pub stderr: String,
+    // None if the API server did not report one, e.g. because the container went away
+    pub exit_code: Option<i32>,
+}
+
+/// The space on the volume holding a node's storage, as `df` sees it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DiskSpace {
+    pub mount_path: String,
+    pub used_bytes: u64,
+    // without the blocks reserved for root, so that used + available is what df counts as 100%
+    pub available_bytes: u64,
+}
+
+impl DiskSpace {
+    pub fn utilization_percent(&self) -> f64 {
+        let usable = self.used_bytes + self.available_bytes;
+        if usable == 0 {
+            return 100.0;
+        }
+        100.0 * self.used_bytes as f64 / usable as f64
+    }
+
+    /// How many bytes to take up for the volume to be `percent` full, none if it already is
+    fn bytes_to_fill(&self, percent: u8) -> u64 {
+        let usable = (self.used_bytes + self.available_bytes) as u128;
+        let target = (usable * percent as u128 / 100) as u64;
+        target.saturating_sub(self.used_bytes)
+    }
+
+    fn fill_file(&self) -> String {
+        format!(
+            "{}/{}",
+            self.mount_path.trim_end_matches('/'),
+            DISK_FILL_FILE_NAME
+        )
+    }
+}
+
+impl ExecOutput {
+    pub fn success(&self) -> bool {
+        self.exit_code == Some(0)
+    }
+}
+
+/// What a port-forward of a node connects to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum PortForwardTarget {
+    // the Service forge reaches the node through, which may be HAProxy's
+    Service,
+    // the node's own Service, bypassing HAProxy
+    DirectService,
+    // the node's pod, which can be reached on any port the node listens on
+    Pod,
+}
+
+/// A port-forward from a local port to a node, killed when dropped
+pub struct LocalPortForward {
+    port_forward: PortForward,
+}
+
+impl LocalPortForward {
+    pub fn port(&self) -> u32 {
+        self.port_forward.local_port()
+    }
+
+    pub fn remote_port(&self) -> u32 {
+        self.port_forward.remote_port()
+    }
+}
 
-const APTOS_DATA_DIR: &str = "/opt/aptos/data";
+#[derive(Error, Debug)]
+pub enum ExecError {
+    #[error("Pod {pod} is not running, phase: {phase}")]
+    PodNotRunning { pod: String, phase: String },
+    #[error("Command {command:?} in pod {pod} timed out after {timeout:?}")]
+    Timeout {
+        pod: String,
+        command: Vec<String>,
+        timeout: Duration,
+    },
+}
+
+/// TLS settings for clusters that serve the node REST API over HTTPS, e.g. behind a
+/// TLS-terminating ingress
+#[derive(Clone, Debug, Default)]
+pub struct RestApiTls {
+    // extra root CA to trust, for clusters using a private CA
+    pub root_ca: Option<Certificate>,
+}
+
+impl RestApiTls {
+    /// Trust the PEM encoded root CA in the given file, in addition to the system roots
+    pub fn with_root_ca_file(path: &str) -> Result<Self> {
+        let pem = fs::read(path).with_context(|| format!("Failed to read root CA {}", path))?;
+        let root_ca = Certificate::from_pem(&pem)
+            .with_context(|| format!("Failed to parse root CA {}", path))?;
+        Ok(Self {
+            root_ca: Some(root_ca),
+        })
+    }
+}
 
 pub struct K8sNode {
     pub(crate) name: String,
     pub(crate) stateful_set_name: String,
+    // the ordinal of the node's pod in its StatefulSet
+    pub(crate) replica_index: u32,
+    // whether other replicas of the StatefulSet are nodes of their own, rather than the
+    // StatefulSet having at most one replica
+    pub(crate) shares_stateful_set: bool,
     pub(crate) peer_id: PeerId,
     pub(crate) index: usize,
     pub(crate) service_name: String,
     pub(crate) rest_api_port: AtomicU32,
+    // the REST API ports on the node's own Service and on its HAProxy Service, as read from the
+    // Services, since charts may not use the defaults
+    pub(crate) rest_api_service_port: u32,
+    pub(crate) rest_api_haproxy_service_port: u32,
+    pub(crate) inspection_service_port: AtomicU32,
     pub version: Version,
+    // the CPU and memory of the node's container in its StatefulSet
+    pub(crate) resources: Option<NodeResources>,
     pub namespace: String,
+    // the cluster the node runs in, and the kubectl to reach it with
+    pub(crate) backend_config: Arc<K8sBackendConfig>,
     // whether this node has HAProxy in front of it
     pub haproxy_enabled: bool,
     // whether we should try using port-forward on the Service to reach this node
     pub port_forward_enabled: bool,
+    // if set, the REST API is served over HTTPS
+    pub rest_api_tls: Option<RestApiTls>,
+    // the node is unhealthy if its ledger timestamp lags behind by more than this
+    pub ledger_staleness_threshold: Option<Duration>,
+    // by how much a TimeSkew chaos moved the node's clock. A node whose clock is behind holds
+    // blocks from its future back, so its ledger may lag by that much on top of the threshold.
+    pub(crate) clock_skew: Duration,
+    // when a memory stress chaos was last injected into the node and removed from it, if ever. The
+    // OOMKills within are expected, and the node restarts from them.
+    pub(crate) memory_stress: Option<(DateTime<Utc>, Option<DateTime<Utc>>)>,
+    // how often the test stopped, killed or rolled the node's pod, which are not unexpected
+    // restarts
+    pub(crate) restarts_by_test: AtomicU32,
+    // the port-forwards owned by this node, killed on stop/drop
+    pub(crate) port_forwards: Mutex<Vec<PortForward>>,
+    // the port-forward to the metrics port handed out by expose_metric, reused while it is alive
+    pub(crate) metrics_port_forward: Mutex<Option<PortForward>>,
+    // the NodeConfig read from the node's ConfigMap, fetched lazily on first use and dropped
+    // whenever the pod is recreated, since it may come back with another config
+    pub(crate) config: Mutex<Option<NodeConfig>>,
 }
 
 impl K8sNode {
@@ -47,12 +270,99 @@ impl K8sNode {
         self.rest_api_port.load(Ordering::SeqCst)
     }
 
+    fn inspection_service_port(&self) -> u32 {
+        self.inspection_service_port.load(Ordering::SeqCst)
+    }
+
     fn service_name(&self) -> String {
         self.service_name.clone()
     }
 
     pub(crate) fn rest_client(&self) -> RestClient {
-        RestClient::new(self.rest_api_endpoint())
+        self.rest_client_for(self.rest_api_endpoint())
+    }
+
+    fn rest_client_for(&self, endpoint: Url) -> RestClient {
+        self.rest_client_options()
+            .apply(RestClient::builder(AptosBaseUrl::Custom(endpoint)))
+            .build()
+    }
+
+    /// A REST client that talks to the node's own Service, bypassing HAProxy even if it is enabled
+    pub async fn rest_client_direct(&self) -> Result<RestClient> {
+        if !self.haproxy_enabled {
+            return Ok(self.rest_client());
+        }
+        Ok(self.rest_client_for(self.direct_rest_api_endpoint().await?))
+    }
+
+    async fn direct_rest_api_endpoint(&self) -> Result<Url> {
+        let (host, port) = if self.port_forward_enabled {
+            (
+                LOCALHOST.to_string(),
+                self.port_forward_direct_rest_api().await?,
+            )
+        } else {
+            (self.direct_service_name(), self.rest_api_service_port)
+        };
+        Ok(Url::from_str(&format!(
+            "{}://{}:{}/v1",
+            self.rest_api_scheme(),
+            host,
+            port
+        ))?)
+    }
+
+    /// A REST client that talks to the node through HAProxy. Fails if HAProxy is not enabled.
+    pub fn rest_client_via_haproxy(&self) -> Result<RestClient> {
+        if !self.haproxy_enabled {
+            bail!("HAProxy is not enabled for {}", self.name);
+        }
+        Ok(self.rest_client())
+    }
+
+    /// The name of the node's own Service, i.e. its Service name without the HAProxy suffix
+    fn direct_service_name(&self) -> String {
+        let haproxy_suffix = format!("-{}", HAPROXY_SERVICE_SUFFIX);
+        let (name, domain) = match self.service_name.split_once('.') {
+            Some((name, domain)) => (name, Some(domain)),
+            None => (self.service_name.as_str(), None),
+        };
+        let name = name.strip_suffix(&haproxy_suffix).unwrap_or(name);
+        match domain {
+            Some(domain) => format!("{}.{}", name, domain),
+            None => name.to_string(),
+        }
+    }
+
+    /// Forward a local port to the REST API on the node's own Service, reusing a live forward
+    async fn port_forward_direct_rest_api(&self) -> Result<u32> {
+        let live_port = self
+            .port_forwards
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|port_forward| {
+                port_forward.remote_port() == self.rest_api_service_port && port_forward.is_alive()
+            })
+            .map(PortForward::local_port);
+        if let Some(local_port) = live_port {
+            return Ok(local_port);
+        }
+        let port_forward = self
+            .start_port_forward(PortForwardTarget::DirectService, self.rest_api_service_port)
+            .await?;
+        let local_port = port_forward.local_port();
+        self.port_forwards.lock().unwrap().push(port_forward);
+        Ok(local_port)
+    }
+
+    fn rest_api_scheme(&self) -> &str {
+        if self.rest_api_tls.is_some() {
+            "https"
+        } else {
+            "http"
+        }
     }
 
     pub fn stateful_set_name(&self) -> &str {
@@ -63,229 +373,3995 @@ impl K8sNode {
         &self.namespace
     }
 
-    /// Start a port-forward to the node's Service
-    fn port_forward(&self, port: u32, remote_port: u32) -> Result<()> {
-        let port_forward_args = [
-            "port-forward",
-            "-n",
-            self.namespace(),
-            &format!("svc/{}", self.service_name()),
-            &format!("{}:{}", port, remote_port),
-        ];
-        // spawn a port-forward child process
-        let cmd = Command::new(KUBECTL_BIN)
-            .args(port_forward_args)
-            .stdout(Stdio::null())
-            // .stderr(Stdio::null())
-            .spawn();
-        match cmd {
-            Ok(mut child) => {
-                // sleep a bit and check if port-forward failed for some reason
-                let timeout = Duration::from_secs(1);
-                thread::sleep(timeout);
-                match child.try_wait() {
-                    Ok(Some(status)) => {
-                        info!("Port-forward may have started already: exit {}", status);
-                        Ok(())
-                    },
-                    Ok(None) => {
-                        info!(
-                            "Port-forward started for {:?} from {} --> {}",
-                            self, port, remote_port
-                        );
-                        Ok(())
-                    },
-                    Err(err) => Err(anyhow!(
-                        "Port-forward did not work: {:?} error {}",
-                        port_forward_args,
-                        err
-                    )),
-                }
-            },
-            Err(err) => Err(anyhow!(
-                "Port-forward did not start: {:?} error {}",
-                port_forward_args,
-                err
-            )),
-        }
+    /// The Service a port-forward to the target resolves its pod and port through, none if it
+    /// goes to the node's pod directly
+    fn port_forward_service_name(&self, target: PortForwardTarget) -> Option<String> {
+        let service_name = match target {
+            PortForwardTarget::Pod => return None,
+            PortForwardTarget::Service => self.service_name(),
+            PortForwardTarget::DirectService => self.direct_service_name(),
+        };
+        // without the domain, if the name is the Service's DNS name
+        service_name.split('.').next().map(str::to_string)
     }
 
-    pub fn port_forward_rest_api(&self) -> Result<()> {
-        let remote_rest_api_port = if self.haproxy_enabled {
-            REST_API_HAPROXY_SERVICE_PORT
+    /// The pod and the port of it that a port-forward to `remote_port` of the target ends up at.
+    /// Only pods can be forwarded to, so a Service is resolved the way kube-proxy would: to a pod
+    /// it selects, and the targetPort that `remote_port` maps to.
+    async fn port_forward_endpoint(
+        &self,
+        kube_client: &K8sClient,
+        target: PortForwardTarget,
+        remote_port: u32,
+    ) -> Result<(String, u32)> {
+        let service_name = match self.port_forward_service_name(target) {
+            Some(service_name) => service_name,
+            None => return Ok((self.pod_name(), remote_port)),
+        };
+        let service_api: Api<Service> = Api::namespaced(kube_client.clone(), self.namespace());
+        let service = service_api
+            .get(&service_name)
+            .await
+            .map_err(|e| K8sError::from_kube(format!("service {}", service_name), e))?;
+        let pod_api: Api<Pod> = Api::namespaced(kube_client.clone(), self.namespace());
+        // HAProxy runs in pods of its own. The node's Service selects the pods of its
+        // StatefulSet, which this node's pod is the one to reach of.
+        let pod_name = if target == PortForwardTarget::Service && self.haproxy_enabled {
+            service_pod(&pod_api, &service).await?
         } else {
-            REST_API_SERVICE_PORT
+            self.pod_name()
         };
-        self.port_forward(self.rest_api_port(), remote_rest_api_port)
+        let pod = pod_api
+            .get(&pod_name)
+            .await
+            .map_err(|e| K8sError::from_kube(format!("pod {}", pod_name), e))?;
+        let pod_port = service_target_port(&service, remote_port, &pod)?;
+        Ok((pod_name, pod_port))
     }
-}
 
-#[async_trait::async_trait]
-impl Node for K8sNode {
-    fn name(&self) -> &str {
-        &self.name
+    /// Start a port-forward from a free local port to `remote_port` of the target, which every
+    /// port-forward of the node goes through
+    async fn start_port_forward(
+        &self,
+        target: PortForwardTarget,
+        remote_port: u32,
+    ) -> Result<PortForward> {
+        let kube_client = self.backend_config.create_client().await?;
+        let (pod, pod_port) = self
+            .port_forward_endpoint(&kube_client, target, remote_port)
+            .await?;
+        let pod_name = pod.as_str();
+        let (_, port_forward) = retry_on_port_conflict(|reserved_port| {
+            let kube_client = kube_client.clone();
+            async move {
+                PortForward::start(
+                    kube_client,
+                    self.namespace(),
+                    pod_name,
+                    pod_port,
+                    remote_port,
+                    reserved_port,
+                )
+            }
+        })
+        .await?;
+        info!(
+            "Port-forward started for {:?} from {} --> {} (port {} of pod {})",
+            self,
+            port_forward.local_port(),
+            remote_port,
+            pod_port,
+            pod
+        );
+        Ok(port_forward)
     }
 
-    fn index(&self) -> usize {
-        self.index
+    /// Start a port-forward from a free local port to the node's Service, owned by this node. The
+    /// local port is stored into `port`.
+    async fn port_forward_service(&self, port: &AtomicU32, remote_port: u32) -> Result<()> {
+        let port_forward = self
+            .start_port_forward(PortForwardTarget::Service, remote_port)
+            .await?;
+        port.store(port_forward.local_port(), Ordering::SeqCst);
+        self.port_forwards.lock().unwrap().push(port_forward);
+        Ok(())
     }
 
-    fn peer_id(&self) -> PeerId {
-        self.peer_id
+    /// Forward a free local port to the given port on the node's pod. Unlike the Service, the pod
+    /// can be reached on any port the node listens on, even ones only bound to its loopback.
+    pub async fn port_forward(&self, remote_port: u32) -> Result<LocalPortForward> {
+        let port_forward = self
+            .start_port_forward(PortForwardTarget::Pod, remote_port)
+            .await?;
+        Ok(LocalPortForward { port_forward })
     }
 
-    async fn start(&self) -> Result<()> {
-        scale_stateful_set_replicas(self.stateful_set_name(), self.namespace(), 1).await?;
-        // need to port-forward again since the node is coming back
-        // note that we will get a new port
-        if self.port_forward_enabled {
-            self.rest_api_port.store(get_free_port(), Ordering::SeqCst);
-            self.port_forward_rest_api()?;
-        }
-        self.wait_until_healthy(Instant::now() + Duration::from_secs(60))
-            .await
+    /// Forward a local port to the node's backup service, used by the backup CLI
+    pub async fn backup_service_port(&self) -> Result<LocalPortForward> {
+        self.port_forward(BACKUP_SERVICE_PORT).await
     }
 
-    async fn stop(&self) -> Result<()> {
-        info!("going to stop node {}", self.stateful_set_name());
-        scale_stateful_set_replicas(self.stateful_set_name(), self.namespace(), 0).await
+    /// Forward a local port to the node's admin service
+    pub async fn admin_service_port(&self) -> Result<LocalPortForward> {
+        self.port_forward(ADMIN_SERVICE_PORT).await
     }
 
-    fn version(&self) -> Version {
-        self.version.clone()
+    pub async fn port_forward_rest_api(&self) -> Result<()> {
+        let remote_rest_api_port = if self.haproxy_enabled {
+            self.rest_api_haproxy_service_port
+        } else {
+            self.rest_api_service_port
+        };
+        self.port_forward_service(&self.rest_api_port, remote_rest_api_port)
+            .await
     }
 
-    fn rest_api_endpoint(&self) -> Url {
-        let host = if self.port_forward_enabled {
+    pub async fn port_forward_inspection_service(&self) -> Result<()> {
+        self.port_forward_service(&self.inspection_service_port, NODE_METRIC_PORT)
+            .await
+    }
+
+    fn host(&self) -> &str {
+        if self.port_forward_enabled {
             LOCALHOST
         } else {
             &self.service_name
+        }
+    }
+
+    /// The first 8 hex digits of the peer id, enough to find the node in on-chain data
+    pub fn short_peer_id(&self) -> String {
+        self.peer_id.to_hex()[..8].to_string()
+    }
+
+    /// A kubectl command that shows the state of the node's pod, for failure reports
+    pub fn describe_pod_command(&self) -> String {
+        format!(
+            "{} -n {} describe pod {}",
+            self.backend_config.kubectl_command_line(),
+            self.namespace(),
+            self.pod_name()
+        )
+    }
+
+    /// The name of the node's pod, i.e. the one of its replica of the StatefulSet
+    pub fn pod_name(&self) -> String {
+        format!("{}-{}", self.stateful_set_name(), self.replica_index)
+    }
+
+    /// The number of replicas to scale the node's StatefulSet to, to start or stop the node. A
+    /// StatefulSet can only scale its highest ordinals up and down, so stopping a replica in the
+    /// middle stops the replicas after it too, until it is started again. A replica can only be
+    /// started once the ones before it are.
+    async fn replicas_to_scale_to(&self, running: bool) -> Result<u64> {
+        if !self.shares_stateful_set {
+            return Ok(running as u64);
+        }
+        let (current, restore) = stateful_set::get_stateful_set_replicas(
+            &self.backend_config,
+            self.stateful_set_name(),
+            self.namespace(),
+        )
+        .await?;
+        let (replicas, new_restore) =
+            replicas_to_scale_to(self.replica_index, current, restore, running).with_context(
+                || {
+                    format!(
+                        "Failed to {} {}",
+                        if running { "start" } else { "stop" },
+                        self.name
+                    )
+                },
+            )?;
+        if !running && current > self.replica_index + 1 {
+            info!(
+                "Stopping {} also stops replicas {}..{} of StatefulSet {}, until it is started again",
+                self.name,
+                self.replica_index + 1,
+                current,
+                self.stateful_set_name()
+            );
+        }
+        if new_restore != restore {
+            stateful_set::set_stateful_set_restore_replicas(
+                &self.backend_config,
+                self.stateful_set_name(),
+                self.namespace(),
+                new_restore,
+            )
+            .await?;
+        }
+        Ok(replicas)
+    }
+
+    /// Fetch the container logs of the node's pod. Set `previous` to get the logs of the last
+    /// terminated container, e.g. after a crash. If the pod runs multiple containers, `container`
+    /// must name the one to read logs from.
+    pub async fn get_pod_logs(
+        &self,
+        since: Option<Duration>,
+        tail_lines: Option<u64>,
+        previous: bool,
+        container: Option<&str>,
+    ) -> Result<String> {
+        let kube_client = self.backend_config.create_client().await?;
+        let pod_api: Api<Pod> = Api::namespaced(kube_client, self.namespace());
+        let log_params = LogParams {
+            container: container.map(|c| c.to_string()),
+            previous,
+            since_seconds: since.map(|since| since.as_secs() as i64),
+            tail_lines: tail_lines.map(|lines| lines as i64),
+            ..LogParams::default()
+        };
+        pod_api
+            .logs(&self.pod_name(), &log_params)
+            .await
+            .map_err(|e| K8sError::from_kube(format!("logs of pod {}", self.pod_name()), e))
+            .with_context(|| {
+                format!(
+                    "Failed to get logs of pod {} in namespace {}",
+                    self.pod_name(),
+                    self.namespace()
+                )
+            })
+    }
+
+    /// Snapshot the CPU and memory usage of the node's pod from metrics-server. If HAProxy is
+    /// enabled, the usage of the node's HAProxy pods is included separately.
+    pub async fn resource_usage(&self) -> Result<PodResourceUsage> {
+        let kube_client = self.backend_config.create_client().await?;
+        let pod_name = self.pod_name();
+        let containers =
+            fetch_container_resource_usage(&kube_client, self.namespace(), &pod_name).await?;
+        let haproxy_containers = if self.haproxy_enabled {
+            // labels given to the HAProxy Deployment by the aptos-node helm chart
+            let label_selector = format!(
+                "app.kubernetes.io/name=haproxy,app.kubernetes.io/instance=haproxy-{}",
+                self.index
+            );
+            fetch_container_resource_usage_by_labels(
+                &kube_client,
+                self.namespace(),
+                &label_selector,
+            )
+            .await?
+        } else {
+            vec![]
         };
-        Url::from_str(&format!("http://{}:{}/v1", host, self.rest_api_port()))
-            .expect("Invalid URL.")
+        Ok(PodResourceUsage {
+            pod_name,
+            containers,
+            haproxy_containers,
+        })
     }
 
-    async fn clear_storage(&self) -> Result<()> {
-        // Remove all storage files
-        let ledger_db_path = format!("{}/db/{}", APTOS_DATA_DIR, LEDGER_DB_NAME);
-        let state_db_path = format!("{}/db/{}", APTOS_DATA_DIR, STATE_MERKLE_DB_NAME);
-        let state_sync_db_path = format!("{}/db/{}", APTOS_DATA_DIR, STATE_SYNC_DB_NAME);
+    /// Start the node, and wait up to `health_timeout` for it to become healthy
+    pub async fn start_with_timeout(&self, health_timeout: Duration) -> Result<()> {
+        let deadline = Instant::now() + health_timeout;
+        let replicas = self.replicas_to_scale_to(true).await?;
+        scale_stateful_set_replicas(
+            &self.backend_config,
+            self.stateful_set_name(),
+            self.namespace(),
+            replicas,
+        )
+        .await?;
+        self.invalidate_config();
+        let result = async {
+            // the REST API may be reachable through HAProxy before the pod is actually Ready
+            self.wait_until_pod_ready(deadline).await?;
+            // need to port-forward again since the node is coming back
+            self.restart_port_forwards().await?;
+            // the node may still need to catch up, so only wait for it to serve requests
+            self.wait_until_reachable(deadline).await
+        }
+        .await;
+        // e.g. FailedScheduling or FailedAttachVolume are only visible in the events
+        match result {
+            Err(e) => Err(e.context(format!(
+                "Failed to start {}. {}",
+                self.name,
+                self.describe_warning_events().await
+            ))),
+            Ok(()) => Ok(()),
+        }
+    }
+
+    /// Check whether a container of the node's pod is failing in a way that waiting won't fix, or
+    /// was OOMKilled by a memory stress chaos, which waiting does fix. Failing to get the pod is
+    /// not a failure of the node, so it is only logged.
+    async fn container_failure(&self) -> Option<HealthCheckError> {
+        match self.get_pod_status().await {
+            Ok(pod) => {
+                if let Some(oom_kill) = oom_killed_during(&pod, self.memory_stress) {
+                    return Some(HealthCheckError::OomKilledByChaos(oom_kill));
+                }
+                container_failure(&pod, self.memory_stress)
+                    .map(|failure| HealthCheckError::Unrecoverable(failure.into()))
+            },
+            Err(e) => {
+                info!(
+                    "Failed to check the status of pod {}: {}",
+                    self.pod_name(),
+                    e
+                );
+                None
+            },
+        }
+    }
+
+    async fn get_pod_status(&self) -> Result<Pod> {
+        let pod_name = self.pod_name();
+        let kube_client = self.backend_config.create_client().await?;
+        let pod_api: Api<Pod> = Api::namespaced(kube_client, self.namespace());
+        pod_api
+            .get_status(&pod_name)
+            .await
+            .map_err(|e| K8sError::from_kube(format!("pod {}", pod_name), e).into())
+    }
+
+    /// Poll the node's pod until its Ready condition is true. Fails right away if one of its
+    /// containers is crash-looping or can't pull its image. On timeout, the error has the
+    /// statuses of the pod's containers.
+    async fn wait_until_pod_ready(&self, deadline: Instant) -> Result<()> {
+        let pod_name = self.pod_name();
+        let kube_client = self.backend_config.create_client().await?;
+        let pod_api: Api<Pod> = Api::namespaced(kube_client, self.namespace());
+        loop {
+            let status = match pod_api.get_status(&pod_name).await {
+                Ok(pod) if pod_is_ready(&pod) => return Ok(()),
+                Ok(pod) => {
+                    if let Some(failure) = container_failure(&pod, self.memory_stress) {
+                        return Err(failure.into());
+                    }
+                    describe_container_statuses(&pod)
+                },
+                Err(kube::Error::Api(response)) if response.code == 404 => {
+                    "pod does not exist".to_string()
+                },
+                Err(e) => return Err(K8sError::from_kube(format!("pod {}", pod_name), e).into()),
+            };
+            if Instant::now() >= deadline {
+                return Err(K8sError::Timeout {
+                    operation: format!("pod {} to be Ready", pod_name),
+                    message: status,
+                }
+                .into());
+            }
+            tokio::time::sleep(POD_READY_POLL_INTERVAL).await;
+        }
+    }
+
+    /// Replace the port-forwards of a node whose pod was recreated. Note that we will get new ports.
+    async fn restart_port_forwards(&self) -> Result<()> {
+        if self.port_forward_enabled {
+            self.kill_port_forwards();
+            futures::try_join!(
+                self.port_forward_rest_api(),
+                self.port_forward_inspection_service()
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Upgrade the node in place to the given version, whose display string is the image tag, and
+    /// wait until it is healthy again
+    pub async fn upgrade(&mut self, version: &Version) -> Result<()> {
+        let image_tag = version.to_string();
+        info!(
+            "going to upgrade node {} from {} to {}",
+            self.stateful_set_name(),
+            self.version,
+            image_tag
+        );
+        // only the node's own container is changed, HAProxy keeps its image
+        stateful_set::set_stateful_set_image_tag(
+            &self.backend_config,
+            self.stateful_set_name().to_string(),
+            self.container_name().to_string(),
+            image_tag.clone(),
+            self.namespace().to_string(),
+        )
+        .await?;
+
+        self.wait_for_rollout(&format!("upgrade {} to {}", self.name, image_tag))
+            .await?;
+
+        self.version = version.clone();
+        // the node may have come back with a different config
+        self.invalidate_config();
+        self.restart_port_forwards().await?;
+        self.wait_until_healthy(Instant::now() + DEFAULT_NODE_START_TIMEOUT)
+            .await
+    }
 
-        let delete_storage_paths = [
+    /// Change the CPU and memory of the node's container, which the StatefulSet controller rolls
+    /// out by recreating the pod, and wait until the node is healthy again
+    pub async fn set_resources(&mut self, resources: &NodeResources) -> Result<()> {
+        if resources.is_empty() {
+            bail!("No resources to set on {}", self.name);
+        }
+        info!(
+            "going to set the resources of {} to {}",
+            self.name, resources
+        );
+        let target = format!("statefulset/{}", self.stateful_set_name());
+        let mut args = vec![
             "-n",
             self.namespace(),
-            "exec",
-            &format!("sts/{}", self.stateful_set_name()),
-            "--",
-            "rm",
-            "-rf",
-            &ledger_db_path,
-            &state_db_path,
-            &state_sync_db_path,
+            "set",
+            "resources",
+            &target,
+            "-c",
+            self.container_name(),
         ];
-        info!("{:?}", delete_storage_paths);
-        let cleanup_output = Command::new(KUBECTL_BIN)
-            .stdout(Stdio::inherit())
-            .args(delete_storage_paths)
+        let resource_args = set_resources_args(resources);
+        args.extend(resource_args.iter().map(String::as_str));
+        let output = self
+            .backend_config
+            .kubectl_async()
+            .args(&args)
             .output()
-            .expect("failed to clear node storage");
-        assert!(
-            cleanup_output.status.success(),
-            "{}",
-            String::from_utf8(cleanup_output.stderr).unwrap()
-        );
+            .await
+            .with_context(|| format!("Failed to set the resources of {}", self.name))?;
+        if !output.status.success() {
+            return Err(K8sError::from_kubectl(&args, &output).into());
+        }
+        self.wait_for_rollout(&format!("set the resources of {}", self.name))
+            .await?;
 
-        // Stop the node to clear buffers
-        // This step must be done after removing the storage files, since clearing storage involves exec into the (running) node
-        self.stop().await?;
+        // what the StatefulSet ended up with, of the resources not set too
+        let kube_client = self.backend_config.create_client().await?;
+        let stateful_set_api: Api<StatefulSet> = Api::namespaced(kube_client, self.namespace());
+        let stateful_set = stateful_set_api
+            .get(self.stateful_set_name())
+            .await
+            .map_err(|e| {
+                K8sError::from_kube(format!("StatefulSet {}", self.stateful_set_name()), e)
+            })?;
+        self.resources = stateful_set_resources(&stateful_set);
+        self.restart_port_forwards().await?;
+        self.wait_until_healthy(Instant::now() + DEFAULT_NODE_START_TIMEOUT)
+            .await
+    }
 
+    /// Wait until a change to the node's StatefulSet has rolled out, failing with the node's
+    /// warning events, which say e.g. why the new image can't be pulled
+    async fn wait_for_rollout(&self, change: &str) -> Result<()> {
+        // the controller recreates the pod with the change
+        self.expect_restart();
+        let kube_client = self.backend_config.create_client().await?;
+        // retry for ~5 min at a fixed interval
+        let retry_policy = RetryPolicy::fixed(Duration::from_secs(10)).with_max_retries(6 * 5);
+        if let Err(e) = stateful_set::wait_stateful_set_rollout(
+            &kube_client,
+            self.namespace(),
+            self.stateful_set_name(),
+            retry_policy,
+        )
+        .await
+        {
+            return Err(e.context(format!(
+                "Failed to {}. {}",
+                change,
+                self.describe_warning_events().await
+            )));
+        }
         Ok(())
     }
 
-    fn config(&self) -> &NodeConfig {
-        todo!()
+    /// The name of the node's container in its StatefulSet, as given by the aptos-node helm chart
+    fn container_name(&self) -> &str {
+        node_container_name(self.stateful_set_name())
     }
 
-    // TODO: replace this with prometheus query?
-    fn counter(&self, counter: &str, port: u64) -> Result<f64> {
-        let response: Value =
-            reqwest::blocking::get(format!("http://{}:{}/counters", LOCALHOST, port))?.json()?;
-        if let Value::Number(ref response) = response[counter] {
-            if let Some(response) = response.as_f64() {
-                Ok(response)
-            } else {
-                Err(format_err!(
-                    "Failed to parse counter({}) as f64: {:?}",
-                    counter,
-                    response
-                ))
-            }
-        } else {
-            Err(format_err!(
-                "Counter({}) was not a Value::Number: {:?}",
-                counter,
-                response[counter]
-            ))
+    /// Fetch the k8s events of the node's pod and of the PVCs it mounts, oldest first
+    pub async fn events(&self) -> Result<Vec<K8sEvent>> {
+        let kube_client = self.backend_config.create_client().await?;
+        let mut object_names = vec![self.pod_name()];
+        let stateful_set_api: Api<StatefulSet> =
+            Api::namespaced(kube_client.clone(), self.namespace());
+        match stateful_set_api.get(self.stateful_set_name()).await {
+            Ok(stateful_set) => object_names.extend(
+                data_volume_claims(&stateful_set, &self.pod_name())
+                    .into_iter()
+                    .map(|claim| claim.name),
+            ),
+            // the events of the pod are still worth having
+            Err(e) => info!(
+                "Failed to get StatefulSet {}, only fetching the events of its pod: {}",
+                self.stateful_set_name(),
+                e
+            ),
         }
+        fetch_events(&kube_client, self.namespace(), &object_names).await
     }
 
-    // TODO: verify this still works
-    fn expose_metric(&self) -> Result<u64> {
-        let port = get_free_port();
-        self.port_forward(port, NODE_METRIC_PORT)?;
+    /// Write the node's logs, of its previous container too, its events and a description of its
+    /// pod into the node's directory under `dir`, recording those that could not be fetched
+    pub async fn collect_artifacts(&self, dir: &Path, since: Option<Duration>) -> NodeArtifacts {
+        let mut artifacts = NodeArtifacts::new(self.name(), self.peer_id());
+        let container = self.container_name();
+        artifacts.write(
+            dir,
+            "current.log",
+            self.get_pod_logs(since, None, false, Some(container)).await,
+        );
+        // fails unless the container restarted
+        artifacts.write(
+            dir,
+            "previous.log",
+            self.get_pod_logs(since, None, true, Some(container)).await,
+        );
+        artifacts.write(
+            dir,
+            "events.txt",
+            self.events().await.map(|events| describe_events(&events)),
+        );
+        let pod = self.get_pod_status().await;
+        artifacts.write(dir, "describe.txt", pod.and_then(|pod| describe_pod(&pod)));
+        artifacts
+    }
 
-        Ok(port as u64)
+    /// Snapshot the node's DB into `dest` with the default limits, see
+    /// [K8sNode::snapshot_db_with_options]
+    pub async fn snapshot_db(&self, dest: &Path) -> Result<PathBuf> {
+        self.snapshot_db_with_options(dest, &DbSnapshotOptions::default())
+            .await
     }
 
-    async fn health_check(&self) -> Result<(), HealthCheckError> {
-        self.rest_client()
-            .get_ledger_information()
+    /// Stop the node, stream a gzipped tarball of its storage dir out of a helper pod mounting its
+    /// volumes into `dest`, and start the node again if it was running before. Fails without a
+    /// snapshot if the storage dir or the archive exceeds the max size.
+    pub async fn snapshot_db_with_options(
+        &self,
+        dest: &Path,
+        options: &DbSnapshotOptions,
+    ) -> Result<PathBuf> {
+        let storage_dir = self.node_config().await?.storage.dir();
+        let storage_dir = storage_dir.display().to_string();
+        let kube_client = self.backend_config.create_client().await?;
+        let stateful_set_api: Api<StatefulSet> =
+            Api::namespaced(kube_client.clone(), self.namespace());
+        let stateful_set = stateful_set_api
+            .get(self.stateful_set_name())
             .await
-            .map(|_| ())
             .map_err(|e| {
-                HealthCheckError::Failure(format_err!("K8s node health_check failed: {}", e))
-            })
+                K8sError::from_kube(format!("StatefulSet {}", self.stateful_set_name()), e)
+            })?;
+        let was_running = runs_replica(&stateful_set, self.replica_index);
+        let pod = db_snapshot_pod(&stateful_set, &self.pod_name(), options.timeout)?;
+        let pod_name = pod.name();
+
+        // a running node keeps writing to its DB, and the volumes are ReadWriteOnce anyway
+        info!("Stopping {} to snapshot its DB", self.name);
+        self.stop().await?;
+        let pod_api: Api<Pod> = Api::namespaced(kube_client, self.namespace());
+        let snapshot = tokio::time::timeout(
+            options.timeout,
+            self.stream_db_snapshot(&pod_api, pod, &storage_dir, dest, options.max_size_bytes),
+        )
+        .await
+        .unwrap_or_else(|_| {
+            Err(K8sError::Timeout {
+                operation: format!("DB snapshot of {}", self.name),
+                message: format!("not done after {:?}", options.timeout),
+            }
+            .into())
+        });
+        let deleted = delete_pod_if_exists(&pod_api, &pod_name).await;
+
+        if was_running {
+            self.start().await?;
+        }
+        let path = snapshot?;
+        deleted?;
+        info!("Snapshotted the DB of {} into {:?}", self.name, path);
+        Ok(path)
     }
 
-    // TODO: verify this still works
-    fn inspection_service_endpoint(&self) -> Url {
-        Url::parse(&format!(
-            "http://{}:{}",
-            &self.service_name(),
-            self.rest_api_port()
-        ))
-        .unwrap()
+    async fn stream_db_snapshot(
+        &self,
+        pod_api: &Api<Pod>,
+        pod: Pod,
+        storage_dir: &str,
+        dest: &Path,
+        max_size_bytes: u64,
+    ) -> Result<PathBuf> {
+        let pod_name = pod.name();
+        // a leftover from an earlier attempt would make the create fail
+        delete_pod_if_exists(pod_api, &pod_name).await?;
+        pod_api
+            .create(&PostParams::default(), &pod)
+            .await
+            .map_err(|e| K8sError::from_kube(format!("pod {}", pod_name), e))?;
+        wait_for_pod_running(pod_api, &pod_name).await?;
+
+        let du = exec_in_pod(pod_api, &pod_name, None, &["du", "-sk", storage_dir]).await?;
+        if !du.success() {
+            bail!(
+                "du of {} in pod {} failed with {:?}: {}",
+                storage_dir,
+                pod_name,
+                du.exit_code,
+                du.stderr.trim()
+            );
+        }
+        let size = parse_du_bytes(&du.stdout)?;
+        if size > max_size_bytes {
+            bail!(
+                "The DB of {} takes {} bytes, more than the {} bytes a snapshot may take",
+                self.name,
+                size,
+                max_size_bytes
+            );
+        }
+
+        fs::create_dir_all(dest)
+            .with_context(|| format!("Failed to create the snapshot directory {:?}", dest))?;
+        let path = dest.join(db_snapshot_file_name(&self.name));
+        let streamed = self
+            .stream_tar(pod_api, &pod_name, storage_dir, &path, max_size_bytes)
+            .await;
+        if streamed.is_err() {
+            // a partial archive would pass for a snapshot
+            let _ = fs::remove_file(&path);
+        }
+        streamed.map(|()| path)
     }
 
-    async fn get_identity(&self) -> Result<String> {
-        stateful_set::get_identity(self.stateful_set_name(), self.namespace()).await
+    async fn stream_tar(
+        &self,
+        pod_api: &Api<Pod>,
+        pod_name: &str,
+        storage_dir: &str,
+        path: &Path,
+        max_size_bytes: u64,
+    ) -> Result<()> {
+        let tar = db_snapshot_tar_command(storage_dir);
+        let tar: Vec<&str> = tar.iter().map(String::as_str).collect();
+        let mut process = start_exec(pod_api, pod_name, None, &tar).await?;
+        let stdout = process
+            .stdout()
+            .ok_or_else(|| format_err!("No stdout of tar in pod {}", pod_name))?;
+        let mut file = tokio::fs::File::create(path)
+            .await
+            .with_context(|| format!("Failed to create {:?}", path))?;
+        // read in the background, as tar blocks once it is full
+        let stderr = tokio::spawn(read_exec_stream(process.stderr()));
+        // a byte more than allowed tells a cut off archive from one of exactly the max size
+        let written = tokio::io::copy(&mut stdout.take(max_size_bytes + 1), &mut file)
+            .await
+            .with_context(|| format!("Failed to write {:?}", path))?;
+        if written > max_size_bytes {
+            bail!(
+                "The DB snapshot of {} exceeds {} bytes, even compressed",
+                self.name,
+                max_size_bytes
+            );
+        }
+        file.flush().await?;
+        let stderr = stderr
+            .await?
+            .with_context(|| format!("Failed to read the stderr of tar in pod {}", pod_name))?;
+        let exit_code = wait_for_exec(process).await?;
+        if exit_code != Some(0) {
+            bail!(
+                "tar of {} in pod {} failed with {:?}: {}",
+                storage_dir,
+                pod_name,
+                exit_code,
+                String::from_utf8_lossy(&stderr).trim()
+            );
+        }
+        Ok(())
     }
 
-    async fn set_identity(&self, k8s_secret_name: String) -> Result<()> {
-        stateful_set::set_identity(
-            self.stateful_set_name(),
-            self.namespace(),
-            k8s_secret_name.as_str(),
-        )
-        .await
+    /// Describe the latest Warning events of the node, to explain why it failed to come up
+    pub(crate) async fn describe_warning_events(&self) -> String {
+        match self.events().await {
+            Ok(events) => format!(
+                "Latest warning events of {}:\n{}",
+                self.name,
+                describe_events(last_warnings(&events, MAX_REPORTED_WARNINGS))
+            ),
+            Err(e) => format!("Failed to get the events of {}: {}", self.name, e),
+        }
     }
 
-    fn service_name(&self) -> Option<String> {
-        Some(self.service_name.clone())
+    /// Clear the node's storage by deleting its PersistentVolumeClaims, and wait until they are
+    /// gone so that the node can't come back with its old data. This stops the node as well.
+    pub async fn clear_storage_with_timeout(&self, timeout: Duration) -> Result<()> {
+        let kube_client = self.backend_config.create_client().await?;
+        let stateful_set_api: Api<StatefulSet> =
+            Api::namespaced(kube_client.clone(), self.namespace());
+        let stateful_set = stateful_set_api
+            .get(self.stateful_set_name())
+            .await
+            .map_err(|e| {
+                K8sError::from_kube(format!("StatefulSet {}", self.stateful_set_name()), e)
+            })?;
+        let claims = self.clear_storage_claims(&stateful_set);
+        if claims.is_empty() {
+            bail!(
+                "StatefulSet {} has no PersistentVolumeClaims of pod {} to clear",
+                self.stateful_set_name(),
+                self.pod_name()
+            );
+        }
+
+        // a PVC is only deleted once no pod mounts it
+        self.stop().await?;
+
+        let pvc_api: Api<PersistentVolumeClaim> = Api::namespaced(kube_client, self.namespace());
+        // the StatefulSet controller only recreates PVCs from its volumeClaimTemplates, so keep the
+        // others around to recreate them ourselves
+        let mut standalone_claims = vec![];
+        for claim in &claims {
+            let pvc = pvc_api
+                .get(&claim.name)
+                .await
+                .map_err(|e| K8sError::from_kube(format!("PVC {}", claim.name), e))?;
+            info!("Deleting PVC {} of {}", claim.name, self.name);
+            pvc_api
+                .delete(&claim.name, &DeleteParams::default())
+                .await
+                .map_err(|e| K8sError::from_kube(format!("PVC {}", claim.name), e))?;
+            if !claim.from_template {
+                standalone_claims.push(pvc);
+            }
+        }
+        for claim in &claims {
+            wait_pvc_deleted(&pvc_api, &claim.name, timeout).await?;
+        }
+        for pvc in standalone_claims {
+            pvc_api
+                .create(&PostParams::default(), &recreated_claim(&pvc))
+                .await
+                .map_err(|e| K8sError::from_kube(format!("PVC {}", pvc.name()), e))?;
+        }
+        Ok(())
     }
-}
 
-impl Validator for K8sNode {}
+    /// The PVCs clearing the node's storage deletes, those of its own pod. A claim named by the pod
+    /// template is mounted by every replica of the StatefulSet, so it is only the node's if the
+    /// StatefulSet runs no other nodes.
+    fn clear_storage_claims(&self, stateful_set: &StatefulSet) -> Vec<DataVolumeClaim> {
+        data_volume_claims(stateful_set, &self.pod_name())
+            .into_iter()
+            .filter(|claim| claim.from_template || !self.shares_stateful_set)
+            .collect()
+    }
 
-impl FullNode for K8sNode {}
+    /// Clear the node's storage the given way. The node is stopped for it.
+    pub async fn clear_storage_with_mode(
+        &self,
+        mode: ClearStorageMode,
+        timeout: Duration,
+    ) -> Result<()> {
+        match mode {
+            ClearStorageMode::DeleteVolumes => self.clear_storage_with_timeout(timeout).await,
+            ClearStorageMode::InPlace => self.clear_storage_in_place_with_timeout(timeout).await,
+        }
+    }
 
-impl Debug for K8sNode {
-    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
-        let host = if self.port_forward_enabled {
-            LOCALHOST
-        } else {
-            &self.service_name
-        };
-        write!(f, "{} @ {}", self.name, host)
+    /// Clear the node's storage without deleting its volumes, see [ClearStorageMode::InPlace]
+    pub async fn clear_storage_in_place(&self) -> Result<()> {
+        self.clear_storage_in_place_with_timeout(DEFAULT_PVC_DELETION_TIMEOUT)
+            .await
+    }
+
+    /// Stop the node, wipe its volumes from a helper pod, and start the node again if it was
+    /// running before
+    async fn clear_storage_in_place_with_timeout(&self, timeout: Duration) -> Result<()> {
+        let kube_client = self.backend_config.create_client().await?;
+        let stateful_set_api: Api<StatefulSet> =
+            Api::namespaced(kube_client.clone(), self.namespace());
+        let stateful_set = stateful_set_api
+            .get(self.stateful_set_name())
+            .await
+            .map_err(|e| {
+                K8sError::from_kube(format!("StatefulSet {}", self.stateful_set_name()), e)
+            })?;
+        let was_running = runs_replica(&stateful_set, self.replica_index);
+
+        // the volumes are ReadWriteOnce, so the node's pod has to be gone first
+        self.stop().await?;
+        let pod_api: Api<Pod> = Api::namespaced(kube_client, self.namespace());
+        // a standalone PVC shared by the replicas holds the others' data too
+        let claims = self.clear_storage_claims(&stateful_set);
+        let wiped = match clear_storage_pod(&stateful_set, &self.pod_name(), claims) {
+            Ok(pod) => run_to_completion(&pod_api, pod, timeout).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = wiped {
+            info!(
+                "Failed to clear the storage of {} in place, deleting its PVCs instead: {:#}",
+                self.name, e
+            );
+            self.clear_storage_with_timeout(timeout).await?;
+        }
+
+        if was_running {
+            self.start().await?;
+        }
+        Ok(())
+    }
+
+    /// Stop the node, remove its safety rules storage from a helper pod, and start the node again,
+    /// so that it initializes the storage from its identity again, e.g. with a new consensus key.
+    /// The storage holds the node's safety data, so the node must not have voted in the current
+    /// epoch.
+    pub async fn reset_safety_rules_storage(&self) -> Result<()> {
+        let config = self.node_config().await?;
+        let path = safety_rules_storage_path(&config)?;
+        let kube_client = self.backend_config.create_client().await?;
+        let stateful_set_api: Api<StatefulSet> =
+            Api::namespaced(kube_client.clone(), self.namespace());
+        let stateful_set = stateful_set_api
+            .get(self.stateful_set_name())
+            .await
+            .map_err(|e| {
+                K8sError::from_kube(format!("StatefulSet {}", self.stateful_set_name()), e)
+            })?;
+        let was_running = runs_replica(&stateful_set, self.replica_index);
+
+        self.stop().await?;
+        let pod_api: Api<Pod> = Api::namespaced(kube_client, self.namespace());
+        let pod = reset_safety_rules_storage_pod(&stateful_set, &self.pod_name(), &path)?;
+        run_to_completion(&pod_api, pod, RESET_SAFETY_RULES_STORAGE_TIMEOUT).await?;
+        info!(
+            "Removed the safety rules storage {:?} of {}",
+            path, self.name
+        );
+
+        if was_running {
+            self.start().await?;
+        }
+        Ok(())
+    }
+
+    /// Stop the node and wait until its pod is gone. If `force_delete` is set, a pod that is still
+    /// around after its termination grace period is force deleted, e.g. because its k8s node is
+    /// unresponsive.
+    pub async fn stop_with_timeout(&self, timeout: Duration, force_delete: bool) -> Result<()> {
+        info!("going to stop node {}", self.stateful_set_name());
+        self.expect_restart();
+        let deadline = Instant::now() + timeout;
+        let replicas = self.replicas_to_scale_to(false).await?;
+        scale_stateful_set_replicas(
+            &self.backend_config,
+            self.stateful_set_name(),
+            self.namespace(),
+            replicas,
+        )
+        .await?;
+        // the port-forwards point at a pod that no longer exists
+        self.kill_port_forwards();
+
+        let pod_name = self.pod_name();
+        let kube_client = self.backend_config.create_client().await?;
+        let pod_api: Api<Pod> = Api::namespaced(kube_client, self.namespace());
+        let mut force_deleted = false;
+        loop {
+            let pod = match pod_api.get(&pod_name).await {
+                Ok(pod) => pod,
+                Err(kube::Error::Api(response)) if response.code == 404 => return Ok(()),
+                Err(e) => return Err(K8sError::from_kube(format!("pod {}", pod_name), e).into()),
+            };
+            if force_delete && !force_deleted && grace_period_expired(&pod, Utc::now()) {
+                info!(
+                    "Pod {} outlived its termination grace period, force deleting it",
+                    pod_name
+                );
+                stateful_set::force_delete_stateful_set_pod(
+                    &self.backend_config,
+                    self.stateful_set_name(),
+                    self.replica_index,
+                    self.namespace(),
+                )
+                .await?;
+                force_deleted = true;
+            }
+            if Instant::now() >= deadline {
+                return Err(K8sError::Timeout {
+                    operation: format!("pod {} to terminate", pod_name),
+                    message: describe_container_statuses(&pod),
+                }
+                .into());
+            }
+            tokio::time::sleep(POD_TERMINATION_POLL_INTERVAL).await;
+        }
+    }
+
+    fn expect_restart(&self) {
+        self.restarts_by_test.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// How often the containers of the node's pod restarted, none if the pod does not exist,
+    /// e.g. while the node is stopped
+    pub async fn restart_count(&self) -> Result<Option<PodRestartCount>> {
+        let kube_client = self.backend_config.create_client().await?;
+        let pod_api: Api<Pod> = Api::namespaced(kube_client, self.namespace());
+        let pod_name = self.pod_name();
+        match pod_api.get(&pod_name).await {
+            Ok(pod) => Ok(Some(PodRestartCount::of(&pod))),
+            Err(kube::Error::Api(response)) if response.code == 404 => Ok(None),
+            Err(e) => Err(K8sError::from_kube(format!("pod {}", pod_name), e).into()),
+        }
+    }
+
+    /// The restarts of the node since the `baseline` count of its pod, see
+    /// [crate::Swarm::restarts]
+    pub(crate) fn restarts_since(
+        &self,
+        baseline: Option<&PodRestartCount>,
+        current: Option<&PodRestartCount>,
+    ) -> NodeRestarts {
+        let restarts = restarts_since(baseline, current);
+        let last_termination_reason =
+            current.and_then(|current| current.last_termination_reason.clone());
+        // the OOMKills of a memory stress are why it is injected
+        let expected = if self.memory_stress.is_some()
+            && last_termination_reason.as_deref() == Some("OOMKilled")
+        {
+            restarts
+        } else {
+            self.restarts_by_test.load(Ordering::SeqCst)
+        };
+        NodeRestarts {
+            name: self.name.clone(),
+            restarts,
+            expected,
+            last_termination_reason,
+        }
+    }
+
+    /// Ungracefully kill the node and keep it down, until it is started again
+    pub async fn kill_and_stop(&self) -> Result<()> {
+        info!("going to kill and stop node {}", self.stateful_set_name());
+        self.expect_restart();
+        // remembers the replicas after this one, which stop with it
+        self.replicas_to_scale_to(false).await?;
+        stateful_set::kill_stateful_set(
+            &self.backend_config,
+            self.stateful_set_name(),
+            self.replica_index,
+            self.namespace(),
+        )
+        .await?;
+        self.kill_port_forwards();
+        Ok(())
+    }
+
+    /// Run a command inside the node's container, failing if it takes longer than a minute
+    pub async fn exec(&self, command: &[&str]) -> Result<ExecOutput> {
+        self.exec_with_timeout(command, DEFAULT_EXEC_TIMEOUT).await
+    }
+
+    /// Run a command inside the node's container, killing it if it does not complete in time
+    pub async fn exec_with_timeout(
+        &self,
+        command: &[&str],
+        timeout: Duration,
+    ) -> Result<ExecOutput> {
+        let pod_name = self.pod_name();
+        let kube_client = self.backend_config.create_client().await?;
+        let pod_api: Api<Pod> = Api::namespaced(kube_client, self.namespace());
+        let phase = pod_api
+            .get_status(&pod_name)
+            .await
+            .map_err(|e| K8sError::from_kube(format!("pod {}", pod_name), e))?
+            .status
+            .and_then(|status| status.phase)
+            .unwrap_or_else(|| "Unknown".to_string());
+        if phase != "Running" {
+            return Err(ExecError::PodNotRunning {
+                pod: pod_name,
+                phase,
+            }
+            .into());
+        }
+
+        let output = exec_in_pod(&pod_api, &pod_name, Some(self.container_name()), command);
+        match tokio::time::timeout(timeout, output).await {
+            Ok(output) => output,
+            Err(_) => Err(ExecError::Timeout {
+                pod: pod_name,
+                command: command.iter().map(|c| c.to_string()).collect(),
+                timeout,
+            }
+            .into()),
+        }
+    }
+
+    /// The IP of the node's pod, at which the other pods reach it without its Service
+    pub(crate) async fn pod_ip(&self) -> Result<String> {
+        self.get_pod_status()
+            .await?
+            .status
+            .and_then(|status| status.pod_ip)
+            .with_context(|| format!("Pod of node {} has no IP", self.name()))
+    }
+
+    /// How many UDP datagrams to closed ports the network namespace of the node's pod received
+    pub(crate) async fn udp_no_ports(&self) -> Result<u64> {
+        let output = self.exec(&["cat", "/proc/net/snmp"]).await?;
+        if !output.success() {
+            bail!(
+                "Reading /proc/net/snmp of node {} failed with {:?}: {}",
+                self.name(),
+                output.exit_code,
+                output.stderr.trim()
+            );
+        }
+        parse_udp_no_ports(&output.stdout)
+    }
+
+    /// Send UDP datagrams from inside the node's pod. Sending them fails where a partition drops
+    /// them on the way out, which is no error here: whether they arrive is what is probed.
+    pub(crate) async fn send_udp_probes(&self, ip: &str, port: u16, count: u64) -> Result<()> {
+        let script = format!(
+            "for i in $(seq {}); do echo forge-probe > /dev/udp/{}/{}; done",
+            count, ip, port
+        );
+        let output = self.exec(&["bash", "-c", &script]).await?;
+        if !output.success() {
+            info!(
+                "Sending UDP probes from node {} to {} failed: {}",
+                self.name(),
+                ip,
+                output.stderr.trim()
+            );
+        }
+        Ok(())
+    }
+
+    /// The disk space the node's storage dir takes, measured with du inside the container. A
+    /// cross-check for the DB sizes in [`crate::StorageMetrics`], which miss WALs and unflushed data.
+    pub async fn disk_usage_bytes(&self) -> Result<u64> {
+        let storage_dir = self.node_config().await?.storage.dir();
+        let storage_dir = storage_dir.display().to_string();
+        let output = self.exec(&["du", "-sk", &storage_dir]).await?;
+        if !output.success() {
+            bail!(
+                "du of {} in node {} failed with {:?}: {}",
+                storage_dir,
+                self.name(),
+                output.exit_code,
+                output.stderr.trim()
+            );
+        }
+        parse_du_bytes(&output.stdout)
+    }
+
+    /// The space on the volume the node's storage dir is on, which is the data volume for both
+    /// the validator and the fullnode charts
+    pub async fn disk_space(&self) -> Result<DiskSpace> {
+        let storage_dir = self.node_config().await?.storage.dir();
+        let storage_dir = storage_dir.display().to_string();
+        let output = self.exec(&["df", "-Pk", &storage_dir]).await?;
+        if !output.success() {
+            bail!(
+                "df of {} in node {} failed with {:?}: {}",
+                storage_dir,
+                self.name(),
+                output.exit_code,
+                output.stderr.trim()
+            );
+        }
+        parse_df(&output.stdout)
+    }
+
+    /// The name of the ConfigMap mounted into the node's StatefulSet, and the key of the node's
+    /// config in it
+    async fn config_map_location(&self, kube_client: &K8sClient) -> Result<(String, &'static str)> {
+        let stateful_set_api: Api<StatefulSet> =
+            Api::namespaced(kube_client.clone(), self.namespace());
+        let stateful_set = stateful_set_api
+            .get(self.stateful_set_name())
+            .await
+            .map_err(|e| {
+                K8sError::from_kube(format!("StatefulSet {}", self.stateful_set_name()), e)
+            })?;
+        let config_map_name = stateful_set
+            .spec
+            .and_then(|spec| spec.template.spec)
+            .and_then(|spec| spec.volumes)
+            .unwrap_or_default()
+            .into_iter()
+            .find(|volume| volume.name == APTOS_CONFIG_VOLUME_NAME)
+            .and_then(|volume| volume.config_map)
+            .and_then(|config_map| config_map.name)
+            .ok_or_else(|| {
+                format_err!(
+                    "StatefulSet {} has no {} ConfigMap volume",
+                    self.stateful_set_name(),
+                    APTOS_CONFIG_VOLUME_NAME
+                )
+            })?;
+        // validators and fullnodes from the same helm release share a ConfigMap
+        let config_map_key = if self.stateful_set_name().contains("fullnode") {
+            FULLNODE_CONFIG_MAP_KEY
+        } else {
+            VALIDATOR_CONFIG_MAP_KEY
+        };
+        Ok((config_map_name, config_map_key))
+    }
+
+    /// The serialized NodeConfig in the ConfigMap mounted into the node's StatefulSet, with the
+    /// name and key of the ConfigMap it is in
+    async fn fetch_serialized_config(
+        &self,
+        kube_client: &K8sClient,
+    ) -> Result<(String, &'static str, String)> {
+        let (config_map_name, config_map_key) = self.config_map_location(kube_client).await?;
+        let config_map_api: Api<ConfigMap> = Api::namespaced(kube_client.clone(), self.namespace());
+        let config_map = config_map_api
+            .get(&config_map_name)
+            .await
+            .map_err(|e| K8sError::from_kube(format!("configmap {}", config_map_name), e))?;
+        let serialized_config = config_map
+            .data
+            .and_then(|mut data| data.remove(config_map_key))
+            .ok_or_else(|| {
+                format_err!(
+                    "ConfigMap {} has no {} key",
+                    config_map_name,
+                    config_map_key
+                )
+            })?;
+        Ok((config_map_name, config_map_key, serialized_config))
+    }
+
+    /// The node's NodeConfig, read from the ConfigMap mounted into its StatefulSet on first use
+    /// and cached until the pod is recreated, see [Self::invalidate_config]
+    pub async fn node_config(&self) -> Result<NodeConfig> {
+        let cached = self.config.lock().unwrap().clone();
+        if let Some(config) = cached {
+            return Ok(config);
+        }
+        let kube_client = self.backend_config.create_client().await?;
+        let (_, _, serialized_config) = self.fetch_serialized_config(&kube_client).await?;
+        let config = NodeConfig::parse_serialized_config(&serialized_config)
+            .with_context(|| format!("Invalid NodeConfig of {}", self.name))?;
+        *self.config.lock().unwrap() = Some(config.clone());
+        Ok(config)
+    }
+
+    /// Drop the cached NodeConfig so that it is read again from the ConfigMap on next use.
+    /// Called whenever the pod is recreated, since it may come back with another config.
+    pub fn invalidate_config(&self) {
+        self.config.lock().unwrap().take();
+    }
+
+    /// Whether the node is the fullnode of one of the validators, rather than a public fullnode
+    pub fn is_validator_fullnode(&self) -> bool {
+        self.stateful_set_name().starts_with("aptos-node-")
+            && self.stateful_set_name().contains("fullnode")
+    }
+
+    /// Whether the node is a public fullnode forge deployed, see [crate::install_public_fullnode]
+    pub fn is_public_fullnode(&self) -> bool {
+        self.stateful_set_name().starts_with("public-fullnode-")
+    }
+
+    /// The network identity the node actually runs with: the one of its validator network, or of
+    /// its public network for fullnodes. Identity files are read from inside the container.
+    pub async fn fetch_identity(&self) -> Result<NodeIdentity> {
+        let config = self.node_config().await?;
+        let network = if self.stateful_set_name().contains("fullnode") {
+            config
+                .full_node_networks
+                .iter()
+                .find(|network| network.network_id == NetworkId::Public)
+        } else {
+            config.validator_network.as_ref()
+        }
+        .ok_or_else(|| format_err!("Node {} has no primary network", self.name))?;
+
+        match &network.identity {
+            Identity::FromConfig(identity) => Ok(NodeIdentity {
+                peer_id: identity.peer_id,
+                network_public_key: identity.key.public_key(),
+            }),
+            Identity::FromFile(identity) => {
+                let path = identity.path.display().to_string();
+                let output = self.exec(&["cat", &path]).await?;
+                if !output.success() {
+                    bail!(
+                        "Failed to read identity {} of node {}: {}",
+                        path,
+                        self.name,
+                        output.stderr.trim()
+                    );
+                }
+                let blob: IdentityBlob = serde_yaml::from_str(&output.stdout)
+                    .with_context(|| format!("Invalid identity {} of node {}", path, self.name))?;
+                let network_public_key = blob.network_private_key.public_key();
+                Ok(NodeIdentity {
+                    // the same fallback the node itself uses
+                    peer_id: blob
+                        .account_address
+                        .unwrap_or_else(|| from_identity_public_key(network_public_key)),
+                    network_public_key,
+                })
+            },
+            identity => bail!(
+                "Node {} has an identity forge can not read: {:?}",
+                self.name,
+                identity
+            ),
+        }
+    }
+
+    /// Check that the node is who forge deployed it as: that the identity it runs with is on
+    /// chain, in the validator network addresses for validators and in the fullnode network
+    /// addresses for validator fullnodes, of the validator account its genesis Secret holds.
+    /// Returns the account of the validator the node is, or is the fullnode of.
+    pub async fn verify_identity(&self, validator_set: &ValidatorSet) -> Result<PeerId> {
+        if self.stateful_set_name().contains("fullnode") && !self.is_validator_fullnode() {
+            bail!(
+                "Public fullnode {} has no on-chain identity to verify",
+                self.name
+            );
+        }
+        let expected = self.deployed_account_address().await?;
+        let identity = self.fetch_identity().await?;
+        match_on_chain_identity(
+            &self.name,
+            !self.stateful_set_name().contains("fullnode"),
+            expected,
+            &identity,
+            validator_set,
+        )
+    }
+
+    /// The account of the validator the node was deployed as, or for, from the validator
+    /// identity in the genesis Secret its StatefulSet mounts
+    async fn deployed_account_address(&self) -> Result<PeerId> {
+        let kube_client = self.backend_config.create_client().await?;
+        let stateful_set_api: Api<StatefulSet> =
+            Api::namespaced(kube_client.clone(), self.namespace());
+        let stateful_set = stateful_set_api
+            .get(self.stateful_set_name())
+            .await
+            .map_err(|e| {
+                K8sError::from_kube(format!("StatefulSet {}", self.stateful_set_name()), e)
+            })?;
+        let secret_name = genesis_secret_name(&stateful_set).ok_or_else(|| {
+            format_err!(
+                "StatefulSet {} mounts no genesis Secret",
+                self.stateful_set_name()
+            )
+        })?;
+        let secret_api: Api<Secret> = Api::namespaced(kube_client, self.namespace());
+        let secret = secret_api
+            .get(secret_name)
+            .await
+            .map_err(|e| K8sError::from_kube(format!("Secret {}", secret_name), e))?;
+        validator_identity_from_secret(&secret)?
+            .account_address
+            .ok_or_else(|| format_err!("Secret {} has no validator account address", secret_name))
+    }
+
+    /// Change the node's config: apply `modify` to the NodeConfig in the node's ConfigMap, write
+    /// it back, and restart the node so it picks the change up. Refuses to write back a config
+    /// that would lose fields NodeConfig does not know, e.g. ones only newer nodes have.
+    pub async fn update_config<F: FnOnce(&mut NodeConfig)>(&mut self, modify: F) -> Result<()> {
+        if self.shares_stateful_set {
+            bail!(
+                "The config of {} is shared by all replicas of StatefulSet {}, \
+                 update it with the helm values instead",
+                self.name,
+                self.stateful_set_name()
+            );
+        }
+        let kube_client = self.backend_config.create_client().await?;
+        let (config_map_name, config_map_key, serialized_config) =
+            self.fetch_serialized_config(&kube_client).await?;
+        let config_map_api: Api<ConfigMap> = Api::namespaced(kube_client, self.namespace());
+
+        let mut config = NodeConfig::parse_serialized_config(&serialized_config)?;
+        let original: serde_yaml::Value = serde_yaml::from_str(&serialized_config)?;
+        let round_trip = serde_yaml::to_value(&config)?;
+        let lost = lost_fields(&original, &round_trip);
+        if !lost.is_empty() {
+            bail!(
+                "Refusing to update the config of {}, writing it back would lose the fields {:?}",
+                self.name,
+                lost
+            );
+        }
+
+        modify(&mut config);
+        let patch = serde_json::json!({
+            "data": { config_map_key: serde_yaml::to_string(&config)? }
+        });
+        config_map_api
+            .patch(
+                &config_map_name,
+                &PatchParams::default(),
+                &Patch::Merge(&patch),
+            )
+            .await
+            .map_err(|e| K8sError::from_kube(format!("configmap {}", config_map_name), e))?;
+        info!(
+            "Updated {} in ConfigMap {}, restarting {}",
+            config_map_key, config_map_name, self.name
+        );
+
+        self.restart(DEFAULT_NODE_START_TIMEOUT).await?;
+        *self.config.lock().unwrap() = Some(config);
+        Ok(())
+    }
+
+    /// The local port of the metrics port-forward, if it is alive. A dead one is cleaned up.
+    fn live_metrics_port_forward(&self) -> Option<u32> {
+        let mut metrics_port_forward = self.metrics_port_forward.lock().unwrap();
+        let port_forward = metrics_port_forward.as_ref()?;
+        if port_forward.is_alive() {
+            return Some(port_forward.local_port());
+        }
+        info!(
+            "Metrics port-forward for {:?} is dead, restarting it: {:?}",
+            self,
+            port_forward.exit_reason()
+        );
+        metrics_port_forward.take();
+        None
+    }
+
+    /// Kill all port-forwards started for this node
+    pub fn kill_port_forwards(&self) {
+        for port_forward in self.port_forwards.lock().unwrap().drain(..) {
+            port_forward.kill();
+        }
+        if let Some(port_forward) = self.metrics_port_forward.lock().unwrap().take() {
+            port_forward.kill();
+        }
+    }
+
+    /// Fails if any of this node's port-forwards has died, saying why
+    pub fn check_port_forwards(&self) -> Result<()> {
+        let dead = self
+            .port_forwards
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(PortForward::exit_reason)
+            .collect::<Vec<_>>();
+        if !dead.is_empty() {
+            bail!("Port-forward to {} died: {}", self.name, dead.join("; "));
+        }
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl Node for K8sNode {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn index(&self) -> usize {
+        self.index
+    }
+
+    fn peer_id(&self) -> PeerId {
+        self.peer_id
+    }
+
+    async fn start(&self) -> Result<()> {
+        self.start_with_timeout(DEFAULT_NODE_START_TIMEOUT).await
+    }
+
+    async fn stop(&self) -> Result<()> {
+        self.stop_with_timeout(DEFAULT_NODE_STOP_TIMEOUT, true)
+            .await
+    }
+
+    async fn restart(&self, health_timeout: Duration) -> Result<()> {
+        self.stop().await?;
+        self.start_with_timeout(health_timeout).await
+    }
+
+    // the StatefulSet controller recreates the pod, but the port-forwards do not survive it
+    async fn kill(&self) -> Result<()> {
+        info!("going to kill node {}", self.stateful_set_name());
+        self.expect_restart();
+        stateful_set::force_delete_stateful_set_pod(
+            &self.backend_config,
+            self.stateful_set_name(),
+            self.replica_index,
+            self.namespace(),
+        )
+        .await?;
+        self.kill_port_forwards();
+        self.invalidate_config();
+        Ok(())
+    }
+
+    fn version(&self) -> Version {
+        self.version.clone()
+    }
+
+    fn resources(&self) -> Option<NodeResources> {
+        self.resources.clone()
+    }
+
+    async fn fill_disk(&self, percent: u8) -> Result<()> {
+        if percent > 100 {
+            bail!("Can't fill the disk of {} to {}%", self.name(), percent);
+        }
+        // start over from the space the node itself takes up
+        self.free_disk().await?;
+        let disk_space = self.disk_space().await?;
+        let bytes = disk_space.bytes_to_fill(percent);
+        info!(
+            "Filling the disk of {} at {} from {:.1}% to {}% with {} bytes",
+            self.name(),
+            disk_space.mount_path,
+            disk_space.utilization_percent(),
+            percent,
+            bytes
+        );
+        if bytes == 0 {
+            return Ok(());
+        }
+        // dd writes the space out where the file system has no fallocate, in MiB, rounding down
+        let fill_file = disk_space.fill_file();
+        let script = format!(
+            "fallocate -l {bytes} {file} || dd if=/dev/zero of={file} bs=1048576 count={mib}",
+            bytes = bytes,
+            file = fill_file,
+            mib = bytes / 1_048_576,
+        );
+        let error = match self
+            .exec_with_timeout(&["sh", "-c", &script], DISK_FILL_TIMEOUT)
+            .await
+        {
+            Ok(output) if output.success() => return Ok(()),
+            Ok(output) => format_err!(
+                "Failed to fill the disk of {} with {}, exit code {:?}: {}",
+                self.name(),
+                fill_file,
+                output.exit_code,
+                output.stderr.trim()
+            ),
+            Err(e) => e,
+        };
+        // not to leave behind what was written before it failed
+        if let Err(e) = self.free_disk().await {
+            warn!("Failed to free the disk of {}: {}", self.name(), e);
+        }
+        Err(error)
+    }
+
+    async fn free_disk(&self) -> Result<()> {
+        let fill_file = self.disk_space().await?.fill_file();
+        let output = self.exec(&["rm", "-f", &fill_file]).await?;
+        if !output.success() {
+            bail!(
+                "Failed to remove {} from {}, exit code {:?}: {}",
+                fill_file,
+                self.name(),
+                output.exit_code,
+                output.stderr.trim()
+            );
+        }
+        Ok(())
+    }
+
+    async fn check_haproxy_consistency(&self) -> Result<()> {
+        let direct = self.rest_client_direct().await?;
+        let via_haproxy = self.rest_client_via_haproxy()?;
+        let direct_state = direct.get_ledger_information().await?.into_inner();
+        let haproxy_state = via_haproxy.get_ledger_information().await?.into_inner();
+        if direct_state.chain_id != haproxy_state.chain_id {
+            bail!(
+                "{} serves chain {} directly, but chain {} through HAProxy",
+                self.name,
+                direct_state.chain_id,
+                haproxy_state.chain_id
+            );
+        }
+        // the ledger keeps moving, so compare a version both have committed
+        let version = direct_state.version.min(haproxy_state.version);
+        let direct_block = direct
+            .get_block_by_version(version, false)
+            .await?
+            .into_inner();
+        let haproxy_block = via_haproxy
+            .get_block_by_version(version, false)
+            .await?
+            .into_inner();
+        if direct_block.block_hash != haproxy_block.block_hash {
+            bail!(
+                "{} serves block {} at version {} directly, but block {} through HAProxy",
+                self.name,
+                direct_block.block_hash,
+                version,
+                haproxy_block.block_hash
+            );
+        }
+        Ok(())
+    }
+
+    fn rest_api_endpoint(&self) -> Url {
+        Url::from_str(&format!(
+            "{}://{}:{}/v1",
+            self.rest_api_scheme(),
+            self.host(),
+            self.rest_api_port()
+        ))
+        .expect("Invalid URL.")
+    }
+
+    // the clients of tests and of the swarm's ChainInfo trust the node's TLS root CA too
+    fn rest_client_options(&self) -> RestClientOptions {
+        let mut options = self.backend_config.rest_client_options.clone();
+        if let Some(root_ca) = self
+            .rest_api_tls
+            .as_ref()
+            .and_then(|tls| tls.root_ca.clone())
+        {
+            options.root_ca = Some(root_ca);
+        }
+        options
+    }
+
+    async fn clear_storage(&self) -> Result<()> {
+        self.clear_storage_with_timeout(DEFAULT_PVC_DELETION_TIMEOUT)
+            .await
+    }
+
+    async fn config(&self) -> Result<NodeConfig> {
+        self.node_config().await
+    }
+
+    // TODO: replace this with prometheus query?
+    async fn counter(&self, counter: &str) -> Result<f64> {
+        let port = self.expose_metric().await?;
+        fetch_counter(self.host(), *port, counter).await
+    }
+
+    // the port-forward is owned by this node and shared by all callers, so the returned guard
+    // does not kill it. Without port-forwards the metrics port of the node's Service is reachable
+    // as it is.
+    async fn expose_metric(&self) -> Result<MetricsPortForward> {
+        if !self.port_forward_enabled {
+            return Ok(MetricsPortForward::new(NODE_METRIC_PORT as u64, None));
+        }
+        if let Some(port) = self.live_metrics_port_forward() {
+            return Ok(MetricsPortForward::new(port as u64, None));
+        }
+
+        // the lock is not held while spawning, so that other nodes' callers are not blocked
+        let port_forward = self
+            .start_port_forward(PortForwardTarget::Service, NODE_METRIC_PORT)
+            .await?;
+        let mut metrics_port_forward = self.metrics_port_forward.lock().unwrap();
+        match metrics_port_forward.as_ref() {
+            // another caller started one meanwhile, keep using that. Ours stops when dropped.
+            Some(existing) if existing.is_alive() => {
+                Ok(MetricsPortForward::new(existing.local_port() as u64, None))
+            },
+            _ => {
+                let port = port_forward.local_port();
+                *metrics_port_forward = Some(port_forward);
+                Ok(MetricsPortForward::new(port as u64, None))
+            },
+        }
+    }
+
+    async fn health_check(&self) -> Result<(), HealthCheckError> {
+        let state = match self.rest_client().get_ledger_information().await {
+            Ok(state) => state.into_inner(),
+            Err(e) => {
+                // no point in polling the REST API of a container that keeps crashing
+                if let Some(failure) = self.container_failure().await {
+                    return Err(failure);
+                }
+                return Err(match self.check_port_forwards() {
+                    // a dead tunnel is the likely cause of the failure
+                    Err(port_forward_error) => HealthCheckError::Failure(format_err!(
+                        "K8s node health_check failed: {}. {}",
+                        e,
+                        port_forward_error
+                    )),
+                    Ok(()) => HealthCheckError::Failure(format_err!(
+                        "K8s node health_check failed: {}",
+                        e
+                    )),
+                });
+            },
+        };
+        if let Some(threshold) = self.ledger_staleness_threshold {
+            check_ledger_freshness(
+                state.timestamp_usecs,
+                SystemTime::now(),
+                threshold + self.clock_skew,
+            )?;
+        }
+        Ok(())
+    }
+
+    fn inspection_service_endpoint(&self) -> Url {
+        Url::parse(&format!(
+            "http://{}:{}",
+            self.host(),
+            self.inspection_service_port()
+        ))
+        .unwrap()
+    }
+
+    async fn get_identity(&self) -> Result<String> {
+        stateful_set::get_identity(
+            &self.backend_config,
+            self.stateful_set_name(),
+            self.namespace(),
+        )
+        .await
+    }
+
+    async fn set_identity(&self, k8s_secret_name: String) -> Result<()> {
+        stateful_set::set_identity(
+            &self.backend_config,
+            self.stateful_set_name(),
+            self.namespace(),
+            k8s_secret_name.as_str(),
+        )
+        .await
+    }
+
+    // the admin service is not on the node's Service, so always forward to the pod
+    async fn admin_service_endpoint(&self) -> Result<ServiceEndpoint> {
+        let port_forward = self.admin_service_port().await?;
+        let url = Url::parse(&format!("http://{}:{}", LOCALHOST, port_forward.port()))?;
+        Ok(ServiceEndpoint::new(url, Some(Box::new(port_forward))))
+    }
+
+    fn service_name(&self) -> Option<String> {
+        Some(self.service_name.clone())
+    }
+
+    async fn get_connected_peer_ids(&self, network_id: NetworkId) -> Result<Vec<PeerId>> {
+        fetch_connected_peers(self.inspection_service_endpoint(), network_id).await
+    }
+}
+
+/// The number of replicas that starts (`running`) or stops the replica with the given ordinal,
+/// given the current number of replicas, and the number to restore once the replica in the middle
+/// that stopped the ones after it starts again. Returns the new number to restore too.
+fn replicas_to_scale_to(
+    replica_index: u32,
+    current_replicas: u32,
+    restore_replicas: Option<u32>,
+    running: bool,
+) -> Result<(u64, Option<u32>)> {
+    if running {
+        if current_replicas < replica_index {
+            bail!(
+                "replicas {}..{} have to be started first",
+                current_replicas,
+                replica_index
+            );
+        }
+        if current_replicas > replica_index {
+            // already running
+            return Ok((current_replicas as u64, restore_replicas));
+        }
+        let replicas =
+            restore_replicas.map_or(replica_index + 1, |restore| restore.max(replica_index + 1));
+        Ok((replicas as u64, None))
+    } else {
+        if current_replicas <= replica_index {
+            // already stopped
+            return Ok((current_replicas as u64, restore_replicas));
+        }
+        let restore_replicas = if current_replicas > replica_index + 1 {
+            Some(restore_replicas.map_or(current_replicas, |restore| restore.max(current_replicas)))
+        } else {
+            restore_replicas
+        };
+        Ok((replica_index as u64, restore_replicas))
+    }
+}
+
+/// The container of the pod that was last OOMKilled within the given window, with when it was
+fn oom_killed_during(
+    pod: &Pod,
+    window: Option<(DateTime<Utc>, Option<DateTime<Utc>>)>,
+) -> Option<String> {
+    let (start, end) = window?;
+    let container_statuses = pod
+        .status
+        .as_ref()
+        .and_then(|status| status.container_statuses.as_deref())
+        .unwrap_or_default();
+    container_statuses.iter().find_map(|container| {
+        let terminated = container
+            .state
+            .as_ref()
+            .and_then(|state| state.terminated.as_ref())
+            .or_else(|| {
+                container
+                    .last_state
+                    .as_ref()
+                    .and_then(|state| state.terminated.as_ref())
+            })?;
+        if terminated.reason.as_deref() != Some("OOMKilled") {
+            return None;
+        }
+        let finished_at = terminated.finished_at.as_ref()?.0;
+        if finished_at < start || end.map_or(false, |end| finished_at > end) {
+            return None;
+        }
+        Some(format!(
+            "Container {} of pod {} was OOMKilled by a memory stress at {}",
+            container.name,
+            pod.metadata.name.as_deref().unwrap_or_default(),
+            finished_at.to_rfc3339()
+        ))
+    })
+}
+
+/// Fails with HealthCheckError::Stale if the ledger timestamp lags behind `now` by more than `threshold`
+fn check_ledger_freshness(
+    ledger_timestamp_usecs: u64,
+    now: SystemTime,
+    threshold: Duration,
+) -> Result<(), HealthCheckError> {
+    let ledger_time = UNIX_EPOCH + Duration::from_micros(ledger_timestamp_usecs);
+    // a ledger timestamp ahead of our clock is not stale
+    let lag = now.duration_since(ledger_time).unwrap_or_default();
+    if lag > threshold {
+        return Err(HealthCheckError::Stale(format!(
+            "Ledger timestamp is {:?} behind, more than the threshold of {:?}",
+            lag, threshold
+        )));
+    }
+    Ok(())
+}
+
+/// Call `spawn` with a freshly reserved port, for as long as the port turns out to be taken by
+/// someone outside this process, up to PORT_FORWARD_MAX_ATTEMPTS times. Returns the port that worked.
+async fn retry_on_port_conflict<T, F, Fut>(mut spawn: F) -> Result<(u32, T)>
+where
+    F: FnMut(ReservedPort) -> Fut,
+    Fut: Future<Output = Result<T, PortForwardError>>,
+{
+    let mut tried_ports = vec![];
+    loop {
+        let reserved_port = get_free_port()?;
+        let port = reserved_port.port();
+        tried_ports.push(port);
+        match spawn(reserved_port).await {
+            Ok(res) => return Ok((port, res)),
+            Err(PortForwardError::PortInUse(_))
+                if tried_ports.len() < PORT_FORWARD_MAX_ATTEMPTS =>
+            {
+                info!(
+                    "Local port {} is already in use, retrying port-forward",
+                    port
+                );
+            },
+            Err(PortForwardError::PortInUse(_)) => {
+                bail!(
+                    "Port-forward failed, all local ports tried were in use: {:?}",
+                    tried_ports
+                )
+            },
+            Err(PortForwardError::Other(e)) => return Err(e),
+        }
+    }
+}
+
+/// The paths of the fields of `original` that are missing from `round_trip`, e.g. "mempool.foo"
+fn lost_fields(original: &serde_yaml::Value, round_trip: &serde_yaml::Value) -> Vec<String> {
+    fn collect(
+        path: &str,
+        original: &serde_yaml::Value,
+        round_trip: &serde_yaml::Value,
+        lost: &mut Vec<String>,
+    ) {
+        match (original, round_trip) {
+            (serde_yaml::Value::Mapping(original), serde_yaml::Value::Mapping(round_trip)) => {
+                for (key, value) in original {
+                    let key_path = match key.as_str() {
+                        Some(key) if path.is_empty() => key.to_string(),
+                        Some(key) => format!("{}.{}", path, key),
+                        None => format!("{}.{:?}", path, key),
+                    };
+                    match round_trip.get(key) {
+                        Some(round_trip_value) => collect(&key_path, value, round_trip_value, lost),
+                        None => lost.push(key_path),
+                    }
+                }
+            },
+            (serde_yaml::Value::Sequence(original), serde_yaml::Value::Sequence(round_trip)) => {
+                for (i, value) in original.iter().enumerate() {
+                    let item_path = format!("{}[{}]", path, i);
+                    match round_trip.get(i) {
+                        Some(round_trip_value) => {
+                            collect(&item_path, value, round_trip_value, lost)
+                        },
+                        None => lost.push(item_path),
+                    }
+                }
+            },
+            _ => {},
+        }
+    }
+    let mut lost = vec![];
+    collect("", original, round_trip, &mut lost);
+    lost
+}
+
+fn pod_is_ready(pod: &Pod) -> bool {
+    pod.status
+        .as_ref()
+        .and_then(|status| status.conditions.as_ref())
+        .map_or(false, |conditions| {
+            conditions
+                .iter()
+                .any(|condition| condition.type_ == "Ready" && condition.status == "True")
+        })
+}
+
+// waiting reasons of containers that won't recover by themselves
+const FAILING_WAITING_REASONS: [&str; 4] = [
+    "CrashLoopBackOff",
+    "ImagePullBackOff",
+    "ErrImagePull",
+    "CreateContainerConfigError",
+];
+
+/// Returns K8sError::ContainerFailing for the first container of the pod that is crash-looping,
+/// was OOMKilled, or can't pull its image
+fn container_failure(
+    pod: &Pod,
+    memory_stress: Option<(DateTime<Utc>, Option<DateTime<Utc>>)>,
+) -> Option<K8sError> {
+    if oom_killed_during(pod, memory_stress).is_some() {
+        return None;
+    }
+    let container_statuses = pod
+        .status
+        .as_ref()
+        .and_then(|status| status.container_statuses.as_deref())
+        .unwrap_or_default();
+    container_statuses.iter().find_map(|container| {
+        let state = container.state.as_ref()?;
+        // the last termination explains a crash loop, e.g. OOMKilled and the final log line
+        let last_terminated = container
+            .last_state
+            .as_ref()
+            .and_then(|state| state.terminated.as_ref());
+        let (reason, message) = if let Some(terminated) = &state.terminated {
+            if terminated.reason.as_deref() != Some("OOMKilled") {
+                return None;
+            }
+            ("OOMKilled".to_string(), terminated.message.clone())
+        } else if let Some(waiting) = &state.waiting {
+            let waiting_reason = waiting.reason.as_deref().unwrap_or_default();
+            if !FAILING_WAITING_REASONS.contains(&waiting_reason) {
+                return None;
+            }
+            match last_terminated {
+                Some(terminated) if terminated.reason.as_deref() == Some("OOMKilled") => (
+                    format!("{} (OOMKilled)", waiting_reason),
+                    terminated.message.clone(),
+                ),
+                Some(terminated) => (
+                    waiting_reason.to_string(),
+                    terminated.message.clone().or_else(|| {
+                        Some(format!(
+                            "{}, exit code {}",
+                            terminated.reason.as_deref().unwrap_or("terminated"),
+                            terminated.exit_code
+                        ))
+                    }),
+                ),
+                None => (waiting_reason.to_string(), waiting.message.clone()),
+            }
+        } else {
+            return None;
+        };
+        Some(K8sError::ContainerFailing {
+            pod: pod.metadata.name.clone().unwrap_or_default(),
+            container: container.name.clone(),
+            reason,
+            message: message.unwrap_or_else(|| "no termination message".to_string()),
+            restart_count: container.restart_count,
+        })
+    })
+}
+
+/// Like `kubectl describe pod`, where the pod runs and the states of its containers, followed by
+/// its full status
+fn describe_pod(pod: &Pod) -> Result<String> {
+    let status = pod.status.clone().unwrap_or_default();
+    let conditions: Vec<_> = status
+        .conditions
+        .iter()
+        .flatten()
+        .map(|condition| format!("{}={}", condition.type_, condition.status))
+        .collect();
+    let summary = [
+        ("Name", pod.metadata.name.clone()),
+        ("Namespace", pod.metadata.namespace.clone()),
+        (
+            "Node",
+            pod.spec.as_ref().and_then(|spec| spec.node_name.clone()),
+        ),
+        ("Phase", status.phase.clone()),
+        ("Pod IP", status.pod_ip.clone()),
+        (
+            "Started",
+            status.start_time.as_ref().map(|t| t.0.to_rfc3339()),
+        ),
+        ("Conditions", Some(conditions.join(", "))),
+        ("Containers", Some(describe_container_statuses(pod))),
+    ];
+    let mut lines: Vec<_> = summary
+        .iter()
+        .map(|(field, value)| format!("{}: {}", field, value.as_deref().unwrap_or("<none>")))
+        .collect();
+    lines.push(format!("\nStatus:\n{}", serde_yaml::to_string(&status)?));
+    Ok(lines.join("\n"))
+}
+
+/// The restarts of a pod's containers, to tell later whether they restarted or the pod was
+/// recreated since
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PodRestartCount {
+    pub uid: Option<String>,
+    pub restarts: u32,
+    pub last_termination_reason: Option<String>,
+}
+
+impl PodRestartCount {
+    pub fn of(pod: &Pod) -> Self {
+        let container_statuses = pod
+            .status
+            .as_ref()
+            .and_then(|status| status.container_statuses.as_deref())
+            .unwrap_or_default();
+        let last_termination_reason = container_statuses
+            .iter()
+            .filter(|container| container.restart_count > 0)
+            .max_by_key(|container| container.restart_count)
+            .and_then(|container| container.last_state.as_ref())
+            .and_then(|state| state.terminated.as_ref())
+            .and_then(|terminated| terminated.reason.clone());
+        Self {
+            uid: pod.metadata.uid.clone(),
+            restarts: container_statuses
+                .iter()
+                .map(|container| container.restart_count.max(0) as u32)
+                .sum(),
+            last_termination_reason,
+        }
+    }
+}
+
+// a pod that was recreated, or is gone, counts as restarted once on top of the restarts of the
+// new pod, the restarts of the ones in between are not known
+fn restarts_since(baseline: Option<&PodRestartCount>, current: Option<&PodRestartCount>) -> u32 {
+    match (baseline, current) {
+        (Some(baseline), Some(current)) if baseline.uid == current.uid => {
+            current.restarts.saturating_sub(baseline.restarts)
+        },
+        (Some(_), Some(current)) => current.restarts + 1,
+        (Some(_), None) => 1,
+        // e.g. a node added during the test, or one that was not running when it started
+        (None, Some(current)) => current.restarts,
+        (None, None) => 0,
+    }
+}
+
+/// Summarize why the pod's containers are not ready, e.g.
+/// `validator: waiting (CrashLoopBackOff: back-off 40s restarting failed container), 3 restarts`
+fn describe_container_statuses(pod: &Pod) -> String {
+    let status = match pod.status.as_ref() {
+        Some(status) => status,
+        None => return "pod has no status".to_string(),
+    };
+    let container_statuses = status.container_statuses.as_deref().unwrap_or_default();
+    if container_statuses.is_empty() {
+        // why the pod was not scheduled, e.g. Unschedulable because no node fits it
+        let unscheduled = status
+            .conditions
+            .iter()
+            .flatten()
+            .find(|condition| condition.type_ == "PodScheduled" && condition.status == "False")
+            .map(|condition| {
+                format!(
+                    " ({}: {})",
+                    condition.reason.as_deref().unwrap_or_default(),
+                    condition.message.as_deref().unwrap_or_default()
+                )
+            })
+            .unwrap_or_default();
+        return format!(
+            "pod is {} without container statuses{}",
+            status.phase.as_deref().unwrap_or("in unknown phase"),
+            unscheduled
+        );
+    }
+    container_statuses
+        .iter()
+        .map(|container| {
+            let state = match container.state.as_ref() {
+                Some(ContainerState {
+                    waiting: Some(waiting),
+                    ..
+                }) => format!(
+                    "waiting ({}: {})",
+                    waiting.reason.as_deref().unwrap_or_default(),
+                    waiting.message.as_deref().unwrap_or_default()
+                ),
+                Some(ContainerState {
+                    terminated: Some(terminated),
+                    ..
+                }) => format!(
+                    "terminated ({}, exit code {})",
+                    terminated.reason.as_deref().unwrap_or_default(),
+                    terminated.exit_code
+                ),
+                Some(ContainerState {
+                    running: Some(_), ..
+                }) if !container.ready => "running but not ready".to_string(),
+                Some(ContainerState {
+                    running: Some(_), ..
+                }) => "ready".to_string(),
+                _ => "unknown state".to_string(),
+            };
+            format!(
+                "{}: {}, {} restarts",
+                container.name, state, container.restart_count
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// Whether the pod is being deleted and its termination grace period has passed, so that it should
+/// have been killed already
+fn grace_period_expired(pod: &Pod, now: DateTime<Utc>) -> bool {
+    // the deletion timestamp is when the grace period ends, not when deletion was requested
+    pod.metadata
+        .deletion_timestamp
+        .as_ref()
+        .map_or(false, |deletion_time| deletion_time.0 <= now)
+}
+
+/// A PersistentVolumeClaim mounted by a node's pod
+#[derive(Debug, PartialEq)]
+struct DataVolumeClaim {
+    name: String,
+    // the name of the volume in the pod template
+    volume_name: String,
+    // whether the StatefulSet controller creates the claim from one of its volumeClaimTemplates
+    from_template: bool,
+}
+
+/// The PVCs mounted by the given pod of the StatefulSet, both those referenced directly by the pod
+/// template and those created from the volumeClaimTemplates
+fn data_volume_claims(stateful_set: &StatefulSet, pod_name: &str) -> Vec<DataVolumeClaim> {
+    let spec = match stateful_set.spec.as_ref() {
+        Some(spec) => spec,
+        None => return vec![],
+    };
+    let standalone = spec
+        .template
+        .spec
+        .iter()
+        .flat_map(|pod_spec| pod_spec.volumes.iter().flatten())
+        .filter_map(|volume| {
+            let claim = volume.persistent_volume_claim.as_ref()?;
+            Some(DataVolumeClaim {
+                name: claim.claim_name.clone(),
+                volume_name: volume.name.clone(),
+                from_template: false,
+            })
+        });
+    // the controller names these <template>-<pod>
+    let templated = spec
+        .volume_claim_templates
+        .iter()
+        .flatten()
+        .map(|template| DataVolumeClaim {
+            name: format!("{}-{}", template.name(), pod_name),
+            volume_name: template.name(),
+            from_template: true,
+        });
+    standalone.chain(templated).collect()
+}
+
+/// The name of the node's container in a StatefulSet of the given name
+fn node_container_name(stateful_set_name: &str) -> &'static str {
+    if stateful_set_name.contains("fullnode") {
+        "fullnode"
+    } else {
+        "validator"
+    }
+}
+
+/// The node's container of the StatefulSet, rather than one of its sidecars like HAProxy
+fn node_container(stateful_set: &StatefulSet) -> Option<&Container> {
+    let name = node_container_name(stateful_set.metadata.name.as_deref().unwrap_or_default());
+    stateful_set
+        .spec
+        .as_ref()?
+        .template
+        .spec
+        .as_ref()?
+        .containers
+        .iter()
+        .find(|container| container.name == name)
+}
+
+/// The resources of the node's container of the StatefulSet, none if it has no such container
+pub(crate) fn stateful_set_resources(stateful_set: &StatefulSet) -> Option<NodeResources> {
+    let container = node_container(stateful_set)?;
+    let requirements = container.resources.as_ref();
+    let quantity = |quantities: Option<&BTreeMap<String, Quantity>>, name: &str| {
+        quantities
+            .and_then(|quantities| quantities.get(name))
+            .map(|quantity| quantity.0.clone())
+    };
+    let requests = requirements.and_then(|r| r.requests.as_ref());
+    let limits = requirements.and_then(|r| r.limits.as_ref());
+    Some(NodeResources {
+        cpu_request: quantity(requests, "cpu"),
+        cpu_limit: quantity(limits, "cpu"),
+        memory_request: quantity(requests, "memory"),
+        memory_limit: quantity(limits, "memory"),
+    })
+}
+
+/// The --requests and --limits of kubectl set resources, of what is set
+fn set_resources_args(resources: &NodeResources) -> Vec<String> {
+    let arg = |flag: &str, cpu: &Option<String>, memory: &Option<String>| {
+        let quantities: Vec<_> = [("cpu", cpu), ("memory", memory)]
+            .into_iter()
+            .filter_map(|(name, quantity)| {
+                quantity
+                    .as_ref()
+                    .map(|quantity| format!("{}={}", name, quantity))
+            })
+            .collect();
+        (!quantities.is_empty()).then(|| format!("--{}={}", flag, quantities.join(",")))
+    };
+    [
+        arg(
+            "requests",
+            &resources.cpu_request,
+            &resources.memory_request,
+        ),
+        arg("limits", &resources.cpu_limit, &resources.memory_limit),
+    ]
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+/// Whether the StatefulSet is scaled to run the pod of the given replica
+fn runs_replica(stateful_set: &StatefulSet, replica_index: u32) -> bool {
+    stateful_set
+        .spec
+        .as_ref()
+        .and_then(|spec| spec.replicas)
+        .map_or(true, |replicas| replicas > replica_index as i32)
+}
+
+/// A pod that mounts the given PVCs of the pod of the StatefulSet at the same paths as the node,
+/// and removes everything on them
+fn clear_storage_pod(
+    stateful_set: &StatefulSet,
+    pod_name: &str,
+    claims: Vec<DataVolumeClaim>,
+) -> Result<Pod> {
+    data_volume_pod(
+        stateful_set,
+        pod_name,
+        claims,
+        "clear-storage",
+        |mount_paths| {
+            format!(
+                "find {} -mindepth 1 -maxdepth 1 -exec rm -rf {{}} +",
+                mount_paths.join(" ")
+            )
+        },
+    )
+}
+
+/// Where the node keeps its safety rules storage, which has to be on one of its volumes
+fn safety_rules_storage_path(config: &NodeConfig) -> Result<PathBuf> {
+    match &config.consensus.safety_rules.backend {
+        SecureBackend::OnDiskStorage(storage) => {
+            let mut storage = storage.clone();
+            storage.set_data_dir(config.base.data_dir.clone());
+            Ok(storage.path())
+        },
+        backend => bail!(
+            "Only safety rules storage on disk can be reset, not {:?}",
+            backend
+        ),
+    }
+}
+
+/// A pod that mounts the PVCs of the given pod of the StatefulSet at the same paths as the node,
+/// and removes the safety rules storage at the given path from them
+fn reset_safety_rules_storage_pod(
+    stateful_set: &StatefulSet,
+    pod_name: &str,
+    path: &Path,
+) -> Result<Pod> {
+    data_volume_pod(
+        stateful_set,
+        pod_name,
+        data_volume_claims(stateful_set, pod_name),
+        "reset-safety-rules",
+        |mount_paths| {
+            if mount_paths
+                .iter()
+                .any(|mount_path| path.starts_with(mount_path))
+            {
+                format!("rm -f {}", path.display())
+            } else {
+                // it would be back with the old keys on the next start of the pod
+                format!(
+                    "echo '{} is not on a volume, but on {}' >&2; exit 1",
+                    path.display(),
+                    mount_paths.join(", ")
+                )
+            }
+        },
+    )
+}
+
+/// A pod that mounts the PVCs of the given pod of the StatefulSet at the same paths as the node,
+/// for the DB to be archived from with [db_snapshot_tar_command]. It exits by itself after the
+/// timeout, should forge not get to delete it.
+fn db_snapshot_pod(stateful_set: &StatefulSet, pod_name: &str, timeout: Duration) -> Result<Pod> {
+    data_volume_pod(
+        stateful_set,
+        pod_name,
+        data_volume_claims(stateful_set, pod_name),
+        "db-snapshot",
+        |_| format!("sleep {}", timeout.as_secs()),
+    )
+}
+
+/// The archive of the storage dir, written to stdout. Relative paths, so that it unpacks anywhere.
+fn db_snapshot_tar_command(storage_dir: &str) -> Vec<String> {
+    ["tar", "-czf", "-", "-C", storage_dir, "."]
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect()
+}
+
+fn db_snapshot_file_name(node_name: &str) -> String {
+    format!("{}-db.tar.gz", node_name)
+}
+
+/// A pod running the script in a container named `name`, with the given PVCs of the pod of the
+/// StatefulSet mounted where the node mounts them. The charts mount the data volume at different
+/// paths, so they are taken from the node's container, and passed to the script.
+fn data_volume_pod(
+    stateful_set: &StatefulSet,
+    pod_name: &str,
+    claims: Vec<DataVolumeClaim>,
+    name: &str,
+    script: impl FnOnce(&[String]) -> String,
+) -> Result<Pod> {
+    let template_spec = stateful_set
+        .spec
+        .as_ref()
+        .and_then(|spec| spec.template.spec.as_ref())
+        .ok_or_else(|| format_err!("StatefulSet {} has no pod template", stateful_set.name()))?;
+    let node_container = node_container(stateful_set).ok_or_else(|| {
+        format_err!(
+            "StatefulSet {} has no {} container",
+            stateful_set.name(),
+            node_container_name(&stateful_set.name())
+        )
+    })?;
+
+    let mut volumes = vec![];
+    let mut volume_mounts = vec![];
+    for claim in claims {
+        let mount = node_container
+            .volume_mounts
+            .iter()
+            .flatten()
+            .find(|mount| mount.name == claim.volume_name);
+        let mount_path = match mount {
+            Some(mount) => mount.mount_path.clone(),
+            // not used by the node itself, so there is nothing of the node's on it
+            None => continue,
+        };
+        volumes.push(Volume {
+            name: claim.volume_name.clone(),
+            persistent_volume_claim: Some(PersistentVolumeClaimVolumeSource {
+                claim_name: claim.name,
+                read_only: None,
+            }),
+            ..Volume::default()
+        });
+        volume_mounts.push(VolumeMount {
+            name: claim.volume_name,
+            mount_path,
+            ..VolumeMount::default()
+        });
+    }
+    if volume_mounts.is_empty() {
+        bail!(
+            "Container {} of StatefulSet {} mounts no PersistentVolumeClaims",
+            node_container.name,
+            stateful_set.name()
+        );
+    }
+    let mount_paths: Vec<_> = volume_mounts
+        .iter()
+        .map(|mount| mount.mount_path.clone())
+        .collect();
+    let script = script(&mount_paths);
+
+    Ok(Pod {
+        metadata: ObjectMeta {
+            name: Some(format!("{}-{}", pod_name, name)),
+            ..ObjectMeta::default()
+        },
+        spec: Some(PodSpec {
+            containers: vec![Container {
+                name: name.to_string(),
+                // the node's image is known to be pullable, and has a shell
+                image: node_container.image.clone(),
+                command: Some(vec!["sh".to_string(), "-c".to_string(), script]),
+                volume_mounts: Some(volume_mounts),
+                // the same user as the node's, who owns the files
+                security_context: node_container.security_context.clone(),
+                ..Container::default()
+            }],
+            volumes: Some(volumes),
+            restart_policy: Some("Never".to_string()),
+            security_context: template_spec.security_context.clone(),
+            node_selector: template_spec.node_selector.clone(),
+            tolerations: template_spec.tolerations.clone(),
+            image_pull_secrets: template_spec.image_pull_secrets.clone(),
+            ..PodSpec::default()
+        }),
+        ..Pod::default()
+    })
+}
+
+/// Create the pod, wait until it has run to completion, and delete it again
+async fn run_to_completion(pod_api: &Api<Pod>, pod: Pod, timeout: Duration) -> Result<()> {
+    let pod_name = pod.name();
+    // a leftover from an earlier attempt would make the create fail
+    delete_pod_if_exists(pod_api, &pod_name).await?;
+    pod_api
+        .create(&PostParams::default(), &pod)
+        .await
+        .map_err(|e| K8sError::from_kube(format!("pod {}", pod_name), e))?;
+    let result = wait_for_pod_completion(pod_api, &pod_name, timeout).await;
+    delete_pod_if_exists(pod_api, &pod_name).await?;
+    result
+}
+
+async fn wait_for_pod_completion(
+    pod_api: &Api<Pod>,
+    pod_name: &str,
+    timeout: Duration,
+) -> Result<()> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        let pod = pod_api
+            .get_status(pod_name)
+            .await
+            .map_err(|e| K8sError::from_kube(format!("pod {}", pod_name), e))?;
+        let phase = pod
+            .status
+            .as_ref()
+            .and_then(|status| status.phase.clone())
+            .unwrap_or_default();
+        match phase.as_str() {
+            "Succeeded" => return Ok(()),
+            "Failed" => bail!(
+                "Pod {} failed: {}",
+                pod_name,
+                describe_container_statuses(&pod)
+            ),
+            _ => {},
+        }
+        if Instant::now() >= deadline {
+            return Err(K8sError::Timeout {
+                operation: format!("completion of pod {}", pod_name),
+                message: format!("phase {:?}", phase),
+            }
+            .into());
+        }
+        tokio::time::sleep(CLEAR_STORAGE_POD_POLL_INTERVAL).await;
+    }
+}
+
+/// Poll until the pod runs, failing if it has already exited. Bounded by the caller's timeout.
+async fn wait_for_pod_running(pod_api: &Api<Pod>, pod_name: &str) -> Result<()> {
+    loop {
+        let pod = pod_api
+            .get_status(pod_name)
+            .await
+            .map_err(|e| K8sError::from_kube(format!("pod {}", pod_name), e))?;
+        let phase = pod
+            .status
+            .as_ref()
+            .and_then(|status| status.phase.clone())
+            .unwrap_or_default();
+        match phase.as_str() {
+            "Running" => return Ok(()),
+            "Succeeded" | "Failed" => bail!(
+                "Pod {} exited before it was used: {}",
+                pod_name,
+                describe_container_statuses(&pod)
+            ),
+            _ => {},
+        }
+        tokio::time::sleep(CLEAR_STORAGE_POD_POLL_INTERVAL).await;
+    }
+}
+
+async fn delete_pod_if_exists(pod_api: &Api<Pod>, pod_name: &str) -> Result<()> {
+    match pod_api.delete(pod_name, &DeleteParams::default()).await {
+        Ok(_) => Ok(()),
+        Err(kube::Error::Api(response)) if response.code == 404 => Ok(()),
+        Err(e) => Err(K8sError::from_kube(format!("pod {}", pod_name), e).into()),
+    }
+}
+
+/// Start a command in a container of the pod through the kube API, in its default container if
+/// none is given
+async fn start_exec(
+    pod_api: &Api<Pod>,
+    pod_name: &str,
+    container: Option<&str>,
+    command: &[&str],
+) -> Result<AttachedProcess> {
+    let mut params = AttachParams::default().stdout(true).stderr(true);
+    if let Some(container) = container {
+        params = params.container(container);
+    }
+    pod_api
+        .exec(pod_name, command.to_vec(), &params)
+        .await
+        .map_err(|e| {
+            K8sError::from_kube(format!("exec {:?} in pod {}", command, pod_name), e).into()
+        })
+}
+
+/// Run a command in a container of the pod through the kube API, and collect its output
+async fn exec_in_pod(
+    pod_api: &Api<Pod>,
+    pod_name: &str,
+    container: Option<&str>,
+    command: &[&str],
+) -> Result<ExecOutput> {
+    let mut process = start_exec(pod_api, pod_name, container, command).await?;
+    // read both at once, the command blocks once either of them is full
+    let (stdout, stderr) = futures::try_join!(
+        read_exec_stream(process.stdout()),
+        read_exec_stream(process.stderr())
+    )
+    .with_context(|| {
+        format!(
+            "Failed to read the output of {:?} in pod {}",
+            command, pod_name
+        )
+    })?;
+    Ok(ExecOutput {
+        stdout: String::from_utf8_lossy(&stdout).into_owned(),
+        stderr: String::from_utf8_lossy(&stderr).into_owned(),
+        exit_code: wait_for_exec(process).await?,
+    })
+}
+
+/// Everything written to a stream of an exec'd command, nothing if it was taken already
+async fn read_exec_stream(stream: Option<impl AsyncRead + Unpin>) -> std::io::Result<Vec<u8>> {
+    let mut buf = vec![];
+    if let Some(mut stream) = stream {
+        stream.read_to_end(&mut buf).await?;
+    }
+    Ok(buf)
+}
+
+/// Wait for an exec'd command to complete, and return its exit code
+async fn wait_for_exec(mut process: AttachedProcess) -> Result<Option<i32>> {
+    let status = match process.take_status() {
+        Some(status) => status.await,
+        None => None,
+    };
+    process
+        .join()
+        .await
+        .context("The exec of a command in a pod failed")?;
+    Ok(status.as_ref().and_then(exec_exit_code))
+}
+
+/// The exit code in the Status the API server ends an exec with: Success for 0, otherwise a
+/// Failure with an ExitCode cause
+fn exec_exit_code(status: &Status) -> Option<i32> {
+    if status.status.as_deref() == Some("Success") {
+        return Some(0);
+    }
+    status
+        .details
+        .as_ref()?
+        .causes
+        .as_ref()?
+        .iter()
+        .find(|cause| cause.reason.as_deref() == Some("ExitCode"))?
+        .message
+        .as_ref()?
+        .parse()
+        .ok()
+}
+
+/// A running pod the Service selects, as the one a connection to the Service could end up at
+async fn service_pod(pod_api: &Api<Pod>, service: &Service) -> Result<String> {
+    let selector = service
+        .spec
+        .as_ref()
+        .and_then(|spec| spec.selector.as_ref())
+        .filter(|selector| !selector.is_empty())
+        .ok_or_else(|| format_err!("Service {} selects no pods", service.name()))?;
+    let labels = selector
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect::<Vec<_>>()
+        .join(",");
+    let pods = pod_api
+        .list(&ListParams::default().labels(&labels))
+        .await
+        .map_err(|e| K8sError::from_kube(format!("pods {}", labels), e))?;
+    pods.items
+        .iter()
+        .find(|pod| {
+            pod.metadata.deletion_timestamp.is_none()
+                && pod
+                    .status
+                    .as_ref()
+                    .and_then(|status| status.phase.as_deref())
+                    == Some("Running")
+        })
+        .map(|pod| pod.name())
+        .ok_or_else(|| format_err!("No running pod behind Service {}", service.name()))
+}
+
+/// The port of the pod that `port` of the Service forwards to. A named targetPort is looked up
+/// among the ports of the pod's containers, and none means the same port.
+fn service_target_port(service: &Service, port: u32, pod: &Pod) -> Result<u32> {
+    let service_port = service
+        .spec
+        .as_ref()
+        .and_then(|spec| spec.ports.as_ref())
+        .and_then(|ports| ports.iter().find(|p| p.port == port as i32))
+        .ok_or_else(|| format_err!("Service {} has no port {}", service.name(), port))?;
+    match &service_port.target_port {
+        None => Ok(port),
+        Some(IntOrString::Int(target_port)) => Ok(*target_port as u32),
+        Some(IntOrString::String(name)) => pod
+            .spec
+            .iter()
+            .flat_map(|spec| &spec.containers)
+            .flat_map(|container| container.ports.iter().flatten())
+            .find(|container_port| container_port.name.as_ref() == Some(name))
+            .map(|container_port| container_port.container_port as u32)
+            .ok_or_else(|| format_err!("Pod {} has no port named {}", pod.name(), name)),
+    }
+}
+
+/// Poll until the PVC no longer exists. On timeout, the error says what is holding it up.
+async fn wait_pvc_deleted(
+    pvc_api: &Api<PersistentVolumeClaim>,
+    name: &str,
+    timeout: Duration,
+) -> Result<()> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        let pvc = match pvc_api.get(name).await {
+            Ok(pvc) => pvc,
+            Err(kube::Error::Api(response)) if response.code == 404 => return Ok(()),
+            Err(e) => return Err(K8sError::from_kube(format!("PVC {}", name), e).into()),
+        };
+        if Instant::now() >= deadline {
+            return Err(K8sError::Timeout {
+                operation: format!("deletion of PVC {}", name),
+                message: describe_pvc(&pvc),
+            }
+            .into());
+        }
+        tokio::time::sleep(PVC_DELETION_POLL_INTERVAL).await;
+    }
+}
+
+/// Describe the state of a PVC that is not going away, e.g.
+/// `phase Bound, terminating, finalizers ["kubernetes.io/pvc-protection"]`
+fn describe_pvc(pvc: &PersistentVolumeClaim) -> String {
+    let phase = pvc
+        .status
+        .as_ref()
+        .and_then(|status| status.phase.clone())
+        .unwrap_or_else(|| "unknown".to_string());
+    let terminating = if pvc.metadata.deletion_timestamp.is_some() {
+        "terminating"
+    } else {
+        "not terminating"
+    };
+    format!(
+        "phase {}, {}, finalizers {:?}",
+        phase,
+        terminating,
+        pvc.finalizers()
+    )
+}
+
+/// A new claim with the same spec as the given one, but not bound to its deleted volume, so that a
+/// fresh volume is provisioned for it
+fn recreated_claim(pvc: &PersistentVolumeClaim) -> PersistentVolumeClaim {
+    // drop the annotations k8s adds when binding the claim
+    let annotations = pvc.metadata.annotations.as_ref().map(|annotations| {
+        annotations
+            .iter()
+            .filter(|(key, _)| !key.starts_with("pv.kubernetes.io/") && !key.starts_with("volume."))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect()
+    });
+    PersistentVolumeClaim {
+        metadata: ObjectMeta {
+            name: pvc.metadata.name.clone(),
+            namespace: pvc.metadata.namespace.clone(),
+            labels: pvc.metadata.labels.clone(),
+            annotations,
+            ..ObjectMeta::default()
+        },
+        spec: pvc.spec.clone().map(|spec| PersistentVolumeClaimSpec {
+            volume_name: None,
+            ..spec
+        }),
+        status: None,
+    }
+}
+
+impl Validator for K8sNode {}
+
+impl FullNode for K8sNode {}
+
+impl Drop for K8sNode {
+    fn drop(&mut self) {
+        self.kill_port_forwards();
+    }
+}
+
+// these are parsed by CI log scripts, so keep the key=value format stable
+impl Display for K8sNode {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} [namespace={} sts={} peer_id={} version={} haproxy={} port_forward={}]",
+            self.name,
+            self.namespace,
+            self.stateful_set_name,
+            self.short_peer_id(),
+            self.version,
+            self.haproxy_enabled,
+            self.port_forward_enabled
+        )
+    }
+}
+
+impl Debug for K8sNode {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} @ {} [namespace={} sts={} peer_id={} version={} haproxy={} port_forward={}]",
+            self.name,
+            self.host(),
+            self.namespace,
+            self.stateful_set_name,
+            self.short_peer_id(),
+            self.version,
+            self.haproxy_enabled,
+            self.port_forward_enabled
+        )
+    }
+}
+
+/// The network identity of a node: its peer id and the key of its noise handshakes
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NodeIdentity {
+    pub peer_id: PeerId,
+    pub network_public_key: x25519::PublicKey,
+}
+
+fn noise_keys(
+    addresses: Result<Vec<NetworkAddress>, aptos_sdk::bcs::Error>,
+) -> Vec<x25519::PublicKey> {
+    addresses
+        .unwrap_or_default()
+        .iter()
+        .filter_map(NetworkAddress::find_noise_proto)
+        .collect()
+}
+
+/// Find the on-chain validator a node's identity belongs to, which is to be the validator it was
+/// deployed as or for, see [K8sNode::verify_identity]
+fn match_on_chain_identity(
+    node_name: &str,
+    is_validator: bool,
+    expected: PeerId,
+    identity: &NodeIdentity,
+    validator_set: &ValidatorSet,
+) -> Result<PeerId> {
+    if is_validator && identity.peer_id != expected {
+        bail!(
+            "Validator {} is deployed as {} but runs as {}",
+            node_name,
+            expected,
+            identity.peer_id
+        );
+    }
+    let owner = if is_validator {
+        let info = validator_set
+            .payload()
+            .find(|info| *info.account_address() == identity.peer_id)
+            .ok_or_else(|| {
+                let on_chain: Vec<_> = validator_set
+                    .payload()
+                    .map(|info| info.account_address().to_string())
+                    .collect();
+                format_err!(
+                    "Validator {} runs as {}, which is not in the on-chain validator set {:?}",
+                    node_name,
+                    identity.peer_id,
+                    on_chain
+                )
+            })?;
+        let on_chain_keys = noise_keys(info.config().validator_network_addresses());
+        if !on_chain_keys.contains(&identity.network_public_key) {
+            bail!(
+                "Validator {} runs with network key {}, but its on-chain validator network addresses have keys {:?}",
+                node_name,
+                identity.network_public_key,
+                on_chain_keys
+            );
+        }
+        *info.account_address()
+    } else {
+        validator_set
+            .payload()
+            .find(|info| {
+                noise_keys(info.config().fullnode_network_addresses())
+                    .contains(&identity.network_public_key)
+            })
+            .map(|info| *info.account_address())
+            .ok_or_else(|| {
+                let on_chain: Vec<_> = validator_set
+                    .payload()
+                    .map(|info| {
+                        format!(
+                            "{}: {:?}",
+                            info.account_address(),
+                            noise_keys(info.config().fullnode_network_addresses())
+                        )
+                    })
+                    .collect();
+                format_err!(
+                    "Fullnode {} runs with network key {}, which is in none of the on-chain fullnode network addresses {:?}",
+                    node_name,
+                    identity.network_public_key,
+                    on_chain
+                )
+            })?
+    };
+    if owner != expected {
+        bail!(
+            "Fullnode {} is deployed for validator {} but is on chain for validator {}",
+            node_name,
+            expected,
+            owner
+        );
+    }
+    Ok(owner)
+}
+
+/// Parse the output of `df -Pk <dir>`, a header and then a line like
+/// "/dev/sdb 1031987208 51380 1031919444 1% /opt/aptos/data"
+fn parse_df(output: &str) -> Result<DiskSpace> {
+    let line = output
+        .lines()
+        .nth(1)
+        .ok_or_else(|| format_err!("Invalid df output {:?}", output))?;
+    let fields: Vec<_> = line.split_whitespace().collect();
+    if fields.len() < 6 {
+        bail!("Invalid df output {:?}", output);
+    }
+    let kib = |field: &str| -> Result<u64> {
+        let kib: u64 = field
+            .parse()
+            .map_err(|e| format_err!("Invalid df output {:?}: {}", output, e))?;
+        Ok(kib * 1024)
+    };
+    Ok(DiskSpace {
+        // mount paths may contain spaces
+        mount_path: fields[5..].join(" "),
+        used_bytes: kib(fields[2])?,
+        available_bytes: kib(fields[3])?,
+    })
+}
+
+/// Parse the output of `du -sk <dir>`, e.g. "1024\t/opt/aptos/data", into bytes
+fn parse_du_bytes(output: &str) -> Result<u64> {
+    let kib = output
+        .split_whitespace()
+        .next()
+        .ok_or_else(|| format_err!("du printed nothing"))?;
+    let kib: u64 = kib
+        .parse()
+        .map_err(|e| format_err!("Invalid du output {:?}: {}", output, e))?;
+    Ok(kib * 1024)
+}
+
+/// The NoPorts counter of the Udp lines of /proc/net/snmp, a header line and a line of values
+fn parse_udp_no_ports(snmp: &str) -> Result<u64> {
+    let mut udp_lines = snmp.lines().filter(|line| line.starts_with("Udp:"));
+    let (header, values) = match (udp_lines.next(), udp_lines.next()) {
+        (Some(header), Some(values)) => (header, values),
+        _ => bail!("No Udp counters in /proc/net/snmp: {:?}", snmp),
+    };
+    let index = header
+        .split_whitespace()
+        .position(|name| name == "NoPorts")
+        .ok_or_else(|| format_err!("No NoPorts counter in {:?}", header))?;
+    values
+        .split_whitespace()
+        .nth(index)
+        .ok_or_else(|| format_err!("No NoPorts value in {:?}", values))?
+        .parse()
+        .map_err(|e| format_err!("Invalid NoPorts value in {:?}: {}", values, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        backend::k8s::port_forward::port_forward_path, REST_API_HAPROXY_SERVICE_PORT,
+        REST_API_SERVICE_PORT,
+    };
+    use anyhow::anyhow;
+    use aptos_config::config::OnDiskStorageConfig;
+    use k8s_openapi::{
+        api::{
+            apps::v1::StatefulSetSpec,
+            core::v1::{
+                ContainerPort, ContainerStateRunning, ContainerStateTerminated,
+                ContainerStateWaiting, ContainerStatus, PersistentVolumeClaimStatus, PodCondition,
+                PodStatus, PodTemplateSpec, ResourceRequirements, ServicePort, ServiceSpec,
+            },
+        },
+        apimachinery::pkg::apis::meta::v1::{StatusCause, StatusDetails, Time},
+        chrono,
+    };
+    use std::{
+        collections::BTreeMap,
+        net::{TcpListener, TcpStream},
+    };
+
+    fn make_node(port_forward_enabled: bool) -> K8sNode {
+        K8sNode {
+            name: "validator-0".to_string(),
+            stateful_set_name: "aptos-node-0-validator".to_string(),
+            replica_index: 0,
+            shares_stateful_set: false,
+            peer_id: PeerId::random(),
+            index: 0,
+            service_name: "aptos-node-0-validator.forge-test.svc".to_string(),
+            rest_api_port: AtomicU32::new(REST_API_SERVICE_PORT),
+            rest_api_service_port: REST_API_SERVICE_PORT,
+            rest_api_haproxy_service_port: REST_API_HAPROXY_SERVICE_PORT,
+            inspection_service_port: AtomicU32::new(
+                if port_forward_enabled {
+                    12345
+                } else {
+                    NODE_METRIC_PORT
+                },
+            ),
+            version: Version::new(0, "banana".to_string()),
+            resources: None,
+            namespace: "forge-test".to_string(),
+            backend_config: Arc::new(K8sBackendConfig::default()),
+            haproxy_enabled: false,
+            port_forward_enabled,
+            rest_api_tls: None,
+            ledger_staleness_threshold: Some(DEFAULT_LEDGER_STALENESS_THRESHOLD),
+            clock_skew: Duration::ZERO,
+            memory_stress: None,
+            restarts_by_test: AtomicU32::new(0),
+            port_forwards: Mutex::new(Vec::new()),
+            metrics_port_forward: Mutex::new(None),
+            config: Mutex::new(None),
+        }
+    }
+
+    #[test]
+    fn test_port_forward_args() {
+        let mut node = make_node(true);
+        node.haproxy_enabled = true;
+        node.service_name = "aptos-node-0-validator-lb".to_string();
+        assert_eq!(
+            node.port_forward_service_name(PortForwardTarget::Service),
+            Some("aptos-node-0-validator-lb".to_string())
+        );
+        assert_eq!(
+            node.port_forward_service_name(PortForwardTarget::DirectService),
+            Some("aptos-node-0-validator".to_string())
+        );
+        assert_eq!(node.port_forward_service_name(PortForwardTarget::Pod), None);
+        // always in the node's namespace rather than the one of the current context
+        assert_eq!(
+            port_forward_path(node.namespace(), &node.pod_name(), ADMIN_SERVICE_PORT),
+            "/api/v1/namespaces/forge-test/pods/aptos-node-0-validator-0/portforward?ports=9102"
+        );
+
+        // the Service is looked up by its name, not its DNS name
+        let node = make_node(true);
+        assert_eq!(
+            node.port_forward_service_name(PortForwardTarget::Service),
+            Some("aptos-node-0-validator".to_string())
+        );
+    }
+
+    #[test]
+    fn test_service_target_port() {
+        let service_port = |port: i32, target_port: Option<IntOrString>| ServicePort {
+            port,
+            target_port,
+            ..ServicePort::default()
+        };
+        let service = Service {
+            metadata: ObjectMeta {
+                name: Some("aptos-node-0-validator-lb".to_string()),
+                ..ObjectMeta::default()
+            },
+            spec: Some(ServiceSpec {
+                ports: Some(vec![
+                    service_port(80, Some(IntOrString::Int(8180))),
+                    service_port(9101, Some(IntOrString::String("metrics".to_string()))),
+                    service_port(8080, None),
+                    service_port(9102, Some(IntOrString::String("admin".to_string()))),
+                ]),
+                ..ServiceSpec::default()
+            }),
+            ..Service::default()
+        };
+        let pod = Pod {
+            spec: Some(PodSpec {
+                containers: vec![Container {
+                    ports: Some(vec![ContainerPort {
+                        name: Some("metrics".to_string()),
+                        container_port: 9102,
+                        ..ContainerPort::default()
+                    }]),
+                    ..Container::default()
+                }],
+                ..PodSpec::default()
+            }),
+            ..Pod::default()
+        };
+        // HAProxy's port 80 is not what its container listens on
+        assert_eq!(service_target_port(&service, 80, &pod).unwrap(), 8180);
+        assert_eq!(service_target_port(&service, 9101, &pod).unwrap(), 9102);
+        assert_eq!(service_target_port(&service, 8080, &pod).unwrap(), 8080);
+        service_target_port(&service, 9102, &pod).unwrap_err();
+        service_target_port(&service, 6180, &pod).unwrap_err();
+    }
+
+    #[test]
+    fn test_exec_exit_code() {
+        let status = |status: &str, causes: Option<Vec<StatusCause>>| Status {
+            status: Some(status.to_string()),
+            details: Some(StatusDetails {
+                causes,
+                ..StatusDetails::default()
+            }),
+            ..Status::default()
+        };
+        let exit_code = |code: &str| StatusCause {
+            reason: Some("ExitCode".to_string()),
+            message: Some(code.to_string()),
+            ..StatusCause::default()
+        };
+        assert_eq!(exec_exit_code(&status("Success", None)), Some(0));
+        assert_eq!(
+            exec_exit_code(&status("Failure", Some(vec![exit_code("137")]))),
+            Some(137)
+        );
+        // e.g. the container could not be exec'd into
+        assert_eq!(exec_exit_code(&status("Failure", None)), None);
+        assert_eq!(
+            exec_exit_code(&status("Failure", Some(vec![exit_code("oops")]))),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_direct_service_name() {
+        let mut node = make_node(false);
+        node.rest_client_via_haproxy().unwrap_err();
+        assert_eq!(
+            node.direct_service_name(),
+            "aptos-node-0-validator.forge-test.svc"
+        );
+
+        node.haproxy_enabled = true;
+        node.service_name = "aptos-node-0-validator-lb.forge-test.svc.cluster-a".to_string();
+        assert_eq!(
+            node.direct_service_name(),
+            "aptos-node-0-validator.forge-test.svc.cluster-a"
+        );
+        assert_eq!(
+            node.direct_rest_api_endpoint().await.unwrap().as_str(),
+            "http://aptos-node-0-validator.forge-test.svc.cluster-a:8080/v1"
+        );
+        node.service_name = "aptos-node-0-fullnode-lb".to_string();
+        assert_eq!(node.direct_service_name(), "aptos-node-0-fullnode");
+    }
+
+    #[test]
+    fn test_node_display() {
+        let mut node = make_node(true);
+        node.peer_id =
+            PeerId::from_hex("4d8a1b2c00000000000000000000000000000000000000000000000000000000")
+                .unwrap();
+        assert_eq!(
+            node.to_string(),
+            "validator-0 [namespace=forge-test sts=aptos-node-0-validator peer_id=4d8a1b2c version=banana haproxy=false port_forward=true]"
+        );
+        assert_eq!(
+            format!("{:?}", node),
+            "validator-0 @ 127.0.0.1 [namespace=forge-test sts=aptos-node-0-validator peer_id=4d8a1b2c version=banana haproxy=false port_forward=true]"
+        );
+        assert_eq!(
+            node.describe_pod_command(),
+            "kubectl -n forge-test describe pod aptos-node-0-validator-0"
+        );
+    }
+
+    #[test]
+    fn test_inspection_service_endpoint() {
+        let node = make_node(false);
+        assert_eq!(
+            node.inspection_service_endpoint().as_str(),
+            "http://aptos-node-0-validator.forge-test.svc:9101/"
+        );
+        assert_eq!(
+            node.rest_api_endpoint().as_str(),
+            "http://aptos-node-0-validator.forge-test.svc:8080/v1"
+        );
+
+        let node = make_node(true);
+        assert_eq!(
+            node.inspection_service_endpoint().as_str(),
+            "http://127.0.0.1:12345/"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_expose_metric_without_port_forward() {
+        // the metrics port of the Service, not one of a port-forward
+        let node = make_node(false);
+        assert_eq!(node.expose_metric().await.unwrap().port(), 9101);
+        assert!(node.metrics_port_forward.lock().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_rest_api_tls_endpoint() {
+        let mut node = make_node(false);
+        node.rest_api_tls = Some(RestApiTls::default());
+        assert_eq!(
+            node.rest_api_endpoint().as_str(),
+            "https://aptos-node-0-validator.forge-test.svc:8080/v1"
+        );
+        // only the REST API is behind TLS
+        assert_eq!(
+            node.inspection_service_endpoint().as_str(),
+            "http://aptos-node-0-validator.forge-test.svc:9101/"
+        );
+    }
+
+    #[test]
+    fn test_describe_container_statuses() {
+        let pod = |ready: bool, state: ContainerState| Pod {
+            status: Some(PodStatus {
+                conditions: Some(vec![PodCondition {
+                    type_: "Ready".to_string(),
+                    status: if ready { "True" } else { "False" }.to_string(),
+                    ..PodCondition::default()
+                }]),
+                container_statuses: Some(vec![ContainerStatus {
+                    name: "validator".to_string(),
+                    ready,
+                    restart_count: 3,
+                    state: Some(state),
+                    ..ContainerStatus::default()
+                }]),
+                ..PodStatus::default()
+            }),
+            ..Pod::default()
+        };
+
+        let crash_looping = pod(false, ContainerState {
+            waiting: Some(ContainerStateWaiting {
+                reason: Some("CrashLoopBackOff".to_string()),
+                message: Some("back-off 40s".to_string()),
+            }),
+            ..ContainerState::default()
+        });
+        assert!(!pod_is_ready(&crash_looping));
+        assert_eq!(
+            describe_container_statuses(&crash_looping),
+            "validator: waiting (CrashLoopBackOff: back-off 40s), 3 restarts"
+        );
+
+        let running = pod(true, ContainerState {
+            running: Some(ContainerStateRunning::default()),
+            ..ContainerState::default()
+        });
+        assert!(pod_is_ready(&running));
+        assert_eq!(
+            describe_container_statuses(&running),
+            "validator: ready, 3 restarts"
+        );
+
+        // a pod that was just created has no conditions yet
+        assert!(!pod_is_ready(&Pod::default()));
+        assert_eq!(
+            describe_container_statuses(&Pod::default()),
+            "pod has no status"
+        );
+    }
+
+    #[test]
+    fn test_describe_pod() {
+        let pod = Pod {
+            metadata: ObjectMeta {
+                name: Some("aptos-node-0-validator-0".to_string()),
+                namespace: Some("forge-main".to_string()),
+                ..ObjectMeta::default()
+            },
+            status: Some(PodStatus {
+                phase: Some("Running".to_string()),
+                conditions: Some(vec![PodCondition {
+                    type_: "Ready".to_string(),
+                    status: "False".to_string(),
+                    ..PodCondition::default()
+                }]),
+                ..PodStatus::default()
+            }),
+            ..Pod::default()
+        };
+        let description = describe_pod(&pod).unwrap();
+        let lines: Vec<_> = description.lines().collect();
+        assert_eq!(lines[0], "Name: aptos-node-0-validator-0");
+        assert_eq!(lines[2], "Node: <none>");
+        assert_eq!(lines[3], "Phase: Running");
+        assert_eq!(lines[6], "Conditions: Ready=False");
+        assert_eq!(
+            lines[7],
+            "Containers: pod is Running without container statuses"
+        );
+        assert!(description.contains("\nStatus:\n"));
+    }
+
+    #[test]
+    fn test_container_failure() {
+        let pod = |state: ContainerState, last_state: Option<ContainerState>| Pod {
+            metadata: ObjectMeta {
+                name: Some("aptos-node-0-validator-0".to_string()),
+                ..ObjectMeta::default()
+            },
+            status: Some(PodStatus {
+                container_statuses: Some(vec![ContainerStatus {
+                    name: "validator".to_string(),
+                    restart_count: 4,
+                    state: Some(state),
+                    last_state,
+                    ..ContainerStatus::default()
+                }]),
+                ..PodStatus::default()
+            }),
+            ..Pod::default()
+        };
+        let waiting = |reason: &str| ContainerState {
+            waiting: Some(ContainerStateWaiting {
+                reason: Some(reason.to_string()),
+                message: Some(format!("{} message", reason)),
+            }),
+            ..ContainerState::default()
+        };
+        let terminated = |reason: &str, message: Option<&str>| ContainerState {
+            terminated: Some(ContainerStateTerminated {
+                reason: Some(reason.to_string()),
+                message: message.map(str::to_string),
+                exit_code: 137,
+                ..ContainerStateTerminated::default()
+            }),
+            ..ContainerState::default()
+        };
+
+        let oom_looping = pod(
+            waiting("CrashLoopBackOff"),
+            Some(terminated("OOMKilled", None)),
+        );
+        let failure = container_failure(&oom_looping, None).unwrap();
+        assert_eq!(
+            failure.to_string(),
+            "Container validator of pod aptos-node-0-validator-0 is failing with \
+             CrashLoopBackOff (OOMKilled) after 4 restarts: no termination message"
+        );
+        assert!(K8sError::is_container_failing(&failure.into()));
+
+        let panicking = pod(
+            waiting("CrashLoopBackOff"),
+            Some(terminated("Error", Some("thread 'main' panicked"))),
+        );
+        assert!(matches!(
+            container_failure(&panicking, None),
+            Some(K8sError::ContainerFailing { message, .. }) if message == "thread 'main' panicked"
+        ));
+
+        let bad_image = pod(waiting("ImagePullBackOff"), None);
+        assert!(matches!(
+            container_failure(&bad_image, None),
+            Some(K8sError::ContainerFailing { reason, message, .. })
+                if reason == "ImagePullBackOff" && message == "ImagePullBackOff message"
+        ));
+
+        // still starting, or running after an earlier crash
+        assert!(container_failure(&pod(waiting("ContainerCreating"), None), None).is_none());
+        let running = ContainerState {
+            running: Some(ContainerStateRunning::default()),
+            ..ContainerState::default()
+        };
+        assert!(
+            container_failure(&pod(running, Some(terminated("OOMKilled", None))), None).is_none()
+        );
+        assert!(container_failure(&Pod::default(), None).is_none());
+    }
+
+    #[test]
+    fn test_oom_killed_during() {
+        let time = |secs| DateTime::<Utc>::from(UNIX_EPOCH + Duration::from_secs(secs));
+        let oom_killed_at = |secs| Pod {
+            metadata: ObjectMeta {
+                name: Some("aptos-node-0-validator-0".to_string()),
+                ..ObjectMeta::default()
+            },
+            status: Some(PodStatus {
+                container_statuses: Some(vec![ContainerStatus {
+                    name: "validator".to_string(),
+                    state: Some(ContainerState {
+                        waiting: Some(ContainerStateWaiting {
+                            reason: Some("CrashLoopBackOff".to_string()),
+                            message: None,
+                        }),
+                        ..ContainerState::default()
+                    }),
+                    last_state: Some(ContainerState {
+                        terminated: Some(ContainerStateTerminated {
+                            reason: Some("OOMKilled".to_string()),
+                            exit_code: 137,
+                            finished_at: Some(Time(time(secs))),
+                            ..ContainerStateTerminated::default()
+                        }),
+                        ..ContainerState::default()
+                    }),
+                    ..ContainerStatus::default()
+                }]),
+                ..PodStatus::default()
+            }),
+            ..Pod::default()
+        };
+        let window = Some((time(100), Some(time(200))));
+        // expected, so not a failure that waiting won't fix
+        assert!(oom_killed_during(&oom_killed_at(150), window)
+            .unwrap()
+            .contains("validator of pod aptos-node-0-validator-0"));
+        assert!(container_failure(&oom_killed_at(150), window).is_none());
+        // before or after the memory stress, or without one
+        assert!(oom_killed_during(&oom_killed_at(50), window).is_none());
+        assert!(container_failure(&oom_killed_at(250), window).is_some());
+        assert!(oom_killed_during(&oom_killed_at(150), None).is_none());
+        // still stressed
+        assert!(oom_killed_during(&oom_killed_at(250), Some((time(100), None))).is_some());
+    }
+
+    #[test]
+    fn test_replicas_to_scale_to() {
+        // a StatefulSet with a single node
+        assert_eq!(replicas_to_scale_to(0, 1, None, false).unwrap(), (0, None));
+        assert_eq!(replicas_to_scale_to(0, 0, None, true).unwrap(), (1, None));
+        // stopping and starting again is a noop
+        assert_eq!(replicas_to_scale_to(0, 0, None, false).unwrap(), (0, None));
+        assert_eq!(replicas_to_scale_to(0, 1, None, true).unwrap(), (1, None));
+
+        // the last of three replicas
+        assert_eq!(replicas_to_scale_to(2, 3, None, false).unwrap(), (2, None));
+        assert_eq!(replicas_to_scale_to(2, 2, None, true).unwrap(), (3, None));
+        // a replica in the middle stops the last one too, which starts again with it
+        assert_eq!(
+            replicas_to_scale_to(1, 3, None, false).unwrap(),
+            (1, Some(3))
+        );
+        assert_eq!(
+            replicas_to_scale_to(1, 1, Some(3), true).unwrap(),
+            (3, None)
+        );
+        // a replica before it as well, what to restore stays
+        assert_eq!(
+            replicas_to_scale_to(0, 1, Some(3), false).unwrap(),
+            (0, Some(3))
+        );
+        assert_eq!(
+            replicas_to_scale_to(0, 0, Some(3), true).unwrap(),
+            (3, None)
+        );
+        // a replica can't be started while the ones before it are stopped
+        let err = replicas_to_scale_to(2, 1, Some(3), true).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("replicas 1..2 have to be started first"));
+        // the middle replica is already running
+        assert_eq!(replicas_to_scale_to(1, 3, None, true).unwrap(), (3, None));
+        // the last replica was stopped on its own before, so it stays stopped
+        assert_eq!(
+            replicas_to_scale_to(0, 2, None, false).unwrap(),
+            (0, Some(2))
+        );
+        assert_eq!(
+            replicas_to_scale_to(0, 0, Some(2), true).unwrap(),
+            (2, None)
+        );
+    }
+
+    #[test]
+    fn test_grace_period_expired() {
+        let now = Utc::now();
+        let pod = |deletion_timestamp: Option<DateTime<Utc>>| Pod {
+            metadata: ObjectMeta {
+                deletion_timestamp: deletion_timestamp.map(Time),
+                ..ObjectMeta::default()
+            },
+            ..Pod::default()
+        };
+        assert!(!grace_period_expired(&pod(None), now));
+        assert!(!grace_period_expired(
+            &pod(Some(now + chrono::Duration::seconds(30))),
+            now
+        ));
+        assert!(grace_period_expired(
+            &pod(Some(now - chrono::Duration::seconds(1))),
+            now
+        ));
+    }
+
+    /// A StatefulSet with both a claim named by its pod template and a volumeClaimTemplate
+    fn data_volume_stateful_set() -> StatefulSet {
+        StatefulSet {
+            spec: Some(StatefulSetSpec {
+                template: PodTemplateSpec {
+                    spec: Some(PodSpec {
+                        volumes: Some(vec![
+                            Volume {
+                                name: "aptos-data".to_string(),
+                                persistent_volume_claim: Some(PersistentVolumeClaimVolumeSource {
+                                    claim_name: "aptos-node-0-validator-e42".to_string(),
+                                    ..PersistentVolumeClaimVolumeSource::default()
+                                }),
+                                ..Volume::default()
+                            },
+                            Volume {
+                                name: "aptos-config".to_string(),
+                                ..Volume::default()
+                            },
+                        ]),
+                        ..PodSpec::default()
+                    }),
+                    ..PodTemplateSpec::default()
+                },
+                volume_claim_templates: Some(vec![PersistentVolumeClaim {
+                    metadata: ObjectMeta {
+                        name: Some("fn".to_string()),
+                        ..ObjectMeta::default()
+                    },
+                    ..PersistentVolumeClaim::default()
+                }]),
+                ..StatefulSetSpec::default()
+            }),
+            ..StatefulSet::default()
+        }
+    }
+
+    #[test]
+    fn test_data_volume_claims() {
+        let stateful_set = data_volume_stateful_set();
+        assert_eq!(
+            data_volume_claims(&stateful_set, "aptos-node-0-validator-0"),
+            vec![
+                DataVolumeClaim {
+                    name: "aptos-node-0-validator-e42".to_string(),
+                    volume_name: "aptos-data".to_string(),
+                    from_template: false,
+                },
+                DataVolumeClaim {
+                    name: "fn-aptos-node-0-validator-0".to_string(),
+                    volume_name: "fn".to_string(),
+                    from_template: true,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_clear_storage_args() {
+        let stateful_set = data_volume_stateful_set();
+        let mut node = make_node(false);
+        // a node of its own owns all of its StatefulSet's claims
+        assert_eq!(
+            node.clear_storage_claims(&stateful_set)
+                .into_iter()
+                .map(|claim| claim.name)
+                .collect::<Vec<_>>(),
+            vec!["aptos-node-0-validator-e42", "fn-aptos-node-0-validator-0"]
+        );
+
+        // a replica only owns the claim of its pod, the others' data must not be touched
+        node.shares_stateful_set = true;
+        node.replica_index = 1;
+        assert_eq!(node.clear_storage_claims(&stateful_set), vec![
+            DataVolumeClaim {
+                name: "fn-aptos-node-0-validator-1".to_string(),
+                volume_name: "fn".to_string(),
+                from_template: true,
+            }
+        ]);
+    }
+
+    #[test]
+    fn test_clear_storage_pod() {
+        let mount = |name: &str, mount_path: &str| VolumeMount {
+            name: name.to_string(),
+            mount_path: mount_path.to_string(),
+            ..VolumeMount::default()
+        };
+        let mut stateful_set = StatefulSet {
+            metadata: ObjectMeta {
+                name: Some("aptos-node-0-fullnode-e42".to_string()),
+                ..ObjectMeta::default()
+            },
+            spec: Some(StatefulSetSpec {
+                template: PodTemplateSpec {
+                    spec: Some(PodSpec {
+                        containers: vec![Container {
+                            name: "fullnode".to_string(),
+                            image: Some("aptoslabs/validator:banana".to_string()),
+                            volume_mounts: Some(vec![
+                                mount("aptos-config", "/opt/aptos/etc"),
+                                mount("fn", "/storage"),
+                            ]),
+                            ..Container::default()
+                        }],
+                        ..PodSpec::default()
+                    }),
+                    ..PodTemplateSpec::default()
+                },
+                volume_claim_templates: Some(vec![PersistentVolumeClaim {
+                    metadata: ObjectMeta {
+                        name: Some("fn".to_string()),
+                        ..ObjectMeta::default()
+                    },
+                    ..PersistentVolumeClaim::default()
+                }]),
+                ..StatefulSetSpec::default()
+            }),
+            ..StatefulSet::default()
+        };
+
+        let pod = clear_storage_pod(
+            &stateful_set,
+            "aptos-node-0-fullnode-e42-0",
+            data_volume_claims(&stateful_set, "aptos-node-0-fullnode-e42-0"),
+        )
+        .unwrap();
+        assert_eq!(pod.name(), "aptos-node-0-fullnode-e42-0-clear-storage");
+        let spec = pod.spec.unwrap();
+        assert_eq!(spec.restart_policy.as_deref(), Some("Never"));
+        let volumes = spec.volumes.unwrap();
+        assert_eq!(
+            volumes[0]
+                .persistent_volume_claim
+                .as_ref()
+                .unwrap()
+                .claim_name,
+            "fn-aptos-node-0-fullnode-e42-0"
+        );
+        let container = &spec.containers[0];
+        assert_eq!(
+            container.image.as_deref(),
+            Some("aptoslabs/validator:banana")
+        );
+        // mounted where the fullnode chart mounts it, the config volume is left alone
+        assert_eq!(container.volume_mounts.as_ref().unwrap(), &vec![mount(
+            "fn", "/storage"
+        )]);
+        assert_eq!(
+            container.command.as_ref().unwrap()[2],
+            "find /storage -mindepth 1 -maxdepth 1 -exec rm -rf {} +"
+        );
+
+        // the same volumes, for the DB to be archived from
+        let pod = db_snapshot_pod(
+            &stateful_set,
+            "aptos-node-0-fullnode-e42-0",
+            Duration::from_secs(600),
+        )
+        .unwrap();
+        assert_eq!(pod.name(), "aptos-node-0-fullnode-e42-0-db-snapshot");
+        let container = &pod.spec.as_ref().unwrap().containers[0];
+        assert_eq!(container.name, "db-snapshot");
+        assert_eq!(container.volume_mounts.as_ref().unwrap(), &vec![mount(
+            "fn", "/storage"
+        )]);
+        assert_eq!(container.command.as_ref().unwrap()[2], "sleep 600");
+        assert_eq!(db_snapshot_tar_command("/storage/db"), vec![
+            "tar",
+            "-czf",
+            "-",
+            "-C",
+            "/storage/db",
+            "."
+        ]);
+
+        // the safety rules storage is removed from the volume it is on
+        let pod = reset_safety_rules_storage_pod(
+            &stateful_set,
+            "aptos-node-0-fullnode-e42-0",
+            Path::new("/storage/secure-data.json"),
+        )
+        .unwrap();
+        assert_eq!(pod.name(), "aptos-node-0-fullnode-e42-0-reset-safety-rules");
+        let container = &pod.spec.as_ref().unwrap().containers[0];
+        assert_eq!(
+            container.command.as_ref().unwrap()[2],
+            "rm -f /storage/secure-data.json"
+        );
+        let pod = reset_safety_rules_storage_pod(
+            &stateful_set,
+            "aptos-node-0-fullnode-e42-0",
+            Path::new("/tmp/secure-data.json"),
+        )
+        .unwrap();
+        assert!(pod.spec.unwrap().containers[0].command.as_ref().unwrap()[2].ends_with("exit 1"));
+
+        let mut config = NodeConfig::default();
+        config.base.data_dir = PathBuf::from("/storage");
+        // relative to the data dir, as in the chart's configs
+        let mut storage = OnDiskStorageConfig::default();
+        storage.path = PathBuf::from("secure-data.json");
+        config.consensus.safety_rules.backend = SecureBackend::OnDiskStorage(storage);
+        assert_eq!(
+            safety_rules_storage_path(&config).unwrap(),
+            PathBuf::from("/storage/secure-data.json")
+        );
+        config.consensus.safety_rules.backend = SecureBackend::InMemoryStorage;
+        safety_rules_storage_path(&config).unwrap_err();
+
+        // without a mounted PVC there is nothing to clear in place
+        stateful_set.spec.as_mut().unwrap().volume_claim_templates = None;
+        clear_storage_pod(
+            &stateful_set,
+            "aptos-node-0-fullnode-e42-0",
+            data_volume_claims(&stateful_set, "aptos-node-0-fullnode-e42-0"),
+        )
+        .unwrap_err();
+    }
+
+    #[test]
+    fn test_node_resources() {
+        let quantities = |cpu: &str, memory: &str| {
+            BTreeMap::from([
+                ("cpu".to_string(), Quantity(cpu.to_string())),
+                ("memory".to_string(), Quantity(memory.to_string())),
+            ])
+        };
+        let container = |name: &str, resources: Option<ResourceRequirements>| Container {
+            name: name.to_string(),
+            resources,
+            ..Container::default()
+        };
+        let mut stateful_set = StatefulSet {
+            spec: Some(StatefulSetSpec {
+                template: PodTemplateSpec {
+                    spec: Some(PodSpec {
+                        containers: vec![
+                            container(
+                                "validator",
+                                Some(ResourceRequirements {
+                                    requests: Some(quantities("14", "56Gi")),
+                                    limits: Some(BTreeMap::from([(
+                                        "cpu".to_string(),
+                                        Quantity("16".to_string()),
+                                    )])),
+                                    ..ResourceRequirements::default()
+                                }),
+                            ),
+                            // the resources of the sidecars don't count
+                            container(
+                                "haproxy",
+                                Some(ResourceRequirements {
+                                    requests: Some(quantities("1", "1Gi")),
+                                    ..ResourceRequirements::default()
+                                }),
+                            ),
+                        ],
+                        ..PodSpec::default()
+                    }),
+                    ..PodTemplateSpec::default()
+                },
+                ..StatefulSetSpec::default()
+            }),
+            ..StatefulSet::default()
+        };
+        let resources = stateful_set_resources(&stateful_set).unwrap();
+        assert_eq!(resources, NodeResources {
+            cpu_request: Some("14".to_string()),
+            cpu_limit: Some("16".to_string()),
+            memory_request: Some("56Gi".to_string()),
+            memory_limit: None,
+        });
+        // the node's container is found by its name, wherever the sidecars are
+        let containers = &mut stateful_set
+            .spec
+            .as_mut()
+            .unwrap()
+            .template
+            .spec
+            .as_mut()
+            .unwrap()
+            .containers;
+        containers.reverse();
+        assert_eq!(stateful_set_resources(&stateful_set).unwrap(), resources);
+        assert_eq!(set_resources_args(&resources), vec![
+            "--requests=cpu=14,memory=56Gi",
+            "--limits=cpu=16"
+        ]);
+        assert_eq!(set_resources_args(&NodeResources::new(8, 32)), vec![
+            "--requests=cpu=8,memory=32Gi",
+            "--limits=cpu=8,memory=32Gi"
+        ]);
+        assert_eq!(stateful_set_resources(&StatefulSet::default()), None);
+    }
+
+    #[test]
+    fn test_recreated_claim() {
+        let pvc = PersistentVolumeClaim {
+            metadata: ObjectMeta {
+                name: Some("aptos-node-0-validator-e42".to_string()),
+                annotations: Some(BTreeMap::from([
+                    (
+                        "meta.helm.sh/release-name".to_string(),
+                        "aptos-node".to_string(),
+                    ),
+                    (
+                        "pv.kubernetes.io/bind-completed".to_string(),
+                        "yes".to_string(),
+                    ),
+                ])),
+                finalizers: Some(vec!["kubernetes.io/pvc-protection".to_string()]),
+                resource_version: Some("1234".to_string()),
+                ..ObjectMeta::default()
+            },
+            spec: Some(PersistentVolumeClaimSpec {
+                storage_class_name: Some("ssd".to_string()),
+                volume_name: Some("pvc-5678".to_string()),
+                ..PersistentVolumeClaimSpec::default()
+            }),
+            status: Some(PersistentVolumeClaimStatus {
+                phase: Some("Bound".to_string()),
+                ..PersistentVolumeClaimStatus::default()
+            }),
+        };
+        assert_eq!(
+            describe_pvc(&pvc),
+            "phase Bound, not terminating, finalizers [\"kubernetes.io/pvc-protection\"]"
+        );
+
+        let recreated = recreated_claim(&pvc);
+        assert_eq!(recreated.metadata.name, pvc.metadata.name);
+        assert_eq!(recreated.metadata.resource_version, None);
+        assert_eq!(
+            recreated
+                .metadata
+                .annotations
+                .unwrap()
+                .keys()
+                .collect::<Vec<_>>(),
+            vec!["meta.helm.sh/release-name"]
+        );
+        let spec = recreated.spec.unwrap();
+        assert_eq!(spec.storage_class_name.as_deref(), Some("ssd"));
+        // the old volume is gone, a new one has to be provisioned
+        assert_eq!(spec.volume_name, None);
+        assert!(recreated.status.is_none());
+    }
+
+    /// Stands in for a port-forward: binds the local port, failing if it is taken
+    fn bind_port(mut port: ReservedPort) -> Result<TcpListener, PortForwardError> {
+        port.release();
+        TcpListener::bind((LOCALHOST, port.port() as u16))
+            .map_err(|_| PortForwardError::PortInUse(port.port()))
+    }
+
+    #[tokio::test]
+    async fn test_retry_on_port_conflict() {
+        // another process grabs the first port in between its release and it being bound
+        let mut occupied = None;
+        let (port, listener) = retry_on_port_conflict(|mut port| {
+            if occupied.is_none() {
+                port.release();
+                occupied = Some(TcpListener::bind((LOCALHOST, port.port() as u16)).unwrap());
+            }
+            async move { bind_port(port) }
+        })
+        .await
+        .unwrap();
+        let occupied_port = occupied.unwrap().local_addr().unwrap().port() as u32;
+        assert_ne!(port, occupied_port);
+        // the new port is the one actually being served
+        assert_eq!(listener.local_addr().unwrap().port() as u32, port);
+        TcpStream::connect((LOCALHOST, port as u16)).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_retry_on_port_conflict_gives_up() {
+        let mut attempts = 0;
+        let mut first_port = None;
+        let err = retry_on_port_conflict(|port| {
+            attempts += 1;
+            first_port.get_or_insert(port.port());
+            async move { Err::<(), _>(PortForwardError::PortInUse(port.port())) }
+        })
+        .await
+        .unwrap_err();
+        assert_eq!(attempts, PORT_FORWARD_MAX_ATTEMPTS);
+        assert!(err.to_string().contains(&first_port.unwrap().to_string()));
+
+        // other errors are not retried
+        let mut attempts = 0;
+        retry_on_port_conflict(|_| {
+            attempts += 1;
+            async { Err::<(), _>(anyhow!("pod not found").into()) }
+        })
+        .await
+        .unwrap_err();
+        assert_eq!(attempts, 1);
+    }
+
+    #[test]
+    fn test_lost_fields() {
+        let original = r#"
+base:
+  role: validator
+mempool:
+  capacity: 100
+  future_flag: true
+seeds:
+  - addr: a
+    weight: 1
+"#;
+        let original: serde_yaml::Value = serde_yaml::from_str(original).unwrap();
+        assert!(lost_fields(&original, &original).is_empty());
+
+        let round_trip = r#"
+base:
+  role: validator
+  waypoint: none
+mempool:
+  capacity: 100
+seeds:
+  - addr: a
+"#;
+        let round_trip: serde_yaml::Value = serde_yaml::from_str(round_trip).unwrap();
+        assert_eq!(lost_fields(&original, &round_trip), vec![
+            "mempool.future_flag",
+            "seeds[0].weight"
+        ]);
+
+        // a NodeConfig survives its own round trip
+        let config = serde_yaml::to_value(NodeConfig::default()).unwrap();
+        let round_trip = serde_yaml::to_string(&config).unwrap();
+        let round_trip = NodeConfig::parse_serialized_config(&round_trip).unwrap();
+        assert!(lost_fields(&config, &serde_yaml::to_value(round_trip).unwrap()).is_empty());
+    }
+
+    #[test]
+    fn test_check_ledger_freshness() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_000);
+        let threshold = Duration::from_secs(30);
+        let usecs = |secs: u64| secs * 1_000_000;
+
+        check_ledger_freshness(usecs(990), now, threshold).unwrap();
+        // clocks may be slightly skewed
+        check_ledger_freshness(usecs(1_001), now, threshold).unwrap();
+        assert!(matches!(
+            check_ledger_freshness(usecs(900), now, threshold),
+            Err(HealthCheckError::Stale(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_df() {
+        let disk_space = parse_df(
+            "Filesystem     1024-blocks    Used Available Capacity Mounted on\n\
+             /dev/sdb           1000000  900000     80000      92% /opt/aptos/data\n",
+        )
+        .unwrap();
+        assert_eq!(disk_space, DiskSpace {
+            mount_path: "/opt/aptos/data".to_string(),
+            used_bytes: 900_000 * 1024,
+            available_bytes: 80_000 * 1024,
+        });
+        assert_eq!(disk_space.fill_file(), "/opt/aptos/data/forge-disk-fill");
+        assert_eq!(disk_space.bytes_to_fill(90), 0);
+        assert_eq!(disk_space.bytes_to_fill(100), 80_000 * 1024);
+        assert_eq!(disk_space.bytes_to_fill(98), (960_400 - 900_000) * 1024);
+        parse_df("df: /opt/aptos/data: No such file or directory").unwrap_err();
+    }
+
+    #[test]
+    fn test_parse_du_bytes() {
+        assert_eq!(
+            parse_du_bytes("1024\t/opt/aptos/data\n").unwrap(),
+            1024 * 1024
+        );
+        parse_du_bytes("").unwrap_err();
+        parse_du_bytes("du: cannot access '/opt/aptos/data'").unwrap_err();
+    }
+
+    #[test]
+    fn test_parse_udp_no_ports() {
+        let snmp = "Tcp: RtoAlgorithm RtoMin\n\
+                    Tcp: 1 200\n\
+                    Udp: InDatagrams NoPorts InErrors OutDatagrams\n\
+                    Udp: 120 7 0 130\n\
+                    UdpLite: InDatagrams NoPorts\n\
+                    UdpLite: 0 0\n";
+        assert_eq!(parse_udp_no_ports(snmp).unwrap(), 7);
+        parse_udp_no_ports("Tcp: RtoAlgorithm\nTcp: 1\n").unwrap_err();
+    }
+
+    #[test]
+    fn test_match_on_chain_identity() {
+        use aptos_sdk::{
+            crypto::{bls12381, PrivateKey, Uniform},
+            types::{validator_config::ValidatorConfig, validator_info::ValidatorInfo},
+        };
+
+        let identity = || NodeIdentity {
+            peer_id: PeerId::random(),
+            network_public_key: x25519::PrivateKey::generate_for_testing().public_key(),
+        };
+        let addresses = |identity: &NodeIdentity| {
+            let address = NetworkAddress::from_str("/dns/aptos-node-0-validator/tcp/6180")
+                .unwrap()
+                .append_prod_protos(identity.network_public_key, 0);
+            aptos_sdk::bcs::to_bytes(&vec![address]).unwrap()
+        };
+        let (validator, fullnode, stranger) = (identity(), identity(), identity());
+        let validator_set = ValidatorSet::new(vec![ValidatorInfo::new(
+            validator.peer_id,
+            1,
+            ValidatorConfig::new(
+                bls12381::PrivateKey::generate_for_testing().public_key(),
+                addresses(&validator),
+                addresses(&fullnode),
+                0,
+            ),
+        )]);
+
+        let matched = |is_validator, expected, identity: &NodeIdentity| {
+            match_on_chain_identity("node", is_validator, expected, identity, &validator_set)
+        };
+        assert_eq!(
+            matched(true, validator.peer_id, &validator).unwrap(),
+            validator.peer_id
+        );
+        assert_eq!(
+            matched(false, validator.peer_id, &fullnode).unwrap(),
+            validator.peer_id
+        );
+        // deployed as, or for, another validator than the one it is on chain
+        assert!(matched(true, stranger.peer_id, &validator)
+            .unwrap_err()
+            .to_string()
+            .contains("deployed as"));
+        assert!(matched(false, stranger.peer_id, &fullnode)
+            .unwrap_err()
+            .to_string()
+            .contains("deployed for"));
+        // not on chain, or re-keyed
+        matched(true, stranger.peer_id, &stranger).unwrap_err();
+        matched(false, validator.peer_id, &stranger).unwrap_err();
+        let rekeyed = NodeIdentity {
+            network_public_key: stranger.network_public_key,
+            ..validator.clone()
+        };
+        assert!(matched(true, validator.peer_id, &rekeyed)
+            .unwrap_err()
+            .to_string()
+            .contains("network key"));
+    }
+
+    #[test]
+    fn test_restarts_since() {
+        let pod = |uid: &str, restart_counts: &[(i32, Option<&str>)]| Pod {
+            metadata: ObjectMeta {
+                uid: Some(uid.to_string()),
+                ..ObjectMeta::default()
+            },
+            status: Some(PodStatus {
+                container_statuses: Some(
+                    restart_counts
+                        .iter()
+                        .map(|(restart_count, reason)| ContainerStatus {
+                            restart_count: *restart_count,
+                            last_state: Some(ContainerState {
+                                terminated: Some(ContainerStateTerminated {
+                                    reason: reason.map(|reason| reason.to_string()),
+                                    ..ContainerStateTerminated::default()
+                                }),
+                                ..ContainerState::default()
+                            }),
+                            ..ContainerStatus::default()
+                        })
+                        .collect(),
+                ),
+                ..PodStatus::default()
+            }),
+            ..Pod::default()
+        };
+        let baseline = PodRestartCount::of(&pod("a", &[(1, Some("Error")), (0, None)]));
+        assert_eq!(baseline.restarts, 1);
+        let current = PodRestartCount::of(&pod("a", &[(1, Some("Error")), (3, Some("OOMKilled"))]));
+        assert_eq!(
+            current.last_termination_reason.as_deref(),
+            Some("OOMKilled")
+        );
+        assert_eq!(restarts_since(Some(&baseline), Some(&current)), 3);
+        // the pod was recreated, e.g. evicted
+        let recreated = PodRestartCount::of(&pod("b", &[(2, Some("Error"))]));
+        assert_eq!(restarts_since(Some(&baseline), Some(&recreated)), 3);
+        assert_eq!(restarts_since(Some(&baseline), None), 1);
+        assert_eq!(restarts_since(None, Some(&recreated)), 2);
+
+        let node = make_node(false);
+        assert_eq!(node.restarts_since(Some(&baseline), None).unexpected(), 1);
+        node.expect_restart();
+        assert_eq!(node.restarts_since(Some(&baseline), None).unexpected(), 0);
+        let restarts = node.restarts_since(Some(&baseline), Some(&current));
+        assert_eq!((restarts.restarts, restarts.unexpected()), (3, 2));
     }
 }
@@ -0,0 +1,496 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{K8sBackendConfig, K8sError, ReservedPort, Result, LOCALHOST};
+use anyhow::{bail, format_err, Context};
+use aptos_logger::info;
+use futures::{FutureExt, SinkExt, StreamExt};
+use kube::Client as K8sClient;
+use once_cell::sync::Lazy;
+use std::{
+    collections::HashSet,
+    process::Command,
+    sync::{Arc, Mutex},
+};
+use thiserror::Error;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+    task::{JoinHandle, JoinSet},
+};
+
+// the portforward subprotocol starts each frame with its channel, 0 for data and 1 for errors
+const DATA_CHANNEL: u8 = 0;
+// how much to read from a local connection before sending it on as a frame
+const PORT_FORWARD_BUFFER_SIZE: usize = 64 * 1024;
+
+// the local ports of the port-forwards of this run, whose kubectl port-forwards are never killed
+// as orphans
+static OWNED_PORT_FORWARDS: Lazy<Mutex<HashSet<u32>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+pub(crate) fn register_port_forward(local_port: u32) {
+    OWNED_PORT_FORWARDS.lock().unwrap().insert(local_port);
+}
+
+pub(crate) fn unregister_port_forward(local_port: u32) {
+    OWNED_PORT_FORWARDS.lock().unwrap().remove(&local_port);
+}
+
+#[derive(Error, Debug)]
+pub(crate) enum PortForwardError {
+    #[error("Local port {0} is already in use")]
+    PortInUse(u32),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+/// A port-forward from a local port to a port of a pod, through the kube API rather than a
+/// kubectl process. Like kubectl port-forward, every connection to the local port gets its own
+/// websocket to the pod's portforward subresource. Stops forwarding when dropped.
+pub(crate) struct PortForward {
+    local_port: u32,
+    // the port that was asked for, which the port of the pod it maps to may differ from
+    remote_port: u32,
+    pod: String,
+    // keeps the local port from being handed out again while it is being served
+    _reserved_port: ReservedPort,
+    // why the port-forward stopped, once it did
+    stop_reason: Arc<Mutex<Option<String>>>,
+    listener: JoinHandle<()>,
+}
+
+impl PortForward {
+    /// Forward the reserved local port to `pod_port` of the pod, `remote_port` being the port
+    /// that resolved to it, e.g. the port of a Service
+    pub(crate) fn start(
+        client: K8sClient,
+        namespace: &str,
+        pod: &str,
+        pod_port: u32,
+        remote_port: u32,
+        mut reserved_port: ReservedPort,
+    ) -> Result<Self, PortForwardError> {
+        let local_port = reserved_port.port();
+        // serving the listener the port was reserved with leaves no window for others to take it
+        let listener = match reserved_port.release() {
+            Some(listener) => listener,
+            // once released, someone outside this process may have taken the port
+            None => std::net::TcpListener::bind((LOCALHOST, local_port as u16))
+                .map_err(|_| PortForwardError::PortInUse(local_port))?,
+        };
+        listener
+            .set_nonblocking(true)
+            .context("Failed to make the local listener non-blocking")?;
+        let listener =
+            TcpListener::from_std(listener).context("Failed to serve the local listener")?;
+        let path = port_forward_path(namespace, pod, pod_port);
+        let stop_reason = Arc::new(Mutex::new(None));
+        let listener = tokio::spawn(forward_connections(
+            client,
+            path,
+            listener,
+            stop_reason.clone(),
+        ));
+        register_port_forward(local_port);
+        Ok(Self {
+            local_port,
+            remote_port,
+            pod: pod.to_string(),
+            _reserved_port: reserved_port,
+            stop_reason,
+            listener,
+        })
+    }
+
+    pub(crate) fn local_port(&self) -> u32 {
+        self.local_port
+    }
+
+    pub(crate) fn remote_port(&self) -> u32 {
+        self.remote_port
+    }
+
+    /// Whether the local port is still being forwarded
+    pub(crate) fn is_alive(&self) -> bool {
+        !self.listener.is_finished()
+    }
+
+    /// Describes why the port-forward stopped, or None if it is still running
+    pub(crate) fn exit_reason(&self) -> Option<String> {
+        if self.is_alive() {
+            return None;
+        }
+        let stop_reason = self.stop_reason.lock().unwrap().clone();
+        Some(format!(
+            "{} --> {} of pod {} stopped: {}",
+            self.local_port,
+            self.remote_port,
+            self.pod,
+            stop_reason.unwrap_or_else(|| "killed".to_string())
+        ))
+    }
+
+    /// Stop accepting connections, and drop the ones that are being forwarded
+    pub(crate) fn kill(&self) {
+        self.listener.abort();
+    }
+}
+
+impl Drop for PortForward {
+    fn drop(&mut self) {
+        self.kill();
+        unregister_port_forward(self.local_port);
+    }
+}
+
+/// The path of the portforward subresource of the pod, for `port` of it
+pub(crate) fn port_forward_path(namespace: &str, pod: &str, port: u32) -> String {
+    format!(
+        "/api/v1/namespaces/{}/pods/{}/portforward?ports={}",
+        namespace, pod, port
+    )
+}
+
+/// Forward the connections to the listener to the portforward subresource at `path`, until a
+/// websocket to it cannot be opened anymore, e.g. because the pod is gone. That is when kubectl
+/// port-forward exits too.
+async fn forward_connections(
+    client: K8sClient,
+    path: String,
+    listener: TcpListener,
+    stop_reason: Arc<Mutex<Option<String>>>,
+) {
+    // dropped along with the listener task when the port-forward is killed, which aborts them all
+    let mut connections = JoinSet::new();
+    let reason = loop {
+        let mut socket = match listener.accept().await {
+            Ok((socket, _)) => socket,
+            Err(e) => break format!("Failed to accept a local connection: {}", e),
+        };
+        let request = match hyper::Request::get(path.as_str()).body(vec![]) {
+            Ok(request) => request,
+            Err(e) => break format!("Invalid port-forward request {}: {}", path, e),
+        };
+        let mut ws = match client.connect(request).await {
+            Ok(ws) => ws,
+            Err(e) => break K8sError::from_kube(path.clone(), e).to_string(),
+        };
+        let path = path.clone();
+        // pipe the connection through the websocket, until either end closes it
+        connections.spawn(async move {
+            let mut frames = PortForwardFrameReader::default();
+            let mut buf = vec![0; PORT_FORWARD_BUFFER_SIZE + 1];
+            let piped: Result<()> = async {
+                loop {
+                    tokio::select! {
+                        read = socket.read(&mut buf[1..]) => {
+                            let len = read.context("Failed to read from the local connection")?;
+                            if len == 0 {
+                                break;
+                            }
+                            buf[0] = DATA_CHANNEL;
+                            ws.send(buf[..=len].to_vec().into()).await?;
+                        },
+                        message = ws.next() => {
+                            let message = match message {
+                                Some(message) => message?,
+                                None => break,
+                            };
+                            if message.is_close() {
+                                break;
+                            }
+                            // pings are answered by the websocket itself
+                            if !message.is_binary() {
+                                continue;
+                            }
+                            match frames.read(&message.into_data())? {
+                                PortForwardFrame::Data(payload) => socket
+                                    .write_all(&payload)
+                                    .await
+                                    .context("Failed to write to the local connection")?,
+                                PortForwardFrame::Error(message) => bail!("{}", message),
+                                PortForwardFrame::Port => {},
+                            }
+                        },
+                    }
+                }
+                Ok(())
+            }
+            .await;
+            let _ = ws.close(None).await;
+            let _ = socket.shutdown().await;
+            if let Err(e) = piped {
+                info!("Port-forward connection to {} failed: {:#}", path, e);
+            }
+        });
+        // reap the connections that are done, so that their results do not pile up
+        while let Some(Some(_)) = connections.join_next().now_or_never() {}
+    };
+    info!("Port-forward to {} stopped: {}", path, reason);
+    *stop_reason.lock().unwrap() = Some(reason);
+}
+
+/// A frame the API server sends on a port-forward websocket, without its channel byte
+#[derive(Debug, PartialEq, Eq)]
+enum PortForwardFrame {
+    Data(Vec<u8>),
+    // why forwarding the connection failed, e.g. because nothing listens on the port in the pod
+    Error(String),
+    // the first frame of each channel only carries the port, as two little endian bytes
+    Port,
+}
+
+/// Tells the frames of a port-forward websocket apart, by their channel and whether they are the
+/// first on it
+#[derive(Default)]
+struct PortForwardFrameReader {
+    port_seen: [bool; 2],
+}
+
+impl PortForwardFrameReader {
+    fn read(&mut self, frame: &[u8]) -> Result<PortForwardFrame> {
+        let (&channel, payload) = frame
+            .split_first()
+            .ok_or_else(|| format_err!("Empty port-forward frame"))?;
+        let port_seen = self
+            .port_seen
+            .get_mut(channel as usize)
+            .ok_or_else(|| format_err!("Port-forward frame on unknown channel {}", channel))?;
+        if !*port_seen {
+            *port_seen = true;
+            return Ok(PortForwardFrame::Port);
+        }
+        Ok(if channel == DATA_CHANNEL {
+            PortForwardFrame::Data(payload.to_vec())
+        } else {
+            PortForwardFrame::Error(String::from_utf8_lossy(payload).trim().to_string())
+        })
+    }
+}
+
+/// A process, as listed by ps
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct ProcessInfo {
+    pid: u32,
+    ppid: u32,
+    args: String,
+}
+
+/// Parses the lines of `ps -eo pid=,ppid=,args=`
+fn parse_processes(output: &str) -> Vec<ProcessInfo> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let pid = fields.next()?.parse().ok()?;
+            let ppid = fields.next()?.parse().ok()?;
+            Some(ProcessInfo {
+                pid,
+                ppid,
+                args: fields.collect::<Vec<_>>().join(" "),
+            })
+        })
+        .collect()
+}
+
+/// The namespace of a kubectl port-forward command line, none if it is not one
+fn port_forward_namespace(args: &str) -> Option<&str> {
+    let mut tokens = args.split_whitespace();
+    if !tokens.next()?.contains("kubectl") {
+        return None;
+    }
+    let tokens: Vec<_> = tokens.collect();
+    if !tokens.contains(&"port-forward") {
+        return None;
+    }
+    tokens
+        .windows(2)
+        .find_map(|pair| match pair {
+            ["-n" | "--namespace", namespace] => Some(*namespace),
+            _ => None,
+        })
+        .or_else(|| {
+            tokens
+                .iter()
+                .find_map(|token| token.strip_prefix("--namespace="))
+        })
+}
+
+/// The local port of a kubectl port-forward command line, from its `<local>:<remote>` argument
+fn port_forward_local_port(args: &str) -> Option<u32> {
+    args.split_whitespace().find_map(|token| {
+        let (local, remote) = token.split_once(':')?;
+        remote.parse::<u32>().ok()?;
+        local.parse().ok()
+    })
+}
+
+/// The kubectl port-forwards to forge namespaces that are not to local ports of this run, and
+/// whose parent is gone, so that the live port-forwards of other runs on the same machine are
+/// left alone
+fn orphaned_port_forwards(
+    processes: &[ProcessInfo],
+    namespace_prefix: &str,
+    owned: &HashSet<u32>,
+) -> Vec<(u32, String)> {
+    let running: HashSet<u32> = processes.iter().map(|process| process.pid).collect();
+    processes
+        .iter()
+        .filter(|process| {
+            port_forward_local_port(&process.args).map_or(true, |port| !owned.contains(&port))
+        })
+        .filter(|process| process.ppid == 1 || !running.contains(&process.ppid))
+        .filter_map(|process| {
+            let namespace = port_forward_namespace(&process.args)?;
+            namespace
+                .starts_with(namespace_prefix)
+                .then(|| (process.pid, namespace.to_string()))
+        })
+        .collect()
+}
+
+/// Kills the kubectl port-forwards crashed forge runs left behind, which would otherwise hold
+/// on to local ports, unless the config opts out. Forge forwards ports in-process now, these are
+/// left over by older versions and by users' scripts. Returns the pids that were killed.
+pub fn cleanup_orphaned_port_forwards(config: &K8sBackendConfig) -> Vec<u32> {
+    if !config.cleanup_orphaned_port_forwards {
+        return vec![];
+    }
+    let output = match Command::new("ps")
+        .args(["-eo", "pid=,ppid=,args="])
+        .output()
+    {
+        Ok(output) if output.status.success() => output.stdout,
+        Ok(output) => {
+            info!(
+                "Failed to list the processes to clean up port-forwards: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+            return vec![];
+        },
+        Err(e) => {
+            info!(
+                "Failed to list the processes to clean up port-forwards: {}",
+                e
+            );
+            return vec![];
+        },
+    };
+    let processes = parse_processes(&String::from_utf8_lossy(&output));
+    let owned = OWNED_PORT_FORWARDS.lock().unwrap().clone();
+    let mut killed = vec![];
+    for (pid, namespace) in orphaned_port_forwards(&processes, &config.namespace_prefix, &owned) {
+        match Command::new("kill").arg(pid.to_string()).status() {
+            Ok(status) if status.success() => {
+                info!(
+                    "Killed orphaned port-forward {} to namespace {}",
+                    pid, namespace
+                );
+                killed.push(pid);
+            },
+            _ => info!(
+                "Failed to kill orphaned port-forward {} to namespace {}",
+                pid, namespace
+            ),
+        }
+    }
+    killed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::get_free_port;
+
+    #[test]
+    fn test_orphaned_port_forwards() {
+        let processes = parse_processes(
+            &[
+                "    1     0 /sbin/init",
+                "  200     1 /usr/bin/forge test k8s-swarm",
+                "  300     1 kubectl port-forward -n forge-old-run svc/aptos-node-0-validator 9000:8080",
+                "  301   200 kubectl port-forward -n forge-live-run svc/aptos-node-0-validator 9001:8080",
+                "  302  4242 /opt/bin/kubectl --context staging port-forward --namespace=forge-gone pod/x 9002:9102",
+                "  303     1 kubectl port-forward -n my-namespace svc/postgres 5432:5432",
+                "  304     1 kubectl get pods -n forge-old-run",
+                "  305     1 kubectl port-forward -n forge-this-run svc/aptos-node-1-validator 9003:8080",
+            ]
+            .join("\n"),
+        );
+        assert_eq!(processes.len(), 8);
+        assert_eq!(
+            port_forward_namespace(&processes[4].args),
+            Some("forge-gone")
+        );
+        assert_eq!(port_forward_local_port(&processes[4].args), Some(9002));
+        let owned = HashSet::from([9003]);
+        assert_eq!(orphaned_port_forwards(&processes, "forge", &owned), vec![
+            (300, "forge-old-run".to_string()),
+            (302, "forge-gone".to_string()),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_port_forward_exit_reason() {
+        let reserved_port = get_free_port().unwrap();
+        let local_port = reserved_port.port();
+        let port_forward = PortForward {
+            local_port,
+            remote_port: 9101,
+            pod: "aptos-node-0-validator-0".to_string(),
+            _reserved_port: reserved_port,
+            stop_reason: Arc::new(Mutex::new(Some("pod not found".to_string()))),
+            listener: tokio::spawn(std::future::pending()),
+        };
+        assert!(port_forward.is_alive());
+        assert_eq!(port_forward.exit_reason(), None);
+
+        port_forward.kill();
+        while port_forward.is_alive() {
+            tokio::task::yield_now().await;
+        }
+        let reason = port_forward.exit_reason().unwrap();
+        assert!(
+            reason.contains(&format!("{} --> 9101", local_port)),
+            "{}",
+            reason
+        );
+        assert!(reason.contains("aptos-node-0-validator-0"), "{}", reason);
+        assert!(reason.contains("pod not found"), "{}", reason);
+    }
+
+    #[test]
+    fn test_port_forward_frames() {
+        let mut frames = PortForwardFrameReader::default();
+        // each channel starts with the port, 8080 here
+        assert_eq!(
+            frames.read(&[0, 0x90, 0x1F]).unwrap(),
+            PortForwardFrame::Port
+        );
+        assert_eq!(
+            frames.read(&[1, 0x90, 0x1F]).unwrap(),
+            PortForwardFrame::Port
+        );
+        assert_eq!(
+            frames.read(b"\x00HTTP/1.1 200 OK").unwrap(),
+            PortForwardFrame::Data(b"HTTP/1.1 200 OK".to_vec())
+        );
+        assert_eq!(
+            frames.read(b"\x01connection refused\n").unwrap(),
+            PortForwardFrame::Error("connection refused".to_string())
+        );
+        frames.read(&[]).unwrap_err();
+        frames.read(&[3, 0]).unwrap_err();
+
+        // the port of the data channel is not taken for data
+        let mut frames = PortForwardFrameReader::default();
+        assert_eq!(
+            frames.read(&[0, 0x90, 0x1F]).unwrap(),
+            PortForwardFrame::Port
+        );
+        assert_eq!(
+            frames.read(&[0, 0x90, 0x1F]).unwrap(),
+            PortForwardFrame::Data(vec![0x90, 0x1F])
+        );
+    }
+}
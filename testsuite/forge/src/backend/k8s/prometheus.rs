@@ -1,7 +1,7 @@
 // Copyright © Aptos Foundation
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::{create_k8s_client, K8sApi, ReadWrite, Result};
+use crate::{K8sApi, K8sBackendConfig, ReadWrite, Result};
 use again::RetryPolicy;
 use anyhow::{anyhow, bail};
 use aptos_logger::{info, warn};
@@ -20,9 +20,9 @@ static PROMETHEUS_RETRY_POLICY: Lazy<RetryPolicy> = Lazy::new(|| {
         .with_jitter(true)
 });
 
-pub async fn get_prometheus_client() -> Result<PrometheusClient> {
+pub async fn get_prometheus_client(config: &K8sBackendConfig) -> Result<PrometheusClient> {
     // read from the environment
-    let kube_client = create_k8s_client().await?;
+    let kube_client = config.create_client().await?;
     let secrets_api = Arc::new(K8sApi::<Secret>::from_client(
         kube_client,
         Some("default".to_string()),
@@ -282,7 +282,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_query_prometheus() {
-        let client_result = get_prometheus_client().await;
+        let client_result = get_prometheus_client(&K8sBackendConfig::default()).await;
 
         // Currently this test tries to connect to the internet... and doesnt
         // require success so it is likely to be skipped
@@ -0,0 +1,626 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{NodeVersions, Result, Version};
+use anyhow::{bail, format_err, Context};
+use aptos_logger::info;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use reqwest::{header, Client, Response, StatusCode};
+use serde::Deserialize;
+use std::collections::HashMap;
+use thiserror::Error;
+use tokio::process::Command;
+
+// the image of the validators in the aptos-node helm chart, and of the fullnodes unless the
+// values say otherwise
+pub const DEFAULT_VALIDATOR_IMAGE_REPO: &str = "aptoslabs/validator";
+// where the images without a registry host are pulled from
+const DOCKER_HUB_REGISTRY: &str = "registry-1.docker.io";
+const TAGS_PAGE_SIZE: usize = 1000;
+// the manifests of single and multi-arch images, of both docker and OCI
+const MANIFEST_MEDIA_TYPES: &str = "application/vnd.docker.distribution.manifest.v2+json, \
+    application/vnd.docker.distribution.manifest.list.v2+json, \
+    application/vnd.oci.image.manifest.v1+json, application/vnd.oci.image.index.v1+json";
+
+// e.g. v1.8.3 or aptos-node-v1.8.3, but not the release candidates
+static RELEASE_TAG: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^(?:aptos-node-)?v?(\d+)\.(\d+)\.(\d+)$").unwrap());
+// a `key="value"` parameter of a WWW-Authenticate challenge
+static CHALLENGE_PARAM: Lazy<Regex> = Lazy::new(|| Regex::new(r#"(\w+)="([^"]*)""#).unwrap());
+
+#[derive(Error, Debug)]
+pub enum RegistryError {
+    #[error("Registry {registry} is unreachable: {source}")]
+    Unreachable {
+        registry: String,
+        source: reqwest::Error,
+    },
+    #[error("Registry {registry} denied access to {repo}, check the credentials of {credentials}")]
+    Unauthorized {
+        registry: String,
+        repo: String,
+        credentials: &'static str,
+    },
+    #[error("Image {repo}:{tag} does not exist")]
+    TagNotFound { repo: String, tag: String },
+    #[error("Unexpected response {status} of registry {registry}: {body}")]
+    UnexpectedResponse {
+        registry: String,
+        status: u16,
+        body: String,
+    },
+}
+
+/// An image repository, e.g. `aptoslabs/validator` on Docker Hub or
+/// `us-docker.pkg.dev/aptos-registry/docker/validator`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ImageRepo {
+    pub registry: String,
+    pub name: String,
+}
+
+impl ImageRepo {
+    pub fn parse(repo: &str) -> Self {
+        match repo.split_once('/') {
+            Some((host, name))
+                if host.contains('.') || host.contains(':') || host == "localhost" =>
+            {
+                Self {
+                    registry: host.to_string(),
+                    name: name.to_string(),
+                }
+            },
+            Some(_) => Self {
+                registry: DOCKER_HUB_REGISTRY.to_string(),
+                name: repo.to_string(),
+            },
+            // the official images of Docker Hub
+            None => Self {
+                registry: DOCKER_HUB_REGISTRY.to_string(),
+                name: format!("library/{}", repo),
+            },
+        }
+    }
+
+    // what the ambient credentials of the registry come from
+    fn credentials_source(&self) -> &'static str {
+        let registry = self.registry.as_str();
+        if registry == "gcr.io" || registry.ends_with(".gcr.io") || registry.ends_with(".pkg.dev") {
+            "gcloud"
+        } else if registry.contains(".dkr.ecr.") {
+            "aws"
+        } else if registry == "ghcr.io" {
+            "GITHUB_TOKEN"
+        } else {
+            "none"
+        }
+    }
+
+    /// The user and password to authenticate to the registry with: a gcloud access token for
+    /// GCR and Artifact Registry, an aws ECR password, GITHUB_TOKEN for GHCR, and none otherwise,
+    /// e.g. for the public images of Docker Hub
+    async fn ambient_credentials(&self) -> Result<Option<(String, String)>> {
+        Ok(match self.credentials_source() {
+            "gcloud" => Some((
+                "oauth2accesstoken".to_string(),
+                command_output("gcloud", &["auth", "print-access-token"]).await?,
+            )),
+            "aws" => {
+                // e.g. 123456789012.dkr.ecr.us-west-2.amazonaws.com
+                let region = self.registry.split('.').nth(3).ok_or_else(|| {
+                    format_err!("Failed to parse the region of registry {}", self.registry)
+                })?;
+                Some((
+                    "AWS".to_string(),
+                    command_output("aws", &["ecr", "get-login-password", "--region", region])
+                        .await?,
+                ))
+            },
+            "GITHUB_TOKEN" => std::env::var("GITHUB_TOKEN")
+                .ok()
+                .map(|token| ("forge".to_string(), token)),
+            _ => None,
+        })
+    }
+}
+
+async fn command_output(program: &str, args: &[&str]) -> Result<String> {
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .await
+        .with_context(|| format!("Failed to run {}", program))?;
+    if !output.status.success() {
+        bail!(
+            "{} {} failed: {}",
+            program,
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// In which order [list_versions] returns the versions, oldest first
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VersionOrder {
+    // when the tags were pushed, as far as the registry says, e.g. GCR does. The tags it says
+    // nothing about go first, by name.
+    BuildTime,
+    // by the release version of the tags, the other tags go first, by name
+    Semver,
+}
+
+/// Which tags of an image repository are versions to deploy, see [list_versions]
+#[derive(Clone, Debug)]
+pub struct VersionFilter {
+    image_repo: String,
+    tag_prefix: Option<String>,
+    releases_only: bool,
+    order: VersionOrder,
+}
+
+impl Default for VersionFilter {
+    fn default() -> Self {
+        Self {
+            image_repo: DEFAULT_VALIDATOR_IMAGE_REPO.to_string(),
+            tag_prefix: None,
+            releases_only: false,
+            order: VersionOrder::BuildTime,
+        }
+    }
+}
+
+impl VersionFilter {
+    pub fn with_image_repo(mut self, image_repo: &str) -> Self {
+        self.image_repo = image_repo.to_string();
+        self
+    }
+
+    /// Only the tags starting with the prefix, e.g. of the builds of a branch
+    pub fn with_tag_prefix(mut self, tag_prefix: &str) -> Self {
+        self.tag_prefix = Some(tag_prefix.to_string());
+        self
+    }
+
+    /// Only the release tags, e.g. `aptos-node-v1.8.3`
+    pub fn releases_only(mut self) -> Self {
+        self.releases_only = true;
+        self
+    }
+
+    pub fn with_order(mut self, order: VersionOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    fn matches(&self, tag: &str) -> bool {
+        self.tag_prefix
+            .as_ref()
+            .map_or(true, |prefix| tag.starts_with(prefix.as_str()))
+            && (!self.releases_only || release_version(tag).is_some())
+    }
+}
+
+/// The release version of a release tag, none for the other tags
+pub fn release_version(tag: &str) -> Option<(u64, u64, u64)> {
+    let captures = RELEASE_TAG.captures(tag)?;
+    let part = |i: usize| captures[i].parse().ok();
+    Some((part(1)?, part(2)?, part(3)?))
+}
+
+/// A tag of an image repository, with when it was pushed if the registry says
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct ImageTag {
+    tag: String,
+    pushed_at_ms: Option<u64>,
+}
+
+// a page of the tag list, and the GCR extension of it with the push times
+#[derive(Deserialize)]
+struct TagList {
+    tags: Option<Vec<String>>,
+    #[serde(default)]
+    manifest: HashMap<String, GcrManifest>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GcrManifest {
+    #[serde(default)]
+    tag: Vec<String>,
+    time_uploaded_ms: Option<String>,
+}
+
+impl TagList {
+    fn into_image_tags(self) -> Vec<ImageTag> {
+        let pushed_at: HashMap<_, _> = self
+            .manifest
+            .values()
+            .flat_map(|manifest| {
+                let pushed_at_ms = manifest
+                    .time_uploaded_ms
+                    .as_ref()
+                    .and_then(|ms| ms.parse::<u64>().ok());
+                manifest
+                    .tag
+                    .iter()
+                    .map(move |tag| (tag.clone(), pushed_at_ms))
+            })
+            .collect();
+        self.tags
+            .unwrap_or_default()
+            .into_iter()
+            .map(|tag| ImageTag {
+                pushed_at_ms: pushed_at.get(&tag).copied().flatten(),
+                tag,
+            })
+            .collect()
+    }
+}
+
+fn sort_tags(tags: &mut [ImageTag], order: VersionOrder) {
+    match order {
+        VersionOrder::BuildTime => {
+            tags.sort_by(|a, b| (a.pushed_at_ms, &a.tag).cmp(&(b.pushed_at_ms, &b.tag)))
+        },
+        VersionOrder::Semver => tags.sort_by(|a, b| {
+            (release_version(&a.tag), &a.tag).cmp(&(release_version(&b.tag), &b.tag))
+        }),
+    }
+}
+
+// the parameters of a `WWW-Authenticate: Bearer realm="...",service="...",scope="..."` challenge
+fn parse_bearer_challenge(header: &str) -> Option<HashMap<String, String>> {
+    let params = header.strip_prefix("Bearer ")?;
+    let params: HashMap<_, _> = CHALLENGE_PARAM
+        .captures_iter(params)
+        .map(|captures| (captures[1].to_string(), captures[2].to_string()))
+        .collect();
+    params.contains_key("realm").then_some(params)
+}
+
+// the URL of the next page of the tag list, from a `Link: </v2/...&last=x>; rel="next"` header
+fn next_page_url(registry: &str, link: &str) -> Option<String> {
+    if !link.contains("rel=\"next\"") {
+        return None;
+    }
+    let path = link.split_once('<')?.1.split_once('>')?.0;
+    Some(
+        if path.starts_with("http") {
+            path.to_string()
+        } else {
+            format!("https://{}{}", registry, path)
+        },
+    )
+}
+
+/// Talks the Docker Registry HTTP API to a registry, with its ambient credentials
+struct RegistryClient {
+    client: Client,
+    repo: ImageRepo,
+    credentials: Option<(String, String)>,
+    // the token of a Bearer challenge, once one was answered
+    token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    token: Option<String>,
+    access_token: Option<String>,
+}
+
+impl RegistryClient {
+    async fn new(image_repo: &str) -> Result<Self> {
+        let repo = ImageRepo::parse(image_repo);
+        let credentials = repo.ambient_credentials().await?;
+        Ok(Self {
+            client: Client::new(),
+            repo,
+            credentials,
+            token: None,
+        })
+    }
+
+    async fn send(&self, url: &str, head: bool) -> Result<Response> {
+        let request = if head {
+            self.client.head(url)
+        } else {
+            self.client.get(url)
+        }
+        .header(header::ACCEPT, MANIFEST_MEDIA_TYPES);
+        let request = match (&self.token, &self.credentials) {
+            (Some(token), _) => request.bearer_auth(token),
+            (None, Some((user, password))) => request.basic_auth(user, Some(password)),
+            (None, None) => request,
+        };
+        request.send().await.map_err(|source| {
+            RegistryError::Unreachable {
+                registry: self.repo.registry.clone(),
+                source,
+            }
+            .into()
+        })
+    }
+
+    /// Sends the request, answering the Bearer challenge of the registry if it has one, e.g.
+    /// Docker Hub and GHCR hand out tokens for the credentials
+    async fn request(&mut self, url: &str, head: bool) -> Result<Response> {
+        let response = self.send(url, head).await?;
+        if response.status() != StatusCode::UNAUTHORIZED || self.token.is_some() {
+            return self.check_authorized(response);
+        }
+        let challenge = response
+            .headers()
+            .get(header::WWW_AUTHENTICATE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(parse_bearer_challenge);
+        let Some(challenge) = challenge else {
+            return self.check_authorized(response);
+        };
+        let mut token_request = self.client.get(&challenge["realm"]).query(
+            &challenge
+                .iter()
+                .filter(|(key, _)| key.as_str() != "realm")
+                .collect::<Vec<_>>(),
+        );
+        if let Some((user, password)) = &self.credentials {
+            token_request = token_request.basic_auth(user, Some(password));
+        }
+        let token_response =
+            token_request
+                .send()
+                .await
+                .map_err(|source| RegistryError::Unreachable {
+                    registry: self.repo.registry.clone(),
+                    source,
+                })?;
+        let token_response: TokenResponse = self.check_authorized(token_response)?.json().await?;
+        self.token = token_response.token.or(token_response.access_token);
+        let response = self.send(url, head).await?;
+        self.check_authorized(response)
+    }
+
+    fn check_authorized(&self, response: Response) -> Result<Response> {
+        match response.status() {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Err(RegistryError::Unauthorized {
+                registry: self.repo.registry.clone(),
+                repo: self.repo.name.clone(),
+                credentials: self.repo.credentials_source(),
+            }
+            .into()),
+            _ => Ok(response),
+        }
+    }
+
+    async fn list_tags(&mut self) -> Result<Vec<ImageTag>> {
+        let mut url = Some(format!(
+            "https://{}/v2/{}/tags/list?n={}",
+            self.repo.registry, self.repo.name, TAGS_PAGE_SIZE
+        ));
+        let mut tags = vec![];
+        while let Some(page_url) = url.take() {
+            let response = self.request(&page_url, false).await?;
+            let status = response.status();
+            if !status.is_success() {
+                return Err(RegistryError::UnexpectedResponse {
+                    registry: self.repo.registry.clone(),
+                    status: status.as_u16(),
+                    body: response.text().await.unwrap_or_default(),
+                }
+                .into());
+            }
+            url = response
+                .headers()
+                .get(header::LINK)
+                .and_then(|link| link.to_str().ok())
+                .and_then(|link| next_page_url(&self.repo.registry, link));
+            let page: TagList = response.json().await?;
+            tags.extend(page.into_image_tags());
+        }
+        Ok(tags)
+    }
+
+    async fn tag_exists(&mut self, tag: &str) -> Result<bool> {
+        let url = format!(
+            "https://{}/v2/{}/manifests/{}",
+            self.repo.registry, self.repo.name, tag
+        );
+        let response = self.request(&url, true).await?;
+        match response.status() {
+            status if status.is_success() => Ok(true),
+            StatusCode::NOT_FOUND => Ok(false),
+            status => Err(RegistryError::UnexpectedResponse {
+                registry: self.repo.registry.clone(),
+                status: status.as_u16(),
+                body: String::new(),
+            }
+            .into()),
+        }
+    }
+}
+
+/// The tags of the image repository the filter matches, as Versions ordered oldest first.
+/// The registry is queried with its ambient credentials, see [ImageRepo].
+pub async fn list_versions(filter: &VersionFilter) -> Result<Vec<Version>> {
+    let mut client = RegistryClient::new(&filter.image_repo).await?;
+    let mut tags: Vec<_> = client
+        .list_tags()
+        .await?
+        .into_iter()
+        .filter(|tag| filter.matches(&tag.tag))
+        .collect();
+    sort_tags(&mut tags, filter.order);
+    Ok(tags
+        .into_iter()
+        .enumerate()
+        .map(|(index, tag)| Version::new(index, tag.tag))
+        .collect())
+}
+
+/// The latest release of the image repository, or the latest before `current` if it is a
+/// release itself, e.g. to upgrade from the previous release to the build under test
+pub async fn previous_release(image_repo: &str, current: &str) -> Result<Version> {
+    let releases = list_versions(
+        &VersionFilter::default()
+            .with_image_repo(image_repo)
+            .releases_only()
+            .with_order(VersionOrder::Semver),
+    )
+    .await?;
+    let current_release = release_version(current);
+    releases
+        .into_iter()
+        .rev()
+        .find(|version| {
+            current_release.map_or(true, |current_release| {
+                release_version(&version.to_string()) < Some(current_release)
+            })
+        })
+        .ok_or_else(|| format_err!("{} has no release before {}", image_repo, current))
+}
+
+/// Fails with [RegistryError::TagNotFound] if the image does not exist, e.g. to fail before
+/// deploying pods that can't pull it
+pub async fn ensure_image_exists(image_repo: &str, tag: &str) -> Result<()> {
+    let mut client = RegistryClient::new(image_repo).await?;
+    if !client.tag_exists(tag).await? {
+        return Err(RegistryError::TagNotFound {
+            repo: image_repo.to_string(),
+            tag: tag.to_string(),
+        }
+        .into());
+    }
+    info!("Found image {}:{}", image_repo, tag);
+    Ok(())
+}
+
+/// Checks the images the nodes of a new swarm start with, from the repos of the node versions
+/// if they have their own, else from those of the helm values, see [crate::node_image_repos]
+pub(crate) async fn check_node_images(
+    init_version: &Version,
+    node_versions: Option<&NodeVersions>,
+    helm_image_repos: (Option<String>, Option<String>),
+) -> Result<()> {
+    let (helm_validator_repo, helm_fullnode_repo) = helm_image_repos;
+    let helm_validator_repo = helm_validator_repo
+        .as_deref()
+        .unwrap_or(DEFAULT_VALIDATOR_IMAGE_REPO);
+    let Some(node_versions) = node_versions else {
+        return ensure_image_exists(helm_validator_repo, &init_version.to_string()).await;
+    };
+    let validator_repo = node_versions
+        .validator_image_repo
+        .as_deref()
+        .unwrap_or(helm_validator_repo);
+    let fullnode_repo = node_versions
+        .fullnode_image_repo
+        .as_deref()
+        .or(helm_fullnode_repo.as_deref())
+        .unwrap_or(validator_repo);
+    let mut images: Vec<_> = node_versions
+        .validators
+        .iter()
+        .map(|version| (validator_repo, version.to_string()))
+        .chain(std::iter::once((
+            fullnode_repo,
+            node_versions.fullnodes.to_string(),
+        )))
+        .collect();
+    images.sort();
+    images.dedup();
+    for (repo, tag) in images {
+        ensure_image_exists(repo, &tag).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_image_repo() {
+        assert_eq!(ImageRepo::parse("aptoslabs/validator"), ImageRepo {
+            registry: DOCKER_HUB_REGISTRY.to_string(),
+            name: "aptoslabs/validator".to_string(),
+        });
+        assert_eq!(ImageRepo::parse("haproxy").name, "library/haproxy");
+        let repo = ImageRepo::parse("us-docker.pkg.dev/aptos-registry/docker/validator");
+        assert_eq!(repo.registry, "us-docker.pkg.dev");
+        assert_eq!(repo.name, "aptos-registry/docker/validator");
+        assert_eq!(repo.credentials_source(), "gcloud");
+        assert_eq!(
+            ImageRepo::parse("123456789012.dkr.ecr.us-west-2.amazonaws.com/aptos/validator")
+                .credentials_source(),
+            "aws"
+        );
+        assert_eq!(
+            ImageRepo::parse("localhost:5000/validator").registry,
+            "localhost:5000"
+        );
+    }
+
+    #[test]
+    fn test_filter_and_sort_tags() {
+        assert_eq!(release_version("aptos-node-v1.10.2"), Some((1, 10, 2)));
+        assert_eq!(release_version("v1.9.0"), Some((1, 9, 0)));
+        assert_eq!(release_version("aptos-node-v1.9.0-rc"), None);
+        assert_eq!(release_version("devnet"), None);
+
+        let page: TagList = serde_json::from_value(serde_json::json!({
+            "name": "aptos-registry/docker/validator",
+            "tags": ["main_1234abc", "aptos-node-v1.10.2", "aptos-node-v1.9.0", "main_5678def", "devnet"],
+            "manifest": {
+                "sha256:1": { "tag": ["main_5678def", "devnet"], "timeUploadedMs": "2000" },
+                "sha256:2": { "tag": ["main_1234abc"], "timeUploadedMs": "1000" },
+            },
+        }))
+        .unwrap();
+        let mut tags = page.into_image_tags();
+        sort_tags(&mut tags, VersionOrder::BuildTime);
+        let names = |tags: &[ImageTag]| tags.iter().map(|tag| tag.tag.clone()).collect::<Vec<_>>();
+        assert_eq!(names(&tags), vec![
+            "aptos-node-v1.10.2",
+            "aptos-node-v1.9.0",
+            "main_1234abc",
+            "devnet",
+            "main_5678def",
+        ]);
+
+        let filter = VersionFilter::default().with_tag_prefix("main_");
+        assert!(filter.matches("main_1234abc") && !filter.matches("devnet"));
+        let filter = VersionFilter::default().releases_only();
+        let mut releases: Vec<_> = tags
+            .into_iter()
+            .filter(|tag| filter.matches(&tag.tag))
+            .collect();
+        sort_tags(&mut releases, VersionOrder::Semver);
+        assert_eq!(names(&releases), vec![
+            "aptos-node-v1.9.0",
+            "aptos-node-v1.10.2"
+        ]);
+    }
+
+    #[test]
+    fn test_registry_headers() {
+        let challenge = parse_bearer_challenge(
+            r#"Bearer realm="https://auth.docker.io/token",service="registry.docker.io",scope="repository:aptoslabs/validator:pull""#,
+        )
+        .unwrap();
+        assert_eq!(challenge["realm"], "https://auth.docker.io/token");
+        assert_eq!(challenge["scope"], "repository:aptoslabs/validator:pull");
+        assert!(parse_bearer_challenge(r#"Basic realm="registry""#).is_none());
+
+        assert_eq!(
+            next_page_url(
+                "ghcr.io",
+                r#"</v2/aptos-labs/validator/tags/list?n=1000&last=main_1234abc>; rel="next""#
+            )
+            .unwrap(),
+            "https://ghcr.io/v2/aptos-labs/validator/tags/list?n=1000&last=main_1234abc"
+        );
+        assert!(next_page_url("ghcr.io", r#"</v2/x>; rel="prev""#).is_none());
+    }
+}
@@ -0,0 +1,287 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{K8sError, Result};
+use anyhow::{format_err, Context};
+use k8s_openapi::api::core::v1::Pod;
+use kube::{
+    api::{Api, ApiResource, DynamicObject, GroupVersionKind, ListParams},
+    client::Client as K8sClient,
+    ResourceExt,
+};
+use serde_json::Value;
+
+/// Resource usage of a single container, as reported by metrics-server
+#[derive(Clone, Debug, PartialEq)]
+pub struct ContainerResourceUsage {
+    pub name: String,
+    pub cpu_millicores: f64,
+    pub memory_bytes: u64,
+    // the limits set on the container, if any
+    pub cpu_limit_millicores: Option<f64>,
+    pub memory_limit_bytes: Option<u64>,
+}
+
+impl ContainerResourceUsage {
+    /// The fraction of its CPU limit the container is using, if it has one
+    pub fn cpu_limit_fraction(&self) -> Option<f64> {
+        self.cpu_limit_millicores
+            .map(|limit| self.cpu_millicores / limit)
+    }
+
+    /// The fraction of its memory limit the container is using, if it has one
+    pub fn memory_limit_fraction(&self) -> Option<f64> {
+        self.memory_limit_bytes
+            .map(|limit| self.memory_bytes as f64 / limit as f64)
+    }
+}
+
+/// Resource usage of a node's pod
+#[derive(Clone, Debug, Default)]
+pub struct PodResourceUsage {
+    pub pod_name: String,
+    pub containers: Vec<ContainerResourceUsage>,
+    // HAProxy runs in its own pods in front of the node. Only collected if HAProxy is enabled.
+    pub haproxy_containers: Vec<ContainerResourceUsage>,
+}
+
+impl PodResourceUsage {
+    /// Total CPU used by the node's containers, excluding HAProxy
+    pub fn cpu_millicores(&self) -> f64 {
+        self.containers.iter().map(|c| c.cpu_millicores).sum()
+    }
+
+    /// Total memory used by the node's containers, excluding HAProxy
+    pub fn memory_bytes(&self) -> u64 {
+        self.containers.iter().map(|c| c.memory_bytes).sum()
+    }
+
+    /// The highest fraction of its CPU limit any of the node's containers is using
+    pub fn max_cpu_limit_fraction(&self) -> Option<f64> {
+        self.containers
+            .iter()
+            .filter_map(ContainerResourceUsage::cpu_limit_fraction)
+            .reduce(f64::max)
+    }
+}
+
+fn pod_metrics_api(kube_client: &K8sClient, kube_namespace: &str) -> Api<DynamicObject> {
+    let gvk = GroupVersionKind::gvk("metrics.k8s.io", "v1beta1", "PodMetrics");
+    let api_resource = ApiResource::from_gvk_with_plural(&gvk, "pods");
+    Api::namespaced_with(kube_client.clone(), kube_namespace, &api_resource)
+}
+
+/// Fetch the resource usage of each container in the given pod, along with the container limits
+pub(crate) async fn fetch_container_resource_usage(
+    kube_client: &K8sClient,
+    kube_namespace: &str,
+    pod_name: &str,
+) -> Result<Vec<ContainerResourceUsage>> {
+    let metrics = pod_metrics_api(kube_client, kube_namespace)
+        .get(pod_name)
+        .await
+        .map_err(|e| K8sError::from_kube(format!("metrics of pod {}", pod_name), e))
+        .with_context(|| {
+            format!(
+                "Failed to get metrics of pod {}. Is metrics-server installed in the cluster?",
+                pod_name
+            )
+        })?;
+    let pod = Api::<Pod>::namespaced(kube_client.clone(), kube_namespace)
+        .get(pod_name)
+        .await
+        .map_err(|e| K8sError::from_kube(format!("pod {}", pod_name), e))?;
+    parse_container_usage(&metrics.data, &pod)
+}
+
+/// Fetch the resource usage of each container in all pods matching the label selector
+pub(crate) async fn fetch_container_resource_usage_by_labels(
+    kube_client: &K8sClient,
+    kube_namespace: &str,
+    label_selector: &str,
+) -> Result<Vec<ContainerResourceUsage>> {
+    let metrics = pod_metrics_api(kube_client, kube_namespace)
+        .list(&ListParams::default().labels(label_selector))
+        .await
+        .map_err(|e| K8sError::from_kube(format!("metrics of pods {}", label_selector), e))
+        .with_context(|| {
+            format!(
+                "Failed to list metrics of pods {}. Is metrics-server installed in the cluster?",
+                label_selector
+            )
+        })?;
+    let pod_api = Api::<Pod>::namespaced(kube_client.clone(), kube_namespace);
+    let mut usage = vec![];
+    for pod_metrics in metrics.items {
+        let pod_name = pod_metrics.name();
+        let pod = pod_api
+            .get(&pod_name)
+            .await
+            .map_err(|e| K8sError::from_kube(format!("pod {}", pod_name), e))?;
+        usage.extend(parse_container_usage(&pod_metrics.data, &pod)?);
+    }
+    Ok(usage)
+}
+
+/// Parse the `containers` of a PodMetrics object, and match them up with the limits in the pod spec
+fn parse_container_usage(metrics: &Value, pod: &Pod) -> Result<Vec<ContainerResourceUsage>> {
+    let containers = metrics["containers"]
+        .as_array()
+        .ok_or_else(|| format_err!("PodMetrics has no containers: {}", metrics))?;
+    containers
+        .iter()
+        .map(|container| {
+            let name = container["name"]
+                .as_str()
+                .ok_or_else(|| format_err!("Container metrics have no name: {}", container))?;
+            let cpu = container["usage"]["cpu"]
+                .as_str()
+                .ok_or_else(|| format_err!("Container {} has no cpu usage", name))?;
+            let memory = container["usage"]["memory"]
+                .as_str()
+                .ok_or_else(|| format_err!("Container {} has no memory usage", name))?;
+
+            let limits = pod
+                .spec
+                .as_ref()
+                .and_then(|spec| spec.containers.iter().find(|c| c.name == name))
+                .and_then(|c| c.resources.as_ref())
+                .and_then(|resources| resources.limits.as_ref());
+            let cpu_limit_millicores = limits
+                .and_then(|limits| limits.get("cpu"))
+                .map(|limit| parse_cpu_millicores(&limit.0))
+                .transpose()?;
+            let memory_limit_bytes = limits
+                .and_then(|limits| limits.get("memory"))
+                .map(|limit| parse_memory_bytes(&limit.0))
+                .transpose()?;
+
+            Ok(ContainerResourceUsage {
+                name: name.to_string(),
+                cpu_millicores: parse_cpu_millicores(cpu)?,
+                memory_bytes: parse_memory_bytes(memory)?,
+                cpu_limit_millicores,
+                memory_limit_bytes,
+            })
+        })
+        .collect()
+}
+
+/// Parse a k8s CPU quantity, e.g. "2", "500m" or "250000000n", into millicores
+fn parse_cpu_millicores(quantity: &str) -> Result<f64> {
+    let (number, scale) = match quantity.char_indices().last() {
+        Some((i, 'n')) => (&quantity[..i], 1e-6),
+        Some((i, 'u')) => (&quantity[..i], 1e-3),
+        Some((i, 'm')) => (&quantity[..i], 1.0),
+        _ => (quantity, 1e3),
+    };
+    let number: f64 = number
+        .parse()
+        .map_err(|e| format_err!("Invalid CPU quantity {:?}: {}", quantity, e))?;
+    Ok(number * scale)
+}
+
+/// Parse a k8s memory quantity, e.g. "128974848", "129M" or "123Mi", into bytes
+fn parse_memory_bytes(quantity: &str) -> Result<u64> {
+    const SUFFIXES: [(&str, f64); 12] = [
+        ("Ki", 1024.0),
+        ("Mi", 1024.0 * 1024.0),
+        ("Gi", 1024.0 * 1024.0 * 1024.0),
+        ("Ti", 1024.0 * 1024.0 * 1024.0 * 1024.0),
+        ("Pi", 1024.0 * 1024.0 * 1024.0 * 1024.0 * 1024.0),
+        ("Ei", 1024.0 * 1024.0 * 1024.0 * 1024.0 * 1024.0 * 1024.0),
+        ("k", 1e3),
+        ("M", 1e6),
+        ("G", 1e9),
+        ("T", 1e12),
+        ("P", 1e15),
+        ("E", 1e18),
+    ];
+    let (number, scale) = SUFFIXES
+        .iter()
+        .find_map(|(suffix, scale)| quantity.strip_suffix(suffix).map(|number| (number, *scale)))
+        .unwrap_or((quantity, 1.0));
+    let number: f64 = number
+        .parse()
+        .map_err(|e| format_err!("Invalid memory quantity {:?}: {}", quantity, e))?;
+    Ok((number * scale).round() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use k8s_openapi::{
+        api::core::v1::{Container, PodSpec, ResourceRequirements},
+        apimachinery::pkg::api::resource::Quantity,
+    };
+    use serde_json::json;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_parse_quantities() {
+        assert_eq!(parse_cpu_millicores("2").unwrap(), 2000.0);
+        assert_eq!(parse_cpu_millicores("1.5").unwrap(), 1500.0);
+        assert_eq!(parse_cpu_millicores("500m").unwrap(), 500.0);
+        assert_eq!(parse_cpu_millicores("2500u").unwrap(), 2.5);
+        assert_eq!(parse_cpu_millicores("250000000n").unwrap(), 250.0);
+        parse_cpu_millicores("lots").unwrap_err();
+
+        assert_eq!(parse_memory_bytes("128974848").unwrap(), 128974848);
+        assert_eq!(parse_memory_bytes("1Ki").unwrap(), 1024);
+        assert_eq!(parse_memory_bytes("123Mi").unwrap(), 123 * 1024 * 1024);
+        assert_eq!(parse_memory_bytes("2Gi").unwrap(), 2 * 1024 * 1024 * 1024);
+        assert_eq!(parse_memory_bytes("129M").unwrap(), 129_000_000);
+        assert_eq!(parse_memory_bytes("1e3").unwrap(), 1000);
+        parse_memory_bytes("12Qi").unwrap_err();
+    }
+
+    #[test]
+    fn test_parse_container_usage() {
+        let metrics = json!({
+            "containers": [
+                {"name": "validator", "usage": {"cpu": "3500m", "memory": "2Gi"}},
+                {"name": "sidecar", "usage": {"cpu": "1000000n", "memory": "1Mi"}},
+            ]
+        });
+        let limits = BTreeMap::from([
+            ("cpu".to_string(), Quantity("4".to_string())),
+            ("memory".to_string(), Quantity("8Gi".to_string())),
+        ]);
+        let pod = Pod {
+            spec: Some(PodSpec {
+                containers: vec![
+                    Container {
+                        name: "validator".to_string(),
+                        resources: Some(ResourceRequirements {
+                            limits: Some(limits),
+                            ..ResourceRequirements::default()
+                        }),
+                        ..Container::default()
+                    },
+                    Container {
+                        name: "sidecar".to_string(),
+                        ..Container::default()
+                    },
+                ],
+                ..PodSpec::default()
+            }),
+            ..Pod::default()
+        };
+
+        let containers = parse_container_usage(&metrics, &pod).unwrap();
+        assert_eq!(containers.len(), 2);
+        assert_eq!(containers[0].cpu_limit_fraction(), Some(0.875));
+        assert_eq!(containers[0].memory_limit_fraction(), Some(0.25));
+        assert_eq!(containers[1].cpu_millicores, 1.0);
+        assert_eq!(containers[1].cpu_limit_fraction(), None);
+
+        let usage = PodResourceUsage {
+            pod_name: "aptos-node-0-validator-0".to_string(),
+            containers,
+            haproxy_containers: vec![],
+        };
+        assert_eq!(usage.cpu_millicores(), 3501.0);
+        assert_eq!(usage.memory_bytes(), 2 * 1024 * 1024 * 1024 + 1024 * 1024);
+        assert_eq!(usage.max_cpu_limit_fraction(), Some(0.875));
+    }
+}
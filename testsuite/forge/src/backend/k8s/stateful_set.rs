@@ -1,26 +1,42 @@
 // Copyright © Aptos Foundation
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::{create_k8s_client, k8s_wait_nodes_strategy, K8sApi, ReadWrite, Result, KUBECTL_BIN};
+use crate::{k8s_wait_nodes_strategy, K8sApi, K8sBackendConfig, K8sError, ReadWrite, Result};
 use again::RetryPolicy;
-use anyhow::bail;
+use anyhow::{bail, format_err};
 use aptos_logger::info;
 use json_patch::{Patch as JsonPatch, PatchOperation, ReplaceOperation};
-use k8s_openapi::api::{apps::v1::StatefulSet, core::v1::Pod};
+use k8s_openapi::{
+    api::{
+        apps::v1::StatefulSet,
+        core::v1::{Pod, PodStatus},
+    },
+    chrono::{self, DateTime, Utc},
+};
 use kube::{
-    api::{Api, Patch, PatchParams},
+    api::{Api, DeleteParams, Patch, PatchParams},
     client::Client as K8sClient,
     ResourceExt,
 };
 use serde_json::{json, Value};
-use std::{process::Command, sync::Arc, time::Duration};
+use std::{sync::Arc, time::Duration};
 use thiserror::Error;
 
+// the replicas a StatefulSet shared by nodes ran before a replica in the middle of it was stopped,
+// which stopped the replicas after it too. They start again with it.
+const RESTORE_REPLICAS_ANNOTATION: &str = "forge.aptoslabs.com/restore-replicas";
+// how long a pod may be unschedulable before waiting for it fails, long enough for the cluster
+// autoscaler to add a k8s node for it
+const UNSCHEDULABLE_GRACE_PERIOD: Duration = Duration::from_secs(300);
+
 #[derive(Error, Debug)]
-#[error("{0}")]
 enum WorkloadScalingError {
+    #[error("{0}")]
     RetryableError(String),
+    #[error("{0}")]
     FinalError(String),
+    #[error(transparent)]
+    K8s(K8sError),
 }
 
 pub struct KubeImage {
@@ -78,9 +94,104 @@ pub async fn wait_stateful_set(
             },
             |e: &WorkloadScalingError| matches!(e, WorkloadScalingError::RetryableError(_)),
         )
-        .await?;
+        .await
+        .map_err(|e| {
+            into_wait_error(
+                e,
+                format!(
+                    "StatefulSet {} to have {} replicas",
+                    sts_name, desired_replicas
+                ),
+            )
+        })
+}
 
-    Ok(())
+/// Waits for the pods of a single K8s StatefulSet to be replaced and ready after its pod template
+/// was changed, e.g. to run a new image
+pub async fn wait_stateful_set_rollout(
+    kube_client: &K8sClient,
+    kube_namespace: &str,
+    sts_name: &str,
+    retry_policy: RetryPolicy,
+) -> Result<()> {
+    let stateful_set_api = Arc::new(K8sApi::<StatefulSet>::from_client(
+        kube_client.clone(),
+        Some(kube_namespace.to_string()),
+    ));
+    let pod_api = Arc::new(K8sApi::<Pod>::from_client(
+        kube_client.clone(),
+        Some(kube_namespace.to_string()),
+    ));
+    retry_policy
+        .retry_if(
+            move || check_stateful_set_rollout(stateful_set_api.clone(), pod_api.clone(), sts_name),
+            |e: &WorkloadScalingError| matches!(e, WorkloadScalingError::RetryableError(_)),
+        )
+        .await
+        .map_err(|e| into_wait_error(e, format!("rollout of StatefulSet {}", sts_name)))
+}
+
+/// Why the scheduler has not placed the pod for longer than [UNSCHEDULABLE_GRACE_PERIOD], e.g.
+/// that no k8s node satisfies its anti-affinity. Until then, the cluster may still scale up for it.
+fn unschedulable_reason(status: &PodStatus, now: DateTime<Utc>) -> Option<String> {
+    let condition = status.conditions.iter().flatten().find(|condition| {
+        condition.type_ == "PodScheduled"
+            && condition.status == "False"
+            && condition.reason.as_deref() == Some("Unschedulable")
+    })?;
+    let since = condition.last_transition_time.as_ref()?.0;
+    let grace_period = chrono::Duration::from_std(UNSCHEDULABLE_GRACE_PERIOD).ok()?;
+    if now - since < grace_period {
+        return None;
+    }
+    Some(
+        condition
+            .message
+            .clone()
+            .unwrap_or_else(|| "Unschedulable".to_string()),
+    )
+}
+
+fn into_wait_error(error: WorkloadScalingError, operation: String) -> anyhow::Error {
+    match error {
+        WorkloadScalingError::RetryableError(message) => {
+            K8sError::Timeout { operation, message }.into()
+        },
+        WorkloadScalingError::K8s(e) => e.into(),
+        e => anyhow::Error::from(e),
+    }
+}
+
+/// Checks that the latest revision of a single K8s StatefulSet has been rolled out to all its
+/// replicas, and that they are ready. Fails fast if the new pods can't start, e.g. because the
+/// image can't be pulled.
+async fn check_stateful_set_rollout(
+    stateful_set_api: Arc<dyn ReadWrite<StatefulSet>>,
+    pod_api: Arc<dyn ReadWrite<Pod>>,
+    sts_name: &str,
+) -> Result<(), WorkloadScalingError> {
+    let s = stateful_set_api
+        .get(sts_name)
+        .await
+        .map_err(|e| retryable_unless_forbidden(&format!("StatefulSet {}", sts_name), e))?;
+    let desired_replicas = s.spec.as_ref().and_then(|spec| spec.replicas).unwrap_or(1) as u64;
+    // the controller has to see the new spec before its status says anything about it
+    let rolled_out = s.status.as_ref().map_or(false, |status| {
+        status.observed_generation >= s.metadata.generation
+            && status.update_revision.is_some()
+            && status.update_revision == status.current_revision
+            && status.updated_replicas.unwrap_or(0) as u64 == desired_replicas
+    });
+    check_stateful_set_status(stateful_set_api, pod_api, sts_name, desired_replicas).await?;
+    if rolled_out {
+        info!("StatefulSet {} has rolled out", sts_name);
+        Ok(())
+    } else {
+        Err(WorkloadScalingError::RetryableError(format!(
+            "StatefulSet {} is still rolling out",
+            sts_name
+        )))
+    }
 }
 
 /// Checks the status of a single K8s StatefulSet. Also inspects the pods to make sure they are all ready.
@@ -114,7 +225,7 @@ async fn check_stateful_set_status(
             if let Some(status) = pod_api
                 .get(&pod_name)
                 .await
-                .map_err(|e| WorkloadScalingError::RetryableError(e.to_string()))?
+                .map_err(|e| retryable_unless_forbidden(&format!("pod {}", pod_name), e))?
                 .status
             {
                 if let Some(ref container_statuses) = status.container_statuses {
@@ -153,6 +264,13 @@ async fn check_stateful_set_status(
                         }
                     }
                 }
+                if let Some(reason) = unschedulable_reason(&status, Utc::now()) {
+                    info!("Pod {} can't be scheduled: {}", &pod_name, reason);
+                    return Err(WorkloadScalingError::FinalError(format!(
+                        "Pod {} can't be scheduled: {}",
+                        &pod_name, reason
+                    )));
+                }
                 if let Some(phase) = status.phase.as_ref() {
                     info!("Pod {} at phase {}", &pod_name, phase)
                 }
@@ -169,23 +287,33 @@ async fn check_stateful_set_status(
         },
         Err(e) => {
             info!("Failed to get StatefulSet: {}", e);
-            Err(WorkloadScalingError::RetryableError(format!(
-                "Failed to get StatefulSet: {}",
-                e
-            )))
+            Err(retryable_unless_forbidden(
+                &format!("StatefulSet {}", sts_name),
+                e,
+            ))
         },
     }
 }
 
-/// Given the name of a node's StatefulSet, sets the node's image tag. Assumes that the StatefulSet has only one container
+/// Missing permissions won't fix themselves, so only retry other errors
+fn retryable_unless_forbidden(resource: &str, error: kube::Error) -> WorkloadScalingError {
+    match K8sError::from_kube(resource, error) {
+        e @ K8sError::Forbidden { .. } => WorkloadScalingError::K8s(e),
+        e => WorkloadScalingError::RetryableError(e.to_string()),
+    }
+}
+
+/// Given the name of a node's StatefulSet, sets the node's image tag. Only the named container is changed, so
+/// any other containers keep their image.
 /// Note that this function will not wait for the StatefulSet to be ready.
 pub async fn set_stateful_set_image_tag(
+    config: &K8sBackendConfig,
     stateful_set_name: String,
     container_name: String,
     image_tag: String,
     kube_namespace: String,
 ) -> Result<()> {
-    let kube_client: K8sClient = create_k8s_client().await?;
+    let kube_client: K8sClient = config.create_client().await?;
     let sts_api: Api<StatefulSet> = Api::namespaced(kube_client.clone(), &kube_namespace);
     let sts = sts_api.get(&stateful_set_name).await?;
     let image_repo = get_stateful_set_image(&sts)?.name;
@@ -195,28 +323,36 @@ pub async fn set_stateful_set_image_tag(
 
     // set the image using kubectl
     // patching the node spec may not work
-    Command::new(KUBECTL_BIN)
-        .args([
-            "-n",
-            &kube_namespace,
-            "set",
-            "image",
-            &format!("statefulset/{}", &stateful_set_name),
-            &format!("{}={}", &container_name, &new_image),
-        ])
-        .status()
-        .expect("Failed to set image for StatefulSet");
+    let target = format!("statefulset/{}", &stateful_set_name);
+    let image = format!("{}={}", &container_name, &new_image);
+    let args = [
+        "-n",
+        kube_namespace.as_str(),
+        "set",
+        "image",
+        &target,
+        &image,
+    ];
+    let output = config
+        .kubectl_async()
+        .args(args)
+        .output()
+        .await
+        .map_err(|e| format_err!("Failed to set image for StatefulSet: {}", e))?;
+    if !output.status.success() {
+        return Err(K8sError::from_kubectl(&args, &output).into());
+    }
 
     Ok(())
 }
 
-/// Scales the given StatefulSet to the given number of replicas
-pub async fn scale_stateful_set_replicas(
+/// Sets the desired number of replicas of the given StatefulSet, without waiting for it to be ready
+async fn patch_stateful_set_replicas(
+    kube_client: &K8sClient,
     sts_name: &str,
     kube_namespace: &str,
     replica_num: u64,
 ) -> Result<()> {
-    let kube_client = create_k8s_client().await?;
     let stateful_set_api: Api<StatefulSet> = Api::namespaced(kube_client.clone(), kube_namespace);
     let pp = PatchParams::apply("forge").force();
     let patch = serde_json::json!({
@@ -230,7 +366,22 @@ pub async fn scale_stateful_set_replicas(
         }
     });
     let patch = Patch::Apply(&patch);
-    stateful_set_api.patch(sts_name, &pp, &patch).await?;
+    stateful_set_api
+        .patch(sts_name, &pp, &patch)
+        .await
+        .map_err(|e| K8sError::from_kube(format!("StatefulSet {}", sts_name), e))?;
+    Ok(())
+}
+
+/// Scales the given StatefulSet to the given number of replicas
+pub async fn scale_stateful_set_replicas(
+    config: &K8sBackendConfig,
+    sts_name: &str,
+    kube_namespace: &str,
+    replica_num: u64,
+) -> Result<()> {
+    let kube_client = config.create_client().await?;
+    patch_stateful_set_replicas(&kube_client, sts_name, kube_namespace, replica_num).await?;
     // retry for ~5 min at a fixed interval
     let retry_policy = RetryPolicy::fixed(Duration::from_secs(10)).with_max_retries(6 * 5);
     wait_stateful_set(
@@ -245,12 +396,110 @@ pub async fn scale_stateful_set_replicas(
     Ok(())
 }
 
+/// Force deletes the pod of the given replica of the StatefulSet without a grace period,
+/// simulating a crash. The StatefulSet controller will recreate the pod unless the StatefulSet is
+/// scaled down.
+pub async fn force_delete_stateful_set_pod(
+    config: &K8sBackendConfig,
+    sts_name: &str,
+    replica_index: u32,
+    kube_namespace: &str,
+) -> Result<()> {
+    let kube_client = config.create_client().await?;
+    let pod_api: Api<Pod> = Api::namespaced(kube_client, kube_namespace);
+    let pod_name = format!("{}-{}", sts_name, replica_index);
+    let dp = DeleteParams {
+        grace_period_seconds: Some(0),
+        ..DeleteParams::default()
+    };
+    pod_api
+        .delete(&pod_name, &dp)
+        .await
+        .map_err(|e| K8sError::from_kube(format!("pod {}", pod_name), e))?;
+    info!("Force deleted pod {}", pod_name);
+    Ok(())
+}
+
+/// Force deletes the pod of the given replica of the StatefulSet and scales the StatefulSet down
+/// to that replica, so it stays down
+pub async fn kill_stateful_set(
+    config: &K8sBackendConfig,
+    sts_name: &str,
+    replica_index: u32,
+    kube_namespace: &str,
+) -> Result<()> {
+    let kube_client = config.create_client().await?;
+    let replicas = replica_index as u64;
+    // scale down first so that the pod is not recreated
+    patch_stateful_set_replicas(&kube_client, sts_name, kube_namespace, replicas).await?;
+    force_delete_stateful_set_pod(config, sts_name, replica_index, kube_namespace).await?;
+    let retry_policy = RetryPolicy::fixed(Duration::from_secs(10)).with_max_retries(6 * 5);
+    wait_stateful_set(
+        &kube_client,
+        kube_namespace,
+        sts_name,
+        replicas,
+        retry_policy,
+    )
+    .await
+}
+
+/// The number of replicas in the spec of the StatefulSet, and the number to restore once its
+/// stopped replica in the middle starts again, see [set_stateful_set_restore_replicas]
+pub(crate) async fn get_stateful_set_replicas(
+    config: &K8sBackendConfig,
+    sts_name: &str,
+    kube_namespace: &str,
+) -> Result<(u32, Option<u32>)> {
+    let kube_client = config.create_client().await?;
+    let stateful_set_api: Api<StatefulSet> = Api::namespaced(kube_client, kube_namespace);
+    let stateful_set = stateful_set_api
+        .get(sts_name)
+        .await
+        .map_err(|e| K8sError::from_kube(format!("StatefulSet {}", sts_name), e))?;
+    let restore_replicas = stateful_set
+        .annotations()
+        .get(RESTORE_REPLICAS_ANNOTATION)
+        .and_then(|replicas| replicas.parse().ok());
+    let replicas = stateful_set
+        .spec
+        .and_then(|spec| spec.replicas)
+        .unwrap_or(1) as u32;
+    Ok((replicas, restore_replicas))
+}
+
+/// Remember the number of replicas to restore once the stopped replica in the middle of the
+/// StatefulSet starts again, or forget it
+pub(crate) async fn set_stateful_set_restore_replicas(
+    config: &K8sBackendConfig,
+    sts_name: &str,
+    kube_namespace: &str,
+    restore_replicas: Option<u32>,
+) -> Result<()> {
+    let kube_client = config.create_client().await?;
+    let stateful_set_api: Api<StatefulSet> = Api::namespaced(kube_client, kube_namespace);
+    // null removes the annotation
+    let patch = json!({
+        "metadata": {
+            "annotations": {
+                RESTORE_REPLICAS_ANNOTATION: restore_replicas.map(|replicas| replicas.to_string()),
+            },
+        },
+    });
+    stateful_set_api
+        .patch(sts_name, &PatchParams::default(), &Patch::Merge(&patch))
+        .await
+        .map_err(|e| K8sError::from_kube(format!("StatefulSet {}", sts_name), e))?;
+    Ok(())
+}
+
 pub async fn set_identity(
+    config: &K8sBackendConfig,
     sts_name: &str,
     kube_namespace: &str,
     k8s_secret_name: &str,
 ) -> Result<()> {
-    let kube_client = create_k8s_client().await?;
+    let kube_client = config.create_client().await?;
     let stateful_set_api: Api<StatefulSet> = Api::namespaced(kube_client.clone(), kube_namespace);
     let patch_op = PatchOperation::Replace(ReplaceOperation {
         // The json path below should match `terraform/helm/aptos-node/templates/validator.yaml`.
@@ -263,8 +512,12 @@ pub async fn set_identity(
     Ok(())
 }
 
-pub async fn get_identity(sts_name: &str, kube_namespace: &str) -> Result<String> {
-    let kube_client = create_k8s_client().await?;
+pub async fn get_identity(
+    config: &K8sBackendConfig,
+    sts_name: &str,
+    kube_namespace: &str,
+) -> Result<String> {
+    let kube_client = config.create_client().await?;
     let stateful_set_api: Api<StatefulSet> = Api::namespaced(kube_client.clone(), kube_namespace);
     let sts = stateful_set_api.get(sts_name).await?;
     // The json path below should match `terraform/helm/aptos-node/templates/validator.yaml`.
@@ -318,9 +571,9 @@ mod tests {
     use k8s_openapi::{
         api::{
             apps::v1::{StatefulSet, StatefulSetSpec, StatefulSetStatus},
-            core::v1::{ContainerState, ContainerStateWaiting, ContainerStatus, PodStatus},
+            core::v1::{ContainerState, ContainerStateWaiting, ContainerStatus, PodCondition},
         },
-        apimachinery::pkg::apis::meta::v1::ObjectMeta,
+        apimachinery::pkg::apis::meta::v1::{ObjectMeta, Time},
     };
 
     #[tokio::test]
@@ -395,4 +648,92 @@ mod tests {
             Some(WorkloadScalingError::FinalError(_))
         ));
     }
+
+    #[test]
+    fn test_unschedulable_reason() {
+        let now = Utc::now();
+        let status = |reason: &str, since: DateTime<Utc>| PodStatus {
+            phase: Some("Pending".to_string()),
+            conditions: Some(vec![PodCondition {
+                type_: "PodScheduled".to_string(),
+                status: "False".to_string(),
+                reason: Some(reason.to_string()),
+                message: Some(
+                    "0/4 nodes are available: 4 node(s) didn't match pod anti-affinity rules"
+                        .to_string(),
+                ),
+                last_transition_time: Some(Time(since)),
+                ..PodCondition::default()
+            }]),
+            ..PodStatus::default()
+        };
+        let long_ago = now - chrono::Duration::minutes(10);
+        assert_eq!(
+            unschedulable_reason(&status("Unschedulable", long_ago), now).unwrap(),
+            "0/4 nodes are available: 4 node(s) didn't match pod anti-affinity rules"
+        );
+        // the cluster may still scale up for it
+        let just_now = now - chrono::Duration::seconds(30);
+        assert_eq!(
+            unschedulable_reason(&status("Unschedulable", just_now), now),
+            None
+        );
+        assert_eq!(
+            unschedulable_reason(&status("SchedulingGated", long_ago), now),
+            None
+        );
+        assert_eq!(unschedulable_reason(&PodStatus::default(), now), None);
+    }
+
+    #[tokio::test]
+    async fn test_check_stateful_set_rollout() {
+        let stateful_set = |current_revision: &str| StatefulSet {
+            metadata: ObjectMeta {
+                name: Some("test-stateful-set".to_string()),
+                generation: Some(2),
+                ..ObjectMeta::default()
+            },
+            spec: Some(StatefulSetSpec {
+                replicas: Some(1),
+                ..StatefulSetSpec::default()
+            }),
+            status: Some(StatefulSetStatus {
+                replicas: 1,
+                ready_replicas: Some(1),
+                updated_replicas: Some(1),
+                observed_generation: Some(2),
+                current_revision: Some(current_revision.to_string()),
+                update_revision: Some("test-stateful-set-new".to_string()),
+                ..StatefulSetStatus::default()
+            }),
+        };
+        let pod_api = Arc::new(MockPodApi::from_pod(Pod {
+            status: Some(PodStatus::default()),
+            ..Pod::default()
+        }));
+
+        // the old pod is still ready, but it has not been replaced yet
+        let ret = check_stateful_set_rollout(
+            Arc::new(MockStatefulSetApi::from_stateful_set(stateful_set(
+                "test-stateful-set-old",
+            ))),
+            pod_api.clone(),
+            "test-stateful-set",
+        )
+        .await;
+        assert!(matches!(
+            ret.err(),
+            Some(WorkloadScalingError::RetryableError(_))
+        ));
+
+        check_stateful_set_rollout(
+            Arc::new(MockStatefulSetApi::from_stateful_set(stateful_set(
+                "test-stateful-set-new",
+            ))),
+            pod_api,
+            "test-stateful-set",
+        )
+        .await
+        .unwrap();
+    }
 }
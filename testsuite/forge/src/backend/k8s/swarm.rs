@@ -3,29 +3,57 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::{
+    apply_rotated_identity,
+    backend::k8s::event::describe_events,
+    chaos::{conflicts, updates_in_place},
     chaos_schema::{
-        Chaos, ChaosConditionType, ChaosStatus, ConditionStatus, NetworkChaos, StressChaos,
+        Chaos, ChaosConditionType, ChaosStatus, ConditionStatus, DNSChaos, NetworkChaos,
+        StressChaos, TimeChaos,
     },
-    check_for_container_restart, create_k8s_client, delete_all_chaos, get_default_pfn_node_config,
-    get_free_port, get_stateful_set_image, install_public_fullnode,
-    node::K8sNode,
+    check_for_container_restart, cleanup_orphaned_port_forwards, collect_with_timeout,
+    delete_all_chaos, delete_fullnode_resources, delete_validator_companion_resources,
+    delete_validator_resources, force_end_epoch, get_default_pfn_node_config,
+    get_stateful_set_image, get_validator_account, get_validator_set, install_public_fullnode,
+    install_validator, install_validator_attached_fullnode, lagging_nodes, leave_validator_set,
+    next_faulty_validator,
+    node::{stateful_set_resources, K8sNode, RestApiTls},
     prometheus::{self, query_range_with_metadata, query_with_metadata},
-    query_sequence_number, set_stateful_set_image_tag, uninstall_testnet_resources, ChainInfo,
-    FullNode, K8sApi, Node, Result, Swarm, SwarmChaos, Validator, Version, HAPROXY_SERVICE_SUFFIX,
-    REST_API_HAPROXY_SERVICE_PORT, REST_API_SERVICE_PORT,
+    prometheus_metrics::PrometheusUnavailable,
+    public_fullnode_node_config, query_sequence_number, set_faulty_failpoints, set_stake,
+    store_rotated_identity, uninstall_testnet_resources, ArtifactManifest, ChainInfo,
+    DbSnapshotOptions, EventWatcher, FaultyBehavior, FullNode, FullNodeConfig,
+    FullNodeResourceNames, FullNodeUpgradeOrder, InfrastructureEvent, K8sApi, K8sBackendConfig,
+    K8sError, KeyRotationResult, KeyRotationStage, NewValidator, NewValidatorOptions, Node,
+    NodeArtifacts, NodeExt, NodeResources, NodeRestarts, PodResourceUsage, PodRestartCount, Result,
+    RollingUpgradeOptions, RollingUpgradeReport, Swarm, SwarmChaos, UpgradeBatchTiming,
+    UpgradeSelector, Validator, ValidatorCompanionResourceNames, ValidatorResourceNames, Version,
+    APTOS_NODE_HELM_RELEASE_NAME, ARTIFACT_COLLECTION_TIMEOUT, DEFAULT_LEDGER_STALENESS_THRESHOLD,
+    HAPROXY_SERVICE_SUFFIX, NODE_METRIC_PORT, REST_API_HAPROXY_SERVICE_PORT, REST_API_PORT_NAME,
+    REST_API_SERVICE_PORT, VALIDATOR_HAPROXY_SERVICE_SUFFIX,
 };
 use ::aptos_logger::*;
-use anyhow::{anyhow, bail, format_err};
+use anyhow::{anyhow, bail, format_err, Context};
 use aptos_config::config::{NodeConfig, OverrideNodeConfig};
+use aptos_keygen::KeyGen;
 use aptos_retrier::fixed_retry_strategy;
 use aptos_sdk::{
     crypto::ed25519::Ed25519PrivateKey,
     move_types::account_address::AccountAddress,
-    types::{chain_id::ChainId, AccountKey, LocalAccount, PeerId},
+    types::{
+        account_address::from_identity_public_key, account_config::CORE_CODE_ADDRESS,
+        chain_id::ChainId, on_chain_config::ValidatorSet, AccountKey, LocalAccount, PeerId,
+    },
+};
+use futures::{
+    future::{join_all, try_join_all},
+    FutureExt,
 };
-use k8s_openapi::api::{
-    apps::v1::StatefulSet,
-    core::v1::{ConfigMap, PersistentVolumeClaim, Service},
+use k8s_openapi::{
+    api::{
+        apps::v1::StatefulSet,
+        core::v1::{ConfigMap, PersistentVolumeClaim, Secret, Service},
+    },
+    chrono::Utc,
 };
 use kube::{
     api::{Api, ListParams},
@@ -39,17 +67,31 @@ use regex::Regex;
 use std::{
     collections::{BTreeMap, HashMap, HashSet},
     convert::TryFrom,
-    env, str,
-    sync::{atomic::AtomicU32, Arc},
+    env, fs,
+    path::{Path, PathBuf},
+    str,
+    sync::{atomic::AtomicU32, Arc, Mutex},
+    time::Instant,
 };
 // use std::sync::Mutex;
 use tokio::{runtime::Runtime, time::Duration};
 
+// how long a removed validator gets to leave the validator set
+const REMOVE_VALIDATOR_TIMEOUT: Duration = Duration::from_secs(300);
+// of the whole key rotation, through the epoch change and the restart of the validator
+const ROTATE_VALIDATOR_KEYS_TIMEOUT: Duration = Duration::from_secs(600);
+// how long deployed public fullnodes get to catch up with the validators' fullnodes
+const PUBLIC_FULLNODE_HEALTH_TIMEOUT: Duration = Duration::from_secs(600);
+
 pub struct K8sSwarm {
     validators: HashMap<PeerId, K8sNode>,
     fullnodes: HashMap<PeerId, K8sNode>,
+    // the fullnodes that peer with the validators' fullnodes over their public network, rather
+    // than with a validator
+    public_fullnodes: HashMap<PeerId, K8sNode>,
     root_account: Arc<LocalAccount>,
     kube_client: K8sClient,
+    backend_config: Arc<K8sBackendConfig>,
     versions: Arc<HashMap<Version, String>>,
     pub chain_id: ChainId,
     pub kube_namespace: String,
@@ -59,21 +101,28 @@ pub struct K8sSwarm {
     era: Option<String>,
     use_port_forward: bool,
     chaos_experiment_ops: Box<dyn ChaosExperimentOps + Send + Sync>,
+    // logs and records the Warning events of the namespace, if enabled in the backend config
+    event_watcher: Option<EventWatcher>,
+    // the restart counts of the nodes' pods when the swarm was created, by pod name
+    restart_baseline: HashMap<String, PodRestartCount>,
+    // the validators designated faulty, and how they misbehave
+    faulty_validators: HashMap<PeerId, FaultyBehavior>,
 }
 
 impl K8sSwarm {
     pub async fn new<'b>(
+        backend_config: Arc<K8sBackendConfig>,
         root_key: &[u8],
         image_tag: &str,
         upgrade_image_tag: &str,
         kube_namespace: &str,
-        validators: HashMap<AccountAddress, K8sNode>,
+        mut validators: HashMap<AccountAddress, K8sNode>,
         fullnodes: HashMap<AccountAddress, K8sNode>,
         keep: bool,
         era: Option<String>,
         use_port_forward: bool,
     ) -> Result<Self> {
-        let kube_client = create_k8s_client().await?;
+        let kube_client = backend_config.create_client().await?;
 
         let client = validators.values().next().unwrap().rest_client();
         let key = load_root_key(root_key);
@@ -94,8 +143,26 @@ impl K8sSwarm {
         let upgrade_version = Version::new(1, upgrade_image_tag.to_string());
         versions.insert(upgrade_version, upgrade_image_tag.to_string());
         versions.insert(cur_version, image_tag.to_string());
+        // e.g. in a reused namespace, the public fullnodes are listed with the others
+        let (mut public_fullnodes, mut fullnodes): (HashMap<_, _>, HashMap<_, _>) = fullnodes
+            .into_iter()
+            .partition(|(_, node)| node.is_public_fullnode());
+        // the nodes only know their image tag, e.g. when the swarm started on mixed versions, so
+        // give them the version of that tag
+        for node in validators
+            .values_mut()
+            .chain(fullnodes.values_mut())
+            .chain(public_fullnodes.values_mut())
+        {
+            if let Some(version) = versions
+                .keys()
+                .find(|version| version.to_string() == node.version.to_string())
+            {
+                node.version = version.clone();
+            }
+        }
 
-        let prom_client = match prometheus::get_prometheus_client().await {
+        let prom_client = match prometheus::get_prometheus_client(&backend_config).await {
             Ok(p) => Some(p),
             Err(e) => {
                 // Fail fast if prometheus is not configured. A test is meaningless if we do not have observability
@@ -103,11 +170,33 @@ impl K8sSwarm {
             },
         };
 
+        // the restarts before, e.g. of a reused namespace, are not the test's
+        let mut restart_baseline = HashMap::new();
+        for node in validators
+            .values()
+            .chain(fullnodes.values())
+            .chain(public_fullnodes.values())
+        {
+            if let Some(restart_count) = node.restart_count().await? {
+                restart_baseline.insert(node.pod_name(), restart_count);
+            }
+        }
+        let event_watcher = backend_config.watch_events.then(|| {
+            let nodes = validators
+                .values()
+                .chain(fullnodes.values())
+                .chain(public_fullnodes.values())
+                .map(|node| (event_object_prefix(node), node.name().to_string()))
+                .collect();
+            EventWatcher::start(kube_client.clone(), kube_namespace, nodes)
+        });
         let swarm = K8sSwarm {
             validators,
             fullnodes,
+            public_fullnodes,
             root_account,
             kube_client: kube_client.clone(),
+            backend_config,
             chain_id: ChainId::new(4),
             versions: Arc::new(versions),
             kube_namespace: kube_namespace.to_string(),
@@ -120,6 +209,9 @@ impl K8sSwarm {
                 kube_client: kube_client.clone(),
                 kube_namespace: kube_namespace.to_string(),
             }),
+            event_watcher,
+            restart_baseline,
+            faulty_validators: HashMap::new(),
         };
 
         // test hitting the configured prometheus endpoint
@@ -130,9 +222,203 @@ impl K8sSwarm {
             info!("container_memory_usage_bytes: {}", iv.sample().value());
         }
 
+        // tests address nodes by peer_id, so fail fast if those do not match the chain
+        swarm.verify_topology().await?;
+
         Ok(swarm)
     }
 
+    // so that the events of the node's objects are logged and reported with its name
+    fn watch_events_of(&self, node: &K8sNode) {
+        if let Some(event_watcher) = &self.event_watcher {
+            event_watcher.add_node(&event_object_prefix(node), node.name());
+        }
+    }
+
+    /// The cluster the swarm runs in, and how forge talks to it
+    pub(crate) fn backend_config(&self) -> &K8sBackendConfig {
+        &self.backend_config
+    }
+
+    /// The validator or fullnode of the given peer id
+    pub(crate) fn k8s_node(&self, peer_id: &PeerId) -> Option<&K8sNode> {
+        self.validators
+            .get(peer_id)
+            .or_else(|| self.k8s_fullnode(peer_id))
+    }
+
+    /// The validator fullnode or public fullnode of the given peer id
+    fn k8s_fullnode(&self, peer_id: &PeerId) -> Option<&K8sNode> {
+        self.fullnodes
+            .get(peer_id)
+            .or_else(|| self.public_fullnodes.get(peer_id))
+    }
+
+    fn k8s_node_mut(&mut self, peer_id: &PeerId) -> Option<&mut K8sNode> {
+        match self.validators.get_mut(peer_id) {
+            Some(node) => Some(node),
+            None => match self.fullnodes.get_mut(peer_id) {
+                Some(node) => Some(node),
+                None => self.public_fullnodes.get_mut(peer_id),
+            },
+        }
+    }
+
+    /// The validator fullnodes and the public fullnodes
+    fn all_fullnodes(&self) -> impl Iterator<Item = &K8sNode> {
+        self.fullnodes
+            .values()
+            .chain(self.public_fullnodes.values())
+    }
+
+    /// Let the health checks of the nodes tolerate the skew of their clocks by the injected chaos
+    fn update_clock_skews(&mut self) {
+        let mut skews: HashMap<PeerId, Duration> = HashMap::new();
+        for chaos in &self.chaoses {
+            if let SwarmChaos::TimeSkew(time_skew) = chaos {
+                for peer_id in &time_skew.peers {
+                    skews.insert(*peer_id, time_skew.offset);
+                }
+            }
+        }
+        for (peer_id, node) in self
+            .validators
+            .iter_mut()
+            .chain(self.fullnodes.iter_mut())
+            .chain(self.public_fullnodes.iter_mut())
+        {
+            node.clock_skew = skews.get(peer_id).copied().unwrap_or_default();
+        }
+    }
+
+    /// Record when a memory stress of some nodes starts or ends, so that their OOMKills meanwhile
+    /// are told apart from other failures
+    fn track_memory_stress(&mut self, chaos: &SwarmChaos, injected: bool) {
+        let peers = match chaos {
+            SwarmChaos::PeerMemoryStress(memory_stress) => &memory_stress.peers,
+            _ => return,
+        };
+        let now = Utc::now();
+        for peer_id in peers {
+            let Some(node) = self.k8s_node_mut(peer_id) else {
+                continue;
+            };
+            node.memory_stress = if injected {
+                Some((now, None))
+            } else {
+                node.memory_stress.map(|(start, _)| (start, Some(now)))
+            };
+        }
+    }
+
+    /// Check the identity of every validator and validator fullnode against the on-chain
+    /// validator set, and that each validator fullnode belongs to the validator of its index.
+    /// Every mismatch is reported, not just the first one.
+    pub async fn verify_topology(&self) -> Result<()> {
+        let client = self
+            .validators
+            .values()
+            .next()
+            .ok_or_else(|| format_err!("Swarm has no validators"))?
+            .rest_client();
+        let validator_set: ValidatorSet = client
+            .get_account_resource_bcs(CORE_CODE_ADDRESS, "0x1::stake::ValidatorSet")
+            .await?
+            .into_inner();
+
+        let mut mismatches = vec![];
+        for validator in self.validators.values() {
+            if let Err(e) = validator.verify_identity(&validator_set).await {
+                mismatches.push(e.to_string());
+            }
+        }
+        let unmatched: Vec<_> = validator_set
+            .payload()
+            .map(|info| *info.account_address())
+            .filter(|address| !self.validators.contains_key(address))
+            .collect();
+        if !unmatched.is_empty() {
+            mismatches.push(format!(
+                "On-chain validators {:?} are not in the swarm",
+                unmatched
+            ));
+        }
+
+        for fullnode in self
+            .fullnodes
+            .values()
+            .filter(|fullnode| fullnode.is_validator_fullnode())
+        {
+            let owner = match fullnode.verify_identity(&validator_set).await {
+                Ok(owner) => owner,
+                Err(e) => {
+                    mismatches.push(e.to_string());
+                    continue;
+                },
+            };
+            let expected_owner = self
+                .validators
+                .values()
+                .find(|validator| validator.index() == fullnode.index())
+                .map(|validator| validator.peer_id());
+            if expected_owner != Some(owner) {
+                mismatches.push(format!(
+                    "Fullnode {} is expected to belong to validator {:?}, but is on chain for validator {}",
+                    fullnode.name(),
+                    expected_owner,
+                    owner
+                ));
+            }
+        }
+
+        if !mismatches.is_empty() {
+            bail!(
+                "Swarm topology does not match the chain:\n  {}",
+                mismatches.join("\n  ")
+            );
+        }
+        Ok(())
+    }
+
+    /// Snapshot the resource usage of every validator, e.g. to check that none of them was CPU
+    /// bound during a load test
+    pub async fn validators_resource_usage(&self) -> Result<HashMap<PeerId, PodResourceUsage>> {
+        let mut usage = HashMap::new();
+        for (peer_id, validator) in &self.validators {
+            usage.insert(*peer_id, validator.resource_usage().await?);
+        }
+        Ok(usage)
+    }
+
+    /// Collect CPU profiles of the `count` validators using the most CPU, in parallel, and write
+    /// them to `<dir>/<node name>-cpu-profile.pb`. Returns the paths written.
+    pub async fn collect_busiest_cpu_profiles(
+        &self,
+        count: usize,
+        duration: Duration,
+        dir: &Path,
+    ) -> Result<Vec<PathBuf>> {
+        let usage = self.validators_resource_usage().await?;
+        fs::create_dir_all(dir)?;
+        try_join_all(
+            busiest_nodes(&usage, count)
+                .into_iter()
+                .map(|peer_id| async move {
+                    let validator = &self.validators[&peer_id];
+                    let profile = validator.collect_cpu_profile(duration).await?;
+                    let path = dir.join(format!("{}-cpu-profile.pb", validator.name()));
+                    fs::write(&path, profile)?;
+                    info!(
+                        "Wrote CPU profile of {} to {}",
+                        validator.name(),
+                        path.display()
+                    );
+                    Ok::<_, anyhow::Error>(path)
+                }),
+        )
+        .await
+    }
+
     fn get_rest_api_url(&self, idx: usize) -> String {
         self.validators
             .values()
@@ -156,12 +442,18 @@ impl K8sSwarm {
         self.kube_client.clone()
     }
 
-    /// Installs a PFN with the given version and node config
+    /// Installs a PFN with the given version and node config, without starting it
     async fn install_public_fullnode_resources<'a>(
         &mut self,
         version: &'a Version,
         node_config: &'a OverrideNodeConfig,
     ) -> Result<(PeerId, K8sNode)> {
+        let index = self
+            .public_fullnodes
+            .values()
+            .map(|fullnode| fullnode.index() + 1)
+            .max()
+            .unwrap_or_default();
         // create APIs
         let stateful_set_api: Arc<K8sApi<_>> = Arc::new(K8sApi::<StatefulSet>::from_client(
             self.get_kube_client(),
@@ -192,19 +484,751 @@ impl K8sSwarm {
                 .expect("Installing PFN requires acquiring the current chain era")
                 .clone(),
             self.kube_namespace.clone(),
+            self.backend_config.clone(),
             self.use_port_forward,
-            self.fullnodes.len(),
+            index,
         )
         .await?;
-        k8snode.start().await?; // actually start the node. if port-forward is enabled, this is when it gets its ephemeral port
         Ok((peer_id, k8snode))
     }
+
+    /// Deploy `count` public fullnodes, which sync from the validators' fullnodes over their
+    /// public network, on top of the given config, and wait until they are healthy. Returns their
+    /// PeerIds.
+    pub async fn deploy_public_fullnodes(
+        &mut self,
+        count: usize,
+        version: &Version,
+        config: NodeConfig,
+    ) -> Result<Vec<PeerId>> {
+        if !self.versions.contains_key(version) {
+            bail!("Invalid version: {:?}", version);
+        }
+        let mut peer_ids = vec![];
+        for _ in 0..count {
+            let identity_key = KeyGen::from_os_rng().generate_x25519_private_key()?;
+            let (_, node_config) = public_fullnode_node_config(config.clone(), identity_key);
+            let (peer_id, node) = self
+                .install_public_fullnode_resources(
+                    version,
+                    &OverrideNodeConfig::new_with_default_base(node_config),
+                )
+                .await?;
+            self.watch_events_of(&node);
+            self.public_fullnodes.insert(peer_id, node);
+            peer_ids.push(peer_id);
+        }
+        // healthy once they caught up with the validators' fullnodes
+        let deadline = Instant::now() + PUBLIC_FULLNODE_HEALTH_TIMEOUT;
+        let public_fullnodes = &self.public_fullnodes;
+        try_join_all(peer_ids.iter().map(|peer_id| async move {
+            let node = &public_fullnodes[peer_id];
+            // if port-forward is enabled, this is when the node gets its ephemeral port
+            node.start_with_timeout(PUBLIC_FULLNODE_HEALTH_TIMEOUT)
+                .await?;
+            node.wait_until_healthy(deadline)
+                .await
+                .with_context(|| format!("Public fullnode {} did not become healthy", node.name()))
+        }))
+        .await?;
+        info!("Deployed {} public fullnodes", peer_ids.len());
+        Ok(peer_ids)
+    }
+
+    /// Deploy a fullnode that syncs from the given validator over the validator's VFN network,
+    /// and wait for it to catch up. Returns the PeerId of the fullnode.
+    pub async fn add_fullnode(
+        &mut self,
+        validator_peer_id: PeerId,
+        config: FullNodeConfig,
+    ) -> Result<PeerId> {
+        if !self.versions.contains_key(&config.version) {
+            bail!("Invalid version: {:?}", config.version);
+        }
+        let era = self.era.clone().ok_or_else(|| {
+            format_err!("Adding a fullnode requires acquiring the current chain era")
+        })?;
+        let validator = self
+            .validators
+            .get(&validator_peer_id)
+            .ok_or_else(|| anyhow!("Invalid id: {}", validator_peer_id))?;
+        let validator_index = validator.index();
+        let validator_name = validator.name().to_string();
+        let identity_key = KeyGen::from_os_rng().generate_x25519_private_key()?;
+        let names = FullNodeResourceNames::attached_to_validator(
+            validator_index,
+            from_identity_public_key(identity_key.public_key()),
+        );
+        info!(
+            "Adding fullnode {} to validator {}",
+            names.stateful_set, validator_name
+        );
+        // the node is named after its StatefulSet
+        if let Some(event_watcher) = &self.event_watcher {
+            event_watcher.add_node(&names.stateful_set, &names.stateful_set);
+        }
+
+        let deadline = Instant::now() + config.health_timeout;
+        let result = async {
+            let node = install_validator_attached_fullnode(
+                Arc::new(K8sApi::<StatefulSet>::from_client(
+                    self.get_kube_client(),
+                    Some(self.kube_namespace.clone()),
+                )),
+                Arc::new(K8sApi::<ConfigMap>::from_client(
+                    self.get_kube_client(),
+                    Some(self.kube_namespace.clone()),
+                )),
+                Arc::new(K8sApi::<PersistentVolumeClaim>::from_client(
+                    self.get_kube_client(),
+                    Some(self.kube_namespace.clone()),
+                )),
+                Arc::new(K8sApi::<Service>::from_client(
+                    self.get_kube_client(),
+                    Some(self.kube_namespace.clone()),
+                )),
+                validator_index,
+                &era,
+                &config,
+                identity_key,
+                self.kube_namespace.clone(),
+                self.backend_config.clone(),
+                self.use_port_forward,
+                // the fullnodes of a validator share its index
+                validator_index,
+            )
+            .await?;
+            node.start_with_timeout(config.health_timeout).await?;
+            // healthy once it caught up with the validator
+            node.wait_until_healthy(deadline).await?;
+            Ok::<_, anyhow::Error>(node)
+        }
+        .await;
+        match result {
+            Ok(node) => {
+                let peer_id = node.peer_id();
+                self.fullnodes.insert(peer_id, node);
+                Ok(peer_id)
+            },
+            Err(e) => {
+                if let Err(cleanup_error) =
+                    delete_fullnode_resources(self.get_kube_client(), &self.kube_namespace, &names)
+                        .await
+                {
+                    info!("{}", cleanup_error);
+                }
+                Err(e.context(format!(
+                    "Failed to add fullnode {} to validator {}",
+                    names.stateful_set, validator_name
+                )))
+            },
+        }
+    }
+
+    /// Tear down a fullnode forge deployed, with [K8sSwarm::add_fullnode] or as a PFN. The
+    /// fullnodes of the validators' helm releases are left to helm.
+    pub async fn remove_fullnode(&mut self, peer_id: PeerId) -> Result<()> {
+        let node = self
+            .k8s_fullnode(&peer_id)
+            .ok_or_else(|| anyhow!("Invalid id: {}", peer_id))?;
+        if node
+            .stateful_set_name()
+            .starts_with(APTOS_NODE_HELM_RELEASE_NAME)
+        {
+            bail!(
+                "Fullnode {} belongs to the {} helm release",
+                node.name(),
+                APTOS_NODE_HELM_RELEASE_NAME
+            );
+        }
+        node.stop().await?;
+        delete_fullnode_resources(
+            self.get_kube_client(),
+            &self.kube_namespace,
+            &FullNodeResourceNames::new(node.stateful_set_name().to_string()),
+        )
+        .await?;
+        info!("Removed fullnode {}", node.name());
+        self.fullnodes.remove(&peer_id);
+        self.public_fullnodes.remove(&peer_id);
+        Ok(())
+    }
+
+    /// Upgrade exactly the selected validators, one at a time, and leave the others on their
+    /// version
+    pub async fn upgrade_subset(&mut self, to: &Version, selector: UpgradeSelector) -> Result<()> {
+        let validators: Vec<_> = self.validators().map(|v| v.peer_id()).collect();
+        let selected = selector.select(&validators)?;
+        info!(
+            "Upgrading {} of {} validators to {}",
+            selected.len(),
+            validators.len(),
+            to
+        );
+        for peer_id in selected {
+            self.upgrade_validator(peer_id, to).await?;
+        }
+        Ok(())
+    }
+
+    /// Roll the version out to the validators, batch_size of them at a time, see
+    /// [K8sSwarm::rolling_upgrade_with_options]
+    pub async fn rolling_upgrade(
+        &mut self,
+        to: &Version,
+        batch_size: usize,
+        wait_between: Duration,
+    ) -> Result<()> {
+        let upgrade = self
+            .rolling_upgrade_with_options(to, &RollingUpgradeOptions::new(batch_size, wait_between))
+            .await?;
+        info!(
+            "Rolled {} out in {:?}, over {} batches",
+            to,
+            upgrade.total,
+            upgrade.batches.len()
+        );
+        Ok(())
+    }
+
+    /// Upgrade the nodes batch by batch, the next batch only once the previous one is healthy
+    /// and caught up with the other validators. Stops at the first batch that doesn't recover,
+    /// leaving the later batches on their version.
+    pub async fn rolling_upgrade_with_options(
+        &mut self,
+        to: &Version,
+        options: &RollingUpgradeOptions,
+    ) -> Result<RollingUpgradeReport> {
+        if !self.versions.contains_key(to) {
+            bail!("Invalid version: {:?}", to);
+        }
+        if options.batch_size == 0 {
+            bail!("Rolling upgrade batch size must be positive");
+        }
+        let batches_of = |nodes: &HashMap<PeerId, K8sNode>, fullnodes: bool| {
+            let mut peer_ids: Vec<_> = nodes
+                .values()
+                .map(|node| (node.index(), node.peer_id()))
+                .collect();
+            peer_ids.sort();
+            peer_ids
+                .chunks(options.batch_size)
+                .map(|chunk| (fullnodes, chunk.iter().map(|(_, id)| *id).collect()))
+                .collect::<Vec<(bool, Vec<PeerId>)>>()
+        };
+        let validator_batches = batches_of(&self.validators, false);
+        let fullnode_batches = batches_of(&self.fullnodes, true);
+        let batches = match options.fullnodes {
+            FullNodeUpgradeOrder::Skip => validator_batches,
+            FullNodeUpgradeOrder::BeforeValidators => fullnode_batches
+                .into_iter()
+                .chain(validator_batches)
+                .collect(),
+            FullNodeUpgradeOrder::AfterValidators => validator_batches
+                .into_iter()
+                .chain(fullnode_batches)
+                .collect(),
+        };
+
+        let start = Instant::now();
+        let mut timings = vec![];
+        for (i, (fullnodes, batch)) in batches.iter().enumerate() {
+            if i > 0 && !options.wait_between.is_zero() {
+                tokio::time::sleep(options.wait_between).await;
+            }
+            let timing = self
+                .upgrade_batch(*fullnodes, batch, to, options)
+                .await
+                .with_context(|| {
+                    format!(
+                        "Rolling upgrade to {} stopped at batch {} of {}, the batches before it \
+                        were upgraded in {:?}",
+                        to,
+                        i,
+                        batches.len(),
+                        start.elapsed()
+                    )
+                })?;
+            info!(
+                "Upgraded batch {} of {} [{}] in {:?}",
+                i,
+                batches.len(),
+                timing.nodes.join(", "),
+                timing.upgrade + timing.catch_up
+            );
+            timings.push(timing);
+        }
+        Ok(RollingUpgradeReport {
+            version: to.clone(),
+            batches: timings,
+            total: start.elapsed(),
+        })
+    }
+
+    /// Upgrade the nodes of a batch at once, and wait until they are within the version lag of
+    /// the validators out of the batch
+    async fn upgrade_batch(
+        &mut self,
+        fullnodes: bool,
+        batch: &[PeerId],
+        to: &Version,
+        options: &RollingUpgradeOptions,
+    ) -> Result<UpgradeBatchTiming> {
+        let start = Instant::now();
+        let nodes = if fullnodes {
+            &mut self.fullnodes
+        } else {
+            &mut self.validators
+        };
+        let results = join_all(
+            nodes
+                .iter_mut()
+                .filter(|(id, _)| batch.contains(*id))
+                .map(|(_, node)| async move { (node.name().to_string(), node.upgrade(to).await) }),
+        )
+        .await;
+        let names: Vec<_> = results.iter().map(|(name, _)| name.clone()).collect();
+        let failures: Vec<_> = results
+            .iter()
+            .filter_map(|(name, result)| {
+                result.as_ref().err().map(|e| format!("{}: {:#}", name, e))
+            })
+            .collect();
+        if !failures.is_empty() {
+            bail!(
+                "Nodes failed to come back healthy on {}: {}",
+                to,
+                failures.join("; ")
+            );
+        }
+        let upgrade = start.elapsed();
+
+        let deadline = Instant::now() + options.catch_up_timeout;
+        loop {
+            let nodes = if fullnodes {
+                &self.fullnodes
+            } else {
+                &self.validators
+            };
+            let batch_versions = join_all(
+                batch
+                    .iter()
+                    .filter_map(|id| nodes.get(id))
+                    .map(
+                        |node| async move { (node.name().to_string(), ledger_version(node).await) },
+                    ),
+            )
+            .await;
+            // with every validator in the batch, the batch is its own reference
+            let reference_version = join_all(
+                self.validators
+                    .values()
+                    .filter(|node| fullnodes || !batch.contains(&node.peer_id()))
+                    .map(ledger_version),
+            )
+            .await
+            .into_iter()
+            .flatten()
+            .max()
+            .or_else(|| {
+                batch_versions
+                    .iter()
+                    .filter_map(|(_, version)| *version)
+                    .max()
+            })
+            .unwrap_or_default();
+            let lagging =
+                lagging_nodes(&batch_versions, reference_version, options.max_version_lag);
+            if lagging.is_empty() {
+                break;
+            }
+            if Instant::now() > deadline {
+                bail!(
+                    "Nodes did not catch up within {} versions of ledger version {} in {:?} \
+                    after the upgrade to {}: {:?}",
+                    options.max_version_lag,
+                    reference_version,
+                    options.catch_up_timeout,
+                    to,
+                    lagging
+                );
+            }
+            tokio::time::sleep(Duration::from_secs(5)).await;
+        }
+        Ok(UpgradeBatchTiming {
+            nodes: names,
+            upgrade,
+            catch_up: start.elapsed() - upgrade,
+        })
+    }
+
+    /// Deploy a new validator, register it on chain and wait for it to join, see add_validator.
+    /// `joined` is set once the validator asked to join the validator set.
+    async fn join_new_validator(
+        &self,
+        validator: &NewValidator,
+        era: &str,
+        version: &Version,
+        options: &NewValidatorOptions,
+        joined: &mut bool,
+    ) -> Result<K8sNode> {
+        let deadline = Instant::now() + options.join_timeout;
+        // validator 0 may well have been removed
+        let template_index = self
+            .validators
+            .values()
+            .map(|validator| validator.index())
+            .min()
+            .ok_or_else(|| format_err!("Swarm has no validator to copy"))?;
+        let (stateful_set, service) = install_validator(
+            Arc::new(K8sApi::<StatefulSet>::from_client(
+                self.get_kube_client(),
+                Some(self.kube_namespace.clone()),
+            )),
+            Arc::new(K8sApi::<ConfigMap>::from_client(
+                self.get_kube_client(),
+                Some(self.kube_namespace.clone()),
+            )),
+            Arc::new(K8sApi::<PersistentVolumeClaim>::from_client(
+                self.get_kube_client(),
+                Some(self.kube_namespace.clone()),
+            )),
+            Arc::new(K8sApi::<Service>::from_client(
+                self.get_kube_client(),
+                Some(self.kube_namespace.clone()),
+            )),
+            Arc::new(K8sApi::<Secret>::from_client(
+                self.get_kube_client(),
+                Some(self.kube_namespace.clone()),
+            )),
+            validator,
+            template_index,
+            era,
+            version,
+        )
+        .await?;
+
+        let services = [(validator.names.service.clone(), service)]
+            .into_iter()
+            .collect();
+        let mut node = get_k8s_node_from_stateful_set(
+            &self.backend_config,
+            &stateful_set,
+            &services,
+            0,
+            false,
+            false,
+            self.use_port_forward,
+            None,
+        );
+        node.peer_id = validator.peer_id();
+        // the node can't sync before it joined, so only wait for it to serve requests
+        node.start().await?;
+
+        let mut info = self.aptos_public_info();
+        validator
+            .register(
+                &mut info,
+                validator.network_address(&self.kube_namespace)?,
+                options.stake,
+            )
+            .await?;
+        *joined = true;
+
+        self.reconfigure_until(validator.peer_id(), node.name(), true, deadline)
+            .await?;
+
+        // healthy once it caught up with the others
+        node.wait_until_healthy(deadline).await?;
+        Ok(node)
+    }
+
+    /// End the epoch, so that pending validator set changes take effect right away, and wait
+    /// until the validator is in the validator set, or out of it
+    async fn reconfigure_until(
+        &self,
+        peer_id: PeerId,
+        name: &str,
+        in_validator_set: bool,
+        deadline: Instant,
+    ) -> Result<()> {
+        let operation = format!(
+            "validator {} to {} the validator set",
+            name,
+            if in_validator_set { "join" } else { "leave" }
+        );
+        self.reconfigure_until_validator_set(&operation, deadline, |validator_set| {
+            validator_set
+                .payload()
+                .any(|validator_info| *validator_info.account_address() == peer_id)
+                == in_validator_set
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// End the epoch, and wait until the validator set is as expected. Gives the validator set
+    /// and the epoch it is of.
+    async fn reconfigure_until_validator_set<F: Fn(&ValidatorSet) -> bool>(
+        &self,
+        operation: &str,
+        deadline: Instant,
+        done: F,
+    ) -> Result<(ValidatorSet, u64)> {
+        let info = self.aptos_public_info();
+        force_end_epoch(
+            info.client(),
+            info.transaction_factory(),
+            &self.root_account,
+        )
+        .await?;
+        loop {
+            let response = info
+                .client()
+                .get_account_resource_bcs::<ValidatorSet>(
+                    CORE_CODE_ADDRESS,
+                    "0x1::stake::ValidatorSet",
+                )
+                .await?;
+            let epoch = response.state().epoch;
+            let validator_set = response.into_inner();
+            if done(&validator_set) {
+                return Ok((validator_set, epoch));
+            }
+            if Instant::now() > deadline {
+                let on_chain: Vec<_> = validator_set
+                    .payload()
+                    .map(|validator_info| *validator_info.account_address())
+                    .collect();
+                return Err(K8sError::Timeout {
+                    operation: operation.to_string(),
+                    message: format!("the validator set is {:?}", on_chain),
+                }
+                .into());
+            }
+            tokio::time::sleep(Duration::from_secs(5)).await;
+        }
+    }
+
+    /// The steps of [Swarm::rotate_validator_keys], recording in `stage` how far they got
+    async fn rotate_keys(
+        &self,
+        validator: &K8sNode,
+        era: &str,
+        stage: &mut KeyRotationStage,
+    ) -> Result<KeyRotationResult> {
+        let deadline = Instant::now() + ROTATE_VALIDATOR_KEYS_TIMEOUT;
+        let names = ValidatorResourceNames::new(validator.index(), era);
+        let info = self.aptos_public_info();
+        let account = get_validator_account(
+            Arc::new(K8sApi::<Secret>::from_client(
+                self.get_kube_client(),
+                Some(self.kube_namespace.clone()),
+            )),
+            &names,
+            info.client(),
+        )
+        .await?;
+
+        let rotation =
+            store_rotated_identity(self.get_kube_client(), &self.kube_namespace, &names).await?;
+        *stage = KeyRotationStage::KeysStored;
+
+        let validator_set: ValidatorSet = info
+            .client()
+            .get_account_resource_bcs(CORE_CODE_ADDRESS, "0x1::stake::ValidatorSet")
+            .await?
+            .into_inner();
+        let validator_config = validator_set
+            .payload()
+            .find(|validator_info| *validator_info.account_address() == account.address())
+            .map(|validator_info| validator_info.config().clone())
+            .ok_or_else(|| {
+                format_err!("Validator {} is not in the validator set", validator.name())
+            })?;
+        rotation.submit(&account, &info, &validator_config).await?;
+        *stage = KeyRotationStage::Submitted;
+
+        // the validator can't vote in the epoch the new keys take effect with, until it runs
+        // with them
+        let (validator_set, epoch) = self
+            .reconfigure_until_validator_set(
+                &format!(
+                    "the new consensus key of validator {} to take effect",
+                    validator.name()
+                ),
+                deadline,
+                |validator_set| {
+                    validator_set.payload().any(|validator_info| {
+                        *validator_info.account_address() == account.address()
+                            && *validator_info.consensus_public_key()
+                                == rotation.consensus_public_key
+                    })
+                },
+            )
+            .await?;
+        *stage = KeyRotationStage::OnChain { epoch };
+
+        apply_rotated_identity(self.get_kube_client(), &self.kube_namespace, &names).await?;
+        validator.reset_safety_rules_storage().await?;
+        *stage = KeyRotationStage::NodeUpdated { epoch };
+
+        validator.wait_until_healthy(deadline).await?;
+        validator.verify_identity(&validator_set).await?;
+        Ok(KeyRotationResult {
+            peer_id: validator.peer_id(),
+            old_consensus_public_key: rotation.old_consensus_public_key,
+            new_consensus_public_key: rotation.consensus_public_key,
+            old_network_public_key: rotation.old_network_public_key,
+            new_network_public_key: rotation.network_public_key,
+            epoch,
+        })
+    }
+
+    /// Have the validator leave the validator set, if it is still in it
+    async fn remove_from_validator_set(&self, validator: &K8sNode, era: &str) -> Result<()> {
+        let names = ValidatorResourceNames::new(validator.index(), era);
+        let info = self.aptos_public_info();
+        let account = get_validator_account(
+            Arc::new(K8sApi::<Secret>::from_client(
+                self.get_kube_client(),
+                Some(self.kube_namespace.clone()),
+            )),
+            &names,
+            info.client(),
+        )
+        .await?;
+        let validator_set: ValidatorSet = info
+            .client()
+            .get_account_resource_bcs(CORE_CODE_ADDRESS, "0x1::stake::ValidatorSet")
+            .await?
+            .into_inner();
+        // e.g. a previous removal that failed to delete the resources
+        if validator_set
+            .payload()
+            .any(|validator_info| *validator_info.account_address() == account.address())
+        {
+            leave_validator_set(&account, &info).await?;
+        }
+        self.reconfigure_until(
+            validator.peer_id(),
+            validator.name(),
+            false,
+            Instant::now() + REMOVE_VALIDATOR_TIMEOUT,
+        )
+        .await
+    }
+
+    /// Fails if some nodes run another chain than the validators, e.g. when the validator and
+    /// fullnode images come with different chain ids in their genesis
+    pub async fn check_chain_ids(&self) -> Result<()> {
+        let validators = try_join_all(self.validators.values().map(chain_id)).await?;
+        let fullnodes = try_join_all(self.all_fullnodes().map(chain_id)).await?;
+        check_same_chain(&validators, &fullnodes)
+    }
+
+    /// The names of the validators that are not in the on-chain validator set, e.g. in a reused
+    /// namespace an earlier run removed validators from
+    pub async fn validators_not_on_chain(&self) -> Result<Vec<String>> {
+        let validator_set = get_validator_set(&self.aptos_public_info()).await?;
+        let on_chain: HashSet<PeerId> = validator_set
+            .payload()
+            .map(|validator_info| *validator_info.account_address())
+            .collect();
+        let mut names: Vec<_> = self
+            .validators
+            .values()
+            .filter(|validator| !on_chain.contains(&validator.peer_id()))
+            .map(|validator| validator.name().to_string())
+            .collect();
+        names.sort();
+        Ok(names)
+    }
+
+    /// The chain info with the REST API of one of the nodes, and the REST APIs of all the
+    /// validators to wait on
+    fn chain_info_of(&self, rest_api_url: String, inspection_service_url: String) -> ChainInfo {
+        ChainInfo::new(
+            self.root_account.clone(),
+            rest_api_url,
+            inspection_service_url,
+            self.chain_id,
+        )
+        .with_validator_rest_api_urls(
+            self.validators
+                .values()
+                .map(|v| v.rest_api_endpoint().to_string())
+                .collect(),
+        )
+        .with_rest_client_options(
+            self.validators
+                .values()
+                .next()
+                .unwrap()
+                .rest_client_options(),
+        )
+    }
+}
+
+/// The name, version and chain id of the node
+async fn chain_id(node: &K8sNode) -> Result<(String, Version, u8)> {
+    let state = node
+        .rest_client()
+        .get_ledger_information()
+        .await
+        .with_context(|| format!("Failed to get the chain id of {}", node.name()))?
+        .into_inner();
+    Ok((node.name().to_string(), node.version(), state.chain_id))
+}
+
+/// Fails with the nodes whose chain id differs from that of most of the validators
+fn check_same_chain(
+    validators: &[(String, Version, u8)],
+    fullnodes: &[(String, Version, u8)],
+) -> Result<()> {
+    let mut counts: BTreeMap<u8, usize> = BTreeMap::new();
+    for (_, _, chain_id) in validators {
+        *counts.entry(*chain_id).or_default() += 1;
+    }
+    let expected = match counts.into_iter().max_by_key(|(_, count)| *count) {
+        Some((chain_id, _)) => chain_id,
+        None => return Ok(()),
+    };
+    let mismatched: Vec<_> = validators
+        .iter()
+        .chain(fullnodes)
+        .filter(|(_, _, chain_id)| *chain_id != expected)
+        .map(|(name, version, chain_id)| {
+            format!("{} on version {} has chain id {}", name, version, chain_id)
+        })
+        .collect();
+    if !mismatched.is_empty() {
+        bail!(
+            "Nodes are incompatible with the validators on chain id {}: {}",
+            expected,
+            mismatched.join(", ")
+        );
+    }
+    Ok(())
+}
+
+/// The ledger version of the node, if it is reachable
+async fn ledger_version(node: &K8sNode) -> Option<u64> {
+    node.rest_client()
+        .get_ledger_information()
+        .await
+        .ok()
+        .map(|state| state.into_inner().version)
 }
 
 #[async_trait::async_trait]
 impl Swarm for K8sSwarm {
     async fn health_check(&self) -> Result<()> {
-        let nodes = self.validators.values().collect();
+        let nodes = self
+            .validators
+            .values()
+            .chain(self.public_fullnodes.values())
+            .collect();
         let unhealthy_nodes = nodes_healthcheck(nodes).await.unwrap();
         if !unhealthy_nodes.is_empty() {
             bail!("Unhealthy nodes: {:?}", unhealthy_nodes)
@@ -227,34 +1251,22 @@ impl Swarm for K8sSwarm {
         self.validators.get(&id).map(|v| v as &dyn Validator)
     }
 
-    /// TODO: this should really be a method on Node rather than Swarm
     async fn upgrade_validator(&mut self, id: PeerId, version: &Version) -> Result<()> {
         let validator = self
             .validators
             .get_mut(&id)
             .ok_or_else(|| anyhow!("Invalid id: {}", id))?;
-        let version = self
-            .versions
-            .get(version)
-            .cloned()
-            .ok_or_else(|| anyhow!("Invalid version: {:?}", version))?;
-        // stop the validator first so there is no race on the upgrade
-        validator.stop().await?;
-        // set the image tag of the StatefulSet spec while there are 0 replicas
-        set_stateful_set_image_tag(
-            validator.stateful_set_name().to_string(),
-            // the container name for the validator in its StatefulSet is "validator"
-            "validator".to_string(),
-            // extract the image tag from the "version"
-            version.to_string(),
-            self.kube_namespace.clone(),
-        )
-        .await?;
+        if !self.versions.contains_key(version) {
+            bail!("Invalid version: {:?}", version);
+        }
+        validator.upgrade(version).await
+    }
 
-        // To ensure that the validator is fully spun back up
-        // If port-forward is enabled, this ensures that the pod is back before attempting a port-forward
-        validator.start().await?;
-        Ok(())
+    async fn set_node_resources(&mut self, id: PeerId, resources: &NodeResources) -> Result<()> {
+        let node = self
+            .k8s_node_mut(&id)
+            .ok_or_else(|| anyhow!("Invalid id: {}", id))?;
+        node.set_resources(resources).await
     }
 
     fn full_nodes<'a>(&'a self) -> Box<dyn Iterator<Item = &'a dyn FullNode> + 'a> {
@@ -264,19 +1276,291 @@ impl Swarm for K8sSwarm {
             .map(|n| n as &'a dyn FullNode)
             .collect();
         full_nodes.sort_by_key(|n| n.index());
-        Box::new(full_nodes.into_iter())
+        Box::new(full_nodes.into_iter().chain(self.public_fullnodes()))
     }
 
     fn full_node(&self, id: PeerId) -> Option<&dyn FullNode> {
-        self.fullnodes.get(&id).map(|v| v as &dyn FullNode)
+        self.k8s_fullnode(&id).map(|v| v as &dyn FullNode)
     }
 
-    fn add_validator(&mut self, _version: &Version, _template: NodeConfig) -> Result<PeerId> {
-        todo!()
+    fn fullnodes_of(&self, validator: PeerId) -> Vec<&dyn FullNode> {
+        let Some(validator) = self.validators.get(&validator) else {
+            return vec![];
+        };
+        fullnodes_of_index(self.fullnodes.values(), validator.index())
+            .into_iter()
+            .map(|fullnode| fullnode as &dyn FullNode)
+            .collect()
     }
 
-    fn remove_validator(&mut self, _id: PeerId) -> Result<()> {
-        todo!()
+    async fn attach_fullnode(&mut self, validator: PeerId, version: &Version) -> Result<PeerId> {
+        self.add_fullnode(validator, FullNodeConfig::new(version.clone()))
+            .await
+    }
+
+    fn infrastructure_events(&self) -> Vec<InfrastructureEvent> {
+        self.event_watcher
+            .as_ref()
+            .map_or_else(Vec::new, |event_watcher| event_watcher.events())
+    }
+
+    fn public_fullnodes<'a>(&'a self) -> Box<dyn Iterator<Item = &'a dyn FullNode> + 'a> {
+        let mut public_fullnodes: Vec<_> = self
+            .public_fullnodes
+            .values()
+            .map(|n| n as &'a dyn FullNode)
+            .collect();
+        public_fullnodes.sort_by_key(|n| n.index());
+        Box::new(public_fullnodes.into_iter())
+    }
+
+    async fn deploy_public_fullnodes(
+        &mut self,
+        count: usize,
+        version: &Version,
+        config: NodeConfig,
+    ) -> Result<Vec<PeerId>> {
+        K8sSwarm::deploy_public_fullnodes(self, count, version, config).await
+    }
+
+    async fn add_validator(
+        &mut self,
+        version: &Version,
+        options: NewValidatorOptions,
+    ) -> Result<PeerId> {
+        if !self.versions.contains_key(version) {
+            bail!("Invalid version: {:?}", version);
+        }
+        let era = self.era.clone().ok_or_else(|| {
+            format_err!("Adding a validator requires acquiring the current chain era")
+        })?;
+        let index = self
+            .validators
+            .values()
+            .map(|validator| validator.index() + 1)
+            .max()
+            .unwrap_or_default();
+        let validator = NewValidator::generate(index, &era)?;
+        info!(
+            "Adding validator {} as {}",
+            validator.peer_id(),
+            validator.names.stateful_set
+        );
+
+        let mut joined = false;
+        match self
+            .join_new_validator(&validator, &era, version, &options, &mut joined)
+            .await
+        {
+            Ok(node) => {
+                self.watch_events_of(&node);
+                self.validators.insert(validator.peer_id(), node);
+                Ok(validator.peer_id())
+            },
+            Err(e) => {
+                // the swarm is left as it was, but for the funded account
+                if joined {
+                    if let Err(leave_error) = validator.leave(&self.aptos_public_info()).await {
+                        info!(
+                            "Failed to have validator {} leave the validator set: {}",
+                            validator.peer_id(),
+                            leave_error
+                        );
+                    }
+                }
+                if let Err(cleanup_error) = delete_validator_resources(
+                    self.get_kube_client(),
+                    &self.kube_namespace,
+                    &validator.names,
+                )
+                .await
+                {
+                    info!("{}", cleanup_error);
+                }
+                Err(e.context(format!(
+                    "Failed to add validator {}",
+                    validator.names.stateful_set
+                )))
+            },
+        }
+    }
+
+    async fn remove_validator(&mut self, id: PeerId) -> Result<()> {
+        let era = self.era.clone().ok_or_else(|| {
+            format_err!("Removing a validator requires acquiring the current chain era")
+        })?;
+        let validator = self
+            .validators
+            .get(&id)
+            .ok_or_else(|| anyhow!("Invalid id: {}", id))?;
+        let name = validator.name().to_string();
+        let index = validator.index();
+        info!("Removing validator {} ({})", id, name);
+
+        // the resources stay until the validator is out of the validator set, so that a failed
+        // removal leaves a working validator behind
+        self.remove_from_validator_set(validator, &era)
+            .await
+            .with_context(|| {
+                format!(
+                    "Validator {} did not leave the validator set, its resources are left intact",
+                    name
+                )
+            })?;
+        validator.stop().await.with_context(|| {
+            format!(
+                "Validator {} left the validator set, but failed to scale it down",
+                name
+            )
+        })?;
+        delete_validator_resources(
+            self.get_kube_client(),
+            &self.kube_namespace,
+            &ValidatorResourceNames::new(index, &era),
+        )
+        .await
+        .with_context(|| {
+            format!(
+                "Validator {} left the validator set, but failed to delete its resources",
+                name
+            )
+        })?;
+        self.validators.remove(&id);
+        self.faulty_validators.remove(&id);
+
+        // its VFN, the HAProxy in front of both, and the fullnodes forge attached to it go along
+        let attached: Vec<PeerId> = self
+            .fullnodes
+            .values()
+            .filter(|fullnode| fullnode.index() == index && !fullnode.is_public_fullnode())
+            .map(|fullnode| fullnode.peer_id())
+            .collect();
+        for peer_id in &attached {
+            let fullnode = &self.fullnodes[peer_id];
+            if fullnode.is_validator_fullnode() {
+                continue;
+            }
+            delete_fullnode_resources(
+                self.get_kube_client(),
+                &self.kube_namespace,
+                &FullNodeResourceNames::new(fullnode.stateful_set_name().to_string()),
+            )
+            .await
+            .with_context(|| {
+                format!(
+                    "Validator {} was removed, but its fullnode {} could not be",
+                    name,
+                    fullnode.name()
+                )
+            })?;
+        }
+        delete_validator_companion_resources(
+            self.get_kube_client(),
+            &self.kube_namespace,
+            &ValidatorCompanionResourceNames::new(index, &era),
+        )
+        .await
+        .with_context(|| {
+            format!(
+                "Validator {} was removed, but its VFN and HAProxy could not be",
+                name
+            )
+        })?;
+        for peer_id in attached {
+            self.fullnodes.remove(&peer_id);
+        }
+        info!("Removed validator {}", name);
+        Ok(())
+    }
+
+    async fn deploy_faulty_validator(&mut self, behavior: FaultyBehavior) -> Result<PeerId> {
+        let mut validators: Vec<_> = self.validators.values().collect();
+        validators.sort_by_key(|validator| validator.index());
+        let validators: Vec<_> = validators
+            .iter()
+            .map(|validator| validator.peer_id())
+            .collect();
+        let faulty: Vec<_> = self.faulty_validators.keys().copied().collect();
+        let id = next_faulty_validator(&validators, &faulty)?;
+        let validator = self
+            .validators
+            .get_mut(&id)
+            .ok_or_else(|| anyhow!("Invalid id: {}", id))?;
+        // any image can misbehave, it need not be one of the swarm's versions
+        if let FaultyBehavior::Image(version) = &behavior {
+            validator.upgrade(version).await?;
+        }
+        set_faulty_failpoints(&*validator, &behavior).await?;
+        self.faulty_validators.insert(id, behavior);
+        Ok(id)
+    }
+
+    fn faulty_validators(&self) -> Vec<PeerId> {
+        self.faulty_validators.keys().copied().collect()
+    }
+
+    async fn rotate_validator_keys(&mut self, id: PeerId) -> Result<KeyRotationResult> {
+        let era = self.era.clone().ok_or_else(|| {
+            format_err!("Rotating the keys of a validator requires acquiring the current chain era")
+        })?;
+        let validator = self
+            .validators
+            .get(&id)
+            .ok_or_else(|| anyhow!("Invalid id: {}", id))?;
+        info!("Rotating the keys of validator {}", validator.name());
+        let mut stage = KeyRotationStage::NotStarted;
+        let result = self
+            .rotate_keys(validator, &era, &mut stage)
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to rotate the keys of validator {}, {}",
+                    validator.name(),
+                    stage
+                )
+            })?;
+        info!(
+            "Rotated the keys of validator {}, new as of epoch {}",
+            validator.name(),
+            result.epoch
+        );
+        Ok(result)
+    }
+
+    async fn validator_owner_account(&self, id: PeerId) -> Result<LocalAccount> {
+        let era = self.era.clone().ok_or_else(|| {
+            format_err!("Acting as a validator's owner requires acquiring the current chain era")
+        })?;
+        let validator = self
+            .validators
+            .get(&id)
+            .ok_or_else(|| anyhow!("Invalid id: {}", id))?;
+        get_validator_account(
+            Arc::new(K8sApi::<Secret>::from_client(
+                self.get_kube_client(),
+                Some(self.kube_namespace.clone()),
+            )),
+            &ValidatorResourceNames::new(validator.index(), &era),
+            &self.chain_info().rest_client(),
+        )
+        .await
+    }
+
+    async fn set_validator_stake(
+        &mut self,
+        id: PeerId,
+        amount: u64,
+        wait_for_epoch: bool,
+    ) -> Result<()> {
+        let account = self.validator_owner_account(id).await?;
+        set_stake(
+            &mut self.aptos_public_info(),
+            &account,
+            amount,
+            wait_for_epoch,
+        )
+        .await
+        .with_context(|| format!("Failed to set the stake of validator {}", id))
     }
 
     fn add_validator_full_node(
@@ -293,12 +1577,14 @@ impl Swarm for K8sSwarm {
         version: &Version,
         config: OverrideNodeConfig,
     ) -> Result<PeerId> {
-        self.install_public_fullnode_resources(version, &config)
-            .await
-            .map(|(peer_id, node)| {
-                self.fullnodes.insert(peer_id, node);
-                peer_id
-            })
+        let (peer_id, node) = self
+            .install_public_fullnode_resources(version, &config)
+            .await?;
+        self.watch_events_of(&node);
+        // if port-forward is enabled, this is when the node gets its ephemeral port
+        node.start().await?;
+        self.public_fullnodes.insert(peer_id, node);
+        Ok(peer_id)
     }
 
     fn remove_full_node(&mut self, _id: PeerId) -> Result<()> {
@@ -312,12 +1598,7 @@ impl Swarm for K8sSwarm {
     fn chain_info(&self) -> ChainInfo {
         let rest_api_url = self.get_rest_api_url(0);
         let inspection_service_url = self.get_inspection_service_url(0);
-        ChainInfo::new(
-            self.root_account.clone(),
-            rest_api_url,
-            inspection_service_url,
-            self.chain_id,
-        )
+        self.chain_info_of(rest_api_url, inspection_service_url)
     }
 
     // returns a kubectl logs command to retrieve the logs manually
@@ -326,12 +1607,65 @@ impl Swarm for K8sSwarm {
         "See fgi output for more information.".to_string()
     }
 
+    async fn collect_artifacts(
+        &self,
+        dir: &Path,
+        since: Option<Duration>,
+    ) -> Result<ArtifactManifest> {
+        let collections = self
+            .validators
+            .values()
+            .chain(self.all_fullnodes())
+            .map(|node| {
+                (
+                    NodeArtifacts::new(node.name(), node.peer_id()),
+                    node.collect_artifacts(dir, since).boxed(),
+                )
+            })
+            .collect();
+        collect_with_timeout(dir, collections, ARTIFACT_COLLECTION_TIMEOUT).await
+    }
+
+    async fn snapshot_db(
+        &self,
+        id: PeerId,
+        dest: &Path,
+        options: &DbSnapshotOptions,
+    ) -> Result<PathBuf> {
+        let node = self
+            .k8s_node(&id)
+            .ok_or_else(|| anyhow!("No node with peer id {}", id))?;
+        node.snapshot_db_with_options(dest, options).await
+    }
+
     async fn inject_chaos(&mut self, chaos: SwarmChaos) -> Result<()> {
+        if let Some(injected) = self
+            .chaoses
+            .iter()
+            .find(|injected| conflicts(injected, &chaos))
+        {
+            bail!(
+                "Chaos {:?} conflicts with the injected {:?}",
+                chaos,
+                injected
+            );
+        }
         self.inject_swarm_chaos(&chaos)?;
-        self.chaoses.insert(chaos);
+        // from when the stress is in place, not to excuse OOMKills from before it
+        self.track_memory_stress(&chaos, true);
+        self.chaoses.insert(chaos.clone());
+        self.update_clock_skews();
         self.chaos_experiment_ops
             .ensure_chaos_experiments_active()
             .await?;
+        // a partition the wrong way round would pass for a working one
+        if let SwarmChaos::GroupPartition(group_partition) = &chaos {
+            if let Err(e) = self.verify_group_partition(group_partition).await {
+                self.chaoses.remove(&chaos);
+                self.remove_swarm_chaos(&chaos)?;
+                return Err(e);
+            }
+        }
 
         Ok(())
     }
@@ -343,12 +1677,29 @@ impl Swarm for K8sSwarm {
 
         if self.chaoses.remove(&chaos) {
             self.remove_swarm_chaos(&chaos)?;
+            self.track_memory_stress(&chaos, false);
         } else {
             bail!("Chaos {:?} not found", chaos);
         }
+        self.update_clock_skews();
         Ok(())
     }
 
+    async fn update_chaos(&mut self, chaos: SwarmChaos) -> Result<()> {
+        let injected = self
+            .chaoses
+            .iter()
+            .find(|injected| updates_in_place(injected, &chaos))
+            .cloned()
+            .ok_or_else(|| format_err!("No chaos to update to {:?} is injected", chaos))?;
+        self.update_swarm_chaos(&injected, &chaos)?;
+        self.chaoses.remove(&injected);
+        self.chaoses.insert(chaos);
+        self.chaos_experiment_ops
+            .ensure_chaos_experiments_active()
+            .await
+    }
+
     async fn remove_all_chaos(&mut self) -> Result<()> {
         self.chaos_experiment_ops
             .ensure_chaos_experiments_active()
@@ -357,14 +1708,31 @@ impl Swarm for K8sSwarm {
         // try removing all existing chaoses
         for chaos in self.chaoses.clone() {
             self.remove_swarm_chaos(&chaos)?;
+            self.track_memory_stress(&chaos, false);
         }
         // force remove all others
-        delete_all_chaos(&self.kube_namespace)?;
+        delete_all_chaos(&self.backend_config, &self.kube_namespace)?;
 
         self.chaoses.clear();
+        self.update_clock_skews();
         Ok(())
     }
 
+    async fn restarts(&self) -> Result<Vec<NodeRestarts>> {
+        let mut restarts = try_join_all(self.validators.values().chain(self.all_fullnodes()).map(
+            |node| async move {
+                let current = node.restart_count().await?;
+                Ok::<_, anyhow::Error>(node.restarts_since(
+                    self.restart_baseline.get(&node.pod_name()),
+                    current.as_ref(),
+                ))
+            },
+        ))
+        .await?;
+        restarts.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(restarts)
+    }
+
     async fn ensure_no_validator_restart(&self) -> Result<()> {
         for validator in &self.validators {
             check_for_container_restart(
@@ -379,11 +1747,11 @@ impl Swarm for K8sSwarm {
     }
 
     async fn ensure_no_fullnode_restart(&self) -> Result<()> {
-        for fullnode in &self.fullnodes {
+        for fullnode in self.all_fullnodes() {
             check_for_container_restart(
                 &self.kube_client,
                 &self.kube_namespace.clone(),
-                fullnode.1.stateful_set_name(),
+                fullnode.stateful_set_name(),
             )
             .await?;
         }
@@ -402,7 +1770,7 @@ impl Swarm for K8sSwarm {
             labels_map.insert("namespace".to_string(), self.kube_namespace.clone());
             return query_with_metadata(c, query, time, timeout, &labels_map).await;
         }
-        bail!("No prom client");
+        Err(PrometheusUnavailable.into())
     }
 
     async fn query_range_metrics(
@@ -426,18 +1794,13 @@ impl Swarm for K8sSwarm {
             )
             .await;
         }
-        bail!("No prom client");
+        Err(PrometheusUnavailable.into())
     }
 
     fn chain_info_for_node(&mut self, idx: usize) -> ChainInfo {
         let rest_api_url = self.get_rest_api_url(idx);
         let inspection_service_url = self.get_inspection_service_url(idx);
-        ChainInfo::new(
-            self.root_account.clone(),
-            rest_api_url,
-            inspection_service_url,
-            self.chain_id,
-        )
+        self.chain_info_of(rest_api_url, inspection_service_url)
     }
 
     fn get_default_pfn_node_config(&self) -> NodeConfig {
@@ -464,6 +1827,45 @@ async fn list_stateful_sets(client: K8sClient, kube_namespace: &str) -> Result<V
     Ok(stateful_sets)
 }
 
+/// The Services in the namespace, by name
+async fn list_services(
+    client: K8sClient,
+    kube_namespace: &str,
+) -> Result<HashMap<String, Service>> {
+    let service_api: Api<Service> = Api::namespaced(client, kube_namespace);
+    let services = service_api.list(&ListParams::default()).await?.items;
+    Ok(services
+        .into_iter()
+        .filter_map(|service| Some((service.metadata.name.clone()?, service)))
+        .collect())
+}
+
+/// The port of the REST API on the given Service, i.e. the port named "api" by the aptos-node helm
+/// chart. Falls back to the given default if the Service or port does not exist.
+fn rest_api_service_port(
+    services: &HashMap<String, Service>,
+    service_name: &str,
+    default_port: u32,
+) -> u32 {
+    let port = services
+        .get(service_name)
+        .and_then(|service| service.spec.as_ref())
+        .and_then(|spec| spec.ports.as_ref())
+        .and_then(|ports| {
+            ports
+                .iter()
+                .find(|port| port.name.as_deref() == Some(REST_API_PORT_NAME))
+        })
+        .map(|port| port.port as u32);
+    port.unwrap_or_else(|| {
+        info!(
+            "Service {} has no {} port, assuming the REST API is on port {}",
+            service_name, REST_API_PORT_NAME, default_port
+        );
+        default_port
+    })
+}
+
 fn stateful_set_name_matches(sts: &StatefulSet, suffix: &str) -> bool {
     if let Some(s) = sts.metadata.name.as_ref() {
         s.contains(suffix)
@@ -472,29 +1874,73 @@ fn stateful_set_name_matches(sts: &StatefulSet, suffix: &str) -> bool {
     }
 }
 
+/// The Service of a StatefulSet of the aptos-node chart, none for other StatefulSets, like those
+/// of the fullnodes forge deploys itself
 fn parse_service_name_from_stateful_set_name(
     stateful_set_name: &str,
     enable_haproxy: bool,
-) -> String {
+) -> Option<String> {
     let re = Regex::new(r"(aptos-node-\d+)-(validator|fullnode)").unwrap();
-    let cap = re.captures(stateful_set_name).unwrap();
+    let cap = re.captures(stateful_set_name)?;
     let service_base_name = format!("{}-{}", &cap[1], &cap[2]);
-    if enable_haproxy {
+    let service_name = if enable_haproxy {
         format!("{}-{}", &service_base_name, HAPROXY_SERVICE_SUFFIX)
     } else {
         service_base_name
-    }
+    };
+    Some(service_name)
+}
+
+/// One K8sNode for each replica of the StatefulSet, since some deployments run several fullnodes
+/// as replicas of a single StatefulSet
+fn get_k8s_nodes_from_stateful_set(
+    backend_config: &Arc<K8sBackendConfig>,
+    sts: &StatefulSet,
+    services: &HashMap<String, Service>,
+    enable_haproxy: bool,
+    use_port_forward: bool,
+    rest_api_tls: Option<RestApiTls>,
+) -> Vec<K8sNode> {
+    let replicas = sts
+        .spec
+        .as_ref()
+        .and_then(|spec| spec.replicas)
+        .unwrap_or(1)
+        .max(1) as u32;
+    (0..replicas)
+        .map(|replica_index| {
+            get_k8s_node_from_stateful_set(
+                backend_config,
+                sts,
+                services,
+                replica_index,
+                replicas > 1,
+                enable_haproxy,
+                use_port_forward,
+                rest_api_tls.clone(),
+            )
+        })
+        .collect()
 }
 
 fn get_k8s_node_from_stateful_set(
+    backend_config: &Arc<K8sBackendConfig>,
     sts: &StatefulSet,
+    services: &HashMap<String, Service>,
+    replica_index: u32,
+    shares_stateful_set: bool,
     enable_haproxy: bool,
     use_port_forward: bool,
+    rest_api_tls: Option<RestApiTls>,
 ) -> K8sNode {
     let stateful_set_name = sts.metadata.name.as_ref().unwrap();
+    // the Services of the StatefulSets forge deploys itself are named after them
+    let service_name_of = |enable_haproxy: bool| {
+        parse_service_name_from_stateful_set_name(stateful_set_name, enable_haproxy)
+            .unwrap_or_else(|| stateful_set_name.clone())
+    };
     // If HAProxy is enabled, use its Service name. Otherwise the Service name matches the StatefulSet name
-    let mut service_name =
-        parse_service_name_from_stateful_set_name(stateful_set_name, enable_haproxy);
+    let mut service_name = service_name_of(enable_haproxy);
 
     // the full service name includes the namespace
     let namespace = sts.metadata.namespace.as_ref().unwrap();
@@ -502,7 +1948,15 @@ fn get_k8s_node_from_stateful_set(
     // if we're not using port-forward and expecting to hit the service directly, we should use the full service name
     // since the test runner may be in a separate namespace
     if !use_port_forward {
-        service_name = format!("{}.{}.svc", &service_name, &namespace);
+        service_name = match sts.spec.as_ref().map(|spec| &spec.service_name) {
+            // the Service balances across the replicas, so reach the replica's pod through the
+            // DNS name the StatefulSet's governing Service gives it
+            Some(governing_service_name) if shares_stateful_set => format!(
+                "{}-{}.{}.{}.svc",
+                stateful_set_name, replica_index, governing_service_name, &namespace
+            ),
+            _ => format!("{}.{}.svc", &service_name, &namespace),
+        };
     }
 
     // Append the cluster name if its a multi-cluster deployment
@@ -517,16 +1971,30 @@ fn get_k8s_node_from_stateful_set(
         service_name
     };
 
-    // If HAProxy is enabled, use the port on its Service. Otherwise use the port on the validator Service
-    let mut rest_api_port = if enable_haproxy {
+    // the charts may expose the REST API on other ports than the defaults
+    let direct_service_name = service_name_of(false);
+    let rest_api_service_port =
+        rest_api_service_port(services, &direct_service_name, REST_API_SERVICE_PORT);
+    let rest_api_haproxy_service_port = if enable_haproxy {
+        let haproxy_service_name = service_name_of(true);
+        rest_api_service_port(
+            services,
+            &haproxy_service_name,
+            REST_API_HAPROXY_SERVICE_PORT,
+        )
+    } else {
         REST_API_HAPROXY_SERVICE_PORT
+    };
+
+    // If HAProxy is enabled, use the port on its Service. Otherwise use the port on the validator Service
+    // In the case of port-forward, these ports are changed to the local ones at runtime
+    let rest_api_port = if enable_haproxy {
+        rest_api_haproxy_service_port
     } else {
-        REST_API_SERVICE_PORT
+        rest_api_service_port
     };
 
-    if use_port_forward {
-        rest_api_port = get_free_port();
-    }
+    let inspection_service_port = NODE_METRIC_PORT;
     let index = parse_node_index(stateful_set_name).expect("error to parse node index");
     let node_type = parse_node_type(stateful_set_name);
 
@@ -535,59 +2003,201 @@ fn get_k8s_node_from_stateful_set(
         .expect("Failed to get StatefulSet image")
         .tag;
 
+    let name = if !stateful_set_name.starts_with(APTOS_NODE_HELM_RELEASE_NAME) {
+        // the fullnodes forge deployed are named after their StatefulSet, as when they were
+        // installed, since a validator may have several
+        stateful_set_name.clone()
+    } else if shares_stateful_set {
+        format!("{}-{}-{}", &node_type, index, replica_index)
+    } else {
+        format!("{}-{}", &node_type, index)
+    };
+
     K8sNode {
-        name: format!("{}-{}", &node_type, index),
+        name,
         stateful_set_name: stateful_set_name.clone(),
-        // TODO: fetch this from running node
+        replica_index,
+        shares_stateful_set,
+        // replaced with the node's identity once it runs, see collect_running_nodes
         peer_id: PeerId::random(),
         index,
         service_name,
         rest_api_port: AtomicU32::new(rest_api_port),
+        rest_api_service_port,
+        rest_api_haproxy_service_port,
+        inspection_service_port: AtomicU32::new(inspection_service_port),
         version: Version::new(0, image_tag),
+        resources: stateful_set_resources(sts),
         namespace: namespace.to_string(),
+        backend_config: backend_config.clone(),
         haproxy_enabled: enable_haproxy,
         port_forward_enabled: use_port_forward,
+        rest_api_tls,
+        ledger_staleness_threshold: Some(DEFAULT_LEDGER_STALENESS_THRESHOLD),
+        clock_skew: Duration::ZERO,
+        memory_stress: None,
+        restarts_by_test: AtomicU32::new(0),
+        port_forwards: Mutex::new(Vec::new()),
+        metrics_port_forward: Mutex::new(None),
+        config: Mutex::new(None),
     }
 }
 
 pub(crate) async fn get_validators(
+    backend_config: &Arc<K8sBackendConfig>,
     client: K8sClient,
     kube_namespace: &str,
     use_port_forward: bool,
     enable_haproxy: bool,
+    rest_api_tls: Option<RestApiTls>,
 ) -> Result<HashMap<PeerId, K8sNode>> {
-    let stateful_sets = list_stateful_sets(client, kube_namespace).await?;
+    let stateful_sets = list_stateful_sets(client.clone(), kube_namespace).await?;
+    let services = list_services(client, kube_namespace).await?;
     let validators = stateful_sets
         .into_iter()
         .filter(|sts| stateful_set_name_matches(sts, "validator"))
-        .map(|sts| {
-            let node = get_k8s_node_from_stateful_set(&sts, enable_haproxy, use_port_forward);
-            (node.peer_id(), node)
+        .flat_map(|sts| {
+            get_k8s_nodes_from_stateful_set(
+                backend_config,
+                &sts,
+                &services,
+                enable_haproxy,
+                use_port_forward,
+                rest_api_tls.clone(),
+            )
         })
+        .map(|node| (node.peer_id(), node))
         .collect::<HashMap<_, _>>();
 
     Ok(validators)
 }
 
 pub(crate) async fn get_fullnodes(
+    backend_config: &Arc<K8sBackendConfig>,
     client: K8sClient,
     kube_namespace: &str,
     use_port_forward: bool,
     enable_haproxy: bool,
+    rest_api_tls: Option<RestApiTls>,
 ) -> Result<HashMap<PeerId, K8sNode>> {
-    let stateful_sets = list_stateful_sets(client, kube_namespace).await?;
+    let stateful_sets = list_stateful_sets(client.clone(), kube_namespace).await?;
+    let services = list_services(client, kube_namespace).await?;
     let fullnodes = stateful_sets
         .into_iter()
         .filter(|sts| stateful_set_name_matches(sts, "fullnode"))
-        .map(|sts| {
-            let node = get_k8s_node_from_stateful_set(&sts, enable_haproxy, use_port_forward);
-            (node.peer_id(), node)
+        .flat_map(|sts| {
+            get_k8s_nodes_from_stateful_set(
+                backend_config,
+                &sts,
+                &services,
+                enable_haproxy,
+                use_port_forward,
+                rest_api_tls.clone(),
+            )
         })
+        .map(|node| (node.peer_id(), node))
         .collect::<HashMap<_, _>>();
 
     Ok(fullnodes)
 }
 
+/// How the nodes of a namespace were deployed, as told by its resources, to reuse them without
+/// redeploying
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DiscoveredDeployment {
+    pub era: Option<String>,
+    pub haproxy_enabled: bool,
+}
+
+pub(crate) async fn discover_deployment(
+    client: K8sClient,
+    kube_namespace: &str,
+) -> Result<DiscoveredDeployment> {
+    let stateful_sets = list_stateful_sets(client.clone(), kube_namespace).await?;
+    let services = list_services(client, kube_namespace).await?;
+    Ok(DiscoveredDeployment {
+        era: stateful_sets
+            .iter()
+            .filter(|sts| stateful_set_name_matches(sts, "validator"))
+            .find_map(stateful_set_era),
+        haproxy_enabled: services
+            .keys()
+            .any(|name| name.ends_with(VALIDATOR_HAPROXY_SERVICE_SUFFIX)),
+    })
+}
+
+/// The era of the genesis Secret the StatefulSet mounts, e.g. forge42 of
+/// aptos-node-0-genesis-eforge42
+fn stateful_set_era(sts: &StatefulSet) -> Option<String> {
+    let (_, era) = genesis_secret_name(sts)?.rsplit_once("-genesis-e")?;
+    Some(era.to_string())
+}
+
+/// The name of the genesis Secret the StatefulSet mounts, which holds the identity of the
+/// validator of its index
+pub(crate) fn genesis_secret_name(sts: &StatefulSet) -> Option<&str> {
+    sts.spec
+        .as_ref()?
+        .template
+        .spec
+        .as_ref()?
+        .volumes
+        .as_ref()?
+        .iter()
+        .find_map(|volume| {
+            let secret_name = volume.secret.as_ref()?.secret_name.as_deref()?;
+            secret_name.contains("-genesis-e").then_some(secret_name)
+        })
+}
+
+/// Why the running nodes don't make the swarm a test asked for, with the expected version of
+/// each validator by index and of the fullnodes
+pub(crate) fn reuse_mismatches(
+    validators: &HashMap<PeerId, K8sNode>,
+    fullnodes: &HashMap<PeerId, K8sNode>,
+    num_validators: usize,
+    num_fullnodes: usize,
+    validator_version: impl Fn(usize) -> Version,
+    fullnode_version: &Version,
+) -> Vec<String> {
+    let mut mismatches = vec![];
+    if validators.len() != num_validators {
+        mismatches.push(format!(
+            "{} validators run, {} were requested",
+            validators.len(),
+            num_validators
+        ));
+    }
+    if fullnodes.len() != num_fullnodes {
+        mismatches.push(format!(
+            "{} fullnodes run, {} were requested",
+            fullnodes.len(),
+            num_fullnodes
+        ));
+    }
+    let mut nodes: Vec<_> = validators
+        .values()
+        .map(|node| (node, validator_version(node.index())))
+        .chain(
+            fullnodes
+                .values()
+                .map(|node| (node, fullnode_version.clone())),
+        )
+        .collect();
+    nodes.sort_by(|(a, _), (b, _)| a.name().cmp(b.name()));
+    for (node, expected) in nodes {
+        if node.version().to_string() != expected.to_string() {
+            mismatches.push(format!(
+                "{} runs {}, {} was requested",
+                node.name(),
+                node.version(),
+                expected
+            ));
+        }
+    }
+    mismatches
+}
+
 /// Given a string like the StatefulSet name or Service name, parse the node type,
 /// whether it's a validator or fullnode
 fn parse_node_type(s: &str) -> String {
@@ -596,19 +2206,58 @@ fn parse_node_type(s: &str) -> String {
     cap[1].to_string()
 }
 
+// what the names of the node's objects contain, e.g. of its pod and volume claims. The replicas
+// of a StatefulSet are told apart by their pods.
+fn event_object_prefix(node: &K8sNode) -> String {
+    if node.shares_stateful_set {
+        node.pod_name()
+    } else {
+        node.stateful_set_name().to_string()
+    }
+}
+
 // gets the node index based on its associated statefulset name
 // e.g. aptos-node-<idx>-validator
 // e.g. aptos-node-<idx>-fullnode-e<era>
+// e.g. fullnode-<validator idx>-<peer id> or public-fullnode-<idx>-<peer id>, deployed by forge
 fn parse_node_index(s: &str) -> Result<usize> {
     // first get rid of the prefixes
-    let v = s.split("aptos-node-").collect::<Vec<&str>>();
-    if v.len() < 2 {
-        return Err(format_err!("Failed to parse {:?} node id format", s));
-    }
+    let rest = match s.split_once("aptos-node-") {
+        Some((_, rest)) => Some(rest),
+        None => s
+            .strip_prefix("public-fullnode-")
+            .or_else(|| s.strip_prefix("fullnode-")),
+    };
     // then get rid of the node type suffix
-    let v = v[1].split('-').collect::<Vec<&str>>();
-    let idx: usize = v[0].parse().unwrap();
-    Ok(idx)
+    rest.and_then(|rest| rest.split('-').next())
+        .and_then(|idx| idx.parse().ok())
+        .ok_or_else(|| format_err!("Failed to parse {:?} node id format", s))
+}
+
+/// The fullnodes of the validator with the index, those of the helm release first, then those
+/// forge attached, each by name
+fn fullnodes_of_index<'a>(
+    fullnodes: impl Iterator<Item = &'a K8sNode>,
+    index: usize,
+) -> Vec<&'a K8sNode> {
+    let mut fullnodes: Vec<_> = fullnodes
+        .filter(|fullnode| fullnode.index() == index)
+        .collect();
+    fullnodes.sort_by(|a, b| {
+        (!a.is_validator_fullnode(), a.name()).cmp(&(!b.is_validator_fullnode(), b.name()))
+    });
+    fullnodes
+}
+
+/// The `count` nodes using the most CPU, busiest first
+fn busiest_nodes(usage: &HashMap<PeerId, PodResourceUsage>, count: usize) -> Vec<PeerId> {
+    let mut nodes: Vec<_> = usage.iter().collect();
+    nodes.sort_by(|(_, a), (_, b)| b.cpu_millicores().total_cmp(&a.cpu_millicores()));
+    nodes
+        .into_iter()
+        .take(count)
+        .map(|(peer_id, _)| *peer_id)
+        .collect()
 }
 
 fn load_root_key(root_key_bytes: &[u8]) -> Ed25519PrivateKey {
@@ -649,7 +2298,31 @@ pub async fn nodes_healthcheck(nodes: Vec<&K8sNode>) -> Result<Vec<String>> {
         })
         .await;
         if check.is_err() {
-            unhealthy_nodes.push(node_name);
+            // surface the tail of the node logs to make the failure easier to diagnose
+            match node.get_pod_logs(None, Some(100), false, None).await {
+                Ok(logs) => info!("Last logs of unhealthy node {}:\n{}", node_name, logs),
+                Err(err) => info!(
+                    "Failed to get logs of unhealthy node {}: {}",
+                    node_name, err
+                ),
+            }
+            match node.events().await {
+                Ok(events) => info!(
+                    "Events of unhealthy node {}:\n{}",
+                    node_name,
+                    describe_events(&events)
+                ),
+                Err(err) => info!(
+                    "Failed to get events of unhealthy node {}: {}",
+                    node_name, err
+                ),
+            }
+            info!(
+                "Inspect unhealthy node {} with: {}",
+                node,
+                node.describe_pod_command()
+            );
+            unhealthy_nodes.push(node.to_string());
         }
     }
     if !unhealthy_nodes.is_empty() {
@@ -661,14 +2334,26 @@ pub async fn nodes_healthcheck(nodes: Vec<&K8sNode>) -> Result<Vec<String>> {
 
 impl Drop for K8sSwarm {
     fn drop(&mut self) {
+        // e.g. a test panicked with a partition in place, which must not outlive it in a kept
+        // namespace
+        if !self.chaoses.is_empty() {
+            if let Err(e) = delete_all_chaos(&self.backend_config, &self.kube_namespace) {
+                info!("Failed to remove the chaos of the swarm: {}", e);
+            }
+        }
         let runtime = Runtime::new().unwrap();
         if !self.keep {
             runtime
-                .block_on(uninstall_testnet_resources(self.kube_namespace.clone()))
+                .block_on(uninstall_testnet_resources(
+                    &self.backend_config,
+                    self.kube_namespace.clone(),
+                ))
                 .unwrap();
         } else {
             println!("Keeping kube_namespace {}", self.kube_namespace);
         }
+        // the port-forwards of the swarm's own nodes are still registered, and left to the nodes
+        cleanup_orphaned_port_forwards(&self.backend_config);
     }
 }
 
@@ -676,6 +2361,8 @@ impl Drop for K8sSwarm {
 trait ChaosExperimentOps {
     async fn list_network_chaos(&self) -> Result<Vec<NetworkChaos>>;
     async fn list_stress_chaos(&self) -> Result<Vec<StressChaos>>;
+    async fn list_time_chaos(&self) -> Result<Vec<TimeChaos>>;
+    async fn list_dns_chaos(&self) -> Result<Vec<DNSChaos>>;
 
     async fn ensure_chaos_experiments_active(&self) -> Result<()> {
         let timeout_duration = Duration::from_secs(300); // 5 minutes
@@ -712,19 +2399,27 @@ trait ChaosExperimentOps {
 
     /// Checks if all chaos experiments are active
     async fn are_chaos_experiments_active(&self) -> Result<bool> {
-        let (network_chaoses, stress_chaoses) =
-            tokio::join!(self.list_network_chaos(), self.list_stress_chaos());
+        let (network_chaoses, stress_chaoses, time_chaoses, dns_chaoses) = tokio::join!(
+            self.list_network_chaos(),
+            self.list_stress_chaos(),
+            self.list_time_chaos(),
+            self.list_dns_chaos()
+        );
 
         let chaoses: Vec<Chaos> = network_chaoses?
             .into_iter()
             .map(Chaos::Network)
             .chain(stress_chaoses?.into_iter().map(Chaos::Stress))
+            .chain(time_chaoses?.into_iter().map(Chaos::Time))
+            .chain(dns_chaoses?.into_iter().map(Chaos::Dns))
             .collect();
 
         Ok(!chaoses.is_empty()
             && chaoses.iter().all(|chaos| match chaos {
                 Chaos::Network(network_chaos) => check_all_injected(&network_chaos.status),
                 Chaos::Stress(stress_chaos) => check_all_injected(&stress_chaos.status),
+                Chaos::Time(time_chaos) => check_all_injected(&time_chaos.status),
+                Chaos::Dns(dns_chaos) => check_all_injected(&dns_chaos.status),
             }))
     }
 }
@@ -743,6 +2438,8 @@ fn check_all_injected(status: &Option<ChaosStatus>) -> bool {
 struct MockChaosExperimentOps {
     network_chaos: Vec<NetworkChaos>,
     stress_chaos: Vec<StressChaos>,
+    time_chaos: Vec<TimeChaos>,
+    dns_chaos: Vec<DNSChaos>,
 }
 
 #[async_trait::async_trait]
@@ -754,6 +2451,14 @@ impl ChaosExperimentOps for MockChaosExperimentOps {
     async fn list_stress_chaos(&self) -> Result<Vec<StressChaos>> {
         Ok(self.stress_chaos.clone())
     }
+
+    async fn list_time_chaos(&self) -> Result<Vec<TimeChaos>> {
+        Ok(self.time_chaos.clone())
+    }
+
+    async fn list_dns_chaos(&self) -> Result<Vec<DNSChaos>> {
+        Ok(self.dns_chaos.clone())
+    }
 }
 
 struct RealChaosExperimentOps {
@@ -778,32 +2483,160 @@ impl ChaosExperimentOps for RealChaosExperimentOps {
         let stress_chaoses = stress_chaos_api.list(&lp).await?.items;
         Ok(stress_chaoses)
     }
+
+    async fn list_time_chaos(&self) -> Result<Vec<TimeChaos>> {
+        let time_chaos_api: Api<TimeChaos> =
+            Api::namespaced(self.kube_client.clone(), &self.kube_namespace);
+        let time_chaoses = time_chaos_api.list(&ListParams::default()).await?.items;
+        Ok(time_chaoses)
+    }
+
+    async fn list_dns_chaos(&self) -> Result<Vec<DNSChaos>> {
+        let dns_chaos_api: Api<DNSChaos> =
+            Api::namespaced(self.kube_client.clone(), &self.kube_namespace);
+        let dns_chaoses = dns_chaos_api.list(&ListParams::default()).await?.items;
+        Ok(dns_chaoses)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::chaos_schema::ChaosCondition;
+    use crate::{chaos_schema::ChaosCondition, ContainerResourceUsage};
+    use k8s_openapi::api::core::v1::{ServicePort, ServiceSpec};
+
+    #[test]
+    fn test_check_same_chain() {
+        let old = Version::new(0, "old".to_string());
+        let new = Version::new(1, "new".to_string());
+        let validators = vec![
+            ("validator-0".to_string(), old.clone(), 4),
+            ("validator-1".to_string(), old.clone(), 4),
+            ("validator-2".to_string(), old.clone(), 4),
+        ];
+        assert!(
+            check_same_chain(&validators, &[("fullnode-0".to_string(), new.clone(), 4)]).is_ok()
+        );
+        assert!(check_same_chain(&[], &[]).is_ok());
+
+        let error = check_same_chain(&validators, &[
+            ("fullnode-0".to_string(), new, 2),
+            ("fullnode-1".to_string(), old, 4),
+        ])
+        .unwrap_err()
+        .to_string();
+        assert_eq!(
+            error,
+            "Nodes are incompatible with the validators on chain id 4: fullnode-0 on version new \
+             has chain id 2"
+        );
+    }
+
+    #[test]
+    fn test_busiest_nodes() {
+        let usage = |cpu_millicores| PodResourceUsage {
+            containers: vec![ContainerResourceUsage {
+                name: "validator".to_string(),
+                cpu_millicores,
+                memory_bytes: 0,
+                cpu_limit_millicores: None,
+                memory_limit_bytes: None,
+            }],
+            ..PodResourceUsage::default()
+        };
+        let (idle, busy, busiest) = (PeerId::random(), PeerId::random(), PeerId::random());
+        let usage = HashMap::from([
+            (idle, usage(10.0)),
+            (busiest, usage(3500.0)),
+            (busy, usage(1200.0)),
+        ]);
+        assert_eq!(busiest_nodes(&usage, 2), vec![busiest, busy]);
+        assert_eq!(busiest_nodes(&usage, 5).len(), 3);
+    }
 
     #[test]
     fn test_parse_service_name_from_stateful_set_name() {
         let validator_sts_name = "aptos-node-19-validator";
         let validator_service_name =
-            parse_service_name_from_stateful_set_name(validator_sts_name, false);
+            parse_service_name_from_stateful_set_name(validator_sts_name, false).unwrap();
         assert_eq!("aptos-node-19-validator", &validator_service_name);
         // with haproxy
         let validator_service_name =
-            parse_service_name_from_stateful_set_name(validator_sts_name, true);
+            parse_service_name_from_stateful_set_name(validator_sts_name, true).unwrap();
         assert_eq!("aptos-node-19-validator-lb", &validator_service_name);
 
         let fullnode_sts_name = "aptos-node-0-fullnode-eforge195";
         let fullnode_service_name =
-            parse_service_name_from_stateful_set_name(fullnode_sts_name, false);
+            parse_service_name_from_stateful_set_name(fullnode_sts_name, false).unwrap();
         assert_eq!("aptos-node-0-fullnode", &fullnode_service_name);
         // with haproxy
         let fullnode_service_name =
-            parse_service_name_from_stateful_set_name(fullnode_sts_name, true);
+            parse_service_name_from_stateful_set_name(fullnode_sts_name, true).unwrap();
         assert_eq!("aptos-node-0-fullnode-lb", &fullnode_service_name);
+
+        assert_eq!(
+            parse_service_name_from_stateful_set_name("public-fullnode-7-8bc1a4f3", false),
+            None
+        );
+    }
+
+    #[test]
+    fn test_parse_node_index() {
+        assert_eq!(parse_node_index("aptos-node-19-validator").unwrap(), 19);
+        assert_eq!(
+            parse_node_index("aptos-node-3-fullnode-eforge195").unwrap(),
+            3
+        );
+        assert_eq!(parse_node_index("fullnode-2-8bc1a4f3").unwrap(), 2);
+        assert_eq!(parse_node_index("public-fullnode-7-8bc1a4f3").unwrap(), 7);
+        parse_node_index("postgres-0").unwrap_err();
+    }
+
+    #[test]
+    fn test_rest_api_service_port() {
+        let service = |ports: Vec<(&str, i32)>| Service {
+            spec: Some(ServiceSpec {
+                ports: Some(
+                    ports
+                        .into_iter()
+                        .map(|(name, port)| ServicePort {
+                            name: Some(name.to_string()),
+                            port,
+                            ..ServicePort::default()
+                        })
+                        .collect(),
+                ),
+                ..ServiceSpec::default()
+            }),
+            ..Service::default()
+        };
+        let services = HashMap::from([
+            (
+                "aptos-node-0-validator".to_string(),
+                service(vec![("validator", 6180), ("api", 8081)]),
+            ),
+            (
+                "aptos-node-0-validator-lb".to_string(),
+                service(vec![("validator", 6180)]),
+            ),
+        ]);
+        assert_eq!(
+            rest_api_service_port(&services, "aptos-node-0-validator", REST_API_SERVICE_PORT),
+            8081
+        );
+        // the HAProxy Service does not expose the REST API
+        assert_eq!(
+            rest_api_service_port(
+                &services,
+                "aptos-node-0-validator-lb",
+                REST_API_HAPROXY_SERVICE_PORT
+            ),
+            REST_API_HAPROXY_SERVICE_PORT
+        );
+        assert_eq!(
+            rest_api_service_port(&services, "aptos-node-1-validator", REST_API_SERVICE_PORT),
+            REST_API_SERVICE_PORT
+        );
     }
 
     async fn create_chaos_experiments(
@@ -837,6 +2670,8 @@ mod tests {
         let chaos_ops = MockChaosExperimentOps {
             network_chaos: vec![],
             stress_chaos: vec![],
+            time_chaos: vec![],
+            dns_chaos: vec![],
         };
         assert!(!chaos_ops.are_chaos_experiments_active().await.unwrap());
 
@@ -846,6 +2681,8 @@ mod tests {
         let chaos_ops = MockChaosExperimentOps {
             network_chaos,
             stress_chaos,
+            time_chaos: vec![],
+            dns_chaos: vec![],
         };
         assert!(!chaos_ops.are_chaos_experiments_active().await.unwrap());
 
@@ -855,7 +2692,152 @@ mod tests {
         let chaos_ops = MockChaosExperimentOps {
             network_chaos,
             stress_chaos,
+            time_chaos: vec![],
+            dns_chaos: vec![],
         };
         assert!(chaos_ops.are_chaos_experiments_active().await.unwrap());
+
+        // a time chaos that is not injected yet
+        let (network_chaos, stress_chaos) =
+            create_chaos_experiments(ConditionStatus::True, ConditionStatus::True).await;
+        let chaos_ops = MockChaosExperimentOps {
+            network_chaos,
+            stress_chaos,
+            time_chaos: vec![TimeChaos::new("test", Default::default())],
+            dns_chaos: vec![],
+        };
+        assert!(!chaos_ops.are_chaos_experiments_active().await.unwrap());
+
+        // a DNS chaos that is not injected yet
+        let (network_chaos, stress_chaos) =
+            create_chaos_experiments(ConditionStatus::True, ConditionStatus::True).await;
+        let chaos_ops = MockChaosExperimentOps {
+            network_chaos,
+            stress_chaos,
+            time_chaos: vec![],
+            dns_chaos: vec![DNSChaos::new("test", Default::default())],
+        };
+        assert!(!chaos_ops.are_chaos_experiments_active().await.unwrap());
+    }
+
+    fn stateful_set(name: &str, image_tag: &str, era: &str) -> StatefulSet {
+        use k8s_openapi::api::{
+            apps::v1::StatefulSetSpec,
+            core::v1::{Container, PodSpec, PodTemplateSpec, SecretVolumeSource, Volume},
+        };
+        use kube::api::ObjectMeta;
+
+        let index = parse_node_index(name).unwrap();
+        StatefulSet {
+            metadata: ObjectMeta {
+                name: Some(name.to_string()),
+                namespace: Some("forge-banana".to_string()),
+                ..ObjectMeta::default()
+            },
+            spec: Some(StatefulSetSpec {
+                template: PodTemplateSpec {
+                    spec: Some(PodSpec {
+                        containers: vec![Container {
+                            image: Some(format!("aptos/validator:{}", image_tag)),
+                            ..Container::default()
+                        }],
+                        volumes: Some(vec![Volume {
+                            name: "genesis-config".to_string(),
+                            secret: Some(SecretVolumeSource {
+                                secret_name: Some(format!("aptos-node-{}-genesis-e{}", index, era)),
+                                ..SecretVolumeSource::default()
+                            }),
+                            ..Volume::default()
+                        }]),
+                        ..PodSpec::default()
+                    }),
+                    ..PodTemplateSpec::default()
+                },
+                ..StatefulSetSpec::default()
+            }),
+            status: None,
+        }
+    }
+
+    #[test]
+    fn test_fullnodes_of_index() {
+        let fullnodes: Vec<_> = [
+            "fullnode-0-8bc1a4f3",
+            "aptos-node-1-fullnode-eforge7",
+            "fullnode-0-1d9e2c07",
+            "aptos-node-0-fullnode-eforge7",
+        ]
+        .iter()
+        .flat_map(|name| {
+            get_k8s_nodes_from_stateful_set(
+                &Arc::new(K8sBackendConfig::default()),
+                &stateful_set(name, "banana", "forge7"),
+                &HashMap::new(),
+                false,
+                false,
+                None,
+            )
+        })
+        .collect();
+
+        let names = |index| {
+            fullnodes_of_index(fullnodes.iter(), index)
+                .iter()
+                .map(|fullnode| fullnode.stateful_set_name().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(0), vec![
+            "aptos-node-0-fullnode-eforge7",
+            "fullnode-0-1d9e2c07",
+            "fullnode-0-8bc1a4f3",
+        ]);
+        assert_eq!(names(1), vec!["aptos-node-1-fullnode-eforge7"]);
+        assert!(names(2).is_empty());
+    }
+
+    #[test]
+    fn test_reuse_mismatches() {
+        let nodes = |stateful_sets: Vec<StatefulSet>| {
+            stateful_sets
+                .iter()
+                .flat_map(|sts| {
+                    get_k8s_nodes_from_stateful_set(
+                        &Arc::new(K8sBackendConfig::default()),
+                        sts,
+                        &HashMap::new(),
+                        false,
+                        false,
+                        None,
+                    )
+                })
+                .map(|node| (PeerId::random(), node))
+                .collect::<HashMap<_, _>>()
+        };
+        let old = Version::new(0, "old".to_string());
+        let new = Version::new(1, "new".to_string());
+        let validators = nodes(vec![
+            stateful_set("aptos-node-0-validator", "old", "forge7"),
+            stateful_set("aptos-node-1-validator", "new", "forge7"),
+        ]);
+        let fullnodes = nodes(vec![stateful_set(
+            "aptos-node-0-fullnode-eforge7",
+            "old",
+            "forge7",
+        )]);
+        assert_eq!(
+            stateful_set_era(&stateful_set("aptos-node-0-validator", "old", "forge7")),
+            Some("forge7".to_string())
+        );
+
+        let by_index = |index: usize| if index == 1 { new.clone() } else { old.clone() };
+        assert!(reuse_mismatches(&validators, &fullnodes, 2, 1, by_index, &old).is_empty());
+        assert_eq!(
+            reuse_mismatches(&validators, &fullnodes, 3, 1, |_| old.clone(), &new),
+            vec![
+                "2 validators run, 3 were requested".to_string(),
+                "fullnode-0 runs old, new was requested".to_string(),
+                "validator-1 runs new, old was requested".to_string(),
+            ]
+        );
     }
 }
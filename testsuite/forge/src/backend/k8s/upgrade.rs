@@ -0,0 +1,183 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{TestReport, Version};
+use std::time::Duration;
+
+// how far behind the rest an upgraded node may be, in ledger versions, to count as caught up
+const DEFAULT_MAX_VERSION_LAG: u64 = 1_000;
+// how long an upgraded batch gets to catch up with the rest
+const DEFAULT_CATCH_UP_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// When the fullnodes are upgraded in a rolling upgrade, if at all
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FullNodeUpgradeOrder {
+    // the fullnodes keep their version
+    Skip,
+    BeforeValidators,
+    AfterValidators,
+}
+
+/// How to roll a new version out to the nodes of a swarm
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RollingUpgradeOptions {
+    // how many nodes are upgraded at once. The fullnodes are upgraded in batches of their own.
+    pub batch_size: usize,
+    // between two batches, once the first one caught up
+    pub wait_between: Duration,
+    pub max_version_lag: u64,
+    pub catch_up_timeout: Duration,
+    pub fullnodes: FullNodeUpgradeOrder,
+}
+
+impl RollingUpgradeOptions {
+    pub fn new(batch_size: usize, wait_between: Duration) -> Self {
+        Self {
+            batch_size,
+            wait_between,
+            max_version_lag: DEFAULT_MAX_VERSION_LAG,
+            catch_up_timeout: DEFAULT_CATCH_UP_TIMEOUT,
+            fullnodes: FullNodeUpgradeOrder::Skip,
+        }
+    }
+
+    pub fn with_max_version_lag(mut self, max_version_lag: u64) -> Self {
+        self.max_version_lag = max_version_lag;
+        self
+    }
+
+    pub fn with_catch_up_timeout(mut self, catch_up_timeout: Duration) -> Self {
+        self.catch_up_timeout = catch_up_timeout;
+        self
+    }
+
+    pub fn with_fullnodes(mut self, fullnodes: FullNodeUpgradeOrder) -> Self {
+        self.fullnodes = fullnodes;
+        self
+    }
+}
+
+/// How long one batch of a rolling upgrade took
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UpgradeBatchTiming {
+    pub nodes: Vec<String>,
+    // until all the nodes of the batch were rolled out and healthy
+    pub upgrade: Duration,
+    // from then on, until they were within the version lag of the rest
+    pub catch_up: Duration,
+}
+
+/// How a rolling upgrade went, batch by batch
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RollingUpgradeReport {
+    pub version: Version,
+    pub batches: Vec<UpgradeBatchTiming>,
+    // including the waits between the batches
+    pub total: Duration,
+}
+
+impl RollingUpgradeReport {
+    pub fn report(&self, report: &mut TestReport, test_name: &str) {
+        let lines: Vec<_> = self
+            .batches
+            .iter()
+            .enumerate()
+            .map(|(i, batch)| {
+                format!(
+                    "  batch {} [{}]: upgraded in {:.1}s, caught up in {:.1}s",
+                    i,
+                    batch.nodes.join(", "),
+                    batch.upgrade.as_secs_f64(),
+                    batch.catch_up.as_secs_f64()
+                )
+            })
+            .collect();
+        report.report_text(format!(
+            "Rolling upgrade to {} took {:.1}s:\n{}",
+            self.version,
+            self.total.as_secs_f64(),
+            lines.join("\n")
+        ));
+        report.report_metric(
+            test_name,
+            "rolling_upgrade_duration_s",
+            self.total.as_secs_f64(),
+        );
+        if let Some(slowest) = self
+            .batches
+            .iter()
+            .map(|batch| batch.upgrade + batch.catch_up)
+            .max()
+        {
+            report.report_metric(
+                test_name,
+                "rolling_upgrade_slowest_batch_s",
+                slowest.as_secs_f64(),
+            );
+        }
+    }
+}
+
+/// The nodes of a batch more than `max_version_lag` behind the given ledger version, with their
+/// own ledger versions. Nodes that failed to report are lagging too.
+pub(crate) fn lagging_nodes(
+    batch_versions: &[(String, Option<u64>)],
+    reference_version: u64,
+    max_version_lag: u64,
+) -> Vec<(String, Option<u64>)> {
+    batch_versions
+        .iter()
+        .filter(|(_, version)| match version {
+            Some(version) => version.saturating_add(max_version_lag) < reference_version,
+            None => true,
+        })
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lagging_nodes() {
+        let batch = vec![
+            ("validator-0".to_string(), Some(9_500)),
+            ("validator-1".to_string(), Some(8_000)),
+            ("validator-2".to_string(), None),
+        ];
+        assert_eq!(lagging_nodes(&batch, 10_000, 1_000), vec![
+            ("validator-1".to_string(), Some(8_000)),
+            ("validator-2".to_string(), None),
+        ]);
+        assert_eq!(lagging_nodes(&batch, 8_000, 0), vec![(
+            "validator-2".to_string(),
+            None
+        )]);
+    }
+
+    #[test]
+    fn test_rolling_upgrade_report() {
+        let upgrade = RollingUpgradeReport {
+            version: Version::new(1, "banana".to_string()),
+            batches: vec![
+                UpgradeBatchTiming {
+                    nodes: vec!["validator-0".to_string(), "validator-1".to_string()],
+                    upgrade: Duration::from_secs(40),
+                    catch_up: Duration::from_secs(5),
+                },
+                UpgradeBatchTiming {
+                    nodes: vec!["validator-2".to_string()],
+                    upgrade: Duration::from_secs(30),
+                    catch_up: Duration::from_secs(20),
+                },
+            ],
+            total: Duration::from_secs(160),
+        };
+        let mut report = TestReport::default();
+        upgrade.report(&mut report, "rolling upgrade");
+        assert!(report
+            .to_string()
+            .contains("batch 1 [validator-2]: upgraded in 30.0s, caught up in 20.0s"));
+    }
+}
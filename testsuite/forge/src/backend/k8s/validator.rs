@@ -0,0 +1,1203 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{
+    get_stateful_set_image, query_sequence_number, AptosPublicInfo, K8sError, ReadWrite, Result,
+    Version, APTOS_NODE_HELM_RELEASE_NAME, FULLNODE_HAPROXY_SERVICE_SUFFIX,
+    VALIDATOR_HAPROXY_SERVICE_SUFFIX,
+};
+use anyhow::{format_err, Context};
+use aptos_cached_packages::aptos_stdlib;
+use aptos_config::config::IdentityBlob;
+use aptos_genesis::keys::{generate_key_objects, PublicIdentity};
+use aptos_keygen::KeyGen;
+use aptos_logger::info;
+use aptos_rest_client::Client as RestClient;
+use aptos_sdk::{
+    bcs,
+    crypto::{bls12381, x25519, PrivateKey as _},
+    types::{
+        network_address::NetworkAddress, validator_config::ValidatorConfig, LocalAccount, PeerId,
+    },
+};
+use k8s_openapi::{
+    api::{
+        apps::v1::{Deployment, StatefulSet, StatefulSetSpec},
+        core::v1::{
+            ConfigMap, PersistentVolumeClaim, PersistentVolumeClaimSpec, PodTemplateSpec, Secret,
+            Service, ServicePort, ServiceSpec,
+        },
+    },
+    apimachinery::pkg::apis::meta::v1::LabelSelector,
+    ByteString,
+};
+use kube::{
+    api::{Api, DeleteParams, ObjectMeta, PostParams},
+    client::Client as K8sClient,
+};
+use serde::de::DeserializeOwned;
+use std::{collections::BTreeMap, fmt::Debug, str::FromStr, sync::Arc};
+
+// these are given by the aptos-node helm chart
+// see terraform/helm/aptos-node/templates/validator.yaml
+
+// the label the chart tells the validators apart by, "validator-<index>"
+const INSTANCE_LABEL: &str = "app.kubernetes.io/instance";
+const VALIDATOR_CONTAINER_NAME: &str = "validator";
+const VALIDATOR_NETWORK_PORT: u16 = 6180;
+
+// the volumes of the validator pod
+const APTOS_CONFIG_VOLUME_NAME: &str = "aptos-config";
+const GENESIS_CONFIG_VOLUME_NAME: &str = "genesis-config";
+const APTOS_DATA_VOLUME_NAME: &str = "aptos-data";
+
+// the keys of the genesis Secret the validator reads its identity from
+const VALIDATOR_IDENTITY_KEY: &str = "validator-identity.yaml";
+const VALIDATOR_FULLNODE_IDENTITY_KEY: &str = "validator-full-node-identity.yaml";
+// the new identity of a validator whose keys are being rotated, until it runs with it
+const ROTATED_VALIDATOR_IDENTITY_KEY: &str = "rotated-validator-identity.yaml";
+
+// the handshake version of the validator network, see NetworkAddress::append_prod_protos
+const HANDSHAKE_VERSION: u8 = 0;
+
+// on top of the stake, for the gas of the registration transactions
+const NEW_VALIDATOR_GAS_FUNDS: u64 = 100_000_000;
+
+/// The names the aptos-node helm chart gives the k8s resources of the validator of an index
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ValidatorResourceNames {
+    pub stateful_set: String,
+    // the same as the StatefulSet's
+    pub service: String,
+    pub config_map: String,
+    pub genesis_secret: String,
+    pub data_volume: String,
+    // of the genesis the resources belong to
+    pub era: String,
+}
+
+impl ValidatorResourceNames {
+    pub fn new(index: usize, era: &str) -> Self {
+        let prefix = format!("{}-{}", APTOS_NODE_HELM_RELEASE_NAME, index);
+        Self {
+            stateful_set: format!("{}-validator", prefix),
+            service: format!("{}-validator", prefix),
+            config_map: prefix.clone(),
+            genesis_secret: format!("{}-genesis-e{}", prefix, era),
+            data_volume: format!("{}-validator-e{}", prefix, era),
+            era: era.to_string(),
+        }
+    }
+}
+
+/// The names the aptos-node helm chart gives the k8s resources that go along with the validator
+/// of an index: those of its VFN, and of the HAProxy in front of both, if enabled
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ValidatorCompanionResourceNames {
+    pub vfn_stateful_set: String,
+    pub vfn_service: String,
+    pub vfn_data_volume: String,
+    pub haproxy_deployment: String,
+    pub haproxy_config_map: String,
+    // in front of the validator and of its VFN
+    pub haproxy_services: Vec<String>,
+}
+
+impl ValidatorCompanionResourceNames {
+    pub fn new(index: usize, era: &str) -> Self {
+        let prefix = format!("{}-{}", APTOS_NODE_HELM_RELEASE_NAME, index);
+        Self {
+            vfn_stateful_set: format!("{}-fullnode-e{}", prefix, era),
+            vfn_service: format!("{}-fullnode", prefix),
+            vfn_data_volume: format!("{}-fullnode-e{}", prefix, era),
+            haproxy_deployment: format!("{}-haproxy", prefix),
+            haproxy_config_map: format!("{}-haproxy", prefix),
+            haproxy_services: vec![
+                format!("{}-{}", prefix, VALIDATOR_HAPROXY_SERVICE_SUFFIX),
+                format!("{}-{}", prefix, FULLNODE_HAPROXY_SERVICE_SUFFIX),
+            ],
+        }
+    }
+}
+
+/// A validator to add to a running swarm, with its keys
+pub struct NewValidator {
+    pub index: usize,
+    pub names: ValidatorResourceNames,
+    pub validator_identity: IdentityBlob,
+    pub vfn_identity: IdentityBlob,
+    pub public_identity: PublicIdentity,
+    // the owner and operator of the validator's stake pool
+    pub account: LocalAccount,
+}
+
+impl NewValidator {
+    /// A validator of the given index with fresh keys
+    pub fn generate(index: usize, era: &str) -> Result<Self> {
+        Self::from_keygen(index, era, &mut KeyGen::from_os_rng())
+    }
+
+    fn from_keygen(index: usize, era: &str, keygen: &mut KeyGen) -> Result<Self> {
+        let (validator_identity, vfn_identity, private_identity, public_identity) =
+            generate_key_objects(keygen)?;
+        Ok(Self {
+            index,
+            names: ValidatorResourceNames::new(index, era),
+            validator_identity,
+            vfn_identity,
+            account: LocalAccount::new(
+                public_identity.account_address,
+                private_identity.account_private_key,
+                0,
+            ),
+            public_identity,
+        })
+    }
+
+    pub fn peer_id(&self) -> PeerId {
+        self.public_identity.account_address
+    }
+
+    /// The address the other validators reach the validator at on the validator network. New
+    /// validators have no HAProxy, so this is their Service.
+    pub fn network_address(&self, namespace: &str) -> Result<NetworkAddress> {
+        let network_public_key = self
+            .public_identity
+            .validator_network_public_key
+            .ok_or_else(|| format_err!("Validator {} has no network key", self.peer_id()))?;
+        validator_network_address(&self.names, namespace, network_public_key)
+    }
+
+    /// Create and fund the account of the validator, and have it join the validator set with the
+    /// given stake. It becomes active with the next epoch.
+    pub async fn register(
+        &self,
+        info: &mut AptosPublicInfo,
+        network_address: NetworkAddress,
+        stake: u64,
+    ) -> Result<()> {
+        let address = self.peer_id();
+        info.create_user_account(&self.public_identity.account_public_key)
+            .await?;
+        info.mint(address, stake + NEW_VALIDATOR_GAS_FUNDS).await?;
+
+        let consensus_public_key = self
+            .public_identity
+            .consensus_public_key
+            .as_ref()
+            .ok_or_else(|| format_err!("Validator {} has no consensus key", address))?;
+        let proof_of_possession = self
+            .public_identity
+            .consensus_proof_of_possession
+            .as_ref()
+            .ok_or_else(|| format_err!("Validator {} has no proof of possession", address))?;
+        // without a validator fullnode, the validator has no fullnode addresses
+        let payloads = vec![
+            aptos_stdlib::stake_initialize_validator(
+                consensus_public_key.to_bytes().to_vec(),
+                proof_of_possession.to_bytes().to_vec(),
+                bcs::to_bytes(&vec![network_address])?,
+                bcs::to_bytes(&Vec::<NetworkAddress>::new())?,
+            ),
+            aptos_stdlib::stake_add_stake(stake),
+            aptos_stdlib::stake_join_validator_set(address),
+        ];
+        let transaction_factory = info.transaction_factory();
+        for payload in payloads {
+            let txn = self
+                .account
+                .sign_with_transaction_builder(transaction_factory.payload(payload));
+            info.client()
+                .submit_and_wait(&txn)
+                .await
+                .with_context(|| format!("Failed to register validator {}", address))?;
+        }
+        Ok(())
+    }
+
+    /// Have the validator leave the validator set, with the next epoch
+    pub async fn leave(&self, info: &AptosPublicInfo) -> Result<()> {
+        leave_validator_set(&self.account, info).await
+    }
+}
+
+/// Have the validator the account operates leave the validator set, with the next epoch
+pub async fn leave_validator_set(account: &LocalAccount, info: &AptosPublicInfo) -> Result<()> {
+    let txn = account.sign_with_transaction_builder(
+        info.transaction_factory()
+            .payload(aptos_stdlib::stake_leave_validator_set(account.address())),
+    );
+    info.client().submit_and_wait(&txn).await?;
+    Ok(())
+}
+
+/// The old and new public keys of a validator whose keys are being rotated
+#[derive(Clone, Debug)]
+pub struct KeyRotation {
+    pub old_consensus_public_key: bls12381::PublicKey,
+    pub old_network_public_key: x25519::PublicKey,
+    pub consensus_public_key: bls12381::PublicKey,
+    pub proof_of_possession: bls12381::ProofOfPossession,
+    pub network_public_key: x25519::PublicKey,
+}
+
+impl KeyRotation {
+    fn new(old: &IdentityBlob, new: &IdentityBlob) -> Result<Self> {
+        let new_consensus_key = consensus_private_key(new)?;
+        Ok(Self {
+            old_consensus_public_key: consensus_private_key(old)?.public_key(),
+            old_network_public_key: old.network_private_key.public_key(),
+            consensus_public_key: new_consensus_key.public_key(),
+            proof_of_possession: bls12381::ProofOfPossession::create(new_consensus_key),
+            network_public_key: new.network_private_key.public_key(),
+        })
+    }
+
+    /// Have the chain switch the validator the account operates to the new keys with the next
+    /// epoch. Its network addresses stay those of its on-chain config, with the new network key.
+    pub async fn submit(
+        &self,
+        account: &LocalAccount,
+        info: &AptosPublicInfo,
+        validator_config: &ValidatorConfig,
+    ) -> Result<()> {
+        let mut network_addresses = validator_config.validator_network_addresses()?;
+        for address in &mut network_addresses {
+            address.rotate_noise_public_key(&self.old_network_public_key, &self.network_public_key);
+        }
+        let payloads = vec![
+            aptos_stdlib::stake_rotate_consensus_key(
+                account.address(),
+                self.consensus_public_key.to_bytes().to_vec(),
+                self.proof_of_possession.to_bytes().to_vec(),
+            ),
+            aptos_stdlib::stake_update_network_and_fullnode_addresses(
+                account.address(),
+                bcs::to_bytes(&network_addresses)?,
+                validator_config.fullnode_network_addresses.clone(),
+            ),
+        ];
+        let transaction_factory = info.transaction_factory();
+        for payload in payloads {
+            let txn =
+                account.sign_with_transaction_builder(transaction_factory.clone().payload(payload));
+            info.client().submit_and_wait(&txn).await.with_context(|| {
+                format!(
+                    "Failed to rotate the keys of validator {}",
+                    account.address()
+                )
+            })?;
+        }
+        Ok(())
+    }
+}
+
+fn consensus_private_key(identity: &IdentityBlob) -> Result<&bls12381::PrivateKey> {
+    identity.consensus_private_key.as_ref().ok_or_else(|| {
+        format_err!(
+            "Identity of {:?} has no consensus key",
+            identity.account_address
+        )
+    })
+}
+
+/// Generate new consensus and network keys for the validator, and keep them in its genesis Secret
+/// next to the identity it keeps running with. The keys of an earlier, unfinished rotation are
+/// kept, as the chain may know them already.
+pub async fn store_rotated_identity(
+    kube_client: K8sClient,
+    namespace: &str,
+    names: &ValidatorResourceNames,
+) -> Result<KeyRotation> {
+    let secret_api: Api<Secret> = Api::namespaced(kube_client, namespace);
+    let secret = secret_api
+        .get(&names.genesis_secret)
+        .await
+        .map_err(|e| K8sError::from_kube(&names.genesis_secret, e))?;
+    let (secret, rotation) = with_rotated_identity(&secret, &mut KeyGen::from_os_rng())?;
+    secret_api
+        .replace(&names.genesis_secret, &PostParams::default(), &secret)
+        .await
+        .map_err(|e| K8sError::from_kube(&names.genesis_secret, e))?;
+    Ok(rotation)
+}
+
+/// Have the validator run with the identity of [store_rotated_identity] from its next start on
+pub async fn apply_rotated_identity(
+    kube_client: K8sClient,
+    namespace: &str,
+    names: &ValidatorResourceNames,
+) -> Result<()> {
+    let secret_api: Api<Secret> = Api::namespaced(kube_client, namespace);
+    let secret = secret_api
+        .get(&names.genesis_secret)
+        .await
+        .map_err(|e| K8sError::from_kube(&names.genesis_secret, e))?;
+    secret_api
+        .replace(
+            &names.genesis_secret,
+            &PostParams::default(),
+            &with_applied_rotated_identity(&secret)?,
+        )
+        .await
+        .map_err(|e| K8sError::from_kube(&names.genesis_secret, e))?;
+    Ok(())
+}
+
+/// The Secret with a rotated identity next to the validator's, the same but for new consensus
+/// and network keys
+fn with_rotated_identity(secret: &Secret, keygen: &mut KeyGen) -> Result<(Secret, KeyRotation)> {
+    let identity = validator_identity_from_secret(secret)?;
+    let mut data = secret.data.clone().unwrap_or_default();
+    let rotated: IdentityBlob = match data.get(ROTATED_VALIDATOR_IDENTITY_KEY) {
+        Some(rotated) => serde_yaml::from_slice(&rotated.0)?,
+        None => {
+            // parsed again, as its private keys can't be cloned
+            let mut rotated: IdentityBlob =
+                serde_yaml::from_slice(&data[VALIDATOR_IDENTITY_KEY].0)?;
+            rotated.consensus_private_key = Some(keygen.generate_bls12381_private_key());
+            rotated.network_private_key = keygen.generate_x25519_private_key()?;
+            rotated
+        },
+    };
+    let rotation = KeyRotation::new(&identity, &rotated)?;
+    data.insert(
+        ROTATED_VALIDATOR_IDENTITY_KEY.to_string(),
+        ByteString(serde_yaml::to_string(&rotated)?.into_bytes()),
+    );
+    Ok((
+        Secret {
+            data: Some(data),
+            ..secret.clone()
+        },
+        rotation,
+    ))
+}
+
+/// The Secret with the rotated identity in place of the validator's
+fn with_applied_rotated_identity(secret: &Secret) -> Result<Secret> {
+    let mut data = secret.data.clone().unwrap_or_default();
+    let rotated = data.remove(ROTATED_VALIDATOR_IDENTITY_KEY).ok_or_else(|| {
+        format_err!(
+            "Secret {} has no {}",
+            secret.metadata.name.clone().unwrap_or_default(),
+            ROTATED_VALIDATOR_IDENTITY_KEY
+        )
+    })?;
+    data.insert(VALIDATOR_IDENTITY_KEY.to_string(), rotated);
+    Ok(Secret {
+        data: Some(data),
+        ..secret.clone()
+    })
+}
+
+/// The identity of a validator, as its genesis Secret holds it
+pub(crate) fn validator_identity_from_secret(secret: &Secret) -> Result<IdentityBlob> {
+    let name = secret.metadata.name.clone().unwrap_or_default();
+    let identity = secret
+        .data
+        .as_ref()
+        .and_then(|data| data.get(VALIDATOR_IDENTITY_KEY))
+        .ok_or_else(|| format_err!("Secret {} has no {}", name, VALIDATOR_IDENTITY_KEY))?;
+    serde_yaml::from_slice(&identity.0).with_context(|| {
+        format!(
+            "Failed to parse {} of Secret {}",
+            VALIDATOR_IDENTITY_KEY, name
+        )
+    })
+}
+
+/// The account of a running validator, with its current sequence number. The validators of the
+/// chart, as well as those added with [install_validator], have the same account as owner,
+/// operator and voter of their stake pool.
+pub async fn get_validator_account(
+    secret_api: Arc<dyn ReadWrite<Secret>>,
+    names: &ValidatorResourceNames,
+    client: &RestClient,
+) -> Result<LocalAccount> {
+    let secret = secret_api
+        .get(&names.genesis_secret)
+        .await
+        .map_err(|e| K8sError::from_kube(&names.genesis_secret, e))?;
+    let identity = validator_identity_from_secret(&secret)?;
+    let address = identity
+        .account_address
+        .ok_or_else(|| format_err!("Validator {} has no account address", names.stateful_set))?;
+    let private_key = identity
+        .account_private_key
+        .ok_or_else(|| format_err!("Validator {} has no account key", names.stateful_set))?;
+    let sequence_number = query_sequence_number(client, address).await?;
+    Ok(LocalAccount::new(address, private_key, sequence_number))
+}
+
+/// Point the labels that tell validators apart at the validator of the given index
+fn with_validator_instance_label(
+    labels: &Option<BTreeMap<String, String>>,
+    index: usize,
+) -> Option<BTreeMap<String, String>> {
+    labels.as_ref().map(|labels| {
+        let mut labels = labels.clone();
+        if let Some(instance) = labels.get_mut(INSTANCE_LABEL) {
+            *instance = format!("validator-{}", index);
+        }
+        labels
+    })
+}
+
+/// The metadata to create a copy of a resource under a new name with. Everything the cluster or
+/// helm set on the original, e.g. its uid or the helm release annotations, is left out.
+fn copy_metadata(
+    name: &str,
+    template: &ObjectMeta,
+    labels: Option<BTreeMap<String, String>>,
+) -> ObjectMeta {
+    ObjectMeta {
+        name: Some(name.to_string()),
+        namespace: template.namespace.clone(),
+        labels,
+        ..ObjectMeta::default()
+    }
+}
+
+/// Create the genesis Secret of a new validator, with the genesis of the template validator and
+/// the given identities
+fn create_validator_genesis_secret(
+    names: &ValidatorResourceNames,
+    template: &Secret,
+    validator_identity: &IdentityBlob,
+    vfn_identity: &IdentityBlob,
+) -> Result<Secret> {
+    let mut data = template.data.clone().unwrap_or_default();
+    data.insert(
+        VALIDATOR_IDENTITY_KEY.to_string(),
+        ByteString(serde_yaml::to_string(validator_identity)?.into_bytes()),
+    );
+    data.insert(
+        VALIDATOR_FULLNODE_IDENTITY_KEY.to_string(),
+        ByteString(serde_yaml::to_string(vfn_identity)?.into_bytes()),
+    );
+    Ok(Secret {
+        metadata: copy_metadata(
+            &names.genesis_secret,
+            &template.metadata,
+            template.metadata.labels.clone(),
+        ),
+        data: Some(data),
+        type_: template.type_.clone(),
+        ..Secret::default()
+    })
+}
+
+/// The NodeConfigs of the chart don't depend on the validator, so a new validator gets a copy of
+/// those of the template validator
+fn create_validator_config_map(names: &ValidatorResourceNames, template: &ConfigMap) -> ConfigMap {
+    ConfigMap {
+        metadata: copy_metadata(
+            &names.config_map,
+            &template.metadata,
+            template.metadata.labels.clone(),
+        ),
+        data: template.data.clone(),
+        binary_data: template.binary_data.clone(),
+        immutable: None,
+    }
+}
+
+/// Create the data volume of a new validator, with the storage class and size of the template
+/// validator's
+fn create_validator_persistent_volume_claim(
+    names: &ValidatorResourceNames,
+    template: &PersistentVolumeClaim,
+) -> Result<PersistentVolumeClaim> {
+    let template_spec = template
+        .spec
+        .as_ref()
+        .context("Validator PersistentVolumeClaim does not have spec")?;
+    Ok(PersistentVolumeClaim {
+        metadata: copy_metadata(
+            &names.data_volume,
+            &template.metadata,
+            template.metadata.labels.clone(),
+        ),
+        // not the volume_name, which is the volume the template is bound to
+        spec: Some(PersistentVolumeClaimSpec {
+            access_modes: template_spec.access_modes.clone(),
+            resources: template_spec.resources.clone(),
+            selector: template_spec.selector.clone(),
+            storage_class_name: template_spec.storage_class_name.clone(),
+            volume_mode: template_spec.volume_mode.clone(),
+            ..PersistentVolumeClaimSpec::default()
+        }),
+        ..PersistentVolumeClaim::default()
+    })
+}
+
+/// Create the Service of a new validator, exposing the same ports as the template validator's
+fn create_validator_service(
+    names: &ValidatorResourceNames,
+    index: usize,
+    template: &Service,
+) -> Result<Service> {
+    let template_spec = template
+        .spec
+        .as_ref()
+        .context("Validator Service does not have spec")?;
+    // the cluster allocates the IPs and node ports, unless the Service is headless
+    let headless = template_spec.cluster_ip.as_deref() == Some("None");
+    let ports = template_spec.ports.as_ref().map(|ports| {
+        ports
+            .iter()
+            .map(|port| ServicePort {
+                node_port: None,
+                ..port.clone()
+            })
+            .collect()
+    });
+    Ok(Service {
+        metadata: copy_metadata(
+            &names.service,
+            &template.metadata,
+            template.metadata.labels.clone(),
+        ),
+        spec: Some(ServiceSpec {
+            selector: with_validator_instance_label(&template_spec.selector, index),
+            ports,
+            cluster_ip: template_spec.cluster_ip.clone().filter(|_| headless),
+            cluster_ips: template_spec.cluster_ips.clone().filter(|_| headless),
+            health_check_node_port: None,
+            load_balancer_ip: None,
+            ..template_spec.clone()
+        }),
+        ..Service::default()
+    })
+}
+
+/// Create the StatefulSet of a new validator given the template validator's, running the given
+/// image on the new validator's volumes
+fn create_validator_stateful_set(
+    names: &ValidatorResourceNames,
+    index: usize,
+    image: String,
+    template: &StatefulSet,
+) -> Result<StatefulSet> {
+    let template_spec = template
+        .spec
+        .as_ref()
+        .context("Validator StatefulSet does not have spec")?;
+    let mut pod_spec = template_spec
+        .template
+        .spec
+        .clone()
+        .context("Validator StatefulSet does not have spec.template.spec")?;
+
+    let container = pod_spec
+        .containers
+        .iter_mut()
+        .find(|container| container.name == VALIDATOR_CONTAINER_NAME)
+        .context("Validator StatefulSet does not have a validator container")?;
+    container.image = Some(image);
+
+    for volume in pod_spec.volumes.iter_mut().flatten() {
+        match volume.name.as_str() {
+            APTOS_CONFIG_VOLUME_NAME => {
+                if let Some(config_map) = volume.config_map.as_mut() {
+                    config_map.name = Some(names.config_map.clone());
+                }
+            },
+            GENESIS_CONFIG_VOLUME_NAME => {
+                if let Some(secret) = volume.secret.as_mut() {
+                    secret.secret_name = Some(names.genesis_secret.clone());
+                }
+            },
+            APTOS_DATA_VOLUME_NAME => {
+                if let Some(claim) = volume.persistent_volume_claim.as_mut() {
+                    claim.claim_name = names.data_volume.clone();
+                }
+            },
+            _ => {},
+        }
+    }
+
+    let pod_metadata = template_spec
+        .template
+        .metadata
+        .as_ref()
+        .map(|metadata| ObjectMeta {
+            labels: with_validator_instance_label(&metadata.labels, index),
+            annotations: metadata.annotations.clone(),
+            ..ObjectMeta::default()
+        });
+    Ok(StatefulSet {
+        metadata: copy_metadata(
+            &names.stateful_set,
+            &template.metadata,
+            with_validator_instance_label(&template.metadata.labels, index),
+        ),
+        spec: Some(StatefulSetSpec {
+            // the template may be scaled down, e.g. if a test stopped it
+            replicas: Some(1),
+            service_name: names.service.clone(),
+            selector: LabelSelector {
+                match_labels: with_validator_instance_label(
+                    &template_spec.selector.match_labels,
+                    index,
+                ),
+                ..template_spec.selector.clone()
+            },
+            template: PodTemplateSpec {
+                metadata: pod_metadata,
+                spec: Some(pod_spec),
+            },
+            ..template_spec.clone()
+        }),
+        ..StatefulSet::default()
+    })
+}
+
+fn validator_network_address(
+    names: &ValidatorResourceNames,
+    namespace: &str,
+    network_public_key: x25519::PublicKey,
+) -> Result<NetworkAddress> {
+    let address = NetworkAddress::from_str(&format!(
+        "/dns/{}.{}.svc/tcp/{}",
+        names.service, namespace, VALIDATOR_NETWORK_PORT
+    ))?;
+    Ok(address.append_prod_protos(network_public_key, HANDSHAKE_VERSION))
+}
+
+/// Deploy a new validator, using the resources of the validator of the template index as
+/// templates. The validator runs the given version, from the genesis of the template validator.
+/// Returns its StatefulSet and Service. Resources created before a failure are not deleted, see
+/// [delete_validator_resources].
+pub async fn install_validator(
+    stateful_set_api: Arc<dyn ReadWrite<StatefulSet>>,
+    configmap_api: Arc<dyn ReadWrite<ConfigMap>>,
+    persistent_volume_claim_api: Arc<dyn ReadWrite<PersistentVolumeClaim>>,
+    service_api: Arc<dyn ReadWrite<Service>>,
+    secret_api: Arc<dyn ReadWrite<Secret>>,
+    validator: &NewValidator,
+    template_index: usize,
+    era: &str,
+    version: &Version,
+) -> Result<(StatefulSet, Service)> {
+    let names = &validator.names;
+    let index = validator.index;
+    let template_names = ValidatorResourceNames::new(template_index, era);
+    let template_stateful_set = stateful_set_api
+        .get(&template_names.stateful_set)
+        .await
+        .map_err(|e| K8sError::from_kube(&template_names.stateful_set, e))?;
+    let template_service = service_api
+        .get(&template_names.service)
+        .await
+        .map_err(|e| K8sError::from_kube(&template_names.service, e))?;
+    let template_config_map = configmap_api
+        .get(&template_names.config_map)
+        .await
+        .map_err(|e| K8sError::from_kube(&template_names.config_map, e))?;
+    let template_secret = secret_api
+        .get(&template_names.genesis_secret)
+        .await
+        .map_err(|e| K8sError::from_kube(&template_names.genesis_secret, e))?;
+    let template_data_volume = persistent_volume_claim_api
+        .get(&template_names.data_volume)
+        .await
+        .map_err(|e| K8sError::from_kube(&template_names.data_volume, e))?;
+
+    // new validators run the image repo of the template validator, with the tag of the version
+    let image = format!(
+        "{}:{}",
+        get_stateful_set_image(&template_stateful_set)?.name,
+        version
+    );
+
+    let secret = create_validator_genesis_secret(
+        names,
+        &template_secret,
+        &validator.validator_identity,
+        &validator.vfn_identity,
+    )?;
+    let config_map = create_validator_config_map(names, &template_config_map);
+    let data_volume = create_validator_persistent_volume_claim(names, &template_data_volume)?;
+    let service = create_validator_service(names, index, &template_service)?;
+    let stateful_set = create_validator_stateful_set(names, index, image, &template_stateful_set)?;
+
+    // the StatefulSet goes last, so that its pod finds everything it mounts
+    let pp = PostParams::default();
+    secret_api
+        .create(&pp, &secret)
+        .await
+        .map_err(|e| K8sError::from_kube(&names.genesis_secret, e))?;
+    configmap_api
+        .create(&pp, &config_map)
+        .await
+        .map_err(|e| K8sError::from_kube(&names.config_map, e))?;
+    persistent_volume_claim_api
+        .create(&pp, &data_volume)
+        .await
+        .map_err(|e| K8sError::from_kube(&names.data_volume, e))?;
+    let service = service_api
+        .create(&pp, &service)
+        .await
+        .map_err(|e| K8sError::from_kube(&names.service, e))?;
+    let stateful_set = stateful_set_api
+        .create(&pp, &stateful_set)
+        .await
+        .map_err(|e| K8sError::from_kube(&names.stateful_set, e))?;
+    info!(
+        "Created validator StatefulSet {} with image {:?}",
+        names.stateful_set,
+        get_stateful_set_image(&stateful_set).ok()
+    );
+    Ok((stateful_set, service))
+}
+
+pub(crate) async fn delete_if_exists<T>(api: Api<T>, kind: &'static str, name: &str) -> Result<()>
+where
+    T: kube::Resource + Clone + DeserializeOwned + Debug,
+    <T as kube::Resource>::DynamicType: Default,
+{
+    match api.delete(name, &DeleteParams::default()).await {
+        Ok(_) => {
+            info!("Deleted {} {}", kind, name);
+            Ok(())
+        },
+        Err(e) => match K8sError::from_kube(format!("{} {}", kind, name), e) {
+            K8sError::NotFound { .. } => Ok(()),
+            e => Err(e.into()),
+        },
+    }
+}
+
+/// Delete the k8s resources of a validator, skipping those that don't exist. Every deletion is
+/// attempted, and the failed ones are reported together.
+pub(crate) async fn delete_validator_resources(
+    kube_client: K8sClient,
+    namespace: &str,
+    names: &ValidatorResourceNames,
+) -> Result<()> {
+    let results = vec![
+        delete_if_exists(
+            Api::<StatefulSet>::namespaced(kube_client.clone(), namespace),
+            "StatefulSet",
+            &names.stateful_set,
+        )
+        .await,
+        delete_if_exists(
+            Api::<Service>::namespaced(kube_client.clone(), namespace),
+            "Service",
+            &names.service,
+        )
+        .await,
+        delete_if_exists(
+            Api::<ConfigMap>::namespaced(kube_client.clone(), namespace),
+            "ConfigMap",
+            &names.config_map,
+        )
+        .await,
+        delete_if_exists(
+            Api::<Secret>::namespaced(kube_client.clone(), namespace),
+            "Secret",
+            &names.genesis_secret,
+        )
+        .await,
+        delete_if_exists(
+            Api::<PersistentVolumeClaim>::namespaced(kube_client, namespace),
+            "PersistentVolumeClaim",
+            &names.data_volume,
+        )
+        .await,
+    ];
+    report_deletions(results, &format!("validator {}", names.stateful_set))
+}
+
+/// Delete the k8s resources that go along with a validator, skipping those that don't exist, as
+/// [delete_validator_resources] does
+pub(crate) async fn delete_validator_companion_resources(
+    kube_client: K8sClient,
+    namespace: &str,
+    names: &ValidatorCompanionResourceNames,
+) -> Result<()> {
+    let services = Api::<Service>::namespaced(kube_client.clone(), namespace);
+    let mut results = vec![
+        delete_if_exists(
+            Api::<StatefulSet>::namespaced(kube_client.clone(), namespace),
+            "StatefulSet",
+            &names.vfn_stateful_set,
+        )
+        .await,
+        delete_if_exists(services.clone(), "Service", &names.vfn_service).await,
+        delete_if_exists(
+            Api::<PersistentVolumeClaim>::namespaced(kube_client.clone(), namespace),
+            "PersistentVolumeClaim",
+            &names.vfn_data_volume,
+        )
+        .await,
+        delete_if_exists(
+            Api::<Deployment>::namespaced(kube_client.clone(), namespace),
+            "Deployment",
+            &names.haproxy_deployment,
+        )
+        .await,
+        delete_if_exists(
+            Api::<ConfigMap>::namespaced(kube_client, namespace),
+            "ConfigMap",
+            &names.haproxy_config_map,
+        )
+        .await,
+    ];
+    for service in &names.haproxy_services {
+        results.push(delete_if_exists(services.clone(), "Service", service).await);
+    }
+    report_deletions(
+        results,
+        &format!("the VFN and HAProxy of {}", names.vfn_service),
+    )
+}
+
+fn report_deletions(results: Vec<Result<()>>, of: &str) -> Result<()> {
+    let errors: Vec<_> = results
+        .into_iter()
+        .filter_map(|result| result.err())
+        .map(|e| e.to_string())
+        .collect();
+    if !errors.is_empty() {
+        return Err(format_err!(
+            "Failed to delete the resources of {}: {}",
+            of,
+            errors.join("; ")
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        MockConfigMapApi, MockPersistentVolumeClaimApi, MockSecretApi, MockServiceApi,
+        MockStatefulSetApi,
+    };
+    use k8s_openapi::api::core::v1::{
+        ConfigMapVolumeSource, Container, PersistentVolumeClaimVolumeSource, PodSpec,
+        SecretVolumeSource, Volume,
+    };
+
+    const ERA: &str = "42069";
+
+    fn labels(pairs: &[(&str, &str)]) -> Option<BTreeMap<String, String>> {
+        Some(
+            pairs
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+        )
+    }
+
+    fn selector_labels() -> Option<BTreeMap<String, String>> {
+        labels(&[
+            ("app.kubernetes.io/part-of", "aptos-node"),
+            ("app.kubernetes.io/name", "validator"),
+            (INSTANCE_LABEL, "validator-0"),
+        ])
+    }
+
+    /// A validator 0 StatefulSet that looks like one created by terraform/helm/aptos-node/templates/validator.yaml
+    fn get_dummy_validator_stateful_set() -> StatefulSet {
+        let names = ValidatorResourceNames::new(0, ERA);
+        StatefulSet {
+            metadata: ObjectMeta {
+                name: Some(names.stateful_set.clone()),
+                namespace: Some("forge42069".to_string()),
+                labels: selector_labels(),
+                uid: Some("7c1cb1f0".to_string()),
+                ..ObjectMeta::default()
+            },
+            spec: Some(StatefulSetSpec {
+                replicas: Some(0),
+                service_name: names.service.clone(),
+                selector: LabelSelector {
+                    match_labels: selector_labels(),
+                    ..LabelSelector::default()
+                },
+                template: PodTemplateSpec {
+                    metadata: Some(ObjectMeta {
+                        labels: selector_labels(),
+                        ..ObjectMeta::default()
+                    }),
+                    spec: Some(PodSpec {
+                        containers: vec![Container {
+                            name: VALIDATOR_CONTAINER_NAME.to_string(),
+                            image: Some(
+                                "banana.fruit.aptos/potato/validator:banana_image_tag".to_string(),
+                            ),
+                            ..Container::default()
+                        }],
+                        volumes: Some(vec![
+                            Volume {
+                                name: APTOS_CONFIG_VOLUME_NAME.to_string(),
+                                config_map: Some(ConfigMapVolumeSource {
+                                    name: Some(names.config_map.clone()),
+                                    ..ConfigMapVolumeSource::default()
+                                }),
+                                ..Volume::default()
+                            },
+                            Volume {
+                                name: GENESIS_CONFIG_VOLUME_NAME.to_string(),
+                                secret: Some(SecretVolumeSource {
+                                    secret_name: Some(names.genesis_secret.clone()),
+                                    ..SecretVolumeSource::default()
+                                }),
+                                ..Volume::default()
+                            },
+                            Volume {
+                                name: APTOS_DATA_VOLUME_NAME.to_string(),
+                                persistent_volume_claim: Some(PersistentVolumeClaimVolumeSource {
+                                    claim_name: names.data_volume.clone(),
+                                    ..PersistentVolumeClaimVolumeSource::default()
+                                }),
+                                ..Volume::default()
+                            },
+                        ]),
+                        ..PodSpec::default()
+                    }),
+                },
+                ..StatefulSetSpec::default()
+            }),
+            ..StatefulSet::default()
+        }
+    }
+
+    fn get_dummy_validator_service() -> Service {
+        Service {
+            metadata: ObjectMeta {
+                name: Some(ValidatorResourceNames::new(0, ERA).service),
+                ..ObjectMeta::default()
+            },
+            spec: Some(ServiceSpec {
+                selector: selector_labels(),
+                cluster_ip: Some("10.0.0.7".to_string()),
+                ports: Some(vec![ServicePort {
+                    name: Some("validator".to_string()),
+                    port: VALIDATOR_NETWORK_PORT as i32,
+                    node_port: Some(31234),
+                    ..ServicePort::default()
+                }]),
+                ..ServiceSpec::default()
+            }),
+            ..Service::default()
+        }
+    }
+
+    fn new_validator() -> NewValidator {
+        NewValidator::from_keygen(4, ERA, &mut KeyGen::from_seed([7; 32])).unwrap()
+    }
+
+    #[test]
+    fn test_validator_resource_names() {
+        assert_eq!(
+            ValidatorResourceNames::new(4, ERA),
+            ValidatorResourceNames {
+                stateful_set: "aptos-node-4-validator".to_string(),
+                service: "aptos-node-4-validator".to_string(),
+                config_map: "aptos-node-4".to_string(),
+                genesis_secret: "aptos-node-4-genesis-e42069".to_string(),
+                data_volume: "aptos-node-4-validator-e42069".to_string(),
+                era: "42069".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    /// Test that the created StatefulSet runs the given image on the new validator's volumes, and
+    /// that its pods are selected by the created Service only
+    fn test_create_validator_stateful_set_and_service() {
+        let names = ValidatorResourceNames::new(4, ERA);
+        let stateful_set = create_validator_stateful_set(
+            &names,
+            4,
+            "banana.fruit.aptos/potato/validator:apple".to_string(),
+            &get_dummy_validator_stateful_set(),
+        )
+        .unwrap();
+        let service = create_validator_service(&names, 4, &get_dummy_validator_service()).unwrap();
+
+        assert_eq!(stateful_set.metadata.name, Some(names.stateful_set.clone()));
+        assert_eq!(stateful_set.metadata.uid, None);
+        let spec = stateful_set.spec.unwrap();
+        assert_eq!(spec.replicas, Some(1));
+        assert_eq!(spec.service_name, names.service);
+        let pod_labels = spec.template.metadata.unwrap().labels.unwrap();
+        assert_eq!(pod_labels[INSTANCE_LABEL], "validator-4");
+        assert_eq!(spec.selector.match_labels.as_ref(), Some(&pod_labels));
+
+        let pod_spec = spec.template.spec.unwrap();
+        assert_eq!(
+            pod_spec.containers[0].image.as_deref(),
+            Some("banana.fruit.aptos/potato/validator:apple")
+        );
+        let volumes = pod_spec.volumes.unwrap();
+        assert_eq!(
+            volumes[0].config_map.as_ref().unwrap().name,
+            Some(names.config_map.clone())
+        );
+        assert_eq!(
+            volumes[1].secret.as_ref().unwrap().secret_name,
+            Some(names.genesis_secret.clone())
+        );
+        assert_eq!(
+            volumes[2]
+                .persistent_volume_claim
+                .as_ref()
+                .unwrap()
+                .claim_name,
+            names.data_volume
+        );
+
+        assert_eq!(service.metadata.name, Some(names.service));
+        let service_spec = service.spec.unwrap();
+        assert_eq!(service_spec.selector, Some(pod_labels));
+        assert_eq!(service_spec.cluster_ip, None);
+        assert_eq!(service_spec.ports.unwrap()[0].node_port, None);
+    }
+
+    #[test]
+    fn test_create_validator_genesis_secret() {
+        let validator = new_validator();
+        let template = Secret {
+            metadata: ObjectMeta {
+                name: Some(ValidatorResourceNames::new(0, ERA).genesis_secret),
+                ..ObjectMeta::default()
+            },
+            data: Some(
+                [
+                    ("genesis.blob", "genesis"),
+                    ("waypoint.txt", "0:abcd"),
+                    (VALIDATOR_IDENTITY_KEY, "validator 0"),
+                ]
+                .iter()
+                .map(|(key, value)| (key.to_string(), ByteString(value.as_bytes().to_vec())))
+                .collect(),
+            ),
+            ..Secret::default()
+        };
+
+        let secret = create_validator_genesis_secret(
+            &validator.names,
+            &template,
+            &validator.validator_identity,
+            &validator.vfn_identity,
+        )
+        .unwrap();
+        assert_eq!(secret.metadata.name, Some(validator.names.genesis_secret));
+        let identity = validator_identity_from_secret(&secret).unwrap();
+        assert_eq!(identity.account_address, Some(validator.peer_id()));
+        assert!(identity.account_private_key.is_some());
+        let data = secret.data.unwrap();
+        assert_eq!(data["waypoint.txt"].0, b"0:abcd");
+        assert!(data.contains_key(VALIDATOR_FULLNODE_IDENTITY_KEY));
+
+        // the template's identity is no identity
+        validator_identity_from_secret(&template).unwrap_err();
+        validator_identity_from_secret(&Secret::default()).unwrap_err();
+    }
+
+    #[test]
+    fn test_with_rotated_identity() {
+        let validator = new_validator();
+        let secret = create_validator_genesis_secret(
+            &validator.names,
+            &Secret::default(),
+            &validator.validator_identity,
+            &validator.vfn_identity,
+        )
+        .unwrap();
+        let mut keygen = KeyGen::from_seed([8; 32]);
+        let (rotated_secret, rotation) = with_rotated_identity(&secret, &mut keygen).unwrap();
+        assert_eq!(
+            Some(&rotation.old_consensus_public_key),
+            validator.public_identity.consensus_public_key.as_ref()
+        );
+        assert_eq!(
+            Some(rotation.old_network_public_key),
+            validator.public_identity.validator_network_public_key
+        );
+        assert_ne!(
+            rotation.consensus_public_key,
+            rotation.old_consensus_public_key
+        );
+        assert_ne!(rotation.network_public_key, rotation.old_network_public_key);
+        rotation
+            .proof_of_possession
+            .verify(&rotation.consensus_public_key)
+            .unwrap();
+        // the validator keeps running with its old identity until the rotation is applied
+        let identity = validator_identity_from_secret(&rotated_secret).unwrap();
+        assert_eq!(
+            identity.network_private_key.public_key(),
+            rotation.old_network_public_key
+        );
+
+        // an unfinished rotation is picked up again
+        let (_, again) = with_rotated_identity(&rotated_secret, &mut keygen).unwrap();
+        assert_eq!(again.consensus_public_key, rotation.consensus_public_key);
+        assert_eq!(again.network_public_key, rotation.network_public_key);
+
+        let applied = with_applied_rotated_identity(&rotated_secret).unwrap();
+        let identity = validator_identity_from_secret(&applied).unwrap();
+        assert_eq!(identity.account_address, Some(validator.peer_id()));
+        assert_eq!(
+            identity.consensus_private_key.unwrap().public_key(),
+            rotation.consensus_public_key
+        );
+        assert_eq!(
+            identity.network_private_key.public_key(),
+            rotation.network_public_key
+        );
+        assert!(!applied
+            .data
+            .unwrap()
+            .contains_key(ROTATED_VALIDATOR_IDENTITY_KEY));
+        with_applied_rotated_identity(&secret).unwrap_err();
+    }
+
+    #[tokio::test]
+    async fn test_install_validator_without_template() {
+        // validator 0 has no Service to copy
+        let error = install_validator(
+            Arc::new(MockStatefulSetApi::from_stateful_set(
+                get_dummy_validator_stateful_set(),
+            )),
+            Arc::new(MockConfigMapApi::from_config_map(ConfigMap::default())),
+            Arc::new(MockPersistentVolumeClaimApi::from_persistent_volume_claim(
+                PersistentVolumeClaim::default(),
+            )),
+            Arc::new(MockServiceApi::from_service(Service::default())),
+            Arc::new(MockSecretApi::from_secret(None)),
+            &new_validator(),
+            0,
+            ERA,
+            &Version::new(0, "apple".to_string()),
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<K8sError>(),
+            Some(K8sError::NotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn test_validator_network_address() {
+        let validator = new_validator();
+        let address = validator.network_address("forge42069").unwrap();
+        assert!(address
+            .to_string()
+            .starts_with("/dns/aptos-node-4-validator.forge42069.svc/tcp/6180/noise-ik/"));
+        assert_eq!(
+            address.find_noise_proto(),
+            validator.public_identity.validator_network_public_key
+        );
+    }
+}
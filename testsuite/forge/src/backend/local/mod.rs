@@ -2,7 +2,10 @@
 // Parts of the project are originally copyright © Meta Platforms, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::{Factory, GenesisConfig, GenesisConfigFn, NodeConfigFn, Result, Swarm, Version};
+use crate::{
+    Factory, GenesisConfig, GenesisConfigFn, NodeConfigFn, NodeVersions, RestClientOptions, Result,
+    Swarm, Version,
+};
 use anyhow::{bail, Context};
 use aptos_config::config::{NodeConfig, OverrideNodeConfig};
 use aptos_framework::ReleaseBundle;
@@ -48,6 +51,7 @@ impl LocalVersion {
 pub struct LocalFactory {
     versions: Arc<HashMap<Version, LocalVersion>>,
     swarm_dir: Option<String>,
+    rest_client_options: RestClientOptions,
 }
 
 impl LocalFactory {
@@ -55,9 +59,16 @@ impl LocalFactory {
         Self {
             versions: Arc::new(versions),
             swarm_dir,
+            rest_client_options: RestClientOptions::default(),
         }
     }
 
+    /// Build the REST clients of the swarms' nodes with these options
+    pub fn with_rest_client_options(mut self, rest_client_options: RestClientOptions) -> Self {
+        self.rest_client_options = rest_client_options;
+        self
+    }
+
     pub fn from_workspace(swarm_dir: Option<String>) -> Result<Self> {
         let mut versions = HashMap::new();
         let new_version = cargo::get_aptos_node_binary_from_worktree().map(|(revision, bin)| {
@@ -143,6 +154,7 @@ impl LocalFactory {
             genesis_framework,
             guard,
         )?;
+        swarm.set_rest_client_options(self.rest_client_options.clone());
 
         // Launch the swarm
         swarm
@@ -172,6 +184,11 @@ impl Factory for LocalFactory {
         Box::new(self.versions.keys().cloned())
     }
 
+    // each swarm gets a temporary directory of its own, unless the directory is given
+    fn use_fresh_namespace(&self) -> bool {
+        self.swarm_dir.is_none()
+    }
+
     async fn launch_swarm(
         &self,
         rng: &mut StdRng,
@@ -184,7 +201,16 @@ impl Factory for LocalFactory {
         _genesis_config_fn: Option<GenesisConfigFn>,
         _node_config_fn: Option<NodeConfigFn>,
         _existing_db_tag: Option<String>,
+        node_versions: Option<&NodeVersions>,
     ) -> Result<Box<dyn Swarm>> {
+        if node_versions.map_or(false, |versions| versions.versions().len() > 1) {
+            bail!("local forge backend does not support swarms starting on mixed versions");
+        }
+        if node_versions.map_or(false, |versions| {
+            versions.validator_image_repo.is_some() || versions.fullnode_image_repo.is_some()
+        }) {
+            bail!("local forge backend does not run images");
+        }
         let framework = match genesis_config {
             Some(config) => match config {
                 GenesisConfig::Bundle(bundle) => Some(bundle.clone()),
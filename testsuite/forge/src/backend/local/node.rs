@@ -2,11 +2,15 @@
 // Parts of the project are originally copyright © Meta Platforms, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::{FullNode, HealthCheckError, LocalVersion, Node, NodeExt, Validator, Version};
+use crate::{
+    fetch_connected_peers, fetch_counter, FullNode, HealthCheckError, LocalVersion,
+    MetricsPortForward, Node, NodeExt, RestClientOptions, ServiceEndpoint, Validator, Version,
+};
 use anyhow::{anyhow, ensure, Context, Result};
 use aptos_config::{
     config::{NodeConfig, SECURE_STORAGE_FILENAME},
     keys::ConfigKey,
+    network_id::NetworkId,
 };
 use aptos_db::{
     common::{LEDGER_DB_NAME, STATE_MERKLE_DB_NAME},
@@ -57,6 +61,7 @@ pub struct LocalNode {
     peer_id: AccountAddress,
     directory: PathBuf,
     config: NodeConfig,
+    rest_client_options: RestClientOptions,
 }
 
 impl LocalNode {
@@ -88,9 +93,14 @@ impl LocalNode {
             peer_id,
             directory,
             config,
+            rest_client_options: RestClientOptions::default(),
         })
     }
 
+    pub fn set_rest_client_options(&mut self, rest_client_options: RestClientOptions) {
+        self.rest_client_options = rest_client_options;
+    }
+
     pub fn base_dir(&self) -> PathBuf {
         self.directory.clone()
     }
@@ -277,6 +287,10 @@ impl Node for LocalNode {
         Url::from_str(&format!("http://{}:{}/v1", ip, port)).expect("Invalid URL.")
     }
 
+    fn rest_client_options(&self) -> RestClientOptions {
+        self.rest_client_options.clone()
+    }
+
     fn inspection_service_endpoint(&self) -> Url {
         Url::parse(&format!(
             "http://localhost:{}",
@@ -285,8 +299,8 @@ impl Node for LocalNode {
         .unwrap()
     }
 
-    fn config(&self) -> &NodeConfig {
-        self.config()
+    async fn config(&self) -> Result<NodeConfig> {
+        Ok(self.config().clone())
     }
 
     async fn start(&self) -> Result<()> {
@@ -298,6 +312,12 @@ impl Node for LocalNode {
         Ok(())
     }
 
+    // stopping a local node already sends SIGKILL to the process
+    async fn kill(&self) -> Result<()> {
+        self.stop();
+        Ok(())
+    }
+
     async fn get_identity(&self) -> Result<String> {
         todo!()
     }
@@ -367,18 +387,33 @@ impl Node for LocalNode {
         self.health_check().await
     }
 
-    fn counter(&self, _counter: &str, _port: u64) -> Result<f64> {
-        todo!()
+    async fn counter(&self, counter: &str) -> Result<f64> {
+        fetch_counter("localhost", self.inspection_service_port() as u64, counter).await
+    }
+
+    // local node does not need a port-forward, the inspection service is already local
+    async fn expose_metric(&self) -> Result<MetricsPortForward> {
+        Ok(MetricsPortForward::new(
+            self.inspection_service_port() as u64,
+            None,
+        ))
     }
 
-    // local node does not need to expose metric end point
-    fn expose_metric(&self) -> Result<u64> {
-        Ok(0)
+    async fn admin_service_endpoint(&self) -> Result<ServiceEndpoint> {
+        let url = Url::parse(&format!(
+            "http://localhost:{}",
+            self.config.admin_service.port
+        ))?;
+        Ok(ServiceEndpoint::new(url, None))
     }
 
     fn service_name(&self) -> Option<String> {
         None
     }
+
+    async fn get_connected_peer_ids(&self, network_id: NetworkId) -> Result<Vec<PeerId>> {
+        fetch_connected_peers(self.inspection_service_endpoint(), network_id).await
+    }
 }
 
 impl Validator for LocalNode {}
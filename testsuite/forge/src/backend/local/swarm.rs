@@ -3,8 +3,12 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::{
-    ChainInfo, FullNode, HealthCheckError, LocalNode, LocalVersion, Node, Swarm, SwarmChaos,
-    SwarmExt, Validator, Version,
+    collect_with_timeout, next_faulty_validator, prometheus_metrics::PrometheusUnavailable,
+    query_sequence_number, set_faulty_failpoints, set_stake, ArtifactManifest, ChainInfo,
+    DbSnapshotOptions, FaultyBehavior, FullNode, HealthCheckError, InfrastructureEvent,
+    KeyRotationResult, LocalNode, LocalVersion, NewValidatorOptions, Node, NodeArtifacts,
+    NodeResources, NodeRestarts, RestClientOptions, Swarm, SwarmChaos, SwarmExt, Validator,
+    Version, ARTIFACT_COLLECTION_TIMEOUT,
 };
 use anyhow::{anyhow, bail, Result};
 use aptos_config::{
@@ -25,6 +29,7 @@ use aptos_sdk::{
         PeerId,
     },
 };
+use futures::FutureExt;
 use prometheus_http_query::response::{PromqlResult, Sample};
 use std::{
     collections::HashMap,
@@ -99,6 +104,10 @@ pub struct LocalSwarm {
     root_account: Arc<LocalAccount>,
     chain_id: ChainId,
     root_key: ConfigKey<Ed25519PrivateKey>,
+    // applied to the nodes added later as well
+    rest_client_options: RestClientOptions,
+    // the validators designated faulty, and how they misbehave
+    faulty_validators: HashMap<PeerId, FaultyBehavior>,
 
     launched: bool,
     #[allow(dead_code)]
@@ -259,11 +268,25 @@ impl LocalSwarm {
             root_account,
             chain_id: ChainId::test(),
             root_key,
+            rest_client_options: RestClientOptions::default(),
+            faulty_validators: HashMap::new(),
             launched: false,
             guard,
         })
     }
 
+    /// Build the REST clients of all the nodes, including the ones added later, with these options
+    pub fn set_rest_client_options(&mut self, rest_client_options: RestClientOptions) {
+        for node in self
+            .validators
+            .values_mut()
+            .chain(self.fullnodes.values_mut())
+        {
+            node.set_rest_client_options(rest_client_options.clone());
+        }
+        self.rest_client_options = rest_client_options;
+    }
+
     pub async fn launch(&mut self) -> Result<()> {
         if self.launched {
             return Err(anyhow!("Swarm already launched"));
@@ -316,10 +339,18 @@ impl LocalSwarm {
                             error
                         ));
                     },
+                    Err(HealthCheckError::Unrecoverable(e)) => {
+                        return Err(e.context(format!("Node '{}' will not start", node.name())));
+                    },
                     Err(HealthCheckError::Failure(e)) => {
                         warn!("health check failure: {}", e);
                         break;
                     },
+                    Err(HealthCheckError::Stale(error))
+                    | Err(HealthCheckError::OomKilledByChaos(error)) => {
+                        warn!("health check failure: {}", error);
+                        break;
+                    },
                 }
             }
 
@@ -368,13 +399,14 @@ impl LocalSwarm {
         )?;
 
         let version = self.versions.get(version).unwrap();
-        let fullnode = LocalNode::new(
+        let mut fullnode = LocalNode::new(
             version.to_owned(),
             fullnode_config.name,
             index,
             fullnode_config.dir,
             None,
         )?;
+        fullnode.set_rest_client_options(self.rest_client_options.clone());
 
         let peer_id = fullnode.peer_id();
         assert_eq!(peer_id, validator_peer_id);
@@ -398,13 +430,14 @@ impl LocalSwarm {
         )?;
 
         let version = self.versions.get(version).unwrap();
-        let fullnode = LocalNode::new(
+        let mut fullnode = LocalNode::new(
             version.to_owned(),
             fullnode_config.name,
             index,
             fullnode_config.dir,
             None,
         )?;
+        fullnode.set_rest_client_options(self.rest_client_options.clone());
 
         let peer_id = fullnode.peer_id();
         fullnode.start()?;
@@ -465,6 +498,23 @@ impl LocalSwarm {
     pub fn dir(&self) -> &Path {
         self.dir.as_ref()
     }
+
+    /// The chain info with the REST API of one of the nodes, and the REST APIs of all the
+    /// validators to wait on
+    fn chain_info_of(&self, rest_api_url: String, inspection_service_url: String) -> ChainInfo {
+        ChainInfo::new(
+            self.root_account.clone(),
+            rest_api_url,
+            inspection_service_url,
+            self.chain_id,
+        )
+        .with_validator_rest_api_urls(
+            self.validators()
+                .map(|v| v.rest_api_endpoint().to_string())
+                .collect(),
+        )
+        .with_rest_client_options(self.rest_client_options.clone())
+    }
 }
 
 impl Drop for LocalSwarm {
@@ -509,6 +559,10 @@ impl Swarm for LocalSwarm {
         validator.upgrade(version)
     }
 
+    async fn set_node_resources(&mut self, _id: PeerId, _resources: &NodeResources) -> Result<()> {
+        bail!("Local nodes have no resource limits to set")
+    }
+
     fn full_nodes<'a>(&'a self) -> Box<dyn Iterator<Item = &'a dyn FullNode> + 'a> {
         let mut full_nodes: Vec<_> = self
             .fullnodes
@@ -523,14 +577,104 @@ impl Swarm for LocalSwarm {
         self.fullnodes.get(&id).map(|v| v as &dyn FullNode)
     }
 
-    fn add_validator(&mut self, _version: &Version, _template: NodeConfig) -> Result<PeerId> {
+    fn fullnodes_of(&self, validator: PeerId) -> Vec<&dyn FullNode> {
+        // a local VFN shares the peer id of its validator
+        self.fullnodes
+            .get(&validator)
+            .map(|fullnode| fullnode as &dyn FullNode)
+            .into_iter()
+            .collect()
+    }
+
+    async fn attach_fullnode(&mut self, _validator: PeerId, _version: &Version) -> Result<PeerId> {
+        bail!("Local swarms run at most one fullnode per validator")
+    }
+
+    fn infrastructure_events(&self) -> Vec<InfrastructureEvent> {
+        vec![]
+    }
+
+    fn public_fullnodes<'a>(&'a self) -> Box<dyn Iterator<Item = &'a dyn FullNode> + 'a> {
+        Box::new(std::iter::empty())
+    }
+
+    async fn deploy_public_fullnodes(
+        &mut self,
+        _count: usize,
+        _version: &Version,
+        _config: NodeConfig,
+    ) -> Result<Vec<PeerId>> {
+        bail!("Public fullnode deployments are only supported on k8s swarms")
+    }
+
+    async fn add_validator(
+        &mut self,
+        _version: &Version,
+        _options: NewValidatorOptions,
+    ) -> Result<PeerId> {
         todo!()
     }
 
-    fn remove_validator(&mut self, _id: PeerId) -> Result<()> {
+    async fn remove_validator(&mut self, _id: PeerId) -> Result<()> {
         todo!()
     }
 
+    async fn deploy_faulty_validator(&mut self, behavior: FaultyBehavior) -> Result<PeerId> {
+        let validators: Vec<_> = self
+            .validators()
+            .map(|validator| validator.peer_id())
+            .collect();
+        let faulty: Vec<_> = self.faulty_validators.keys().copied().collect();
+        let id = next_faulty_validator(&validators, &faulty)?;
+        if let FaultyBehavior::Image(version) = &behavior {
+            self.upgrade_validator(id, version).await?;
+        }
+        let validator = self
+            .validator(id)
+            .ok_or_else(|| anyhow!("Invalid id: {}", id))?;
+        set_faulty_failpoints(validator, &behavior).await?;
+        self.faulty_validators.insert(id, behavior);
+        Ok(id)
+    }
+
+    fn faulty_validators(&self) -> Vec<PeerId> {
+        self.faulty_validators.keys().copied().collect()
+    }
+
+    async fn rotate_validator_keys(&mut self, _id: PeerId) -> Result<KeyRotationResult> {
+        bail!("Key rotation is only supported on k8s swarms")
+    }
+
+    async fn validator_owner_account(&self, id: PeerId) -> Result<LocalAccount> {
+        let validator = self
+            .validators
+            .get(&id)
+            .ok_or_else(|| anyhow!("Invalid id: {}", id))?;
+        let private_key = validator
+            .account_private_key()
+            .as_ref()
+            .ok_or_else(|| anyhow!("Validator {} has no account key", validator.name()))?
+            .private_key();
+        let sequence_number = query_sequence_number(&self.chain_info().rest_client(), id).await?;
+        Ok(LocalAccount::new(id, private_key, sequence_number))
+    }
+
+    async fn set_validator_stake(
+        &mut self,
+        id: PeerId,
+        amount: u64,
+        wait_for_epoch: bool,
+    ) -> Result<()> {
+        let account = self.validator_owner_account(id).await?;
+        set_stake(
+            &mut self.aptos_public_info(),
+            &account,
+            amount,
+            wait_for_epoch,
+        )
+        .await
+    }
+
     fn add_validator_full_node(
         &mut self,
         version: &Version,
@@ -574,12 +718,7 @@ impl Swarm for LocalSwarm {
             .inspection_service_endpoint()
             .to_string();
 
-        ChainInfo::new(
-            self.root_account.clone(),
-            rest_api_url,
-            inspection_service_url,
-            self.chain_id,
-        )
+        self.chain_info_of(rest_api_url, inspection_service_url)
     }
 
     fn logs_location(&mut self) -> String {
@@ -587,6 +726,40 @@ impl Swarm for LocalSwarm {
         self.dir.display().to_string()
     }
 
+    async fn collect_artifacts(
+        &self,
+        dir: &Path,
+        _since: Option<Duration>,
+    ) -> Result<ArtifactManifest> {
+        // the log of a local node spans its restarts
+        let collections = self
+            .validators
+            .values()
+            .chain(self.fullnodes.values())
+            .map(|node| {
+                let placeholder = NodeArtifacts::new(node.name(), node.peer_id());
+                let mut artifacts = placeholder.clone();
+                let collection = async move {
+                    let log = fs::read_to_string(node.log_path()).map_err(Into::into);
+                    artifacts.write(dir, "current.log", log);
+                    artifacts
+                };
+                (placeholder, collection.boxed())
+            })
+            .collect();
+        collect_with_timeout(dir, collections, ARTIFACT_COLLECTION_TIMEOUT).await
+    }
+
+    async fn snapshot_db(
+        &self,
+        _id: PeerId,
+        _dest: &Path,
+        _options: &DbSnapshotOptions,
+    ) -> Result<PathBuf> {
+        // the DB of a local node is on this machine already
+        bail!("DB snapshots are only supported on k8s swarms")
+    }
+
     async fn inject_chaos(&mut self, _chaos: SwarmChaos) -> Result<()> {
         todo!()
     }
@@ -595,6 +768,10 @@ impl Swarm for LocalSwarm {
         todo!()
     }
 
+    async fn update_chaos(&mut self, _chaos: SwarmChaos) -> Result<()> {
+        bail!("Updating chaos in place is only supported on k8s swarms")
+    }
+
     async fn remove_all_chaos(&mut self) -> Result<()> {
         todo!()
     }
@@ -607,13 +784,18 @@ impl Swarm for LocalSwarm {
         todo!()
     }
 
+    // local nodes are processes, without restart counts to read
+    async fn restarts(&self) -> Result<Vec<NodeRestarts>> {
+        Ok(vec![])
+    }
+
     async fn query_metrics(
         &self,
         _query: &str,
         _time: Option<i64>,
         _timeout: Option<i64>,
     ) -> Result<PromqlResult> {
-        todo!()
+        Err(PrometheusUnavailable.into())
     }
 
     async fn query_range_metrics(
@@ -623,7 +805,7 @@ impl Swarm for LocalSwarm {
         _end_time: i64,
         _timeout: Option<i64>,
     ) -> Result<Vec<Sample>> {
-        todo!()
+        Err(PrometheusUnavailable.into())
     }
 
     fn chain_info_for_node(&mut self, idx: usize) -> ChainInfo {
@@ -639,12 +821,7 @@ impl Swarm for LocalSwarm {
             .unwrap()
             .inspection_service_endpoint()
             .to_string();
-        ChainInfo::new(
-            self.root_account.clone(),
-            rest_api_url,
-            inspection_service_url,
-            self.chain_id,
-        )
+        self.chain_info_of(rest_api_url, inspection_service_url)
     }
 
     fn get_default_pfn_node_config(&self) -> NodeConfig {
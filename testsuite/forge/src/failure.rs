@@ -0,0 +1,108 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{K8sError, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use thiserror::Error;
+
+/// Whether a test failed because of the cluster it ran on, e.g. an image pull timeout or a failed
+/// request to the kube API, or because of the test itself, e.g. the nodes not committing or
+/// crashing. Only infra failures are worth retrying, see the --retry-infra-failures option of the
+/// runner.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureCategory {
+    Infra,
+    Test,
+}
+
+impl FailureCategory {
+    /// The category of the error: infra if an [InfraFailure] or an infra [K8sError] caused it,
+    /// test otherwise
+    pub fn of(error: &anyhow::Error) -> Self {
+        let infra = error.downcast_ref::<InfraFailure>().is_some()
+            || error.chain().any(|e| {
+                e.downcast_ref::<InfraFailure>().is_some()
+                    || e.downcast_ref::<K8sError>()
+                        .map_or(false, K8sError::is_infra)
+            });
+        if infra {
+            FailureCategory::Infra
+        } else {
+            FailureCategory::Test
+        }
+    }
+}
+
+impl Display for FailureCategory {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FailureCategory::Infra => write!(f, "infra"),
+            FailureCategory::Test => write!(f, "test"),
+        }
+    }
+}
+
+/// A failure of the infrastructure the swarm runs on, see [FailureCategory]. Backends return it,
+/// or add it as context with [InfraContext], for failures of their own that are not [K8sError]s.
+#[derive(Error, Debug)]
+#[error("{0}")]
+pub struct InfraFailure(pub String);
+
+pub trait InfraContext<T> {
+    /// Adds the context to the error, classifying it as an infra failure
+    fn infra_context<C: Display>(self, context: C) -> Result<T>;
+}
+
+impl<T, E: Into<anyhow::Error>> InfraContext<T> for std::result::Result<T, E> {
+    fn infra_context<C: Display>(self, context: C) -> Result<T> {
+        self.map_err(|e| e.into().context(InfraFailure(context.to_string())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::{format_err, Context};
+
+    #[test]
+    fn test_failure_category() {
+        let error = format_err!("Nodes did not catch up");
+        assert_eq!(FailureCategory::of(&error), FailureCategory::Test);
+        let error = error.context("Test failed");
+        assert_eq!(FailureCategory::of(&error), FailureCategory::Test);
+
+        let error = Err::<(), _>(format_err!("connection reset"))
+            .infra_context("Failed to create the namespace")
+            .context("Failed to launch swarm")
+            .unwrap_err();
+        assert_eq!(FailureCategory::of(&error), FailureCategory::Infra);
+        let error: anyhow::Error = InfraFailure("Upgrade not completed".to_string()).into();
+        assert_eq!(
+            FailureCategory::of(&error.context("Failed to launch swarm")),
+            FailureCategory::Infra
+        );
+
+        let pull_failing = K8sError::ContainerFailing {
+            pod: "aptos-node-0-validator-0".to_string(),
+            container: "validator".to_string(),
+            reason: "ImagePullBackOff".to_string(),
+            message: "timed out pulling".to_string(),
+            restart_count: 0,
+        };
+        assert_eq!(
+            FailureCategory::of(&anyhow::Error::from(pull_failing).context("Failed to upgrade")),
+            FailureCategory::Infra
+        );
+        // the nodes crashing is on the test
+        let crashing = K8sError::ContainerFailing {
+            pod: "aptos-node-0-validator-0".to_string(),
+            container: "validator".to_string(),
+            reason: "CrashLoopBackOff".to_string(),
+            message: "back-off restarting".to_string(),
+            restart_count: 5,
+        };
+        assert_eq!(FailureCategory::of(&crashing.into()), FailureCategory::Test);
+    }
+}
@@ -6,7 +6,6 @@ use super::{ChainInfo, CoreContext, Test};
 use crate::{Result, TestReport};
 use aptos_rest_client::Client as RestClient;
 use aptos_sdk::types::LocalAccount;
-use reqwest::Url;
 
 /// The testing interface which defines a test written from the perspective of the Admin of the
 /// network. This means that the test will have access to the Root account but do not control any
@@ -42,7 +41,7 @@ impl<'t> AdminContext<'t> {
     }
 
     pub fn rest_client(&self) -> RestClient {
-        RestClient::new(Url::parse(self.chain_info.rest_api()).unwrap())
+        self.chain_info.rest_client()
     }
 
     pub fn chain_info(&mut self) -> &mut ChainInfo {
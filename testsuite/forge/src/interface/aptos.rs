@@ -53,7 +53,7 @@ impl<'t> AptosContext<'t> {
     }
 
     pub fn client(&self) -> RestClient {
-        RestClient::new(self.public_info.rest_api_url.clone())
+        self.public_info.rest_client.clone()
     }
 
     pub fn url(&self) -> &str {
@@ -141,6 +141,12 @@ impl AptosPublicInfo {
         }
     }
 
+    /// Use this client rather than a plain one, e.g. one trusting the root CA of a TLS endpoint
+    pub fn with_rest_client(mut self, rest_client: RestClient) -> Self {
+        self.rest_client = rest_client;
+        self
+    }
+
     pub fn client(&self) -> &RestClient {
         &self.rest_client
     }
@@ -0,0 +1,187 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::Result;
+use anyhow::Context;
+use aptos_logger::warn;
+use aptos_sdk::types::PeerId;
+use futures::future::{join_all, BoxFuture};
+use serde::Serialize;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+/// How long collecting the artifacts of a whole swarm may take, so that a hung API server can't
+/// stall the teardown
+pub const ARTIFACT_COLLECTION_TIMEOUT: Duration = Duration::from_secs(300);
+pub const ARTIFACT_MANIFEST_FILE: &str = "manifest.json";
+
+/// Limits of a snapshot of a node's DB, see [crate::Swarm::snapshot_db]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DbSnapshotOptions {
+    // of the data directory, before compression, and of the archive
+    pub max_size_bytes: u64,
+    // of the whole snapshot, the node is down for most of it
+    pub timeout: Duration,
+}
+
+impl Default for DbSnapshotOptions {
+    fn default() -> Self {
+        Self {
+            max_size_bytes: 20 * 1024 * 1024 * 1024,
+            timeout: Duration::from_secs(1800),
+        }
+    }
+}
+
+/// What was collected of one node, see [crate::Swarm::collect_artifacts]
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct NodeArtifacts {
+    pub name: String,
+    pub peer_id: String,
+    // relative to the artifact directory
+    pub files: Vec<PathBuf>,
+    // why the other artifacts of the node are missing
+    pub errors: Vec<String>,
+    // the collection was cut short, so the files of the node were not listed
+    pub timed_out: bool,
+}
+
+impl NodeArtifacts {
+    pub fn new(name: &str, peer_id: PeerId) -> Self {
+        Self {
+            name: name.to_string(),
+            peer_id: peer_id.to_string(),
+            ..Self::default()
+        }
+    }
+
+    /// Writes the artifact into the directory of the node, or records why it is missing
+    pub fn write(&mut self, dir: &Path, file_name: &str, content: Result<String>) {
+        let relative_path = Path::new(&self.name).join(file_name);
+        let path = dir.join(&relative_path);
+        let written = content.and_then(|content| {
+            fs::create_dir_all(dir.join(&self.name))?;
+            fs::write(&path, content).with_context(|| format!("Failed to write {:?}", path))
+        });
+        match written {
+            Ok(()) => self.files.push(relative_path),
+            Err(e) => self.errors.push(format!("{}: {:#}", file_name, e)),
+        }
+    }
+}
+
+/// The artifacts collected of every node of a swarm, written as JSON next to them
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct ArtifactManifest {
+    pub dir: PathBuf,
+    pub nodes: Vec<NodeArtifacts>,
+}
+
+impl ArtifactManifest {
+    pub fn file_count(&self) -> usize {
+        self.nodes.iter().map(|node| node.files.len()).sum()
+    }
+
+    pub fn timed_out(&self) -> Vec<&str> {
+        self.nodes
+            .iter()
+            .filter(|node| node.timed_out)
+            .map(|node| node.name.as_str())
+            .collect()
+    }
+
+    pub fn write(&self) -> Result<PathBuf> {
+        let path = self.dir.join(ARTIFACT_MANIFEST_FILE);
+        fs::write(&path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write the artifact manifest {:?}", path))?;
+        Ok(path)
+    }
+}
+
+/// Runs the collections of the nodes all at once, those not done within the timeout counting as
+/// timed out, and writes the manifest
+pub async fn collect_with_timeout(
+    dir: &Path,
+    collections: Vec<(NodeArtifacts, BoxFuture<'_, NodeArtifacts>)>,
+    timeout: Duration,
+) -> Result<ArtifactManifest> {
+    fs::create_dir_all(dir)
+        .with_context(|| format!("Failed to create the artifact directory {:?}", dir))?;
+    let nodes = join_all(
+        collections
+            .into_iter()
+            .map(|(mut placeholder, collection)| async move {
+                match tokio::time::timeout(timeout, collection).await {
+                    Ok(artifacts) => artifacts,
+                    Err(_) => {
+                        warn!(
+                            "Collecting the artifacts of {} timed out after {:?}",
+                            placeholder.name, timeout
+                        );
+                        placeholder.timed_out = true;
+                        placeholder
+                    },
+                }
+            }),
+    )
+    .await;
+    let manifest = ArtifactManifest {
+        dir: dir.to_path_buf(),
+        nodes,
+    };
+    manifest.write()?;
+    Ok(manifest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::format_err;
+    use futures::FutureExt;
+
+    #[tokio::test]
+    async fn test_collect_with_timeout() {
+        let dir = tempfile::tempdir().unwrap();
+        let collect = |name: &str, delay: Duration| {
+            let placeholder = NodeArtifacts::new(name, PeerId::random());
+            let mut artifacts = placeholder.clone();
+            let dir = dir.path().to_path_buf();
+            let collection = async move {
+                tokio::time::sleep(delay).await;
+                artifacts.write(&dir, "current.log", Ok("started".to_string()));
+                artifacts.write(
+                    &dir,
+                    "previous.log",
+                    Err(format_err!("no previous container")),
+                );
+                artifacts
+            };
+            (placeholder, collection.boxed())
+        };
+        let manifest = collect_with_timeout(
+            dir.path(),
+            vec![
+                collect("validator-0", Duration::ZERO),
+                collect("validator-1", Duration::from_secs(60)),
+            ],
+            Duration::from_millis(100),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(manifest.file_count(), 1);
+        assert_eq!(manifest.timed_out(), vec!["validator-1"]);
+        let node = &manifest.nodes[0];
+        assert_eq!(node.files, vec![PathBuf::from("validator-0/current.log")]);
+        assert!(node.errors[0].contains("no previous container"));
+        assert_eq!(
+            fs::read_to_string(dir.path().join("validator-0/current.log")).unwrap(),
+            "started"
+        );
+        let written = fs::read_to_string(dir.path().join(ARTIFACT_MANIFEST_FILE)).unwrap();
+        assert!(written.contains("\"timed_out\": true"));
+    }
+}
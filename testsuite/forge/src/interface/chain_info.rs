@@ -2,15 +2,23 @@
 // Parts of the project are originally copyright © Meta Platforms, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::AptosPublicInfo;
-use anyhow::Result;
-use aptos_rest_client::Client as RestClient;
+use crate::{force_end_epoch, AptosPublicInfo, ProposalOutcome, RestClientOptions};
+use anyhow::{bail, Result};
+use aptos_logger::info;
+use aptos_rest_client::{AptosBaseUrl, Client as RestClient};
 use aptos_sdk::{
     transaction_builder::TransactionFactory,
-    types::{chain_id::ChainId, LocalAccount},
+    types::{chain_id::ChainId, transaction::TransactionArgument, LocalAccount},
 };
+use futures::future::join_all;
 use reqwest::Url;
-use std::sync::Arc;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+// for all the validators to get to the epoch a reconfiguration starts
+const RECONFIGURATION_TIMEOUT: Duration = Duration::from_secs(120);
 
 #[derive(Debug)]
 pub struct ChainInfo {
@@ -18,6 +26,10 @@ pub struct ChainInfo {
     pub rest_api_url: String,
     pub inspection_service_url: String,
     pub chain_id: ChainId,
+    // of every validator, to wait on them all, see [ChainInfo::trigger_reconfiguration]
+    pub validator_rest_api_urls: Vec<String>,
+    // how the REST clients are built, e.g. with the root CA of the nodes' TLS endpoints
+    pub rest_client_options: RestClientOptions,
 }
 
 impl ChainInfo {
@@ -32,9 +44,21 @@ impl ChainInfo {
             rest_api_url,
             inspection_service_url,
             chain_id,
+            validator_rest_api_urls: vec![],
+            rest_client_options: RestClientOptions::default(),
         }
     }
 
+    pub fn with_validator_rest_api_urls(mut self, validator_rest_api_urls: Vec<String>) -> Self {
+        self.validator_rest_api_urls = validator_rest_api_urls;
+        self
+    }
+
+    pub fn with_rest_client_options(mut self, rest_client_options: RestClientOptions) -> Self {
+        self.rest_client_options = rest_client_options;
+        self
+    }
+
     pub fn root_account(&self) -> Arc<LocalAccount> {
         self.root_account.clone()
     }
@@ -52,7 +76,15 @@ impl ChainInfo {
     }
 
     pub fn rest_client(&self) -> RestClient {
-        RestClient::new(Url::parse(self.rest_api()).unwrap())
+        self.rest_client_of(self.rest_api()).unwrap()
+    }
+
+    // with the options of the swarm, as the other nodes' endpoints are alike
+    fn rest_client_of(&self, url: &str) -> Result<RestClient> {
+        Ok(self
+            .rest_client_options
+            .apply(RestClient::builder(AptosBaseUrl::Custom(Url::parse(url)?)))
+            .build())
     }
 
     pub fn chain_id(&self) -> ChainId {
@@ -63,6 +95,84 @@ impl ChainInfo {
         TransactionFactory::new(self.chain_id())
     }
 
+    /// Proposes the compiled script, see [crate::compile_proposal_script], with the first of the
+    /// voters, votes for it with all of them, waits for the voting period to end and executes it
+    /// with the proposal id followed by `args`. The voters are the owners of stake pools, and
+    /// together need the min voting threshold.
+    /// Which stage failed is told by the [crate::GovernanceError] the error downcasts to.
+    pub async fn execute_governance_proposal(
+        &self,
+        script: &[u8],
+        args: Vec<TransactionArgument>,
+        voters: &[LocalAccount],
+    ) -> Result<ProposalOutcome> {
+        crate::execute_governance_proposal(
+            &self.rest_client(),
+            &self.transaction_factory(),
+            script,
+            args,
+            voters,
+        )
+        .await
+    }
+
+    /// Ends the current epoch with the root account, and waits for every validator to be in the
+    /// next one, which is returned. The root account's sequence number is resynced first, so it
+    /// can be called again and again, also after other transactions of the root account.
+    pub async fn trigger_reconfiguration(&self) -> Result<u64> {
+        let client = self.rest_client();
+        let root_address = self.root_account.address();
+        let account = client.get_account(root_address).await?.into_inner();
+        self.root_account
+            .set_sequence_number(account.sequence_number);
+        force_end_epoch(&client, self.transaction_factory(), &self.root_account).await?;
+        let epoch = client.get_ledger_information().await?.into_inner().epoch;
+
+        let urls = if self.validator_rest_api_urls.is_empty() {
+            vec![self.rest_api_url.clone()]
+        } else {
+            self.validator_rest_api_urls.clone()
+        };
+        let clients: Vec<_> = urls
+            .iter()
+            .map(|url| self.rest_client_of(url))
+            .collect::<Result<_>>()?;
+        let deadline = Instant::now() + RECONFIGURATION_TIMEOUT;
+        loop {
+            let epochs = join_all(clients.iter().map(|client| async move {
+                client
+                    .get_ledger_information()
+                    .await
+                    .map(|state| state.into_inner().epoch)
+            }))
+            .await;
+            if epochs
+                .iter()
+                .all(|node_epoch| matches!(node_epoch, Ok(node_epoch) if *node_epoch >= epoch))
+            {
+                info!("All {} validators are in epoch {}", clients.len(), epoch);
+                return Ok(epoch);
+            }
+            if Instant::now() > deadline {
+                let epochs: Vec<_> = urls
+                    .iter()
+                    .zip(epochs)
+                    .map(|(url, node_epoch)| match node_epoch {
+                        Ok(node_epoch) => format!("{}: {}", url, node_epoch),
+                        Err(e) => format!("{}: {}", url, e),
+                    })
+                    .collect();
+                bail!(
+                    "Not all validators got to epoch {} within {:?}, their epochs are {}",
+                    epoch,
+                    RECONFIGURATION_TIMEOUT,
+                    epochs.join(", ")
+                );
+            }
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    }
+
     pub fn into_aptos_public_info(self) -> AptosPublicInfo {
         AptosPublicInfo::new(
             self.chain_id,
@@ -70,5 +180,6 @@ impl ChainInfo {
             self.rest_api_url.clone(),
             self.root_account.clone(),
         )
+        .with_rest_client(self.rest_client())
     }
 }
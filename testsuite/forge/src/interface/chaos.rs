@@ -1,17 +1,70 @@
 // Copyright © Aptos Foundation
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::{Result, Swarm, TestReport};
+use aptos_logger::info;
 use aptos_sdk::types::PeerId;
-use std::fmt::{Display, Formatter};
+use chrono::{DateTime, Utc};
+use std::{
+    fmt::{Display, Formatter},
+    time::Duration,
+};
+use tokio::sync::RwLock;
 
 #[derive(Eq, Hash, PartialEq, Debug, Clone)]
 pub enum SwarmChaos {
     Delay(SwarmNetworkDelay),
     Partition(SwarmNetworkPartition),
+    GroupPartition(SwarmGroupPartition),
     Bandwidth(SwarmNetworkBandwidth),
+    PeerBandwidth(SwarmPeerBandwidth),
     Loss(SwarmNetworkLoss),
+    PeerLoss(SwarmPeerLoss),
     NetEm(SwarmNetEm),
     CpuStress(SwarmCpuStress),
+    PeerCpuStress(SwarmPeerCpuStress),
+    PeerMemoryStress(SwarmPeerMemoryStress),
+    TimeSkew(SwarmTimeSkew),
+    DnsFailure(SwarmDnsFailure),
+}
+
+impl SwarmChaos {
+    /// The nodes the chaos affects, or None if it affects the whole swarm
+    pub fn affected_nodes(&self) -> Option<Vec<PeerId>> {
+        let mut nodes = match self {
+            SwarmChaos::Delay(c) => c
+                .group_network_delays
+                .iter()
+                .flat_map(|group| group.source_nodes.iter().chain(&group.target_nodes))
+                .cloned()
+                .collect(),
+            SwarmChaos::GroupPartition(c) => c.group_a.iter().chain(&c.group_b).cloned().collect(),
+            SwarmChaos::NetEm(c) => c
+                .group_netems
+                .iter()
+                .flat_map(|group| group.source_nodes.iter().chain(&group.target_nodes))
+                .cloned()
+                .collect(),
+            SwarmChaos::CpuStress(c) => c
+                .group_cpu_stresses
+                .iter()
+                .flat_map(|group| group.target_nodes.iter())
+                .cloned()
+                .collect(),
+            SwarmChaos::PeerBandwidth(c) => c.peers.clone(),
+            SwarmChaos::PeerLoss(c) => c.peers.clone(),
+            SwarmChaos::PeerCpuStress(c) => c.peers.clone(),
+            SwarmChaos::PeerMemoryStress(c) => c.peers.clone(),
+            SwarmChaos::TimeSkew(c) => c.peers.clone(),
+            SwarmChaos::DnsFailure(c) => c.peers.clone(),
+            SwarmChaos::Partition(_) | SwarmChaos::Bandwidth(_) | SwarmChaos::Loss(_) => {
+                return None
+            },
+        };
+        nodes.sort();
+        nodes.dedup();
+        Some(nodes)
+    }
 }
 
 #[derive(Eq, Hash, PartialEq, Debug, Clone)]
@@ -33,16 +86,87 @@ pub struct GroupNetworkDelay {
     pub latency_ms: u64,
     pub jitter_ms: u64,
     pub correlation_percentage: u64,
+    pub direction: NetworkDelayDirection,
+}
+
+/// Which packets between the source and the target nodes of a delay are delayed
+#[derive(Eq, Hash, PartialEq, Debug, Clone, Copy)]
+pub enum NetworkDelayDirection {
+    // from the source to the target nodes only
+    To,
+    // from the target to the source nodes only
+    From,
+    // both ways, so a round trip is delayed twice
+    Both,
 }
 
+impl Display for NetworkDelayDirection {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        // as Chaos Mesh calls them
+        f.write_str(match self {
+            NetworkDelayDirection::To => "to",
+            NetworkDelayDirection::From => "from",
+            NetworkDelayDirection::Both => "both",
+        })
+    }
+}
+
+/// Partitions the given share of the validators from all of them. Group A of the direction is
+/// all the validators, and group B the partitioned share.
 #[derive(Eq, Hash, PartialEq, Debug, Clone)]
 pub struct SwarmNetworkPartition {
     pub partition_percentage: u64,
+    pub direction: PartitionDirection,
 }
 
 impl Display for SwarmNetworkPartition {
     fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
-        write!(f, "Partition {} nodes", self.partition_percentage)
+        write!(
+            f,
+            "Partition {} nodes {}",
+            self.partition_percentage, self.direction
+        )
+    }
+}
+
+/// Splits the given nodes into two groups that can't reach each other, or only one way, while the
+/// nodes of each group still can
+#[derive(Eq, Hash, PartialEq, Debug, Clone)]
+pub struct SwarmGroupPartition {
+    pub group_a: Vec<PeerId>,
+    pub group_b: Vec<PeerId>,
+    pub direction: PartitionDirection,
+}
+
+impl Display for SwarmGroupPartition {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "Partition nodes {:?} from nodes {:?} {}",
+            self.group_a, self.group_b, self.direction
+        )
+    }
+}
+
+/// Which way a partition drops the packets between its groups. Dropping them one way only still
+/// stalls TCP connections both ways, as the acks of the other way get lost, but unlike a
+/// symmetric partition it lets some messages through until the connections time out.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum PartitionDirection {
+    Both,
+    // group A can't send to group B, but receives from it
+    FromAToB,
+    // group B can't send to group A, but receives from it
+    FromBToA,
+}
+
+impl Display for PartitionDirection {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            PartitionDirection::Both => write!(f, "both ways"),
+            PartitionDirection::FromAToB => write!(f, "from A to B"),
+            PartitionDirection::FromBToA => write!(f, "from B to A"),
+        }
     }
 }
 
@@ -66,18 +190,62 @@ pub struct GroupNetworkBandwidth {
     pub buffer: u64,
 }
 
+/// Throttles the links of the given nodes to the rest of the swarm, both ways, as if they were
+/// behind constrained links
+#[derive(Eq, Hash, PartialEq, Debug, Clone)]
+pub struct SwarmPeerBandwidth {
+    pub peers: Vec<PeerId>,
+    pub rate_kbps: u64,
+    // the bytes that may be queued, see tc-tbf
+    pub limit_bytes: u64,
+    // the bytes that may be sent at once, above the rate
+    pub buffer_bytes: u64,
+}
+
+impl Display for SwarmPeerBandwidth {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "Bandwidth of nodes {:?} limited to {}kbps",
+            self.peers, self.rate_kbps
+        )
+    }
+}
+
+/// Drops a share of the packets between all the validators. Both groups of the direction are all
+/// the validators, so a one-way loss drops the packets they send, or those they receive.
 #[derive(Eq, Hash, PartialEq, Debug, Clone)]
 pub struct SwarmNetworkLoss {
     pub loss_percentage: u64,
     pub correlation_percentage: u64,
+    pub direction: PartitionDirection,
 }
 
 impl Display for SwarmNetworkLoss {
     fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
         write!(
             f,
-            "Loss on all nodes: loss {}, correlation {},",
-            self.loss_percentage, self.correlation_percentage,
+            "Loss on all nodes {}: loss {}, correlation {},",
+            self.direction, self.loss_percentage, self.correlation_percentage,
+        )
+    }
+}
+
+/// Drops a share of the packets the given nodes send, on top of any delay of theirs. The
+/// percentages are whole, so that the chaos can be hashed.
+#[derive(Eq, Hash, PartialEq, Debug, Clone)]
+pub struct SwarmPeerLoss {
+    pub peers: Vec<PeerId>,
+    pub loss_percentage: u64,
+    pub correlation_percentage: u64,
+}
+
+impl Display for SwarmPeerLoss {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "Loss on nodes {:?}: loss {}, correlation {}",
+            self.peers, self.loss_percentage, self.correlation_percentage
         )
     }
 }
@@ -124,3 +292,194 @@ pub struct GroupCpuStress {
     pub num_workers: u64,
     pub load_per_worker: u64,
 }
+
+/// Keeps the CPUs of the given nodes busy, as a noisy neighbor would
+#[derive(Eq, Hash, PartialEq, Debug, Clone)]
+pub struct SwarmPeerCpuStress {
+    pub peers: Vec<PeerId>,
+    pub workers: u32,
+    // how busy each worker keeps its CPU
+    pub load_percent: u32,
+}
+
+impl Display for SwarmPeerCpuStress {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "CpuStress nodes {:?}: {} workers at {}%",
+            self.peers, self.workers, self.load_percent
+        )
+    }
+}
+
+/// Takes up memory in the given nodes' pods, so that they may be OOMKilled
+#[derive(Eq, Hash, PartialEq, Debug, Clone)]
+pub struct SwarmPeerMemoryStress {
+    pub peers: Vec<PeerId>,
+    pub size_mb: u64,
+}
+
+impl Display for SwarmPeerMemoryStress {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "MemoryStress nodes {:?}: {}MB", self.peers, self.size_mb)
+    }
+}
+
+/// Moves the clock of the given nodes' processes by the offset, as seen through CLOCK_REALTIME
+#[derive(Eq, Hash, PartialEq, Debug, Clone)]
+pub struct SwarmTimeSkew {
+    pub peers: Vec<PeerId>,
+    pub offset: Duration,
+    pub direction: SkewDirection,
+}
+
+impl Display for SwarmTimeSkew {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "TimeSkew nodes {:?}: {:?} {}",
+            self.peers, self.offset, self.direction
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum SkewDirection {
+    Ahead,
+    Behind,
+}
+
+impl Display for SkewDirection {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            SkewDirection::Ahead => write!(f, "ahead"),
+            SkewDirection::Behind => write!(f, "behind"),
+        }
+    }
+}
+
+/// Fails the DNS lookups of the given nodes' pods for the domains matching any of the patterns,
+/// or all of them if there are none. Patterns are as in Chaos Mesh, e.g. `*.aptoslabs.com`. The
+/// swarm itself reaches the nodes through port-forwards or from outside their pods, so it is not
+/// affected.
+#[derive(Eq, Hash, PartialEq, Debug, Clone)]
+pub struct SwarmDnsFailure {
+    pub peers: Vec<PeerId>,
+    pub patterns: Vec<String>,
+    pub action: DnsAction,
+}
+
+impl Display for SwarmDnsFailure {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "DnsFailure nodes {:?}: {} for {:?}",
+            self.peers, self.action, self.patterns
+        )
+    }
+}
+
+/// How the failing DNS lookups fail
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum DnsAction {
+    // the lookups return an error
+    Error,
+    // the lookups return a random IP
+    Random,
+}
+
+impl Display for DnsAction {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            DnsAction::Error => write!(f, "error"),
+            DnsAction::Random => write!(f, "random"),
+        }
+    }
+}
+
+/// When a chaos was in effect, e.g. to tell the latencies measured meanwhile apart
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChaosWindow {
+    pub chaos: String,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+impl ChaosWindow {
+    pub fn contains(&self, time: DateTime<Utc>) -> bool {
+        self.start <= time && time < self.end
+    }
+
+    pub fn report(&self, report: &mut TestReport) {
+        report.report_text(format!(
+            "{} from {} to {}",
+            self.chaos,
+            self.start.to_rfc3339(),
+            self.end.to_rfc3339()
+        ));
+    }
+}
+
+/// Injects the chaos, and removes it again once the duration is over, even if the swarm failed
+/// to confirm it was active. Spawn it to run the chaos alongside something else.
+pub async fn inject_chaos_for(
+    swarm: &RwLock<Box<dyn Swarm>>,
+    chaos: SwarmChaos,
+    duration: Duration,
+) -> Result<ChaosWindow> {
+    let name = format!("{:?}", chaos);
+    let injected = swarm.write().await.inject_chaos(chaos.clone()).await;
+    let start = Utc::now();
+    if injected.is_ok() {
+        info!("Injected {} for {:?}", name, duration);
+        tokio::time::sleep(duration).await;
+    }
+    let end = Utc::now();
+    let removed = swarm.write().await.remove_chaos(chaos).await;
+    injected?;
+    removed?;
+    Ok(ChaosWindow {
+        chaos: name,
+        start,
+        end,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_affected_nodes() {
+        let (a, b, c) = (PeerId::random(), PeerId::random(), PeerId::random());
+        let delay = |source_nodes: Vec<PeerId>, target_nodes: Vec<PeerId>| GroupNetworkDelay {
+            name: "delay".to_string(),
+            source_nodes,
+            target_nodes,
+            latency_ms: 100,
+            jitter_ms: 10,
+            correlation_percentage: 50,
+            direction: NetworkDelayDirection::Both,
+        };
+        let chaos = SwarmChaos::Delay(SwarmNetworkDelay {
+            group_network_delays: vec![delay(vec![a], vec![b]), delay(vec![b], vec![c])],
+        });
+        let mut expected = vec![a, b, c];
+        expected.sort();
+        assert_eq!(chaos.affected_nodes(), Some(expected));
+
+        let chaos = SwarmChaos::PeerLoss(SwarmPeerLoss {
+            peers: vec![a],
+            loss_percentage: 10,
+            correlation_percentage: 10,
+        });
+        assert_eq!(chaos.affected_nodes(), Some(vec![a]));
+
+        let chaos = SwarmChaos::Loss(SwarmNetworkLoss {
+            loss_percentage: 10,
+            correlation_percentage: 10,
+            direction: PartitionDirection::Both,
+        });
+        assert_eq!(chaos.affected_nodes(), None);
+    }
+}
@@ -0,0 +1,318 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{ChaosWindow, Node, Result, Swarm, SwarmChaos, TestReport};
+use anyhow::{format_err, Context};
+use aptos_logger::{info, warn};
+use aptos_sdk::types::PeerId;
+use chrono::{DateTime, Utc};
+use std::{sync::Arc, time::Duration};
+use tokio::{
+    sync::{watch, RwLock},
+    task::JoinHandle,
+};
+
+/// One step of a [ChaosSchedule]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ChaosStep {
+    // the chaos is injected, and removed again once the duration is over
+    Inject {
+        chaos: SwarmChaos,
+        duration: Duration,
+    },
+    // the node's pod is deleted without a grace period, and the node is started again once the
+    // downtime is over
+    Kill {
+        peer_id: PeerId,
+        downtime: Duration,
+    },
+    Wait(Duration),
+}
+
+/// Faults to run one after the other, see [spawn_chaos_schedule]. Each fault is over before the
+/// next one starts, so e.g. a delay, then a partition, then a node kill, with gaps in between
+/// for the network to settle.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ChaosSchedule {
+    pub steps: Vec<ChaosStep>,
+}
+
+impl ChaosSchedule {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn then(mut self, chaos: SwarmChaos, duration: Duration) -> Self {
+        self.steps.push(ChaosStep::Inject { chaos, duration });
+        self
+    }
+
+    pub fn then_kill(mut self, peer_id: PeerId, downtime: Duration) -> Self {
+        self.steps.push(ChaosStep::Kill { peer_id, downtime });
+        self
+    }
+
+    pub fn then_wait(mut self, gap: Duration) -> Self {
+        self.steps.push(ChaosStep::Wait(gap));
+        self
+    }
+
+    /// How long the schedule runs for, without the time the swarm takes to inject and remove
+    /// the faults
+    pub fn duration(&self) -> Duration {
+        self.steps
+            .iter()
+            .map(|step| match step {
+                ChaosStep::Inject { duration, .. } => *duration,
+                ChaosStep::Kill { downtime, .. } => *downtime,
+                ChaosStep::Wait(gap) => *gap,
+            })
+            .sum()
+    }
+}
+
+/// When each fault of a schedule was in effect, in order, e.g. to tell apart the latencies
+/// measured meanwhile
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ChaosTimeline {
+    pub windows: Vec<ChaosWindow>,
+}
+
+impl ChaosTimeline {
+    /// The fault in effect at the given time, if any
+    pub fn window_at(&self, time: DateTime<Utc>) -> Option<&ChaosWindow> {
+        self.windows.iter().find(|window| window.contains(time))
+    }
+
+    pub fn report(&self, report: &mut TestReport) {
+        let events: Vec<_> = self
+            .windows
+            .iter()
+            .map(|window| {
+                format!(
+                    "{} to {}: {}",
+                    window.start.to_rfc3339(),
+                    window.end.to_rfc3339(),
+                    window.chaos
+                )
+            })
+            .collect();
+        let lines: Vec<_> = events.iter().map(|event| format!("  {}", event)).collect();
+        report.report_text(format!(
+            "Chaos timeline of {} faults:\n{}",
+            self.windows.len(),
+            lines.join("\n")
+        ));
+        for event in events {
+            report.report_chaos_event(event);
+        }
+    }
+}
+
+/// Removes the fault in effect and skips the rest of the schedule when dropped, in the
+/// background. Call [ChaosScheduleHandle::wait] or [ChaosScheduleHandle::stop] to get the
+/// timeline.
+pub struct ChaosScheduleHandle {
+    stop_sender: watch::Sender<bool>,
+    task: Option<JoinHandle<Result<ChaosTimeline>>>,
+}
+
+impl ChaosScheduleHandle {
+    /// Waits for the whole schedule to run
+    pub async fn wait(mut self) -> Result<ChaosTimeline> {
+        self.join().await
+    }
+
+    /// Removes the fault in effect, and skips the rest of the schedule
+    pub async fn stop(mut self) -> Result<ChaosTimeline> {
+        let _ = self.stop_sender.send(true);
+        self.join().await
+    }
+
+    async fn join(&mut self) -> Result<ChaosTimeline> {
+        let task = self
+            .task
+            .take()
+            .ok_or_else(|| format_err!("Chaos schedule was joined already"))?;
+        task.await
+            .map_err(|e| format_err!("Chaos schedule panicked: {}", e))?
+    }
+}
+
+impl Drop for ChaosScheduleHandle {
+    fn drop(&mut self) {
+        let _ = self.stop_sender.send(true);
+    }
+}
+
+/// Spawns a task that runs the steps of the schedule one after the other. Whether the schedule
+/// completes, fails, or the returned handle is dropped because the test failed meanwhile, the
+/// fault in effect is removed, and a killed node is started again.
+pub fn spawn_chaos_schedule(
+    swarm: Arc<RwLock<Box<dyn Swarm>>>,
+    schedule: ChaosSchedule,
+) -> ChaosScheduleHandle {
+    let (stop_sender, stop_receiver) = watch::channel(false);
+    let task = tokio::spawn(run_chaos_schedule(swarm, schedule, stop_receiver));
+    ChaosScheduleHandle {
+        stop_sender,
+        task: Some(task),
+    }
+}
+
+async fn run_chaos_schedule(
+    swarm: Arc<RwLock<Box<dyn Swarm>>>,
+    schedule: ChaosSchedule,
+    mut stop_receiver: watch::Receiver<bool>,
+) -> Result<ChaosTimeline> {
+    let mut timeline = ChaosTimeline::default();
+    for step in schedule.steps {
+        if *stop_receiver.borrow() {
+            info!("Chaos schedule stopped, skipping the rest of it");
+            break;
+        }
+        let window = match step {
+            ChaosStep::Wait(gap) => {
+                tokio::select! {
+                    _ = tokio::time::sleep(gap) => {},
+                    _ = stop_receiver.changed() => {},
+                }
+                continue;
+            },
+            ChaosStep::Inject { chaos, duration } => {
+                let name = format!("{:?}", chaos);
+                let injected = swarm.write().await.inject_chaos(chaos.clone()).await;
+                let start = Utc::now();
+                if injected.is_ok() {
+                    info!("Chaos schedule injected {} for {:?}", name, duration);
+                    tokio::select! {
+                        _ = tokio::time::sleep(duration) => {},
+                        _ = stop_receiver.changed() => {},
+                    }
+                }
+                let end = Utc::now();
+                // the chaos may be partly in effect even if the swarm failed to confirm it
+                let removed = swarm.write().await.remove_chaos(chaos).await;
+                injected.with_context(|| format!("Chaos schedule failed to inject {}", name))?;
+                removed.with_context(|| format!("Chaos schedule failed to remove {}", name))?;
+                ChaosWindow {
+                    chaos: name,
+                    start,
+                    end,
+                }
+            },
+            ChaosStep::Kill { peer_id, downtime } => {
+                let killed = kill_node(&swarm, peer_id).await;
+                let start = Utc::now();
+                if killed.is_ok() {
+                    tokio::select! {
+                        _ = tokio::time::sleep(downtime) => {},
+                        _ = stop_receiver.changed() => {},
+                    }
+                }
+                let end = Utc::now();
+                let started = start_node(&swarm, peer_id).await;
+                let name = killed?;
+                started.with_context(|| format!("Chaos schedule failed to start {}", name))?;
+                ChaosWindow {
+                    chaos: format!("Kill {}", name),
+                    start,
+                    end,
+                }
+            },
+        };
+        info!("Chaos schedule: {} is over", window.chaos);
+        timeline.windows.push(window);
+    }
+    Ok(timeline)
+}
+
+/// Kills the node, returning its name
+async fn kill_node(swarm: &RwLock<Box<dyn Swarm>>, peer_id: PeerId) -> Result<String> {
+    let swarm = swarm.read().await;
+    if let Some(validator) = swarm.validator(peer_id) {
+        validator.kill().await?;
+        Ok(validator.name().to_string())
+    } else if let Some(fullnode) = swarm.full_node(peer_id) {
+        fullnode.kill().await?;
+        Ok(fullnode.name().to_string())
+    } else {
+        Err(format_err!("Node {} to kill is not in the swarm", peer_id))
+    }
+}
+
+async fn start_node(swarm: &RwLock<Box<dyn Swarm>>, peer_id: PeerId) -> Result<()> {
+    let swarm = swarm.read().await;
+    let result = if let Some(validator) = swarm.validator(peer_id) {
+        validator.start().await
+    } else if let Some(fullnode) = swarm.full_node(peer_id) {
+        fullnode.start().await
+    } else {
+        return Ok(());
+    };
+    if let Err(e) = &result {
+        warn!("Chaos schedule failed to start {}: {}", peer_id, e);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PartitionDirection, SwarmNetworkPartition};
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_chaos_schedule() {
+        let partition = SwarmChaos::Partition(SwarmNetworkPartition {
+            partition_percentage: 30,
+            direction: PartitionDirection::Both,
+        });
+        let peer_id = PeerId::random();
+        let schedule = ChaosSchedule::new()
+            .then(partition.clone(), Duration::from_secs(120))
+            .then_wait(Duration::from_secs(30))
+            .then_kill(peer_id, Duration::from_secs(60));
+        assert_eq!(schedule.steps, vec![
+            ChaosStep::Inject {
+                chaos: partition,
+                duration: Duration::from_secs(120),
+            },
+            ChaosStep::Wait(Duration::from_secs(30)),
+            ChaosStep::Kill {
+                peer_id,
+                downtime: Duration::from_secs(60),
+            },
+        ]);
+        assert_eq!(schedule.duration(), Duration::from_secs(210));
+    }
+
+    #[test]
+    fn test_chaos_timeline() {
+        let at = |secs: i64| Utc.timestamp_opt(secs, 0).unwrap();
+        let timeline = ChaosTimeline {
+            windows: vec![
+                ChaosWindow {
+                    chaos: "Delay".to_string(),
+                    start: at(100),
+                    end: at(220),
+                },
+                ChaosWindow {
+                    chaos: "Kill validator-1".to_string(),
+                    start: at(250),
+                    end: at(310),
+                },
+            ],
+        };
+        assert_eq!(timeline.window_at(at(150)).unwrap().chaos, "Delay");
+        assert!(timeline.window_at(at(230)).is_none());
+        assert_eq!(
+            timeline.window_at(at(250)).unwrap().chaos,
+            "Kill validator-1"
+        );
+
+        let mut report = TestReport::default();
+        timeline.report(&mut report);
+        assert!(report.to_string().contains("Chaos timeline of 2 faults"));
+    }
+}
@@ -4,14 +4,22 @@
 
 use super::{GenesisConfig, Swarm, Version};
 use crate::{GenesisConfigFn, NodeConfigFn, Result};
+use anyhow::bail;
 use rand::rngs::StdRng;
-use std::{num::NonZeroUsize, time::Duration};
+use std::{num::NonZeroUsize, ops::Range, time::Duration};
 
 /// Trait used to represent a interface for constructing a launching new networks
 #[async_trait::async_trait]
 pub trait Factory {
     fn versions<'a>(&'a self) -> Box<dyn Iterator<Item = Version> + 'a>;
 
+    /// Launches the swarms from now on where no earlier swarm ran, e.g. in a fresh namespace, so
+    /// that a test can be retried on a new swarm while the failed one is torn down. Returns false
+    /// if the factory can't.
+    fn use_fresh_namespace(&self) -> bool {
+        false
+    }
+
     async fn launch_swarm(
         &self,
         rng: &mut StdRng,
@@ -24,5 +32,162 @@ pub trait Factory {
         genesis_config_fn: Option<GenesisConfigFn>,
         node_config_fn: Option<NodeConfigFn>,
         existing_db_tag: Option<String>,
+        node_versions: Option<&NodeVersions>,
     ) -> Result<Box<dyn Swarm>>;
 }
+
+/// The version each node of a new swarm starts with, for swarms that start out running more than
+/// one version
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NodeVersions {
+    // by validator index
+    pub validators: Vec<Version>,
+    pub fullnodes: Version,
+    // the image repos of the roles, if not those of the backend, e.g. when the validator and
+    // fullnode images are built separately
+    pub validator_image_repo: Option<String>,
+    pub fullnode_image_repo: Option<String>,
+}
+
+impl NodeVersions {
+    pub fn validator(&self, index: usize) -> Option<&Version> {
+        self.validators.get(index)
+    }
+
+    /// Every version some node starts with, oldest first
+    pub fn versions(&self) -> Vec<Version> {
+        let mut versions: Vec<_> = self
+            .validators
+            .iter()
+            .chain(std::iter::once(&self.fullnodes))
+            .cloned()
+            .collect();
+        versions.sort();
+        versions.dedup();
+        versions
+    }
+}
+
+/// Which version each group of nodes starts with, e.g. validators 0-1 on the oldest version and
+/// the rest of the nodes on the newest one. Versions are referred to by their index among the
+/// factory's versions, oldest first. The nodes outside every group start with the initial version.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MixedVersions {
+    validators: Vec<(Range<usize>, usize)>,
+    fullnodes: Option<usize>,
+    validator_image_repo: Option<String>,
+    fullnode_image_repo: Option<String>,
+}
+
+impl MixedVersions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_validators(mut self, indices: Range<usize>, version_index: usize) -> Self {
+        self.validators.push((indices, version_index));
+        self
+    }
+
+    pub fn with_fullnodes(mut self, version_index: usize) -> Self {
+        self.fullnodes = Some(version_index);
+        self
+    }
+
+    pub fn with_validator_image_repo(mut self, repo: &str) -> Self {
+        self.validator_image_repo = Some(repo.to_string());
+        self
+    }
+
+    pub fn with_fullnode_image_repo(mut self, repo: &str) -> Self {
+        self.fullnode_image_repo = Some(repo.to_string());
+        self
+    }
+
+    /// The version of every node of a swarm of the given size, out of the factory's versions
+    pub fn resolve(
+        &self,
+        versions: &[Version],
+        initial_version: &Version,
+        num_validators: usize,
+    ) -> Result<NodeVersions> {
+        let mut sorted = versions.to_vec();
+        sorted.sort();
+        let version = |index: usize| match sorted.get(index) {
+            Some(version) => Ok(version.clone()),
+            None => bail!(
+                "Version {} does not exist, there are only {} versions",
+                index,
+                sorted.len()
+            ),
+        };
+
+        let mut validators: Vec<Option<Version>> = vec![None; num_validators];
+        for (indices, version_index) in &self.validators {
+            if indices.end > num_validators {
+                bail!(
+                    "Validators {:?} start on version {}, but there are only {} validators",
+                    indices,
+                    version_index,
+                    num_validators
+                );
+            }
+            for index in indices.clone() {
+                if validators[index].is_some() {
+                    bail!("Validator {} is in more than one version group", index);
+                }
+                validators[index] = Some(version(*version_index)?);
+            }
+        }
+        let fullnodes = match self.fullnodes {
+            Some(version_index) => version(version_index)?,
+            None => initial_version.clone(),
+        };
+        Ok(NodeVersions {
+            validators: validators
+                .into_iter()
+                .map(|version| version.unwrap_or_else(|| initial_version.clone()))
+                .collect(),
+            fullnodes,
+            validator_image_repo: self.validator_image_repo.clone(),
+            fullnode_image_repo: self.fullnode_image_repo.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_mixed_versions() {
+        let old = Version::new(0, "old".to_string());
+        let new = Version::new(1, "new".to_string());
+        let versions = vec![new.clone(), old.clone()];
+
+        let mixed = MixedVersions::new()
+            .with_validators(0..2, 0)
+            .with_validators(2..3, 1)
+            .with_fullnodes(1)
+            .with_fullnode_image_repo("aptoslabs/fullnode");
+        let node_versions = mixed.resolve(&versions, &old, 4).unwrap();
+        assert_eq!(node_versions, NodeVersions {
+            validators: vec![old.clone(), old.clone(), new.clone(), old.clone()],
+            fullnodes: new.clone(),
+            validator_image_repo: None,
+            fullnode_image_repo: Some("aptoslabs/fullnode".to_string()),
+        });
+        assert_eq!(node_versions.versions(), vec![old.clone(), new]);
+
+        assert!(mixed.resolve(&versions, &old, 2).is_err());
+        assert!(MixedVersions::new()
+            .with_validators(0..2, 2)
+            .resolve(&versions, &old, 4)
+            .is_err());
+        assert!(MixedVersions::new()
+            .with_validators(0..2, 0)
+            .with_validators(1..3, 1)
+            .resolve(&versions, &old, 4)
+            .is_err());
+    }
+}
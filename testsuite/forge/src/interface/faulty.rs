@@ -0,0 +1,133 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{NodeExt, Result, Validator, Version};
+use anyhow::{bail, format_err};
+use aptos_logger::info;
+use aptos_sdk::types::PeerId;
+use std::fmt::{Display, Formatter};
+
+/// How a validator designated faulty by [crate::Swarm::deploy_faulty_validator] misbehaves. All but
+/// [FaultyBehavior::Image] rely on the failpoints of the node software, so the images must be built
+/// with them and the validators must enable them in their API config. The failpoints are set at
+/// runtime, and don't survive restarts of the node.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FaultyBehavior {
+    // sends each of its votes twice, identical, which is no equivocation: the other validators
+    // only have to count it once
+    ResendVotes,
+    // never sends its votes
+    WithholdVotes,
+    // never sends its proposals
+    WithholdProposals,
+    // the given failpoints of the node software, and their actions, e.g. "10%return"
+    Failpoints(Vec<(String, String)>),
+    // runs another image, e.g. one built with a misbehaving consensus
+    Image(Version),
+}
+
+impl FaultyBehavior {
+    /// The failpoints to set on the faulty validator, and their actions
+    pub fn failpoints(&self) -> Vec<(String, String)> {
+        let failpoint = |name: &str| vec![(name.to_string(), "return".to_string())];
+        match self {
+            FaultyBehavior::ResendVotes => failpoint("consensus::send::vote::resend"),
+            FaultyBehavior::WithholdVotes => failpoint("consensus::send::vote"),
+            FaultyBehavior::WithholdProposals => failpoint("consensus::send::broadcast_proposal"),
+            FaultyBehavior::Failpoints(failpoints) => failpoints.clone(),
+            FaultyBehavior::Image(_) => vec![],
+        }
+    }
+}
+
+impl Display for FaultyBehavior {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FaultyBehavior::ResendVotes => write!(f, "resends its votes"),
+            FaultyBehavior::WithholdVotes => write!(f, "withholds its votes"),
+            FaultyBehavior::WithholdProposals => write!(f, "withholds its proposals"),
+            FaultyBehavior::Failpoints(failpoints) => {
+                let failpoints: Vec<_> = failpoints
+                    .iter()
+                    .map(|(name, actions)| format!("{}={}", name, actions))
+                    .collect();
+                write!(f, "runs with failpoints {}", failpoints.join(", "))
+            },
+            FaultyBehavior::Image(version) => write!(f, "runs image {}", version),
+        }
+    }
+}
+
+/// The validator to designate faulty next, from the validators ordered by index: the last one that
+/// is not faulty yet. Fails if that would leave a third of the validators or more faulty, as the
+/// network could not be expected to keep committing then.
+pub fn next_faulty_validator(validators: &[PeerId], faulty: &[PeerId]) -> Result<PeerId> {
+    if (faulty.len() + 1) * 3 >= validators.len() {
+        bail!(
+            "Cannot make {} of {} validators faulty, fewer than a third of them must be",
+            faulty.len() + 1,
+            validators.len()
+        );
+    }
+    validators
+        .iter()
+        .rev()
+        .find(|peer_id| !faulty.contains(peer_id))
+        .copied()
+        .ok_or_else(|| format_err!("Every validator is faulty already"))
+}
+
+/// Set the failpoints of the behavior on the validator, see [FaultyBehavior::failpoints]
+pub async fn set_faulty_failpoints(
+    validator: &dyn Validator,
+    behavior: &FaultyBehavior,
+) -> Result<()> {
+    let client = validator.rest_client();
+    for (name, actions) in behavior.failpoints() {
+        client
+            .set_failpoint(name.clone(), actions.clone())
+            .await
+            .map_err(|e| {
+                format_err!(
+                    "Failed to set failpoint {}={} on {}, are failpoints enabled in its API config? {}",
+                    name,
+                    actions,
+                    validator.name(),
+                    e
+                )
+            })?;
+    }
+    info!("Validator {} {}", validator.name(), behavior);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_faulty_validator() {
+        let validators: Vec<_> = (0..7).map(|_| PeerId::random()).collect();
+        assert_eq!(
+            next_faulty_validator(&validators, &[]).unwrap(),
+            validators[6]
+        );
+        assert_eq!(
+            next_faulty_validator(&validators, &[validators[6]]).unwrap(),
+            validators[5]
+        );
+        // 3 of 7 validators can't be faulty
+        assert!(next_faulty_validator(&validators, &[validators[6], validators[5]]).is_err());
+        // nor 1 of 3
+        assert!(next_faulty_validator(&validators[..3], &[]).is_err());
+        assert!(next_faulty_validator(&validators[..4], &[]).is_ok());
+
+        assert_eq!(FaultyBehavior::ResendVotes.failpoints(), vec![(
+            "consensus::send::vote::resend".to_string(),
+            "return".to_string()
+        )]);
+        assert!(FaultyBehavior::Image(Version::new(1, "faulty".to_string()))
+            .failpoints()
+            .is_empty());
+    }
+}
@@ -0,0 +1,422 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::Result;
+use anyhow::{format_err, Context};
+use aptos_cached_packages::aptos_stdlib;
+use aptos_framework::{BuildOptions, BuiltPackage};
+use aptos_logger::info;
+use aptos_rest_client::{
+    aptos_api_types::{EntryFunctionId, MoveType, ViewRequest},
+    Client as RestClient, Transaction,
+};
+use aptos_sdk::{
+    crypto::HashValue,
+    transaction_builder::TransactionFactory,
+    types::{
+        account_config::CORE_CODE_ADDRESS,
+        on_chain_config::ConfigurationResource,
+        transaction::{Script, TransactionArgument, TransactionPayload},
+        LocalAccount, PeerId,
+    },
+};
+use serde::Deserialize;
+use serde_json::json;
+use std::{
+    fs, iter,
+    path::PathBuf,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+use thiserror::Error;
+
+// how much longer than the voting duration to wait for the voting to close
+const VOTING_CLOSE_SLACK: Duration = Duration::from_secs(60);
+// of the transaction executing the proposal, too much for the gas estimation of a simulation
+const PROPOSAL_EXECUTION_MAX_GAS: u64 = 2_000_000;
+const GOVERNANCE_PROPOSAL_TYPE: &str = "0x1::governance_proposal::GovernanceProposal";
+// 0x1::voting::PROPOSAL_STATE_SUCCEEDED
+const PROPOSAL_STATE_SUCCEEDED: u64 = 1;
+
+/// Every error of [crate::ChainInfo::execute_governance_proposal] downcasts to one of these, which
+/// tells the stage that failed
+#[derive(Error, Debug)]
+pub enum GovernanceError {
+    #[error("Failed to create the governance proposal: {0:#}")]
+    Propose(anyhow::Error),
+    #[error("Validator {voter} failed to vote on proposal {proposal_id}: {source:#}")]
+    Vote {
+        proposal_id: u64,
+        voter: PeerId,
+        source: anyhow::Error,
+    },
+    #[error("The voting on proposal {proposal_id} did not close within {timeout:?}")]
+    VotingTimeout { proposal_id: u64, timeout: Duration },
+    #[error("Proposal {proposal_id} did not pass, with {yes_votes} yes and {no_votes} no votes")]
+    Rejected {
+        proposal_id: u64,
+        yes_votes: u128,
+        no_votes: u128,
+    },
+    #[error("Failed to execute proposal {proposal_id}: {source:#}")]
+    Execute {
+        proposal_id: u64,
+        source: anyhow::Error,
+    },
+    #[error("Proposal {proposal_id} was executed, but on chain {state}")]
+    NotExecuted { proposal_id: u64, state: String },
+    #[error("Failed to read the state of proposal {proposal_id}: {source:#}")]
+    State {
+        proposal_id: u64,
+        source: anyhow::Error,
+    },
+}
+
+/// A governance proposal that passed and was executed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProposalOutcome {
+    pub proposal_id: u64,
+    pub yes_votes: u128,
+    pub no_votes: u128,
+    // of the transaction that executed the proposal
+    pub version: u64,
+    // the chain is in right after the execution, the next one if the proposal reconfigured
+    // immediately
+    pub epoch: u64,
+}
+
+// 0x1::aptos_governance::GovernanceConfig
+#[derive(Deserialize)]
+struct GovernanceConfig {
+    _min_voting_threshold: u128,
+    _required_proposer_stake: u64,
+    voting_duration_secs: u64,
+}
+
+/// Compile the source of a governance proposal script against the framework of this tree. The
+/// script takes the proposal id as its first argument, and has to resolve the proposal with
+/// `aptos_governance::resolve(proposal_id, @aptos_framework)` for the signer of the framework.
+pub fn compile_proposal_script(source: &str) -> Result<Vec<u8>> {
+    let dir = tempfile::tempdir()?;
+    let framework_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("../../aptos-move/framework/aptos-framework");
+    fs::write(
+        dir.path().join("Move.toml"),
+        format!(
+            "[package]\nname = \"ForgeProposal\"\nversion = \"0.0.0\"\n\n[dependencies]\n\
+             AptosFramework = {{ local = \"{}\" }}\n",
+            framework_dir.display()
+        ),
+    )?;
+    fs::create_dir(dir.path().join("sources"))?;
+    fs::write(dir.path().join("sources/proposal.move"), source)?;
+    let package = BuiltPackage::build(dir.path().to_path_buf(), BuildOptions {
+        skip_fetch_latest_git_deps: true,
+        ..BuildOptions::default()
+    })
+    .context("Failed to compile the proposal script")?;
+    package
+        .extract_script_code()
+        .pop()
+        .ok_or_else(|| format_err!("The proposal source has no script"))
+}
+
+/// Propose, vote on, wait for and execute a governance proposal, see
+/// [crate::ChainInfo::execute_governance_proposal]
+pub async fn execute_governance_proposal(
+    client: &RestClient,
+    transaction_factory: &TransactionFactory,
+    script: &[u8],
+    args: Vec<TransactionArgument>,
+    voters: &[LocalAccount],
+) -> Result<ProposalOutcome> {
+    let proposer = voters.first().ok_or_else(|| {
+        GovernanceError::Propose(format_err!(
+            "A governance proposal needs at least one voter"
+        ))
+    })?;
+    let submit = |account: &LocalAccount, payload: TransactionPayload| {
+        let txn =
+            account.sign_with_transaction_builder(transaction_factory.clone().payload(payload));
+        async move { Ok::<_, anyhow::Error>(client.submit_and_wait(&txn).await?.into_inner()) }
+    };
+
+    let propose = async {
+        let config: GovernanceConfig = client
+            .get_account_resource_bcs(CORE_CODE_ADDRESS, "0x1::aptos_governance::GovernanceConfig")
+            .await?
+            .into_inner();
+        // the stake of the voters has to be locked up for the whole voting
+        for voter in voters {
+            submit(voter, aptos_stdlib::stake_increase_lockup()).await?;
+        }
+        let txn = submit(
+            proposer,
+            aptos_stdlib::aptos_governance_create_proposal_v2(
+                proposer.address(),
+                HashValue::sha3_256_of(script).to_vec(),
+                vec![],
+                vec![],
+                false,
+            ),
+        )
+        .await?;
+        Ok::<_, anyhow::Error>((proposal_id_of(&txn)?, config.voting_duration_secs))
+    };
+    let (proposal_id, voting_duration_secs) = propose.await.map_err(GovernanceError::Propose)?;
+    info!("Created governance proposal {}", proposal_id);
+
+    for voter in voters {
+        submit(
+            voter,
+            aptos_stdlib::aptos_governance_vote(voter.address(), proposal_id, true),
+        )
+        .await
+        .map_err(|source| GovernanceError::Vote {
+            proposal_id,
+            voter: voter.address(),
+            source,
+        })?;
+    }
+
+    let state_error = |source| GovernanceError::State {
+        proposal_id,
+        source,
+    };
+    let timeout = Duration::from_secs(voting_duration_secs) + VOTING_CLOSE_SLACK;
+    let deadline = Instant::now() + timeout;
+    while !view_voting(client, "is_voting_closed", proposal_id)
+        .await
+        .map_err(state_error)?
+        .as_bool()
+        .unwrap_or(false)
+    {
+        if Instant::now() > deadline {
+            return Err(GovernanceError::VotingTimeout {
+                proposal_id,
+                timeout,
+            }
+            .into());
+        }
+        tokio::time::sleep(Duration::from_secs(5)).await;
+    }
+    let (yes_votes, no_votes) = votes(client, proposal_id).await.map_err(state_error)?;
+    let min_vote_threshold =
+        async { view_u128(&view_voting(client, "get_min_vote_threshold", proposal_id).await?) }
+            .await
+            .map_err(state_error)?;
+    if yes_votes <= no_votes || yes_votes + no_votes < min_vote_threshold {
+        return Err(GovernanceError::Rejected {
+            proposal_id,
+            yes_votes,
+            no_votes,
+        }
+        .into());
+    }
+    info!(
+        "Governance proposal {} passed with {} yes votes",
+        proposal_id, yes_votes
+    );
+
+    let execute = async {
+        submit(
+            proposer,
+            aptos_stdlib::aptos_governance_add_approved_script_hash_script(proposal_id),
+        )
+        .await?;
+        let txn = proposer.sign_with_transaction_builder(
+            transaction_factory
+                .clone()
+                .payload(TransactionPayload::Script(Script::new(
+                    script.to_vec(),
+                    vec![],
+                    iter::once(TransactionArgument::U64(proposal_id))
+                        .chain(args)
+                        .collect(),
+                )))
+                .max_gas_amount(PROPOSAL_EXECUTION_MAX_GAS),
+        );
+        Ok::<_, anyhow::Error>(client.submit_and_wait(&txn).await?.into_inner())
+    };
+    let txn = execute.await.map_err(|source| GovernanceError::Execute {
+        proposal_id,
+        source,
+    })?;
+    if let Some(state) = unexecuted_state(client, proposal_id, script)
+        .await
+        .map_err(state_error)?
+    {
+        return Err(GovernanceError::NotExecuted { proposal_id, state }.into());
+    }
+    let version = txn.version().unwrap_or_default();
+    let epoch = client
+        .get_account_resource_at_version_bcs::<ConfigurationResource>(
+            CORE_CODE_ADDRESS,
+            "0x1::reconfiguration::Configuration",
+            version,
+        )
+        .await
+        .map_err(|error| state_error(error.into()))?
+        .into_inner()
+        .epoch();
+    info!(
+        "Executed governance proposal {}, which left the chain in epoch {}",
+        proposal_id, epoch
+    );
+    Ok(ProposalOutcome {
+        proposal_id,
+        yes_votes,
+        no_votes,
+        version,
+        epoch,
+    })
+}
+
+/// The id of the proposal the transaction created, as of its CreateProposalEvent
+fn proposal_id_of(txn: &Transaction) -> Result<u64> {
+    let events = match txn {
+        Transaction::UserTransaction(txn) => &txn.events,
+        _ => return Err(format_err!("Not a user transaction: {}", txn.type_str())),
+    };
+    let event = events
+        .iter()
+        .find(|event| {
+            let typ = event.typ.to_string();
+            typ.ends_with("::aptos_governance::CreateProposalEvent")
+                || typ.ends_with("::aptos_governance::CreateProposal")
+        })
+        .ok_or_else(|| format_err!("The transaction created no proposal"))?;
+    event.data["proposal_id"]
+        .as_str()
+        .and_then(|id| id.parse().ok())
+        .ok_or_else(|| format_err!("Unexpected proposal event {}", event.data))
+}
+
+/// What tells on chain that the proposal was not executed with the script, if anything does
+async fn unexecuted_state(
+    client: &RestClient,
+    proposal_id: u64,
+    script: &[u8],
+) -> Result<Option<String>> {
+    let state = view_u64(&view_voting(client, "get_proposal_state", proposal_id).await?)?;
+    if state != PROPOSAL_STATE_SUCCEEDED {
+        return Ok(Some(format!("its state is {}", state)));
+    }
+    let execution_hash = view_voting(client, "get_execution_hash", proposal_id).await?;
+    let script_hash = HashValue::sha3_256_of(script).to_hex_literal();
+    if execution_hash.as_str() != Some(script_hash.as_str()) {
+        return Ok(Some(format!(
+            "its execution hash is {}, not the one of the script {}",
+            execution_hash, script_hash
+        )));
+    }
+    if view_voting(client, "is_resolved", proposal_id)
+        .await?
+        .as_bool()
+        != Some(true)
+    {
+        return Ok(Some("it is not resolved".to_string()));
+    }
+    if view_voting(client, "is_multi_step_proposal_in_execution", proposal_id)
+        .await?
+        .as_bool()
+        != Some(false)
+    {
+        return Ok(Some("it is still in execution".to_string()));
+    }
+    Ok(None)
+}
+
+async fn view_voting(
+    client: &RestClient,
+    function: &str,
+    proposal_id: u64,
+) -> Result<serde_json::Value> {
+    let request = ViewRequest {
+        function: EntryFunctionId::from_str(&format!("0x1::voting::{}", function))?,
+        type_arguments: vec![MoveType::from_str(GOVERNANCE_PROPOSAL_TYPE)?],
+        arguments: vec![json!("0x1"), json!(proposal_id.to_string())],
+    };
+    client
+        .view(&request, None)
+        .await
+        .with_context(|| format!("Failed to view {} of proposal {}", function, proposal_id))?
+        .into_inner()
+        .into_iter()
+        .next()
+        .ok_or_else(|| format_err!("{} returned nothing", function))
+}
+
+async fn votes(client: &RestClient, proposal_id: u64) -> Result<(u128, u128)> {
+    let request = ViewRequest {
+        function: EntryFunctionId::from_str("0x1::voting::get_votes")?,
+        type_arguments: vec![MoveType::from_str(GOVERNANCE_PROPOSAL_TYPE)?],
+        arguments: vec![json!("0x1"), json!(proposal_id.to_string())],
+    };
+    let values = client.view(&request, None).await?.into_inner();
+    match values.as_slice() {
+        [yes, no] => Ok((view_u128(yes)?, view_u128(no)?)),
+        _ => Err(format_err!("Unexpected votes {:?}", values)),
+    }
+}
+
+// u128 values are returned as strings
+fn view_u128(value: &serde_json::Value) -> Result<u128> {
+    value
+        .as_str()
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| format_err!("Not a u128: {}", value))
+}
+
+// and so are u64 values
+fn view_u64(value: &serde_json::Value) -> Result<u64> {
+    value
+        .as_str()
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| format_err!("Not a u64: {}", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_view_u128() {
+        assert_eq!(
+            view_u128(&json!("100000000000000")).unwrap(),
+            100_000_000_000_000
+        );
+        view_u128(&json!(5)).unwrap_err();
+        assert_eq!(view_u64(&json!("1")).unwrap(), 1);
+        view_u64(&json!(1)).unwrap_err();
+    }
+
+    #[test]
+    fn test_governance_error() {
+        let error = GovernanceError::Rejected {
+            proposal_id: 3,
+            yes_votes: 1,
+            no_votes: 2,
+        };
+        assert_eq!(
+            error.to_string(),
+            "Proposal 3 did not pass, with 1 yes and 2 no votes"
+        );
+        let error = GovernanceError::Vote {
+            proposal_id: 3,
+            voter: PeerId::ONE,
+            source: format_err!("insufficient stake lockup"),
+        };
+        assert!(error
+            .to_string()
+            .ends_with("failed to vote on proposal 3: insufficient stake lockup"));
+        let error = GovernanceError::NotExecuted {
+            proposal_id: 3,
+            state: "it is not resolved".to_string(),
+        };
+        assert_eq!(
+            error.to_string(),
+            "Proposal 3 was executed, but on chain it is not resolved"
+        );
+    }
+}
@@ -0,0 +1,77 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use aptos_sdk::{
+    crypto::{bls12381, x25519},
+    types::PeerId,
+};
+use std::fmt::{Display, Formatter};
+
+/// The keys a validator had and has after [crate::Swarm::rotate_validator_keys]
+#[derive(Clone, Debug)]
+pub struct KeyRotationResult {
+    pub peer_id: PeerId,
+    pub old_consensus_public_key: bls12381::PublicKey,
+    pub new_consensus_public_key: bls12381::PublicKey,
+    pub old_network_public_key: x25519::PublicKey,
+    pub new_network_public_key: x25519::PublicKey,
+    // the first epoch the validator votes in with the new consensus key
+    pub epoch: u64,
+}
+
+/// How far a key rotation got before it failed, i.e. what the chain and the node are left with
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyRotationStage {
+    NotStarted,
+    // the new keys are stored next to the validator's identity, it still runs with the old ones
+    KeysStored,
+    // the rotation was submitted on chain, and takes effect with the next epoch
+    Submitted,
+    // the chain expects the new keys, but the node still runs with the old ones
+    OnChain { epoch: u64 },
+    // the node was restarted with the new keys
+    NodeUpdated { epoch: u64 },
+}
+
+impl Display for KeyRotationStage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KeyRotationStage::NotStarted => write!(f, "nothing was changed"),
+            KeyRotationStage::KeysStored => write!(
+                f,
+                "the new keys were stored, but neither the chain nor the node use them"
+            ),
+            KeyRotationStage::Submitted => write!(
+                f,
+                "the rotation was submitted on chain, but may not have taken effect"
+            ),
+            KeyRotationStage::OnChain { epoch } => write!(
+                f,
+                "the chain expects the new keys since epoch {}, but the node runs with the old ones",
+                epoch
+            ),
+            KeyRotationStage::NodeUpdated { epoch } => write!(
+                f,
+                "the chain expects the new keys since epoch {} and the node was restarted with them",
+                epoch
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_rotation_stage() {
+        assert_eq!(
+            KeyRotationStage::NotStarted.to_string(),
+            "nothing was changed"
+        );
+        assert_eq!(
+            KeyRotationStage::OnChain { epoch: 5 }.to_string(),
+            "the chain expects the new keys since epoch 5, but the node runs with the old ones"
+        );
+    }
+}
@@ -0,0 +1,333 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{NodeExt, NodeMetrics, Result, Swarm, TestReport};
+use anyhow::{bail, format_err};
+use aptos_logger::info;
+use futures::future::join_all;
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::task::JoinHandle;
+
+// exported by node-resource-metrics for every node
+const PROCESS_MEMORY_METRIC: &str = "node_process_memory";
+const PROCESS_VIRTUAL_MEMORY_METRIC: &str = "node_process_virtual_memory";
+// the jemalloc stats, only exported by nodes built with them
+const JEMALLOC_ALLOCATED_METRIC: &str = "aptos_jemalloc_allocated_bytes";
+const JEMALLOC_RESIDENT_METRIC: &str = "aptos_jemalloc_resident_bytes";
+const JEMALLOC_MAPPED_METRIC: &str = "aptos_jemalloc_mapped_bytes";
+
+/// How much memory a node uses, as reported by the node's metrics
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MemoryStats {
+    // resident memory as jemalloc sees it, or the rss of the process if jemalloc stats are missing
+    pub resident_bytes: u64,
+    pub virtual_bytes: Option<u64>,
+    // only set if the node exports jemalloc stats
+    pub allocated_bytes: Option<u64>,
+    pub mapped_bytes: Option<u64>,
+}
+
+impl MemoryStats {
+    pub fn from_metrics(metrics: &NodeMetrics) -> Result<Self> {
+        let gauge = |name: &str| metrics.get(name).first().map(|sample| sample.value as u64);
+        let resident_bytes = gauge(JEMALLOC_RESIDENT_METRIC)
+            .or_else(|| gauge(PROCESS_MEMORY_METRIC))
+            .ok_or_else(|| {
+                format_err!(
+                    "Node reports neither {} nor {}",
+                    JEMALLOC_RESIDENT_METRIC,
+                    PROCESS_MEMORY_METRIC
+                )
+            })?;
+        Ok(Self {
+            resident_bytes,
+            virtual_bytes: gauge(PROCESS_VIRTUAL_MEMORY_METRIC),
+            allocated_bytes: gauge(JEMALLOC_ALLOCATED_METRIC),
+            mapped_bytes: gauge(JEMALLOC_MAPPED_METRIC),
+        })
+    }
+}
+
+/// The memory stats of the nodes at one point in time. Nodes that failed to report are missing.
+#[derive(Clone, Debug, Default)]
+pub struct MemorySnapshot {
+    pub unix_secs: u64,
+    // set on the snapshot growth is measured from, e.g. the one at the end of the warmup
+    pub baseline: bool,
+    pub validators: BTreeMap<String, MemoryStats>,
+    pub fullnodes: BTreeMap<String, MemoryStats>,
+}
+
+impl MemorySnapshot {
+    pub async fn take(swarm: &dyn Swarm) -> Self {
+        let validators: Vec<_> = swarm.validators().collect();
+        let fullnodes: Vec<_> = swarm.full_nodes().collect();
+        let validator_stats = join_all(
+            validators
+                .iter()
+                .map(|node| async move { (node.name().to_string(), node.memory_stats().await) }),
+        );
+        let fullnode_stats = join_all(
+            fullnodes
+                .iter()
+                .map(|node| async move { (node.name().to_string(), node.memory_stats().await) }),
+        );
+        let (validator_stats, fullnode_stats) = futures::join!(validator_stats, fullnode_stats);
+        Self {
+            unix_secs: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .expect("Time went backwards")
+                .as_secs(),
+            baseline: false,
+            validators: Self::successful(validator_stats),
+            fullnodes: Self::successful(fullnode_stats),
+        }
+    }
+
+    fn successful(stats: Vec<(String, Result<MemoryStats>)>) -> BTreeMap<String, MemoryStats> {
+        stats
+            .into_iter()
+            .filter_map(|(name, result)| match result {
+                Ok(stats) => Some((name, stats)),
+                // nodes may well be down, e.g. during chaos
+                Err(e) => {
+                    info!("Failed to get memory stats of {}: {}", name, e);
+                    None
+                },
+            })
+            .collect()
+    }
+}
+
+/// Samples the memory stats of all the nodes of a swarm on an interval, until stopped
+pub struct MemorySampler {
+    swarm: Arc<tokio::sync::RwLock<Box<dyn Swarm>>>,
+    snapshots: Arc<Mutex<Vec<MemorySnapshot>>>,
+    // taken on stop, aborted on drop so that a failed test doesn't leave the sampler running
+    task: Option<JoinHandle<()>>,
+}
+
+impl MemorySampler {
+    pub fn start(swarm: Arc<tokio::sync::RwLock<Box<dyn Swarm>>>, interval: Duration) -> Self {
+        let snapshots = Arc::new(Mutex::new(vec![]));
+        let task = tokio::spawn({
+            let swarm = swarm.clone();
+            let snapshots = snapshots.clone();
+            async move {
+                let mut ticker = tokio::time::interval(interval);
+                loop {
+                    ticker.tick().await;
+                    let snapshot = MemorySnapshot::take(swarm.read().await.as_ref()).await;
+                    snapshots.lock().unwrap().push(snapshot);
+                }
+            }
+        });
+        Self {
+            swarm,
+            snapshots,
+            task: Some(task),
+        }
+    }
+
+    /// Sample right away and measure growth from this sample on, e.g. at the end of the warmup
+    pub async fn sample_baseline(&self) {
+        let mut snapshot = MemorySnapshot::take(self.swarm.read().await.as_ref()).await;
+        snapshot.baseline = true;
+        self.snapshots.lock().unwrap().push(snapshot);
+    }
+
+    /// Stop sampling, and return all the samples along with a last one taken right away
+    pub async fn stop(mut self) -> MemoryTimeline {
+        if let Some(task) = self.task.take() {
+            task.abort();
+            // only fails with the cancellation
+            let _ = task.await;
+        }
+        let last = MemorySnapshot::take(self.swarm.read().await.as_ref()).await;
+        let mut snapshots = std::mem::take(&mut *self.snapshots.lock().unwrap());
+        snapshots.push(last);
+        MemoryTimeline { snapshots }
+    }
+}
+
+impl Drop for MemorySampler {
+    fn drop(&mut self) {
+        if let Some(task) = &self.task {
+            task.abort();
+        }
+    }
+}
+
+/// The memory stats of the nodes over a run, oldest first
+#[derive(Clone, Debug, Default)]
+pub struct MemoryTimeline {
+    pub snapshots: Vec<MemorySnapshot>,
+}
+
+impl MemoryTimeline {
+    /// The snapshot growth is measured from: the baseline one if any, the first one otherwise
+    fn baseline(&self) -> Option<&MemorySnapshot> {
+        self.snapshots
+            .iter()
+            .rev()
+            .find(|snapshot| snapshot.baseline)
+            .or_else(|| self.snapshots.first())
+    }
+
+    /// How much each validator's resident memory grew from the baseline to the last snapshot, in
+    /// percent. Validators missing from either snapshot are left out.
+    pub fn validator_resident_growth_pct(&self) -> BTreeMap<String, f64> {
+        let (baseline, last) = match (self.baseline(), self.snapshots.last()) {
+            (Some(baseline), Some(last)) => (baseline, last),
+            _ => return BTreeMap::new(),
+        };
+        baseline
+            .validators
+            .iter()
+            .filter_map(|(name, from)| {
+                let to = last.validators.get(name)?;
+                let growth = (to.resident_bytes as f64 - from.resident_bytes as f64)
+                    / from.resident_bytes.max(1) as f64
+                    * 100.0;
+                Some((name.clone(), growth))
+            })
+            .collect()
+    }
+
+    /// Fail if the resident memory of any validator grew by more than `max_growth_pct` percent
+    pub fn check_max_growth(&self, max_growth_pct: f64) -> Result<()> {
+        let growth = self.validator_resident_growth_pct();
+        if growth.is_empty() {
+            bail!("No validator memory samples to check the memory growth against");
+        }
+        let exceeded: Vec<_> = growth
+            .iter()
+            .filter(|(_, pct)| **pct > max_growth_pct)
+            .map(|(name, pct)| format!("{} grew by {:.1}%", name, pct))
+            .collect();
+        if !exceeded.is_empty() {
+            bail!(
+                "Resident memory grew by more than {:.1}%: {}",
+                max_growth_pct,
+                exceeded.join(", ")
+            );
+        }
+        Ok(())
+    }
+
+    /// Report the first, last and max resident memory of each node, and the validators' growth
+    pub fn report(&self, report: &mut TestReport, test_name: &str) {
+        let mut resident: BTreeMap<&str, Vec<u64>> = BTreeMap::new();
+        for snapshot in &self.snapshots {
+            for (name, stats) in snapshot.validators.iter().chain(&snapshot.fullnodes) {
+                resident
+                    .entry(name.as_str())
+                    .or_default()
+                    .push(stats.resident_bytes);
+            }
+        }
+        let mib = |bytes: u64| bytes as f64 / (1024.0 * 1024.0);
+        let lines: Vec<_> = resident
+            .iter()
+            .map(|(name, samples)| {
+                let max = samples.iter().copied().max().unwrap_or_default();
+                format!(
+                    "  {}: {:.0} MiB -> {:.0} MiB, max {:.0} MiB over {} samples",
+                    name,
+                    mib(samples[0]),
+                    mib(samples[samples.len() - 1]),
+                    mib(max),
+                    samples.len()
+                )
+            })
+            .collect();
+        report.report_text(format!("Resident memory:\n{}", lines.join("\n")));
+        for (name, pct) in self.validator_resident_growth_pct() {
+            report.report_metric(
+                test_name,
+                format!("{}_resident_memory_growth_pct", name),
+                pct,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(baseline: bool, validators: &[(&str, u64)]) -> MemorySnapshot {
+        MemorySnapshot {
+            baseline,
+            validators: validators
+                .iter()
+                .map(|(name, resident_bytes)| {
+                    (name.to_string(), MemoryStats {
+                        resident_bytes: *resident_bytes,
+                        virtual_bytes: None,
+                        allocated_bytes: None,
+                        mapped_bytes: None,
+                    })
+                })
+                .collect(),
+            ..MemorySnapshot::default()
+        }
+    }
+
+    #[test]
+    fn test_memory_stats() {
+        let text = r#"
+node_process_memory 2000
+node_process_virtual_memory 9000
+"#;
+        assert_eq!(
+            MemoryStats::from_metrics(&NodeMetrics::parse(text).unwrap()).unwrap(),
+            MemoryStats {
+                resident_bytes: 2000,
+                virtual_bytes: Some(9000),
+                allocated_bytes: None,
+                mapped_bytes: None,
+            }
+        );
+
+        let text = r#"
+node_process_memory 2000
+aptos_jemalloc_allocated_bytes 1200
+aptos_jemalloc_resident_bytes 1500
+aptos_jemalloc_mapped_bytes 1800
+"#;
+        let stats = MemoryStats::from_metrics(&NodeMetrics::parse(text).unwrap()).unwrap();
+        assert_eq!(stats.resident_bytes, 1500);
+        assert_eq!(stats.allocated_bytes, Some(1200));
+        assert_eq!(stats.mapped_bytes, Some(1800));
+
+        MemoryStats::from_metrics(&NodeMetrics::default()).unwrap_err();
+    }
+
+    #[test]
+    fn test_check_max_growth() {
+        let timeline = MemoryTimeline {
+            snapshots: vec![
+                snapshot(false, &[("validator-0", 100), ("validator-1", 100)]),
+                snapshot(true, &[("validator-0", 1024), ("validator-1", 1024)]),
+                snapshot(false, &[("validator-0", 1100), ("validator-1", 1300)]),
+                // validator-1 didn't report the last time
+                snapshot(false, &[("validator-0", 1280)]),
+            ],
+        };
+        let growth = timeline.validator_resident_growth_pct();
+        assert_eq!(growth.len(), 1);
+        assert_eq!(growth["validator-0"], 25.0);
+        timeline.check_max_growth(30.0).unwrap();
+        let message = timeline.check_max_growth(20.0).unwrap_err().to_string();
+        assert!(message.contains("validator-0 grew by 25.0%"), "{}", message);
+
+        MemoryTimeline::default()
+            .check_max_growth(10.0)
+            .unwrap_err();
+    }
+}
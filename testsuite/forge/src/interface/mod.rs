@@ -4,20 +4,48 @@
 
 mod admin;
 pub use admin::*;
+mod artifacts;
+pub use artifacts::*;
 mod aptos;
 pub use self::aptos::*;
+mod governance;
+pub use governance::*;
+mod key_rotation;
+pub use key_rotation::*;
 mod network;
 pub use network::*;
 mod test;
 pub use test::*;
 mod factory;
 pub use factory::*;
+mod faulty;
+pub use faulty::*;
 mod swarm;
 pub use swarm::*;
+mod swarm_rest_client;
+pub use swarm_rest_client::*;
+mod swarm_health;
+pub use swarm_health::*;
 mod chaos;
 pub use chaos::*;
+mod chaos_schedule;
+pub use chaos_schedule::*;
 mod node;
 pub use node::*;
+mod node_killer;
+pub use node_killer::*;
+mod node_operations;
+pub use node_operations::*;
+mod node_resources;
+pub use node_resources::*;
+mod node_metrics;
+pub use node_metrics::*;
+mod stake;
+pub use stake::*;
+mod storage_metrics;
+pub use storage_metrics::*;
+mod memory_stats;
+pub use memory_stats::*;
 mod chain_info;
 pub mod prometheus_metrics;
 
@@ -6,12 +6,12 @@ use super::Test;
 use crate::{
     prometheus_metrics::LatencyBreakdown,
     success_criteria::{SuccessCriteria, SuccessCriteriaChecker},
-    CoreContext, Result, Swarm, TestReport,
+    CoreContext, MemoryTimeline, Result, Swarm, TestReport,
 };
 use aptos_transaction_emitter_lib::{EmitJobRequest, TxnStats};
 use async_trait::async_trait;
 use std::{future::Future, sync::Arc, time::Duration};
-use tokio::runtime::{Handle, Runtime};
+use tokio::runtime::Handle;
 
 /// The testing interface which defines a test written with full control over an existing network.
 /// Tests written against this interface will have access to both the Root account as well as the
@@ -60,7 +60,13 @@ pub struct NetworkContext<'t> {
     pub global_duration: Duration,
     pub emit_job: EmitJobRequest,
     pub success_criteria: SuccessCriteria,
-    pub runtime: Runtime,
+    // how long to wait for a node to become healthy after restarting it
+    pub node_restart_timeout: Duration,
+    // the memory samples of the run, if the test collected any
+    pub memory_timeline: Option<MemoryTimeline>,
+    // of the runtime the test runs on, which the runner owns, to abort what the test spawned if
+    // it times out
+    runtime_handle: Handle,
 }
 
 impl<'t> NetworkContext<'t> {
@@ -71,6 +77,8 @@ impl<'t> NetworkContext<'t> {
         global_duration: Duration,
         emit_job: EmitJobRequest,
         success_criteria: SuccessCriteria,
+        node_restart_timeout: Duration,
+        runtime_handle: Handle,
     ) -> Self {
         Self {
             core,
@@ -79,7 +87,9 @@ impl<'t> NetworkContext<'t> {
             global_duration,
             emit_job,
             success_criteria,
-            runtime: aptos_runtimes::spawn_named_runtime("emitter".into(), Some(64)),
+            node_restart_timeout,
+            memory_timeline: None,
+            runtime_handle,
         }
     }
 
@@ -108,6 +118,7 @@ impl<'t> NetworkContext<'t> {
             end_time,
             start_version,
             end_version,
+            self.memory_timeline.as_ref(),
         )
         .await
     }
@@ -118,7 +129,7 @@ impl<'t> NetworkContext<'t> {
                 // we are in an async context, we don't need block_on
                 handle
             },
-            Err(_) => self.runtime.handle().clone(),
+            Err(_) => self.runtime_handle.clone(),
         }
     }
 
@@ -128,7 +139,7 @@ impl<'t> NetworkContext<'t> {
                 // we are in an async context, we don't need block_on
                 handle.block_on(future)
             },
-            Err(_) => self.runtime.block_on(future),
+            Err(_) => self.runtime_handle.block_on(future),
         }
     }
 }
@@ -2,22 +2,54 @@
 // Parts of the project are originally copyright © Meta Platforms, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::{Result, Version};
-use anyhow::anyhow;
+use crate::{MemoryStats, NodeMetrics, NodeResources, Result, StorageMetrics, Version};
+use anyhow::{anyhow, bail, format_err};
 use aptos_config::{config::NodeConfig, network_id::NetworkId};
 use aptos_inspection_service::inspection_client::InspectionClient;
-use aptos_rest_client::{AptosBaseUrl, Client as RestClient};
-use aptos_sdk::types::PeerId;
+use aptos_logger::info;
+use aptos_rest_client::{
+    aptos_api_types::{TransactionData, TransactionOnChainData},
+    error::RestError,
+    AptosBaseUrl, Client as RestClient, ClientBuilder, RetryPolicy,
+};
+use aptos_sdk::{
+    crypto::HashValue,
+    types::{
+        account_config::CORE_CODE_ADDRESS, ledger_info::LedgerInfo, on_chain_config::ValidatorSet,
+        transaction::Transaction, waypoint::Waypoint, PeerId,
+    },
+};
+use rand::Rng;
+use reqwest::{Certificate, StatusCode};
+use serde_json::Value;
 use std::{
     collections::HashMap,
+    fmt::{Debug, Formatter},
+    future::Future,
+    ops::Deref,
+    process::Child,
+    str::FromStr,
     time::{Duration, Instant},
 };
+use thiserror::Error;
 use url::Url;
 
+// how many times in a row fetching a node's ledger version may fail while waiting on it
+const DEFAULT_MAX_CONSECUTIVE_REST_FAILURES: usize = 5;
+
+// how much longer than the profile itself a profiling request may take
+const CPU_PROFILE_TIMEOUT_SLACK: Duration = Duration::from_secs(30);
+
 #[derive(Debug)]
 pub enum HealthCheckError {
     NotRunning(String),
     Failure(anyhow::Error),
+    // the node is reachable, but its ledger has not advanced recently
+    Stale(String),
+    // the node will not become healthy without intervention, e.g. its container is crash-looping
+    Unrecoverable(anyhow::Error),
+    // the node was OOMKilled by a memory stress chaos, as expected, and is restarting
+    OomKilledByChaos(String),
     Unknown(anyhow::Error),
 }
 
@@ -29,6 +61,94 @@ impl std::fmt::Display for HealthCheckError {
 
 impl std::error::Error for HealthCheckError {}
 
+/// What a Node has to pass to be considered healthy
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HealthCheckMode {
+    /// Node::health_check passes, i.e. the REST API answers (and for k8s nodes with a staleness
+    /// threshold, the ledger timestamp is recent)
+    #[default]
+    Reachability,
+    /// The REST API's `/-/healthy` endpoint reports that the ledger progressed within the last
+    /// `duration_secs`, so a halted chain fails the check on the first poll
+    Liveness { duration_secs: u64 },
+}
+
+/// Fetch the peers connected on the given network from the `/peer_information` page of the
+/// inspection service at `inspection_service_endpoint`
+pub(crate) async fn fetch_connected_peers(
+    inspection_service_endpoint: Url,
+    network_id: NetworkId,
+) -> Result<Vec<PeerId>> {
+    let url = inspection_service_endpoint.join("peer_information")?;
+    let response = reqwest::get(url.clone()).await?;
+    if response.status() == reqwest::StatusCode::FORBIDDEN {
+        bail!(
+            "{} is disabled, set inspection_service.expose_peer_information in the node config",
+            url
+        );
+    }
+    let text = response.error_for_status()?.text().await?;
+    parse_connected_peers(&text, network_id)
+}
+
+/// Parse the connection metadata lines of the `/peer_information` page, which look like
+/// `- Peer: Validator:4d8a1b2c, connection state: Connected, connection metadata: {...}`
+fn parse_connected_peers(peer_information: &str, network_id: NetworkId) -> Result<Vec<PeerId>> {
+    let mut peers = vec![];
+    for line in peer_information.lines() {
+        let line = match line.trim().strip_prefix("- Peer: ") {
+            Some(line) => line,
+            None => continue,
+        };
+        let (peer, rest) = match line.split_once(", connection state: ") {
+            Some(split) => split,
+            None => continue,
+        };
+        let (state, metadata) = rest
+            .split_once(", connection metadata: ")
+            .ok_or_else(|| format_err!("Peer {} has no connection metadata", peer))?;
+        if !peer.starts_with(&format!("{}:", network_id.as_str())) || state != "Connected" {
+            continue;
+        }
+        let metadata: Value = serde_json::from_str(metadata)
+            .map_err(|e| format_err!("Invalid connection metadata of peer {}: {}", peer, e))?;
+        let remote_peer_id = metadata["remote_peer_id"]
+            .as_str()
+            .ok_or_else(|| format_err!("Peer {} has no remote_peer_id", peer))?;
+        peers.push(PeerId::from_str(remote_peer_id)?);
+    }
+    Ok(peers)
+}
+
+/// How the REST clients of a Node are built. Unset timeouts keep the defaults of the client.
+#[derive(Clone, Debug, Default)]
+pub struct RestClientOptions {
+    // of a whole request, slow queries may need a longer one
+    pub timeout: Option<Duration>,
+    pub connect_timeout: Option<Duration>,
+    pub retry_policy: Option<RetryPolicy>,
+    // extra root CA to trust, for REST APIs served over HTTPS with a private CA
+    pub root_ca: Option<Certificate>,
+}
+
+impl RestClientOptions {
+    pub fn apply(&self, mut builder: ClientBuilder) -> ClientBuilder {
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(connect_timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(connect_timeout);
+        }
+        if let Some(retry_policy) = &self.retry_policy {
+            builder = builder.retry_policy(retry_policy.clone());
+        }
+        if let Some(root_ca) = &self.root_ca {
+            builder = builder.add_root_certificate(root_ca.clone());
+        }
+        builder
+    }
+}
+
 /// Trait used to represent a running Validator or FullNode
 #[async_trait::async_trait]
 pub trait Node: Send + Sync {
@@ -44,14 +164,25 @@ pub trait Node: Send + Sync {
     /// Return the version this node is running
     fn version(&self) -> Version;
 
+    /// The CPU and memory the node was deployed with, if the backend limits them
+    fn resources(&self) -> Option<NodeResources> {
+        None
+    }
+
     /// Return the URL for the REST API endpoint of this Node
     fn rest_api_endpoint(&self) -> Url;
 
+    /// How the REST clients of this Node are built. The default sends every request once, with
+    /// the default timeouts of the client.
+    fn rest_client_options(&self) -> RestClientOptions {
+        RestClientOptions::default()
+    }
+
     /// Return the URL for the debug-interface for this Node
     fn inspection_service_endpoint(&self) -> Url;
 
-    /// Return a reference to the Config this Node is using
-    fn config(&self) -> &NodeConfig;
+    /// Return the Config this Node is using. Backends that read it from the cluster may fail to.
+    async fn config(&self) -> Result<NodeConfig>;
 
     /// Start this Node.
     /// This should be a noop if the Node is already running.
@@ -61,19 +192,178 @@ pub trait Node: Send + Sync {
     /// This should be a noop if the Node isn't running.
     async fn stop(&self) -> Result<()>;
 
+    /// Restarts this Node by calling Node::Stop followed by Node::Start, and waits up to
+    /// `health_timeout` for it to become healthy again
+    async fn restart(&self, health_timeout: Duration) -> Result<()> {
+        self.stop().await?;
+        self.start().await?;
+        self.wait_until_healthy(Instant::now() + health_timeout)
+            .await
+    }
+
+    /// Ungracefully kill this Node, simulating a crash.
+    /// Depending on the backend the Node may be brought back automatically; call `start` to
+    /// ensure it is running and reachable again.
+    async fn kill(&self) -> Result<()>;
+
     async fn get_identity(&self) -> Result<String>;
 
     async fn set_identity(&self, k8s_secret_name: String) -> Result<()>;
     /// Clears this Node's Storage. This stops the node as well
     async fn clear_storage(&self) -> Result<()>;
 
+    /// Take up space on the volume holding this Node's storage until `percent` of it is used, as
+    /// when a disk fills up. Undone by `free_disk`.
+    async fn fill_disk(&self, _percent: u8) -> Result<()> {
+        bail!("Filling the disk of {} is not supported", self.name())
+    }
+
+    /// Free the space `fill_disk` took up
+    async fn free_disk(&self) -> Result<()> {
+        bail!("Freeing the disk of {} is not supported", self.name())
+    }
+
+    /// Check that the REST API serves the same ledger directly and through the HAProxy in front
+    /// of this Node, which catches HAProxy misconfiguration, e.g. it forwarding to the wrong node.
+    /// Fails if there is no HAProxy in front of it.
+    async fn check_haproxy_consistency(&self) -> Result<()> {
+        bail!("{} has no HAProxy in front of it", self.name())
+    }
+
     async fn health_check(&self) -> Result<(), HealthCheckError>;
 
-    fn counter(&self, counter: &str, port: u64) -> Result<f64>;
+    /// Read a counter from the `/counters` endpoint of this Node, exposing its metrics port if needed
+    async fn counter(&self, counter: &str) -> Result<f64>;
+
+    /// Expose the metrics port of this Node on localhost, unless it is reachable at its service
+    /// name already. The returned guard must be held for as long as the metrics are scraped.
+    /// Calling this again may hand out the same port.
+    async fn expose_metric(&self) -> Result<MetricsPortForward>;
 
-    fn expose_metric(&self) -> Result<u64>;
+    /// Expose the admin service of this Node, which serves e.g. CPU profiles. The returned guard
+    /// must be held for as long as the service is used.
+    async fn admin_service_endpoint(&self) -> Result<ServiceEndpoint>;
 
     fn service_name(&self) -> Option<String>;
+
+    /// Return the peers this Node is currently connected to on the given network
+    async fn get_connected_peer_ids(&self, network_id: NetworkId) -> Result<Vec<PeerId>>;
+}
+
+/// A local port serving a Node's metrics. If the port is backed by a port-forward process, the
+/// process is killed when this guard is dropped.
+#[derive(Debug)]
+pub struct MetricsPortForward {
+    port: u64,
+    child: Option<Child>,
+}
+
+impl MetricsPortForward {
+    pub fn new(port: u64, child: Option<Child>) -> Self {
+        Self { port, child }
+    }
+
+    pub fn port(&self) -> u64 {
+        self.port
+    }
+}
+
+impl Deref for MetricsPortForward {
+    type Target = u64;
+
+    fn deref(&self) -> &Self::Target {
+        &self.port
+    }
+}
+
+impl Drop for MetricsPortForward {
+    fn drop(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+/// A URL serving one of a Node's services. If the URL is backed by a port-forward, the
+/// port-forward is closed when this guard is dropped.
+pub struct ServiceEndpoint {
+    url: Url,
+    _port_forward: Option<Box<dyn Send + Sync>>,
+}
+
+impl ServiceEndpoint {
+    pub fn new(url: Url, port_forward: Option<Box<dyn Send + Sync>>) -> Self {
+        Self {
+            url,
+            _port_forward: port_forward,
+        }
+    }
+
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+}
+
+impl Debug for ServiceEndpoint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.url)
+    }
+}
+
+/// Why a Node could not be profiled
+#[derive(Error, Debug)]
+pub enum ProfilingError {
+    // the admin service is disabled, requires a passcode, or the node is not built for linux
+    #[error("Profiling is not enabled on node {node}: {message}")]
+    NotEnabled { node: String, message: String },
+    #[error("Profiling node {node} failed with {status}: {message}")]
+    Failed {
+        node: String,
+        status: StatusCode,
+        message: String,
+    },
+}
+
+impl ProfilingError {
+    fn from_response(node: &str, status: StatusCode, message: String) -> Self {
+        let node = node.to_string();
+        match status {
+            StatusCode::NOT_FOUND | StatusCode::NETWORK_AUTHENTICATION_REQUIRED => {
+                ProfilingError::NotEnabled { node, message }
+            },
+            status => ProfilingError::Failed {
+                node,
+                status,
+                message,
+            },
+        }
+    }
+}
+
+/// Fetch the `/counters` JSON served at `host:port` and read the given counter as a f64
+pub(crate) async fn fetch_counter(host: &str, port: u64, counter: &str) -> Result<f64> {
+    let response: Value = reqwest::get(format!("http://{}:{}/counters", host, port))
+        .await?
+        .json()
+        .await?;
+    if let Value::Number(ref response) = response[counter] {
+        if let Some(response) = response.as_f64() {
+            Ok(response)
+        } else {
+            Err(format_err!(
+                "Failed to parse counter({}) as f64: {:?}",
+                counter,
+                response
+            ))
+        }
+    } else {
+        Err(format_err!(
+            "Counter({}) was not a Value::Number: {:?}",
+            counter,
+            response[counter]
+        ))
+    }
 }
 
 /// Trait used to represent a running Validator
@@ -150,14 +440,20 @@ impl<T: ?Sized> NodeExt for T where T: Node {}
 pub trait NodeExt: Node {
     /// Return REST API client of this Node
     fn rest_client(&self) -> RestClient {
-        RestClient::new(self.rest_api_endpoint())
+        self.rest_client_builder().build()
     }
 
     /// Return REST API client of this Node
     fn rest_client_with_timeout(&self, timeout: Duration) -> RestClient {
-        RestClient::builder(AptosBaseUrl::Custom(self.rest_api_endpoint()))
-            .timeout(timeout)
-            .build()
+        self.rest_client_builder().timeout(timeout).build()
+    }
+
+    /// A builder for REST API clients of this Node, with the Node's timeouts and retry policy
+    fn rest_client_builder(&self) -> ClientBuilder {
+        self.rest_client_options()
+            .apply(RestClient::builder(AptosBaseUrl::Custom(
+                self.rest_api_endpoint(),
+            )))
     }
 
     /// Return an InspectionClient for this Node
@@ -165,12 +461,6 @@ pub trait NodeExt: Node {
         InspectionClient::new(self.inspection_service_endpoint())
     }
 
-    /// Restarts this Node by calling Node::Stop followed by Node::Start
-    async fn restart(&mut self) -> Result<()> {
-        self.stop().await?;
-        self.start().await
-    }
-
     /// Query a Metric for from this Node
     async fn get_metric_i64(&self, metric_name: &str) -> Result<Option<i64>> {
         self.inspection_client()
@@ -178,6 +468,65 @@ pub trait NodeExt: Node {
             .await
     }
 
+    /// Scrape all the metrics exposed on this Node's inspection service
+    async fn get_metrics(&self) -> Result<NodeMetrics> {
+        let url = self.inspection_service_endpoint().join("metrics")?;
+        let text = reqwest::get(url).await?.error_for_status()?.text().await?;
+        NodeMetrics::parse(&text)
+    }
+
+    /// Scrape the given metric (counter or gauge) and sum the samples whose labels match all of
+    /// `label_filters`. Returns 0 if no samples match, including when the metric is not exposed.
+    async fn metric_sum(&self, name: &str, label_filters: &[(&str, &str)]) -> Result<f64> {
+        Ok(self
+            .get_metrics()
+            .await?
+            .sum_with_labels(name, label_filters))
+    }
+
+    /// The size of the node's DBs and the progress of its pruners, from its metrics
+    async fn get_storage_metrics(&self) -> Result<StorageMetrics> {
+        Ok(StorageMetrics::from_metrics(&self.get_metrics().await?))
+    }
+
+    /// The node's resident memory and, if it exports them, its jemalloc stats
+    async fn memory_stats(&self) -> Result<MemoryStats> {
+        MemoryStats::from_metrics(&self.get_metrics().await?)
+    }
+
+    /// The hash of the genesis transaction of the node's chain
+    async fn get_genesis_txn_hash(&self) -> Result<HashValue> {
+        fetch_genesis_txn_hash(&self.rest_client()).await
+    }
+
+    /// The waypoint of the node's genesis, which new nodes bootstrap from
+    async fn get_waypoint(&self) -> Result<Waypoint> {
+        fetch_genesis_waypoint(&self.rest_client()).await
+    }
+
+    /// Record a CPU profile of this Node for the given duration, through its admin service, and
+    /// return it in the pprof protobuf format. Fails with a ProfilingError if profiling is not
+    /// enabled on the node.
+    async fn collect_cpu_profile(&self, duration: Duration) -> Result<Vec<u8>> {
+        let endpoint = self.admin_service_endpoint().await?;
+        let mut url = endpoint.url().join("profilez")?;
+        url.query_pairs_mut()
+            .append_pair("seconds", &duration.as_secs().max(1).to_string())
+            .append_pair("format", "proto");
+        info!("Collecting a {:?} CPU profile of {}", duration, self.name());
+        let response = reqwest::Client::new()
+            .get(url)
+            .timeout(duration + CPU_PROFILE_TIMEOUT_SLACK)
+            .send()
+            .await?;
+        let status = response.status();
+        if !status.is_success() {
+            let message = response.text().await.unwrap_or_default();
+            return Err(ProfilingError::from_response(self.name(), status, message).into());
+        }
+        Ok(response.bytes().await?.to_vec())
+    }
+
     async fn get_metric_with_fields_i64(
         &self,
         metric_name: &str,
@@ -223,35 +572,807 @@ pub trait NodeExt: Node {
             .await
     }
 
-    async fn liveness_check(&self, seconds: u64) -> Result<()> {
-        Ok(self.rest_client().health_check(seconds).await?)
+    /// Check that the ledger of this Node progressed within the last `duration_secs`, using the
+    /// `/-/healthy` endpoint of its REST API
+    async fn liveness_check(&self, duration_secs: u64) -> Result<()> {
+        Ok(self.rest_client().health_check(duration_secs).await?)
+    }
+
+    /// Check the health of this Node once, in the given mode
+    async fn health_check_with_mode(&self, mode: HealthCheckMode) -> Result<(), HealthCheckError> {
+        match mode {
+            HealthCheckMode::Reachability => self.health_check().await,
+            HealthCheckMode::Liveness { duration_secs } => self
+                .rest_client()
+                .health_check(duration_secs)
+                .await
+                .map_err(|e| liveness_error(duration_secs, e)),
+        }
     }
 
     async fn wait_until_healthy(&self, deadline: Instant) -> Result<()> {
-        let mut healthcheck_error =
-            HealthCheckError::Unknown(anyhow::anyhow!("No healthcheck performed yet"));
-        while Instant::now() < deadline {
-            healthcheck_error = match self.health_check().await {
-                Ok(()) => return Ok(()),
-                Err(HealthCheckError::NotRunning(error)) => {
-                    return Err(anyhow::anyhow!(
-                        "Node {}:{} not running! Error: {:?}",
-                        self.name(),
-                        self.peer_id(),
-                        error,
-                    ))
-                },
-                Err(e) => e, // For other errors we'll retry
-            };
+        self.wait_until_healthy_with_backoff(deadline, &HealthCheckBackoff::default())
+            .await
+    }
+
+    /// Wait until this Node is healthy, polling according to the given backoff
+    async fn wait_until_healthy_with_backoff(
+        &self,
+        deadline: Instant,
+        backoff: &HealthCheckBackoff,
+    ) -> Result<()> {
+        wait_for_health(
+            self,
+            deadline,
+            HealthCheckMode::Reachability,
+            false,
+            backoff,
+        )
+        .await
+    }
+
+    /// Wait until this Node is healthy in the given mode, polling according to the given backoff
+    async fn wait_until_healthy_with_mode(
+        &self,
+        deadline: Instant,
+        mode: HealthCheckMode,
+        backoff: &HealthCheckBackoff,
+    ) -> Result<()> {
+        wait_for_health(self, deadline, mode, false, backoff).await
+    }
+
+    /// Wait until this Node is reachable, even though its ledger may be stale, e.g. because it is
+    /// still syncing after being restarted
+    async fn wait_until_reachable(&self, deadline: Instant) -> Result<()> {
+        wait_for_health(
+            self,
+            deadline,
+            HealthCheckMode::Reachability,
+            true,
+            &HealthCheckBackoff::default(),
+        )
+        .await
+    }
 
+    /// Wait until this Node is connected to at least `min_peers` peers on the given network
+    async fn wait_for_connected_peers(
+        &self,
+        network_id: NetworkId,
+        min_peers: usize,
+        deadline: Instant,
+    ) -> Result<()> {
+        loop {
+            let last_result = match self.get_connected_peer_ids(network_id).await {
+                Ok(peers) if peers.len() >= min_peers => return Ok(()),
+                Ok(peers) => format!("connected to {} peers", peers.len()),
+                Err(e) => format!("failed to get connected peers: {}", e),
+            };
+            if Instant::now() >= deadline {
+                bail!(
+                    "Timed out waiting for Node {} to connect to {} peers on the {} network, it is {}",
+                    self.name(),
+                    min_peers,
+                    network_id,
+                    last_result
+                );
+            }
             tokio::time::sleep(Duration::from_millis(500)).await;
         }
+    }
 
-        Err(anyhow::anyhow!(
-            "Timed out waiting for Node {}:{} to be healthy: Error: {:?}",
+    /// Wait until this Node is within `max_lag` versions of the `reference` Node, e.g. after it
+    /// was restarted or wiped and has to state sync
+    async fn wait_until_caught_up_to(
+        &self,
+        reference: &dyn Node,
+        max_lag: u64,
+        timeout: Duration,
+    ) -> Result<()> {
+        let client = &self.rest_client();
+        let reference_client = &reference.rest_client();
+        wait_for_catch_up(
             self.name(),
-            self.peer_id(),
-            healthcheck_error
-        ))
+            reference.name(),
+            max_lag,
+            timeout,
+            &HealthCheckBackoff::default(),
+            DEFAULT_MAX_CONSECUTIVE_REST_FAILURES,
+            || async move {
+                let (state, reference_state) = futures::try_join!(
+                    client.get_ledger_information(),
+                    reference_client.get_ledger_information()
+                )?;
+                Ok::<_, anyhow::Error>((
+                    state.into_inner().version,
+                    reference_state.into_inner().version,
+                ))
+            },
+        )
+        .await
+    }
+
+    /// Wait until this Node has committed at least the given version
+    async fn wait_for_ledger_version(&self, version: u64, timeout: Duration) -> Result<()> {
+        self.wait_for_ledger_version_with_backoff(
+            version,
+            timeout,
+            &HealthCheckBackoff::default(),
+            DEFAULT_MAX_CONSECUTIVE_REST_FAILURES,
+        )
+        .await
+    }
+
+    /// Wait until this Node has committed at least the given version, polling according to the
+    /// given backoff. Fails early if fetching the ledger version fails more than
+    /// `max_consecutive_failures` times in a row.
+    async fn wait_for_ledger_version_with_backoff(
+        &self,
+        version: u64,
+        timeout: Duration,
+        backoff: &HealthCheckBackoff,
+        max_consecutive_failures: usize,
+    ) -> Result<()> {
+        let client = &self.rest_client();
+        wait_for_version(
+            self.name(),
+            version,
+            timeout,
+            backoff,
+            max_consecutive_failures,
+            || async move {
+                let state = client.get_ledger_information().await?.into_inner();
+                Ok::<_, anyhow::Error>(state.version)
+            },
+        )
+        .await
+    }
+}
+
+/// How to space out health checks while waiting for a Node to become healthy. The interval
+/// between checks grows exponentially, so that large swarms don't flood their REST APIs.
+#[derive(Clone, Debug)]
+pub struct HealthCheckBackoff {
+    pub initial_interval: Duration,
+    pub max_interval: Duration,
+    pub multiplier: f64,
+    // randomize each interval by +/- 50%, to avoid many nodes polling in lockstep
+    pub jitter: bool,
+}
+
+impl Default for HealthCheckBackoff {
+    fn default() -> Self {
+        Self {
+            initial_interval: Duration::from_millis(500),
+            max_interval: Duration::from_secs(10),
+            multiplier: 2.0,
+            jitter: true,
+        }
+    }
+}
+
+impl HealthCheckBackoff {
+    fn next_interval(&self, interval: Duration) -> Duration {
+        interval.mul_f64(self.multiplier).min(self.max_interval)
+    }
+
+    fn sleep_duration(&self, interval: Duration) -> Duration {
+        if self.jitter {
+            interval.mul_f64(rand::thread_rng().gen_range(0.5..1.5))
+        } else {
+            interval
+        }
+    }
+}
+
+/// The genesis transaction, as committed at version 0. Fails if the node pruned it.
+async fn fetch_genesis_txn(client: &RestClient) -> Result<TransactionOnChainData> {
+    match client.get_transaction_by_version_bcs(0).await?.into_inner() {
+        TransactionData::OnChain(txn) => match &txn.transaction {
+            Transaction::GenesisTransaction(_) => Ok(txn),
+            _ => bail!("Transaction at version 0 is not a genesis transaction"),
+        },
+        TransactionData::Pending(_) => bail!("Transaction at version 0 is pending"),
+    }
+}
+
+/// The hash of the genesis transaction of the chain the REST API serves
+pub async fn fetch_genesis_txn_hash(client: &RestClient) -> Result<HashValue> {
+    Ok(fetch_genesis_txn(client).await?.info.transaction_hash())
+}
+
+/// The genesis waypoint of the chain the REST API serves. The nodes derive it from the ledger
+/// info at version 0, which is the genesis one: epoch 0 at timestamp 0, ending with the validator
+/// set the genesis installed.
+pub async fn fetch_genesis_waypoint(client: &RestClient) -> Result<Waypoint> {
+    let txn = fetch_genesis_txn(client).await?;
+    let validator_set: ValidatorSet = client
+        .get_account_resource_at_version_bcs(CORE_CODE_ADDRESS, "0x1::stake::ValidatorSet", 0)
+        .await?
+        .into_inner();
+    Waypoint::new_epoch_boundary(&LedgerInfo::genesis(
+        txn.accumulator_root_hash,
+        validator_set,
+    ))
+}
+
+/// Poll `fetch` until `done` holds for what it returns, or the timeout expires, in which case the
+/// last value fetched is returned. Errors are retried, unless there are more than
+/// `max_consecutive_failures` of them in a row, e.g. because the node is down rather than its
+/// port-forward blipping.
+async fn poll_until<T, F, Fut>(
+    what: &str,
+    timeout: Duration,
+    backoff: &HealthCheckBackoff,
+    max_consecutive_failures: usize,
+    mut fetch: F,
+    done: impl Fn(&T) -> bool + Send,
+) -> Result<Result<(), Option<T>>>
+where
+    T: Send,
+    F: FnMut() -> Fut + Send,
+    Fut: Future<Output = Result<T>> + Send,
+{
+    let deadline = Instant::now() + timeout;
+    let mut interval = backoff.initial_interval;
+    let mut last_value = None;
+    let mut consecutive_failures = 0;
+    loop {
+        match fetch().await {
+            Ok(value) if done(&value) => return Ok(Ok(())),
+            Ok(value) => {
+                last_value = Some(value);
+                consecutive_failures = 0;
+            },
+            Err(e) => {
+                consecutive_failures += 1;
+                if consecutive_failures > max_consecutive_failures {
+                    return Err(e.context(format!(
+                        "Failed to {} {} times in a row",
+                        what, consecutive_failures
+                    )));
+                }
+                info!("Failed to {}, retrying: {}", what, e);
+            },
+        }
+
+        let now = Instant::now();
+        if now >= deadline {
+            return Ok(Err(last_value));
+        }
+        tokio::time::sleep(backoff.sleep_duration(interval).min(deadline - now)).await;
+        interval = backoff.next_interval(interval);
+    }
+}
+
+/// Poll `fetch_version` until it reports at least `target_version`
+async fn wait_for_version<F, Fut>(
+    node_name: &str,
+    target_version: u64,
+    timeout: Duration,
+    backoff: &HealthCheckBackoff,
+    max_consecutive_failures: usize,
+    fetch_version: F,
+) -> Result<()>
+where
+    F: FnMut() -> Fut + Send,
+    Fut: Future<Output = Result<u64>> + Send,
+{
+    let what = format!("get the ledger version of Node {}", node_name);
+    let outcome = poll_until(
+        &what,
+        timeout,
+        backoff,
+        max_consecutive_failures,
+        fetch_version,
+        |version| *version >= target_version,
+    )
+    .await?;
+    match outcome {
+        Ok(()) => Ok(()),
+        Err(Some(version)) => bail!(
+            "Timed out after {:?} waiting for Node {} to reach version {}: it is at version {}, {} behind",
+            timeout,
+            node_name,
+            target_version,
+            version,
+            target_version - version
+        ),
+        Err(None) => bail!(
+            "Timed out after {:?} waiting for Node {} to reach version {}: it never reported its version",
+            timeout,
+            node_name,
+            target_version
+        ),
+    }
+}
+
+/// Poll `fetch_versions`, which returns the versions of a node and of a reference node, until the
+/// node lags behind the reference by at most `max_lag`. The reference may keep advancing meanwhile.
+async fn wait_for_catch_up<F, Fut>(
+    node_name: &str,
+    reference_name: &str,
+    max_lag: u64,
+    timeout: Duration,
+    backoff: &HealthCheckBackoff,
+    max_consecutive_failures: usize,
+    fetch_versions: F,
+) -> Result<()>
+where
+    F: FnMut() -> Fut + Send,
+    Fut: Future<Output = Result<(u64, u64)>> + Send,
+{
+    let what = format!(
+        "get the ledger versions of Nodes {} and {}",
+        node_name, reference_name
+    );
+    let outcome = poll_until(
+        &what,
+        timeout,
+        backoff,
+        max_consecutive_failures,
+        fetch_versions,
+        |(version, reference_version)| reference_version.saturating_sub(*version) <= max_lag,
+    )
+    .await?;
+    match outcome {
+        Ok(()) => Ok(()),
+        Err(Some((version, reference_version))) => bail!(
+            "Timed out after {:?} waiting for Node {} to catch up to {}: it is at version {} and {} at version {}, a lag of {} (max {})",
+            timeout,
+            node_name,
+            reference_name,
+            version,
+            reference_name,
+            reference_version,
+            reference_version - version,
+            max_lag
+        ),
+        Err(None) => bail!(
+            "Timed out after {:?} waiting for Node {} to catch up to {}: they never reported their versions",
+            timeout,
+            node_name,
+            reference_name
+        ),
+    }
+}
+
+/// The `/-/healthy` endpoint answers with an error status if the ledger is stale, any other
+/// failure means the REST API could not be reached
+fn liveness_error(duration_secs: u64, error: RestError) -> HealthCheckError {
+    match error {
+        RestError::Api(_) | RestError::Http(..) => HealthCheckError::Stale(format!(
+            "Ledger did not progress in the last {}s: {}",
+            duration_secs, error
+        )),
+        error => HealthCheckError::Failure(error.into()),
+    }
+}
+
+async fn wait_for_health<N: Node + ?Sized>(
+    node: &N,
+    deadline: Instant,
+    mode: HealthCheckMode,
+    allow_stale: bool,
+    backoff: &HealthCheckBackoff,
+) -> Result<()> {
+    let start = Instant::now();
+    let mut interval = backoff.initial_interval;
+    let healthcheck_error = loop {
+        let healthcheck_error = match node.health_check_with_mode(mode).await {
+            Ok(()) => return Ok(()),
+            Err(HealthCheckError::Stale(_)) if allow_stale => return Ok(()),
+            Err(HealthCheckError::NotRunning(error)) => {
+                return Err(anyhow::anyhow!(
+                    "Node {}:{} not running! Error: {:?}",
+                    node.name(),
+                    node.peer_id(),
+                    error,
+                ))
+            },
+            // keep the error itself, so that callers can tell why the node is failing
+            Err(HealthCheckError::Unrecoverable(error)) => {
+                return Err(error.context(format!(
+                    "Node {}:{} will not become healthy",
+                    node.name(),
+                    node.peer_id()
+                )))
+            },
+            Err(e) => e, // For other errors we'll retry
+        };
+
+        // always check one last time at the deadline, instead of giving up right before it
+        let now = Instant::now();
+        if now >= deadline {
+            break healthcheck_error;
+        }
+        tokio::time::sleep(backoff.sleep_duration(interval).min(deadline - now)).await;
+        interval = backoff.next_interval(interval);
+    };
+
+    info!(
+        "Node {} still unhealthy at the deadline: {:?}",
+        node.name(),
+        healthcheck_error
+    );
+    Err(anyhow::anyhow!(
+        "Timed out after {:?} waiting for Node {}:{} to be healthy: Error: {:?}",
+        start.elapsed(),
+        node.name(),
+        node.peer_id(),
+        healthcheck_error
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aptos_rest_client::aptos_api_types::{AptosError, AptosErrorCode};
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    };
+
+    /// A node that fails its first N health checks
+    struct MockNode {
+        failures_left: AtomicUsize,
+        health_checks: AtomicUsize,
+        rest_api_endpoint: Option<Url>,
+        rest_client_options: RestClientOptions,
+    }
+
+    impl MockNode {
+        fn new(failures: usize) -> Self {
+            Self {
+                failures_left: AtomicUsize::new(failures),
+                health_checks: AtomicUsize::new(0),
+                rest_api_endpoint: None,
+                rest_client_options: RestClientOptions::default(),
+            }
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl Node for MockNode {
+        fn peer_id(&self) -> PeerId {
+            PeerId::ZERO
+        }
+
+        fn index(&self) -> usize {
+            0
+        }
+
+        fn name(&self) -> &str {
+            "mock-node"
+        }
+
+        fn version(&self) -> Version {
+            Version::new(0, "mock".to_string())
+        }
+
+        fn rest_api_endpoint(&self) -> Url {
+            self.rest_api_endpoint.clone().unwrap()
+        }
+
+        fn rest_client_options(&self) -> RestClientOptions {
+            self.rest_client_options.clone()
+        }
+
+        fn inspection_service_endpoint(&self) -> Url {
+            // nothing listens there, so the requests to it fail
+            Url::parse("http://127.0.0.1:9").unwrap()
+        }
+
+        async fn config(&self) -> Result<NodeConfig> {
+            bail!("{} has no config", self.name())
+        }
+
+        async fn start(&self) -> Result<()> {
+            Ok(())
+        }
+
+        async fn stop(&self) -> Result<()> {
+            Ok(())
+        }
+
+        async fn kill(&self) -> Result<()> {
+            Ok(())
+        }
+
+        async fn get_identity(&self) -> Result<String> {
+            bail!("{} has no identity", self.name())
+        }
+
+        async fn set_identity(&self, _k8s_secret_name: String) -> Result<()> {
+            bail!("{} has no identity", self.name())
+        }
+
+        async fn clear_storage(&self) -> Result<()> {
+            Ok(())
+        }
+
+        async fn health_check(&self) -> Result<(), HealthCheckError> {
+            self.health_checks.fetch_add(1, Ordering::SeqCst);
+            let failures_left = self.failures_left.load(Ordering::SeqCst);
+            if failures_left == 0 {
+                return Ok(());
+            }
+            self.failures_left
+                .store(failures_left - 1, Ordering::SeqCst);
+            Err(HealthCheckError::Failure(anyhow!(
+                "{} failures left",
+                failures_left
+            )))
+        }
+
+        async fn counter(&self, counter: &str) -> Result<f64> {
+            bail!("{} has no counter {}", self.name(), counter)
+        }
+
+        async fn expose_metric(&self) -> Result<MetricsPortForward> {
+            bail!("{} has no metrics", self.name())
+        }
+
+        async fn admin_service_endpoint(&self) -> Result<ServiceEndpoint> {
+            bail!("{} has no admin service", self.name())
+        }
+
+        fn service_name(&self) -> Option<String> {
+            None
+        }
+
+        async fn get_connected_peer_ids(&self, _network_id: NetworkId) -> Result<Vec<PeerId>> {
+            Ok(vec![])
+        }
+    }
+
+    fn fast_backoff() -> HealthCheckBackoff {
+        HealthCheckBackoff {
+            initial_interval: Duration::from_millis(1),
+            max_interval: Duration::from_millis(8),
+            multiplier: 2.0,
+            jitter: true,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_wait_until_healthy_after_failures() {
+        let node = MockNode::new(5);
+        node.wait_until_healthy_with_backoff(
+            Instant::now() + Duration::from_secs(10),
+            &fast_backoff(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(node.health_checks.load(Ordering::SeqCst), 6);
+    }
+
+    #[tokio::test]
+    async fn test_wait_until_healthy_times_out() {
+        let node = MockNode::new(usize::MAX);
+        let err = node
+            .wait_until_healthy_with_backoff(
+                Instant::now() + Duration::from_millis(50),
+                &fast_backoff(),
+            )
+            .await
+            .unwrap_err();
+        // the last health check error is reported
+        assert!(err.to_string().contains("failures left"));
+        assert!(node.health_checks.load(Ordering::SeqCst) > 1);
+    }
+
+    fn is_timeout(error: &RestError) -> bool {
+        matches!(error, RestError::Unknown(e)
+            if e.downcast_ref::<reqwest::Error>().map_or(false, reqwest::Error::is_timeout))
+    }
+
+    #[tokio::test]
+    async fn test_rest_client_timeouts() {
+        // connections are queued by the kernel, but never answered
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut node = MockNode::new(0);
+        node.rest_api_endpoint =
+            Some(Url::parse(&format!("http://{}/v1", listener.local_addr().unwrap())).unwrap());
+        node.rest_client_options = RestClientOptions {
+            timeout: Some(Duration::from_millis(100)),
+            ..RestClientOptions::default()
+        };
+
+        let start = Instant::now();
+        let error = node
+            .rest_client()
+            .get_ledger_information()
+            .await
+            .unwrap_err();
+        assert!(is_timeout(&error), "{:?}", error);
+
+        // a per-call override beats the node's options
+        let error = node
+            .rest_client_with_timeout(Duration::from_millis(50))
+            .get_ledger_information()
+            .await
+            .unwrap_err();
+        assert!(is_timeout(&error), "{:?}", error);
+        // far below the 10s default of the client
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_liveness_error() {
+        let error = AptosError::new_with_error_code("stale", AptosErrorCode::HealthCheckFailed);
+        let stale = liveness_error(
+            10,
+            RestError::from((error, None, reqwest::StatusCode::SERVICE_UNAVAILABLE)),
+        );
+        assert!(matches!(stale, HealthCheckError::Stale(_)));
+
+        let unreachable = liveness_error(10, RestError::Unknown(anyhow!("connection refused")));
+        assert!(matches!(unreachable, HealthCheckError::Failure(_)));
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_version() {
+        // the version advances, with a transient failure along the way
+        let versions = Mutex::new(vec![Ok(10), Err(anyhow!("502")), Ok(50), Ok(100)].into_iter());
+        wait_for_version(
+            "mock-node",
+            100,
+            Duration::from_secs(10),
+            &fast_backoff(),
+            1,
+            || {
+                let version = versions.lock().unwrap().next().unwrap();
+                async move { version }
+            },
+        )
+        .await
+        .unwrap();
+
+        // too many failures in a row
+        let versions = Mutex::new(vec![Err(anyhow!("502")), Err(anyhow!("502"))].into_iter());
+        let err = wait_for_version(
+            "mock-node",
+            100,
+            Duration::from_secs(10),
+            &fast_backoff(),
+            1,
+            || {
+                let version = versions.lock().unwrap().next().unwrap();
+                async move { version }
+            },
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains("2 times in a row"));
+
+        // the node is stuck, so the error says how far behind it is
+        let err = wait_for_version(
+            "mock-node",
+            100,
+            Duration::from_millis(50),
+            &fast_backoff(),
+            1,
+            || async { Ok(40) },
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains("it is at version 40, 60 behind"));
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_catch_up() {
+        // the reference keeps advancing, but the node catches up to it
+        let versions = Mutex::new(vec![Ok((10, 100)), Ok((95, 150)), Ok((140, 150))].into_iter());
+        wait_for_catch_up(
+            "mock-node",
+            "reference",
+            10,
+            Duration::from_secs(10),
+            &fast_backoff(),
+            1,
+            || {
+                let versions = versions.lock().unwrap().next().unwrap();
+                async move { versions }
+            },
+        )
+        .await
+        .unwrap();
+
+        let err = wait_for_catch_up(
+            "mock-node",
+            "reference",
+            10,
+            Duration::from_millis(50),
+            &fast_backoff(),
+            1,
+            || async { Ok((10, 100)) },
+        )
+        .await
+        .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("it is at version 10 and reference at version 100, a lag of 90"));
+    }
+
+    #[test]
+    fn test_parse_connected_peers() {
+        let validator = PeerId::random();
+        let disconnecting = PeerId::random();
+        let vfn = PeerId::random();
+        let line = |network_id: NetworkId, peer_id: PeerId, state: &str| {
+            format!(
+                "\t- Peer: {}:{}, connection state: {}, connection metadata: {{\"remote_peer_id\":\"{}\",\"connection_id\":3}}",
+                network_id,
+                peer_id.short_str(),
+                state,
+                peer_id.to_hex()
+            )
+        };
+        let peer_information = [
+            "Peer information summary:".to_string(),
+            "\t- Number of peers: 3".to_string(),
+            "Connection metadata for each peer:".to_string(),
+            line(NetworkId::Validator, validator, "Connected"),
+            line(NetworkId::Validator, disconnecting, "Disconnecting"),
+            line(NetworkId::Vfn, vfn, "Connected"),
+            "Basic monitoring metadata for each peer:".to_string(),
+            format!(
+                "\t- Peer: Validator:{}, basic metadata: {{}}",
+                validator.short_str()
+            ),
+        ]
+        .join("\n");
+
+        assert_eq!(
+            parse_connected_peers(&peer_information, NetworkId::Validator).unwrap(),
+            vec![validator]
+        );
+        assert_eq!(
+            parse_connected_peers(&peer_information, NetworkId::Vfn).unwrap(),
+            vec![vfn]
+        );
+        assert!(parse_connected_peers(&peer_information, NetworkId::Public)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_health_check_backoff_intervals() {
+        let backoff = HealthCheckBackoff {
+            jitter: false,
+            ..fast_backoff()
+        };
+        let mut interval = backoff.initial_interval;
+        let mut intervals = vec![];
+        for _ in 0..5 {
+            intervals.push(backoff.sleep_duration(interval).as_millis());
+            interval = backoff.next_interval(interval);
+        }
+        assert_eq!(intervals, vec![1, 2, 4, 8, 8]);
+    }
+
+    #[test]
+    fn test_profiling_error_from_response() {
+        let disabled = "AdminService is not enabled.".to_string();
+        assert!(matches!(
+            ProfilingError::from_response("validator-0", StatusCode::NOT_FOUND, disabled),
+            ProfilingError::NotEnabled { .. }
+        ));
+        assert!(matches!(
+            ProfilingError::from_response(
+                "validator-0",
+                StatusCode::NETWORK_AUTHENTICATION_REQUIRED,
+                "/profilez endpoint requires authentication.".to_string()
+            ),
+            ProfilingError::NotEnabled { .. }
+        ));
+        assert!(matches!(
+            ProfilingError::from_response(
+                "validator-0",
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to generate cpu profile".to_string()
+            ),
+            ProfilingError::Failed { .. }
+        ));
     }
 }
@@ -0,0 +1,366 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{Node, Result, Swarm, TestReport};
+use anyhow::{format_err, Context};
+use aptos_logger::{info, warn};
+use aptos_sdk::types::PeerId;
+use chrono::{DateTime, Utc};
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::{
+    sync::{watch, RwLock},
+    task::JoinHandle,
+    time::Instant,
+};
+
+const DEFAULT_DOWNTIME: Duration = Duration::from_secs(30);
+
+/// Which nodes the node killer picks from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NodeSelector {
+    Validators,
+    FullNodes,
+    All,
+}
+
+/// How the node killer takes a node down
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KillMode {
+    // the node is stopped, as with Node::stop
+    Graceful,
+    // the node's pod is deleted without a grace period, as with Node::kill. The backend may bring
+    // it back before the downtime is over.
+    Force,
+}
+
+/// When and how the node killer takes nodes down, see [spawn_node_killer]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NodeKillerOptions {
+    // between two kills, whether or not the nodes killed before are back
+    pub interval: Duration,
+    pub targets: NodeSelector,
+    pub kill_mode: KillMode,
+    // how long a killed node stays down before it is brought back
+    pub downtime: Duration,
+    // the killer skips a kill rather than take more validators down than this at once
+    pub max_validators_down: usize,
+}
+
+impl NodeKillerOptions {
+    pub fn new(interval: Duration, targets: NodeSelector, kill_mode: KillMode) -> Self {
+        Self {
+            interval,
+            targets,
+            kill_mode,
+            downtime: DEFAULT_DOWNTIME,
+            max_validators_down: 1,
+        }
+    }
+
+    pub fn with_downtime(mut self, downtime: Duration) -> Self {
+        self.downtime = downtime;
+        self
+    }
+
+    pub fn with_max_validators_down(mut self, max_validators_down: usize) -> Self {
+        self.max_validators_down = max_validators_down;
+        self
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NodeKillerEventKind {
+    Killed,
+    Restored,
+    Failed(String),
+}
+
+/// Something the node killer did to a node, and when
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NodeKillerEvent {
+    pub time: DateTime<Utc>,
+    pub node: String,
+    pub kind: NodeKillerEventKind,
+}
+
+/// Stops the node killer when dropped, after which it brings back the nodes that are down in the
+/// background. Call [ChaosHandle::stop] to wait for them instead.
+pub struct ChaosHandle {
+    stop_sender: watch::Sender<bool>,
+    task: Option<JoinHandle<()>>,
+    events: Arc<Mutex<Vec<NodeKillerEvent>>>,
+}
+
+impl ChaosHandle {
+    /// Stops killing nodes, and brings back the ones that are down
+    pub async fn stop(mut self) -> Result<Vec<NodeKillerEvent>> {
+        let _ = self.stop_sender.send(true);
+        if let Some(task) = self.task.take() {
+            task.await
+                .map_err(|e| format_err!("Node killer panicked: {}", e))?;
+        }
+        Ok(self.events())
+    }
+
+    /// What the node killer did so far
+    pub fn events(&self) -> Vec<NodeKillerEvent> {
+        self.events.lock().unwrap().clone()
+    }
+
+    pub fn report(&self, report: &mut TestReport) {
+        let events = self.events();
+        let lines: Vec<_> = events
+            .iter()
+            .map(|event| {
+                let kind = match &event.kind {
+                    NodeKillerEventKind::Killed => "killed".to_string(),
+                    NodeKillerEventKind::Restored => "restored".to_string(),
+                    NodeKillerEventKind::Failed(error) => format!("failed: {}", error),
+                };
+                format!("  {} {} {}", event.time.to_rfc3339(), event.node, kind)
+            })
+            .collect();
+        report.report_text(format!(
+            "Node killer made {} kills:\n{}",
+            events
+                .iter()
+                .filter(|event| event.kind == NodeKillerEventKind::Killed)
+                .count(),
+            lines.join("\n")
+        ));
+    }
+}
+
+impl Drop for ChaosHandle {
+    fn drop(&mut self) {
+        let _ = self.stop_sender.send(true);
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct DownNode {
+    peer_id: PeerId,
+    name: String,
+    validator: bool,
+    restore_at: Instant,
+}
+
+/// Spawns a task that, every interval, kills a random node of the swarm and brings it back after
+/// the downtime, until the returned handle is stopped or dropped. Kills are skipped while no node
+/// may be taken down. Only the nodes the killer took down count towards the validators down.
+pub fn spawn_node_killer(
+    swarm: Arc<RwLock<Box<dyn Swarm>>>,
+    options: NodeKillerOptions,
+) -> ChaosHandle {
+    let (stop_sender, stop_receiver) = watch::channel(false);
+    let events = Arc::new(Mutex::new(vec![]));
+    let task = tokio::spawn(run_node_killer(
+        swarm,
+        options,
+        stop_receiver,
+        events.clone(),
+    ));
+    ChaosHandle {
+        stop_sender,
+        task: Some(task),
+        events,
+    }
+}
+
+async fn run_node_killer(
+    swarm: Arc<RwLock<Box<dyn Swarm>>>,
+    options: NodeKillerOptions,
+    mut stop_receiver: watch::Receiver<bool>,
+    events: Arc<Mutex<Vec<NodeKillerEvent>>>,
+) {
+    let record = |node: &str, kind: NodeKillerEventKind| {
+        info!("Node killer: {} {:?}", node, kind);
+        events.lock().unwrap().push(NodeKillerEvent {
+            time: Utc::now(),
+            node: node.to_string(),
+            kind,
+        });
+    };
+    let mut rng = StdRng::from_entropy();
+    let mut down: Vec<DownNode> = vec![];
+    let mut next_kill = Instant::now() + options.interval;
+    while !*stop_receiver.borrow() {
+        let wake_up = down
+            .iter()
+            .map(|node| node.restore_at)
+            .chain(std::iter::once(next_kill))
+            .min()
+            .unwrap();
+        tokio::select! {
+            _ = tokio::time::sleep_until(wake_up) => {},
+            _ = stop_receiver.changed() => break,
+        }
+
+        let now = Instant::now();
+        let (due, still_down): (Vec<_>, Vec<_>) =
+            down.into_iter().partition(|node| node.restore_at <= now);
+        down = still_down;
+        for node in due {
+            restore(&swarm, &node, &record).await;
+        }
+
+        if now >= next_kill {
+            next_kill = now + options.interval;
+            let (validators, fullnodes) = {
+                let swarm = swarm.read().await;
+                let validators: Vec<_> = swarm.validators().map(|v| v.peer_id()).collect();
+                let fullnodes: Vec<_> = swarm.full_nodes().map(|f| f.peer_id()).collect();
+                (validators, fullnodes)
+            };
+            let candidates = kill_candidates(&validators, &fullnodes, &down, &options);
+            let (peer_id, validator) = match candidates.choose(&mut rng) {
+                Some(candidate) => *candidate,
+                None => {
+                    info!("Node killer: no node may be killed now, skipping");
+                    continue;
+                },
+            };
+            match kill(&swarm, peer_id, validator, options.kill_mode).await {
+                Ok(name) => {
+                    record(&name, NodeKillerEventKind::Killed);
+                    down.push(DownNode {
+                        peer_id,
+                        name,
+                        validator,
+                        restore_at: Instant::now() + options.downtime,
+                    });
+                },
+                Err(e) => record(
+                    &peer_id.to_string(),
+                    NodeKillerEventKind::Failed(e.to_string()),
+                ),
+            }
+        }
+    }
+    for node in down {
+        restore(&swarm, &node, &record).await;
+    }
+}
+
+/// The nodes that may be killed next, and whether they are validators
+fn kill_candidates(
+    validators: &[PeerId],
+    fullnodes: &[PeerId],
+    down: &[DownNode],
+    options: &NodeKillerOptions,
+) -> Vec<(PeerId, bool)> {
+    let is_up = |peer_id: &&PeerId| !down.iter().any(|node| node.peer_id == **peer_id);
+    let validators_down = down.iter().filter(|node| node.validator).count();
+    let mut candidates = vec![];
+    if options.targets != NodeSelector::FullNodes && validators_down < options.max_validators_down {
+        candidates.extend(validators.iter().filter(is_up).map(|v| (*v, true)));
+    }
+    if options.targets != NodeSelector::Validators {
+        candidates.extend(fullnodes.iter().filter(is_up).map(|f| (*f, false)));
+    }
+    candidates
+}
+
+async fn kill(
+    swarm: &RwLock<Box<dyn Swarm>>,
+    peer_id: PeerId,
+    validator: bool,
+    kill_mode: KillMode,
+) -> Result<String> {
+    let swarm = swarm.read().await;
+    if validator {
+        let node = swarm
+            .validator(peer_id)
+            .with_context(|| format!("Validator {} to kill is gone", peer_id))?;
+        kill_node(node, kill_mode).await?;
+        Ok(node.name().to_string())
+    } else {
+        let node = swarm
+            .full_node(peer_id)
+            .with_context(|| format!("Fullnode {} to kill is gone", peer_id))?;
+        kill_node(node, kill_mode).await?;
+        Ok(node.name().to_string())
+    }
+}
+
+async fn kill_node<N: Node + ?Sized>(node: &N, kill_mode: KillMode) -> Result<()> {
+    match kill_mode {
+        KillMode::Graceful => node.stop().await,
+        KillMode::Force => node.kill().await,
+    }
+}
+
+async fn restore(
+    swarm: &RwLock<Box<dyn Swarm>>,
+    node: &DownNode,
+    record: &impl Fn(&str, NodeKillerEventKind),
+) {
+    let swarm = swarm.read().await;
+    let result = if node.validator {
+        match swarm.validator(node.peer_id) {
+            Some(validator) => validator.start().await,
+            None => Err(format_err!("Validator is gone")),
+        }
+    } else {
+        match swarm.full_node(node.peer_id) {
+            Some(fullnode) => fullnode.start().await,
+            None => Err(format_err!("Fullnode is gone")),
+        }
+    };
+    match result {
+        Ok(()) => record(&node.name, NodeKillerEventKind::Restored),
+        Err(e) => {
+            warn!("Node killer failed to restore {}: {}", node.name, e);
+            record(&node.name, NodeKillerEventKind::Failed(e.to_string()));
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kill_candidates() {
+        let validators: Vec<_> = (0..4).map(|_| PeerId::random()).collect();
+        let fullnodes = vec![PeerId::random()];
+        let options = NodeKillerOptions::new(
+            Duration::from_secs(60),
+            NodeSelector::All,
+            KillMode::Graceful,
+        );
+        assert_eq!(
+            kill_candidates(&validators, &fullnodes, &[], &options).len(),
+            5
+        );
+
+        // one validator is down already, which is the most that may be
+        let down = vec![DownNode {
+            peer_id: validators[0],
+            name: "validator-0".to_string(),
+            validator: true,
+            restore_at: Instant::now(),
+        }];
+        assert_eq!(
+            kill_candidates(&validators, &fullnodes, &down, &options),
+            vec![(fullnodes[0], false)]
+        );
+        let options = options.with_max_validators_down(2);
+        let candidates = kill_candidates(&validators, &fullnodes, &down, &options);
+        assert_eq!(candidates.len(), 4);
+        assert!(!candidates.contains(&(validators[0], true)));
+
+        let options = NodeKillerOptions {
+            targets: NodeSelector::Validators,
+            ..options
+        };
+        assert_eq!(
+            kill_candidates(&validators, &fullnodes, &down, &options).len(),
+            3
+        );
+    }
+}
@@ -0,0 +1,202 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::Result;
+use anyhow::{bail, format_err};
+use std::{
+    collections::{BTreeMap, HashMap},
+    str::FromStr,
+};
+
+/// A single sample of a metric, as scraped from a node's Prometheus text exposition
+#[derive(Clone, Debug, PartialEq)]
+pub struct MetricSample {
+    pub labels: BTreeMap<String, String>,
+    pub value: f64,
+}
+
+impl MetricSample {
+    /// Returns true if every one of the given labels is present on this sample with the same value
+    pub fn matches(&self, labels: &[(&str, &str)]) -> bool {
+        labels
+            .iter()
+            .all(|(key, value)| self.labels.get(*key).map(String::as_str) == Some(*value))
+    }
+}
+
+/// All the metrics exposed by a node at a point in time, keyed by metric name
+#[derive(Clone, Debug, Default)]
+pub struct NodeMetrics(HashMap<String, Vec<MetricSample>>);
+
+impl NodeMetrics {
+    /// Parse the Prometheus text exposition format. Comments (HELP/TYPE) and timestamps are ignored.
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut metrics: HashMap<String, Vec<MetricSample>> = HashMap::new();
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (name, sample) = parse_sample_line(line)
+                .map_err(|e| format_err!("Failed to parse metric line {:?}: {}", line, e))?;
+            metrics.entry(name).or_default().push(sample);
+        }
+        Ok(Self(metrics))
+    }
+
+    /// Return all the samples for the given metric name
+    pub fn get(&self, name: &str) -> &[MetricSample] {
+        self.0.get(name).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Sum the values of all the samples of the given metric, across all label sets
+    pub fn sum(&self, name: &str) -> f64 {
+        self.get(name).iter().map(|sample| sample.value).sum()
+    }
+
+    /// Sum the values of the samples of the given metric that match all the given labels
+    pub fn sum_with_labels(&self, name: &str, labels: &[(&str, &str)]) -> f64 {
+        self.get(name)
+            .iter()
+            .filter(|sample| sample.matches(labels))
+            .map(|sample| sample.value)
+            .sum()
+    }
+
+    /// Return the names of all metrics that were exposed
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.0.keys().map(String::as_str)
+    }
+}
+
+fn parse_sample_line(line: &str) -> Result<(String, MetricSample)> {
+    let name_end = line
+        .find(|c: char| c == '{' || c.is_whitespace())
+        .ok_or_else(|| format_err!("missing value"))?;
+    let name = line[..name_end].to_string();
+    let mut rest = &line[name_end..];
+
+    let mut labels = BTreeMap::new();
+    if rest.starts_with('{') {
+        let mut chars = rest.char_indices().skip(1).peekable();
+        let mut end = None;
+        loop {
+            // skip separators between labels
+            while let Some((_, c)) = chars.peek() {
+                if *c == ',' || c.is_whitespace() {
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+            match chars.next() {
+                Some((i, '}')) => {
+                    end = Some(i);
+                    break;
+                },
+                Some((_, c)) => {
+                    let mut key = c.to_string();
+                    for (_, c) in chars.by_ref() {
+                        if c == '=' {
+                            break;
+                        }
+                        key.push(c);
+                    }
+                    if !matches!(chars.next(), Some((_, '"'))) {
+                        bail!("label {} has no quoted value", key);
+                    }
+                    let mut value = String::new();
+                    let mut closed = false;
+                    while let Some((_, c)) = chars.next() {
+                        match c {
+                            '\\' => match chars.next() {
+                                Some((_, 'n')) => value.push('\n'),
+                                Some((_, c)) => value.push(c),
+                                None => break,
+                            },
+                            '"' => {
+                                closed = true;
+                                break;
+                            },
+                            c => value.push(c),
+                        }
+                    }
+                    if !closed {
+                        bail!("unterminated value for label {}", key);
+                    }
+                    labels.insert(key.trim().to_string(), value);
+                },
+                None => break,
+            }
+        }
+        let end = end.ok_or_else(|| format_err!("unterminated label set"))?;
+        rest = &rest[end + 1..];
+    }
+
+    let value = rest
+        .split_whitespace()
+        .next()
+        .ok_or_else(|| format_err!("missing value"))?;
+    let value = f64::from_str(value).map_err(|e| format_err!("invalid value {}: {}", value, e))?;
+    Ok((name, MetricSample { labels, value }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_node_metrics() {
+        let text = r#"
+# HELP aptos_connections Number of current connections and their direction
+# TYPE aptos_connections gauge
+aptos_connections{direction="inbound",network_id="Validator",peer_id="4d8a",role_type="validator"} 3
+aptos_connections{direction="outbound",network_id="Validator",peer_id="4d8a",role_type="validator"} 2
+aptos_consensus_proposals_count 17 1700000000000
+escaped_metric{path="a \"quoted\", {braced} value"} +Inf
+"#;
+        let metrics = NodeMetrics::parse(text).unwrap();
+        assert_eq!(metrics.sum("aptos_connections"), 5.0);
+        assert_eq!(
+            metrics.sum_with_labels("aptos_connections", &[("direction", "inbound")]),
+            3.0
+        );
+        assert_eq!(metrics.sum("aptos_consensus_proposals_count"), 17.0);
+        assert_eq!(metrics.sum("missing_metric"), 0.0);
+
+        let escaped = metrics.get("escaped_metric");
+        assert_eq!(escaped.len(), 1);
+        assert_eq!(
+            escaped[0].labels.get("path").unwrap(),
+            "a \"quoted\", {braced} value"
+        );
+        assert!(escaped[0].value.is_infinite());
+    }
+
+    #[test]
+    fn test_sum_with_labels() {
+        let text = r#"
+aptos_network_pending_outbound_messages{peer_id="a",network_id="Validator"} 2
+aptos_network_pending_outbound_messages{peer_id="b",network_id="Validator"} 3
+aptos_network_pending_outbound_messages{peer_id="c",network_id="Public"} 5
+"#;
+        let metrics = NodeMetrics::parse(text).unwrap();
+        let name = "aptos_network_pending_outbound_messages";
+        assert_eq!(metrics.sum_with_labels(name, &[]), 10.0);
+        assert_eq!(
+            metrics.sum_with_labels(name, &[("network_id", "Validator")]),
+            5.0
+        );
+        assert_eq!(
+            metrics.sum_with_labels(name, &[("network_id", "Validator"), ("peer_id", "b")]),
+            3.0
+        );
+        assert_eq!(metrics.sum_with_labels(name, &[("peer_id", "d")]), 0.0);
+    }
+
+    #[test]
+    fn test_parse_node_metrics_invalid() {
+        NodeMetrics::parse("aptos_connections{direction=\"inbound\" 3").unwrap_err();
+        NodeMetrics::parse("aptos_connections not_a_number").unwrap_err();
+    }
+}
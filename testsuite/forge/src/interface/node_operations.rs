@@ -0,0 +1,234 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{Node, NodeExt, Result};
+use anyhow::format_err;
+use aptos_sdk::types::PeerId;
+use futures::{future::BoxFuture, stream, FutureExt, StreamExt};
+use std::{
+    fmt::{Display, Formatter},
+    time::{Duration, Instant},
+};
+
+/// How many nodes SwarmExt::start_all and SwarmExt::stop_all operate on at once
+pub const NODE_OPERATION_FAN_OUT: usize = 16;
+
+/// What is done to each node by [crate::SwarmExt::start_all], [crate::SwarmExt::stop_all] and
+/// [crate::SwarmExt::restart_all]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NodeOperation {
+    Start,
+    Stop,
+    // stop, start and wait for the node to be healthy again
+    Restart { health_timeout: Duration },
+}
+
+impl Display for NodeOperation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NodeOperation::Start => write!(f, "start"),
+            NodeOperation::Stop => write!(f, "stop"),
+            NodeOperation::Restart { .. } => write!(f, "restart"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct NodeOperationResult {
+    pub name: String,
+    pub peer_id: PeerId,
+    pub duration: Duration,
+    pub result: Result<()>,
+}
+
+/// The outcome of an operation on many nodes. Every node is operated on, whether or not the
+/// others fail.
+#[derive(Debug)]
+pub struct NodeOperationSummary {
+    pub operation: NodeOperation,
+    // in the order the nodes were given in
+    pub nodes: Vec<NodeOperationResult>,
+}
+
+impl NodeOperationSummary {
+    pub fn is_ok(&self) -> bool {
+        self.nodes.iter().all(|node| node.result.is_ok())
+    }
+
+    pub fn failed(&self) -> impl Iterator<Item = &NodeOperationResult> {
+        self.nodes.iter().filter(|node| node.result.is_err())
+    }
+
+    pub fn slowest(&self) -> Option<&NodeOperationResult> {
+        self.nodes.iter().max_by_key(|node| node.duration)
+    }
+
+    /// Fails with the errors of all the failed nodes, if any
+    pub fn into_result(self) -> Result<Self> {
+        if self.is_ok() {
+            Ok(self)
+        } else {
+            Err(format_err!("{}", self))
+        }
+    }
+}
+
+/// The failures of the operation, nothing if all the nodes succeeded
+impl Display for NodeOperationSummary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.is_ok() {
+            return Ok(());
+        }
+        write!(
+            f,
+            "Failed to {} {} of {} nodes",
+            self.operation,
+            self.failed().count(),
+            self.nodes.len()
+        )?;
+        if let Some(slowest) = self.slowest() {
+            write!(
+                f,
+                ", the slowest, {}, took {:?}",
+                slowest.name, slowest.duration
+            )?;
+        }
+        for node in self.failed() {
+            if let Err(e) = &node.result {
+                write!(
+                    f,
+                    "\n  {} failed after {:?}: {:#}",
+                    node.name, node.duration, e
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Operates on the node, timing it
+pub async fn run_node_operation<N: Node + ?Sized>(
+    node: &N,
+    operation: NodeOperation,
+) -> NodeOperationResult {
+    let start = Instant::now();
+    let result = match operation {
+        NodeOperation::Start => node.start().await,
+        NodeOperation::Stop => node.stop().await,
+        NodeOperation::Restart { health_timeout } => {
+            async {
+                node.stop().await?;
+                node.start().await?;
+                node.wait_until_healthy(start + health_timeout).await
+            }
+            .await
+        },
+    };
+    NodeOperationResult {
+        name: node.name().to_string(),
+        peer_id: node.peer_id(),
+        duration: start.elapsed(),
+        result,
+    }
+}
+
+/// Runs the operations, at most `concurrency` at a time, to completion of all of them
+pub async fn run_node_operations(
+    operation: NodeOperation,
+    operations: Vec<BoxFuture<'_, NodeOperationResult>>,
+    concurrency: usize,
+) -> NodeOperationSummary {
+    // unordered, so that a slow node doesn't hold up those after it
+    let mut nodes: Vec<_> = stream::iter(
+        operations
+            .into_iter()
+            .enumerate()
+            .map(|(i, operation)| operation.map(move |result| (i, result))),
+    )
+    .buffer_unordered(concurrency.max(1))
+    .collect()
+    .await;
+    nodes.sort_by_key(|(i, _)| *i);
+    NodeOperationSummary {
+        operation,
+        nodes: nodes.into_iter().map(|(_, result)| result).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    #[tokio::test]
+    async fn test_run_node_operations() {
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let max_in_flight = Arc::new(AtomicUsize::new(0));
+        let operations = (0..6)
+            .map(|i| {
+                let in_flight = in_flight.clone();
+                let max_in_flight = max_in_flight.clone();
+                async move {
+                    let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_in_flight.fetch_max(now, Ordering::SeqCst);
+                    // the first node is the slowest, the others still complete in the meantime
+                    let delay = if i == 0 { 50 } else { 5 };
+                    tokio::time::sleep(Duration::from_millis(delay)).await;
+                    in_flight.fetch_sub(1, Ordering::SeqCst);
+                    NodeOperationResult {
+                        name: format!("validator-{}", i),
+                        peer_id: PeerId::random(),
+                        duration: Duration::from_millis(delay),
+                        result: if i % 2 == 1 {
+                            Err(format_err!("pod is pending"))
+                        } else {
+                            Ok(())
+                        },
+                    }
+                }
+                .boxed()
+            })
+            .collect();
+        let summary = run_node_operations(NodeOperation::Stop, operations, 3).await;
+
+        assert_eq!(max_in_flight.load(Ordering::SeqCst), 3);
+        let names: Vec<_> = summary
+            .nodes
+            .iter()
+            .map(|node| node.name.as_str())
+            .collect();
+        assert_eq!(names, vec![
+            "validator-0",
+            "validator-1",
+            "validator-2",
+            "validator-3",
+            "validator-4",
+            "validator-5"
+        ]);
+        assert!(!summary.is_ok());
+        assert_eq!(summary.failed().count(), 3);
+        assert_eq!(summary.slowest().unwrap().name, "validator-0");
+
+        let error = summary.into_result().unwrap_err().to_string();
+        assert!(
+            error.starts_with("Failed to stop 3 of 6 nodes, the slowest, validator-0, took 50ms")
+        );
+        assert!(error.contains("validator-5 failed after 5ms: pod is pending"));
+        assert!(!error.contains("validator-4 failed"));
+
+        let summary = NodeOperationSummary {
+            operation: NodeOperation::Start,
+            nodes: vec![NodeOperationResult {
+                name: "validator-0".to_string(),
+                peer_id: PeerId::random(),
+                duration: Duration::from_millis(5),
+                result: Ok(()),
+            }],
+        };
+        assert_eq!(summary.to_string(), "");
+        assert!(summary.into_result().is_ok());
+    }
+}
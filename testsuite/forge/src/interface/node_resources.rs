@@ -0,0 +1,94 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    collections::BTreeMap,
+    fmt::{Display, Formatter},
+};
+
+/// The CPU and memory requests and limits of a node's container, as k8s quantities, e.g. "14"
+/// CPUs and "56Gi" of memory. Those that are not set are left as they are.
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodeResources {
+    pub cpu_request: Option<String>,
+    pub cpu_limit: Option<String>,
+    pub memory_request: Option<String>,
+    pub memory_limit: Option<String>,
+}
+
+impl NodeResources {
+    /// As many CPUs and as much memory requested as the node is limited to
+    pub fn new(cpu_cores: usize, memory_gib: usize) -> Self {
+        Self {
+            cpu_request: Some(cpu_cores.to_string()),
+            cpu_limit: Some(cpu_cores.to_string()),
+            memory_request: Some(format!("{}Gi", memory_gib)),
+            memory_limit: Some(format!("{}Gi", memory_gib)),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+impl Display for NodeResources {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let unset = "-".to_string();
+        write!(
+            f,
+            "cpu {}/{}, memory {}/{} (request/limit)",
+            self.cpu_request.as_ref().unwrap_or(&unset),
+            self.cpu_limit.as_ref().unwrap_or(&unset),
+            self.memory_request.as_ref().unwrap_or(&unset),
+            self.memory_limit.as_ref().unwrap_or(&unset)
+        )
+    }
+}
+
+/// One line per distinct resources, with the nodes that have them, for the test report
+pub fn describe_node_resources(nodes: &[(String, Option<NodeResources>)]) -> String {
+    let mut by_resources: BTreeMap<Option<&NodeResources>, Vec<&str>> = BTreeMap::new();
+    for (name, resources) in nodes {
+        by_resources
+            .entry(resources.as_ref())
+            .or_default()
+            .push(name);
+    }
+    by_resources
+        .into_iter()
+        .map(|(resources, names)| match resources {
+            Some(resources) => format!("{}: {}", names.join(", "), resources),
+            None => format!("{}: unknown", names.join(", ")),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_describe_node_resources() {
+        let large = NodeResources::new(16, 64);
+        let capped = NodeResources {
+            cpu_limit: Some("8".to_string()),
+            ..NodeResources::default()
+        };
+        assert!(NodeResources::default().is_empty());
+        assert!(!capped.is_empty());
+        let description = describe_node_resources(&[
+            ("validator-0".to_string(), Some(large.clone())),
+            ("validator-1".to_string(), Some(capped)),
+            ("validator-2".to_string(), Some(large)),
+            ("local-0".to_string(), None),
+        ]);
+        assert_eq!(
+            description,
+            "local-0: unknown\n\
+             validator-1: cpu -/8, memory -/- (request/limit)\n\
+             validator-0, validator-2: cpu 16/16, memory 64Gi/64Gi (request/limit)"
+        );
+    }
+}
@@ -2,8 +2,50 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::Swarm;
-use prometheus_http_query::response::Sample;
-use std::{collections::BTreeMap, fmt, sync::Arc};
+use prometheus_http_query::response::{PromqlResult, Sample};
+use std::{collections::BTreeMap, fmt, sync::Arc, time::Duration};
+
+/// The error of the Prometheus queries of a swarm without a Prometheus, e.g. a local swarm.
+/// Check for it with `error.is::<PrometheusUnavailable>()` to fall back to the nodes' own
+/// metrics.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PrometheusUnavailable;
+
+impl fmt::Display for PrometheusUnavailable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "No Prometheus is available for this swarm")
+    }
+}
+
+impl std::error::Error for PrometheusUnavailable {}
+
+/// The samples of an instant query, one per series, or the one sample of a scalar
+pub fn instant_samples(result: &PromqlResult) -> Vec<Sample> {
+    if let Some(vectors) = result.as_instant() {
+        vectors.iter().map(|v| v.sample().clone()).collect()
+    } else if let Some(scalar) = result.as_scalar() {
+        vec![scalar.clone()]
+    } else {
+        vec![]
+    }
+}
+
+/// The transactions the validators committed per second, averaged over them and the window
+pub fn avg_tps_query(window: Duration) -> String {
+    format!(
+        r#"avg(rate(aptos_consensus_committed_txns_count{{role=~"validator", state="success"}}[{}s]))"#,
+        window.as_secs().max(1)
+    )
+}
+
+/// The 99th percentile of the time from the proposal of a block to its commit, in seconds, over
+/// the blocks of all validators within the window
+pub fn p99_commit_latency_query(window: Duration) -> String {
+    format!(
+        r#"histogram_quantile(0.99, sum by (le) (rate(aptos_consensus_block_tracing_bucket{{role=~"validator", stage="committed"}}[{}s])))"#,
+        window.as_secs().max(1)
+    )
+}
 
 #[derive(Clone)]
 pub struct MetricSamples(Vec<Sample>);
@@ -211,3 +253,19 @@ pub async fn fetch_latency_breakdown(
 
     Ok(LatencyBreakdown::new(samples))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_queries() {
+        assert_eq!(
+            avg_tps_query(Duration::from_secs(300)),
+            r#"avg(rate(aptos_consensus_committed_txns_count{role=~"validator", state="success"}[300s]))"#
+        );
+        // Prometheus rejects an empty range
+        assert!(p99_commit_latency_query(Duration::from_millis(10)).ends_with("[1s])))"));
+        assert!(anyhow::Error::from(PrometheusUnavailable).is::<PrometheusUnavailable>());
+    }
+}
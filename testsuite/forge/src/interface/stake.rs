@@ -0,0 +1,350 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{AptosPublicInfo, Result};
+use anyhow::{bail, Context};
+use aptos_cached_packages::aptos_stdlib;
+use aptos_logger::{info, warn};
+use aptos_rest_client::Client as RestClient;
+use aptos_sdk::{
+    transaction_builder::TransactionFactory,
+    types::{
+        account_address::AccountAddress, account_config::CORE_CODE_ADDRESS,
+        on_chain_config::ValidatorSet, stake_pool::StakePool, transaction::TransactionPayload,
+        LocalAccount, PeerId,
+    },
+};
+use serde::Deserialize;
+
+/// The on-chain 0x1::staking_config::StakingConfig, with the fields in their on-chain order
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+pub struct StakingConfig {
+    pub minimum_stake: u64,
+    pub maximum_stake: u64,
+    pub recurring_lockup_duration_secs: u64,
+    pub allow_validator_set_change: bool,
+    pub rewards_rate: u64,
+    pub rewards_rate_denominator: u64,
+    // the percentage of the total voting power that may join in an epoch
+    pub voting_power_increase_limit: u64,
+}
+
+/// What to do to a stake pool to have it stake the given amount from the next epoch on
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StakeChange {
+    Add(u64),
+    // of the active stake only, the pending active stake can't be unlocked
+    Unlock(u64),
+}
+
+impl StakeChange {
+    /// None if the pool already stakes the amount. The pending inactive stake is not counted,
+    /// even though it is voted with until the lockup of the pool expires.
+    pub fn to(pool: &StakePool, amount: u64) -> Option<Self> {
+        let staked = pool.active + pool.pending_active;
+        if amount > staked {
+            Some(StakeChange::Add(amount - staked))
+        } else if amount < staked {
+            Some(StakeChange::Unlock((staked - amount).min(pool.active)))
+        } else {
+            None
+        }
+    }
+}
+
+/// How much more stake may join the validators in the current epoch, see
+/// [StakingConfig::voting_power_increase_limit]
+pub fn joining_headroom(validator_set: &ValidatorSet, voting_power_increase_limit: u64) -> u64 {
+    let limit = validator_set.total_voting_power * voting_power_increase_limit as u128 / 100;
+    limit
+        .saturating_sub(validator_set.total_joining_power)
+        .min(u64::MAX as u128) as u64
+}
+
+pub async fn get_stake_pool(info: &AptosPublicInfo, pool: AccountAddress) -> Result<StakePool> {
+    Ok(info
+        .client()
+        .get_account_resource_bcs(pool, "0x1::stake::StakePool")
+        .await
+        .with_context(|| format!("Failed to read the stake pool of {}", pool))?
+        .into_inner())
+}
+
+pub async fn get_staking_config(info: &AptosPublicInfo) -> Result<StakingConfig> {
+    Ok(info
+        .client()
+        .get_account_resource_bcs(CORE_CODE_ADDRESS, "0x1::staking_config::StakingConfig")
+        .await?
+        .into_inner())
+}
+
+pub async fn get_validator_set(info: &AptosPublicInfo) -> Result<ValidatorSet> {
+    Ok(info
+        .client()
+        .get_account_resource_bcs(CORE_CODE_ADDRESS, "0x1::stake::ValidatorSet")
+        .await?
+        .into_inner())
+}
+
+/// Adds coins of the owner to its stake pool, which it stakes from the next epoch on
+pub async fn add_stake(owner: &LocalAccount, info: &AptosPublicInfo, amount: u64) -> Result<()> {
+    submit_stake_payload(owner, info, aptos_stdlib::stake_add_stake(amount)).await
+}
+
+/// Unlocks stake of the owner's stake pool, which it keeps voting with until its lockup expires
+pub async fn unlock_stake(owner: &LocalAccount, info: &AptosPublicInfo, amount: u64) -> Result<()> {
+    submit_stake_payload(owner, info, aptos_stdlib::stake_unlock(amount)).await
+}
+
+/// Withdraws the inactive stake of the owner's stake pool to its account
+pub async fn withdraw_stake(
+    owner: &LocalAccount,
+    info: &AptosPublicInfo,
+    amount: u64,
+) -> Result<()> {
+    submit_stake_payload(owner, info, aptos_stdlib::stake_withdraw(amount)).await
+}
+
+async fn submit_stake_payload(
+    owner: &LocalAccount,
+    info: &AptosPublicInfo,
+    payload: TransactionPayload,
+) -> Result<()> {
+    let txn = owner.sign_with_transaction_builder(info.transaction_factory().payload(payload));
+    info.client()
+        .submit_and_wait(&txn)
+        .await
+        .with_context(|| format!("Failed to change the stake of {}", owner.address()))?;
+    Ok(())
+}
+
+/// Brings the stake of the owner's pool to `amount`, see [crate::Swarm::set_validator_stake].
+/// The root account mints the owner the coins it adds. What is added in one epoch is bounded by
+/// the voting power increase limit, so with `wait_for_epoch` the epoch is ended for as long as
+/// there is more to add, and once more for the last of it to count.
+pub async fn set_stake(
+    info: &mut AptosPublicInfo,
+    owner: &LocalAccount,
+    amount: u64,
+    wait_for_epoch: bool,
+) -> Result<()> {
+    let pool = owner.address();
+    let stake_pool = get_stake_pool(info, pool).await?;
+    let to_add = match StakeChange::to(&stake_pool, amount) {
+        None => 0,
+        Some(StakeChange::Unlock(to_unlock)) => {
+            let staked = stake_pool.active + stake_pool.pending_active;
+            if staked - to_unlock > amount {
+                warn!(
+                    "Unlocking only the {} active stake of {}, out of {} to bring its stake to {}, \
+                     the {} pending active stake can't be unlocked",
+                    to_unlock,
+                    pool,
+                    staked - amount,
+                    amount,
+                    stake_pool.pending_active
+                );
+            }
+            unlock_stake(owner, info, to_unlock).await?;
+            info!("Unlocked {} of the stake of {}", to_unlock, pool);
+            0
+        },
+        Some(StakeChange::Add(to_add)) => {
+            info.mint(pool, to_add).await?;
+            to_add
+        },
+    };
+    let voting_power_increase_limit = get_staking_config(info).await?.voting_power_increase_limit;
+    let mut added = 0;
+    while added < to_add {
+        let headroom =
+            joining_headroom(&get_validator_set(info).await?, voting_power_increase_limit);
+        if headroom == 0 {
+            bail!(
+                "Added {} of the {} stake to add to {}, no more may join the validators in this epoch, \
+                 with a voting power increase limit of {}%",
+                added,
+                to_add,
+                pool,
+                voting_power_increase_limit
+            );
+        }
+        let chunk = headroom.min(to_add - added);
+        add_stake(owner, info, chunk).await?;
+        added += chunk;
+        info!("Added {} of the {} stake to add to {}", added, to_add, pool);
+        if added < to_add {
+            if !wait_for_epoch {
+                bail!(
+                    "Added {} of the {} stake to add to {}, the rest exceeds the voting power \
+                     increase limit of {}% of this epoch",
+                    added,
+                    to_add,
+                    pool,
+                    voting_power_increase_limit
+                );
+            }
+            let root_account = info.root_account();
+            force_end_epoch(info.client(), info.transaction_factory(), &root_account).await?;
+        }
+    }
+    if wait_for_epoch {
+        let root_account = info.root_account();
+        force_end_epoch(info.client(), info.transaction_factory(), &root_account).await?;
+    }
+    Ok(())
+}
+
+/// Ends the current epoch with the root account, without waiting for the validators to be in
+/// the next one, see [crate::ChainInfo::trigger_reconfiguration] for that
+pub(crate) async fn force_end_epoch(
+    client: &RestClient,
+    transaction_factory: TransactionFactory,
+    root_account: &LocalAccount,
+) -> Result<()> {
+    let txn = root_account.sign_with_transaction_builder(
+        transaction_factory.payload(aptos_stdlib::aptos_governance_force_end_epoch_test_only()),
+    );
+    client
+        .submit_and_wait(&txn)
+        .await
+        .context("Failed to end the epoch")?;
+    Ok(())
+}
+
+// 0x1::stake::ValidatorPerformance, of the validators of the current epoch by validator index
+#[derive(Deserialize)]
+struct ValidatorPerformance {
+    validators: Vec<IndividualValidatorPerformance>,
+}
+
+#[derive(Deserialize)]
+struct IndividualValidatorPerformance {
+    successful_proposals: u64,
+    // BCS is positional, so the field is only there to read the others
+    _failed_proposals: u64,
+}
+
+/// How many blocks each validator successfully proposed in the current epoch, in the on-chain
+/// order of the validator set
+pub async fn successful_proposals(info: &AptosPublicInfo) -> Result<Vec<(PeerId, u64)>> {
+    let validator_set = get_validator_set(info).await?;
+    let performance: ValidatorPerformance = info
+        .client()
+        .get_account_resource_bcs(CORE_CODE_ADDRESS, "0x1::stake::ValidatorPerformance")
+        .await?
+        .into_inner();
+    Ok(validator_set
+        .active_validators
+        .iter()
+        .map(|validator_info| {
+            let proposals = performance
+                .validators
+                .get(validator_info.config().validator_index as usize)
+                .map_or(0, |validator| validator.successful_proposals);
+            (*validator_info.account_address(), proposals)
+        })
+        .collect())
+}
+
+/// The voting power of each validator, in the on-chain order of the validator set
+pub fn stake_distribution(validator_set: &ValidatorSet) -> Vec<(PeerId, u64)> {
+    validator_set
+        .payload()
+        .map(|validator_info| {
+            (
+                *validator_info.account_address(),
+                validator_info.consensus_voting_power(),
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aptos_keygen::KeyGen;
+    use aptos_sdk::{
+        crypto::PrivateKey as _,
+        types::{
+            event::{EventHandle, EventKey},
+            validator_config::ValidatorConfig,
+            validator_info::ValidatorInfo,
+        },
+    };
+
+    fn stake_pool(active: u64, pending_active: u64, pending_inactive: u64) -> StakePool {
+        let events = || EventHandle::new(EventKey::new(0, AccountAddress::ZERO), 0);
+        StakePool {
+            active,
+            inactive: 0,
+            pending_active,
+            pending_inactive,
+            locked_until_secs: 0,
+            operator_address: AccountAddress::ZERO,
+            delegated_voter: AccountAddress::ZERO,
+            initialize_validator_events: events(),
+            set_operator_events: events(),
+            add_stake_events: events(),
+            reactivate_stake_events: events(),
+            rotate_consensus_key_events: events(),
+            update_network_and_fullnode_addresses_events: events(),
+            increase_lockup_events: events(),
+            join_validator_set_events: events(),
+            distribute_rewards_events: events(),
+            unlock_stake_events: events(),
+            withdraw_stake_events: events(),
+            leave_validator_set_events: events(),
+        }
+    }
+
+    #[test]
+    fn test_stake_change() {
+        assert_eq!(
+            StakeChange::to(&stake_pool(100, 20, 50), 1000),
+            Some(StakeChange::Add(880))
+        );
+        assert_eq!(StakeChange::to(&stake_pool(100, 20, 50), 120), None);
+        assert_eq!(
+            StakeChange::to(&stake_pool(100, 20, 50), 60),
+            Some(StakeChange::Unlock(60))
+        );
+        // the pending active stake is staked either way
+        assert_eq!(
+            StakeChange::to(&stake_pool(100, 20, 0), 0),
+            Some(StakeChange::Unlock(100))
+        );
+    }
+
+    #[test]
+    fn test_joining_headroom_and_distribution() {
+        let mut keygen = KeyGen::from_seed([3; 32]);
+        let mut validator = |voting_power: u64| {
+            ValidatorInfo::new(
+                PeerId::random(),
+                voting_power,
+                ValidatorConfig::new(
+                    keygen.generate_bls12381_private_key().public_key(),
+                    vec![],
+                    vec![],
+                    0,
+                ),
+            )
+        };
+        let mut validator_set = ValidatorSet::new(vec![validator(100), validator(1000)]);
+        validator_set.total_voting_power = 1100;
+        validator_set.total_joining_power = 20;
+        assert_eq!(joining_headroom(&validator_set, 20), 200);
+        validator_set.total_joining_power = 300;
+        assert_eq!(joining_headroom(&validator_set, 20), 0);
+
+        let distribution = stake_distribution(&validator_set);
+        assert_eq!(
+            distribution
+                .iter()
+                .map(|(_, voting_power)| *voting_power)
+                .collect::<Vec<_>>(),
+            vec![100, 1000]
+        );
+    }
+}
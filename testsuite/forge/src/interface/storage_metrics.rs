@@ -0,0 +1,151 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::NodeMetrics;
+use std::collections::BTreeMap;
+
+// the RocksDB properties AptosDB reports, per column family and, if sharded, per shard
+const ROCKSDB_PROPERTIES_METRIC: &str = "aptos_rocksdb_properties";
+const STATE_KV_DB_PROPERTIES_METRIC: &str = "aptos_state_kv_db_properties";
+const STATE_MERKLE_DB_PROPERTIES_METRIC: &str = "aptos_state_merkle_db_properties";
+const TOTAL_SST_FILES_SIZE_PROPERTY: &str = "aptos_rocksdb_total-sst-files-size";
+const PRUNER_VERSIONS_METRIC: &str = "aptos_pruner_versions";
+const LEDGER_VERSION_METRIC: &str = "aptos_storage_ledger_version";
+
+// Without sharding, all the column families are reported in aptos_rocksdb_properties, so the ones
+// of the state DBs are told apart by name
+const STATE_KV_COLUMN_FAMILIES: [&str; 5] = [
+    "stale_state_value_index",
+    "stale_state_value_index_by_key_hash",
+    "state_value",
+    "state_value_by_key_hash",
+    "state_value_index",
+];
+const STATE_MERKLE_COLUMN_FAMILIES: [&str; 3] = [
+    "jellyfish_merkle_node",
+    "stale_node_index",
+    "stale_node_index_cross_epoch",
+];
+
+/// The versions a pruner reports. Versions below `min_readable` have been pruned.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PrunerVersions {
+    pub min_readable: Option<u64>,
+    pub target: Option<u64>,
+    pub progress: Option<u64>,
+}
+
+/// How big a node's AptosDB is and how far its pruners got, as reported by the node's metrics.
+/// Sizes are the total size of the SST files, so they lag behind writes until memtables flush.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StorageMetrics {
+    pub ledger_version: Option<u64>,
+    pub ledger_db_bytes: u64,
+    pub state_kv_db_bytes: u64,
+    pub state_merkle_db_bytes: u64,
+    // keyed by pruner name, e.g. "ledger_pruner" or "state_merkle_pruner"
+    pub pruners: BTreeMap<String, PrunerVersions>,
+}
+
+impl StorageMetrics {
+    pub fn from_metrics(metrics: &NodeMetrics) -> Self {
+        let mut storage = Self {
+            ledger_version: metrics
+                .get(LEDGER_VERSION_METRIC)
+                .first()
+                .map(|sample| sample.value as u64),
+            ..Self::default()
+        };
+
+        let size_filter = [("property_name", TOTAL_SST_FILES_SIZE_PROPERTY)];
+        for sample in metrics.get(ROCKSDB_PROPERTIES_METRIC) {
+            if !sample.matches(&size_filter) {
+                continue;
+            }
+            let cf_name = sample.labels.get("cf_name").map(String::as_str);
+            let bytes = sample.value as u64;
+            match cf_name {
+                Some(cf) if STATE_KV_COLUMN_FAMILIES.contains(&cf) => {
+                    storage.state_kv_db_bytes += bytes
+                },
+                Some(cf) if STATE_MERKLE_COLUMN_FAMILIES.contains(&cf) => {
+                    storage.state_merkle_db_bytes += bytes
+                },
+                _ => storage.ledger_db_bytes += bytes,
+            }
+        }
+        storage.state_kv_db_bytes +=
+            metrics.sum_with_labels(STATE_KV_DB_PROPERTIES_METRIC, &size_filter) as u64;
+        storage.state_merkle_db_bytes +=
+            metrics.sum_with_labels(STATE_MERKLE_DB_PROPERTIES_METRIC, &size_filter) as u64;
+
+        for sample in metrics.get(PRUNER_VERSIONS_METRIC) {
+            let (name, tag) = match (sample.labels.get("pruner_name"), sample.labels.get("tag")) {
+                (Some(name), Some(tag)) => (name, tag),
+                _ => continue,
+            };
+            let pruner = storage.pruners.entry(name.clone()).or_default();
+            let version = Some(sample.value as u64);
+            match tag.as_str() {
+                "min_readable" => pruner.min_readable = version,
+                "target" => pruner.target = version,
+                "progress" => pruner.progress = version,
+                _ => {},
+            }
+        }
+        storage
+    }
+
+    /// The size of all the DBs together
+    pub fn total_db_bytes(&self) -> u64 {
+        self.ledger_db_bytes + self.state_kv_db_bytes + self.state_merkle_db_bytes
+    }
+
+    /// The lowest version that can still be read from the given pruner's data, if it reports one
+    pub fn min_readable_version(&self, pruner_name: &str) -> Option<u64> {
+        self.pruners
+            .get(pruner_name)
+            .and_then(|pruner| pruner.min_readable)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_storage_metrics() {
+        let text = r#"
+aptos_storage_ledger_version 1500
+aptos_rocksdb_properties{cf_name="transaction",property_name="aptos_rocksdb_total-sst-files-size"} 1000
+aptos_rocksdb_properties{cf_name="write_set",property_name="aptos_rocksdb_total-sst-files-size"} 500
+aptos_rocksdb_properties{cf_name="transaction",property_name="aptos_rocksdb_estimate-num-keys"} 77
+aptos_rocksdb_properties{cf_name="state_value",property_name="aptos_rocksdb_total-sst-files-size"} 300
+aptos_rocksdb_properties{cf_name="jellyfish_merkle_node",property_name="aptos_rocksdb_total-sst-files-size"} 200
+aptos_state_kv_db_properties{shard_id="0",cf_name="state_value_by_key_hash",property_name="aptos_rocksdb_total-sst-files-size"} 40
+aptos_state_merkle_db_properties{shard_id="3",cf_name="jellyfish_merkle_node",property_name="aptos_rocksdb_total-sst-files-size"} 60
+aptos_pruner_versions{pruner_name="ledger_pruner",tag="min_readable"} 1000
+aptos_pruner_versions{pruner_name="ledger_pruner",tag="progress"} 1000
+aptos_pruner_versions{pruner_name="ledger_pruner",tag="target"} 1200
+aptos_pruner_versions{pruner_name="state_kv_pruner",tag="min_readable"} 900
+"#;
+        let storage = StorageMetrics::from_metrics(&NodeMetrics::parse(text).unwrap());
+        assert_eq!(storage.ledger_version, Some(1500));
+        assert_eq!(storage.ledger_db_bytes, 1500);
+        assert_eq!(storage.state_kv_db_bytes, 340);
+        assert_eq!(storage.state_merkle_db_bytes, 260);
+        assert_eq!(storage.total_db_bytes(), 2100);
+        assert_eq!(storage.pruners["ledger_pruner"], PrunerVersions {
+            min_readable: Some(1000),
+            target: Some(1200),
+            progress: Some(1000),
+        });
+        assert_eq!(storage.min_readable_version("state_kv_pruner"), Some(900));
+        assert_eq!(storage.min_readable_version("epoch_snapshot_pruner"), None);
+
+        assert_eq!(
+            StorageMetrics::from_metrics(&NodeMetrics::default()),
+            StorageMetrics::default()
+        );
+    }
+}
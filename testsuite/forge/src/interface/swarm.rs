@@ -3,19 +3,203 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::{
-    AptosPublicInfo, ChainInfo, FullNode, NodeExt, Result, SwarmChaos, Validator, Version,
+    describe_node_resources, fetch_genesis_txn_hash, get_validator_set,
+    prometheus_metrics::{avg_tps_query, instant_samples, p99_commit_latency_query},
+    run_node_operation, run_node_operations, stake_distribution, AptosPublicInfo, ArtifactManifest,
+    ChainInfo, DbSnapshotOptions, FaultyBehavior, FullNode, InfrastructureEvent, KeyRotationResult,
+    Node, NodeExt, NodeHealthResult, NodeOperation, NodeOperationSummary, NodeResources,
+    NodeRestarts, ProposalOutcome, Result, SwarmChaos, SwarmHealth, SwarmRestClient, TestReport,
+    Validator, Version, NODE_OPERATION_FAN_OUT,
 };
-use anyhow::{anyhow, bail};
+use anyhow::{anyhow, bail, Context};
 use aptos_config::{
     config::{NodeConfig, OverrideNodeConfig},
     network_id::NetworkId,
 };
 use aptos_logger::info;
 use aptos_rest_client::Client as RestClient;
-use aptos_sdk::types::PeerId;
-use futures::future::{join_all, try_join_all};
+use aptos_sdk::{
+    crypto::HashValue,
+    types::{transaction::TransactionArgument, LocalAccount, PeerId},
+};
+use futures::{
+    future::{join_all, try_join_all},
+    stream, FutureExt, StreamExt,
+};
 use prometheus_http_query::response::{PromqlResult, Sample};
-use std::time::{Duration, Instant};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+// the minimum stake of the genesis of the aptos-node helm charts, 1M APT with 8 decimals
+const DEFAULT_NEW_VALIDATOR_STAKE: u64 = 100_000_000_000_000;
+// how far behind the highest ledger version a healed node may be, to count as caught up
+const HEAL_MAX_VERSION_LAG: u64 = 1_000;
+// how many nodes SwarmExt::health_check_all checks at once
+const HEALTH_CHECK_FAN_OUT: usize = 16;
+
+/// How to add a validator to a running swarm, see [Swarm::add_validator]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NewValidatorOptions {
+    // Joining fails if this is below the on-chain minimum stake, or if it raises the voting power
+    // of the validator set by more than the voting_power_increase_limit of genesis
+    pub stake: u64,
+    // how long to wait for the validator to join the validator set and catch up
+    pub join_timeout: Duration,
+}
+
+impl Default for NewValidatorOptions {
+    fn default() -> Self {
+        Self {
+            stake: DEFAULT_NEW_VALIDATOR_STAKE,
+            join_timeout: Duration::from_secs(600),
+        }
+    }
+}
+
+/// Which validators to move to another version, e.g. to run a network with half of them on each
+/// of two versions
+#[derive(Clone, Debug, PartialEq)]
+pub enum UpgradeSelector {
+    // of the validators, rounded down, those with the lowest indexes
+    Fraction(f64),
+    PeerIds(Vec<PeerId>),
+    FirstByIndex(usize),
+}
+
+impl UpgradeSelector {
+    /// The selected validators out of the given ones, which are in index order like
+    /// [Swarm::validators]
+    pub fn select(&self, validators: &[PeerId]) -> Result<Vec<PeerId>> {
+        match self {
+            UpgradeSelector::Fraction(fraction) => {
+                if !(0.0..=1.0).contains(fraction) {
+                    bail!("Fraction of validators {} is not within [0, 1]", fraction);
+                }
+                let count = (validators.len() as f64 * fraction).floor() as usize;
+                Ok(validators[..count].to_vec())
+            },
+            UpgradeSelector::PeerIds(peer_ids) => {
+                let unknown: Vec<_> = peer_ids
+                    .iter()
+                    .filter(|peer_id| !validators.contains(peer_id))
+                    .collect();
+                if !unknown.is_empty() {
+                    bail!("Not validators of the swarm: {:?}", unknown);
+                }
+                Ok(peer_ids.clone())
+            },
+            UpgradeSelector::FirstByIndex(count) => {
+                if *count > validators.len() {
+                    bail!(
+                        "Can't select {} validators out of {}",
+                        count,
+                        validators.len()
+                    );
+                }
+                Ok(validators[..*count].to_vec())
+            },
+        }
+    }
+}
+
+/// How long a node affected by a chaos took to recover once the chaos was removed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NodeRecovery {
+    pub name: String,
+    // until it had its peers back and was within the version lag of the highest ledger version
+    pub catch_up: Duration,
+}
+
+/// How the nodes affected by a chaos recovered from it, see [heal_and_verify]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecoveryStats {
+    pub chaos: String,
+    // until the first affected node had its peers back
+    pub first_reconnect: Duration,
+    pub nodes: Vec<NodeRecovery>,
+    // that the validators moved on by until all the nodes recovered. Once the epoch changed, only
+    // the rounds of the last one count.
+    pub rounds: u64,
+    pub epochs: u64,
+}
+
+impl RecoveryStats {
+    /// The time until the last affected node recovered
+    pub fn recovery(&self) -> Duration {
+        self.nodes
+            .iter()
+            .map(|node| node.catch_up)
+            .max()
+            .unwrap_or_default()
+    }
+
+    pub fn report(&self, report: &mut TestReport, test_name: &str) {
+        let lines: Vec<_> = self
+            .nodes
+            .iter()
+            .map(|node| format!("  {} in {:.1}s", node.name, node.catch_up.as_secs_f64()))
+            .collect();
+        report.report_text(format!(
+            "Recovered from {} in {:.1}s, first reconnect after {:.1}s, {} rounds and {} epochs meanwhile:\n{}",
+            self.chaos,
+            self.recovery().as_secs_f64(),
+            self.first_reconnect.as_secs_f64(),
+            self.rounds,
+            self.epochs,
+            lines.join("\n")
+        ));
+        report.report_metric(test_name, "recovery_s", self.recovery().as_secs_f64());
+    }
+}
+
+/// A node that did not recover from a chaos yet, as of the last poll
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct PendingRecovery {
+    peer_id: PeerId,
+    name: String,
+    validator: bool,
+    connected: bool,
+    // None if its REST API did not answer
+    version: Option<u64>,
+}
+
+impl PendingRecovery {
+    fn new(peer_id: PeerId, name: &str, validator: bool) -> Self {
+        Self {
+            peer_id,
+            name: name.to_string(),
+            validator,
+            connected: false,
+            version: None,
+        }
+    }
+
+    fn recovered(&self, highest_version: u64) -> bool {
+        self.connected
+            && self.version.map_or(false, |version| {
+                version.saturating_add(HEAL_MAX_VERSION_LAG) >= highest_version
+            })
+    }
+
+    fn describe(&self, highest_version: u64) -> String {
+        let lag = match self.version {
+            Some(version) => format!(
+                "{} versions behind",
+                highest_version.saturating_sub(version)
+            ),
+            None => "not answering".to_string(),
+        };
+        let connectivity = if self.connected {
+            "connected"
+        } else {
+            "missing peers"
+        };
+        format!("{} ({}, {})", self.name, connectivity, lag)
+    }
+}
 
 /// Trait used to represent a running network comprised of Validators and FullNodes
 #[async_trait::async_trait]
@@ -33,17 +217,77 @@ pub trait Swarm: Sync + Send {
     /// Upgrade a Validator to run specified `Version`
     async fn upgrade_validator(&mut self, id: PeerId, version: &Version) -> Result<()>;
 
+    /// Change the CPU and memory of a node, restarting it, and wait until it is healthy again
+    async fn set_node_resources(&mut self, id: PeerId, resources: &NodeResources) -> Result<()>;
+
     /// Returns an Iterator of references to all the FullNodes in the Swarm
     fn full_nodes<'a>(&'a self) -> Box<dyn Iterator<Item = &'a dyn FullNode> + 'a>;
 
     /// Returns a reference to the FullNode with the provided PeerId
     fn full_node(&self, id: PeerId) -> Option<&dyn FullNode>;
 
-    /// Adds a Validator to the swarm and returns the PeerId
-    fn add_validator(&mut self, version: &Version, template: NodeConfig) -> Result<PeerId>;
+    /// Returns the fullnodes attached to the validator with the provided PeerId, which sync from it
+    /// over its VFN network, whether in StatefulSets of their own or as replicas of one
+    fn fullnodes_of(&self, validator: PeerId) -> Vec<&dyn FullNode>;
+
+    /// Deploys another fullnode attached to the validator, running `version`, and returns its
+    /// PeerId once it caught up with the validator
+    async fn attach_fullnode(&mut self, validator: PeerId, version: &Version) -> Result<PeerId>;
+
+    /// The Warning events of the backend's infrastructure during the test, e.g. pod evictions
+    /// and volume failures, oldest first. Empty if the backend doesn't watch them.
+    fn infrastructure_events(&self) -> Vec<InfrastructureEvent>;
+
+    /// Returns an Iterator of references to the public fullnodes, which peer with the validators'
+    /// fullnodes rather than with a validator. They are FullNodes of the swarm too.
+    fn public_fullnodes<'a>(&'a self) -> Box<dyn Iterator<Item = &'a dyn FullNode> + 'a>;
+
+    /// Deploys `count` public fullnodes running `version`, with the given config for all but
+    /// their identity and networks, and returns their PeerIds once they are healthy
+    async fn deploy_public_fullnodes(
+        &mut self,
+        count: usize,
+        version: &Version,
+        config: NodeConfig,
+    ) -> Result<Vec<PeerId>>;
+
+    /// Adds a new Validator to the running swarm, registers it on chain and returns its PeerId
+    /// once it joined the validator set and caught up with the others
+    async fn add_validator(
+        &mut self,
+        version: &Version,
+        options: NewValidatorOptions,
+    ) -> Result<PeerId>;
+
+    /// Removes the Validator with the provided PeerId from the validator set, then from the swarm
+    async fn remove_validator(&mut self, id: PeerId) -> Result<()>;
+
+    /// Designates a validator faulty and makes it misbehave as told: the last one by index that
+    /// isn't faulty yet, as long as fewer than a third of the validators are. Returns its PeerId.
+    async fn deploy_faulty_validator(&mut self, behavior: FaultyBehavior) -> Result<PeerId>;
+
+    /// The validators designated faulty, which the liveness checks of [SwarmExt] leave out
+    fn faulty_validators(&self) -> Vec<PeerId>;
 
-    /// Removes the Validator with the provided PeerId
-    fn remove_validator(&mut self, id: PeerId) -> Result<()>;
+    /// Gives the validator new consensus and network keys: registers them on chain with its
+    /// operator account, restarts it with them once they take effect with the next epoch, and
+    /// waits until it is healthy again. The errors tell how far the rotation got.
+    async fn rotate_validator_keys(&mut self, id: PeerId) -> Result<KeyRotationResult>;
+
+    /// The account of the validator, with its current sequence number. Forge's validators have
+    /// the same account as owner, operator and voter of their stake pool.
+    async fn validator_owner_account(&self, id: PeerId) -> Result<LocalAccount>;
+
+    /// Brings the stake of the validator's stake pool to `amount`, with its owner account: adds
+    /// coins the root account mints it, or unlocks the difference, which the validator keeps
+    /// voting with until the lockup of its pool expires. With `wait_for_epoch`, the epoch is ended
+    /// until all of an added stake counts, despite the voting power increase limit of genesis.
+    async fn set_validator_stake(
+        &mut self,
+        id: PeerId,
+        amount: u64,
+        wait_for_epoch: bool,
+    ) -> Result<()>;
 
     fn add_validator_full_node(
         &mut self,
@@ -70,14 +314,41 @@ pub trait Swarm: Sync + Send {
 
     fn logs_location(&mut self) -> String;
 
+    /// Writes what there is to know of each node after a failure into its own directory under
+    /// `dir`: its logs, of the previous container too if it restarted, and for k8s nodes the
+    /// events and a description of its pod. Of k8s nodes, only the logs of the last `since`, if
+    /// given.
+    /// Gives up on the nodes not done within [crate::ARTIFACT_COLLECTION_TIMEOUT].
+    async fn collect_artifacts(
+        &self,
+        dir: &Path,
+        since: Option<Duration>,
+    ) -> Result<ArtifactManifest>;
+
+    /// Archives the DB of the node into `dest` for a post-mortem, stopping the node for it and
+    /// starting it again if it was running. Gives the path of the archive.
+    async fn snapshot_db(
+        &self,
+        id: PeerId,
+        dest: &Path,
+        options: &DbSnapshotOptions,
+    ) -> Result<PathBuf>;
+
     /// Injects all types of chaos
     async fn inject_chaos(&mut self, chaos: SwarmChaos) -> Result<()>;
     async fn remove_chaos(&mut self, chaos: SwarmChaos) -> Result<()>;
+    /// Replaces the injected chaos of the same kind and groups with the given one, without a gap
+    /// in between, e.g. to change the latency of a delay mid-test
+    async fn update_chaos(&mut self, chaos: SwarmChaos) -> Result<()>;
     async fn remove_all_chaos(&mut self) -> Result<()>;
 
     async fn ensure_no_validator_restart(&self) -> Result<()>;
     async fn ensure_no_fullnode_restart(&self) -> Result<()>;
 
+    /// How often each node restarted since the swarm was created, by node name. Restarts the
+    /// test did, e.g. with stop/start, kill or memory stress chaos, are counted as expected.
+    async fn restarts(&self) -> Result<Vec<NodeRestarts>>;
+
     // Get prometheus metrics from the swarm
     async fn query_metrics(
         &self,
@@ -113,7 +384,7 @@ impl<T: ?Sized> SwarmExt for T where T: Swarm {}
 pub trait SwarmExt: Swarm {
     async fn liveness_check(&self, deadline: Instant) -> Result<()> {
         let liveness_check_seconds = 10;
-        let validators = self.validators().collect::<Vec<_>>();
+        let validators = self.honest_validators();
         let full_nodes = self.full_nodes().collect::<Vec<_>>();
 
         while try_join_all(
@@ -236,6 +507,20 @@ pub trait SwarmExt: Swarm {
         Ok(())
     }
 
+    /// Check that all validators and fullnodes share the same genesis, e.g. that no node was left
+    /// pointing at the genesis of a previous era
+    async fn assert_same_genesis(&self) -> Result<()> {
+        let clients = self.get_all_nodes_clients_with_names();
+        let hashes = try_join_all(clients.iter().map(|(name, client)| async move {
+            let hash = fetch_genesis_txn_hash(client)
+                .await
+                .with_context(|| format!("Failed to get the genesis of node {}", name))?;
+            Ok::<_, anyhow::Error>((name.clone(), hash))
+        }))
+        .await?;
+        check_same_genesis(&hashes)
+    }
+
     /// Waits for all nodes to have caught up to the specified `target_version`.
     async fn wait_for_all_nodes_to_catchup_to_version(
         &self,
@@ -272,6 +557,43 @@ pub trait SwarmExt: Swarm {
         wait_for_all_nodes_to_catchup(&self.get_all_nodes_clients_with_names(), timeout).await
     }
 
+    /// Waits for every validator and fullnode to be within `max_lag` versions of the highest
+    /// ledger version of the swarm, as the nodes keep committing meanwhile. On timeout, fails
+    /// with the last version seen of each node, and its lag.
+    async fn wait_for_all_nodes_to_catchup_within(
+        &self,
+        max_lag: u64,
+        timeout: Duration,
+    ) -> Result<()> {
+        wait_for_nodes_to_catchup_within(
+            &self.get_all_nodes_clients_with_names(),
+            &[],
+            max_lag,
+            timeout,
+        )
+        .await
+    }
+
+    /// Like [SwarmExt::wait_for_all_nodes_to_catchup_within], for the fullnodes only, with the
+    /// validators counting towards the highest ledger version
+    async fn wait_for_fullnodes_to_catchup_within(
+        &self,
+        max_lag: u64,
+        timeout: Duration,
+    ) -> Result<()> {
+        let fullnode_clients: Vec<_> = self
+            .full_nodes()
+            .map(|node| (node.name().to_string(), node.rest_client()))
+            .collect();
+        wait_for_nodes_to_catchup_within(
+            &fullnode_clients,
+            &self.get_validator_clients_with_names(),
+            max_lag,
+            timeout,
+        )
+        .await
+    }
+
     /// Wait for all nodes in the network to change epochs. This is done by first querying each node
     /// for its current epoch, selecting the max epoch, then waiting for all nodes to sync to max
     /// epoch + 1.
@@ -295,7 +617,8 @@ pub trait SwarmExt: Swarm {
         timeout: Duration,
         versions_to_sync_past: u64,
     ) -> Result<()> {
-        let clients = self.get_all_nodes_clients_with_names();
+        // the faulty validators may well lag behind
+        let clients = self.get_honest_nodes_clients_with_names();
         let highest_synced_version = get_highest_synced_version(&clients).await?;
         wait_for_all_nodes_to_catchup_to_version(
             &clients,
@@ -305,12 +628,252 @@ pub trait SwarmExt: Swarm {
         .await
     }
 
+    /// The highest epoch and consensus round of the validators that answer
+    async fn highest_epoch_and_round(&self) -> (u64, u64) {
+        let mut highest = (0, 0);
+        let validators: Vec<_> = self.validators().collect();
+        for validator in validators {
+            let epoch = validator
+                .rest_client()
+                .get_ledger_information()
+                .await
+                .map(|state| state.into_inner().epoch);
+            let round = validator
+                .get_metric_i64("aptos_consensus_current_round")
+                .await;
+            if let (Ok(epoch), Ok(Some(round))) = (epoch, round) {
+                highest = highest.max((epoch, round.max(0) as u64));
+            }
+        }
+        highest
+    }
+
+    /// Checks the health of every validator and fullnode, at most [HEALTH_CHECK_FAN_OUT] at a
+    /// time, without stopping at the first unhealthy one
+    async fn health_check_all(&self) -> SwarmHealth {
+        let validators: Vec<_> = self.validators().collect();
+        let fullnodes: Vec<_> = self.full_nodes().collect();
+        let checks = validators
+            .into_iter()
+            .map(|v| check_node_health(v, true).boxed())
+            .chain(
+                fullnodes
+                    .into_iter()
+                    .map(|f| check_node_health(f, false).boxed()),
+            );
+        let nodes = stream::iter(checks)
+            .buffered(HEALTH_CHECK_FAN_OUT)
+            .collect()
+            .await;
+        SwarmHealth { nodes }
+    }
+
+    /// Attaches fullnodes running `version` to every validator that has some, until each of them
+    /// has `per_validator`. The validators without fullnodes are left without.
+    async fn ensure_fullnodes_per_validator(
+        &mut self,
+        per_validator: usize,
+        version: &Version,
+    ) -> Result<()> {
+        let validators: Vec<_> = self.validators().map(|v| v.peer_id()).collect();
+        for validator in validators {
+            let attached = self.fullnodes_of(validator).len();
+            if attached == 0 {
+                continue;
+            }
+            for _ in attached..per_validator {
+                let peer_id = self.attach_fullnode(validator, version).await?;
+                info!("Attached fullnode {} to validator {}", peer_id, validator);
+            }
+        }
+        Ok(())
+    }
+
+    /// The nodes that restarted although the test did not restart them, with how often and
+    /// why their container last terminated, see [Swarm::restarts]
+    async fn unexpected_restarts(&self) -> Result<Vec<(String, u32, String)>> {
+        Ok(self
+            .restarts()
+            .await?
+            .into_iter()
+            .filter(|restarts| restarts.unexpected() > 0)
+            .map(|restarts| {
+                (
+                    restarts.name.clone(),
+                    restarts.unexpected(),
+                    restarts
+                        .last_termination_reason
+                        .unwrap_or_else(|| "unknown".to_string()),
+                )
+            })
+            .collect())
+    }
+
+    /// Changes the CPU and memory of the validators one at a time, for the others to keep the
+    /// network going, e.g. to resize them mid-test
+    async fn set_validator_resources(&mut self, resources: &NodeResources) -> Result<()> {
+        let validators: Vec<_> = self.validators().map(|v| v.peer_id()).collect();
+        for id in validators {
+            self.set_node_resources(id, resources).await?;
+        }
+        Ok(())
+    }
+
+    /// The on-chain voting power of each validator, as of the current epoch
+    async fn stake_distribution(&self) -> Result<Vec<(PeerId, u64)>> {
+        let validator_set = get_validator_set(&self.aptos_public_info()).await?;
+        Ok(stake_distribution(&validator_set))
+    }
+
+    /// Passes and executes a governance proposal with the votes of the given validators' stake
+    /// pools, see [ChainInfo::execute_governance_proposal]
+    async fn execute_governance_proposal(
+        &self,
+        script: &[u8],
+        args: Vec<TransactionArgument>,
+        voters: &[PeerId],
+    ) -> Result<ProposalOutcome> {
+        let voters =
+            try_join_all(voters.iter().map(|id| self.validator_owner_account(*id))).await?;
+        self.chain_info()
+            .execute_governance_proposal(script, args, &voters)
+            .await
+    }
+
+    /// The resources of every node, for the test report, none if the backend doesn't limit them
+    fn resources_summary(&self) -> Option<String> {
+        let nodes: Vec<_> = self
+            .validators()
+            .map(|v| (v.name().to_string(), v.resources()))
+            .chain(
+                self.full_nodes()
+                    .map(|f| (f.name().to_string(), f.resources())),
+            )
+            .collect();
+        if nodes.iter().all(|(_, resources)| resources.is_none()) {
+            return None;
+        }
+        Some(describe_node_resources(&nodes))
+    }
+
+    /// Starts every node, [NODE_OPERATION_FAN_OUT] at a time, see [SwarmExt::restart_all]
+    async fn start_all(&self) -> NodeOperationSummary {
+        self.operate_on_all(NodeOperation::Start, NODE_OPERATION_FAN_OUT)
+            .await
+    }
+
+    /// Stops every node, [NODE_OPERATION_FAN_OUT] at a time, see [SwarmExt::restart_all]
+    async fn stop_all(&self) -> NodeOperationSummary {
+        self.operate_on_all(NodeOperation::Stop, NODE_OPERATION_FAN_OUT)
+            .await
+    }
+
+    /// Restarts every node, `concurrency` at a time, each waiting up to `health_timeout` to be
+    /// healthy again. All nodes are restarted even if some fail, use
+    /// [NodeOperationSummary::into_result] to fail on any of them.
+    async fn restart_all(
+        &self,
+        concurrency: usize,
+        health_timeout: Duration,
+    ) -> NodeOperationSummary {
+        self.operate_on_all(NodeOperation::Restart { health_timeout }, concurrency)
+            .await
+    }
+
+    /// The validators first, then the fullnodes, in the summary
+    async fn operate_on_all(
+        &self,
+        operation: NodeOperation,
+        concurrency: usize,
+    ) -> NodeOperationSummary {
+        let validators: Vec<_> = self.validators().collect();
+        let fullnodes: Vec<_> = self.full_nodes().collect();
+        let operations = validators
+            .into_iter()
+            .map(|v| run_node_operation(v, operation).boxed())
+            .chain(
+                fullnodes
+                    .into_iter()
+                    .map(|f| run_node_operation(f, operation).boxed()),
+            )
+            .collect();
+        let summary = run_node_operations(operation, operations, concurrency).await;
+        if let Some(slowest) = summary.slowest() {
+            info!(
+                "Did {} {} nodes, {} failed, the slowest took {:?}",
+                operation,
+                summary.nodes.len(),
+                summary.failed().count(),
+                slowest.duration
+            );
+        }
+        summary
+    }
+
+    /// Runs an instant query against the Prometheus of the swarm, at the given time or now. Fails
+    /// with [crate::prometheus_metrics::PrometheusUnavailable] if the swarm has no Prometheus.
+    async fn prometheus_query(&self, promql: &str, at: Option<SystemTime>) -> Result<Vec<Sample>> {
+        let time = at.map(unix_secs).transpose()?;
+        let result = self.query_metrics(promql, time, None).await?;
+        Ok(instant_samples(&result))
+    }
+
+    /// Runs a range query against the Prometheus of the swarm, see [SwarmExt::prometheus_query]
+    async fn prometheus_query_range(
+        &self,
+        promql: &str,
+        start: SystemTime,
+        end: SystemTime,
+    ) -> Result<Vec<Sample>> {
+        self.query_range_metrics(promql, unix_secs(start)?, unix_secs(end)?, None)
+            .await
+    }
+
+    /// The transactions each validator committed per second, on average, over the window ending
+    /// at `end`, e.g. the end of a test that is checked later
+    async fn avg_tps_over(&self, window: Duration, end: SystemTime) -> Result<f64> {
+        let samples = self
+            .prometheus_query(&avg_tps_query(window), Some(end))
+            .await?;
+        single_value(&samples, "average TPS")
+    }
+
+    /// The 99th percentile of the time from proposing a block to committing it, over the last
+    /// window
+    async fn p99_commit_latency_over(&self, window: Duration) -> Result<Duration> {
+        let samples = self
+            .prometheus_query(&p99_commit_latency_query(window), None)
+            .await?;
+        let latency_s = single_value(&samples, "p99 commit latency")?;
+        Ok(Duration::from_secs_f64(latency_s))
+    }
+
     fn get_validator_clients_with_names(&self) -> Vec<(String, RestClient)> {
         self.validators()
             .map(|node| (node.name().to_string(), node.rest_client()))
             .collect()
     }
 
+    /// The validators that were not designated faulty, see [Swarm::deploy_faulty_validator]
+    fn honest_validators(&self) -> Vec<&dyn Validator> {
+        let faulty = self.faulty_validators();
+        self.validators()
+            .filter(|node| !faulty.contains(&node.peer_id()))
+            .collect()
+    }
+
+    /// Like [SwarmExt::get_all_nodes_clients_with_names], without the faulty validators
+    fn get_honest_nodes_clients_with_names(&self) -> Vec<(String, RestClient)> {
+        self.honest_validators()
+            .into_iter()
+            .map(|node| (node.name().to_string(), node.rest_client()))
+            .chain(
+                self.full_nodes()
+                    .map(|node| (node.name().to_string(), node.rest_client())),
+            )
+            .collect()
+    }
+
     fn get_all_nodes_clients_with_names(&self) -> Vec<(String, RestClient)> {
         self.validators()
             .map(|node| (node.name().to_string(), node.rest_client()))
@@ -321,6 +884,33 @@ pub trait SwarmExt: Swarm {
             .collect()
     }
 
+    /// A REST client to the whole swarm, which round-robins over its fullnodes and honest
+    /// validators and fails over the ones that can't be reached, see [SwarmRestClient]
+    fn rest_client(&self) -> SwarmRestClient {
+        let (fullnodes, validators) = self.rest_client_backends();
+        SwarmRestClient::new(fullnodes, validators)
+    }
+
+    /// Points the client at the current nodes of the swarm, once nodes were added or removed
+    fn refresh_rest_client(&self, client: &SwarmRestClient) {
+        let (fullnodes, validators) = self.rest_client_backends();
+        client.set_backends(fullnodes, validators);
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn rest_client_backends(&self) -> (Vec<(String, RestClient)>, Vec<(String, RestClient)>) {
+        let fullnodes = self
+            .full_nodes()
+            .map(|node| (node.name().to_string(), node.rest_client()))
+            .collect();
+        let validators = self
+            .honest_validators()
+            .into_iter()
+            .map(|node| (node.name().to_string(), node.rest_client()))
+            .collect();
+        (fullnodes, validators)
+    }
+
     fn get_clients_for_peers(&self, peers: &[PeerId], client_timeout: Duration) -> Vec<RestClient> {
         peers
             .iter()
@@ -484,6 +1074,256 @@ pub async fn wait_for_all_nodes_to_catchup(
     wait_for_all_nodes_to_catchup_to_version(clients, highest_synced_version, timeout).await
 }
 
+fn unix_secs(time: SystemTime) -> Result<i64> {
+    Ok(time.duration_since(UNIX_EPOCH)?.as_secs() as i64)
+}
+
+/// The value of a query that aggregates all series into one, which has none without data
+fn single_value(samples: &[Sample], name: &str) -> Result<f64> {
+    match samples {
+        [sample] if sample.value().is_finite() && sample.value() >= 0.0 => Ok(sample.value()),
+        [sample] => bail!("Prometheus has no {} yet: {}", name, sample.value()),
+        _ => bail!("Expected one sample of {}, got {}", name, samples.len()),
+    }
+}
+
+async fn check_node_health<N: Node + ?Sized>(node: &N, validator: bool) -> NodeHealthResult {
+    NodeHealthResult {
+        name: node.name().to_string(),
+        peer_id: node.peer_id(),
+        validator,
+        health: node.health_check().await.into(),
+    }
+}
+
+/// Waits for the nodes of `clients` to be within `max_lag` versions of the highest ledger version
+/// of them and of `other_clients`, polling them all at once. A node whose REST API fails keeps
+/// the version it last answered with, so one failed poll doesn't fail the wait.
+pub async fn wait_for_nodes_to_catchup_within(
+    clients: &[(String, RestClient)],
+    other_clients: &[(String, RestClient)],
+    max_lag: u64,
+    timeout: Duration,
+) -> Result<()> {
+    if clients.is_empty() {
+        bail!("No nodes are available!")
+    }
+    let all_clients: Vec<_> = clients.iter().chain(other_clients).collect();
+    let mut last_seen: Vec<Option<u64>> = vec![None; all_clients.len()];
+    let start_time = Instant::now();
+    loop {
+        let versions = join_all(all_clients.iter().map(|(_, client)| async move {
+            client
+                .get_ledger_information()
+                .await
+                .map(|state| state.into_inner().version)
+        }))
+        .await;
+        for ((seen, version), (name, _)) in last_seen.iter_mut().zip(versions).zip(&all_clients) {
+            match version {
+                Ok(version) => *seen = Some(version),
+                Err(e) => info!("Failed to get the ledger version of {}: {}", name, e),
+            }
+        }
+        let highest_version = last_seen.iter().flatten().max().copied().unwrap_or(0);
+        let rows: Vec<_> = clients
+            .iter()
+            .zip(&last_seen)
+            .map(|((name, _), seen)| (name.as_str(), *seen))
+            .collect();
+        let caught_up = rows.iter().all(|(_, seen)| {
+            seen.map_or(false, |version| {
+                version.saturating_add(max_lag) >= highest_version
+            })
+        });
+        if caught_up {
+            info!(
+                "{} nodes caught up to within {} versions of {}, in {} seconds",
+                clients.len(),
+                max_lag,
+                highest_version,
+                start_time.elapsed().as_secs()
+            );
+            return Ok(());
+        }
+        if start_time.elapsed() > timeout {
+            bail!(
+                "Waiting for nodes to catch up to within {} versions of {} timed out after {} seconds:\n{}",
+                max_lag,
+                highest_version,
+                start_time.elapsed().as_secs(),
+                format_catchup_table(&rows, highest_version)
+            );
+        }
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+}
+
+/// One line per node, of the last version seen of it and how far behind it is
+fn format_catchup_table(rows: &[(&str, Option<u64>)], highest_version: u64) -> String {
+    let width = rows
+        .iter()
+        .map(|(name, _)| name.len())
+        .chain(std::iter::once("node".len()))
+        .max()
+        .unwrap_or_default();
+    let mut lines = vec![format!(
+        "{:width$}  {:>12}  {:>12}",
+        "node", "version", "lag"
+    )];
+    for (name, seen) in rows {
+        let (version, lag) = match seen {
+            Some(version) => (
+                version.to_string(),
+                highest_version.saturating_sub(*version).to_string(),
+            ),
+            None => ("never seen".to_string(), "-".to_string()),
+        };
+        lines.push(format!("{:width$}  {:>12}  {:>12}", name, version, lag));
+    }
+    lines.join("\n")
+}
+
+/// Removes the given chaos, and waits for the nodes it affected to have their peers back and to
+/// catch up to within a small lag of the highest ledger version as of the removal. A validator
+/// has its peers back once connected to all the other validators, as before any chaos, and a
+/// fullnode once connected upstream. Fails naming the nodes that did not recover in time, with
+/// their lags. The swarm is only locked for writing to remove the chaos, so that e.g. the load of
+/// the test goes on while the nodes are polled.
+pub async fn heal_and_verify(
+    swarm: &tokio::sync::RwLock<Box<dyn Swarm>>,
+    chaos: &SwarmChaos,
+    recovery_timeout: Duration,
+) -> Result<RecoveryStats> {
+    let affected = chaos.affected_nodes();
+    let is_affected = |peer_id: PeerId| {
+        affected
+            .as_ref()
+            .map_or(true, |nodes| nodes.contains(&peer_id))
+    };
+    let (validator_count, mut pending, (start_epoch, start_round)) = {
+        let swarm = swarm.read().await;
+        let pending: Vec<_> = swarm
+            .validators()
+            .filter(|v| is_affected(v.peer_id()))
+            .map(|v| PendingRecovery::new(v.peer_id(), v.name(), true))
+            .chain(
+                swarm
+                    .full_nodes()
+                    .filter(|f| is_affected(f.peer_id()))
+                    .map(|f| PendingRecovery::new(f.peer_id(), f.name(), false)),
+            )
+            .collect();
+        (
+            swarm.validators().count(),
+            pending,
+            swarm.highest_epoch_and_round().await,
+        )
+    };
+
+    swarm.write().await.remove_chaos(chaos.clone()).await?;
+    let start = Instant::now();
+    // later versions are not what the nodes fell behind by
+    let target_version = {
+        let clients = swarm.read().await.get_all_nodes_clients_with_names();
+        get_highest_synced_version(&clients).await?
+    };
+    let mut first_reconnect = None;
+    let mut nodes = vec![];
+    loop {
+        {
+            let swarm = swarm.read().await;
+            let polls = join_all(
+                pending
+                    .iter()
+                    .map(|node| poll_recovery(swarm.as_ref(), node, validator_count)),
+            )
+            .await;
+            for (node, (connected, version)) in pending.iter_mut().zip(polls) {
+                node.connected = connected;
+                node.version = version;
+            }
+        }
+        if first_reconnect.is_none() && pending.iter().any(|node| node.connected) {
+            first_reconnect = Some(start.elapsed());
+        }
+        let (recovered, still_pending): (Vec<_>, Vec<_>) = pending
+            .into_iter()
+            .partition(|node| node.recovered(target_version));
+        nodes.extend(recovered.into_iter().map(|node| NodeRecovery {
+            name: node.name,
+            catch_up: start.elapsed(),
+        }));
+        pending = still_pending;
+        if pending.is_empty() {
+            break;
+        }
+        if start.elapsed() > recovery_timeout {
+            let unrecovered: Vec<_> = pending
+                .iter()
+                .map(|node| node.describe(target_version))
+                .collect();
+            bail!(
+                "{} nodes did not recover from {:?} within {:?}: {}",
+                pending.len(),
+                chaos,
+                recovery_timeout,
+                unrecovered.join(", ")
+            );
+        }
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+
+    let (end_epoch, end_round) = swarm.read().await.highest_epoch_and_round().await;
+    let stats = RecoveryStats {
+        chaos: format!("{:?}", chaos),
+        first_reconnect: first_reconnect.unwrap_or_default(),
+        nodes,
+        rounds: if end_epoch == start_epoch {
+            end_round.saturating_sub(start_round)
+        } else {
+            end_round
+        },
+        epochs: end_epoch.saturating_sub(start_epoch),
+    };
+    info!("Recovered from {} in {:?}", stats.chaos, stats.recovery());
+    Ok(stats)
+}
+
+/// Whether the node has its peers back, and its ledger version, None if it did not answer
+async fn poll_recovery(
+    swarm: &dyn Swarm,
+    node: &PendingRecovery,
+    validator_count: usize,
+) -> (bool, Option<u64>) {
+    let (connected, client) = if node.validator {
+        match swarm.validator(node.peer_id) {
+            Some(validator) => (
+                validator
+                    .check_connectivity(NetworkId::Validator, validator_count.saturating_sub(1))
+                    .await
+                    .unwrap_or(false),
+                validator.rest_client(),
+            ),
+            None => return (false, None),
+        }
+    } else {
+        match swarm.full_node(node.peer_id) {
+            Some(fullnode) => (
+                fullnode.check_connectivity().await.unwrap_or(false),
+                fullnode.rest_client(),
+            ),
+            None => return (false, None),
+        }
+    };
+    let version = client
+        .get_ledger_information()
+        .await
+        .map(|state| state.into_inner().version)
+        .ok();
+    (connected, version)
+}
+
 /// Returns the highest synced version of the given clients
 pub async fn get_highest_synced_version(clients: &[(String, RestClient)]) -> Result<u64> {
     let (highest_synced_version, _) = get_highest_synced_version_and_epoch(clients).await?;
@@ -512,3 +1352,142 @@ pub async fn get_highest_synced_version_and_epoch(
     }
     Ok(latest_version_and_epoch)
 }
+
+/// Fail naming every node whose genesis differs from the one most nodes have
+fn check_same_genesis(hashes: &[(String, HashValue)]) -> Result<()> {
+    let mut counts: HashMap<HashValue, usize> = HashMap::new();
+    for (_, hash) in hashes {
+        *counts.entry(*hash).or_default() += 1;
+    }
+    // on a tie, the genesis of the first node wins
+    let mut expected: Option<HashValue> = None;
+    for (_, hash) in hashes {
+        if expected.map_or(true, |expected| counts[hash] > counts[&expected]) {
+            expected = Some(*hash);
+        }
+    }
+    let expected = match expected {
+        Some(expected) => expected,
+        None => return Ok(()),
+    };
+    let mismatches: Vec<_> = hashes
+        .iter()
+        .filter(|(_, hash)| *hash != expected)
+        .map(|(name, hash)| format!("{} has genesis {}", name, hash))
+        .collect();
+    if !mismatches.is_empty() {
+        bail!(
+            "Nodes do not share the same genesis, most have genesis {}, but {}",
+            expected,
+            mismatches.join(", ")
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_same_genesis() {
+        let (genesis, old_genesis) = (HashValue::random(), HashValue::random());
+        let node = |name: &str, hash: HashValue| (name.to_string(), hash);
+
+        check_same_genesis(&[]).unwrap();
+        check_same_genesis(&[node("validator-0", genesis), node("fullnode-0", genesis)]).unwrap();
+
+        let error = check_same_genesis(&[
+            node("validator-0", genesis),
+            node("validator-1", old_genesis),
+            node("validator-2", genesis),
+        ])
+        .unwrap_err()
+        .to_string();
+        assert!(error.contains(&format!("most have genesis {}", genesis)));
+        assert!(error.contains(&format!("validator-1 has genesis {}", old_genesis)));
+        assert!(!error.contains("validator-0 has"));
+    }
+
+    #[test]
+    fn test_format_catchup_table() {
+        let table = format_catchup_table(
+            &[
+                ("validator-0", Some(5_000)),
+                ("fullnode-10", Some(3_800)),
+                ("fullnode-2", None),
+            ],
+            5_000,
+        );
+        let lines: Vec<_> = table.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0].split_whitespace().collect::<Vec<_>>(), vec![
+            "node", "version", "lag"
+        ]);
+        assert_eq!(lines[2].split_whitespace().collect::<Vec<_>>(), vec![
+            "fullnode-10",
+            "3800",
+            "1200"
+        ]);
+        assert_eq!(lines[3].split_whitespace().collect::<Vec<_>>(), vec![
+            "fullnode-2",
+            "never",
+            "seen",
+            "-"
+        ]);
+    }
+
+    #[test]
+    fn test_pending_recovery() {
+        let mut node = PendingRecovery::new(PeerId::random(), "validator-0", true);
+        assert!(!node.recovered(0));
+        assert_eq!(
+            node.describe(5_000),
+            "validator-0 (missing peers, not answering)"
+        );
+
+        node.version = Some(3_000);
+        assert!(!node.recovered(3_000));
+        node.connected = true;
+        assert!(node.recovered(4_000));
+        assert!(!node.recovered(4_001));
+        assert_eq!(
+            node.describe(5_000),
+            "validator-0 (connected, 2000 versions behind)"
+        );
+    }
+
+    #[test]
+    fn test_upgrade_selector() {
+        let validators: Vec<_> = (0..5).map(|_| PeerId::random()).collect();
+        assert_eq!(
+            UpgradeSelector::Fraction(0.5).select(&validators).unwrap(),
+            validators[..2]
+        );
+        assert_eq!(
+            UpgradeSelector::Fraction(1.0).select(&validators).unwrap(),
+            validators
+        );
+        UpgradeSelector::Fraction(1.5)
+            .select(&validators)
+            .unwrap_err();
+        assert_eq!(
+            UpgradeSelector::FirstByIndex(3)
+                .select(&validators)
+                .unwrap(),
+            validators[..3]
+        );
+        UpgradeSelector::FirstByIndex(6)
+            .select(&validators)
+            .unwrap_err();
+        assert_eq!(
+            UpgradeSelector::PeerIds(vec![validators[4], validators[1]])
+                .select(&validators)
+                .unwrap(),
+            vec![validators[4], validators[1]]
+        );
+        UpgradeSelector::PeerIds(vec![PeerId::random()])
+            .select(&validators)
+            .unwrap_err();
+    }
+}
@@ -0,0 +1,204 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::HealthCheckError;
+use aptos_sdk::types::PeerId;
+use std::fmt::{Display, Formatter};
+
+/// The outcome of the health check of one node
+#[derive(Debug)]
+pub enum NodeHealth {
+    Healthy,
+    // the REST API is reachable, but the ledger has not advanced recently
+    Stale(String),
+    // any other failure, e.g. the REST API does not answer or the pod is not running
+    Unreachable(HealthCheckError),
+}
+
+impl From<Result<(), HealthCheckError>> for NodeHealth {
+    fn from(result: Result<(), HealthCheckError>) -> Self {
+        match result {
+            Ok(()) => NodeHealth::Healthy,
+            Err(HealthCheckError::Stale(reason)) => NodeHealth::Stale(reason),
+            Err(e) => NodeHealth::Unreachable(e),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct NodeHealthResult {
+    pub name: String,
+    pub peer_id: PeerId,
+    pub validator: bool,
+    pub health: NodeHealth,
+}
+
+impl NodeHealthResult {
+    pub fn is_healthy(&self) -> bool {
+        matches!(self.health, NodeHealth::Healthy)
+    }
+}
+
+/// The health of every node of a swarm, checked at once, see
+/// [crate::SwarmExt::health_check_all]. Unlike [crate::Swarm::health_check], it tells one
+/// failing node apart from many.
+#[derive(Debug, Default)]
+pub struct SwarmHealth {
+    // validators first, in index order, then the fullnodes
+    pub nodes: Vec<NodeHealthResult>,
+}
+
+impl SwarmHealth {
+    pub fn is_healthy(&self) -> bool {
+        self.nodes.iter().all(|node| node.is_healthy())
+    }
+
+    pub fn unhealthy(&self) -> impl Iterator<Item = &NodeHealthResult> {
+        self.nodes.iter().filter(|node| !node.is_healthy())
+    }
+
+    pub fn unhealthy_validators(&self) -> Vec<&NodeHealthResult> {
+        self.unhealthy().filter(|node| node.validator).collect()
+    }
+
+    pub fn unhealthy_fullnodes(&self) -> Vec<&NodeHealthResult> {
+        self.unhealthy().filter(|node| !node.validator).collect()
+    }
+}
+
+impl Display for SwarmHealth {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let validators = self.nodes.iter().filter(|node| node.validator).count();
+        write!(
+            f,
+            "{} of {} validators and {} of {} fullnodes unhealthy",
+            self.unhealthy_validators().len(),
+            validators,
+            self.unhealthy_fullnodes().len(),
+            self.nodes.len() - validators
+        )?;
+        for node in self.unhealthy() {
+            match &node.health {
+                NodeHealth::Healthy => {},
+                NodeHealth::Stale(reason) => write!(f, "\n  {} is stale: {}", node.name, reason)?,
+                NodeHealth::Unreachable(e) => write!(f, "\n  {} is unreachable: {}", node.name, e)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// How often a node's container restarted during the run, see [crate::Swarm::restarts]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NodeRestarts {
+    pub name: String,
+    pub restarts: u32,
+    // of the restarts, those the test did, e.g. stopping, killing or upgrading the node, and the
+    // OOMKills of memory stress chaos
+    pub expected: u32,
+    // e.g. OOMKilled or Error, of the last time the container terminated
+    pub last_termination_reason: Option<String>,
+}
+
+impl NodeRestarts {
+    pub fn unexpected(&self) -> u32 {
+        self.restarts.saturating_sub(self.expected)
+    }
+}
+
+impl Display for NodeRestarts {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {} restarts, {} unexpected",
+            self.name,
+            self.restarts,
+            self.unexpected()
+        )?;
+        if let Some(reason) = &self.last_termination_reason {
+            write!(f, ", last terminated with {}", reason)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::format_err;
+
+    #[test]
+    fn test_swarm_health() {
+        let node =
+            |name: &str, validator: bool, result: Result<(), HealthCheckError>| NodeHealthResult {
+                name: name.to_string(),
+                peer_id: PeerId::random(),
+                validator,
+                health: result.into(),
+            };
+        let health = SwarmHealth {
+            nodes: vec![
+                node("validator-0", true, Ok(())),
+                node(
+                    "validator-1",
+                    true,
+                    Err(HealthCheckError::Failure(format_err!("connection refused"))),
+                ),
+                node(
+                    "validator-2",
+                    true,
+                    Err(HealthCheckError::Stale("ledger is 30s old".to_string())),
+                ),
+                node(
+                    "fullnode-0",
+                    false,
+                    Err(HealthCheckError::NotRunning("no pod".to_string())),
+                ),
+            ],
+        };
+        assert!(!health.is_healthy());
+        let unhealthy: Vec<_> = health
+            .unhealthy_validators()
+            .iter()
+            .map(|node| node.name.as_str())
+            .collect();
+        assert_eq!(unhealthy, vec!["validator-1", "validator-2"]);
+        assert_eq!(health.unhealthy_fullnodes().len(), 1);
+        assert!(matches!(health.nodes[2].health, NodeHealth::Stale(_)));
+
+        let summary = health.to_string();
+        assert!(summary.starts_with("2 of 3 validators and 1 of 1 fullnodes unhealthy"));
+        assert!(summary.contains("validator-1 is unreachable"));
+        assert!(summary.contains("validator-2 is stale: ledger is 30s old"));
+        assert!(!summary.contains("validator-0"));
+
+        assert!(SwarmHealth::default().is_healthy());
+    }
+
+    #[test]
+    fn test_node_restarts() {
+        let restarts = NodeRestarts {
+            name: "validator-3".to_string(),
+            restarts: 3,
+            expected: 1,
+            last_termination_reason: Some("OOMKilled".to_string()),
+        };
+        assert_eq!(restarts.unexpected(), 2);
+        assert_eq!(
+            restarts.to_string(),
+            "validator-3: 3 restarts, 2 unexpected, last terminated with OOMKilled"
+        );
+        // a node started again by the test may not have restarted yet
+        let restarts = NodeRestarts {
+            restarts: 0,
+            expected: 1,
+            last_termination_reason: None,
+            ..restarts
+        };
+        assert_eq!(restarts.unexpected(), 0);
+        assert_eq!(
+            restarts.to_string(),
+            "validator-3: 0 restarts, 0 unexpected"
+        );
+    }
+}
@@ -0,0 +1,400 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::Result;
+use anyhow::{format_err, Context};
+use aptos_logger::{debug, warn};
+use aptos_rest_client::{
+    aptos_api_types::{PendingTransaction, Transaction},
+    error::RestError,
+    Client as RestClient, Response, State,
+};
+use aptos_sdk::{
+    bcs,
+    types::{
+        account_address::AccountAddress, account_config::CORE_CODE_ADDRESS,
+        on_chain_config::OnChainConsensusConfig, transaction::SignedTransaction,
+    },
+};
+use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, RwLock,
+    },
+    time::{Duration, Instant},
+};
+
+// how long a node that could not be reached is passed over, unless no other node is left. Nodes
+// that were stopped come back after it once they are started again.
+const UNREACHABLE_BACKOFF: Duration = Duration::from_secs(10);
+
+/// A node [SwarmRestClient] sends requests to
+struct Backend {
+    name: String,
+    client: RestClient,
+    // the validators only serve requests when no fullnode is reachable
+    fullnode: bool,
+    unreachable_since: Mutex<Option<Instant>>,
+}
+
+impl Backend {
+    fn reachable(&self, now: Instant) -> bool {
+        self.unreachable_since
+            .lock()
+            .unwrap()
+            .map_or(true, |since| {
+                now.duration_since(since) >= UNREACHABLE_BACKOFF
+            })
+    }
+
+    fn set_reachable(&self, reachable: bool) {
+        *self.unreachable_since.lock().unwrap() = (!reachable).then(Instant::now);
+    }
+}
+
+/// A response of [SwarmRestClient], with the name of the node that served it
+#[derive(Debug)]
+pub struct Served<T> {
+    pub backend: String,
+    pub inner: T,
+}
+
+impl<T> Served<T> {
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    fn try_map<U>(self, f: impl FnOnce(T) -> Result<U>) -> Result<Served<U>> {
+        Ok(Served {
+            backend: self.backend,
+            inner: f(self.inner)?,
+        })
+    }
+}
+
+/// A REST client to the chain rather than to a node, see [crate::SwarmExt::rest_client]. The
+/// requests go round-robin to the fullnodes of the swarm, or to its validators if no fullnode is
+/// reachable, and fail over to the next node if one can't be reached, e.g. because the test
+/// stopped or partitioned it. The transactions of an account are all submitted to the same node,
+/// as long as it is reachable, so that they reach mempool in order.
+#[derive(Clone)]
+pub struct SwarmRestClient {
+    backends: Arc<RwLock<Vec<Arc<Backend>>>>,
+    next: Arc<AtomicUsize>,
+    // the name of the node each account submits its transactions to
+    pinned: Arc<Mutex<HashMap<AccountAddress, String>>>,
+}
+
+impl SwarmRestClient {
+    /// A client to the given nodes, by name, the fullnodes first
+    pub fn new(
+        fullnodes: Vec<(String, RestClient)>,
+        validators: Vec<(String, RestClient)>,
+    ) -> Self {
+        let client = Self {
+            backends: Arc::new(RwLock::new(vec![])),
+            next: Arc::new(AtomicUsize::new(0)),
+            pinned: Arc::new(Mutex::new(HashMap::new())),
+        };
+        client.set_backends(fullnodes, validators);
+        client
+    }
+
+    /// Replaces the nodes of the client, e.g. once nodes were added to or removed from the swarm.
+    /// The nodes it kept stay passed over if they were unreachable.
+    pub fn set_backends(
+        &self,
+        fullnodes: Vec<(String, RestClient)>,
+        validators: Vec<(String, RestClient)>,
+    ) {
+        let mut backends = self.backends.write().unwrap();
+        let previous: HashMap<_, _> = backends
+            .iter()
+            .map(|backend| (backend.name.clone(), backend.clone()))
+            .collect();
+        *backends = fullnodes
+            .into_iter()
+            .map(|node| (node, true))
+            .chain(validators.into_iter().map(|node| (node, false)))
+            .map(|((name, client), fullnode)| {
+                let unreachable_since = previous
+                    .get(&name)
+                    .and_then(|backend| *backend.unreachable_since.lock().unwrap());
+                Arc::new(Backend {
+                    name,
+                    client,
+                    fullnode,
+                    unreachable_since: Mutex::new(unreachable_since),
+                })
+            })
+            .collect();
+    }
+
+    /// The names of the nodes the client sends requests to
+    pub fn backends(&self) -> Vec<String> {
+        self.backends
+            .read()
+            .unwrap()
+            .iter()
+            .map(|backend| backend.name.clone())
+            .collect()
+    }
+
+    /// The nodes to try a request on, in order: the pinned node if reachable, the reachable
+    /// fullnodes starting with the next one in turn, the reachable validators likewise, and the
+    /// unreachable nodes as a last resort
+    fn candidates(&self, pinned: Option<&str>) -> Vec<Arc<Backend>> {
+        let now = Instant::now();
+        let (mut pinned_node, mut fullnodes, mut validators, mut unreachable) =
+            (vec![], vec![], vec![], vec![]);
+        for backend in self.backends.read().unwrap().iter().cloned() {
+            if !backend.reachable(now) {
+                unreachable.push(backend);
+            } else if pinned == Some(backend.name.as_str()) {
+                pinned_node.push(backend);
+            } else if backend.fullnode {
+                fullnodes.push(backend);
+            } else {
+                validators.push(backend);
+            }
+        }
+        let turn = self.next.fetch_add(1, Ordering::Relaxed);
+        for tier in [&mut fullnodes, &mut validators] {
+            if !tier.is_empty() {
+                let len = tier.len();
+                tier.rotate_left(turn % len);
+            }
+        }
+        pinned_node
+            .into_iter()
+            .chain(fullnodes)
+            .chain(validators)
+            .chain(unreachable)
+            .collect()
+    }
+
+    /// Sends the request to the next node in turn, and to the next ones as long as the nodes can't
+    /// be reached. The API errors of a node are returned as is.
+    pub async fn request<T, F, Fut>(&self, request: F) -> Result<Served<T>>
+    where
+        F: Fn(RestClient) -> Fut,
+        Fut: Future<Output = Result<T, RestError>>,
+    {
+        self.request_pinned(None, request).await
+    }
+
+    async fn request_pinned<T, F, Fut>(&self, pinned: Option<&str>, request: F) -> Result<Served<T>>
+    where
+        F: Fn(RestClient) -> Fut,
+        Fut: Future<Output = Result<T, RestError>>,
+    {
+        let mut unreachable = vec![];
+        for backend in self.candidates(pinned) {
+            match request(backend.client.clone()).await {
+                Ok(inner) => {
+                    backend.set_reachable(true);
+                    debug!("Request served by {}", backend.name);
+                    return Ok(Served {
+                        backend: backend.name.clone(),
+                        inner,
+                    });
+                },
+                Err(e) if is_unreachable(&e) => {
+                    warn!("{} is unreachable, failing over: {}", backend.name, e);
+                    backend.set_reachable(false);
+                    unreachable.push(format!("{}: {}", backend.name, e));
+                },
+                Err(e) => {
+                    return Err(e).with_context(|| format!("Request to {} failed", backend.name))
+                },
+            }
+        }
+        if unreachable.is_empty() {
+            return Err(format_err!("The swarm has no nodes to send requests to"));
+        }
+        Err(format_err!(
+            "No node of the swarm could be reached: {}",
+            unreachable.join("; ")
+        ))
+    }
+
+    pub async fn get_ledger_information(&self) -> Result<Served<Response<State>>> {
+        self.request(|client| async move { client.get_ledger_information().await })
+            .await
+    }
+
+    pub async fn get_account_resource_bcs<T: DeserializeOwned>(
+        &self,
+        address: AccountAddress,
+        resource_type: &str,
+    ) -> Result<Served<Response<T>>> {
+        self.request(|client| async move {
+            client
+                .get_account_resource_bcs(address, resource_type)
+                .await
+        })
+        .await
+    }
+
+    /// The consensus config on chain, which the 0x1::consensus_config::ConsensusConfig resource
+    /// holds serialized
+    pub async fn get_consensus_config(&self) -> Result<Served<OnChainConsensusConfig>> {
+        self.get_account_resource_bcs::<Vec<u8>>(
+            CORE_CODE_ADDRESS,
+            "0x1::consensus_config::ConsensusConfig",
+        )
+        .await?
+        .try_map(|response| Ok(bcs::from_bytes(&response.into_inner())?))
+    }
+
+    /// Submits the transaction to the node of its sender, see [SwarmRestClient]
+    pub async fn submit(
+        &self,
+        txn: &SignedTransaction,
+    ) -> Result<Served<Response<PendingTransaction>>> {
+        self.pinned_request(
+            txn.sender(),
+            |client| async move { client.submit(txn).await },
+        )
+        .await
+    }
+
+    /// Submits the transaction to the node of its sender and waits for it there
+    pub async fn submit_and_wait(
+        &self,
+        txn: &SignedTransaction,
+    ) -> Result<Served<Response<Transaction>>> {
+        self.pinned_request(txn.sender(), |client| async move {
+            client.submit_and_wait(txn).await
+        })
+        .await
+    }
+
+    /// Sends the request to the node of the account, and makes the node that served it the node of
+    /// the account
+    async fn pinned_request<T, F, Fut>(
+        &self,
+        account: AccountAddress,
+        request: F,
+    ) -> Result<Served<T>>
+    where
+        F: Fn(RestClient) -> Fut,
+        Fut: Future<Output = Result<T, RestError>>,
+    {
+        let pinned = self.pinned.lock().unwrap().get(&account).cloned();
+        let served = self.request_pinned(pinned.as_deref(), request).await?;
+        if pinned.as_ref() != Some(&served.backend) {
+            debug!("Account {} now submits to {}", account, served.backend);
+            self.pinned
+                .lock()
+                .unwrap()
+                .insert(account, served.backend.clone());
+        }
+        Ok(served)
+    }
+}
+
+/// Whether the node could not be reached at all, or only through a proxy that could not reach
+/// it, as opposed to the node failing the request
+fn is_unreachable(error: &RestError) -> bool {
+    match error {
+        RestError::Http(status, _) => matches!(
+            *status,
+            StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE | StatusCode::GATEWAY_TIMEOUT
+        ),
+        RestError::Unknown(e) => e.downcast_ref::<reqwest::Error>().map_or(false, |e| {
+            e.is_connect() || e.is_timeout() || e.is_request()
+        }),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nodes(names: &[&str]) -> Vec<(String, RestClient)> {
+        names
+            .iter()
+            .map(|name| {
+                let url = format!("http://{}.forge:8080", name).parse().unwrap();
+                (name.to_string(), RestClient::new(url))
+            })
+            .collect()
+    }
+
+    fn names(candidates: &[Arc<Backend>]) -> Vec<&str> {
+        candidates
+            .iter()
+            .map(|backend| backend.name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_candidates() {
+        let client = SwarmRestClient::new(nodes(&["fn-0", "fn-1"]), nodes(&["val-0", "val-1"]));
+        // round-robin over the fullnodes, the validators after them
+        assert_eq!(names(&client.candidates(None)), vec![
+            "fn-0", "fn-1", "val-0", "val-1"
+        ]);
+        assert_eq!(names(&client.candidates(None)), vec![
+            "fn-1", "fn-0", "val-1", "val-0"
+        ]);
+        assert_eq!(names(&client.candidates(None)), vec![
+            "fn-0", "fn-1", "val-0", "val-1"
+        ]);
+
+        // the unreachable nodes go last, unless they are past their backoff
+        let backends = client.backends.read().unwrap().clone();
+        backends[0].set_reachable(false);
+        backends[1].set_reachable(false);
+        let candidates = client.candidates(None);
+        assert_eq!(names(&candidates[2..]), vec!["fn-0", "fn-1"]);
+        *backends[1].unreachable_since.lock().unwrap() = Some(Instant::now() - UNREACHABLE_BACKOFF);
+        assert_eq!(names(&client.candidates(None))[0], "fn-1");
+
+        // the pinned node first, while it is reachable
+        assert_eq!(names(&client.candidates(Some("val-1")))[0], "val-1");
+        assert_eq!(names(&client.candidates(Some("fn-0")))[3], "fn-0");
+
+        // the nodes the client keeps stay unreachable
+        client.set_backends(nodes(&["fn-0"]), nodes(&["val-0"]));
+        assert_eq!(client.backends(), vec!["fn-0", "val-0"]);
+        assert_eq!(names(&client.candidates(None)), vec!["val-0", "fn-0"]);
+    }
+
+    // the error of connecting to a local port nothing listens on
+    async fn connection_error() -> reqwest::Error {
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        reqwest::get(format!("http://127.0.0.1:{}", port))
+            .await
+            .unwrap_err()
+    }
+
+    #[tokio::test]
+    async fn test_is_unreachable() {
+        let refused = RestError::Unknown(connection_error().await.into());
+        assert!(is_unreachable(&refused));
+        for status in [StatusCode::BAD_GATEWAY, StatusCode::SERVICE_UNAVAILABLE] {
+            assert!(is_unreachable(&RestError::Http(
+                status,
+                connection_error().await
+            )));
+        }
+
+        let not_found = RestError::Http(StatusCode::NOT_FOUND, connection_error().await);
+        assert!(!is_unreachable(&not_found));
+        let timeout = RestError::Timeout("waiting for transaction");
+        assert!(!is_unreachable(&timeout));
+        let unknown = RestError::Unknown(format_err!("unexpected response"));
+        assert!(!is_unreachable(&unknown));
+    }
+}
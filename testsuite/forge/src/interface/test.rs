@@ -2,7 +2,9 @@
 // Parts of the project are originally copyright © Meta Platforms, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
+use anyhow::{bail, Error, Result};
 use rand::SeedableRng;
+use std::{str::FromStr, time::Duration};
 
 /// Whether a test is expected to fail or not
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
@@ -28,6 +30,18 @@ pub trait Test: Send + Sync {
     fn should_fail(&self) -> ShouldFail {
         ShouldFail::No
     }
+
+    /// How long the Test may run before it fails, if not the timeout of the suite, see
+    /// [crate::ForgeConfig::with_test_timeout]
+    fn timeout(&self) -> Option<Duration> {
+        None
+    }
+
+    /// The tags the Test is selected by, e.g. "perf", "chaos", "compat" or "land-blocking", see
+    /// [TagQuery]
+    fn tags(&self) -> &'static [&'static str] {
+        &[]
+    }
 }
 
 impl<T: Test + ?Sized> Test for &T {
@@ -42,6 +56,68 @@ impl<T: Test + ?Sized> Test for &T {
     fn should_fail(&self) -> ShouldFail {
         (**self).should_fail()
     }
+
+    fn timeout(&self) -> Option<Duration> {
+        (**self).timeout()
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        (**self).tags()
+    }
+}
+
+/// Selects tests by their tags: a test matches if it has all the tags of any of the terms of the
+/// query, and the empty query matches every test. The runner takes a term per --tag option, with
+/// its tags joined by '+', e.g. `--tag perf+land-blocking --tag chaos`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TagQuery {
+    terms: Vec<Vec<String>>,
+}
+
+impl TagQuery {
+    /// Matches the tests with any of the tags
+    pub fn any(tags: &[&str]) -> Self {
+        tags.iter()
+            .fold(Self::default(), |query, tag| query.or_all(&[tag]))
+    }
+
+    /// Also matches the tests with all of the tags
+    pub fn or_all(mut self, tags: &[&str]) -> Self {
+        self.terms
+            .push(tags.iter().map(|tag| tag.to_string()).collect());
+        self
+    }
+
+    /// Also matches the tests the other query matches
+    pub fn or(mut self, other: TagQuery) -> Self {
+        self.terms.extend(other.terms);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    pub fn matches(&self, tags: &[&str]) -> bool {
+        self.is_empty()
+            || self
+                .terms
+                .iter()
+                .any(|term| term.iter().all(|tag| tags.contains(&tag.as_str())))
+    }
+}
+
+impl FromStr for TagQuery {
+    type Err = Error;
+
+    /// A query of a single term, e.g. "perf+land-blocking"
+    fn from_str(s: &str) -> Result<Self> {
+        let tags: Vec<_> = s.split('+').map(str::trim).collect();
+        if tags.iter().any(|tag| tag.is_empty()) {
+            bail!("Invalid tag query {:?}, expected tags joined by '+'", s);
+        }
+        Ok(Self::default().or_all(&tags))
+    }
 }
 
 #[derive(Debug)]
@@ -64,3 +140,29 @@ impl CoreContext {
         &mut self.rng
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tag_query() {
+        let perf = ["perf", "land-blocking"];
+        let chaos = ["chaos"];
+        assert!(TagQuery::default().matches(&[]));
+        assert!(TagQuery::any(&["perf", "chaos"]).matches(&perf));
+        assert!(TagQuery::any(&["perf", "chaos"]).matches(&chaos));
+        assert!(!TagQuery::any(&["compat"]).matches(&perf));
+
+        // all the tags of a term
+        let query: TagQuery = "perf+land-blocking".parse().unwrap();
+        assert!(query.matches(&perf));
+        assert!(!query.matches(&["perf"]));
+        let query = query.or("chaos".parse().unwrap());
+        assert!(query.matches(&chaos));
+        assert!(!query.matches(&[]));
+
+        assert!("perf+".parse::<TagQuery>().is_err());
+        assert!("".parse::<TagQuery>().is_err());
+    }
+}
@@ -0,0 +1,173 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use anyhow::{Context, Result};
+use std::{fmt::Write, fs, path::Path, time::Duration};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum JunitOutcome {
+    Passed,
+    // with the error of the test
+    Failed(String),
+    // with the reason the test was not run, e.g. it was filtered out
+    Skipped(String),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct JunitTestCase {
+    pub name: String,
+    pub duration: Duration,
+    pub outcome: JunitOutcome,
+}
+
+/// The results of a forge run as a JUnit XML test suite, for CI to render, written by the runner
+/// to --report-junit
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct JunitReport {
+    suite: String,
+    test_cases: Vec<JunitTestCase>,
+}
+
+impl JunitReport {
+    pub fn new(suite: &str) -> Self {
+        Self {
+            suite: suite.to_string(),
+            test_cases: vec![],
+        }
+    }
+
+    pub fn add(&mut self, name: &str, duration: Duration, outcome: JunitOutcome) {
+        self.test_cases.push(JunitTestCase {
+            name: name.to_string(),
+            duration,
+            outcome,
+        });
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.test_cases
+            .iter()
+            .any(|test_case| test_case.name == name)
+    }
+
+    pub fn to_xml(&self) -> String {
+        let count = |f: fn(&JunitOutcome) -> bool| {
+            self.test_cases
+                .iter()
+                .filter(|test_case| f(&test_case.outcome))
+                .count()
+        };
+        let failures = count(|outcome| matches!(outcome, JunitOutcome::Failed(_)));
+        let skipped = count(|outcome| matches!(outcome, JunitOutcome::Skipped(_)));
+        let time: f64 = self
+            .test_cases
+            .iter()
+            .map(|test_case| test_case.duration.as_secs_f64())
+            .sum();
+        let suite = escape(&self.suite);
+        let attributes = format!(
+            "name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"0\" skipped=\"{}\" time=\"{:.3}\"",
+            suite,
+            self.test_cases.len(),
+            failures,
+            skipped,
+            time
+        );
+
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        writeln!(xml, "<testsuites {}>", attributes).unwrap();
+        writeln!(xml, "  <testsuite {}>", attributes).unwrap();
+        for test_case in &self.test_cases {
+            let open = format!(
+                "    <testcase name=\"{}\" classname=\"{}\" time=\"{:.3}\"",
+                escape(&test_case.name),
+                suite,
+                test_case.duration.as_secs_f64()
+            );
+            match &test_case.outcome {
+                JunitOutcome::Passed => writeln!(xml, "{}/>", open),
+                JunitOutcome::Failed(error) => writeln!(
+                    xml,
+                    "{}>\n      <failure message=\"{}\">{}</failure>\n    </testcase>",
+                    open,
+                    escape(error.lines().next().unwrap_or_default()),
+                    escape(error)
+                ),
+                JunitOutcome::Skipped(reason) => writeln!(
+                    xml,
+                    "{}>\n      <skipped message=\"{}\"/>\n    </testcase>",
+                    open,
+                    escape(reason)
+                ),
+            }
+            .unwrap();
+        }
+        xml.push_str("  </testsuite>\n</testsuites>\n");
+        xml
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        fs::write(path, self.to_xml())
+            .with_context(|| format!("Failed to write the JUnit report to {:?}", path))
+    }
+}
+
+// escapes the text for XML attributes and elements, dropping the control characters XML 1.0 does
+// not allow, e.g. those of colored error output
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\n' | '\r' | '\t' => escaped.push(c),
+            c if c.is_control() => {},
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_junit_report() {
+        let mut report = JunitReport::new("land_blocking");
+        report.add("smoke", Duration::from_millis(1500), JunitOutcome::Passed);
+        report.add(
+            "load",
+            Duration::from_secs(2),
+            JunitOutcome::Failed("TPS 10 < 100\n\u{1b}[31mat <load>".to_string()),
+        );
+        report.add(
+            "chaos",
+            Duration::ZERO,
+            JunitOutcome::Skipped("filtered out".to_string()),
+        );
+        assert!(report.contains("load"));
+        assert!(!report.contains("upgrade"));
+
+        assert_eq!(
+            report.to_xml(),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites name="land_blocking" tests="3" failures="1" errors="0" skipped="1" time="3.500">
+  <testsuite name="land_blocking" tests="3" failures="1" errors="0" skipped="1" time="3.500">
+    <testcase name="smoke" classname="land_blocking" time="1.500"/>
+    <testcase name="load" classname="land_blocking" time="2.000">
+      <failure message="TPS 10 &lt; 100">TPS 10 &lt; 100
+[31mat &lt;load&gt;</failure>
+    </testcase>
+    <testcase name="chaos" classname="land_blocking" time="0.000">
+      <skipped message="filtered out"/>
+    </testcase>
+  </testsuite>
+</testsuites>
+"#
+        );
+    }
+}
@@ -20,6 +20,12 @@ pub use backend::*;
 mod report;
 pub use report::*;
 
+mod junit;
+pub use junit::*;
+
+mod failure;
+pub use failure::*;
+
 mod github;
 pub use github::*;
 
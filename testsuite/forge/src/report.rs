@@ -2,18 +2,77 @@
 // Parts of the project are originally copyright © Meta Platforms, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::FailureCategory;
+use anyhow::Context;
 use aptos_logger::info;
 use aptos_transaction_emitter_lib::emitter::stats::TxnStats;
-use serde::Serialize;
-use std::fmt;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt, fs, path::Path, time::Duration};
+
+/// The version of the [JsonReport] schema, bumped whenever a field changes meaning or goes away
+pub const JSON_REPORT_VERSION: u32 = 1;
 
 #[derive(Default, Debug, Serialize)]
 pub struct TestReport {
     metrics: Vec<ReportedMetric>,
     text: String,
+    // what the runner writes to --report-json, kept out of the report printed to stdout
+    #[serde(skip)]
+    json: JsonReport,
+    // the test running now, which the metrics, chaos events and artifacts are reported to
+    #[serde(skip)]
+    current_test: Option<JsonTestReport>,
+}
+
+/// The structured report of a forge run, for CI tooling to ingest
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct JsonReport {
+    pub version: u32,
+    pub tests: Vec<JsonTestReport>,
+    // the metrics reported outside of any test
+    pub metrics: Vec<ReportedMetric>,
+    // by node name
+    pub node_versions: BTreeMap<String, String>,
+    pub restarts: Vec<String>,
+    pub infrastructure_events: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct JsonTestReport {
+    pub name: String,
+    // Failed until the test finishes
+    pub result: JsonTestResult,
+    // of all the attempts
+    pub duration_secs: f64,
+    pub failure: Option<String>,
+    pub failure_category: Option<FailureCategory>,
+    // every run of the test, the last one included, see --retry-infra-failures
+    #[serde(default)]
+    pub attempts: Vec<JsonTestAttempt>,
+    // e.g. avg_tps and p50_latency, p90_latency and p99_latency in ms
+    pub metrics: Vec<ReportedMetric>,
+    pub chaos_events: Vec<String>,
+    pub artifacts: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct JsonTestAttempt {
+    pub result: JsonTestResult,
+    pub duration_secs: f64,
+    pub failure: Option<String>,
+    pub failure_category: Option<FailureCategory>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JsonTestResult {
+    Passed,
+    Failed,
+    // filtered out, see the --filter and --tag options of the runner
+    Skipped,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct ReportedMetric {
     pub test_name: String,
     pub metric: String,
@@ -26,13 +85,145 @@ impl TestReport {
     }
 
     pub fn report_metric<E: ToString, M: ToString>(&mut self, test: E, metric: M, value: f64) {
-        self.metrics.push(ReportedMetric {
+        let metric = ReportedMetric {
             test_name: test.to_string(),
             metric: metric.to_string(),
             value,
+        };
+        match &mut self.current_test {
+            Some(current_test) => current_test.metrics.push(metric.clone()),
+            None => self.json.metrics.push(metric.clone()),
+        }
+        self.metrics.push(metric);
+    }
+
+    /// Reports what follows to the given test in the JSON report, until [TestReport::finish_test]
+    pub fn start_test(&mut self, name: &str) {
+        self.current_test = Some(JsonTestReport {
+            name: name.to_string(),
+            result: JsonTestResult::Failed,
+            duration_secs: 0.0,
+            failure: None,
+            failure_category: None,
+            attempts: vec![],
+            metrics: vec![],
+            chaos_events: vec![],
+            artifacts: vec![],
         });
     }
 
+    /// Lists the test as skipped, in the text report and the JSON report
+    pub fn skip_test(&mut self, name: &str, reason: &str) {
+        self.report_text(format!("Skipped {}: {}", name, reason));
+        self.json.tests.push(JsonTestReport {
+            name: name.to_string(),
+            result: JsonTestResult::Skipped,
+            duration_secs: 0.0,
+            failure: None,
+            failure_category: None,
+            attempts: vec![],
+            metrics: vec![],
+            chaos_events: vec![],
+            artifacts: vec![],
+        });
+    }
+
+    /// Records a failed attempt at the running test, which is run again
+    pub fn retry_test(
+        &mut self,
+        failure: Option<String>,
+        category: Option<FailureCategory>,
+        duration: Duration,
+    ) {
+        if let Some(current_test) = &mut self.current_test {
+            current_test
+                .attempts
+                .push(Self::attempt(failure, category, duration));
+        }
+    }
+
+    /// Records the outcome of the last attempt at the test started last in the JSON report
+    pub fn finish_test(
+        &mut self,
+        failure: Option<String>,
+        category: Option<FailureCategory>,
+        duration: Duration,
+    ) {
+        if let Some(mut test) = self.current_test.take() {
+            let attempt = Self::attempt(failure, category, duration);
+            test.result = attempt.result;
+            test.failure = attempt.failure.clone();
+            test.failure_category = attempt.failure_category;
+            test.attempts.push(attempt);
+            test.duration_secs = test
+                .attempts
+                .iter()
+                .map(|attempt| attempt.duration_secs)
+                .sum();
+            self.json.tests.push(test);
+        }
+    }
+
+    fn attempt(
+        failure: Option<String>,
+        failure_category: Option<FailureCategory>,
+        duration: Duration,
+    ) -> JsonTestAttempt {
+        JsonTestAttempt {
+            result: match failure {
+                Some(_) => JsonTestResult::Failed,
+                None => JsonTestResult::Passed,
+            },
+            duration_secs: duration.as_secs_f64(),
+            failure,
+            failure_category,
+        }
+    }
+
+    /// A fault injected during the running test, for the JSON report only
+    pub fn report_chaos_event(&mut self, event: String) {
+        if let Some(current_test) = &mut self.current_test {
+            current_test.chaos_events.push(event);
+        }
+    }
+
+    /// Where an artifact of the running test went, for the JSON report only
+    pub fn report_artifact<P: AsRef<Path>>(&mut self, path: P) {
+        if let Some(current_test) = &mut self.current_test {
+            current_test
+                .artifacts
+                .push(path.as_ref().display().to_string());
+        }
+    }
+
+    /// The version each node ran at the end of the run, for the JSON report only
+    pub fn report_node_version(&mut self, node: String, version: String) {
+        self.json.node_versions.insert(node, version);
+    }
+
+    /// The restarts and infrastructure events of the run, for the JSON report only
+    pub fn report_swarm_events(
+        &mut self,
+        restarts: Vec<String>,
+        infrastructure_events: Vec<String>,
+    ) {
+        self.json.restarts = restarts;
+        self.json.infrastructure_events = infrastructure_events;
+    }
+
+    pub fn json_report(&self) -> JsonReport {
+        JsonReport {
+            version: JSON_REPORT_VERSION,
+            ..self.json.clone()
+        }
+    }
+
+    pub fn write_json_report(&self, path: &Path) -> anyhow::Result<()> {
+        let json_report = serde_json::to_string_pretty(&self.json_report())?;
+        fs::write(path, json_report)
+            .with_context(|| format!("Failed to write the JSON report to {:?}", path))
+    }
+
     pub fn report_text(&mut self, text: String) {
         if !self.text.is_empty() {
             self.text.push('\n');
@@ -70,3 +261,73 @@ impl fmt::Display for TestReport {
         write!(f, "{}", self.text)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_report() {
+        let mut report = TestReport::new();
+        report.report_metric("setup", "genesis_secs", 10.0);
+        report.start_test("load");
+        report.report_metric("load", "avg_tps", 5000.0);
+        report.report_chaos_event("partition".to_string());
+        report.report_artifact("forge-artifacts/load/val-0.log");
+        report.finish_test(None, None, Duration::from_millis(1500));
+        report.start_test("chaos");
+        report.retry_test(
+            Some("helm upgrade failed".to_string()),
+            Some(FailureCategory::Infra),
+            Duration::from_secs(1),
+        );
+        report.finish_test(
+            Some("liveness".to_string()),
+            Some(FailureCategory::Test),
+            Duration::from_secs(2),
+        );
+        report.report_chaos_event("dropped".to_string());
+        report.skip_test("upgrade", "filtered out");
+        report.report_node_version("val-0".to_string(), "main".to_string());
+
+        let json_report = report.json_report();
+        assert_eq!(json_report.version, JSON_REPORT_VERSION);
+        assert_eq!(json_report.metrics.len(), 1);
+        assert_eq!(json_report.tests.len(), 3);
+        let load = &json_report.tests[0];
+        assert_eq!(load.result, JsonTestResult::Passed);
+        assert_eq!(load.duration_secs, 1.5);
+        assert_eq!(load.attempts.len(), 1);
+        assert_eq!(load.metrics[0].metric, "avg_tps");
+        assert_eq!(load.chaos_events, vec!["partition"]);
+        assert_eq!(load.artifacts, vec!["forge-artifacts/load/val-0.log"]);
+        let chaos = &json_report.tests[1];
+        assert_eq!(chaos.result, JsonTestResult::Failed);
+        assert_eq!(chaos.failure.as_deref(), Some("liveness"));
+        assert_eq!(chaos.failure_category, Some(FailureCategory::Test));
+        // every attempt is kept, with its classification
+        assert_eq!(chaos.duration_secs, 3.0);
+        let categories: Vec<_> = chaos
+            .attempts
+            .iter()
+            .map(|attempt| attempt.failure_category)
+            .collect();
+        assert_eq!(categories, vec![
+            Some(FailureCategory::Infra),
+            Some(FailureCategory::Test)
+        ]);
+        assert!(chaos.chaos_events.is_empty());
+        assert_eq!(json_report.tests[2].result, JsonTestResult::Skipped);
+
+        // the schema round-trips, and stays out of the report printed to stdout
+        let json = serde_json::to_string(&json_report).unwrap();
+        assert!(json.contains("\"result\":\"passed\""));
+        assert_eq!(
+            serde_json::from_str::<JsonReport>(&json).unwrap(),
+            json_report
+        );
+        assert!(!serde_json::to_string(&report)
+            .unwrap()
+            .contains("node_versions"));
+    }
+}
@@ -10,16 +10,25 @@ use crate::{
 use anyhow::{bail, format_err, Error, Result};
 use aptos_config::config::{NodeConfig, OverrideNodeConfig};
 use aptos_framework::ReleaseBundle;
+use aptos_sdk::types::on_chain_config::{FeatureFlag, Features, OnChainConsensusConfig};
 use clap::{Parser, ValueEnum};
-use rand::{rngs::OsRng, Rng, SeedableRng};
+use rand::{
+    rngs::{OsRng, StdRng},
+    Rng, SeedableRng,
+};
+use regex::Regex;
+use serde_json::json;
 use std::{
+    collections::BTreeMap,
     fmt::{Display, Formatter},
+    future::Future,
     io::{self, Write},
     num::NonZeroUsize,
+    path::PathBuf,
     process,
     str::FromStr,
     sync::Arc,
-    time::Duration,
+    time::{Duration, Instant},
 };
 use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
 use tokio::runtime::Runtime;
@@ -36,6 +45,14 @@ pub struct Options {
     #[clap(long = "exact")]
     /// Exactly match filters rather than by substring
     filter_exact: bool,
+    #[clap(long = "filter")]
+    /// Only run the tests whose names match the regex, e.g. "^network::"
+    filter_regex: Option<Regex>,
+    #[clap(long = "tag")]
+    /// Only run the tests with all the tags joined by '+', e.g. "perf+land-blocking". Repeat the
+    /// option to run the tests matching any of them, e.g. `--tag perf --tag chaos`. Applies on top
+    /// of the name filters.
+    tags: Vec<TagQuery>,
     #[allow(dead_code)]
     #[clap(long, default_value = "1", env = "RUST_TEST_THREADS")]
     /// NO-OP: unsupported option, exists for compatibility with the default test harness
@@ -75,6 +92,33 @@ pub struct Options {
     /// NO-OP: unsupported option, exists for compatibility with the default test harness
     /// Show captured stdout of successful tests
     show_output: bool,
+    #[clap(long, env = "FORGE_ARTIFACTS_DIR", default_value = "forge-artifacts")]
+    /// Directory the logs, events and pod descriptions of the nodes are collected into, under
+    /// the name of the network test that failed
+    artifacts_dir: PathBuf,
+    #[clap(long)]
+    /// Collect the artifacts of the nodes after every network test, not only after failures
+    always_collect_artifacts: bool,
+    #[clap(long)]
+    /// After a network test fails, snapshot the DBs of the validators that failed their health
+    /// check into the artifacts directory. The validators are stopped while they are snapshotted.
+    snapshot_dbs_on_failure: bool,
+    #[clap(long)]
+    /// The max size of a DB snapshot, of the data directory and of the compressed archive
+    db_snapshot_max_bytes: Option<u64>,
+    #[clap(long)]
+    /// Write a structured JSON report of the run to the given path: the result, duration,
+    /// metrics, chaos events and artifacts of each test, and the versions of the nodes. It is
+    /// written also when the run aborts.
+    report_json: Option<PathBuf>,
+    #[clap(long)]
+    /// Write a JUnit XML report of the run to the given path, also when the run aborts
+    report_junit: Option<PathBuf>,
+    #[clap(long, default_value_t = 0)]
+    /// Retry a network test that failed because of the infrastructure, e.g. an image pull timeout
+    /// or a kube API error, up to this many times, each on a new swarm. Failures of the test
+    /// itself are never retried.
+    retry_infra_failures: usize,
 }
 
 impl Options {
@@ -120,13 +164,211 @@ pub type GenesisConfigFn = Arc<dyn Fn(&mut serde_yaml::Value) + Send + Sync>;
 /// override_config, base_config (see OverrideNodeConfig)
 pub type OverrideNodeConfigFn = Arc<dyn Fn(&mut NodeConfig, &mut NodeConfig) + Send + Sync>;
 
+/// The CPU and memory of a group of pods, merged into the helm values. The requests are also the
+/// limits, unless the limits are given.
 #[derive(Clone, Copy, Default)]
 pub struct NodeResourceOverride {
     pub cpu_cores: Option<usize>,
     pub memory_gib: Option<usize>,
+    pub cpu_limit_cores: Option<usize>,
+    pub memory_limit_gib: Option<usize>,
+}
+
+impl NodeResourceOverride {
+    /// Sets the requests and limits under the `resources` of a group in the helm values
+    fn apply(&self, resources: &mut serde_yaml::Value) {
+        if let Some(cpu_cores) = self.cpu_cores {
+            resources["requests"]["cpu"] = cpu_cores.into();
+            resources["limits"]["cpu"] = cpu_cores.into();
+        }
+        if let Some(memory_gib) = self.memory_gib {
+            resources["requests"]["memory"] = format!("{}Gi", memory_gib).into();
+            resources["limits"]["memory"] = format!("{}Gi", memory_gib).into();
+        }
+        if let Some(cpu_limit_cores) = self.cpu_limit_cores {
+            resources["limits"]["cpu"] = cpu_limit_cores.into();
+        }
+        if let Some(memory_limit_gib) = self.memory_limit_gib {
+            resources["limits"]["memory"] = format!("{}Gi", memory_limit_gib).into();
+        }
+    }
+}
+
+/// Where the pods of the testnet are scheduled, merged into the helm values. Swarm creation fails
+/// if a pod can't be scheduled with them.
+#[derive(Clone, Debug, Default)]
+pub struct PodScheduling {
+    // at most one validator per k8s node, so that they don't compete for its CPUs
+    pub validator_anti_affinity: bool,
+    // the labels of the k8s nodes of a dedicated node pool, for all pods of the testnet
+    pub node_selector: BTreeMap<String, String>,
+    // the NoSchedule taints of the dedicated node pool to tolerate, as key and value
+    pub tolerations: Vec<(String, String)>,
+    // spread the validators, and the fullnodes, over the zones, at most this many apart
+    pub zone_max_skew: Option<u32>,
+}
+
+impl PodScheduling {
+    fn apply(&self, helm_values: &mut serde_yaml::Value) {
+        let to_value = |value: serde_json::Value| serde_yaml::to_value(value).unwrap();
+        for group in ["validator", "fullnode", "haproxy"] {
+            if !self.node_selector.is_empty() {
+                helm_values[group]["nodeSelector"] = to_value(json!(self.node_selector));
+            }
+            if !self.tolerations.is_empty() {
+                let tolerations: Vec<_> = self
+                    .tolerations
+                    .iter()
+                    .map(|(key, value)| {
+                        json!({
+                            "key": key,
+                            "operator": "Equal",
+                            "value": value,
+                            "effect": "NoSchedule",
+                        })
+                    })
+                    .collect();
+                helm_values[group]["tolerations"] = to_value(json!(tolerations));
+            }
+        }
+        if self.validator_anti_affinity {
+            helm_values["validator"]["affinity"] = to_value(json!({
+                "podAntiAffinity": {
+                    "requiredDuringSchedulingIgnoredDuringExecution": [{
+                        "labelSelector": {
+                            "matchLabels": { "app.kubernetes.io/name": "validator" },
+                        },
+                        "topologyKey": "kubernetes.io/hostname",
+                    }],
+                },
+            }));
+        }
+        if let Some(max_skew) = self.zone_max_skew {
+            for group in ["validator", "fullnode"] {
+                helm_values[group]["topologySpreadConstraints"] = to_value(json!([{
+                    "maxSkew": max_skew,
+                    "topologyKey": "topology.kubernetes.io/zone",
+                    "whenUnsatisfiable": "DoNotSchedule",
+                    "labelSelector": {
+                        "matchLabels": { "app.kubernetes.io/name": group },
+                    },
+                }]));
+            }
+        }
+    }
+}
+
+/// The on-chain config to start the testnet with instead of the defaults of the genesis helm
+/// chart, e.g. shorter epochs. The nodes are deployed once genesis is generated with it.
+#[derive(Clone, Debug, Default)]
+pub struct GenesisOverrides {
+    pub epoch_duration_secs: Option<u64>,
+    // in octas
+    pub min_stake: Option<u64>,
+    pub max_stake: Option<u64>,
+    pub consensus_config: Option<OnChainConsensusConfig>,
+    // enabled on top of, or disabled from, the default feature flags
+    pub enabled_features: Vec<FeatureFlag>,
+    pub disabled_features: Vec<FeatureFlag>,
+    // of governance proposals, see [crate::SwarmExt::execute_governance_proposal]
+    pub voting_duration_secs: Option<u64>,
+    // in percent of the voting power, of the stake that may join the validators in an epoch
+    pub voting_power_increase_limit: Option<u64>,
+}
+
+impl GenesisOverrides {
+    pub fn is_empty(&self) -> bool {
+        self.epoch_duration_secs.is_none()
+            && self.min_stake.is_none()
+            && self.max_stake.is_none()
+            && self.consensus_config.is_none()
+            && self.enabled_features.is_empty()
+            && self.disabled_features.is_empty()
+            && self.voting_duration_secs.is_none()
+            && self.voting_power_increase_limit.is_none()
+    }
+
+    fn features(&self) -> Features {
+        let mut features = Features::default();
+        for flag in &self.enabled_features {
+            features.enable(*flag);
+        }
+        for flag in &self.disabled_features {
+            features.disable(*flag);
+        }
+        features
+    }
+
+    fn apply(&self, helm_values: &mut serde_yaml::Value) {
+        if let Some(epoch_duration_secs) = self.epoch_duration_secs {
+            helm_values["chain"]["epoch_duration_secs"] = epoch_duration_secs.into();
+        }
+        if let Some(min_stake) = self.min_stake {
+            helm_values["chain"]["min_stake"] = min_stake.into();
+        }
+        if let Some(max_stake) = self.max_stake {
+            helm_values["chain"]["max_stake"] = max_stake.into();
+        }
+        if let Some(consensus_config) = &self.consensus_config {
+            helm_values["chain"]["on_chain_consensus_config"] =
+                serde_yaml::to_value(consensus_config).unwrap();
+        }
+        if !self.enabled_features.is_empty() || !self.disabled_features.is_empty() {
+            helm_values["chain"]["initial_features_override"] =
+                serde_yaml::to_value(self.features()).unwrap();
+        }
+        if let Some(voting_duration_secs) = self.voting_duration_secs {
+            helm_values["chain"]["voting_duration_secs"] = voting_duration_secs.into();
+        }
+        if let Some(voting_power_increase_limit) = self.voting_power_increase_limit {
+            helm_values["chain"]["voting_power_increase_limit"] =
+                voting_power_increase_limit.into();
+        }
+    }
+}
+
+impl Display for GenesisOverrides {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut overrides = vec![];
+        if let Some(epoch_duration_secs) = self.epoch_duration_secs {
+            overrides.push(format!("epoch duration {}s", epoch_duration_secs));
+        }
+        if let Some(min_stake) = self.min_stake {
+            overrides.push(format!("min stake {}", min_stake));
+        }
+        if let Some(max_stake) = self.max_stake {
+            overrides.push(format!("max stake {}", max_stake));
+        }
+        if let Some(consensus_config) = &self.consensus_config {
+            overrides.push(format!("consensus config {:?}", consensus_config));
+        }
+        if !self.enabled_features.is_empty() {
+            overrides.push(format!("enabled features {:?}", self.enabled_features));
+        }
+        if !self.disabled_features.is_empty() {
+            overrides.push(format!("disabled features {:?}", self.disabled_features));
+        }
+        if let Some(voting_duration_secs) = self.voting_duration_secs {
+            overrides.push(format!("voting duration {}s", voting_duration_secs));
+        }
+        if let Some(voting_power_increase_limit) = self.voting_power_increase_limit {
+            overrides.push(format!(
+                "voting power increase limit {}%",
+                voting_power_increase_limit
+            ));
+        }
+        if overrides.is_empty() {
+            write!(f, "none")
+        } else {
+            write!(f, "{}", overrides.join(", "))
+        }
+    }
 }
 
 pub struct ForgeConfig {
+    /// The name of the suite the tests are reported under, e.g. in the JUnit report
+    suite_name: String,
+
     aptos_tests: Vec<Box<dyn AptosTest>>,
     admin_tests: Vec<Box<dyn AdminTest>>,
     network_tests: Vec<Box<dyn NetworkTest>>,
@@ -137,15 +379,25 @@ pub struct ForgeConfig {
     /// The initial number of fullnodes to spawn when the test harness creates a swarm
     initial_fullnode_count: usize,
 
+    /// How many fullnodes each validator with one is given, the ones beyond the first are
+    /// deployed once the swarm is up, see [crate::SwarmExt::ensure_fullnodes_per_validator]
+    fullnodes_per_validator: usize,
+
     /// The initial version to use when the test harness creates a swarm
     initial_version: InitialVersion,
 
+    /// The versions node groups start with instead of the initial one, if any
+    mixed_versions: Option<MixedVersions>,
+
     /// The initial genesis modules to use when starting a network
     genesis_config: Option<GenesisConfig>,
 
     /// Optional genesis helm values init function
     genesis_helm_config_fn: Option<GenesisConfigFn>,
 
+    /// The on-chain config overrides of genesis, applied before the genesis helm values function
+    genesis_overrides: GenesisOverrides,
+
     /// Optional validator node config override function
     validator_override_node_config_fn: Option<OverrideNodeConfigFn>,
 
@@ -166,6 +418,16 @@ pub struct ForgeConfig {
     validator_resource_override: NodeResourceOverride,
 
     fullnode_resource_override: NodeResourceOverride,
+
+    haproxy_resource_override: NodeResourceOverride,
+
+    pod_scheduling: PodScheduling,
+
+    /// How long network tests should wait for a node to become healthy after restarting it
+    node_restart_timeout: Duration,
+
+    /// How long a test may run before it fails, unless the test has a timeout of its own
+    test_timeout: Option<Duration>,
 }
 
 impl ForgeConfig {
@@ -173,6 +435,11 @@ impl ForgeConfig {
         Self::default()
     }
 
+    pub fn with_suite_name(mut self, suite_name: &str) -> Self {
+        self.suite_name = suite_name.to_string();
+        self
+    }
+
     pub fn add_aptos_test<T: AptosTest + 'static>(mut self, aptos_test: T) -> Self {
         self.aptos_tests.push(Box::new(aptos_test));
         self
@@ -213,11 +480,35 @@ impl ForgeConfig {
         self
     }
 
+    pub fn with_fullnodes_per_validator(mut self, fullnodes_per_validator: usize) -> Self {
+        self.fullnodes_per_validator = fullnodes_per_validator;
+        self
+    }
+
     pub fn with_genesis_helm_config_fn(mut self, genesis_helm_config_fn: GenesisConfigFn) -> Self {
         self.genesis_helm_config_fn = Some(genesis_helm_config_fn);
         self
     }
 
+    pub fn with_genesis_overrides(mut self, genesis_overrides: GenesisOverrides) -> Self {
+        self.genesis_overrides = genesis_overrides;
+        self
+    }
+
+    pub fn build_genesis_helm_config_fn(&self) -> Option<GenesisConfigFn> {
+        if self.genesis_overrides.is_empty() {
+            return self.genesis_helm_config_fn.clone();
+        }
+        let genesis_overrides = self.genesis_overrides.clone();
+        let genesis_helm_config_fn = self.genesis_helm_config_fn.clone();
+        Some(Arc::new(move |helm_values: &mut serde_yaml::Value| {
+            genesis_overrides.apply(helm_values);
+            if let Some(config_fn) = &genesis_helm_config_fn {
+                (config_fn)(helm_values);
+            }
+        }))
+    }
+
     pub fn with_validator_override_node_config_fn(mut self, f: OverrideNodeConfigFn) -> Self {
         self.validator_override_node_config_fn = Some(f);
         self
@@ -249,6 +540,19 @@ impl ForgeConfig {
         self
     }
 
+    pub fn with_haproxy_resource_override(
+        mut self,
+        resource_override: NodeResourceOverride,
+    ) -> Self {
+        self.haproxy_resource_override = resource_override;
+        self
+    }
+
+    pub fn with_pod_scheduling(mut self, pod_scheduling: PodScheduling) -> Self {
+        self.pod_scheduling = pod_scheduling;
+        self
+    }
+
     fn override_node_config_from_fn(config_fn: OverrideNodeConfigFn) -> OverrideNodeConfig {
         let mut override_config = NodeConfig::default();
         let mut base_config = NodeConfig::default();
@@ -269,6 +573,8 @@ impl ForgeConfig {
         let existing_db_tag = self.existing_db_tag.clone();
         let validator_resource_override = self.validator_resource_override;
         let fullnode_resource_override = self.fullnode_resource_override;
+        let haproxy_resource_override = self.haproxy_resource_override;
+        let pod_scheduling = self.pod_scheduling.clone();
 
         Some(Arc::new(move |helm_values: &mut serde_yaml::Value| {
             if let Some(override_config) = &validator_override_node_config {
@@ -293,28 +599,10 @@ impl ForgeConfig {
                     existing_db_tag.clone().into();
             }
 
-            // validator resource overrides
-            if let Some(cpu_cores) = validator_resource_override.cpu_cores {
-                helm_values["validator"]["resources"]["requests"]["cpu"] = cpu_cores.into();
-                helm_values["validator"]["resources"]["limits"]["cpu"] = cpu_cores.into();
-            }
-            if let Some(memory_gib) = validator_resource_override.memory_gib {
-                helm_values["validator"]["resources"]["requests"]["memory"] =
-                    format!("{}Gi", memory_gib).into();
-                helm_values["validator"]["resources"]["limits"]["memory"] =
-                    format!("{}Gi", memory_gib).into();
-            }
-            // fullnode resource overrides
-            if let Some(cpu_cores) = fullnode_resource_override.cpu_cores {
-                helm_values["fullnode"]["resources"]["requests"]["cpu"] = cpu_cores.into();
-                helm_values["fullnode"]["resources"]["limits"]["cpu"] = cpu_cores.into();
-            }
-            if let Some(memory_gib) = fullnode_resource_override.memory_gib {
-                helm_values["fullnode"]["resources"]["requests"]["memory"] =
-                    format!("{}Gi", memory_gib).into();
-                helm_values["fullnode"]["resources"]["limits"]["memory"] =
-                    format!("{}Gi", memory_gib).into();
-            }
+            validator_resource_override.apply(&mut helm_values["validator"]["resources"]);
+            fullnode_resource_override.apply(&mut helm_values["fullnode"]["resources"]);
+            haproxy_resource_override.apply(&mut helm_values["haproxy"]["resources"]);
+            pod_scheduling.apply(helm_values);
         }))
     }
 
@@ -323,6 +611,13 @@ impl ForgeConfig {
         self
     }
 
+    /// Start the swarm with some nodes on other versions than the initial one, so that tests
+    /// don't need to upgrade them first
+    pub fn with_mixed_versions(mut self, mixed_versions: MixedVersions) -> Self {
+        self.mixed_versions = Some(mixed_versions);
+        self
+    }
+
     pub fn with_genesis_module_bundle(mut self, bundle: ReleaseBundle) -> Self {
         self.genesis_config = Some(GenesisConfig::Bundle(bundle));
         self
@@ -356,6 +651,39 @@ impl ForgeConfig {
         self
     }
 
+    pub fn with_node_restart_timeout(mut self, node_restart_timeout: Duration) -> Self {
+        self.node_restart_timeout = node_restart_timeout;
+        self
+    }
+
+    /// Fail the aptos and network tests that run for longer than the timeout, and go on with the
+    /// next ones. The admin tests are not async, so they can't be timed out. Neither can a test
+    /// while it blocks its thread, e.g. in a synchronous `tokio_scoped::scope`: it only fails
+    /// once the blocking call returns.
+    pub fn with_test_timeout(mut self, test_timeout: Duration) -> Self {
+        self.test_timeout = Some(test_timeout);
+        self
+    }
+
+    /// Keep only the tests the query matches, so that a suite can be defined by the tags of its
+    /// tests rather than by listing them
+    pub fn retain_tagged(mut self, query: &TagQuery) -> Self {
+        self.aptos_tests.retain(|test| query.matches(test.tags()));
+        self.admin_tests.retain(|test| query.matches(test.tags()));
+        self.network_tests.retain(|test| query.matches(test.tags()));
+        self
+    }
+
+    /// Add the tests of the other config the query matches, so that a suite can select its tests
+    /// by their tags and still set up its own swarm
+    pub fn add_tagged_tests(mut self, tests: ForgeConfig, query: &TagQuery) -> Self {
+        let tests = tests.retain_tagged(query);
+        self.aptos_tests.extend(tests.aptos_tests);
+        self.admin_tests.extend(tests.admin_tests);
+        self.network_tests.extend(tests.network_tests);
+        self
+    }
+
     pub fn number_of_tests(&self) -> usize {
         self.admin_tests.len() + self.network_tests.len() + self.aptos_tests.len()
     }
@@ -410,6 +738,22 @@ impl<'a> Test for AnyTestRef<'a> {
             AnyTestRef::Network(t) => t.should_fail(),
         }
     }
+
+    fn timeout(&self) -> Option<Duration> {
+        match self {
+            AnyTestRef::Aptos(t) => t.timeout(),
+            AnyTestRef::Admin(t) => t.timeout(),
+            AnyTestRef::Network(t) => t.timeout(),
+        }
+    }
+
+    fn tags(&self) -> &'static [&'static str] {
+        match self {
+            AnyTestRef::Aptos(t) => t.tags(),
+            AnyTestRef::Admin(t) => t.tags(),
+            AnyTestRef::Network(t) => t.tags(),
+        }
+    }
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -458,14 +802,18 @@ impl Default for ForgeConfig {
                 ))
         };
         Self {
+            suite_name: "forge".to_string(),
             aptos_tests: vec![],
             admin_tests: vec![],
             network_tests: vec![],
             initial_validator_count: NonZeroUsize::new(1).unwrap(),
             initial_fullnode_count: 0,
+            fullnodes_per_validator: 1,
             initial_version: InitialVersion::Oldest,
+            mixed_versions: None,
             genesis_config: None,
             genesis_helm_config_fn: None,
+            genesis_overrides: GenesisOverrides::default(),
             validator_override_node_config_fn: None,
             fullnode_override_node_config_fn: None,
             multi_region_config: false,
@@ -476,6 +824,10 @@ impl Default for ForgeConfig {
             existing_db_tag: None,
             validator_resource_override: NodeResourceOverride::default(),
             fullnode_resource_override: NodeResourceOverride::default(),
+            haproxy_resource_override: NodeResourceOverride::default(),
+            pod_scheduling: PodScheduling::default(),
+            node_restart_timeout: Duration::from_secs(60),
+            test_timeout: None,
         }
     }
 }
@@ -529,12 +881,58 @@ impl<'cfg, F: Factory> Forge<'cfg, F> {
     }
 
     pub fn run(&self) -> Result<TestReport> {
-        let test_count = self.filter_tests(&self.tests.all_tests()).count();
-        let filtered_out = test_count.saturating_sub(self.tests.all_tests().len());
-
+        let mut junit = JunitReport::new(&self.tests.suite_name);
         let mut report = TestReport::new();
-        let mut summary = TestSummary::new(test_count, filtered_out);
+        let result = self.run_tests(&mut junit, &mut report);
+        // the reports are written also when the run aborts
+        let json_written = match &self.options.report_json {
+            Some(path) => report.write_json_report(path),
+            None => Ok(()),
+        };
+        let junit_written = match &self.options.report_junit {
+            Some(path) => {
+                self.skip_unreported_tests(&mut junit, result.as_ref().err());
+                junit.write(path)
+            },
+            None => Ok(()),
+        };
+        // the error of the run comes first
+        result?;
+        json_written?;
+        junit_written?;
+        Ok(report)
+    }
+
+    /// Marks the tests that are missing from the JUnit report skipped, the filtered out ones and
+    /// those the run did not reach before it aborted
+    fn skip_unreported_tests(&self, junit: &mut JunitReport, error: Option<&Error>) {
+        let all_tests = self.tests.all_tests();
+        let filtered: Vec<_> = self.filter_tests(&all_tests).collect();
+        for test in &all_tests {
+            if junit.contains(test.name()) {
+                continue;
+            }
+            let reason = match error {
+                _ if !is_selected(&filtered, test) => "filtered out".to_string(),
+                Some(e) => format!("not run, the run aborted: {:#}", e),
+                None => "not run".to_string(),
+            };
+            junit.add(test.name(), Duration::ZERO, JunitOutcome::Skipped(reason));
+        }
+    }
+
+    fn run_tests(&self, junit: &mut JunitReport, report: &mut TestReport) -> Result<()> {
+        let all_tests = self.tests.all_tests();
+        let selected: Vec<_> = self.filter_tests(&all_tests).collect();
+        let test_count = selected.len();
+
+        let mut summary = TestSummary::new(all_tests.len(), all_tests.len() - test_count);
         summary.write_starting_msg()?;
+        for test in &all_tests {
+            if !is_selected(&selected, test) {
+                report.skip_test(test.name(), "filtered out");
+            }
+        }
 
         if test_count > 0 {
             println!(
@@ -545,71 +943,307 @@ impl<'cfg, F: Factory> Forge<'cfg, F> {
                     .collect::<Vec<_>>()
             );
             let initial_version = self.initial_version();
-            // The genesis version should always match the initial node version
-            let genesis_version = initial_version.clone();
+            let node_versions = match &self.tests.mixed_versions {
+                Some(mixed_versions) => Some(mixed_versions.resolve(
+                    &self.factory.versions().collect::<Vec<_>>(),
+                    &initial_version,
+                    self.tests.initial_validator_count.get(),
+                )?),
+                None => None,
+            };
+            // The genesis version should always match the initial node version, or the oldest
+            // version any node starts with
+            let genesis_version = node_versions
+                .as_ref()
+                .and_then(|node_versions| node_versions.versions().first().cloned())
+                .unwrap_or_else(|| initial_version.clone());
             let runtime = Runtime::new().unwrap(); // TODO: new multithreaded?
-            let mut rng = ::rand::rngs::StdRng::from_seed(OsRng.gen());
-            let mut swarm = runtime.block_on(self.factory.launch_swarm(
+            let mut rng = StdRng::from_seed(OsRng.gen());
+            let swarm = self.launch_swarm(
+                &runtime,
                 &mut rng,
-                self.tests.initial_validator_count,
-                self.tests.initial_fullnode_count,
                 &initial_version,
                 &genesis_version,
-                self.tests.genesis_config.as_ref(),
-                self.global_duration + Duration::from_secs(NAMESPACE_CLEANUP_DURATION_BUFFER_SECS),
-                self.tests.genesis_helm_config_fn.clone(),
-                self.tests.build_node_helm_config_fn(),
-                self.tests.existing_db_tag.clone(),
-            ))?;
+                node_versions.as_ref(),
+            )?;
+            // what the nodes were deployed with, whether from the helm values or the resource
+            // overrides
+            if let Some(resources) = swarm.resources_summary() {
+                report.report_text(format!("Resources of the nodes:\n{}", resources));
+            }
+            if !self.tests.genesis_overrides.is_empty() {
+                report.report_text(format!(
+                    "Genesis overrides: {}",
+                    self.tests.genesis_overrides
+                ));
+            }
 
             // Run AptosTests
             for test in self.filter_tests(&self.tests.aptos_tests) {
+                report.start_test(test.name());
+                let start = Instant::now();
                 let mut aptos_ctx = AptosContext::new(
                     CoreContext::from_rng(&mut rng),
                     swarm.chain_info().into_aptos_public_info(),
-                    &mut report,
+                    report,
                 );
-                let result = run_test(|| runtime.block_on(test.run(&mut aptos_ctx)));
+                let timeout = test.timeout().or(self.tests.test_timeout);
+                // a runtime of its own, to abort what the test spawned if it times out
+                let test_runtime = aptos_runtimes::spawn_named_runtime("aptos-test".into(), None);
+                let mut timed_out = false;
+                let result = run_test(|| {
+                    let result =
+                        test_runtime.block_on(with_timeout(timeout, test.run(&mut aptos_ctx)));
+                    timed_out = matches!(&result, Err(e) if e.is::<TestTimedOut>());
+                    result
+                });
+                if timed_out {
+                    test_runtime.shutdown_timeout(TIMED_OUT_SHUTDOWN_TIMEOUT);
+                } else {
+                    drop(test_runtime);
+                }
+                let duration = start.elapsed();
                 report.report_text(result.to_string());
+                report.finish_test(result.failure(), result.category(), duration);
+                junit.add(test.name(), duration, result.junit_outcome());
                 summary.handle_result(test.name().to_owned(), result)?;
             }
 
             // Run AdminTests
             for test in self.filter_tests(&self.tests.admin_tests) {
-                let mut admin_ctx = AdminContext::new(
-                    CoreContext::from_rng(&mut rng),
-                    swarm.chain_info(),
-                    &mut report,
-                );
+                report.start_test(test.name());
+                let start = Instant::now();
+                let mut admin_ctx =
+                    AdminContext::new(CoreContext::from_rng(&mut rng), swarm.chain_info(), report);
                 let result = run_test(|| test.run(&mut admin_ctx));
+                let duration = start.elapsed();
                 report.report_text(result.to_string());
+                report.finish_test(result.failure(), result.category(), duration);
+                junit.add(test.name(), duration, result.junit_outcome());
                 summary.handle_result(test.name().to_owned(), result)?;
             }
 
-            let logs_location = swarm.logs_location();
+            let mut logs_location = swarm.logs_location();
             let swarm = Arc::new(tokio::sync::RwLock::new(swarm));
             for test in self.filter_tests(&self.tests.network_tests) {
-                let network_ctx = NetworkContext::new(
-                    CoreContext::from_rng(&mut rng),
-                    swarm.clone(),
-                    &mut report,
-                    self.global_duration,
-                    self.tests.emit_job_request.clone(),
-                    self.tests.success_criteria.clone(),
-                );
-                let handle = network_ctx.runtime.handle().clone();
-                let _handle_context = handle.enter();
-                let network_ctx = NetworkContextSynchronizer::new(network_ctx, handle.clone());
-                let result = run_test(|| handle.block_on(test.run(network_ctx.clone())));
-                // explicitly keep network context in scope so that its created tokio Runtime drops after all the stuff has run.
-                let NetworkContextSynchronizer { ctx, handle } = network_ctx;
-                drop(handle);
-                let ctx = Arc::into_inner(ctx).unwrap().into_inner();
-                drop(ctx);
-                report.report_text(result.to_string());
+                report.start_test(test.name());
+                let mut attempt = 1;
+                let (result, duration) = loop {
+                    let start = Instant::now();
+                    // owned here rather than by the context, which the tasks the test spawned
+                    // may still hold when it times out
+                    let test_runtime =
+                        aptos_runtimes::spawn_named_runtime("emitter".into(), Some(64));
+                    let network_ctx = NetworkContext::new(
+                        CoreContext::from_rng(&mut rng),
+                        swarm.clone(),
+                        report,
+                        self.global_duration,
+                        self.tests.emit_job_request.clone(),
+                        self.tests.success_criteria.clone(),
+                        self.tests.node_restart_timeout,
+                        test_runtime.handle().clone(),
+                    );
+                    let handle = test_runtime.handle().clone();
+                    let _handle_context = handle.enter();
+                    let network_ctx = NetworkContextSynchronizer::new(network_ctx, handle.clone());
+                    let timeout = test.timeout().or(self.tests.test_timeout);
+                    let mut timed_out = false;
+                    let result = run_test(|| {
+                        let result =
+                            handle.block_on(with_timeout(timeout, test.run(network_ctx.clone())));
+                        timed_out = matches!(&result, Err(e) if e.is::<TestTimedOut>());
+                        result
+                    });
+                    let duration = start.elapsed();
+                    // explicitly keep network context in scope so that the tokio Runtime drops after all the stuff has run.
+                    let NetworkContextSynchronizer { ctx, handle } = network_ctx;
+                    drop(handle);
+                    drop(ctx);
+                    if timed_out {
+                        // aborts the tasks the test spawned, along with the clones of the
+                        // context they hold, without waiting on those that block
+                        test_runtime.shutdown_timeout(TIMED_OUT_SHUTDOWN_TIMEOUT);
+                        // the faults the test injected would outlive it otherwise
+                        let removed = runtime
+                            .block_on(async { swarm.write().await.remove_all_chaos().await });
+                        if let Err(e) = removed {
+                            report.report_text(format!(
+                                "Failed to remove the chaos of {}: {:#}",
+                                test.name(),
+                                e
+                            ));
+                        }
+                    } else {
+                        drop(test_runtime);
+                    }
+                    report.report_text(result.to_string());
+                    let failed = matches!(result, TestResult::FailedWithMsg(..));
+                    // the namespace is usually gone by the time anyone looks into the failure
+                    let dir = self.options.artifacts_dir.join(match attempt {
+                        1 => test.name().replace("::", "-"),
+                        _ => format!("{}-attempt-{}", test.name().replace("::", "-"), attempt),
+                    });
+                    if failed {
+                        // whether one node or many were down when the test failed
+                        let health =
+                            runtime.block_on(async { swarm.read().await.health_check_all().await });
+                        report.report_text(format!(
+                            "Swarm health after {}: {}",
+                            test.name(),
+                            health
+                        ));
+                        if self.options.snapshot_dbs_on_failure {
+                            let mut options = DbSnapshotOptions::default();
+                            if let Some(max_size_bytes) = self.options.db_snapshot_max_bytes {
+                                options.max_size_bytes = max_size_bytes;
+                            }
+                            // one at a time, not to take down more validators than had failed
+                            for node in health.unhealthy_validators() {
+                                let snapshot = runtime.block_on(async {
+                                    swarm
+                                        .read()
+                                        .await
+                                        .snapshot_db(node.peer_id, &dir, &options)
+                                        .await
+                                });
+                                if let Ok(path) = &snapshot {
+                                    report.report_artifact(path);
+                                }
+                                report.report_text(match snapshot {
+                                    Ok(path) => {
+                                        format!(
+                                            "Snapshotted the DB of {} into {:?}",
+                                            node.name, path
+                                        )
+                                    },
+                                    Err(e) => {
+                                        format!(
+                                            "Failed to snapshot the DB of {}: {:#}",
+                                            node.name, e
+                                        )
+                                    },
+                                });
+                            }
+                        }
+                    }
+                    if failed || self.options.always_collect_artifacts {
+                        let manifest = runtime.block_on(async {
+                            swarm.read().await.collect_artifacts(&dir, None).await
+                        });
+                        match manifest {
+                            Ok(manifest) => {
+                                for file in manifest.nodes.iter().flat_map(|node| &node.files) {
+                                    report.report_artifact(dir.join(file));
+                                }
+                                report.report_text(format!(
+                                    "Collected {} artifacts of {} nodes into {:?}, timed out on {:?}",
+                                    manifest.file_count(),
+                                    manifest.nodes.len(),
+                                    dir,
+                                    manifest.timed_out()
+                                ))
+                            },
+                            Err(e) => report.report_text(format!(
+                                "Failed to collect the artifacts of {}: {:#}",
+                                test.name(),
+                                e
+                            )),
+                        }
+                    }
+                    // every attempt is reported, so that flaky infrastructure stays visible
+                    let category = result.category();
+                    if !retries_attempt(
+                        attempt,
+                        self.options.retry_infra_failures,
+                        category,
+                        self.factory.use_fresh_namespace(),
+                    ) {
+                        break (result, duration);
+                    }
+                    report.report_text(format!(
+                        "Attempt {} of {} failed because of the infrastructure, retrying it on a new \
+                         swarm",
+                        attempt,
+                        test.name()
+                    ));
+                    match self.launch_swarm(
+                        &runtime,
+                        &mut rng,
+                        &initial_version,
+                        &genesis_version,
+                        node_versions.as_ref(),
+                    ) {
+                        Ok(new_swarm) => {
+                            report.retry_test(result.failure(), category, duration);
+                            logs_location = new_swarm.logs_location();
+                            // tears the failed swarm down
+                            *runtime.block_on(swarm.write()) = new_swarm;
+                            attempt += 1;
+                        },
+                        Err(e) => {
+                            report.report_text(format!(
+                                "Failed to launch a new swarm to retry {} on: {:#}",
+                                test.name(),
+                                e
+                            ));
+                            break (result, duration);
+                        },
+                    }
+                };
+                report.finish_test(result.failure(), result.category(), duration);
+                junit.add(test.name(), duration, result.junit_outcome());
                 summary.handle_result(test.name().to_owned(), result)?;
             }
 
+            // whether or not the success criteria check them
+            let restarts = runtime.block_on(async { swarm.read().await.restarts().await });
+            let restart_lines = match &restarts {
+                Ok(restarts) => restarts
+                    .iter()
+                    .map(|restarts| restarts.to_string())
+                    .collect(),
+                Err(_) => vec![],
+            };
+            match restarts {
+                Ok(restarts) if restarts.is_empty() => {},
+                Ok(restarts) => {
+                    let lines: Vec<_> = restarts
+                        .iter()
+                        .map(|restarts| format!("  {}", restarts))
+                        .collect();
+                    report.report_text(format!("Restarts of the nodes:\n{}", lines.join("\n")));
+                },
+                Err(e) => report.report_text(format!(
+                    "Failed to count the restarts of the nodes: {:#}",
+                    e
+                )),
+            }
+            let infrastructure_events =
+                runtime.block_on(async { swarm.read().await.infrastructure_events() });
+            if !infrastructure_events.is_empty() {
+                let lines: Vec<_> = infrastructure_events
+                    .iter()
+                    .map(|event| format!("  {}", event))
+                    .collect();
+                report.report_text(format!("Infrastructure events:\n{}", lines.join("\n")));
+            }
+            report.report_swarm_events(
+                restart_lines,
+                infrastructure_events
+                    .iter()
+                    .map(|event| event.to_string())
+                    .collect(),
+            );
+            runtime.block_on(async {
+                let swarm = swarm.read().await;
+                for node in swarm.validators() {
+                    report.report_node_version(node.name().to_string(), node.version().to_string());
+                }
+                for node in swarm.full_nodes() {
+                    report.report_node_version(node.name().to_string(), node.version().to_string());
+                }
+            });
             report.print_report();
 
             io::stdout().flush()?;
@@ -623,12 +1257,52 @@ impl<'cfg, F: Factory> Forge<'cfg, F> {
         summary.write_summary()?;
 
         if summary.success() {
-            Ok(report)
+            Ok(())
         } else {
             bail!("Tests Failed")
         }
     }
 
+    fn launch_swarm(
+        &self,
+        runtime: &Runtime,
+        rng: &mut StdRng,
+        initial_version: &Version,
+        genesis_version: &Version,
+        node_versions: Option<&NodeVersions>,
+    ) -> Result<Box<dyn Swarm>> {
+        let swarm = runtime.block_on(self.factory.launch_swarm(
+            rng,
+            self.tests.initial_validator_count,
+            self.tests.initial_fullnode_count,
+            initial_version,
+            genesis_version,
+            self.tests.genesis_config.as_ref(),
+            self.global_duration + Duration::from_secs(NAMESPACE_CLEANUP_DURATION_BUFFER_SECS),
+            self.tests.build_genesis_helm_config_fn(),
+            self.tests.build_node_helm_config_fn(),
+            self.tests.existing_db_tag.clone(),
+            node_versions,
+        ));
+        // retrying won't help if we are not allowed to access the cluster
+        let mut swarm = swarm.map_err(|e| {
+            if K8sError::is_forbidden(&e) {
+                e.context("Kubernetes denied access to the cluster, check your kube credentials")
+            } else {
+                e
+            }
+        })?;
+        if self.tests.fullnodes_per_validator > 1 {
+            let fullnode_version =
+                node_versions.map_or(initial_version, |node_versions| &node_versions.fullnodes);
+            runtime.block_on(swarm.ensure_fullnodes_per_validator(
+                self.tests.fullnodes_per_validator,
+                fullnode_version,
+            ))?;
+        }
+        Ok(swarm)
+    }
+
     fn filter_tests<'a, T: Test + ?Sized>(
         &'a self,
         tests: &'a [Box<T>],
@@ -655,23 +1329,103 @@ impl<'cfg, F: Factory> Forge<'cfg, F> {
                     true
                 }
             })
+            .filter(move |test| {
+                self.options
+                    .filter_regex
+                    .as_ref()
+                    .map_or(true, |regex| regex.is_match(test.name()))
+            })
+            // Filter by tags, any of the --tag options
+            .filter(move |test| {
+                self.options.tags.is_empty()
+                    || self
+                        .options
+                        .tags
+                        .iter()
+                        .any(|query| query.matches(test.tags()))
+            })
     }
 }
 
+/// Whether the test is one of the selected, by reference, as tests may share their names
+fn is_selected<T>(selected: &[&T], test: &T) -> bool {
+    selected
+        .iter()
+        .any(|selected| std::ptr::eq(*selected, test))
+}
+
 enum TestResult {
     Ok,
-    FailedWithMsg(String),
+    FailedWithMsg(String, FailureCategory),
+}
+
+impl TestResult {
+    fn failure(&self) -> Option<String> {
+        match self {
+            TestResult::Ok => None,
+            TestResult::FailedWithMsg(msg, _) => Some(msg.clone()),
+        }
+    }
+
+    fn category(&self) -> Option<FailureCategory> {
+        match self {
+            TestResult::Ok => None,
+            TestResult::FailedWithMsg(_, category) => Some(*category),
+        }
+    }
+
+    fn junit_outcome(&self) -> JunitOutcome {
+        match self {
+            TestResult::Ok => JunitOutcome::Passed,
+            TestResult::FailedWithMsg(msg, _) => JunitOutcome::Failed(msg.clone()),
+        }
+    }
 }
 
 impl Display for TestResult {
     fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
         match self {
             TestResult::Ok => write!(f, "Test Ok"),
-            TestResult::FailedWithMsg(msg) => write!(f, "Test Failed: {}", msg),
+            TestResult::FailedWithMsg(msg, category) => {
+                write!(f, "Test Failed, {} failure: {}", category, msg)
+            },
         }
     }
 }
 
+// how long the tasks of a timed out test get to stop
+const TIMED_OUT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, thiserror::Error)]
+#[error("Test timed out after {0:?}")]
+struct TestTimedOut(Duration);
+
+/// Fails the test if it runs for longer than the timeout, if any, as soon as the test yields, see
+/// [ForgeConfig::with_test_timeout]
+async fn with_timeout<F: Future<Output = Result<()>>>(
+    timeout: Option<Duration>,
+    test: F,
+) -> Result<()> {
+    match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, test)
+            .await
+            .unwrap_or_else(|_| Err(TestTimedOut(timeout).into())),
+        None => test.await,
+    }
+}
+
+/// Whether a failed attempt of a network test is retried on a new swarm. Only infra failures are,
+/// up to `retry_infra_failures` times, and only when the factory launches every swarm in a fresh
+/// namespace.
+fn retries_attempt(
+    attempt: usize,
+    retry_infra_failures: usize,
+    category: Option<FailureCategory>,
+    fresh_namespace: bool,
+) -> bool {
+    attempt <= retry_infra_failures && category == Some(FailureCategory::Infra) && fresh_namespace
+}
+
 fn run_test<F: FnOnce() -> Result<()>>(f: F) -> TestResult {
     match f() {
         Ok(()) => TestResult::Ok,
@@ -682,7 +1436,7 @@ fn run_test<F: FnOnce() -> Result<()>>(f: F) -> TestResult {
                 // ::error:: is github specific syntax to set an error on the job that is highlighted as described here https://docs.github.com/en/actions/using-workflows/workflow-commands-for-github-actions#setting-an-error-message
                 println!("::error::{:?}", e);
             }
-            TestResult::FailedWithMsg(format!("{:?}", e))
+            TestResult::FailedWithMsg(format!("{:?}", e), FailureCategory::of(&e))
         },
     }
 }
@@ -713,7 +1467,7 @@ impl TestSummary {
                 self.passed += 1;
                 self.write_ok()?;
             },
-            TestResult::FailedWithMsg(msg) => {
+            TestResult::FailedWithMsg(msg, _) => {
                 self.failed.push(name);
                 self.write_failed()?;
                 writeln!(self.stdout)?;
@@ -788,6 +1542,84 @@ impl TestSummary {
 mod test {
     use super::*;
 
+    #[tokio::test]
+    async fn test_with_timeout() {
+        assert!(with_timeout(None, async { Ok(()) }).await.is_ok());
+        let hung = with_timeout(Some(Duration::from_millis(10)), async {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            Ok(())
+        })
+        .await
+        .unwrap_err();
+        assert!(hung.is::<TestTimedOut>());
+        assert_eq!(hung.to_string(), "Test timed out after 10ms");
+        // the errors of the test are its own
+        let failed = with_timeout(Some(Duration::from_secs(60)), async {
+            Err(format_err!("liveness"))
+        })
+        .await
+        .unwrap_err();
+        assert!(!failed.is::<TestTimedOut>());
+    }
+
+    struct Tagged(&'static [&'static str]);
+
+    impl Test for Tagged {
+        fn name(&self) -> &'static str {
+            "tagged"
+        }
+
+        fn tags(&self) -> &'static [&'static str] {
+            self.0
+        }
+    }
+
+    #[test]
+    fn test_is_selected() {
+        // named the same, but only one of them is selected
+        let tests: Vec<Box<dyn Test>> = vec![Box::new(Tagged(&["perf"])), Box::new(Tagged(&[]))];
+        let query = TagQuery::any(&["perf"]);
+        let selected: Vec<_> = tests
+            .iter()
+            .filter(|test| query.matches(test.tags()))
+            .collect();
+        assert!(is_selected(&selected, &tests[0]));
+        assert!(!is_selected(&selected, &tests[1]));
+    }
+
+    #[test]
+    fn test_retries_attempt() {
+        let infra = run_test(|| {
+            let unavailable = kube::Error::Api(kube::error::ErrorResponse {
+                status: "Failure".to_string(),
+                message: "etcdserver: request timed out".to_string(),
+                reason: "".to_string(),
+                code: 503,
+            });
+            Err(K8sError::from_kube("pod aptos-node-0-validator-0", unavailable).into())
+        });
+        assert_eq!(infra.category(), Some(FailureCategory::Infra));
+        assert!(retries_attempt(1, 2, infra.category(), true));
+        assert!(retries_attempt(2, 2, infra.category(), true));
+        assert!(!retries_attempt(3, 2, infra.category(), true));
+        // a reused namespace can't be started over in
+        assert!(!retries_attempt(1, 2, infra.category(), false));
+
+        // a test failure is never retried, not even when it timed out waiting on the chain
+        let failed = run_test(|| {
+            Err(K8sError::Timeout {
+                operation: "the validator set to have 5 validators".to_string(),
+                message: "it has 4".to_string(),
+            }
+            .into())
+        });
+        assert_eq!(failed.category(), Some(FailureCategory::Test));
+        for attempt in 1..=3 {
+            assert!(!retries_attempt(attempt, 3, failed.category(), true));
+        }
+        assert!(!retries_attempt(1, 3, TestResult::Ok.category(), true));
+    }
+
     #[test]
     fn test_forge_runner_mode_from_env() {
         // HACK we really should not be setting env variables in test
@@ -839,6 +1671,46 @@ mod test {
             "Invalid runner mode: durian"
         );
     }
+
+    #[test]
+    fn test_genesis_overrides() {
+        let overrides = GenesisOverrides {
+            epoch_duration_secs: Some(60),
+            min_stake: Some(1_000_000),
+            enabled_features: vec![FeatureFlag::RECONFIGURE_WITH_DKG],
+            ..GenesisOverrides::default()
+        };
+        let config = ForgeConfig::default()
+            .with_genesis_overrides(overrides.clone())
+            .with_genesis_helm_config_fn(Arc::new(|helm_values| {
+                helm_values["chain"]["epoch_duration_secs"] = 120.into();
+            }));
+        let mut helm_values = serde_yaml::Value::default();
+        (config.build_genesis_helm_config_fn().unwrap())(&mut helm_values);
+
+        // the genesis helm values function has the last word
+        assert_eq!(
+            helm_values["chain"]["epoch_duration_secs"],
+            serde_yaml::Value::from(120)
+        );
+        assert_eq!(
+            helm_values["chain"]["min_stake"],
+            serde_yaml::Value::from(1_000_000)
+        );
+        assert!(helm_values["chain"]["max_stake"].is_null());
+        let features: Features =
+            serde_yaml::from_value(helm_values["chain"]["initial_features_override"].clone())
+                .unwrap();
+        assert!(features.is_enabled(FeatureFlag::RECONFIGURE_WITH_DKG));
+        assert!(features.is_enabled(FeatureFlag::APTOS_STD_CHAIN_ID_NATIVES));
+        assert_eq!(
+            overrides.to_string(),
+            "epoch duration 60s, min stake 1000000, enabled features [RECONFIGURE_WITH_DKG]"
+        );
+        assert!(ForgeConfig::default()
+            .build_genesis_helm_config_fn()
+            .is_none());
+    }
 }
 
 #[test]
@@ -4,16 +4,20 @@
 use crate::{
     prometheus_metrics::{
         fetch_error_metrics, fetch_system_metrics, LatencyBreakdown, LatencyBreakdownSlice,
-        SystemMetrics,
+        PrometheusUnavailable, SystemMetrics,
     },
-    Swarm, SwarmExt, TestReport,
+    MemoryTimeline, Swarm, SwarmExt, TestReport,
 };
 use anyhow::{bail, Context};
 use movement::node::analyze::fetch_metadata::FetchMetadata;
 use aptos_sdk::types::PeerId;
 use aptos_transaction_emitter_lib::{TxnStats, TxnStatsRate};
 use prometheus_http_query::response::Sample;
-use std::{collections::BTreeMap, sync::Arc, time::Duration};
+use std::{
+    collections::BTreeMap,
+    sync::Arc,
+    time::{Duration, UNIX_EPOCH},
+};
 
 #[derive(Clone, Debug)]
 pub struct StateProgressThreshold {
@@ -170,6 +174,11 @@ pub struct SuccessCriteria {
     // Maximum amount of CPU cores and memory bytes used by the nodes.
     system_metrics_threshold: Option<SystemMetricsThreshold>,
     chain_progress_check: Option<StateProgressThreshold>,
+    // Maximum % any validator's resident memory may grow from the end of the warmup on.
+    max_memory_growth_pct: Option<f64>,
+    // Minimum TPS the validators committed, as Prometheus measured it, unlike min_avg_tps which
+    // is what the emitter saw committed.
+    min_committed_tps: Option<usize>,
 }
 
 impl SuccessCriteria {
@@ -185,6 +194,8 @@ impl SuccessCriteria {
             wait_for_all_nodes_to_catchup: None,
             system_metrics_threshold: None,
             chain_progress_check: None,
+            max_memory_growth_pct: None,
+            min_committed_tps: None,
         }
     }
 
@@ -223,6 +234,20 @@ impl SuccessCriteria {
         self
     }
 
+    pub fn add_max_memory_growth(mut self, max_growth_pct: f64) -> Self {
+        self.max_memory_growth_pct = Some(max_growth_pct);
+        self
+    }
+
+    pub fn add_min_committed_tps(mut self, min_tps: usize) -> Self {
+        self.min_committed_tps = Some(min_tps);
+        self
+    }
+
+    pub fn max_memory_growth_pct(&self) -> Option<f64> {
+        self.max_memory_growth_pct
+    }
+
     pub fn add_latency_threshold(mut self, threshold_s: f32, latency_type: LatencyType) -> Self {
         self.latency_thresholds
             .push((Duration::from_secs_f32(threshold_s), latency_type));
@@ -278,6 +303,7 @@ impl SuccessCriteriaChecker {
         end_time: i64,
         start_version: u64,
         end_version: u64,
+        memory_timeline: Option<&MemoryTimeline>,
     ) -> anyhow::Result<()> {
         println!(
             "End to end duration: {}s, performance measured for: {}s",
@@ -316,15 +342,25 @@ impl SuccessCriteriaChecker {
         }
 
         if success_criteria.check_no_restarts {
-            let swarm_read = swarm.read().await;
-            swarm_read
-                .ensure_no_validator_restart()
+            // the restarts the test did itself are expected
+            let unexpected_restarts = swarm
+                .read()
                 .await
-                .context("Failed ensuring no validator restarted")?;
-            swarm_read
-                .ensure_no_fullnode_restart()
+                .unexpected_restarts()
                 .await
-                .context("Failed ensuring no fullnode restarted")?;
+                .context("Failed to count the restarts of the nodes")?;
+            if !unexpected_restarts.is_empty() {
+                let nodes: Vec<_> = unexpected_restarts
+                    .iter()
+                    .map(|(name, restarts, reason)| {
+                        format!(
+                            "{} restarted {} times, last with {}",
+                            name, restarts, reason
+                        )
+                    })
+                    .collect();
+                bail!("Nodes restarted unexpectedly: {}", nodes.join("; "));
+            }
         }
 
         if success_criteria.check_no_errors {
@@ -353,6 +389,25 @@ impl SuccessCriteriaChecker {
             .context("Failed check chain progress")?;
         }
 
+        if let Some(min_tps) = success_criteria.min_committed_tps {
+            Self::check_committed_tps(
+                swarm.clone(),
+                min_tps,
+                window,
+                end_time,
+                start_version,
+                end_version,
+            )
+            .await?;
+        }
+
+        if let Some(max_growth_pct) = success_criteria.max_memory_growth_pct {
+            memory_timeline
+                .context("No memory samples were collected to check the memory growth")?
+                .check_max_growth(max_growth_pct)
+                .context("Failed check memory growth")?;
+        }
+
         Ok(())
     }
 
@@ -592,6 +647,35 @@ impl SuccessCriteriaChecker {
         }
     }
 
+    /// Checks the TPS Prometheus measured over the window the test ended with, or without a
+    /// Prometheus, the TPS of the ledger versions the test started and ended at
+    async fn check_committed_tps(
+        swarm: Arc<tokio::sync::RwLock<Box<dyn Swarm>>>,
+        min_tps: usize,
+        window: Duration,
+        end_time: i64,
+        start_version: u64,
+        end_version: u64,
+    ) -> anyhow::Result<()> {
+        let end = UNIX_EPOCH + Duration::from_secs(end_time.max(0) as u64);
+        let tps = match swarm.read().await.avg_tps_over(window, end).await {
+            Ok(tps) => tps,
+            Err(e) if e.is::<PrometheusUnavailable>() => {
+                end_version.saturating_sub(start_version) as f64 / window.as_secs_f64().max(1.0)
+            },
+            Err(e) => return Err(e.context("Failed to query the committed TPS")),
+        };
+        if tps < min_tps as f64 {
+            bail!(
+                "Committed TPS of {:.1} is below the minimum of {}",
+                tps,
+                min_tps
+            );
+        }
+        println!("Committed TPS of {:.1} is above {}", tps, min_tps);
+        Ok(())
+    }
+
     async fn check_system_metrics(
         swarm: Arc<tokio::sync::RwLock<Box<dyn Swarm>>>,
         start_time: i64,
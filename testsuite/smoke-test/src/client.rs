@@ -10,8 +10,8 @@ use crate::{
     },
 };
 use aptos_cached_packages::aptos_stdlib;
-use aptos_forge::{NodeExt, Swarm};
-use std::time::{Duration, Instant};
+use aptos_forge::{Node, NodeExt, Swarm};
+use std::time::Duration;
 
 #[tokio::test]
 async fn test_create_mint_transfer_block_metadata() {
@@ -64,9 +64,8 @@ async fn test_basic_restartability() {
     assert_balance(&client, &account_1, 20).await;
 
     let validator = swarm.validators_mut().next().unwrap();
-    validator.restart().await.unwrap();
     validator
-        .wait_until_healthy(Instant::now() + Duration::from_secs(MAX_HEALTHY_WAIT_SECS))
+        .restart(Duration::from_secs(MAX_HEALTHY_WAIT_SECS))
         .await
         .unwrap();
 
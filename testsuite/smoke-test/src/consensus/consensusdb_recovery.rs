@@ -6,7 +6,7 @@ use crate::{
     utils::{assert_balance, create_and_fund_account, transfer_coins, MAX_HEALTHY_WAIT_SECS},
 };
 use aptos_consensus::CONSENSUS_DB_NAME;
-use aptos_forge::{HealthCheckError, NodeExt, Swarm};
+use aptos_forge::{HealthCheckError, Node, NodeExt, Swarm};
 use std::{
     fs,
     time::{Duration, Instant},
@@ -56,8 +56,7 @@ async fn test_consensusdb_recovery() {
         }
     }
 
-    node.restart().await.unwrap();
-    node.wait_until_healthy(Instant::now() + Duration::from_secs(MAX_HEALTHY_WAIT_SECS))
+    node.restart(Duration::from_secs(MAX_HEALTHY_WAIT_SECS))
         .await
         .unwrap();
 
@@ -6,3 +6,4 @@ mod consensus_only;
 mod consensusdb_recovery;
 mod dag;
 mod quorum_store_fault_tolerance;
+mod reconfiguration;
@@ -2,14 +2,15 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::{
-    aptos_cli::validator::generate_blob, smoke_test_environment::SwarmBuilder,
+    aptos_cli::validator::generate_blob,
+    smoke_test_environment::SwarmBuilder,
     txn_emitter::generate_traffic,
+    utils::{execute_governance_script, MAX_HEALTHY_WAIT_SECS},
 };
-use movement::test::CliTestFramework;
 use aptos_consensus::QUORUM_STORE_DB_NAME;
 use aptos_forge::{
-    args::TransactionTypeArg, reconfig, wait_for_all_nodes_to_catchup, NodeExt, Swarm, SwarmExt,
-    TransactionType,
+    args::TransactionTypeArg, reconfig, wait_for_all_nodes_to_catchup, LocalSwarm, Node, NodeExt,
+    Swarm, SwarmExt, TransactionType,
 };
 use aptos_logger::info;
 use aptos_rest_client::Client;
@@ -60,18 +61,14 @@ async fn generate_traffic_and_assert_committed(
     assert!(txn_stat.committed > 30);
 }
 
-async fn update_consensus_config(
-    cli: &CliTestFramework,
-    root_cli_index: usize,
-    new_consensus_config: OnChainConsensusConfig,
-) {
+async fn update_consensus_config(swarm: &LocalSwarm, new_consensus_config: OnChainConsensusConfig) {
     let update_consensus_config_script = format!(
         r#"
     script {{
         use aptos_framework::aptos_governance;
         use aptos_framework::consensus_config;
-        fun main(core_resources: &signer) {{
-            let framework_signer = aptos_governance::get_signer_testnet_only(core_resources, @0000000000000000000000000000000000000000000000000000000000000001);
+        fun main(proposal_id: u64) {{
+            let framework_signer = aptos_governance::resolve(proposal_id, @0000000000000000000000000000000000000000000000000000000000000001);
             let config_bytes = {};
             consensus_config::set_for_next_epoch(&framework_signer, config_bytes);
             aptos_governance::force_end_epoch(&framework_signer);
@@ -80,22 +77,22 @@ async fn update_consensus_config(
     "#,
         generate_blob(&bcs::to_bytes(&new_consensus_config).unwrap())
     );
-    cli.run_script(root_cli_index, &update_consensus_config_script)
-        .await
-        .unwrap();
+    execute_governance_script(swarm, &update_consensus_config_script).await;
 }
 
 // TODO: remove when quorum store becomes the in-code default
 #[tokio::test]
 async fn test_onchain_config_quorum_store_enabled_and_disabled() {
-    let (mut swarm, mut cli, _faucet) = SwarmBuilder::new_local(4)
+    let mut swarm = SwarmBuilder::new_local(4)
         .with_aptos()
         // Start with V1
         .with_init_genesis_config(Arc::new(|genesis_config| {
             genesis_config.consensus_config =
-                OnChainConsensusConfig::V1(ConsensusConfigV1::default())
+                OnChainConsensusConfig::V1(ConsensusConfigV1::default());
+            // for the governance proposals to pass quickly
+            genesis_config.voting_duration_secs = 5;
         }))
-        .build_with_cli(0)
+        .build()
         .await;
     let validator_peer_ids = swarm.validators().map(|v| v.peer_id()).collect::<Vec<_>>();
 
@@ -108,10 +105,6 @@ async fn test_onchain_config_quorum_store_enabled_and_disabled() {
         .unwrap();
 
     for _ in 0..5 {
-        let root_cli_index = cli.add_account_with_address_to_cli(
-            swarm.root_key(),
-            swarm.chain_info().root_account().address(),
-        );
         let rest_client = swarm.validators().next().unwrap().rest_client();
 
         let current_consensus_config =
@@ -123,7 +116,7 @@ async fn test_onchain_config_quorum_store_enabled_and_disabled() {
         };
         // Change to V2
         let new_consensus_config = OnChainConsensusConfig::V2(ConsensusConfigV1 { ..inner });
-        update_consensus_config(&cli, root_cli_index, new_consensus_config).await;
+        update_consensus_config(&swarm, new_consensus_config).await;
 
         generate_traffic_and_assert_committed(
             &mut swarm,
@@ -147,7 +140,7 @@ async fn test_onchain_config_quorum_store_enabled_and_disabled() {
 
         // Disaster rollback to V1
         let new_consensus_config = OnChainConsensusConfig::V1(ConsensusConfigV1 { ..inner });
-        update_consensus_config(&cli, root_cli_index, new_consensus_config).await;
+        update_consensus_config(&swarm, new_consensus_config).await;
 
         generate_traffic_and_assert_committed(
             &mut swarm,
@@ -238,7 +231,7 @@ async fn test_batch_id_on_restart(do_wipe_db: bool) {
     swarm
         .validator_mut(node_to_restart)
         .unwrap()
-        .restart()
+        .restart(Duration::from_secs(MAX_HEALTHY_WAIT_SECS))
         .await
         .unwrap();
 
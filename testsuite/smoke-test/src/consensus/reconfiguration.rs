@@ -0,0 +1,28 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::smoke_test_environment::new_local_swarm_with_aptos;
+use aptos_forge::{NodeExt, Swarm};
+
+#[tokio::test]
+async fn test_trigger_reconfiguration() {
+    let swarm = new_local_swarm_with_aptos(4).await;
+    let chain_info = swarm.chain_info();
+
+    let epoch = chain_info.trigger_reconfiguration().await.unwrap();
+    // again right away, the root account's sequence number is resynced
+    assert_eq!(
+        chain_info.trigger_reconfiguration().await.unwrap(),
+        epoch + 1
+    );
+
+    for validator in swarm.validators() {
+        let ledger = validator
+            .rest_client()
+            .get_ledger_information()
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(ledger.epoch > epoch);
+    }
+}
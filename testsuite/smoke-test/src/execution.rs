@@ -3,11 +3,11 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::{
-    aptos_cli::validator::generate_blob, smoke_test_environment::SwarmBuilder,
-    utils::get_current_version,
+    aptos_cli::validator::generate_blob,
+    smoke_test_environment::SwarmBuilder,
+    utils::{execute_governance_script, get_current_version},
 };
-use movement::test::CliTestFramework;
-use aptos_forge::{NodeExt, Swarm, SwarmExt};
+use aptos_forge::{LocalSwarm, NodeExt, Swarm, SwarmExt};
 use aptos_rest_client::Client;
 use aptos_types::on_chain_config::{
     BlockGasLimitType, ExecutionConfigV4, OnChainExecutionConfig, TransactionDeduperType,
@@ -54,18 +54,14 @@ async fn fallback_test() {
     }
 }
 
-async fn update_execution_config(
-    cli: &CliTestFramework,
-    root_cli_index: usize,
-    new_execution_config: OnChainExecutionConfig,
-) {
+async fn update_execution_config(swarm: &LocalSwarm, new_execution_config: OnChainExecutionConfig) {
     let update_execution_config_script = format!(
         r#"
     script {{
         use aptos_framework::aptos_governance;
         use aptos_framework::execution_config;
-        fun main(core_resources: &signer) {{
-            let framework_signer = aptos_governance::get_signer_testnet_only(core_resources, @0000000000000000000000000000000000000000000000000000000000000001);
+        fun main(proposal_id: u64) {{
+            let framework_signer = aptos_governance::resolve(proposal_id, @0000000000000000000000000000000000000000000000000000000000000001);
             let config_bytes = {};
             execution_config::set_for_next_epoch(&framework_signer, config_bytes);
             aptos_governance::force_end_epoch(&framework_signer);
@@ -74,9 +70,7 @@ async fn update_execution_config(
     "#,
         generate_blob(&bcs::to_bytes(&new_execution_config).unwrap())
     );
-    cli.run_script(root_cli_index, &update_execution_config_script)
-        .await
-        .unwrap();
+    execute_governance_script(swarm, &update_execution_config_script).await;
 }
 
 async fn get_last_non_reconfig_block_ending_txn_name(rest_client: &Client) -> Option<&'static str> {
@@ -96,17 +90,19 @@ async fn get_last_non_reconfig_block_ending_txn_name(rest_client: &Client) -> Op
 
 #[tokio::test]
 async fn block_epilogue_upgrade_test() {
-    let (swarm, mut cli, _faucet) = SwarmBuilder::new_local(2)
+    let swarm = SwarmBuilder::new_local(2)
         .with_aptos()
         // Start with V1
         .with_init_genesis_config(Arc::new(|genesis_config| {
+            // for the governance proposals to pass quickly
+            genesis_config.voting_duration_secs = 5;
             genesis_config.execution_config = OnChainExecutionConfig::V4(ExecutionConfigV4 {
                 transaction_shuffler_type: TransactionShufflerType::NoShuffling,
                 block_gas_limit_type: BlockGasLimitType::NoLimit,
                 transaction_deduper_type: TransactionDeduperType::TxnHashAndAuthenticatorV1,
             });
         }))
-        .build_with_cli(0)
+        .build()
         .await;
 
     swarm
@@ -122,11 +118,6 @@ async fn block_epilogue_upgrade_test() {
     );
 
     for _ in 0..3 {
-        let root_cli_index = cli.add_account_with_address_to_cli(
-            swarm.root_key(),
-            swarm.chain_info().root_account().address(),
-        );
-
         let current_execution_config =
             crate::utils::get_current_execution_config(&rest_client).await;
         match current_execution_config {
@@ -150,7 +141,7 @@ async fn block_epilogue_upgrade_test() {
             block_gas_limit_type: block_gas_limit,
             transaction_deduper_type: TransactionDeduperType::TxnHashAndAuthenticatorV1,
         });
-        update_execution_config(&cli, root_cli_index, new_execution_config).await;
+        update_execution_config(&swarm, new_execution_config).await;
 
         swarm
             .wait_for_all_nodes_to_catchup_to_future(Duration::from_secs(MAX_WAIT_SECS), 8)
@@ -176,7 +167,7 @@ async fn block_epilogue_upgrade_test() {
             block_gas_limit_type: BlockGasLimitType::NoLimit,
             transaction_deduper_type: TransactionDeduperType::TxnHashAndAuthenticatorV1,
         });
-        update_execution_config(&cli, root_cli_index, new_execution_config).await;
+        update_execution_config(&swarm, new_execution_config).await;
 
         swarm
             .wait_for_all_nodes_to_catchup_to_future(Duration::from_secs(MAX_WAIT_SECS), 8)
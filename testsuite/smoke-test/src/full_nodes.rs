@@ -228,7 +228,7 @@ async fn test_private_full_node() {
     // And connect the user to the private swarm
     add_node_to_seeds(
         &mut user_config,
-        swarm.full_node(private).unwrap().config(),
+        &swarm.full_node(private).unwrap().config().await.unwrap(),
         NetworkId::Public,
         PeerRole::PreferredUpstream,
     );
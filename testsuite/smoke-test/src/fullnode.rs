@@ -147,7 +147,7 @@ async fn test_internal_indexer_with_fast_sync() {
         .await
         .unwrap();
     let node = swarm.full_node(peer_id).unwrap();
-    let node_config = node.config().to_owned();
+    let node_config = node.config().await.unwrap();
     node.stop().await.unwrap();
     check_indexer_db(&node_config);
 }
@@ -29,19 +29,17 @@ use tokio::time::sleep;
 async fn jwk_consensus_per_issuer() {
     let epoch_duration_secs = 30;
 
-    let (swarm, mut cli, _faucet) = SwarmBuilder::new_local(4)
+    let swarm = SwarmBuilder::new_local(4)
         .with_num_fullnodes(1)
         .with_aptos()
         .with_init_genesis_config(Arc::new(move |conf| {
             conf.epoch_duration_secs = epoch_duration_secs;
+            // for the governance proposal to pass quickly
+            conf.voting_duration_secs = 5;
         }))
-        .build_with_cli(0)
+        .build()
         .await;
     let client = swarm.validators().next().unwrap().rest_client();
-    let root_idx = cli.add_account_with_address_to_cli(
-        swarm.root_key(),
-        swarm.chain_info().root_account().address(),
-    );
     swarm
         .wait_for_all_nodes_to_catchup_to_epoch(2, Duration::from_secs(epoch_duration_secs * 2))
         .await
@@ -101,8 +99,8 @@ async fn jwk_consensus_per_issuer() {
         ],
     });
 
-    let txn_summary = update_jwk_consensus_config(cli, root_idx, &config).await;
-    debug!("txn_summary={:?}", txn_summary);
+    let outcome = update_jwk_consensus_config(&swarm, &config).await;
+    debug!("outcome={:?}", outcome);
 
     info!("Wait for 60 secs and there should only update for Bob, not Alice.");
     sleep(Duration::from_secs(60)).await;
@@ -31,19 +31,17 @@ async fn jwk_consensus_provider_change_mind() {
     // Big epoch duration to ensure epoch change does not help reset validators if they are stuck.
     let epoch_duration_secs = 1800;
 
-    let (swarm, mut cli, _faucet) = SwarmBuilder::new_local(4)
+    let swarm = SwarmBuilder::new_local(4)
         .with_num_fullnodes(1)
         .with_aptos()
         .with_init_genesis_config(Arc::new(move |conf| {
             conf.epoch_duration_secs = epoch_duration_secs;
+            // for the governance proposal to pass quickly
+            conf.voting_duration_secs = 5;
         }))
-        .build_with_cli(0)
+        .build()
         .await;
     let client = swarm.validators().next().unwrap().rest_client();
-    let root_idx = cli.add_account_with_address_to_cli(
-        swarm.root_key(),
-        swarm.chain_info().root_account().address(),
-    );
     swarm
         .wait_for_all_nodes_to_catchup_to_epoch(2, Duration::from_secs(epoch_duration_secs * 2))
         .await
@@ -102,8 +100,8 @@ async fn jwk_consensus_provider_change_mind() {
         ],
     });
 
-    let txn_summary = update_jwk_consensus_config(cli, root_idx, &config).await;
-    debug!("txn_summary={:?}", txn_summary);
+    let outcome = update_jwk_consensus_config(&swarm, &config).await;
+    debug!("outcome={:?}", outcome);
 
     info!("Waiting for an on-chain update. 30 secs should be enough.");
     sleep(Duration::from_secs(30)).await;
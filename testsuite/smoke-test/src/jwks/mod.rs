@@ -6,9 +6,8 @@ mod jwk_consensus_basic;
 mod jwk_consensus_per_issuer;
 mod jwk_consensus_provider_change_mind;
 
-use crate::smoke_test_environment::SwarmBuilder;
-use movement::{common::types::TransactionSummary, test::CliTestFramework};
-use aptos_forge::{NodeExt, Swarm, SwarmExt};
+use crate::{smoke_test_environment::SwarmBuilder, utils::execute_governance_script};
+use aptos_forge::{LocalSwarm, NodeExt, ProposalOutcome, Swarm, SwarmExt};
 use aptos_logger::{debug, info};
 use aptos_rest_client::Client;
 use aptos_types::{
@@ -20,20 +19,19 @@ use aptos_types::{
     on_chain_config::OnChainJWKConsensusConfig,
 };
 use move_core_types::account_address::AccountAddress;
-use std::time::Duration;
+use std::{sync::Arc, time::Duration};
 
 pub async fn update_jwk_consensus_config(
-    cli: CliTestFramework,
-    account_idx: usize,
+    swarm: &LocalSwarm,
     config: &OnChainJWKConsensusConfig,
-) -> TransactionSummary {
+) -> ProposalOutcome {
     let script = match config {
         OnChainJWKConsensusConfig::Off => r#"
 script {
     use aptos_framework::aptos_governance;
     use aptos_framework::jwk_consensus_config;
-    fun main(core_resources: &signer) {
-        let framework = aptos_governance::get_signer_testnet_only(core_resources, @0x1);
+    fun main(proposal_id: u64) {
+        let framework = aptos_governance::resolve(proposal_id, @0x1);
         let config = jwk_consensus_config::new_off();
         jwk_consensus_config::set_for_next_epoch(&framework, config);
         aptos_governance::reconfigure(&framework);
@@ -60,8 +58,8 @@ script {{
     use aptos_framework::jwk_consensus_config;
     use std::string::utf8;
 
-    fun main(core_resources: &signer) {{
-        let framework = aptos_governance::get_signer_testnet_only(core_resources, @0x1);
+    fun main(proposal_id: u64) {{
+        let framework = aptos_governance::resolve(proposal_id, @0x1);
         let config = jwk_consensus_config::new_v1(vector[
             {provider_lines}
         ]);
@@ -75,7 +73,7 @@ script {{
     };
     println!("script={script}");
 
-    cli.run_script(account_idx, script.as_str()).await.unwrap()
+    execute_governance_script(swarm, &script).await
 }
 
 async fn get_patched_jwks(rest_client: &Client) -> PatchedJWKs {
@@ -89,15 +87,15 @@ async fn get_patched_jwks(rest_client: &Client) -> PatchedJWKs {
 /// Patch the JWK with governance proposal and see it is effective.
 #[tokio::test]
 async fn jwk_patching() {
-    let (swarm, mut cli, _faucet) = SwarmBuilder::new_local(4)
+    let swarm = SwarmBuilder::new_local(4)
         .with_aptos()
-        .build_with_cli(0)
+        .with_init_genesis_config(Arc::new(|conf| {
+            // for the governance proposal to pass quickly
+            conf.voting_duration_secs = 5;
+        }))
+        .build()
         .await;
     let client = swarm.validators().next().unwrap().rest_client();
-    let root_idx = cli.add_account_with_address_to_cli(
-        swarm.root_key(),
-        swarm.chain_info().root_account().address(),
-    );
     swarm
         .wait_for_all_nodes_to_catchup_to_epoch(2, Duration::from_secs(60))
         .await
@@ -108,8 +106,8 @@ async fn jwk_patching() {
 script {
     use aptos_framework::jwks;
     use aptos_framework::aptos_governance;
-    fun main(core_resources: &signer) {
-        let framework_signer = aptos_governance::get_signer_testnet_only(core_resources, @0000000000000000000000000000000000000000000000000000000000000001);
+    fun main(proposal_id: u64) {
+        let framework_signer = aptos_governance::resolve(proposal_id, @0000000000000000000000000000000000000000000000000000000000000001);
         let alice_jwk_0 = jwks::new_unsupported_jwk(b"alice_jwk_id_0", b"alice_jwk_payload_0");
         let patches = vector[
             jwks::new_patch_remove_all(),
@@ -120,8 +118,8 @@ script {
 }
 "#;
 
-    let txn_summary = cli.run_script(root_idx, jwk_patch_script).await.unwrap();
-    debug!("txn_summary={:?}", txn_summary);
+    let outcome = execute_governance_script(&swarm, jwk_patch_script).await;
+    debug!("outcome={:?}", outcome);
 
     info!("Use resource API to check the patch result.");
     let patched_jwks = get_patched_jwks(&client).await;
@@ -1,8 +1,7 @@
 // Copyright © Aptos Foundation
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::smoke_test_environment::SwarmBuilder;
-use movement::{common::types::GasOptions, test::CliTestFramework};
+use crate::{smoke_test_environment::SwarmBuilder, utils::execute_governance_script};
 use aptos_cached_packages::aptos_stdlib;
 use aptos_crypto::{
     ed25519::{Ed25519PrivateKey, Ed25519PublicKey},
@@ -40,7 +39,7 @@ use aptos_types::{
 use move_core_types::account_address::AccountAddress;
 use rand::thread_rng;
 use serde::de::DeserializeOwned;
-use std::{fmt::Debug, time::Duration};
+use std::{fmt::Debug, sync::Arc, time::Duration};
 // TODO(keyless): Test the override aud_val path
 
 #[tokio::test]
@@ -61,7 +60,7 @@ async fn test_keyless_oidc_txn_verifies() {
 
 #[tokio::test]
 async fn test_keyless_rotate_vk() {
-    let (tw_sk, config, jwk, swarm, mut cli, root_idx) = setup_local_net().await;
+    let (tw_sk, config, jwk, swarm) = setup_local_net().await;
     let mut info = swarm.aptos_public_info();
 
     let (old_sig, old_pk) = get_sample_groth16_sig_and_pk();
@@ -109,7 +108,7 @@ async fn test_keyless_rotate_vk() {
 
     info!("Rotating VK");
     let vk = get_upgraded_vk().into();
-    rotate_vk_by_governance(&mut cli, &mut info, &vk, root_idx).await;
+    rotate_vk_by_governance(&swarm, &info, &vk).await;
 
     let signed_txn =
         sign_transaction(&mut info, old_sig, old_pk, &jwk, &config, Some(&tw_sk), 2).await;
@@ -164,7 +163,7 @@ async fn test_keyless_secure_test_jwk_initialized_at_genesis() {
 
 #[tokio::test]
 async fn test_keyless_oidc_txn_with_bad_jwt_sig() {
-    let (tw_sk, config, jwk, swarm, _, _) = setup_local_net().await;
+    let (tw_sk, config, jwk, swarm) = setup_local_net().await;
     let (mut sig, pk) = get_sample_openid_sig_and_pk();
 
     match &mut sig.cert {
@@ -190,7 +189,7 @@ async fn test_keyless_oidc_txn_with_bad_jwt_sig() {
 
 #[tokio::test]
 async fn test_keyless_oidc_txn_with_expired_epk() {
-    let (tw_sk, config, jwk, swarm, _, _) = setup_local_net().await;
+    let (tw_sk, config, jwk, swarm) = setup_local_net().await;
     let (mut sig, pk) = get_sample_openid_sig_and_pk();
 
     sig.exp_date_secs = 1; // This should fail the verification since the expiration date is way in the past
@@ -244,12 +243,12 @@ async fn test_keyless_no_extra_field_groth16_verifies() {
 
 #[tokio::test]
 async fn test_keyless_no_training_wheels_groth16_verifies() {
-    let (_tw_sk, config, jwk, swarm, mut cli, root_idx) = setup_local_net().await;
+    let (_tw_sk, config, jwk, swarm) = setup_local_net().await;
     let (sig, pk) = get_sample_groth16_sig_and_pk();
 
     let mut info = swarm.aptos_public_info();
 
-    remove_training_wheels(&mut cli, &mut info, root_idx).await;
+    remove_training_wheels(&swarm, &info).await;
 
     let signed_txn =
         sign_transaction(&mut info, sig.clone(), pk.clone(), &jwk, &config, None, 1).await;
@@ -267,7 +266,7 @@ async fn test_keyless_no_training_wheels_groth16_verifies() {
 
 #[tokio::test]
 async fn test_keyless_groth16_with_mauled_proof() {
-    let (tw_sk, config, jwk, swarm, _, _) = setup_local_net().await;
+    let (tw_sk, config, jwk, swarm) = setup_local_net().await;
     let (sig, pk) = get_sample_groth16_sig_and_pk();
 
     let mut info = swarm.aptos_public_info();
@@ -287,7 +286,7 @@ async fn test_keyless_groth16_with_mauled_proof() {
 
 #[tokio::test]
 async fn test_keyless_groth16_with_bad_tw_signature() {
-    let (_tw_sk, config, jwk, swarm, _, _) = setup_local_net().await;
+    let (_tw_sk, config, jwk, swarm) = setup_local_net().await;
     let (sig, pk) = get_sample_groth16_sig_and_pk();
 
     let mut info = swarm.aptos_public_info();
@@ -438,7 +437,7 @@ async fn get_transaction(
     LocalSwarm,
     SignedTransaction,
 ) {
-    let (tw_sk, config, jwk, swarm, _, _) = setup_local_net().await;
+    let (tw_sk, config, jwk, swarm) = setup_local_net().await;
 
     let (sig, pk) = get_pk_and_sig_func();
 
@@ -457,37 +456,29 @@ async fn get_transaction(
     (sig, pk, swarm, signed_txn)
 }
 
-async fn setup_local_net() -> (
-    Ed25519PrivateKey,
-    Configuration,
-    RSA_JWK,
-    LocalSwarm,
-    CliTestFramework,
-    usize,
-) {
-    let (mut swarm, mut cli, _faucet) = SwarmBuilder::new_local(1)
+async fn setup_local_net() -> (Ed25519PrivateKey, Configuration, RSA_JWK, LocalSwarm) {
+    let swarm = SwarmBuilder::new_local(1)
         .with_aptos()
-        .build_with_cli(0)
+        .with_init_genesis_config(Arc::new(|conf| {
+            // for the governance proposals to pass quickly
+            conf.voting_duration_secs = 5;
+        }))
+        .build()
         .await;
 
-    let (tw_sk, config, jwk, root_idx) =
-        spawn_network_and_execute_gov_proposals(&mut swarm, &mut cli).await;
-    (tw_sk, config, jwk, swarm, cli, root_idx)
+    let (tw_sk, config, jwk) = spawn_network_and_execute_gov_proposals(&swarm).await;
+    (tw_sk, config, jwk, swarm)
 }
 
-async fn remove_training_wheels<'a>(
-    cli: &mut CliTestFramework,
-    info: &mut AptosPublicInfo,
-    root_idx: usize,
-) {
+async fn remove_training_wheels(swarm: &LocalSwarm, info: &AptosPublicInfo) {
     let script = format!(
         r#"
 script {{
 use aptos_framework::{};
 use aptos_framework::aptos_governance;
 use std::option;
-fun main(core_resources: &signer) {{
-    let framework_signer = aptos_governance::get_signer_testnet_only(core_resources, @0x1);
+fun main(proposal_id: u64) {{
+    let framework_signer = aptos_governance::resolve(proposal_id, @0x1);
     {}::update_training_wheels_for_next_epoch(&framework_signer, option::none());
     aptos_governance::force_end_epoch(&framework_signer);
 }}
@@ -495,19 +486,8 @@ fun main(core_resources: &signer) {{
 "#,
         KEYLESS_ACCOUNT_MODULE_NAME, KEYLESS_ACCOUNT_MODULE_NAME
     );
-    let gas_options = GasOptions {
-        gas_unit_price: Some(100),
-        max_gas: Some(2000000),
-        expiration_secs: 60,
-    };
-    let txn_summary = cli
-        .run_script_with_gas_options(root_idx, &script, Some(gas_options))
-        .await
-        .unwrap();
-    debug!("txn_summary={:?}", txn_summary);
-
-    // Increment sequence number as we ran a governance proposal
-    info.root_account().increment_sequence_number();
+    let outcome = execute_governance_script(swarm, &script).await;
+    debug!("outcome={:?}", outcome);
 
     print_account_resource::<Configuration>(
         info.client(),
@@ -520,14 +500,9 @@ fun main(core_resources: &signer) {{
 }
 
 async fn spawn_network_and_execute_gov_proposals(
-    swarm: &mut LocalSwarm,
-    cli: &mut CliTestFramework,
-) -> (Ed25519PrivateKey, Configuration, RSA_JWK, usize) {
+    swarm: &LocalSwarm,
+) -> (Ed25519PrivateKey, Configuration, RSA_JWK) {
     let client = swarm.validators().next().unwrap().rest_client();
-    let root_idx = cli.add_account_with_address_to_cli(
-        swarm.root_key(),
-        swarm.chain_info().root_account().address(),
-    );
     swarm
         .wait_for_all_nodes_to_catchup_to_epoch(2, Duration::from_secs(60))
         .await
@@ -572,8 +547,8 @@ use aptos_framework::{};
 use aptos_framework::aptos_governance;
 use std::string::utf8;
 use std::option;
-fun main(core_resources: &signer) {{
-    let framework_signer = aptos_governance::get_signer_testnet_only(core_resources, @0000000000000000000000000000000000000000000000000000000000000001);
+fun main(proposal_id: u64) {{
+    let framework_signer = aptos_governance::resolve(proposal_id, @0000000000000000000000000000000000000000000000000000000000000001);
     let jwk_0 = jwks::new_rsa_jwk(
         utf8(b"{}"),
         utf8(b"{}"),
@@ -604,16 +579,8 @@ fun main(core_resources: &signer) {{
         hex::encode(training_wheels_pk.to_bytes())
     );
 
-    let gas_options = GasOptions {
-        gas_unit_price: Some(100),
-        max_gas: Some(2000000),
-        expiration_secs: 60,
-    };
-    let txn_summary = cli
-        .run_script_with_gas_options(root_idx, &script, Some(gas_options))
-        .await
-        .unwrap();
-    debug!("txn_summary={:?}", txn_summary);
+    let outcome = execute_governance_script(swarm, &script).await;
+    debug!("outcome={:?}", outcome);
 
     info!("Use resource API to check the patch result.");
     let patched_jwks = get_latest_jwkset(&client).await;
@@ -640,12 +607,7 @@ fun main(core_resources: &signer) {{
     assert_ne!(old_config, new_config);
     assert_eq!(new_config.max_exp_horizon_secs, max_exp_horizon_secs);
 
-    let mut info = swarm.aptos_public_info();
-
-    // Increment sequence number since we patched a JWK
-    info.root_account().increment_sequence_number();
-
-    (training_wheels_sk, new_config, jwk, root_idx)
+    (training_wheels_sk, new_config, jwk)
 }
 
 async fn get_latest_jwkset(rest_client: &Client) -> PatchedJWKs {
@@ -656,19 +618,18 @@ async fn get_latest_jwkset(rest_client: &Client) -> PatchedJWKs {
     response.into_inner()
 }
 
-async fn rotate_vk_by_governance<'a>(
-    cli: &mut CliTestFramework,
-    info: &mut AptosPublicInfo,
+async fn rotate_vk_by_governance(
+    swarm: &LocalSwarm,
+    info: &AptosPublicInfo,
     vk: &Groth16VerificationKey,
-    root_idx: usize,
 ) {
     let script = format!(
         r#"
 script {{
     use aptos_framework::{};
     use aptos_framework::aptos_governance;
-    fun main(core_resources: &signer) {{
-        let framework_signer = aptos_governance::get_signer_testnet_only(core_resources, @0x1);
+    fun main(proposal_id: u64) {{
+        let framework_signer = aptos_governance::resolve(proposal_id, @0x1);
         let vk = {}::new_groth16_verification_key(x"{}", x"{}", x"{}", x"{}", vector[x"{}", x"{}"]);
         {}::set_groth16_verification_key_for_next_epoch(&framework_signer, vk);
         aptos_governance::force_end_epoch(&framework_signer);
@@ -696,19 +657,8 @@ script {{
     )
     .await;
 
-    let gas_options = GasOptions {
-        gas_unit_price: Some(100),
-        max_gas: Some(2000000),
-        expiration_secs: 60,
-    };
-    let txn_summary = cli
-        .run_script_with_gas_options(root_idx, &script, Some(gas_options))
-        .await
-        .unwrap();
-    debug!("txn_summary={:?}", txn_summary);
-
-    // Increment sequence number as we ran a governance proposal
-    info.root_account().increment_sequence_number();
+    let outcome = execute_governance_script(swarm, &script).await;
+    debug!("outcome={:?}", outcome);
 
     print_account_resource::<Groth16VerificationKey>(
         info.client(),
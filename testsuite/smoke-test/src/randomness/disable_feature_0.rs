@@ -6,6 +6,7 @@ use crate::{
         decrypt_key_map, get_on_chain_resource, script_to_disable_main_logic, verify_dkg_transcript,
     },
     smoke_test_environment::SwarmBuilder,
+    utils::execute_governance_script,
 };
 use aptos_forge::{Node, Swarm, SwarmExt};
 use aptos_logger::{debug, info};
@@ -19,23 +20,22 @@ use std::{sync::Arc, time::Duration};
 async fn disable_feature_0() {
     let epoch_duration_secs = 20;
 
-    let (swarm, mut cli, _faucet) = SwarmBuilder::new_local(4)
+    let swarm = SwarmBuilder::new_local(4)
         .with_num_fullnodes(1)
         .with_aptos()
         .with_init_genesis_config(Arc::new(move |conf| {
             conf.epoch_duration_secs = epoch_duration_secs;
             conf.allow_new_validators = true;
+            // for the governance proposals to pass within an epoch or so
+            conf.voting_duration_secs = 5;
 
             // Ensure randomness is enabled.
             conf.consensus_config.enable_validator_txns();
             conf.randomness_config_override = Some(OnChainRandomnessConfig::default_enabled());
         }))
-        .build_with_cli(0)
+        .build()
         .await;
 
-    let root_addr = swarm.chain_info().root_account().address();
-    let root_idx = cli.add_account_with_address_to_cli(swarm.root_key(), root_addr);
-
     let decrypt_key_map = decrypt_key_map(&swarm);
 
     let client_endpoint = swarm.validators().nth(1).unwrap().rest_api_endpoint();
@@ -47,39 +47,42 @@ async fn disable_feature_0() {
         .expect("Waited too long for epoch 3.");
 
     info!("Now in epoch 3. Disabling randomness main logic.");
-    let txn_summary = cli
-        .run_script(root_idx, script_to_disable_main_logic().as_str())
-        .await
-        .expect("Txn execution error.");
-    debug!("txn_summary={:?}", txn_summary);
+    let outcome = execute_governance_script(&swarm, &script_to_disable_main_logic()).await;
+    debug!("outcome={:?}", outcome);
 
+    // with randomness on, the proposal only started the DKG for the next epoch
+    let epoch = outcome.epoch + 1;
     swarm
-        .wait_for_all_nodes_to_catchup_to_epoch(4, Duration::from_secs(epoch_duration_secs * 2))
+        .wait_for_all_nodes_to_catchup_to_epoch(epoch, Duration::from_secs(epoch_duration_secs * 2))
         .await
-        .expect("Waited too long for epoch 4.");
+        .unwrap_or_else(|_| panic!("Waited too long for epoch {}.", epoch));
 
-    info!("Now in epoch 4. DKG transcript should still be available. Randomness seed should be unavailable.");
+    info!("Now in epoch {}. DKG transcript should still be available. Randomness seed should be unavailable.", epoch);
     let dkg_session = get_on_chain_resource::<DKGState>(&client)
         .await
         .last_completed
-        .expect("dkg result for epoch 4 should be present");
-    assert_eq!(4, dkg_session.target_epoch());
+        .expect("dkg result for this epoch should be present");
+    assert_eq!(epoch, dkg_session.target_epoch());
     assert!(verify_dkg_transcript(&dkg_session, &decrypt_key_map).is_ok());
 
     let randomness_seed = get_on_chain_resource::<PerBlockRandomness>(&client).await;
     assert!(randomness_seed.seed.is_none());
 
     swarm
-        .wait_for_all_nodes_to_catchup_to_epoch(5, Duration::from_secs(epoch_duration_secs * 2))
+        .wait_for_all_nodes_to_catchup_to_epoch(
+            epoch + 1,
+            Duration::from_secs(epoch_duration_secs * 2),
+        )
         .await
-        .expect("Waited too long for epoch 5.");
+        .unwrap_or_else(|_| panic!("Waited too long for epoch {}.", epoch + 1));
 
-    info!("Now in epoch 5. DKG transcript should be unavailable. Randomness seed should be unavailable.");
+    info!("Now in epoch {}. DKG transcript should be unavailable. Randomness seed should be unavailable.", epoch + 1);
     let maybe_last_complete = get_on_chain_resource::<DKGState>(&client)
         .await
         .last_completed;
     assert!(
-        maybe_last_complete.is_none() || maybe_last_complete.as_ref().unwrap().target_epoch() != 5
+        maybe_last_complete.is_none()
+            || maybe_last_complete.as_ref().unwrap().target_epoch() != epoch + 1
     );
 
     let randomness_seed = get_on_chain_resource::<PerBlockRandomness>(&client).await;
@@ -7,7 +7,7 @@ use crate::{
         script_to_update_consensus_config, verify_dkg_transcript,
     },
     smoke_test_environment::SwarmBuilder,
-    utils::get_current_consensus_config,
+    utils::{execute_governance_script, get_current_consensus_config},
 };
 use aptos_forge::{Node, Swarm, SwarmExt};
 use aptos_logger::{debug, info};
@@ -22,23 +22,22 @@ async fn enable_feature_1() {
     let epoch_duration_secs = 20;
     let estimated_dkg_latency_secs = 40;
 
-    let (swarm, mut cli, _faucet) = SwarmBuilder::new_local(4)
+    let swarm = SwarmBuilder::new_local(4)
         .with_num_fullnodes(1)
         .with_aptos()
         .with_init_genesis_config(Arc::new(move |conf| {
             conf.epoch_duration_secs = epoch_duration_secs;
             conf.allow_new_validators = true;
+            // for the governance proposals to pass within an epoch or so
+            conf.voting_duration_secs = 5;
 
             // start with vtxn disabled and randomness off.
             conf.consensus_config.disable_validator_txns();
             conf.randomness_config_override = Some(OnChainRandomnessConfig::default_disabled());
         }))
-        .build_with_cli(0)
+        .build()
         .await;
 
-    let root_addr = swarm.chain_info().root_account().address();
-    let root_idx = cli.add_account_with_address_to_cli(swarm.root_key(), root_addr);
-
     let decrypt_key_map = decrypt_key_map(&swarm);
 
     let client_endpoint = swarm.validators().nth(1).unwrap().rest_api_endpoint();
@@ -55,51 +54,46 @@ async fn enable_feature_1() {
     let enable_vtxn_script = script_to_update_consensus_config(&config);
 
     debug!("enable_vtxn_script={}", enable_vtxn_script);
-    let txn_summary = cli
-        .run_script(root_idx, enable_vtxn_script.as_str())
-        .await
-        .expect("Txn execution error.");
-    debug!("enabling_vtxn_summary={:?}", txn_summary);
-
+    let outcome = execute_governance_script(&swarm, &enable_vtxn_script).await;
+    debug!("enabling_vtxn_outcome={:?}", outcome);
+    // with randomness off, the proposal reconfigured immediately
+    let epoch = outcome.epoch;
     swarm
-        .wait_for_all_nodes_to_catchup_to_epoch(4, Duration::from_secs(epoch_duration_secs * 2))
-        .await
-        .expect("Waited too long for epoch 4.");
-
-    info!("Now in epoch 4. Enabling randomness main logic.");
-    let enable_main_logic_script = script_to_enable_main_logic();
-    let txn_summary = cli
-        .run_script(root_idx, enable_main_logic_script.as_str())
+        .wait_for_all_nodes_to_catchup_to_epoch(epoch, Duration::from_secs(epoch_duration_secs))
         .await
-        .expect("Txn execution error.");
-    debug!("txn_summary={:?}", txn_summary);
+        .unwrap_or_else(|_| panic!("Waited too long for epoch {}.", epoch));
 
+    info!("Now in epoch {}. Enabling randomness main logic.", epoch);
+    let outcome = execute_governance_script(&swarm, &script_to_enable_main_logic()).await;
+    debug!("enabling_main_logic_outcome={:?}", outcome);
+    let epoch = outcome.epoch;
     swarm
-        .wait_for_all_nodes_to_catchup_to_epoch(5, Duration::from_secs(epoch_duration_secs * 2))
+        .wait_for_all_nodes_to_catchup_to_epoch(epoch, Duration::from_secs(epoch_duration_secs))
         .await
-        .expect("Waited too long for epoch 5.");
+        .unwrap_or_else(|_| panic!("Waited too long for epoch {}.", epoch));
 
-    info!("Now in epoch 5. Both DKG and vtxn are enabled. There should be no randomness since DKG did not happen at the end of last epoch.");
+    info!("Now in epoch {}. Both DKG and vtxn are enabled. There should be no randomness since DKG did not happen at the end of last epoch.", epoch);
     let maybe_last_complete = get_on_chain_resource::<DKGState>(&client)
         .await
         .last_completed;
     assert!(
-        maybe_last_complete.is_none() || maybe_last_complete.as_ref().unwrap().target_epoch() != 5
+        maybe_last_complete.is_none()
+            || maybe_last_complete.as_ref().unwrap().target_epoch() != epoch
     );
 
-    info!("Waiting for epoch 6.");
+    info!("Waiting for epoch {}.", epoch + 1);
     swarm
         .wait_for_all_nodes_to_catchup_to_epoch(
-            6,
+            epoch + 1,
             Duration::from_secs(epoch_duration_secs + estimated_dkg_latency_secs),
         )
         .await
-        .expect("Waited too long for epoch 6.");
+        .unwrap_or_else(|_| panic!("Waited too long for epoch {}.", epoch + 1));
 
     let dkg_session = get_on_chain_resource::<DKGState>(&client)
         .await
         .last_completed
-        .expect("dkg result for epoch 6 should be present");
-    assert_eq!(6, dkg_session.target_epoch());
+        .expect("dkg result for the next epoch should be present");
+    assert_eq!(epoch + 1, dkg_session.target_epoch());
     assert!(verify_dkg_transcript(&dkg_session, &decrypt_key_map).is_ok());
 }
@@ -4,7 +4,7 @@
 use crate::{
     randomness::{decrypt_key_map, get_on_chain_resource, verify_dkg_transcript},
     smoke_test_environment::SwarmBuilder,
-    utils::get_current_consensus_config,
+    utils::{execute_governance_script, get_current_consensus_config},
 };
 use aptos_forge::{Node, Swarm, SwarmExt};
 use aptos_logger::{debug, info};
@@ -17,23 +17,22 @@ async fn enable_feature_2() {
     let epoch_duration_secs = 20;
     let estimated_dkg_latency_secs = 40;
 
-    let (swarm, mut cli, _faucet) = SwarmBuilder::new_local(4)
+    let swarm = SwarmBuilder::new_local(4)
         .with_num_fullnodes(1)
         .with_aptos()
         .with_init_genesis_config(Arc::new(move |conf| {
             conf.epoch_duration_secs = epoch_duration_secs;
             conf.allow_new_validators = true;
+            // for the governance proposals to pass within an epoch or so
+            conf.voting_duration_secs = 5;
 
             // start with vtxn disabled and randomness off.
             conf.consensus_config.disable_validator_txns();
             conf.randomness_config_override = Some(OnChainRandomnessConfig::default_disabled());
         }))
-        .build_with_cli(0)
+        .build()
         .await;
 
-    let root_addr = swarm.chain_info().root_account().address();
-    let root_idx = cli.add_account_with_address_to_cli(swarm.root_key(), root_addr);
-
     let decrypt_key_map = decrypt_key_map(&swarm);
 
     let client_endpoint = swarm.validators().nth(1).unwrap().rest_api_endpoint();
@@ -56,8 +55,8 @@ script {{
     use aptos_framework::randomness_config;
     use aptos_std::fixed_point64;
 
-    fun main(core_resources: &signer) {{
-        let framework_signer = aptos_governance::get_signer_testnet_only(core_resources, @0x1);
+    fun main(proposal_id: u64) {{
+        let framework_signer = aptos_governance::resolve(proposal_id, @0x1);
         let consensus_config_bytes = vector{:?};
         consensus_config::set_for_next_epoch(&framework_signer, consensus_config_bytes);
         let randomness_config = randomness_config::new_v1(
@@ -73,38 +72,37 @@ script {{
     );
 
     debug!("script={}", script);
-    let txn_summary = cli
-        .run_script(root_idx, script.as_str())
-        .await
-        .expect("Txn execution error.");
-    debug!("txn_summary={:?}", txn_summary);
-
+    let outcome = execute_governance_script(&swarm, &script).await;
+    debug!("outcome={:?}", outcome);
+    // with vtxn disabled, the proposal reconfigured immediately
+    let epoch = outcome.epoch;
     swarm
-        .wait_for_all_nodes_to_catchup_to_epoch(4, Duration::from_secs(epoch_duration_secs * 2))
+        .wait_for_all_nodes_to_catchup_to_epoch(epoch, Duration::from_secs(epoch_duration_secs))
         .await
-        .expect("Waited too long for epoch 4.");
+        .unwrap_or_else(|_| panic!("Waited too long for epoch {}.", epoch));
 
-    info!("Now in epoch 4. Both DKG and vtxn are enabled. There should be no randomness since DKG did not happen at the end of last epoch.");
+    info!("Now in epoch {}. Both DKG and vtxn are enabled. There should be no randomness since DKG did not happen at the end of last epoch.", epoch);
     let maybe_last_complete = get_on_chain_resource::<DKGState>(&client)
         .await
         .last_completed;
     assert!(
-        maybe_last_complete.is_none() || maybe_last_complete.as_ref().unwrap().target_epoch() != 4
+        maybe_last_complete.is_none()
+            || maybe_last_complete.as_ref().unwrap().target_epoch() != epoch
     );
 
-    info!("Waiting for epoch 5.");
+    info!("Waiting for epoch {}.", epoch + 1);
     swarm
         .wait_for_all_nodes_to_catchup_to_epoch(
-            5,
+            epoch + 1,
             Duration::from_secs(epoch_duration_secs + estimated_dkg_latency_secs),
         )
         .await
-        .expect("Waited too long for epoch 5.");
+        .unwrap_or_else(|_| panic!("Waited too long for epoch {}.", epoch + 1));
 
     let dkg_session = get_on_chain_resource::<DKGState>(&client)
         .await
         .last_completed
-        .expect("dkg result for epoch 5 should be present");
-    assert_eq!(5, dkg_session.target_epoch());
+        .expect("dkg result for the next epoch should be present");
+    assert_eq!(epoch + 1, dkg_session.target_epoch());
     assert!(verify_dkg_transcript(&dkg_session, &decrypt_key_map).is_ok());
 }
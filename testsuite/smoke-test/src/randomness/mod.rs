@@ -269,8 +269,8 @@ script {
     use aptos_framework::randomness_config;
     use aptos_std::fixed_point64;
 
-    fun main(core_resources: &signer) {
-        let framework_signer = aptos_governance::get_signer_testnet_only(core_resources, @0x1);
+    fun main(proposal_id: u64) {
+        let framework_signer = aptos_governance::resolve(proposal_id, @0x1);
         let config = randomness_config::new_v1(
             fixed_point64::create_from_rational(1, 2),
             fixed_point64::create_from_rational(2, 3)
@@ -288,8 +288,8 @@ fn script_to_disable_main_logic() -> String {
 script {
     use aptos_framework::aptos_governance;
     use aptos_framework::randomness_config;
-    fun main(core_resources: &signer) {
-        let framework_signer = aptos_governance::get_signer_testnet_only(core_resources, @0x1);
+    fun main(proposal_id: u64) {
+        let framework_signer = aptos_governance::resolve(proposal_id, @0x1);
         let config = randomness_config::new_off();
         randomness_config::set_for_next_epoch(&framework_signer, config);
         aptos_governance::reconfigure(&framework_signer);
@@ -307,8 +307,8 @@ script {{
     use aptos_framework::aptos_governance;
     use aptos_framework::consensus_config;
 
-    fun main(core_resources: &signer) {{
-        let framework_signer = aptos_governance::get_signer_testnet_only(core_resources, @0x1);
+    fun main(proposal_id: u64) {{
+        let framework_signer = aptos_governance::resolve(proposal_id, @0x1);
         let config_bytes = vector{:?};
         consensus_config::set_for_next_epoch(&framework_signer, config_bytes);
         aptos_governance::reconfigure(&framework_signer);
@@ -6,8 +6,9 @@ use crate::{
         decrypt_key_map, get_on_chain_resource, verify_dkg_transcript, wait_for_dkg_finish,
     },
     smoke_test_environment::SwarmBuilder,
+    utils::MAX_HEALTHY_WAIT_SECS,
 };
-use aptos_forge::{NodeExt, SwarmExt};
+use aptos_forge::{Node, NodeExt, SwarmExt};
 use aptos_logger::{debug, info};
 use aptos_rest_client::Client;
 use aptos_types::{dkg::DKGState, on_chain_config::OnChainRandomnessConfig};
@@ -78,7 +79,9 @@ async fn validator_restart_during_dkg() {
             tokio::time::sleep(Duration::from_secs(1)).await;
         }
         info!("node {} panicked", node_idx);
-        node.restart().await.unwrap();
+        node.restart(Duration::from_secs(MAX_HEALTHY_WAIT_SECS))
+            .await
+            .unwrap();
         info!("node {} restarted", node_idx);
     }
 
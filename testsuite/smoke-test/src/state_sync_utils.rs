@@ -157,7 +157,7 @@ pub async fn test_fullnode_sync(
     swarm
         .fullnode_mut(vfn_peer_id)
         .unwrap()
-        .restart()
+        .restart(Duration::from_secs(MAX_HEALTHY_WAIT_SECS))
         .await
         .unwrap();
     wait_for_all_nodes(swarm).await;
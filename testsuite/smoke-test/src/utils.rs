@@ -3,7 +3,9 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use aptos_cached_packages::aptos_stdlib;
-use aptos_forge::{reconfig, LocalSwarm, NodeExt, Swarm, SwarmExt};
+use aptos_forge::{
+    compile_proposal_script, reconfig, LocalSwarm, NodeExt, ProposalOutcome, Swarm, SwarmExt,
+};
 use aptos_rest_client::Client as RestClient;
 use aptos_sdk::{
     transaction_builder::TransactionFactory,
@@ -210,6 +212,18 @@ pub(crate) async fn get_current_execution_config(
     .unwrap()
 }
 
+/// Passes and executes the governance proposal script with the votes of all the validators, see
+/// [aptos_forge::compile_proposal_script]. The voting lasts for the whole voting duration of the
+/// genesis config, so tests calling this shorten it.
+pub async fn execute_governance_script(swarm: &LocalSwarm, source: &str) -> ProposalOutcome {
+    let script = compile_proposal_script(source).unwrap();
+    let voters: Vec<_> = swarm.validators().map(|v| v.peer_id()).collect();
+    swarm
+        .execute_governance_proposal(&script, vec![], &voters)
+        .await
+        .unwrap()
+}
+
 /// Returns the current ledger info version
 pub async fn get_current_version(rest_client: &RestClient) -> u64 {
     rest_client
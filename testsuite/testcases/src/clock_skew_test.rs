@@ -0,0 +1,89 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{generate_traffic, pick_faulty_validator};
+use anyhow::{bail, Context};
+use aptos_forge::{
+    NetworkContextSynchronizer, NetworkTest, NodeExt, Result, SkewDirection, SwarmChaos,
+    SwarmTimeSkew, Test,
+};
+use aptos_logger::info;
+use async_trait::async_trait;
+use std::{
+    ops::DerefMut,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+const SKEW: Duration = Duration::from_secs(30);
+const TRAFFIC_DURATION: Duration = Duration::from_secs(120);
+// how far the ledger may be ahead of our clock without a block from the skewed validator's future
+// having been committed
+const MAX_LEDGER_AHEAD: Duration = Duration::from_secs(5);
+
+/// Moves the clock of one validator 30s ahead. Its proposals carry timestamps that far in the
+/// future, which the other validators refuse to vote for, so the network must keep committing
+/// without them, and with a ledger that does not run ahead of the real time.
+pub struct ClockSkewTest;
+
+impl Test for ClockSkewTest {
+    fn name(&self) -> &'static str {
+        "network::clock-skew-test"
+    }
+}
+
+#[async_trait]
+impl NetworkTest for ClockSkewTest {
+    async fn run<'a>(&self, ctxa: NetworkContextSynchronizer<'a>) -> Result<()> {
+        let mut ctx_locker = ctxa.ctx.lock().await;
+        let ctx = ctx_locker.deref_mut();
+
+        let (skewed, others) = pick_faulty_validator(&ctx.swarm, self.name()).await?;
+
+        let chaos = SwarmChaos::TimeSkew(SwarmTimeSkew {
+            peers: vec![skewed],
+            offset: SKEW,
+            direction: SkewDirection::Ahead,
+        });
+        ctx.swarm.write().await.inject_chaos(chaos.clone()).await?;
+        let stats = generate_traffic(ctx, &others, TRAFFIC_DURATION).await;
+        let ledger_timestamp_usecs = {
+            let swarm = ctx.swarm.read().await;
+            let validator = swarm
+                .validator(others[0])
+                .context("Validator is missing from the swarm")?;
+            validator
+                .rest_client()
+                .get_ledger_information()
+                .await
+                .map(|state| state.into_inner().timestamp_usecs)
+        };
+        ctx.swarm.write().await.remove_chaos(chaos).await?;
+        let stats = stats?;
+        ctx.report
+            .report_txn_stats(format!("{}::skewed", self.name()), &stats);
+
+        if stats.committed == 0 {
+            bail!(
+                "No transactions committed with a validator {:?} ahead",
+                SKEW
+            );
+        }
+        let ledger_time = UNIX_EPOCH + Duration::from_micros(ledger_timestamp_usecs?);
+        let ahead = ledger_time
+            .duration_since(SystemTime::now())
+            .unwrap_or_default();
+        info!("Ledger timestamp is {:?} ahead of our clock", ahead);
+        if ahead > MAX_LEDGER_AHEAD {
+            bail!(
+                "Ledger timestamp is {:?} ahead of our clock, so a proposal from the validator {:?} ahead was committed",
+                ahead,
+                SKEW
+            );
+        }
+        ctx.report.report_text(format!(
+            "With a validator {:?} ahead, {} transactions committed, and the ledger stayed within {:?} of the real time",
+            SKEW, stats.committed, MAX_LEDGER_AHEAD
+        ));
+        Ok(())
+    }
+}
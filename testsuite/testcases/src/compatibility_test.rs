@@ -6,7 +6,7 @@ use crate::{batch_update_gradually, create_emitter_and_request, generate_traffic
 use anyhow::bail;
 use aptos_forge::{
     EmitJobRequest, NetworkContextSynchronizer, NetworkTest, Result, SwarmExt, Test, TxnEmitter,
-    TxnStats, Version,
+    TxnStats, UpgradeSelector, Version,
 };
 use aptos_logger::info;
 use aptos_sdk::types::{LocalAccount, PeerId};
@@ -31,6 +31,10 @@ impl Test for SimpleValidatorUpgrade {
     fn name(&self) -> &'static str {
         "compatibility::simple-validator-upgrade"
     }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["compat", "land-blocking"]
+    }
 }
 
 async fn stat_gather_task(
@@ -183,25 +187,46 @@ impl NetworkTest for SimpleValidatorUpgrade {
         {
             bail!("compat test requires >= 4 validators");
         }
-        let all_validators = ctxa
-            .ctx
-            .lock()
-            .await
-            .swarm
-            .read()
-            .await
-            .validators()
-            .map(|v| v.peer_id())
-            .collect::<Vec<_>>();
-        let mut first_batch = all_validators.clone();
-        let second_batch = first_batch.split_off(first_batch.len() / 2);
-        let first_node = first_batch.pop().unwrap();
+        let (all_validators, upgraded_validators) = {
+            let ctx_locker = ctxa.ctx.lock().await;
+            let swarm = ctx_locker.swarm.read().await;
+            let all_validators = swarm.validators().map(|v| v.peer_id()).collect::<Vec<_>>();
+            let upgraded_validators = swarm
+                .validators()
+                .filter(|v| v.version() == new_version)
+                .map(|v| v.peer_id())
+                .collect::<Vec<_>>();
+            (all_validators, upgraded_validators)
+        };
+        // if the swarm started on mixed versions, the validators on the new one are the first
+        // batch, and don't need to be upgraded
+        let started_mixed = !upgraded_validators.is_empty();
+        let mut first_batch = if started_mixed {
+            upgraded_validators
+        } else {
+            UpgradeSelector::Fraction(0.5).select(&all_validators)?
+        };
+        let second_batch: Vec<_> = all_validators
+            .iter()
+            .filter(|validator| !first_batch.contains(validator))
+            .cloned()
+            .collect();
         let duration = Duration::from_secs(30);
 
-        let msg = format!(
-            "1. Check liveness of validators at old version: {}",
-            old_version
-        );
+        let msg = if started_mixed {
+            format!(
+                "1. Check liveness of validators started at mixed versions: {} on {}, {} on {}",
+                first_batch.len(),
+                new_version,
+                second_batch.len(),
+                old_version
+            )
+        } else {
+            format!(
+                "1. Check liveness of validators at old version: {}",
+                old_version
+            )
+        };
         info!("{}", msg);
         ctxa.report_text(msg).await;
 
@@ -214,65 +239,69 @@ impl NetworkTest for SimpleValidatorUpgrade {
                 .report_txn_stats(format!("{}::liveness-check", self.name()), &txn_stat_prior);
         }
 
-        // Update the first Validator
-        let msg = format!(
-            "2. Upgrading first Validator to new version: {}",
-            new_version
-        );
-        info!("{}", msg);
-        ctxa.report_text(msg).await;
-        let upgrade_stats = upgrade_and_gather_stats(
-            ctxa.clone(),
-            &[first_node],
-            &new_version,
-            upgrade_wait_for_healthy,
-            upgrade_node_delay,
-            upgrade_max_wait,
-            &[first_node],
-        )?;
-        let upgrade_stats_sum = upgrade_stats.into_iter().reduce(|a, b| &a + &b);
-        if let Some(upgrade_stats_sum) = upgrade_stats_sum {
-            ctxa.ctx.lock().await.report.report_txn_stats(
-                format!("{}::single-validator-upgrading", self.name()),
-                &upgrade_stats_sum,
-            );
-        }
-
-        // Generate some traffic
-        {
-            let mut ctx_locker = ctxa.ctx.lock().await;
-            let ctx = ctx_locker.deref_mut();
-            let txn_stat_one = generate_traffic(ctx, &[first_node], duration).await?;
-            ctx.report.report_txn_stats(
-                format!("{}::single-validator-upgrade", self.name()),
-                &txn_stat_one,
-            );
+        if !started_mixed {
+            let first_node = first_batch.pop().unwrap();
 
-            // Update the rest of the first batch
+            // Update the first Validator
             let msg = format!(
-                "3. Upgrading rest of first batch to new version: {}",
+                "2. Upgrading first Validator to new version: {}",
                 new_version
             );
             info!("{}", msg);
-            ctx.report.report_text(msg);
-        }
+            ctxa.report_text(msg).await;
+            let upgrade_stats = upgrade_and_gather_stats(
+                ctxa.clone(),
+                &[first_node],
+                &new_version,
+                upgrade_wait_for_healthy,
+                upgrade_node_delay,
+                upgrade_max_wait,
+                &[first_node],
+            )?;
+            let upgrade_stats_sum = upgrade_stats.into_iter().reduce(|a, b| &a + &b);
+            if let Some(upgrade_stats_sum) = upgrade_stats_sum {
+                ctxa.ctx.lock().await.report.report_txn_stats(
+                    format!("{}::single-validator-upgrading", self.name()),
+                    &upgrade_stats_sum,
+                );
+            }
 
-        // upgrade the rest of the first half
-        let upgrade2_stats = upgrade_and_gather_stats(
-            ctxa.clone(),
-            &first_batch,
-            &new_version,
-            upgrade_wait_for_healthy,
-            upgrade_node_delay,
-            upgrade_max_wait,
-            &first_batch,
-        )?;
-        let upgrade2_stats_sum = upgrade2_stats.into_iter().reduce(|a, b| &a + &b);
-        if let Some(upgrade2_stats_sum) = upgrade2_stats_sum {
-            ctxa.ctx.lock().await.report.report_txn_stats(
-                format!("{}::half-validator-upgrading", self.name()),
-                &upgrade2_stats_sum,
-            );
+            // Generate some traffic
+            {
+                let mut ctx_locker = ctxa.ctx.lock().await;
+                let ctx = ctx_locker.deref_mut();
+                let txn_stat_one = generate_traffic(ctx, &[first_node], duration).await?;
+                ctx.report.report_txn_stats(
+                    format!("{}::single-validator-upgrade", self.name()),
+                    &txn_stat_one,
+                );
+
+                // Update the rest of the first batch
+                let msg = format!(
+                    "3. Upgrading rest of first batch to new version: {}",
+                    new_version
+                );
+                info!("{}", msg);
+                ctx.report.report_text(msg);
+            }
+
+            // upgrade the rest of the first half
+            let upgrade2_stats = upgrade_and_gather_stats(
+                ctxa.clone(),
+                &first_batch,
+                &new_version,
+                upgrade_wait_for_healthy,
+                upgrade_node_delay,
+                upgrade_max_wait,
+                &first_batch,
+            )?;
+            let upgrade2_stats_sum = upgrade2_stats.into_iter().reduce(|a, b| &a + &b);
+            if let Some(upgrade2_stats_sum) = upgrade2_stats_sum {
+                ctxa.ctx.lock().await.report.report_txn_stats(
+                    format!("{}::half-validator-upgrading", self.name()),
+                    &upgrade2_stats_sum,
+                );
+            }
         }
         {
             let mut ctx_locker = ctxa.ctx.lock().await;
@@ -0,0 +1,93 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{generate_traffic, CHAOS_RECOVERY_TIMEOUT};
+use anyhow::bail;
+use aptos_forge::{
+    heal_and_verify, GroupNetworkDelay, NetworkContextSynchronizer, NetworkDelayDirection,
+    NetworkTest, Result, SwarmChaos, SwarmNetworkDelay, Test,
+};
+use aptos_logger::info;
+use aptos_sdk::types::PeerId;
+use async_trait::async_trait;
+use std::{ops::DerefMut, time::Duration};
+
+// each way, so a round trip between the halves takes 300ms longer
+const ONE_WAY_LATENCY_MS: u64 = 150;
+const TRAFFIC_DURATION: Duration = Duration::from_secs(60);
+
+/// Delays the traffic between the two halves of the validators, as if they were in distant
+/// regions. Commits must get slower, but keep coming.
+pub struct ConsensusLatencyTest;
+
+impl Test for ConsensusLatencyTest {
+    fn name(&self) -> &'static str {
+        "network::consensus-latency-test"
+    }
+}
+
+#[async_trait]
+impl NetworkTest for ConsensusLatencyTest {
+    async fn run<'a>(&self, ctxa: NetworkContextSynchronizer<'a>) -> Result<()> {
+        let mut ctx_locker = ctxa.ctx.lock().await;
+        let ctx = ctx_locker.deref_mut();
+
+        let mut validators: Vec<PeerId> = ctx
+            .swarm
+            .read()
+            .await
+            .validators()
+            .map(|v| v.peer_id())
+            .collect();
+        if validators.len() < 2 {
+            bail!("consensus latency test requires >= 2 validators");
+        }
+        validators.sort();
+        let mut first_half = validators.clone();
+        let second_half = first_half.split_off(validators.len() / 2);
+
+        let baseline = generate_traffic(ctx, &validators, TRAFFIC_DURATION).await?;
+        ctx.report
+            .report_txn_stats(format!("{}::baseline", self.name()), &baseline);
+
+        let chaos = SwarmChaos::Delay(SwarmNetworkDelay {
+            group_network_delays: vec![GroupNetworkDelay {
+                name: "consensus-latency-between-halves".to_string(),
+                source_nodes: first_half,
+                target_nodes: second_half,
+                latency_ms: ONE_WAY_LATENCY_MS,
+                jitter_ms: 10,
+                correlation_percentage: 50,
+                direction: NetworkDelayDirection::Both,
+            }],
+        });
+        ctx.swarm.write().await.inject_chaos(chaos.clone()).await?;
+        let delayed = generate_traffic(ctx, &validators, TRAFFIC_DURATION).await;
+        let recovery = heal_and_verify(&ctx.swarm, &chaos, CHAOS_RECOVERY_TIMEOUT).await?;
+        recovery.report(&mut ctx.report, self.name());
+        let delayed = delayed?;
+        ctx.report
+            .report_txn_stats(format!("{}::delayed", self.name()), &delayed);
+
+        let (baseline_latency, delayed_latency) = (baseline.rate().latency, delayed.rate().latency);
+        info!(
+            "Mean commit latency went from {:.0}ms to {:.0}ms",
+            baseline_latency, delayed_latency
+        );
+        if delayed.committed == 0 {
+            bail!(
+                "No transactions committed with {}ms between the halves of the validators",
+                2 * ONE_WAY_LATENCY_MS
+            );
+        }
+        if delayed_latency <= baseline_latency {
+            bail!(
+                "Mean commit latency did not degrade with {}ms between the halves of the validators: {:.0}ms before, {:.0}ms after",
+                2 * ONE_WAY_LATENCY_MS,
+                baseline_latency,
+                delayed_latency
+            );
+        }
+        Ok(())
+    }
+}
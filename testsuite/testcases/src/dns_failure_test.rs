@@ -0,0 +1,84 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::generate_traffic;
+use anyhow::bail;
+use aptos_forge::{
+    DnsAction, NetworkContextSynchronizer, NetworkTest, NodeExt, Result, SwarmChaos,
+    SwarmDnsFailure, Test,
+};
+use aptos_logger::info;
+use aptos_sdk::types::PeerId;
+use async_trait::async_trait;
+use std::{ops::DerefMut, time::Duration};
+
+const TRAFFIC_DURATION: Duration = Duration::from_secs(120);
+// the telemetry service and the other endpoints the nodes call out to
+const EXTERNAL_PATTERNS: &[&str] = &["*.aptoslabs.com"];
+
+/// Fails the DNS lookups of the external endpoints on all validators. They only resolve each other
+/// through the on-chain addresses, so consensus and the REST API must be unaffected.
+pub struct DnsFailureTest;
+
+impl Test for DnsFailureTest {
+    fn name(&self) -> &'static str {
+        "network::dns-failure-test"
+    }
+}
+
+#[async_trait]
+impl NetworkTest for DnsFailureTest {
+    async fn run<'a>(&self, ctxa: NetworkContextSynchronizer<'a>) -> Result<()> {
+        let mut ctx_locker = ctxa.ctx.lock().await;
+        let ctx = ctx_locker.deref_mut();
+
+        let validators: Vec<PeerId> = ctx
+            .swarm
+            .read()
+            .await
+            .validators()
+            .map(|v| v.peer_id())
+            .collect();
+        let chaos = SwarmChaos::DnsFailure(SwarmDnsFailure {
+            peers: validators.clone(),
+            patterns: EXTERNAL_PATTERNS.iter().map(|p| p.to_string()).collect(),
+            action: DnsAction::Error,
+        });
+        ctx.swarm.write().await.inject_chaos(chaos.clone()).await?;
+        let stats = generate_traffic(ctx, &validators, TRAFFIC_DURATION).await;
+        let unreachable = {
+            let swarm = ctx.swarm.read().await;
+            let mut unreachable = vec![];
+            for validator in swarm.validators() {
+                if let Err(e) = validator.rest_client().get_ledger_information().await {
+                    info!("REST API of {} failed: {}", validator.name(), e);
+                    unreachable.push(validator.name().to_string());
+                }
+            }
+            unreachable
+        };
+        ctx.swarm.write().await.remove_chaos(chaos).await?;
+        let stats = stats?;
+        ctx.report
+            .report_txn_stats(format!("{}::dns-failure", self.name()), &stats);
+
+        if stats.committed == 0 {
+            bail!(
+                "No transactions committed with the DNS lookups of {:?} failing",
+                EXTERNAL_PATTERNS
+            );
+        }
+        if !unreachable.is_empty() {
+            bail!(
+                "REST API of {:?} failed with the DNS lookups of {:?} failing",
+                unreachable,
+                EXTERNAL_PATTERNS
+            );
+        }
+        ctx.report.report_text(format!(
+            "With the DNS lookups of {:?} failing on all validators, {} transactions committed, and every REST API answered",
+            EXTERNAL_PATTERNS, stats.committed
+        ));
+        Ok(())
+    }
+}
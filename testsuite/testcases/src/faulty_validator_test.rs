@@ -0,0 +1,90 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{LoadDestination, NetworkLoadTest};
+use anyhow::{bail, Context};
+use aptos_forge::{
+    FaultyBehavior, NetworkContext, NetworkContextSynchronizer, NetworkTest, Swarm, SwarmExt, Test,
+};
+use async_trait::async_trait;
+use std::time::Duration;
+
+/// Makes some validators misbehave, fewer than a third of them, while the others take the load.
+/// The honest validators must keep committing, which the success criteria check, and agree with
+/// each other on what they committed.
+pub struct FaultyValidatorTest {
+    pub behavior: FaultyBehavior,
+    pub num_faulty: usize,
+}
+
+impl Test for FaultyValidatorTest {
+    fn name(&self) -> &'static str {
+        "consensus::faulty-validator-test"
+    }
+}
+
+#[async_trait]
+impl NetworkLoadTest for FaultyValidatorTest {
+    async fn setup<'a>(&self, ctx: &mut NetworkContext<'a>) -> anyhow::Result<LoadDestination> {
+        let mut swarm = ctx.swarm.write().await;
+        for _ in 0..self.num_faulty {
+            let peer_id = swarm.deploy_faulty_validator(self.behavior.clone()).await?;
+            let msg = format!(
+                "Validator {} {}",
+                swarm
+                    .validator(peer_id)
+                    .context("Faulty validator is gone")?
+                    .name(),
+                self.behavior
+            );
+            println!("{}", msg);
+            ctx.report.report_text(msg);
+        }
+        let honest = swarm
+            .honest_validators()
+            .into_iter()
+            .map(|validator| validator.peer_id())
+            .collect();
+        Ok(LoadDestination::Peers(honest))
+    }
+
+    async fn finish<'a>(&self, ctx: &mut NetworkContext<'a>) -> anyhow::Result<()> {
+        let swarm = ctx.swarm.read().await;
+        let honest: Vec<_> = swarm
+            .honest_validators()
+            .into_iter()
+            .map(|validator| validator.peer_id())
+            .collect();
+        let clients = swarm.get_clients_for_peers(&honest, Duration::from_secs(10));
+        let mut versions = vec![];
+        for client in &clients {
+            versions.push(client.get_ledger_information().await?.into_inner().version);
+        }
+        // every honest validator has committed up to the lowest of their versions
+        let version = versions
+            .into_iter()
+            .min()
+            .context("No honest validators are left")?;
+        if !<dyn Swarm>::are_root_hashes_equal_at_version(&clients, version).await? {
+            bail!(
+                "The honest validators committed different transactions at version {}, with {} of \
+                 the validators that {}",
+                version,
+                self.num_faulty,
+                self.behavior
+            );
+        }
+        ctx.report.report_text(format!(
+            "The honest validators agree at version {} with {} faulty validators",
+            version, self.num_faulty
+        ));
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl NetworkTest for FaultyValidatorTest {
+    async fn run<'a>(&self, ctx: NetworkContextSynchronizer<'a>) -> anyhow::Result<()> {
+        <dyn NetworkLoadTest>::run(self, ctx).await
+    }
+}
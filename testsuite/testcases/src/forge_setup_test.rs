@@ -2,8 +2,6 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::generate_traffic;
-use anyhow::Context;
-use aptos_config::config::OverrideNodeConfig;
 use aptos_forge::{NetworkContextSynchronizer, NetworkTest, Result, Test};
 use aptos_logger::info;
 use async_trait::async_trait;
@@ -66,20 +64,15 @@ impl NetworkTest for ForgeSetupTest {
         }
 
         // add some PFNs and send load to them
-        let mut pfns = Vec::new();
         let num_pfns = 5;
-        {
+        let pfns = {
             let mut swarm = ctx.swarm.write().await;
-            for _ in 0..num_pfns {
-                let pfn_version = swarm.versions().max().unwrap();
-                let pfn_node_config =
-                    OverrideNodeConfig::new_with_default_base(swarm.get_default_pfn_node_config());
-                let pfn_peer_id = swarm.add_full_node(&pfn_version, pfn_node_config).await?;
-
-                let _pfn = swarm.full_node(pfn_peer_id).context("pfn not found")?;
-                pfns.push(pfn_peer_id);
-            }
-        }
+            let pfn_version = swarm.versions().max().unwrap();
+            let pfn_node_config = swarm.get_default_pfn_node_config();
+            swarm
+                .deploy_public_fullnodes(num_pfns as usize, &pfn_version, pfn_node_config)
+                .await?
+        };
 
         let duration = Duration::from_secs(10 * num_pfns);
         let txn_stat = generate_traffic(ctx, &pfns, duration).await?;
@@ -26,6 +26,10 @@ impl Test for FrameworkUpgrade {
     fn name(&self) -> &'static str {
         "framework_upgrade::framework-upgrade"
     }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["compat", "land-blocking"]
+    }
 }
 
 #[async_trait]
@@ -0,0 +1,42 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use anyhow::bail;
+use aptos_forge::{NetworkContextSynchronizer, NetworkTest, Result, Test};
+use aptos_logger::info;
+use async_trait::async_trait;
+
+/// Checks that every validator serves the same ledger information directly and through its
+/// HAProxy, so the swarm must be deployed with HAProxy enabled
+pub struct HaproxyConsistencyTest;
+
+impl Test for HaproxyConsistencyTest {
+    fn name(&self) -> &'static str {
+        "network::haproxy-consistency-test"
+    }
+}
+
+#[async_trait]
+impl NetworkTest for HaproxyConsistencyTest {
+    async fn run<'a>(&self, ctxa: NetworkContextSynchronizer<'a>) -> Result<()> {
+        let ctx_locker = ctxa.ctx.lock().await;
+        let swarm = ctx_locker.swarm.read().await;
+        let mut inconsistent = vec![];
+        for validator in swarm.validators() {
+            match validator.check_haproxy_consistency().await {
+                Ok(()) => info!(
+                    "{} serves the same ledger through HAProxy",
+                    validator.name()
+                ),
+                Err(e) => inconsistent.push(format!("{}: {:#}", validator.name(), e)),
+            }
+        }
+        if !inconsistent.is_empty() {
+            bail!(
+                "The ledger differs through HAProxy on:\n  {}",
+                inconsistent.join("\n  ")
+            );
+        }
+        Ok(())
+    }
+}
@@ -2,39 +2,53 @@
 // Parts of the project are originally copyright © Meta Platforms, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
+pub mod clock_skew_test;
 pub mod compatibility_test;
+pub mod consensus_latency_test;
 pub mod consensus_reliability_tests;
 pub mod dag_onchain_enable_test;
+pub mod dns_failure_test;
+pub mod faulty_validator_test;
 pub mod forge_setup_test;
 pub mod framework_upgrade;
 pub mod fullnode_reboot_stress_test;
+pub mod haproxy_consistency_test;
 pub mod load_vs_perf_benchmark;
+pub mod minority_partition_test;
 pub mod modifiers;
 pub mod multi_region_network_test;
 pub mod network_bandwidth_test;
 pub mod network_loss_test;
 pub mod network_partition_test;
+pub mod oom_recovery_test;
 pub mod partial_nodes_down_test;
 pub mod performance_test;
 pub mod public_fullnode_performance;
 pub mod quorum_store_onchain_enable_test;
 pub mod reconfiguration_test;
+pub mod simulated_geo_test;
+pub mod stake_weighted_proposals_test;
 pub mod state_sync_performance;
+pub mod storage_resilience_test;
 pub mod three_region_simulation_test;
 pub mod twin_validator_test;
 pub mod two_traffics_test;
 pub mod validator_join_leave_test;
+pub mod validator_join_test;
 pub mod validator_reboot_stress_test;
 
 use anyhow::Context;
 use aptos_forge::{
+    compile_proposal_script,
     prometheus_metrics::{fetch_latency_breakdown, LatencyBreakdown},
-    EmitJobRequest, NetworkContext, NetworkContextSynchronizer, NetworkTest, NodeExt, Result,
-    Swarm, SwarmExt, Test, TestReport, TxnEmitter, TxnStats, Version,
+    EmitJobRequest, MemorySampler, NetworkContext, NetworkContextSynchronizer, NetworkTest,
+    NodeExt, ProposalOutcome, Result, Swarm, SwarmExt, Test, TestReport, TxnEmitter, TxnStats,
+    Version,
 };
 use aptos_logger::info;
 use aptos_rest_client::Client as RestClient;
-use aptos_sdk::{transaction_builder::TransactionFactory, types::PeerId};
+use aptos_sdk::{bcs, transaction_builder::TransactionFactory, types::PeerId};
+use aptos_types::on_chain_config::OnChainConsensusConfig;
 use async_trait::async_trait;
 use futures::future::join_all;
 use rand::{rngs::StdRng, SeedableRng};
@@ -48,6 +62,10 @@ use tokio::runtime::{Handle, Runtime};
 
 const WARMUP_DURATION_FRACTION: f32 = 0.07;
 const COOLDOWN_DURATION_FRACTION: f32 = 0.04;
+// how often the nodes' memory is sampled, if the success criteria bound its growth
+const MEMORY_SAMPLE_INTERVAL: Duration = Duration::from_secs(60);
+// how long the nodes affected by a chaos get to recover once it is healed
+pub(crate) const CHAOS_RECOVERY_TIMEOUT: Duration = Duration::from_secs(300);
 
 async fn batch_update(
     ctx: &mut NetworkContext<'_>,
@@ -183,6 +201,8 @@ pub enum LoadDestination {
     AllNodes,
     AllValidators,
     AllFullnodes,
+    // only the public fullnodes, which most user traffic lands on
+    AllPublicFullnodes,
     // Send to AllFullnodes, if any exist, otherwise to AllValidators
     FullnodesOtherwiseValidators,
     Peers(Vec<PeerId>),
@@ -196,11 +216,16 @@ impl LoadDestination {
         let swarm = swarm.read().await;
         let all_validators = swarm.validators().map(|v| v.peer_id()).collect::<Vec<_>>();
         let all_fullnodes = swarm.full_nodes().map(|v| v.peer_id()).collect::<Vec<_>>();
+        let all_public_fullnodes = swarm
+            .public_fullnodes()
+            .map(|v| v.peer_id())
+            .collect::<Vec<_>>();
 
         match self {
             LoadDestination::AllNodes => [&all_validators[..], &all_fullnodes[..]].concat(),
             LoadDestination::AllValidators => all_validators,
             LoadDestination::AllFullnodes => all_fullnodes,
+            LoadDestination::AllPublicFullnodes => all_public_fullnodes,
             LoadDestination::FullnodesOtherwiseValidators => {
                 if all_fullnodes.is_empty() {
                     all_validators
@@ -375,6 +400,10 @@ impl dyn NetworkLoadTest + '_ {
             .context("start emitter job")?;
 
         let total_start = PhaseTimingStart::now();
+        let memory_sampler = ctx
+            .success_criteria
+            .max_memory_growth_pct()
+            .map(|_| MemorySampler::start(ctx.swarm.clone(), MEMORY_SAMPLE_INTERVAL));
 
         let warmup_duration = duration.mul_f32(warmup_duration_fraction);
         let cooldown_duration = duration.mul_f32(cooldown_duration_fraction);
@@ -383,6 +412,9 @@ impl dyn NetworkLoadTest + '_ {
 
         job = job.periodic_stat_forward(warmup_duration, 60).await;
         info!("{}s warmup finished", warmup_duration.as_secs());
+        if let Some(memory_sampler) = &memory_sampler {
+            memory_sampler.sample_baseline().await;
+        }
 
         let mut phase_timing = Vec::new();
         let mut phase_start_network_state = Vec::new();
@@ -436,6 +468,11 @@ impl dyn NetworkLoadTest + '_ {
         let stats_by_phase = job.stop_job().await;
 
         info!("Stopped job");
+        if let Some(memory_sampler) = memory_sampler {
+            let memory_timeline = memory_sampler.stop().await;
+            memory_timeline.report(ctx.report, self.name());
+            ctx.memory_timeline = Some(memory_timeline);
+        }
         info!("Warmup stats: {}", stats_by_phase[0].rate());
 
         let mut stats: Option<TxnStats> = None;
@@ -559,6 +596,8 @@ pub struct CompositeNetworkTest {
     // This is the main test, return values from this test are used in setup, and
     // only it's test function is called.
     pub test: Box<dyn NetworkTest>,
+    // The tags the composite test is selected by, if not those of the main test
+    pub tags: Option<&'static [&'static str]>,
 }
 
 impl CompositeNetworkTest {
@@ -569,6 +608,7 @@ impl CompositeNetworkTest {
         CompositeNetworkTest {
             wrappers: vec![Box::new(wrapper)],
             test: Box::new(test),
+            tags: None,
         }
     }
 
@@ -584,8 +624,16 @@ impl CompositeNetworkTest {
         CompositeNetworkTest {
             wrappers: vec![Box::new(wrapper1), Box::new(wrapper2)],
             test: Box::new(test),
+            tags: None,
         }
     }
+
+    /// Selected by the given tags rather than those of the main test, e.g. as the main test is
+    /// only land-blocking in some of the suites
+    pub fn with_tags(mut self, tags: &'static [&'static str]) -> Self {
+        self.tags = Some(tags);
+        self
+    }
 }
 
 #[async_trait]
@@ -614,6 +662,10 @@ impl Test for CompositeNetworkTest {
     fn name(&self) -> &'static str {
         "CompositeNetworkTest"
     }
+
+    fn tags(&self) -> &'static [&'static str] {
+        self.tags.unwrap_or_else(|| self.test.tags())
+    }
 }
 
 pub(crate) fn generate_onchain_config_blob(data: &[u8]) -> String {
@@ -633,3 +685,63 @@ pub(crate) fn generate_onchain_config_blob(data: &[u8]) -> String {
     write!(buf, "]").unwrap();
     buf
 }
+
+/// Sets the consensus config with a governance proposal all the validators vote for, which
+/// reconfigures the chain once it is executed
+pub(crate) async fn set_consensus_config_by_proposal(
+    swarm: &tokio::sync::RwLock<Box<dyn Swarm>>,
+    config: &OnChainConsensusConfig,
+) -> Result<ProposalOutcome> {
+    let script = compile_proposal_script(&format!(
+        r#"
+    script {{
+        use aptos_framework::aptos_governance;
+        use aptos_framework::consensus_config;
+        fun main(proposal_id: u64) {{
+            let framework_signer = aptos_governance::resolve(proposal_id, @0000000000000000000000000000000000000000000000000000000000000001);
+            let config_bytes = {};
+            consensus_config::set(&framework_signer, config_bytes);
+        }}
+    }}
+    "#,
+        generate_onchain_config_blob(&bcs::to_bytes(config)?)
+    ))?;
+    // not to hold the swarm for the whole voting period
+    let (chain_info, voters) = {
+        let swarm = swarm.read().await;
+        let ids: Vec<_> = swarm.validators().map(|v| v.peer_id()).collect();
+        let mut voters = vec![];
+        for id in ids {
+            voters.push(swarm.validator_owner_account(id).await?);
+        }
+        (swarm.chain_info(), voters)
+    };
+    let outcome = chain_info
+        .execute_governance_proposal(&script, vec![], &voters)
+        .await?;
+    info!(
+        "Set the consensus config with proposal {}, in epoch {}",
+        outcome.proposal_id, outcome.epoch
+    );
+    Ok(outcome)
+}
+
+/// The validator to inject a fault into, the first by peer id so that every run picks the same
+/// one, and the other validators. The others keep a quorum without it from 4 validators on.
+pub(crate) async fn pick_faulty_validator(
+    swarm: &tokio::sync::RwLock<Box<dyn Swarm>>,
+    test_name: &str,
+) -> Result<(PeerId, Vec<PeerId>)> {
+    let mut validators: Vec<PeerId> = swarm
+        .read()
+        .await
+        .validators()
+        .map(|v| v.peer_id())
+        .collect();
+    if validators.len() < 4 {
+        anyhow::bail!("{} requires >= 4 validators", test_name);
+    }
+    validators.sort();
+    let faulty = validators.remove(0);
+    Ok((faulty, validators))
+}
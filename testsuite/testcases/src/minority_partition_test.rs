@@ -0,0 +1,89 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{LoadDestination, NetworkLoadTest, CHAOS_RECOVERY_TIMEOUT};
+use aptos_forge::{
+    heal_and_verify, NetworkContext, NetworkContextSynchronizer, NetworkTest, PartitionDirection,
+    SwarmChaos, SwarmGroupPartition, Test,
+};
+use aptos_sdk::types::PeerId;
+use async_trait::async_trait;
+
+/// Cuts the largest minority that can't stop consensus, fewer than a third of the validators, off
+/// from the rest. The majority must keep making progress under load, and the minority must catch
+/// up once the partition is healed. The minority is group A of the partition, so with
+/// [PartitionDirection::FromAToB] it hears the majority but can't answer, and with
+/// [PartitionDirection::FromBToA] it is heard but hears nothing, which leaves it to time out.
+pub struct MinorityPartitionTest {
+    pub direction: PartitionDirection,
+}
+
+impl Test for MinorityPartitionTest {
+    fn name(&self) -> &'static str {
+        match self.direction {
+            PartitionDirection::Both => "network::minority-partition-test",
+            PartitionDirection::FromAToB => "network::minority-partition-outgoing-test",
+            PartitionDirection::FromBToA => "network::minority-partition-incoming-test",
+        }
+    }
+}
+
+impl MinorityPartitionTest {
+    async fn partition<'a>(&self, ctx: &NetworkContext<'a>) -> SwarmGroupPartition {
+        let mut validators: Vec<PeerId> = ctx
+            .swarm
+            .read()
+            .await
+            .validators()
+            .map(|v| v.peer_id())
+            .collect();
+        validators.sort();
+        let minority_size = (validators.len() - 1) / 3;
+        let group_b = validators.split_off(minority_size);
+        SwarmGroupPartition {
+            group_a: validators,
+            group_b,
+            direction: self.direction,
+        }
+    }
+}
+
+#[async_trait]
+impl NetworkLoadTest for MinorityPartitionTest {
+    async fn setup<'a>(&self, ctx: &mut NetworkContext<'a>) -> anyhow::Result<LoadDestination> {
+        let partition = self.partition(ctx).await;
+        if partition.group_a.is_empty() {
+            anyhow::bail!("minority partition test requires >= 4 validators");
+        }
+        ctx.swarm
+            .write()
+            .await
+            .inject_chaos(SwarmChaos::GroupPartition(partition.clone()))
+            .await?;
+
+        let msg = format!(
+            "Partitioned {} validators from the other {} {}",
+            partition.group_a.len(),
+            partition.group_b.len(),
+            partition.direction
+        );
+        println!("{}", msg);
+        ctx.report.report_text(msg);
+        // the minority can't commit, so only send the load to the majority
+        Ok(LoadDestination::Peers(partition.group_b))
+    }
+
+    async fn finish<'a>(&self, ctx: &mut NetworkContext<'a>) -> anyhow::Result<()> {
+        let chaos = SwarmChaos::GroupPartition(self.partition(ctx).await);
+        let recovery = heal_and_verify(&ctx.swarm, &chaos, CHAOS_RECOVERY_TIMEOUT).await?;
+        recovery.report(&mut ctx.report, self.name());
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl NetworkTest for MinorityPartitionTest {
+    async fn run<'a>(&self, ctx: NetworkContextSynchronizer<'a>) -> anyhow::Result<()> {
+        <dyn NetworkLoadTest>::run(self, ctx).await
+    }
+}
@@ -3,14 +3,14 @@
 
 use crate::{multi_region_network_test::chunk_peers, LoadDestination, NetworkLoadTest};
 use aptos_forge::{
-    GroupCpuStress, NetworkContext, NetworkContextSynchronizer, NetworkTest, Swarm, SwarmChaos,
-    SwarmCpuStress, SwarmExt, Test,
+    inject_chaos_for, GroupCpuStress, NetworkContext, NetworkContextSynchronizer, NetworkTest,
+    Swarm, SwarmChaos, SwarmCpuStress, SwarmExt, SwarmPeerCpuStress, Test, TestReport,
 };
 use aptos_logger::info;
 use aptos_types::PeerId;
 use async_trait::async_trait;
 use rand::Rng;
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 async fn add_execution_delay(
     swarm: Arc<tokio::sync::RwLock<Box<(dyn Swarm)>>>,
@@ -319,3 +319,53 @@ impl NetworkTest for CpuChaosTest {
         <dyn NetworkLoadTest>::run(self, ctx).await
     }
 }
+
+/// Stresses the CPUs of a third of the validators for as long as the load runs, as if they had
+/// noisy neighbors. How much that may slow down commits is up to the success criteria.
+pub struct ValidatorCpuStressTest {
+    pub workers: u32,
+    pub load_percent: u32,
+}
+
+impl Test for ValidatorCpuStressTest {
+    fn name(&self) -> &'static str {
+        "ValidatorCpuStress"
+    }
+}
+
+#[async_trait]
+impl NetworkLoadTest for ValidatorCpuStressTest {
+    async fn test(
+        &self,
+        swarm: Arc<tokio::sync::RwLock<Box<dyn Swarm>>>,
+        report: &mut TestReport,
+        duration: Duration,
+    ) -> anyhow::Result<()> {
+        let mut validators: Vec<PeerId> = swarm
+            .read()
+            .await
+            .validators()
+            .map(|v| v.peer_id())
+            .collect();
+        validators.sort();
+        validators.truncate(validators.len() / 3);
+        if validators.is_empty() {
+            anyhow::bail!("validator cpu stress test requires >= 3 validators");
+        }
+        let chaos = SwarmChaos::PeerCpuStress(SwarmPeerCpuStress {
+            peers: validators,
+            workers: self.workers,
+            load_percent: self.load_percent,
+        });
+        let window = inject_chaos_for(&swarm, chaos, duration).await?;
+        window.report(report);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl NetworkTest for ValidatorCpuStressTest {
+    async fn run<'a>(&self, ctx: NetworkContextSynchronizer<'a>) -> anyhow::Result<()> {
+        <dyn NetworkLoadTest>::run(self, ctx).await
+    }
+}
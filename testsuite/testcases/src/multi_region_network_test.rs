@@ -95,13 +95,14 @@ fn create_link_stats_table_with_peer_groups(
 }
 
 // A map of "source" regions to a map of "destination" region to (bandwidth, latency)
-type LinkStatsTable = BTreeMap<String, BTreeMap<String, (u64, f64)>>;
+pub(crate) type LinkStatsTable = BTreeMap<String, BTreeMap<String, (u64, f64)>>;
 // A map of "source" regions to a tuple of (list of peers, map of "destination" region to (bandwidth, latency))
-type LinkStatsTableWithPeerGroups = Vec<(String, Vec<PeerId>, BTreeMap<String, (u64, f64)>)>;
+pub(crate) type LinkStatsTableWithPeerGroups =
+    Vec<(String, Vec<PeerId>, BTreeMap<String, (u64, f64)>)>;
 
 #[derive(Clone)]
 pub struct InterRegionNetEmConfig {
-    delay_jitter_ms: u64,
+    pub(crate) delay_jitter_ms: u64,
     delay_correlation_percentage: u64,
     loss_percentage: u64,
     loss_correlation_percentage: u64,
@@ -120,16 +121,21 @@ impl Default for InterRegionNetEmConfig {
 
 impl InterRegionNetEmConfig {
     // Creates GroupNetEm for inter-region network chaos
-    fn build(&self, peer_groups: &LinkStatsTableWithPeerGroups) -> Vec<GroupNetEm> {
+    pub(crate) fn build(&self, peer_groups: &LinkStatsTableWithPeerGroups) -> Vec<GroupNetEm> {
         let group_netems: Vec<GroupNetEm> = peer_groups
             .iter()
             .combinations(2)
             .flat_map(|comb| {
-                let (from_region, from_chunk, stats) = &comb[0];
-                let (to_region, to_chunk, _) = &comb[1];
-
-                let (bandwidth, rtt_latency) = stats.get(to_region).unwrap();
+                let (from_region, from_chunk, from_stats) = &comb[0];
+                let (to_region, to_chunk, to_stats) = &comb[1];
+
+                // each way by its own stats, falling back to those of the other way
+                let &(bandwidth, rtt_latency) = from_stats.get(to_region).unwrap();
+                let &(reverse_bandwidth, reverse_rtt_latency) = to_stats
+                    .get(from_region)
+                    .unwrap_or(&(bandwidth, rtt_latency));
                 let hop_latency = rtt_latency / 2.0;
+                let reverse_hop_latency = reverse_rtt_latency / 2.0;
                 let netems = [
                     GroupNetEm {
                         name: format!("{}-to-{}-netem", from_region, to_region),
@@ -140,18 +146,18 @@ impl InterRegionNetEmConfig {
                         delay_correlation_percentage: self.delay_correlation_percentage,
                         loss_percentage: self.loss_percentage,
                         loss_correlation_percentage: self.loss_correlation_percentage,
-                        rate_in_mbps: *bandwidth / 1e6 as u64,
+                        rate_in_mbps: bandwidth / 1e6 as u64,
                     },
                     GroupNetEm {
                         name: format!("{}-to-{}-netem", to_region, from_region),
                         source_nodes: to_chunk.to_vec(),
                         target_nodes: from_chunk.to_vec(),
-                        delay_latency_ms: hop_latency as u64,
+                        delay_latency_ms: reverse_hop_latency as u64,
                         delay_jitter_ms: self.delay_jitter_ms,
                         delay_correlation_percentage: self.delay_correlation_percentage,
                         loss_percentage: self.loss_percentage,
                         loss_correlation_percentage: self.loss_correlation_percentage,
-                        rate_in_mbps: *bandwidth / 1e6 as u64,
+                        rate_in_mbps: reverse_bandwidth / 1e6 as u64,
                     },
                 ];
                 info!("inter-region netem {:?}", netems);
@@ -188,7 +194,7 @@ impl Default for IntraRegionNetEmConfig {
 }
 
 impl IntraRegionNetEmConfig {
-    fn build(&self, peer_groups: LinkStatsTableWithPeerGroups) -> Vec<GroupNetEm> {
+    pub(crate) fn build(&self, peer_groups: LinkStatsTableWithPeerGroups) -> Vec<GroupNetEm> {
         let group_netems: Vec<GroupNetEm> = peer_groups
             .iter()
             .map(|(region, chunk, _)| {
@@ -304,7 +310,14 @@ pub fn create_multi_region_swarm_network_chaos(
         all_peers,
         &network_emulation_config.link_stats_table,
     );
+    create_swarm_netem(peer_groups, &network_emulation_config)
+}
 
+/// Creates the SwarmNetEm of the given peer groups, within and between their regions
+pub(crate) fn create_swarm_netem(
+    peer_groups: LinkStatsTableWithPeerGroups,
+    network_emulation_config: &MultiRegionNetworkEmulationConfig,
+) -> SwarmNetEm {
     // Create the inter and intra network emulation configs
     let inter_region_netem = network_emulation_config
         .inter_region_config
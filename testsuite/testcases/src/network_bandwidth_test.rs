@@ -23,6 +23,10 @@ impl Test for NetworkBandwidthTest {
     fn name(&self) -> &'static str {
         "network::bandwidth-test"
     }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["network", "chaos"]
+    }
 }
 
 #[async_trait]
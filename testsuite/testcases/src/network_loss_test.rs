@@ -1,10 +1,12 @@
 // Copyright © Aptos Foundation
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::{LoadDestination, NetworkLoadTest};
+use crate::{LoadDestination, NetworkLoadTest, CHAOS_RECOVERY_TIMEOUT};
 use aptos_forge::{
-    NetworkContext, NetworkContextSynchronizer, NetworkTest, SwarmChaos, SwarmNetworkLoss, Test,
+    heal_and_verify, NetworkContext, NetworkContextSynchronizer, NetworkTest, PartitionDirection,
+    SwarmChaos, SwarmNetworkLoss, SwarmPeerLoss, Test,
 };
+use aptos_logger::info;
 use async_trait::async_trait;
 
 /// This is deprecated. Use [crate::multi_region_network_test::MultiRegionNetworkEmulationTest] instead
@@ -18,6 +20,10 @@ impl Test for NetworkLossTest {
     fn name(&self) -> &'static str {
         "network::loss-test"
     }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["network", "chaos"]
+    }
 }
 
 #[async_trait]
@@ -29,6 +35,7 @@ impl NetworkLoadTest for NetworkLossTest {
             .inject_chaos(SwarmChaos::Loss(SwarmNetworkLoss {
                 loss_percentage: LOSS_PERCENTAGE,
                 correlation_percentage: CORRELATION_PERCENTAGE,
+                direction: PartitionDirection::Both,
             }))
             .await?;
 
@@ -42,14 +49,13 @@ impl NetworkLoadTest for NetworkLossTest {
     }
 
     async fn finish<'a>(&self, ctx: &mut NetworkContext<'a>) -> anyhow::Result<()> {
-        ctx.swarm
-            .write()
-            .await
-            .remove_chaos(SwarmChaos::Loss(SwarmNetworkLoss {
-                loss_percentage: LOSS_PERCENTAGE,
-                correlation_percentage: CORRELATION_PERCENTAGE,
-            }))
-            .await?;
+        let chaos = SwarmChaos::Loss(SwarmNetworkLoss {
+            loss_percentage: LOSS_PERCENTAGE,
+            correlation_percentage: CORRELATION_PERCENTAGE,
+            direction: PartitionDirection::Both,
+        });
+        let recovery = heal_and_verify(&ctx.swarm, &chaos, CHAOS_RECOVERY_TIMEOUT).await?;
+        recovery.report(&mut ctx.report, self.name());
         Ok(())
     }
 }
@@ -60,3 +66,62 @@ impl NetworkTest for NetworkLossTest {
         <dyn NetworkLoadTest>::run(self, ctx).await
     }
 }
+
+/// Drops the given share of the packets every validator sends, for as long as the test it wraps
+/// runs. Unlike [NetworkLossTest], it composes with the delays the wrapped test injects.
+pub struct ValidatorLoss {
+    pub loss_percentage: u64,
+    pub correlation_percentage: u64,
+}
+
+impl ValidatorLoss {
+    pub fn new(loss_percentage: u64) -> Self {
+        Self {
+            loss_percentage,
+            correlation_percentage: CORRELATION_PERCENTAGE,
+        }
+    }
+
+    async fn chaos<'a>(&self, ctx: &NetworkContext<'a>) -> SwarmChaos {
+        SwarmChaos::PeerLoss(SwarmPeerLoss {
+            peers: ctx
+                .swarm
+                .read()
+                .await
+                .validators()
+                .map(|v| v.peer_id())
+                .collect(),
+            loss_percentage: self.loss_percentage,
+            correlation_percentage: self.correlation_percentage,
+        })
+    }
+}
+
+impl Test for ValidatorLoss {
+    fn name(&self) -> &'static str {
+        "network::validator-loss"
+    }
+}
+
+#[async_trait]
+impl NetworkLoadTest for ValidatorLoss {
+    async fn setup<'a>(&self, ctx: &mut NetworkContext<'a>) -> anyhow::Result<LoadDestination> {
+        let chaos = self.chaos(ctx).await;
+        ctx.swarm.write().await.inject_chaos(chaos).await?;
+
+        let msg = format!(
+            "Injected {}% loss with {}% correlation to all validators",
+            self.loss_percentage, self.correlation_percentage,
+        );
+        info!("{}", msg);
+        ctx.report.report_text(msg);
+        Ok(LoadDestination::FullnodesOtherwiseValidators)
+    }
+
+    async fn finish<'a>(&self, ctx: &mut NetworkContext<'a>) -> anyhow::Result<()> {
+        let chaos = self.chaos(ctx).await;
+        let recovery = heal_and_verify(&ctx.swarm, &chaos, CHAOS_RECOVERY_TIMEOUT).await?;
+        recovery.report(&mut ctx.report, self.name());
+        Ok(())
+    }
+}
@@ -1,10 +1,10 @@
 // Copyright © Aptos Foundation
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::{LoadDestination, NetworkLoadTest};
+use crate::{LoadDestination, NetworkLoadTest, CHAOS_RECOVERY_TIMEOUT};
 use aptos_forge::{
-    NetworkContext, NetworkContextSynchronizer, NetworkTest, SwarmChaos, SwarmNetworkPartition,
-    Test,
+    heal_and_verify, NetworkContext, NetworkContextSynchronizer, NetworkTest, PartitionDirection,
+    SwarmChaos, SwarmNetworkPartition, Test,
 };
 use async_trait::async_trait;
 
@@ -28,6 +28,7 @@ impl NetworkLoadTest for NetworkPartitionTest {
             .await
             .inject_chaos(SwarmChaos::Partition(SwarmNetworkPartition {
                 partition_percentage: PARTITION_PERCENTAGE,
+                direction: PartitionDirection::Both,
             }))
             .await?;
 
@@ -49,13 +50,12 @@ impl NetworkLoadTest for NetworkPartitionTest {
     }
 
     async fn finish<'a>(&self, ctx: &mut NetworkContext<'a>) -> anyhow::Result<()> {
-        ctx.swarm
-            .write()
-            .await
-            .remove_chaos(SwarmChaos::Partition(SwarmNetworkPartition {
-                partition_percentage: PARTITION_PERCENTAGE,
-            }))
-            .await?;
+        let chaos = SwarmChaos::Partition(SwarmNetworkPartition {
+            partition_percentage: PARTITION_PERCENTAGE,
+            direction: PartitionDirection::Both,
+        });
+        let recovery = heal_and_verify(&ctx.swarm, &chaos, CHAOS_RECOVERY_TIMEOUT).await?;
+        recovery.report(&mut ctx.report, self.name());
         Ok(())
     }
 }
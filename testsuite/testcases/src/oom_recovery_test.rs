@@ -0,0 +1,123 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::pick_faulty_validator;
+use anyhow::{bail, Context};
+use aptos_forge::{
+    inject_chaos_for, HealthCheckError, NetworkContextSynchronizer, NetworkTest, NodeExt, Result,
+    SwarmChaos, SwarmPeerMemoryStress, Test,
+};
+use aptos_logger::info;
+use async_trait::async_trait;
+use std::{
+    ops::DerefMut,
+    time::{Duration, Instant},
+};
+
+const STRESS_DURATION: Duration = Duration::from_secs(90);
+// from the end of the stress, until the validator votes again
+const RECOVERY_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Stresses the memory of one validator until its container is OOMKilled, and checks that it
+/// rejoins consensus on its own within 300s of the stress ending.
+pub struct OomRecoveryTest {
+    // the memory stress allocates this much on top of what the validator uses
+    pub size_mb: u64,
+}
+
+impl Test for OomRecoveryTest {
+    fn name(&self) -> &'static str {
+        "chaos::oom-recovery-test"
+    }
+}
+
+#[async_trait]
+impl NetworkTest for OomRecoveryTest {
+    async fn run<'a>(&self, ctxa: NetworkContextSynchronizer<'a>) -> Result<()> {
+        let mut ctx_locker = ctxa.ctx.lock().await;
+        let ctx = ctx_locker.deref_mut();
+
+        let (stressed, _) = pick_faulty_validator(&ctx.swarm, self.name()).await?;
+
+        let chaos = SwarmChaos::PeerMemoryStress(SwarmPeerMemoryStress {
+            peers: vec![stressed],
+            size_mb: self.size_mb,
+        });
+        let swarm = ctx.swarm.clone();
+        let watch_for_oom = async {
+            let deadline = Instant::now() + STRESS_DURATION;
+            while Instant::now() < deadline {
+                {
+                    let swarm = swarm.read().await;
+                    let validator = swarm
+                        .validator(stressed)
+                        .context("Stressed validator is missing from the swarm")?;
+                    if let Err(HealthCheckError::OomKilledByChaos(error)) =
+                        validator.health_check().await
+                    {
+                        info!("{} was OOMKilled: {}", validator.name(), error);
+                        return Ok::<_, anyhow::Error>(true);
+                    }
+                }
+                tokio::time::sleep(Duration::from_secs(5)).await;
+            }
+            Ok(false)
+        };
+        let (window, oom_killed) = tokio::join!(
+            inject_chaos_for(&ctx.swarm, chaos, STRESS_DURATION),
+            watch_for_oom
+        );
+        let window = window?;
+        window.report(&mut ctx.report);
+        if !oom_killed? {
+            bail!(
+                "Validator {} was not OOMKilled by a {}MB memory stress",
+                stressed,
+                self.size_mb
+            );
+        }
+
+        let swarm = ctx.swarm.read().await;
+        let validator = swarm
+            .validator(stressed)
+            .context("Stressed validator is missing from the swarm")?;
+        let start = Instant::now();
+        let deadline = start + RECOVERY_TIMEOUT;
+        validator
+            .wait_until_healthy(deadline)
+            .await
+            .with_context(|| format!("{} did not recover from its OOMKill", validator.name()))?;
+        // votes only count once it is back, as the round metric restarts with the node
+        let voted_round = validator
+            .get_metric_i64("aptos_consensus_last_voted_round")
+            .await?
+            .unwrap_or_default();
+        loop {
+            let round = validator
+                .get_metric_i64("aptos_consensus_last_voted_round")
+                .await?
+                .unwrap_or_default();
+            if round > voted_round {
+                break;
+            }
+            if Instant::now() > deadline {
+                bail!(
+                    "Validator {} did not vote within {:?} of its OOMKill",
+                    validator.name(),
+                    RECOVERY_TIMEOUT
+                );
+            }
+            tokio::time::sleep(Duration::from_secs(5)).await;
+        }
+        let recovery = start.elapsed();
+        ctx.report
+            .report_metric(self.name(), "oom_recovery_s", recovery.as_secs_f64());
+        ctx.report.report_text(format!(
+            "{} rejoined consensus {:.1}s after a {}MB memory stress OOMKilled it",
+            validator.name(),
+            recovery.as_secs_f64(),
+            self.size_mb
+        ));
+        Ok(())
+    }
+}
@@ -22,6 +22,7 @@ impl NetworkTest for PartialNodesDown {
         let mut ctx_locker = ctx.ctx.lock().await;
         let ctx = ctx_locker.deref_mut();
         let runtime = Runtime::new()?;
+        let restart_timeout = ctx.node_restart_timeout;
         let duration = Duration::from_secs(120);
         let all_validators = ctx
             .swarm
@@ -48,7 +49,7 @@ impl NetworkTest for PartialNodesDown {
             let swarm = ctx.swarm.read().await;
             let node = swarm.validator(*n).unwrap();
             println!("Node {} is going to restart", node.name());
-            runtime.block_on(node.start())?;
+            runtime.block_on(node.restart(restart_timeout))?;
         }
 
         Ok(())
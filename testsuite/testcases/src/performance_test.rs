@@ -12,6 +12,10 @@ impl Test for PerformanceBenchmark {
     fn name(&self) -> &'static str {
         "performance benchmark"
     }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["perf", "benchmark"]
+    }
 }
 
 impl NetworkLoadTest for PerformanceBenchmark {}
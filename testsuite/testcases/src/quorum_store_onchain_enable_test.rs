@@ -1,16 +1,11 @@
 // Copyright © Aptos Foundation
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::{generate_onchain_config_blob, NetworkLoadTest};
+use crate::{set_consensus_config_by_proposal, NetworkLoadTest};
 use anyhow::Ok;
-use movement::test::CliTestFramework;
-use aptos_forge::{NetworkContextSynchronizer, NetworkTest, NodeExt, SwarmExt, Test};
+use aptos_forge::{NetworkContextSynchronizer, NetworkTest, SwarmExt, Test};
 use aptos_logger::info;
-use aptos_sdk::bcs;
-use aptos_types::{
-    account_config::CORE_CODE_ADDRESS,
-    on_chain_config::{ConsensusConfigV1, OnChainConsensusConfig},
-};
+use aptos_types::on_chain_config::{ConsensusConfigV1, OnChainConsensusConfig};
 use async_trait::async_trait;
 use std::{sync::Arc, time::Duration};
 
@@ -32,42 +27,15 @@ impl NetworkLoadTest for QuorumStoreOnChainEnableTest {
         _report: &mut aptos_forge::TestReport,
         duration: std::time::Duration,
     ) -> anyhow::Result<()> {
-        let faucet_endpoint: reqwest::Url = "http://localhost:8081".parse().unwrap();
-        let (rest_client, rest_api_endpoint) = {
-            let swarm = swarm.read().await;
-            let first_validator = swarm.validators().next().unwrap();
-            let rest_client = first_validator.rest_client();
-            let rest_api_endpoint = first_validator.rest_api_endpoint();
-            (rest_client, rest_api_endpoint)
-        };
-        let mut cli = CliTestFramework::new(
-            rest_api_endpoint,
-            faucet_endpoint,
-            /*num_cli_accounts=*/ 0,
-        )
-        .await;
+        let rest_client = swarm.read().await.rest_client();
 
         tokio::time::sleep(duration / 2).await;
 
-        let root_cli_index = {
-            let root_account = swarm.read().await.chain_info().root_account();
-            cli.add_account_with_address_to_cli(
-                root_account.private_key().clone(),
-                root_account.address(),
-            )
-        };
-
-        let current_consensus_config: OnChainConsensusConfig = bcs::from_bytes(
-            &rest_client
-                .get_account_resource_bcs::<Vec<u8>>(
-                    CORE_CODE_ADDRESS,
-                    "0x1::consensus_config::ConsensusConfig",
-                )
-                .await
-                .unwrap()
-                .into_inner(),
-        )
-        .unwrap();
+        let current_consensus_config = rest_client
+            .get_consensus_config()
+            .await
+            .unwrap()
+            .into_inner();
 
         let inner = match current_consensus_config {
             OnChainConsensusConfig::V1(inner) => inner,
@@ -78,23 +46,7 @@ impl NetworkLoadTest for QuorumStoreOnChainEnableTest {
         // Change to V2
         let new_consensus_config = OnChainConsensusConfig::V2(ConsensusConfigV1 { ..inner });
 
-        let update_consensus_config_script = format!(
-            r#"
-    script {{
-        use aptos_framework::aptos_governance;
-        use aptos_framework::consensus_config;
-        fun main(core_resources: &signer) {{
-            let framework_signer = aptos_governance::get_signer_testnet_only(core_resources, @0000000000000000000000000000000000000000000000000000000000000001);
-            let config_bytes = {};
-            consensus_config::set(&framework_signer, config_bytes);
-        }}
-    }}
-    "#,
-            generate_onchain_config_blob(&bcs::to_bytes(&new_consensus_config).unwrap())
-        );
-
-        cli.run_script_with_default_framework(root_cli_index, &update_consensus_config_script)
-            .await?;
+        set_consensus_config_by_proposal(&swarm, &new_consensus_config).await?;
 
         tokio::time::sleep(duration / 2).await;
 
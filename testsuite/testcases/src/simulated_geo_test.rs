@@ -0,0 +1,407 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{
+    multi_region_network_test::{
+        create_swarm_netem, LinkStatsTable, LinkStatsTableWithPeerGroups,
+        MultiRegionNetworkEmulationConfig,
+    },
+    LoadDestination, NetworkLoadTest,
+};
+use anyhow::bail;
+use aptos_forge::{
+    NetworkContext, NetworkContextSynchronizer, NetworkTest, Result, Swarm, SwarmChaos, SwarmNetEm,
+    Test,
+};
+use aptos_logger::info;
+use aptos_types::PeerId;
+use async_trait::async_trait;
+
+// ChaosMesh can't simulate more regions than this
+const MAX_REGIONS: usize = 4;
+// between any two regions, as in the link stats of the multi-region tests
+const INTER_REGION_BITRATE_BPS: u64 = 300_000_000;
+
+/// Regions over which the validators are spread, with the share of the validators in each, and
+/// the round trips between them. Realized as the network emulation of a
+/// [MultiRegionNetworkEmulationConfig], so with its bandwidth and loss within and between the
+/// regions, see [NetworkTopology::inject].
+#[derive(Clone)]
+pub struct NetworkTopology {
+    // in order, which is the order the validators are assigned in
+    regions: Vec<(String, f64)>,
+    // its link stats are the round trips between the regions
+    emulation: MultiRegionNetworkEmulationConfig,
+    // whether the round trips may differ between the two ways of a link
+    asymmetric: bool,
+}
+
+impl Default for NetworkTopology {
+    fn default() -> Self {
+        Self {
+            regions: vec![],
+            emulation: MultiRegionNetworkEmulationConfig {
+                link_stats_table: LinkStatsTable::new(),
+                ..Default::default()
+            },
+            asymmetric: false,
+        }
+    }
+}
+
+impl NetworkTopology {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Spread over four regions roughly as mainnet's validators are, with typical round trips
+    /// between them
+    pub fn mainnet() -> Self {
+        Self::new()
+            .with_region("eu-west", 0.4)
+            .with_region("us-east", 0.3)
+            .with_region("ap-northeast", 0.2)
+            .with_region("ap-south", 0.1)
+            .with_round_trip("us-east", "eu-west", 80)
+            .with_round_trip("us-east", "ap-northeast", 160)
+            .with_round_trip("us-east", "ap-south", 200)
+            .with_round_trip("eu-west", "ap-northeast", 220)
+            .with_round_trip("eu-west", "ap-south", 120)
+            .with_round_trip("ap-northeast", "ap-south", 70)
+            .with_jitter_ms(5)
+    }
+
+    /// A region with the given share of the validators, relative to those of the other regions
+    pub fn with_region(mut self, name: &str, share: f64) -> Self {
+        self.regions.push((name.to_string(), share));
+        self
+    }
+
+    /// The same round trip measured from either region
+    pub fn with_round_trip(self, region_a: &str, region_b: &str, round_trip_ms: u64) -> Self {
+        self.with_link(region_a, region_b, round_trip_ms).with_link(
+            region_b,
+            region_a,
+            round_trip_ms,
+        )
+    }
+
+    /// A latency of one way of a link only, which makes the topology asymmetric
+    pub fn with_one_way_latency(mut self, from: &str, to: &str, latency_ms: u64) -> Self {
+        self.asymmetric = true;
+        self.with_link(from, to, 2 * latency_ms)
+    }
+
+    pub fn with_jitter_ms(mut self, jitter_ms: u64) -> Self {
+        self.emulation.inter_region_config.delay_jitter_ms = jitter_ms;
+        self
+    }
+
+    // the link stats are of round trips, each way emulated as half of them
+    fn with_link(mut self, from: &str, to: &str, round_trip_ms: u64) -> Self {
+        self.emulation
+            .link_stats_table
+            .entry(from.to_string())
+            .or_default()
+            .insert(
+                to.to_string(),
+                (INTER_REGION_BITRATE_BPS, round_trip_ms as f64),
+            );
+        self
+    }
+
+    /// Fails if a region is invalid, a link between two regions lacks a latency either way, or
+    /// the two ways of a link differ in a topology that is not asymmetric
+    pub fn validate(&self) -> Result<()> {
+        if self.regions.len() < 2 || self.regions.len() > MAX_REGIONS {
+            bail!(
+                "A topology needs between 2 and {} regions, not {}",
+                MAX_REGIONS,
+                self.regions.len()
+            );
+        }
+        for (i, (name, share)) in self.regions.iter().enumerate() {
+            // it ends up in the names of the NetworkChaos
+            let valid_name = !name.is_empty()
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+            if !valid_name {
+                bail!("Invalid region name {:?}", name);
+            }
+            if share.is_nan() || *share <= 0.0 {
+                bail!("Region {} has a share of {}", name, share);
+            }
+            if self.regions[..i].iter().any(|(other, _)| other == name) {
+                bail!("Region {} is in the topology twice", name);
+            }
+        }
+        for (from, _) in &self.regions {
+            for (to, _) in &self.regions {
+                if from == to {
+                    continue;
+                }
+                let stats = match self.link_stats(from, to) {
+                    Some(stats) => stats,
+                    None => bail!("No latency from {} to {}", from, to),
+                };
+                if !self.asymmetric && self.link_stats(to, from) != Some(stats) {
+                    bail!(
+                        "Latency from {} to {} differs from the other way, in a symmetric topology",
+                        from,
+                        to
+                    );
+                }
+            }
+        }
+        for (from, stats) in &self.emulation.link_stats_table {
+            if let Some(to) = stats
+                .keys()
+                .find(|to| !self.has_region(from) || !self.has_region(to))
+            {
+                bail!("Latency from {} to {} is of an unknown region", from, to);
+            }
+        }
+        Ok(())
+    }
+
+    /// The latency one way, in milliseconds, as emulated
+    pub fn latency(&self, from: &str, to: &str) -> Option<u64> {
+        self.link_stats(from, to)
+            .map(|(_, round_trip_ms)| (round_trip_ms / 2.0) as u64)
+    }
+
+    fn link_stats(&self, from: &str, to: &str) -> Option<(u64, f64)> {
+        self.emulation
+            .link_stats_table
+            .get(from)
+            .and_then(|stats| stats.get(to))
+            .copied()
+    }
+
+    fn has_region(&self, name: &str) -> bool {
+        self.regions.iter().any(|(region, _)| region == name)
+    }
+
+    /// Assigns the validators to the regions, in order, by the largest remainder of their shares,
+    /// so that each region gets its share of them, rounded
+    pub fn assign(&self, validators: &[PeerId]) -> Result<Vec<(String, Vec<PeerId>)>> {
+        self.validate()?;
+        let total: f64 = self.regions.iter().map(|(_, share)| share).sum();
+        let quotas: Vec<f64> = self
+            .regions
+            .iter()
+            .map(|(_, share)| share / total * validators.len() as f64)
+            .collect();
+        let mut counts: Vec<usize> = quotas.iter().map(|quota| quota.floor() as usize).collect();
+        let mut by_remainder: Vec<usize> = (0..quotas.len()).collect();
+        // on a tie, the region listed first wins
+        by_remainder.sort_by(|a, b| {
+            let remainder = |i: usize| quotas[i] - quotas[i].floor();
+            remainder(*b).total_cmp(&remainder(*a)).then(a.cmp(b))
+        });
+        let assigned: usize = counts.iter().sum();
+        for i in by_remainder
+            .into_iter()
+            .take(validators.len().saturating_sub(assigned))
+        {
+            counts[i] += 1;
+        }
+
+        let mut remaining = validators;
+        Ok(self
+            .regions
+            .iter()
+            .zip(counts)
+            .map(|((name, _), count)| {
+                let (region_validators, rest) = remaining.split_at(count);
+                remaining = rest;
+                (name.clone(), region_validators.to_vec())
+            })
+            .collect())
+    }
+
+    /// The network emulation of the regions that have validators, within each of them and from
+    /// each to every other. The two ways of a link add up to its round trip.
+    pub fn network_emulation(&self, validators: &[PeerId]) -> Result<SwarmNetEm> {
+        let peer_groups: LinkStatsTableWithPeerGroups = self
+            .assign(validators)?
+            .into_iter()
+            .filter(|(_, region_validators)| !region_validators.is_empty())
+            .map(|(region, region_validators)| {
+                let stats = self.emulation.link_stats_table[&region].clone();
+                (region, region_validators, stats)
+            })
+            .collect();
+        Ok(create_swarm_netem(peer_groups, &self.emulation))
+    }
+
+    /// Spreads the validators of the swarm over the regions, ordered by peer id, and injects the
+    /// network emulation between and within the regions. Removing the returned chaos removes all
+    /// of it at once.
+    pub async fn inject(&self, swarm: &mut dyn Swarm) -> Result<SwarmChaos> {
+        let netem = self.network_emulation(&sorted_validators(swarm))?;
+        info!("Simulating the network topology with {}", netem);
+        let chaos = SwarmChaos::NetEm(netem);
+        swarm.inject_chaos(chaos.clone()).await?;
+        Ok(chaos)
+    }
+}
+
+fn sorted_validators(swarm: &dyn Swarm) -> Vec<PeerId> {
+    let mut validators: Vec<PeerId> = swarm.validators().map(|v| v.peer_id()).collect();
+    validators.sort();
+    validators
+}
+
+/// Spreads the validators over the regions of a topology, with the network emulation between the
+/// regions for the duration of the wrapped test. Defaults to mainnet's regions.
+pub struct SimulatedGeo {
+    pub topology: NetworkTopology,
+}
+
+impl SimulatedGeo {
+    pub fn new(topology: NetworkTopology) -> Self {
+        Self { topology }
+    }
+}
+
+impl Default for SimulatedGeo {
+    fn default() -> Self {
+        Self::new(NetworkTopology::mainnet())
+    }
+}
+
+impl Test for SimulatedGeo {
+    fn name(&self) -> &'static str {
+        "network::simulated-geo"
+    }
+}
+
+#[async_trait]
+impl NetworkLoadTest for SimulatedGeo {
+    async fn setup<'a>(&self, ctx: &mut NetworkContext<'a>) -> anyhow::Result<LoadDestination> {
+        let mut swarm = ctx.swarm.write().await;
+        self.topology.inject(swarm.as_mut()).await?;
+        Ok(LoadDestination::FullnodesOtherwiseValidators)
+    }
+
+    async fn finish<'a>(&self, ctx: &mut NetworkContext<'a>) -> anyhow::Result<()> {
+        let mut swarm = ctx.swarm.write().await;
+        // the same emulation as injected, as the validators are assigned by peer id
+        let netem = self
+            .topology
+            .network_emulation(&sorted_validators(swarm.as_ref()))?;
+        swarm.remove_chaos(SwarmChaos::NetEm(netem)).await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl NetworkTest for SimulatedGeo {
+    async fn run<'a>(&self, ctx: NetworkContextSynchronizer<'a>) -> anyhow::Result<()> {
+        <dyn NetworkLoadTest>::run(self, ctx).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        NetworkTopology::mainnet().validate().unwrap();
+
+        let topology = NetworkTopology::new()
+            .with_region("us-east", 1.0)
+            .with_region("eu-west", 1.0)
+            .with_round_trip("us-east", "eu-west", 80);
+        topology.validate().unwrap();
+        // a third region without latencies
+        topology
+            .clone()
+            .with_region("ap-south", 1.0)
+            .validate()
+            .unwrap_err();
+        topology
+            .clone()
+            .with_round_trip("us-east", "mars", 80)
+            .validate()
+            .unwrap_err();
+        NetworkTopology::new()
+            .with_region("us-east", 1.0)
+            .validate()
+            .unwrap_err();
+        NetworkTopology::new()
+            .with_region("US East", 1.0)
+            .with_region("eu-west", 1.0)
+            .with_round_trip("US East", "eu-west", 80)
+            .validate()
+            .unwrap_err();
+
+        // one way is slower, which is only fine if asymmetric
+        topology
+            .clone()
+            .with_link("us-east", "eu-west", 120)
+            .validate()
+            .unwrap_err();
+        topology
+            .with_one_way_latency("us-east", "eu-west", 60)
+            .validate()
+            .unwrap();
+    }
+
+    #[test]
+    fn test_assign() {
+        let validators: Vec<_> = (0..7).map(|_| PeerId::random()).collect();
+        let assignment = NetworkTopology::mainnet().assign(&validators).unwrap();
+        let counts: Vec<_> = assignment
+            .iter()
+            .map(|(region, validators)| (region.as_str(), validators.len()))
+            .collect();
+        // quotas of 2.8, 2.1, 1.4 and 0.7
+        assert_eq!(counts, vec![
+            ("eu-west", 3),
+            ("us-east", 2),
+            ("ap-northeast", 1),
+            ("ap-south", 1)
+        ]);
+        assert_eq!(assignment[0].1, validators[..3]);
+        assert_eq!(assignment[3].1, validators[6..]);
+    }
+
+    #[test]
+    fn test_network_emulation() {
+        let validators: Vec<_> = (0..3).map(|_| PeerId::random()).collect();
+        let topology = NetworkTopology::new()
+            .with_region("us-east", 2.0)
+            .with_region("eu-west", 1.0)
+            .with_round_trip("us-east", "eu-west", 80)
+            .with_one_way_latency("eu-west", "us-east", 50);
+        let netem = topology.network_emulation(&validators).unwrap();
+        // within each of the two regions, then each way between them
+        assert_eq!(netem.group_netems.len(), 4);
+        let to_eu = netem
+            .group_netems
+            .iter()
+            .find(|netem| netem.name == "us-east-to-eu-west-netem")
+            .unwrap();
+        assert_eq!(to_eu.source_nodes, validators[..2]);
+        assert_eq!(to_eu.target_nodes, validators[2..]);
+        assert_eq!(to_eu.delay_latency_ms, 40);
+        assert_eq!(to_eu.rate_in_mbps, 300);
+        let to_us = netem
+            .group_netems
+            .iter()
+            .find(|netem| netem.name == "eu-west-to-us-east-netem")
+            .unwrap();
+        assert_eq!(to_us.delay_latency_ms, 50);
+        assert_eq!(to_us.rate_in_mbps, 300);
+
+        // a region without validators gets no emulation
+        let netem = NetworkTopology::mainnet()
+            .network_emulation(&validators[..2])
+            .unwrap();
+        assert_eq!(netem.group_netems.len(), 4);
+    }
+}
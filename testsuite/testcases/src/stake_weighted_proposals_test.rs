@@ -0,0 +1,96 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use anyhow::{bail, Context};
+use aptos_forge::{
+    successful_proposals, NetworkContextSynchronizer, NetworkTest, Result, SwarmExt, Test,
+};
+use aptos_logger::info;
+use async_trait::async_trait;
+use std::{ops::DerefMut, time::Duration};
+
+// how many times the stake of each of the others the first validator gets
+const STAKE_MULTIPLIER: u64 = 10;
+// how long to count the proposals of the epoch the stake counts in for
+const OBSERVATION_DURATION: Duration = Duration::from_secs(180);
+
+/// Gives the first validator ten times the stake of each of the others, and checks that it
+/// proposes more often than them. Proposers are elected by the root of their voting power, so it
+/// is expected to propose about three times as often as each of the others.
+pub struct StakeWeightedProposalsTest;
+
+impl Test for StakeWeightedProposalsTest {
+    fn name(&self) -> &'static str {
+        "stake weighted proposals"
+    }
+}
+
+#[async_trait]
+impl NetworkTest for StakeWeightedProposalsTest {
+    async fn run<'a>(&self, ctxa: NetworkContextSynchronizer<'a>) -> Result<()> {
+        let mut ctx_locker = ctxa.ctx.lock().await;
+        let ctx = ctx_locker.deref_mut();
+
+        let heavy = {
+            let mut swarm = ctx.swarm.write().await;
+            let distribution = swarm.stake_distribution().await?;
+            let (heavy, _) = *distribution.first().context("The validator set is empty")?;
+            let others_stake = distribution
+                .iter()
+                .skip(1)
+                .map(|(_, stake)| *stake)
+                .max()
+                .context("The test needs at least two validators")?;
+            swarm
+                .set_validator_stake(heavy, others_stake * STAKE_MULTIPLIER, true)
+                .await?;
+
+            let distribution = swarm.stake_distribution().await?;
+            info!("Stake distribution: {:?}", distribution);
+            let heavy_stake = distribution
+                .iter()
+                .find(|(peer_id, _)| *peer_id == heavy)
+                .map_or(0, |(_, stake)| *stake);
+            if heavy_stake < others_stake * STAKE_MULTIPLIER {
+                bail!(
+                    "Validator {} votes with {} stake, instead of {}",
+                    heavy,
+                    heavy_stake,
+                    others_stake * STAKE_MULTIPLIER
+                );
+            }
+            heavy
+        };
+
+        // without holding the swarm, which the other tests of the context may use meanwhile
+        tokio::time::sleep(OBSERVATION_DURATION).await;
+        let proposals = successful_proposals(&ctx.swarm.read().await.aptos_public_info()).await?;
+        info!("Successful proposals: {:?}", proposals);
+        let heavy_proposals = proposals
+            .iter()
+            .find(|(peer_id, _)| *peer_id == heavy)
+            .map_or(0, |(_, proposals)| *proposals);
+        let others: Vec<_> = proposals
+            .iter()
+            .filter(|(peer_id, _)| *peer_id != heavy)
+            .map(|(_, proposals)| *proposals)
+            .collect();
+        let others_average = others.iter().sum::<u64>() as f64 / others.len().max(1) as f64;
+        // the root of the multiplier, with some slack for the leader reputation
+        if (heavy_proposals as f64) < 2.0 * others_average {
+            bail!(
+                "Validator {} with {}x the stake proposed {} blocks, the others {:.1} on average",
+                heavy,
+                STAKE_MULTIPLIER,
+                heavy_proposals,
+                others_average
+            );
+        }
+
+        ctx.report.report_text(format!(
+            "Validator {} with {}x the stake proposed {} blocks, the others {:.1} on average",
+            heavy, STAKE_MULTIPLIER, heavy_proposals, others_average
+        ));
+        Ok(())
+    }
+}
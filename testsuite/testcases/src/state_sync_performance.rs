@@ -5,8 +5,9 @@
 use crate::generate_traffic;
 use anyhow::bail;
 use aptos_forge::{
-    get_highest_synced_epoch, get_highest_synced_version, NetworkContext,
-    NetworkContextSynchronizer, NetworkTest, Result, SwarmExt, Test,
+    get_highest_synced_epoch, get_highest_synced_version, wait_for_all_nodes_to_catchup_to_version,
+    NetworkContext, NetworkContextSynchronizer, NetworkTest, NodeExt, Result, SwarmChaos, SwarmExt,
+    SwarmPeerBandwidth, Test,
 };
 use aptos_logger::info;
 use aptos_sdk::move_types::account_address::AccountAddress;
@@ -16,6 +17,7 @@ use tokio::{runtime::Runtime, time::Duration};
 
 const MAX_EPOCH_CHANGE_SECS: u64 = 300; // Max amount of time (in seconds) to wait for an epoch change
 const MAX_NODE_LAG_SECS: u64 = 30; // Max amount of lag (in seconds) that nodes should adhere to
+const MAX_FULLNODE_CATCHUP_SECS: u64 = 300; // Max amount of time (in seconds) for a wiped fullnode to catch up
 const NUM_STATE_VALUE_COUNTER_NAME: &str = "aptos_jellyfish_leaf_count"; // The metric to fetch for the number of state values
 
 /// A state sync performance test that measures fullnode sync performance.
@@ -149,31 +151,7 @@ impl NetworkTest for StateSyncValidatorPerformance {
     async fn run<'a>(&self, ctxa: NetworkContextSynchronizer<'a>) -> Result<()> {
         let mut ctx_locker = ctxa.ctx.lock().await;
         let ctx = ctx_locker.deref_mut();
-        // Verify we have at least 7 validators (i.e., 3f+1, where f is 2)
-        // so we can kill 2 validators but still make progress.
-        let all_validators = {
-            ctx.swarm
-                .read()
-                .await
-                .validators()
-                .map(|v| v.peer_id())
-                .collect::<Vec<_>>()
-        };
-        let num_validators = all_validators.len();
-        if num_validators < 7 {
-            return Err(anyhow::format_err!(
-                "State sync validator performance tests require at least 7 validators! Given: {:?} \
-                 This is to ensure the chain can still make progress when 2 validators are killed.",
-                num_validators
-            ));
-        }
-
-        // Log the test setup
-        info!(
-            "Running state sync test {:?} with {:?} validators.",
-            self.name(),
-            num_validators,
-        );
+        let all_validators = get_validators_and_check_setup(ctx, self.name()).await?;
 
         // Generate some traffic through the validators.
         emit_traffic_and_ensure_bounded_sync(ctx, &all_validators).await?;
@@ -189,6 +167,174 @@ impl NetworkTest for StateSyncValidatorPerformance {
     }
 }
 
+/// A state sync performance test that measures validator sync performance over constrained
+/// links. In the test, 2 validators are throttled, wiped, restarted and timed to synchronize.
+pub struct StateSyncValidatorConstrainedBandwidth;
+
+// as if the validators were behind 10 Mbit/s links
+const CONSTRAINED_BANDWIDTH_KBPS: u64 = 10_000;
+
+impl Test for StateSyncValidatorConstrainedBandwidth {
+    fn name(&self) -> &'static str {
+        "StateSyncValidatorConstrainedBandwidth"
+    }
+}
+
+#[async_trait]
+impl NetworkTest for StateSyncValidatorConstrainedBandwidth {
+    async fn run<'a>(&self, ctxa: NetworkContextSynchronizer<'a>) -> Result<()> {
+        let mut ctx_locker = ctxa.ctx.lock().await;
+        let ctx = ctx_locker.deref_mut();
+        let all_validators = get_validators_and_check_setup(ctx, self.name()).await?;
+
+        // Generate some traffic through the validators.
+        emit_traffic_and_ensure_bounded_sync(ctx, &all_validators).await?;
+
+        // Throttle two validators, then reset them so they start syncing from genesis
+        let validators_to_reset = &all_validators[0..2];
+        let chaos = SwarmChaos::PeerBandwidth(SwarmPeerBandwidth {
+            peers: validators_to_reset.to_vec(),
+            rate_kbps: CONSTRAINED_BANDWIDTH_KBPS,
+            limit_bytes: 20971520,
+            buffer_bytes: 10000,
+        });
+        ctx.swarm.write().await.inject_chaos(chaos.clone()).await?;
+        info!("Deleting data for two throttled validators!");
+        let result = match stop_and_reset_nodes(ctx, &[], validators_to_reset).await {
+            // Wait for all nodes to catch up to the highest synced version
+            // then calculate and display the throughput results.
+            Ok(()) => ensure_state_sync_transaction_throughput(ctx, self.name()),
+            Err(e) => Err(e),
+        };
+        ctx.swarm.write().await.remove_chaos(chaos).await?;
+        result
+    }
+}
+
+/// A state sync test of a validator with several fullnodes. In the test, one of the fullnodes
+/// of a validator is wiped and restarted, while the traffic goes through the others.
+pub struct StateSyncFullnodeFanOut;
+
+// of one validator, the one to wipe and the ones to send the traffic through
+const FAN_OUT_FULLNODES: usize = 3;
+
+impl Test for StateSyncFullnodeFanOut {
+    fn name(&self) -> &'static str {
+        "StateSyncFullnodeFanOut"
+    }
+}
+
+#[async_trait]
+impl NetworkTest for StateSyncFullnodeFanOut {
+    async fn run<'a>(&self, ctxa: NetworkContextSynchronizer<'a>) -> Result<()> {
+        let mut ctx_locker = ctxa.ctx.lock().await;
+        let ctx = ctx_locker.deref_mut();
+        let fullnodes = {
+            let swarm = ctx.swarm.read().await;
+            swarm
+                .validators()
+                .map(|validator| {
+                    swarm
+                        .fullnodes_of(validator.peer_id())
+                        .iter()
+                        .map(|fullnode| fullnode.peer_id())
+                        .collect::<Vec<_>>()
+                })
+                .find(|fullnodes| fullnodes.len() >= FAN_OUT_FULLNODES)
+        };
+        let Some(fullnodes) = fullnodes else {
+            bail!(
+                "{} requires a validator with at least {} fullnodes",
+                self.name(),
+                FAN_OUT_FULLNODES
+            );
+        };
+
+        // Wipe one of the fullnodes, it syncs from genesis again
+        let (fullnode_to_reset, other_fullnodes) = fullnodes.split_first().unwrap();
+        info!("Deleting the data of fullnode {}", fullnode_to_reset);
+        stop_and_reset_nodes(ctx, &[*fullnode_to_reset], &[]).await?;
+
+        // The traffic through the other fullnodes of the validator keeps being committed
+        let emit_txn_duration = ctx.global_duration.checked_div(2).unwrap();
+        let txn_stat = generate_traffic(ctx, other_fullnodes, emit_txn_duration).await?;
+        if txn_stat.committed == 0 {
+            bail!(
+                "No transaction was committed through the other fullnodes while {} was syncing",
+                fullnode_to_reset
+            );
+        }
+
+        // Every fullnode of the validator, the wiped one too, catches up with the validators
+        let swarm = ctx.swarm.read().await;
+        let target_version =
+            get_highest_synced_version(&swarm.get_validator_clients_with_names()).await?;
+        let fullnode_clients: Vec<_> = fullnodes
+            .iter()
+            .filter_map(|peer_id| swarm.full_node(*peer_id))
+            .map(|fullnode| (fullnode.name().to_string(), fullnode.rest_client()))
+            .collect();
+        if fullnode_clients.len() != fullnodes.len() {
+            bail!(
+                "Only {} of the {} fullnodes of the validator are left in the swarm",
+                fullnode_clients.len(),
+                fullnodes.len()
+            );
+        }
+        info!(
+            "Waiting for the {} fullnodes to catch up to version {}.",
+            fullnode_clients.len(),
+            target_version
+        );
+        wait_for_all_nodes_to_catchup_to_version(
+            &fullnode_clients,
+            target_version,
+            Duration::from_secs(MAX_FULLNODE_CATCHUP_SECS),
+        )
+        .await?;
+
+        info!("Waiting for the validators and fullnodes to be synchronized.");
+        swarm
+            .wait_for_all_nodes_to_catchup(Duration::from_secs(MAX_NODE_LAG_SECS))
+            .await?;
+        Ok(())
+    }
+}
+
+/// Verifies the setup for the given validator test and returns the
+/// set of validators.
+async fn get_validators_and_check_setup<'a>(
+    ctx: &mut NetworkContext<'a>,
+    test_name: &'static str,
+) -> Result<Vec<AccountAddress>> {
+    // Verify we have at least 7 validators (i.e., 3f+1, where f is 2)
+    // so we can kill 2 validators but still make progress.
+    let all_validators = {
+        ctx.swarm
+            .read()
+            .await
+            .validators()
+            .map(|v| v.peer_id())
+            .collect::<Vec<_>>()
+    };
+    let num_validators = all_validators.len();
+    if num_validators < 7 {
+        return Err(anyhow::format_err!(
+            "State sync validator performance tests require at least 7 validators! Given: {:?} \
+             This is to ensure the chain can still make progress when 2 validators are killed.",
+            num_validators
+        ));
+    }
+
+    // Log the test setup
+    info!(
+        "Running state sync test {:?} with {:?} validators.",
+        test_name, num_validators,
+    );
+
+    Ok(all_validators)
+}
+
 /// Verifies the setup for the given fullnode test and returns the
 /// set of fullnodes.
 async fn get_fullnodes_and_check_setup<'a>(
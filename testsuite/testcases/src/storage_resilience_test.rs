@@ -0,0 +1,131 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{generate_traffic, pick_faulty_validator};
+use anyhow::{bail, Context};
+use aptos_forge::{
+    NetworkContextSynchronizer, NetworkTest, NodeExt, Result, Swarm, SwarmExt, Test,
+};
+use aptos_logger::info;
+use aptos_sdk::types::PeerId;
+use async_trait::async_trait;
+use std::{
+    ops::DerefMut,
+    time::{Duration, Instant},
+};
+
+const FILL_PERCENT: u8 = 98;
+const TRAFFIC_DURATION: Duration = Duration::from_secs(240);
+// the load runs for a while before the disk fills up
+const FILL_AFTER: Duration = Duration::from_secs(30);
+const FULL_DURATION: Duration = Duration::from_secs(120);
+const RECOVERY_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Fills the disk of one validator to 98% under load, and frees it again. The network must keep
+/// committing meanwhile, and the validator must come back healthy and catch up, with the same
+/// ledger as the others.
+pub struct StorageResilienceTest;
+
+impl Test for StorageResilienceTest {
+    fn name(&self) -> &'static str {
+        "storage::disk-fill-test"
+    }
+}
+
+#[async_trait]
+impl NetworkTest for StorageResilienceTest {
+    async fn run<'a>(&self, ctxa: NetworkContextSynchronizer<'a>) -> Result<()> {
+        let mut ctx_locker = ctxa.ctx.lock().await;
+        let ctx = ctx_locker.deref_mut();
+
+        let (filled, others) = pick_faulty_validator(&ctx.swarm, self.name()).await?;
+
+        let swarm = ctx.swarm.clone();
+        let fill = async move {
+            tokio::time::sleep(FILL_AFTER).await;
+            let swarm = swarm.read().await;
+            let validator = swarm
+                .validator(filled)
+                .context("Validator to fill is missing from the swarm")?;
+            validator.fill_disk(FILL_PERCENT).await?;
+            let version_when_full = validator
+                .rest_client()
+                .get_ledger_information()
+                .await
+                .map(|state| state.into_inner().version)
+                .ok();
+            tokio::time::sleep(FULL_DURATION).await;
+            // the node may have crashed or stopped committing, either of which is fine
+            let version_after = validator
+                .rest_client()
+                .get_ledger_information()
+                .await
+                .map(|state| state.into_inner().version)
+                .ok();
+            validator.free_disk().await?;
+            Ok::<_, anyhow::Error>((
+                validator.name().to_string(),
+                version_when_full,
+                version_after,
+            ))
+        };
+        let (stats, filled_run) =
+            tokio::join!(generate_traffic(ctx, &others, TRAFFIC_DURATION), fill);
+        let (name, version_when_full, version_after) = filled_run?;
+        let stats = stats?;
+        ctx.report
+            .report_txn_stats(format!("{}::filled", self.name()), &stats);
+        if stats.committed == 0 {
+            bail!(
+                "No transactions committed with the disk of {} {}% full",
+                name,
+                FILL_PERCENT
+            );
+        }
+        let degradation = match (version_when_full, version_after) {
+            (Some(before), Some(after)) if after > before => {
+                format!("kept committing, from version {} to {}", before, after)
+            },
+            (Some(before), Some(_)) => format!("stopped committing at version {}", before),
+            _ => "stopped serving requests".to_string(),
+        };
+        info!(
+            "With its disk {}% full, {} {}",
+            FILL_PERCENT, name, degradation
+        );
+
+        let swarm = ctx.swarm.read().await;
+        let validator = swarm
+            .validator(filled)
+            .context("Filled validator is missing from the swarm")?;
+        validator
+            .wait_until_healthy(Instant::now() + RECOVERY_TIMEOUT)
+            .await
+            .with_context(|| format!("{} did not recover once its disk was freed", name))?;
+        swarm
+            .wait_for_all_nodes_to_catchup(RECOVERY_TIMEOUT)
+            .await
+            .with_context(|| format!("{} did not catch up once its disk was freed", name))?;
+        // a node that corrupted its DB would diverge from the others
+        let validators: Vec<PeerId> = [filled].into_iter().chain(others).collect();
+        let clients = swarm.get_clients_for_peers(&validators, Duration::from_secs(10));
+        let version = validator
+            .rest_client()
+            .get_ledger_information()
+            .await?
+            .into_inner()
+            .version;
+        if !<dyn Swarm>::are_root_hashes_equal_at_version(&clients, version).await? {
+            bail!(
+                "{} has a different root hash than the other validators at version {}",
+                name,
+                version
+            );
+        }
+        ctx.report.report_text(format!(
+            "With its disk {}% full, {} {}, and it recovered once the disk was freed",
+            FILL_PERCENT, name, degradation
+        ));
+        Ok(())
+    }
+}
@@ -4,7 +4,7 @@
 use crate::{LoadDestination, NetworkLoadTest};
 use aptos_forge::{
     GroupNetworkBandwidth, GroupNetworkDelay, NetworkContext, NetworkContextSynchronizer,
-    NetworkTest, SwarmChaos, SwarmNetworkBandwidth, SwarmNetworkDelay, Test,
+    NetworkDelayDirection, NetworkTest, SwarmChaos, SwarmNetworkBandwidth, SwarmNetworkDelay, Test,
 };
 use aptos_logger::info;
 use aptos_types::account_address::AccountAddress;
@@ -17,6 +17,10 @@ impl Test for ThreeRegionSameCloudSimulationTest {
     fn name(&self) -> &'static str {
         "network::three-region-simulation"
     }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["network", "chaos"]
+    }
 }
 
 /// Create a SwarmNetworkDelay with the following topology:
@@ -43,6 +47,7 @@ fn create_three_region_swarm_network_delay(
             latency_ms: 300,
             jitter_ms: 50,
             correlation_percentage: 50,
+            direction: NetworkDelayDirection::Both,
         },
         GroupNetworkDelay {
             name: "us-west-to-eu-north".to_string(),
@@ -51,6 +56,7 @@ fn create_three_region_swarm_network_delay(
             latency_ms: 150,
             jitter_ms: 50,
             correlation_percentage: 50,
+            direction: NetworkDelayDirection::Both,
         },
         GroupNetworkDelay {
             name: "eu-north-to-af-south".to_string(),
@@ -59,6 +65,7 @@ fn create_three_region_swarm_network_delay(
             latency_ms: 200,
             jitter_ms: 50,
             correlation_percentage: 50,
+            direction: NetworkDelayDirection::Both,
         },
     ];
 
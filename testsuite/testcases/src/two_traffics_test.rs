@@ -23,6 +23,10 @@ impl Test for TwoTrafficsTest {
     fn name(&self) -> &'static str {
         "two traffics test"
     }
+
+    fn tags(&self) -> &'static [&'static str] {
+        &["perf"]
+    }
 }
 
 #[async_trait]
@@ -0,0 +1,95 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use anyhow::{bail, Context};
+use aptos_forge::{
+    NetworkContextSynchronizer, NetworkTest, NewValidatorOptions, NodeExt, Result, Test,
+};
+use aptos_logger::info;
+use async_trait::async_trait;
+use std::{
+    ops::DerefMut,
+    time::{Duration, Instant},
+};
+
+// how long the new validator gets to vote, once it joined
+const PARTICIPATION_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Adds a validator to the running swarm, and checks that it votes in consensus. The genesis must
+/// allow new validators, with a voting power increase limit that lets the new one in.
+pub struct ValidatorJoinTest;
+
+impl Test for ValidatorJoinTest {
+    fn name(&self) -> &'static str {
+        "validator join"
+    }
+}
+
+#[async_trait]
+impl NetworkTest for ValidatorJoinTest {
+    async fn run<'a>(&self, ctxa: NetworkContextSynchronizer<'a>) -> Result<()> {
+        let mut ctx_locker = ctxa.ctx.lock().await;
+        let ctx = ctx_locker.deref_mut();
+
+        let mut swarm = ctx.swarm.write().await;
+        let version = swarm
+            .validators()
+            .next()
+            .context("Swarm has no validators")?
+            .version();
+        let validator_count = swarm.validators().count();
+        let peer_id = swarm
+            .add_validator(&version, NewValidatorOptions::default())
+            .await?;
+        let validator = swarm
+            .validator(peer_id)
+            .context("New validator is missing from the swarm")?;
+        if swarm.validators().count() != validator_count + 1 {
+            bail!("Swarm does not have {} validators", validator_count + 1);
+        }
+
+        // it has to vote in the epoch it joined with
+        let joined_epoch = validator
+            .rest_client()
+            .get_ledger_information()
+            .await?
+            .into_inner()
+            .epoch;
+        let deadline = Instant::now() + PARTICIPATION_TIMEOUT;
+        loop {
+            let epoch = validator
+                .get_metric_i64("aptos_consensus_last_voted_epoch")
+                .await?
+                .unwrap_or_default();
+            let round = validator
+                .get_metric_i64("aptos_consensus_last_voted_round")
+                .await?
+                .unwrap_or_default();
+            if epoch as u64 >= joined_epoch && round > 0 {
+                info!(
+                    "Validator {} voted in round {} of epoch {}",
+                    validator.name(),
+                    round,
+                    epoch
+                );
+                break;
+            }
+            if Instant::now() > deadline {
+                bail!(
+                    "Validator {} did not vote in epoch {} within {:?}",
+                    validator.name(),
+                    joined_epoch,
+                    PARTICIPATION_TIMEOUT
+                );
+            }
+            tokio::time::sleep(Duration::from_secs(10)).await;
+        }
+
+        ctx.report.report_text(format!(
+            "Validator {} joined {} validators and votes in consensus",
+            validator.name(),
+            validator_count
+        ));
+        Ok(())
+    }
+}